@@ -1,4 +1,5 @@
 use std::collections::BTreeMap;
+use std::fmt;
 use serde::{Deserialize, Serialize};
 
 /// Represents the type of aggregation to perform on a column
@@ -44,17 +45,17 @@ pub enum AggregationResult {
     Error(String),
 }
 
-impl AggregationResult {
-    /// Convert the aggregation result to a string representation
-    pub fn to_string(&self) -> String {
+impl fmt::Display for AggregationResult {
+    /// Format the aggregation result as a string
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            AggregationResult::Count(count) => format!("{}", count),
-            AggregationResult::Sum(sum) => format!("{}", sum),
-            AggregationResult::SumFloat(sum) => format!("{}", sum),
-            AggregationResult::Average(avg) => format!("{}", avg),
-            AggregationResult::Min(min) => format!("{:?}", min),
-            AggregationResult::Max(max) => format!("{:?}", max),
-            AggregationResult::Error(err) => format!("Error: {}", err),
+            AggregationResult::Count(count) => write!(f, "{}", count),
+            AggregationResult::Sum(sum) => write!(f, "{}", sum),
+            AggregationResult::SumFloat(sum) => write!(f, "{}", sum),
+            AggregationResult::Average(avg) => write!(f, "{}", avg),
+            AggregationResult::Min(min) => write!(f, "{:?}", min),
+            AggregationResult::Max(max) => write!(f, "{:?}", max),
+            AggregationResult::Error(err) => write!(f, "Error: {}", err),
         }
     }
 }
@@ -133,9 +134,9 @@ impl AggregationSet {
                             if column_values.is_empty() {
                                 AggregationResult::Error("No values to average".to_string())
                             } else {
-                                // Use fold to accumulate sum and count while collecting debug values
-                                let result: Result<(f64, f64, Vec<(&u64, f64)>), &'static str> = column_values.iter()
-                                    .try_fold((0.0, 0.0, Vec::new()), |(sum, count, mut debug_values), (ts, value)| {
+                                // Use fold to accumulate sum and count
+                                let result: Result<(f64, f64), &'static str> = column_values.iter()
+                                    .try_fold((0.0, 0.0), |(sum, count), (_, value)| {
                                         // Try to parse the value as UTF-8
                                         let value_str = std::str::from_utf8(value)
                                             .map_err(|_| "Invalid UTF-8 in value")?;
@@ -144,16 +145,13 @@ impl AggregationSet {
                                         let num = value_str.parse::<f64>()
                                             .map_err(|_| "Non-numeric value found")?;
 
-                                        // Add to debug values
-                                        debug_values.push((ts, num));
-
                                         // Return updated accumulator
-                                        Ok((sum + num, count + 1.0, debug_values))
+                                        Ok((sum + num, count + 1.0))
                                     });
 
                                 // Handle the result
                                 match result {
-                                    Ok((sum, count, _)) => {
+                                    Ok((sum, count)) => {
                                         AggregationResult::Average(sum / count)
                                     },
                                     Err(err) => {
@@ -1,22 +1,184 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     fs,
-    io::Result as IoResult,
+    io::{Error, ErrorKind, Result as IoResult},
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, OnceLock, RwLock,
+    },
+    future::Future,
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
+use futures::{
+    channel::mpsc::{unbounded, UnboundedSender},
+    Stream,
+};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, instrument, warn};
 
-use crate::memstore::{MemStore, WalEntry};
-use crate::storage::{SSTable, SSTableReader};
+use crate::clock::HybridClock;
+use crate::mutation::{LoggedMutation, MutationLog, RowMutation};
+use crate::memory::{self, flush_to_limit, MemoryBudget, MemoryGate, MemoryUsage};
+use crate::memstore::{entry_bytes, truncated, wal_segments, MemStore};
+use crate::recovery::{ignored_sstables, CfRecovery, RecoveryReport};
+use crate::backend::LocalFs;
+use crate::writer::{copy_error, WriteQueue, WriteRequest};
+use crate::expiry::ExpirySchedule;
+use crate::throttle::{CompactionSlot, CompactionThrottle, RateLimiter};
+use crate::changelog::{ChangelogWriter, CHANGELOG_FILE};
+use crate::bloom::BloomFilterOptions;
+use crate::storage::{entry_size, Durability, SSTable, SSTableReader, SSTableWriter};
+use crate::cache::{BlockCache, BlockCacheStats, CachedEntries, ReaderCache, DEFAULT_BLOCK_CACHE_BYTES};
+use crate::manifest::{FileRefs, Manifest, PinnedFiles};
+use crate::verify::{check_sstable, SSTableCheck, VerifyReport, QUARANTINE_DIR};
+use crate::merge::MergeIter;
+use crate::region::{RegionConfig, RegionedColumnFamily, REGIONS_MANIFEST};
+use crate::scan::{ResumeToken, Scanner};
+use crate::result::{Cell, Row};
+use crate::schema::{legacy_options, ColumnFamilyDescriptor, TableSchema, LEGACY_OPTIONS_FILE, SCHEMA_FILE};
+use crate::wal_archive::{WalArchive, WalArchiveState};
+use crate::backup::{self, BackupCatalog, BackupManifest, BackupWriter, CfBackup};
+use crate::validation::{SizeLimits, ValidationError, Validator};
+use crate::observer::Observer;
+use crate::query::{Query, QueryResult};
+use crate::types::TypedValue;
 use crate::filter::{Filter, FilterSet};
 use crate::aggregation::{AggregationSet, AggregationResult};
 
 pub type RowKey = Vec<u8>;
 pub type Column = Vec<u8>;
 pub type Timestamp = u64;
+pub type Seq = u64;
+
+/// Marker file whose presence records that a column family is frozen.
+const FROZEN_MARKER: &str = "FROZEN";
+/// Directory (inside each column family) holding named snapshots.
+const SNAPSHOT_DIR: &str = "snapshots";
+/// Manifest file describing a snapshot.
+const SNAPSHOT_MANIFEST: &str = "manifest.json";
+/// File (inside each column family) listing unresolved range tombstones.
+const RANGE_TOMBSTONES_FILE: &str = "range_tombstones.json";
+
+/// Describes the SSTables captured by a column family snapshot.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SnapshotManifest {
+    /// Snapshot name
+    pub name: String,
+    /// Column family the snapshot was taken from
+    pub column_family: String,
+    /// Creation time in milliseconds since the epoch
+    pub created_at: Timestamp,
+    /// SSTable file names, relative to the snapshot directory
+    pub sst_files: Vec<String>,
+    /// Range tombstones that were not yet resolved by compaction
+    #[serde(default)]
+    pub range_tombstones: Vec<RangeTombstone>,
+}
+
+/// Hard-link src to dst, falling back to a copy (e.g. across filesystems).
+fn link_or_copy(src: &Path, dst: &Path) -> IoResult<()> {
+    if fs::hard_link(src, dst).is_err() {
+        fs::copy(src, dst)?;
+    }
+    Ok(())
+}
+
+/// Check that table_dir is missing or empty, removing it if empty, and return a fresh
+/// staging directory beside it, named with suffix, to build a new table in before it is
+/// renamed into place.
+fn new_table_staging(table_dir: &Path, suffix: &str) -> IoResult<PathBuf> {
+    let dir_name = table_dir.file_name().ok_or_else(|| {
+        Error::new(ErrorKind::InvalidInput, format!("Invalid table directory {}", table_dir.display()))
+    })?;
+    if table_dir.exists() {
+        if fs::read_dir(table_dir)?.next().is_some() {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("Table directory {} is not empty", table_dir.display()),
+            ));
+        }
+        fs::remove_dir(table_dir)?;
+    }
+
+    let mut staging_name = dir_name.to_owned();
+    staging_name.push(".");
+    staging_name.push(suffix);
+    let staging = table_dir.with_file_name(staging_name);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    Ok(staging)
+}
+
+/// Maximum number of entries per SSTable written by `ColumnFamily::bulk_load`.
+const BULK_LOAD_ENTRIES_PER_SST: usize = 1_000_000;
+
+/// Distinguishes staging files of concurrent bulk loads and ingests.
+static STAGING_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Stats by column family directory, shared by every handle of a column family in the
+/// process, so that handles reopened per request (as the REST pool does) report the
+/// compactions, flushes and writes run through the others.
+type SharedStats<T> = Mutex<BTreeMap<PathBuf, Arc<Mutex<T>>>>;
+
+static COMPACTION_STATS: SharedStats<CompactionStats> = Mutex::new(BTreeMap::new());
+static WRITE_STATS: SharedStats<WriteStats> = Mutex::new(BTreeMap::new());
+
+/// The stats in stats of the column family in cf_path.
+fn shared_stats<T: Default>(stats: &SharedStats<T>, cf_path: &Path) -> Arc<Mutex<T>> {
+    let key = fs::canonicalize(cf_path).unwrap_or_else(|_| cf_path.to_path_buf());
+    stats.lock().unwrap().entry(key).or_default().clone()
+}
+
+/// Write entries replayed from the WAL of the column family in cf_path to a new SSTable
+/// recorded in its manifest, as `MemStore::open_bounded` flushes them. Entries a
+/// truncation discarded, numbered up to truncated_seq, are left out; returns how many.
+/// Nothing else runs on the column family while it is opened, so the SSTable is written
+/// in place.
+fn flush_replayed(
+    cf_path: &Path,
+    manifest: &mut Manifest,
+    entries: Vec<Entry>,
+    truncated_seq: Seq,
+    bloom: BloomFilterOptions,
+) -> IoResult<u64> {
+    let replayed = entries.len();
+    let entries: Vec<Entry> = entries.into_iter()
+        .filter(|entry| !truncated(entry.key.seq, truncated_seq))
+        .collect();
+    let skipped = (replayed - entries.len()) as u64;
+    if entries.is_empty() {
+        return Ok(skipped);
+    }
+    let sst_path = manifest.allocate_file();
+    SSTable::create_with_bloom_filter(&sst_path, &entries, Durability::Sync, bloom)?;
+    Durability::Sync.sync_dir(cf_path)?;
+    manifest.advance_seq(entries.iter().map(|entry| entry.key.seq).max().unwrap_or(0));
+    manifest.advance_timestamp(entries.iter().map(|entry| entry.key.timestamp).max().unwrap_or(0));
+    manifest.record(std::slice::from_ref(&sst_path), &[])?;
+    info!(cf = %cf_path.display(), sst = %sst_path.display(), entries = entries.len(), "flushed entries replayed from the WAL");
+    Ok(skipped)
+}
+
+fn validate_snapshot_name(name: &str) -> IoResult<()> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid snapshot name: {:?}", name),
+        ));
+    }
+    Ok(())
+}
+
+/// Versions of every column in a row: column -> [(timestamp, value)], newest first.
+pub type RowVersions = BTreeMap<Column, Vec<(Timestamp, Vec<u8>)>>;
+/// Versioned rows of a range scan, keyed by row key.
+pub type RangeVersions = BTreeMap<RowKey, RowVersions>;
+/// Versions of a row across the column families of a table, keyed by column family name.
+pub type FamilyVersions = BTreeMap<String, RowVersions>;
 
 /// A Get operation that can be used to retrieve data for a specific row.
 /// Similar to the HBase/Java Get API.
@@ -27,6 +189,10 @@ pub struct Get {
     max_versions: Option<usize>,
     /// Optional time range for filtering versions (start_time, end_time)
     time_range: Option<(Timestamp, Timestamp)>,
+    /// Columns to read; every column when empty
+    columns: BTreeSet<Column>,
+    /// Column families to read; every column family when empty
+    families: BTreeSet<String>,
 }
 
 impl Get {
@@ -36,9 +202,25 @@ impl Get {
             row,
             max_versions: None,
             time_range: None,
+            columns: BTreeSet::new(),
+            families: BTreeSet::new(),
         }
     }
 
+    /// Read column, in addition to the columns already added. Without any, every column
+    /// of the row is read.
+    pub fn add_column(&mut self, column: Column) -> &mut Self {
+        self.columns.insert(column);
+        self
+    }
+
+    /// Read the column family cf_name, in addition to the families already added.
+    /// Without any, every column family is read.
+    pub fn add_family(&mut self, cf_name: &str) -> &mut Self {
+        self.families.insert(cf_name.to_string());
+        self
+    }
+
     /// Set the maximum number of versions to retrieve.
     pub fn set_max_versions(&mut self, max_versions: usize) -> &mut Self {
         self.max_versions = Some(max_versions);
@@ -65,6 +247,179 @@ impl Get {
     pub fn time_range(&self) -> Option<(Timestamp, Timestamp)> {
         self.time_range
     }
+
+    /// Get the columns to read; empty for every column.
+    pub fn columns(&self) -> &BTreeSet<Column> {
+        &self.columns
+    }
+
+    /// Get the column families to read; empty for every column family.
+    pub fn families(&self) -> &BTreeSet<String> {
+        &self.families
+    }
+}
+
+/// Rows a `Scanner` returns per `next_batch` unless the `Scan` sets its caching.
+pub const DEFAULT_SCAN_CACHING: usize = 100;
+
+/// A Scan operation that can be used to retrieve the rows in [start_row, stop_row]
+/// (inclusive). Similar to the HBase/Java Scan API.
+#[derive(Debug, Clone)]
+pub struct Scan {
+    /// First row key to return
+    start_row: RowKey,
+    /// Last row key to return
+    stop_row: RowKey,
+    /// Maximum number of versions to retrieve per column
+    max_versions: Option<usize>,
+    /// Optional time range for filtering versions (start_time, end_time)
+    time_range: Option<(Timestamp, Timestamp)>,
+    /// Columns to read; every column when empty
+    columns: BTreeSet<Column>,
+    /// Column families to read; every column family when empty
+    families: BTreeSet<String>,
+    /// Filter set each row is passed through
+    filter_set: Option<FilterSet>,
+    /// Rows returned per `Scanner::next_batch`
+    caching: Option<usize>,
+    /// Maximum number of columns per returned row; wider rows are split
+    batch: Option<usize>,
+    /// Whether rows are returned from stop_row down to start_row
+    reversed: bool,
+    /// Whether delete markers and every stored version are returned
+    raw: bool,
+}
+
+impl Scan {
+    /// Create a new Scan operation for the rows in [start_row, stop_row].
+    pub fn new(start_row: RowKey, stop_row: RowKey) -> Self {
+        Scan {
+            start_row,
+            stop_row,
+            max_versions: None,
+            time_range: None,
+            columns: BTreeSet::new(),
+            families: BTreeSet::new(),
+            filter_set: None,
+            caching: None,
+            batch: None,
+            reversed: false,
+            raw: false,
+        }
+    }
+
+    /// Set the maximum number of versions to retrieve.
+    pub fn set_max_versions(&mut self, max_versions: usize) -> &mut Self {
+        self.max_versions = Some(max_versions);
+        self
+    }
+
+    /// Set the time range for filtering versions.
+    pub fn set_time_range(&mut self, start_time: Timestamp, end_time: Timestamp) -> &mut Self {
+        self.time_range = Some((start_time, end_time));
+        self
+    }
+
+    /// Pass every row through filter_set, as `scan_with_filter` does.
+    pub fn set_filter(&mut self, filter_set: FilterSet) -> &mut Self {
+        self.filter_set = Some(filter_set);
+        self
+    }
+
+    /// Set the number of rows `Scanner::next_batch` returns at a time.
+    pub fn set_caching(&mut self, rows: usize) -> &mut Self {
+        self.caching = Some(rows);
+        self
+    }
+
+    /// Return at most columns columns per row from a `Scanner`, splitting wider rows
+    /// into consecutive parts.
+    pub fn set_batch(&mut self, columns: usize) -> &mut Self {
+        self.batch = Some(columns);
+        self
+    }
+
+    /// Return the rows from stop_row down to start_row.
+    pub fn set_reversed(&mut self, reversed: bool) -> &mut Self {
+        self.reversed = reversed;
+        self
+    }
+
+    /// Return the delete markers along with the puts, and the versions they hide, as
+    /// stored and not yet compacted away. Only `ColumnFamily::scan_rows` runs raw scans.
+    pub fn set_raw(&mut self, raw: bool) -> &mut Self {
+        self.raw = raw;
+        self
+    }
+
+    /// Read column, in addition to the columns already added. Without any, every column
+    /// of each row is read.
+    pub fn add_column(&mut self, column: Column) -> &mut Self {
+        self.columns.insert(column);
+        self
+    }
+
+    /// Read the column family cf_name, in addition to the families already added.
+    /// Without any, every column family is read.
+    pub fn add_family(&mut self, cf_name: &str) -> &mut Self {
+        self.families.insert(cf_name.to_string());
+        self
+    }
+
+    /// Get the first row key of the scan.
+    pub fn start_row(&self) -> &RowKey {
+        &self.start_row
+    }
+
+    /// Get the last row key of the scan.
+    pub fn stop_row(&self) -> &RowKey {
+        &self.stop_row
+    }
+
+    /// Get the maximum number of versions to retrieve.
+    pub fn max_versions(&self) -> Option<usize> {
+        self.max_versions
+    }
+
+    /// Get the time range for filtering versions.
+    pub fn time_range(&self) -> Option<(Timestamp, Timestamp)> {
+        self.time_range
+    }
+
+    /// Get the columns to read; empty for every column.
+    pub fn columns(&self) -> &BTreeSet<Column> {
+        &self.columns
+    }
+
+    /// Get the column families to read; empty for every column family.
+    pub fn families(&self) -> &BTreeSet<String> {
+        &self.families
+    }
+
+    /// Get the filter set rows are passed through.
+    pub fn filter(&self) -> Option<&FilterSet> {
+        self.filter_set.as_ref()
+    }
+
+    /// Get the number of rows returned per `Scanner::next_batch`.
+    pub fn caching(&self) -> usize {
+        self.caching.unwrap_or(DEFAULT_SCAN_CACHING)
+    }
+
+    /// Get the maximum number of columns per returned row.
+    pub fn batch(&self) -> Option<usize> {
+        self.batch
+    }
+
+    /// Whether the rows are returned from stop_row down to start_row.
+    pub fn is_reversed(&self) -> bool {
+        self.reversed
+    }
+
+    /// Whether delete markers are returned.
+    pub fn is_raw(&self) -> bool {
+        self.raw
+    }
 }
 
 /// A Put operation that can be used to add multiple columns to a single row.
@@ -91,15 +446,36 @@ impl Put {
         self
     }
 
+    /// Add a column value to this Put operation in its `types` encoding.
+    pub fn add_typed<T: TypedValue>(&mut self, column: Column, value: &T) -> &mut Self {
+        self.add_column(column, value.encode())
+    }
+
     /// Get the row key for this Put operation.
     pub fn row(&self) -> &RowKey {
         &self.row
     }
 
+    /// This put, writing the same columns to row instead.
+    pub(crate) fn with_row(self, row: RowKey) -> Self {
+        Put { row, columns: self.columns }
+    }
+
     /// Get the columns and values for this Put operation.
     pub fn columns(&self) -> &HashMap<Column, Vec<u8>> {
         &self.columns
     }
+
+    /// The columns of this put as entries written at timestamp.
+    pub(crate) fn into_entries(self, timestamp: Timestamp) -> Vec<Entry> {
+        let row = self.row;
+        self.columns.into_iter()
+            .map(|(column, value)| Entry {
+                key: EntryKey { row: row.clone(), column, timestamp, seq: 0 },
+                value: CellValue::Put(value),
+            })
+            .collect()
+    }
 }
 
 /// A cell can either be a Put (with actual bytes) or a Delete marker with optional TTL.
@@ -110,10 +486,274 @@ pub enum CellValue {
     /// Delete marker with optional TTL (time-to-live in milliseconds)
     /// After TTL expires, the tombstone can be removed during compaction
     Delete(Option<u64>),
+    /// Delete marker for a whole row, written under `FAMILY_DELETE_COLUMN` by
+    /// `ColumnFamily::delete_row`: it hides every cell of the row written at or before
+    /// its timestamp, in any column
+    DeleteFamily,
+}
+
+/// Column under which the `CellValue::DeleteFamily` markers of a row are stored; it sorts
+/// before every other column of the row.
+pub const FAMILY_DELETE_COLUMN: &[u8] = b"";
+
+/// Deletes every cell in rows [start_row, end_row), or only those of one column,
+/// written at or before timestamp.
+///
+/// Range tombstones are kept beside the SSTables and applied by every read, until a
+/// major compaction drops the cells they cover and then the tombstones themselves.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RangeTombstone {
+    /// First deleted row (inclusive)
+    pub start_row: RowKey,
+    /// End of the deleted range (exclusive)
+    pub end_row: RowKey,
+    /// Cells with a timestamp at or before this are deleted
+    pub timestamp: Timestamp,
+    /// The only column deleted, if not every column is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<Column>,
+}
+
+impl RangeTombstone {
+    /// Whether a cell version of (row, column) written at timestamp is deleted by this
+    /// tombstone.
+    pub fn covers(&self, row: &[u8], column: &[u8], timestamp: Timestamp) -> bool {
+        timestamp <= self.timestamp
+            && row >= self.start_row.as_slice()
+            && row < self.end_row.as_slice()
+            && self.column.as_ref().is_none_or(|deleted| deleted.as_slice() == column)
+    }
+}
+
+/// Threads for the partitions and output files of one compaction.
+fn compaction_pool(threads: usize) -> IoResult<rayon::ThreadPool> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("redbase-compact-{}", i))
+        .build()
+        .map_err(Error::other)
+}
+
+/// Split entries, sorted by key, into at most parts runs of about equal length that
+/// never divide a row.
+fn split_rows(entries: Vec<Entry>, parts: usize) -> Vec<Vec<Entry>> {
+    let len = entries.len();
+    let mut bounds: Vec<usize> = Vec::new();
+    for part in 1..parts.max(1) {
+        let mut at = (part * len / parts).max(bounds.last().copied().unwrap_or(0));
+        while at > 0 && at < len && entries[at].key.row == entries[at - 1].key.row {
+            at += 1;
+        }
+        if at > bounds.last().copied().unwrap_or(0) && at < len {
+            bounds.push(at);
+        }
+    }
+    split_at(entries, &bounds)
+}
+
+/// Split entries, sorted by key, into runs that end at the first row boundary after
+/// target_bytes of SSTable space, or into one run if target_bytes is `None`.
+fn split_by_size(entries: Vec<Entry>, target_bytes: Option<u64>) -> Vec<Vec<Entry>> {
+    let Some(target_bytes) = target_bytes else {
+        return vec![entries];
+    };
+    let mut bounds = Vec::new();
+    let mut size = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        if size >= target_bytes.max(1) && entries[i - 1].key.row != entry.key.row {
+            bounds.push(i);
+            size = 0;
+        }
+        size += entry_size(entry);
+    }
+    split_at(entries, &bounds)
+}
+
+/// Split entries before each of the increasing indexes in bounds.
+fn split_at(mut entries: Vec<Entry>, bounds: &[usize]) -> Vec<Vec<Entry>> {
+    let mut runs = Vec::with_capacity(bounds.len() + 1);
+    for &at in bounds.iter().rev() {
+        runs.push(entries.split_off(at));
+    }
+    runs.push(entries);
+    runs.reverse();
+    runs
+}
+
+/// Drop from entries, sorted by key, what a compaction per options drops: cells under
+/// range_tombstones, and versions and tombstones pruned per cell. Rows outside range,
+/// if set, are only cleared of range-deleted cells. Returns the kept entries in key order.
+fn prune_entries(
+    mut merged: Vec<Entry>,
+    range_tombstones: &[RangeTombstone],
+    options: &CompactionOptions,
+    range: Option<(&[u8], &[u8])>,
+    now: Timestamp,
+) -> (Vec<Entry>, DroppedEntries) {
+    let entries_in = merged.len();
+    merged.retain(|e| !range_deleted(range_tombstones, &e.key));
+    let range_deleted = (entries_in - merged.len()) as u64;
+    // A row's markers and the cells they hide are never split across partitions
+    let family_deletes = family_deletes(merged.iter().map(|e| (&e.key, &e.value)));
+    if !family_deletes.is_empty() {
+        merged.retain(|e| !family_deleted(&family_deletes, &e.key, &e.value));
+    }
+    let mut dropped = DroppedEntries {
+        range_deleted,
+        family_deleted: (entries_in - merged.len()) as u64 - range_deleted,
+        ..DroppedEntries::default()
+    };
+
+    if options.max_versions.is_some() || options.max_age_ms.is_some() || options.cleanup_tombstones {
+        // Group entries by row and column using iterators
+        let grouped: BTreeMap<(Vec<u8>, Vec<u8>), Vec<Entry>> = merged
+            .into_iter()
+            .fold(BTreeMap::new(), |mut acc, entry| {
+                let key = (entry.key.row.clone(), entry.key.column.clone());
+                acc.entry(key).or_default().push(entry);
+                acc
+            });
+
+        // Process each group of entries using iterators
+        let filtered: Vec<Entry> = grouped.into_iter()
+            .flat_map(|((row, _), mut entries)| {
+                // A range compaction may not see every SSTable holding other rows
+                if range.is_some_and(|(start_row, end_row)| row.as_slice() < start_row || row.as_slice() >= end_row) {
+                    return entries;
+                }
+
+                // Sort by timestamp (descending)
+                entries.sort_by_key(|e| std::cmp::Reverse((e.key.timestamp, e.key.seq)));
+
+                // Use fold to maintain state while filtering entries
+                entries.into_iter()
+                    .fold((Vec::new(), false), |(mut kept, mut seen_non_tombstone), entry| {
+                        let keep = match &entry.value {
+                            CellValue::Put(_) => {
+                                let within_version_limit = options.max_versions
+                                    .map(|max| kept.len() < max)
+                                    .unwrap_or(true);
+
+                                let within_age_limit = options.max_age_ms
+                                    .map(|max_age| now - entry.key.timestamp <= max_age)
+                                    .unwrap_or(true);
+
+                                if !within_version_limit {
+                                    dropped.excess_versions += 1;
+                                } else if !within_age_limit {
+                                    dropped.expired += 1;
+                                }
+                                within_version_limit && within_age_limit
+                            },
+                            CellValue::Delete(ttl) => {
+                                let keep = if options.cleanup_tombstones {
+                                    match ttl {
+                                        Some(ttl_ms) => {
+                                            entry.key.timestamp + ttl_ms > now
+                                        },
+                                        None => {
+                                            !seen_non_tombstone
+                                        }
+                                    }
+                                } else {
+                                    true
+                                };
+                                if !keep {
+                                    dropped.tombstones += 1;
+                                }
+                                keep
+                            }
+                            // Older cells of the row may be left in SSTables a minor or
+                            // range compaction does not see
+                            CellValue::DeleteFamily => {
+                                let keep = !(options.cleanup_tombstones
+                                    && options.compaction_type == CompactionType::Major
+                                    && range.is_none());
+                                if !keep {
+                                    dropped.tombstones += 1;
+                                }
+                                keep
+                            }
+                        };
+
+                        if keep {
+                            if let CellValue::Put(_) = entry.value {
+                                seen_non_tombstone = true;
+                            }
+                            kept.push(entry);
+                        }
+
+                        (kept, seen_non_tombstone)
+                    })
+                    .0  // Return just the kept entries
+            })
+            .collect();
+
+        // Versions were kept newest first; SSTables must be in key order
+        merged = filtered;
+        merged.sort_by(|a, b| a.key.cmp(&b.key));
+    }
+    (merged, dropped)
+}
+
+/// Keep only the columns of row with a filter in filter_set, and the versions of those
+/// columns that match it and its timestamp range.
+pub(crate) fn apply_filter_set(filter_set: &FilterSet, mut row: RowVersions) -> RowVersions {
+    if !filter_set.column_filters.is_empty() {
+        let filter_columns: Vec<Vec<u8>> = filter_set.column_filters
+            .iter()
+            .map(|cf| cf.column.clone())
+            .collect();
+
+        row.retain(|column, _| filter_columns.contains(column));
+    }
+
+    for column_filter in &filter_set.column_filters {
+        if let Some(versions) = row.get_mut(&column_filter.column) {
+            let filtered_versions: Vec<(Timestamp, Vec<u8>)> = versions
+                .iter()
+                .filter(|(ts, value)| {
+                    filter_set.timestamp_matches(*ts) && column_filter.filter.matches(value)
+                })
+                .cloned()
+                .collect();
+
+            if filtered_versions.is_empty() {
+                row.remove(&column_filter.column);
+            } else {
+                *versions = filtered_versions;
+            }
+        }
+    }
+    row
+}
+
+fn range_deleted(tombstones: &[RangeTombstone], key: &EntryKey) -> bool {
+    tombstones.iter().any(|t| t.covers(&key.row, &key.column, key.timestamp))
+}
+
+/// The timestamp of the newest `DeleteFamily` marker of each row among entries.
+fn family_deletes<'a>(entries: impl IntoIterator<Item = (&'a EntryKey, &'a CellValue)>) -> HashMap<RowKey, Timestamp> {
+    let mut deletes: HashMap<RowKey, Timestamp> = HashMap::new();
+    for (key, cell) in entries {
+        if *cell == CellValue::DeleteFamily {
+            let deleted_at = deletes.entry(key.row.clone()).or_default();
+            *deleted_at = (*deleted_at).max(key.timestamp);
+        }
+    }
+    deletes
+}
+
+/// Whether the cell version at key is hidden by a `DeleteFamily` marker in deletes.
+/// The markers themselves are not.
+fn family_deleted(deletes: &HashMap<RowKey, Timestamp>, key: &EntryKey, cell: &CellValue) -> bool {
+    *cell != CellValue::DeleteFamily
+        && deletes.get(key.row.as_slice()).is_some_and(|&deleted_at| key.timestamp <= deleted_at)
 }
 
 /// Compaction type: minor (merge some SSTables) or major (merge all SSTables)
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CompactionType {
     /// Minor compaction: merge a subset of SSTables
     Minor,
@@ -132,8 +772,14 @@ pub struct CompactionOptions {
     pub max_age_ms: Option<u64>,
     /// Whether to clean up expired tombstones
     pub cleanup_tombstones: bool,
+    /// Start a new output SSTable once one holds this many bytes (never inside a row);
+    /// `None` writes one SSTable per partition (see `CompactionParallelism`)
+    pub target_file_size_bytes: Option<u64>,
 }
 
+/// Default `CompactionOptions::target_file_size_bytes`.
+pub const DEFAULT_TARGET_FILE_SIZE_BYTES: u64 = 256 * 1024 * 1024;
+
 impl Default for CompactionOptions {
     fn default() -> Self {
         CompactionOptions {
@@ -141,127 +787,1534 @@ impl Default for CompactionOptions {
             max_versions: None,
             max_age_ms: None,
             cleanup_tombstones: true,
+            target_file_size_bytes: Some(DEFAULT_TARGET_FILE_SIZE_BYTES),
         }
     }
 }
 
-/// Lexicographically‐ordered key for each versioned cell: (row, column, timestamp).
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct EntryKey {
-    pub row: RowKey,
-    pub column: Column,
-    pub timestamp: Timestamp,
+/// What a compaction record describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompactionKind {
+    Minor,
+    Major,
+    /// A compaction of the SSTables overlapping a row range (see `ColumnFamily::compact_range`)
+    Range,
+    /// A merge of small SSTables, which keeps every version (see `SmallFileMergePolicy`)
+    SmallFileMerge,
 }
 
-/// An Entry couples an EntryKey with a CellValue (Put or Delete).
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Entry {
-    pub key: EntryKey,
-    pub value: CellValue,
+/// Why a compaction dropped entries, by number of entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct DroppedEntries {
+    /// Cells deleted by a range tombstone
+    pub range_deleted: u64,
+    /// Cells hidden by a `DeleteFamily` marker of their row
+    pub family_deleted: u64,
+    /// Versions beyond the version limit
+    pub excess_versions: u64,
+    /// Versions older than the age limit or TTL
+    pub expired: u64,
+    /// Delete markers past their TTL or no longer shadowing anything
+    pub tombstones: u64,
 }
 
-/// A single ColumnFamily inside a Table, with MVCC support and version filtering.
-///
-/// - *MemStore*: in‐memory BTreeMap + WAL (append‐only).
-/// - *SSTables*: on‐disk files (immutable, each is a sorted list of (EntryKey, CellValue)).
-/// - *Compaction*: runs periodically to merge SSTables (we keep all versions in compaction).
-/// - *MVCC reads*: get_versions(...) and scan_row_versions(...) let you fetch multiple versions.
-#[derive(Clone)]
-pub struct ColumnFamily {
-    name: String,
-    path: PathBuf,
-    memstore: Arc<Mutex<MemStore>>,
-    sst_files: Arc<Mutex<Vec<PathBuf>>>,
+impl std::ops::AddAssign for DroppedEntries {
+    fn add_assign(&mut self, other: Self) {
+        self.range_deleted += other.range_deleted;
+        self.family_deleted += other.family_deleted;
+        self.excess_versions += other.excess_versions;
+        self.expired += other.expired;
+        self.tombstones += other.tombstones;
+    }
 }
 
-impl ColumnFamily {
-    /// Open (or create) a column family at table_path/colfam_name.
-    ///
-    /// Spawns a background thread that runs compact() every 60 seconds.
-    pub fn open(table_path: &Path, colfam_name: &str) -> IoResult<Self> {
-        let cf_path = table_path.join(colfam_name);
-        fs::create_dir_all(&cf_path)?;
-
-        let mem = MemStore::open(&cf_path.join("wal.log"))?;
+/// A compaction that is running.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CompactionProgress {
+    pub kind: CompactionKind,
+    /// Start time in milliseconds since the epoch
+    pub started_at: u64,
+    pub files_in: usize,
+    pub bytes_in: u64,
+}
 
-        let mut sst_files = Vec::new();
-        for entry in fs::read_dir(&cf_path)? {
-            let e = entry?;
-            if let Some(ext) = e.path().extension() {
-                if ext == "sst" {
-                    sst_files.push(e.path());
-                }
-            }
-        }
-        sst_files.sort();
+/// A finished compaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CompactionRecord {
+    pub kind: CompactionKind,
+    /// Start time in milliseconds since the epoch
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub files_in: usize,
+    pub bytes_in: u64,
+    /// SSTables written, more than one with parallel partitions or a target file size
+    pub files_out: usize,
+    pub bytes_written: u64,
+    pub entries_in: u64,
+    pub entries_out: u64,
+    pub dropped: DroppedEntries,
+}
 
-        let cf = ColumnFamily {
-            name: colfam_name.to_string(),
-            path: cf_path.clone(),
-            memstore: Arc::new(Mutex::new(mem)),
-            sst_files: Arc::new(Mutex::new(sst_files)),
-        };
+/// Number of finished compactions kept in `CompactionStats::history`.
+pub const COMPACTION_HISTORY_LEN: usize = 16;
 
-        {
-            let cf_clone = cf.clone();
-            thread::spawn(move || {
-                loop {
-                    thread::sleep(Duration::from_secs(60));
-                    if let Err(err) = cf_clone.compact() {
-                        eprintln!(
-                            "[ColumnFamily::compact] error in CF '{}': {:?}",
-                            cf_clone.name, err
-                        );
-                    }
-                }
-            });
-        }
+/// Compactions and small-file merges of a column family since the process opened it.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct CompactionStats {
+    /// The compaction running now, if any
+    pub ongoing: Option<CompactionProgress>,
+    /// The latest finished compactions, oldest first
+    pub history: Vec<CompactionRecord>,
+    pub completed: u64,
+    /// Compactions that failed, or were abandoned because the SSTables changed meanwhile
+    pub failed: u64,
+    pub bytes_written: u64,
+}
 
-        Ok(cf)
+impl CompactionStats {
+    /// The latest finished compaction.
+    pub fn last(&self) -> Option<&CompactionRecord> {
+        self.history.last()
     }
+}
 
-    /// Write a new versioned cell (row, column) = value with a fresh timestamp.
-    pub fn put(&self, row: RowKey, column: Column, value: Vec<u8>) -> IoResult<()> {
-        let ts = chrono::Utc::now().timestamp_millis() as u64;
-        let entry = Entry {
-            key: EntryKey { row, column, timestamp: ts },
-            value: CellValue::Put(value),
+/// Size and contents of a column family, as returned by `ColumnFamily::stats`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct ColumnFamilyStats {
+    pub sst_files: usize,
+    /// Total size of the live SSTables on disk
+    pub sst_bytes: u64,
+    pub memstore_entries: usize,
+    /// Approximate memory taken by the MemStore's keys and values
+    pub memstore_bytes: u64,
+    /// Sum of the distinct rows of each SSTable and the MemStore: a row held by several
+    /// of them is counted once per holder, and deleted rows are counted until compacted
+    pub approx_rows: u64,
+    /// Delete markers in the SSTables and the MemStore
+    pub tombstones: u64,
+    /// Memory taken by the bloom filters of the SSTables, prefix bloom filters included
+    pub bloom_filter_bytes: u64,
+    /// SSTables without a bloom filter, written with bloom filters disabled or before
+    /// they existed
+    pub unfiltered_sst_files: usize,
+}
+
+/// Flushes, WAL writes and write stalls of a column family since the process opened it,
+/// as returned by `ColumnFamily::write_stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct WriteStats {
+    /// MemStore flushes that wrote an SSTable
+    pub flushes: u64,
+    /// Flushes that failed, leaving their entries in the MemStore
+    pub failed_flushes: u64,
+    /// Time spent writing and recording the SSTables of flushes
+    pub flush_ms: u64,
+    /// Size of the SSTables written by flushes
+    pub bytes_flushed: u64,
+    /// Bytes appended to the WAL by writes
+    pub wal_bytes_written: u64,
+    /// Writes delayed by the slowdown thresholds of the write throttle
+    pub writes_delayed: u64,
+    /// Time writes spent delayed
+    pub write_stall_ms: u64,
+    /// Writes rejected by the stop thresholds of the write throttle or the memory budget
+    pub writes_rejected: u64,
+}
+
+/// Estimated size of a row range, as returned by `ColumnFamily::estimate_range_size`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct RangeSizeEstimate {
+    /// Stored versions and tombstones in the range, before merging and pruning
+    pub entries: u64,
+    /// Approximate bytes of those entries, on disk and in the MemStore
+    pub bytes: u64,
+    /// Approximate number of rows, counted once per SSTable holding them
+    pub approx_rows: u64,
+    /// SSTables holding part of the range
+    pub sst_files: usize,
+}
+
+/// Reports a compaction as ongoing in a column family's stats until it is finished;
+/// dropping it unfinished counts the compaction as failed.
+struct CompactionTracker<'a> {
+    stats: &'a Mutex<CompactionStats>,
+    progress: CompactionProgress,
+    started: Instant,
+    finished: bool,
+}
+
+impl<'a> CompactionTracker<'a> {
+    fn start(stats: &'a Mutex<CompactionStats>, kind: CompactionKind, inputs: &[PathBuf]) -> IoResult<Self> {
+        let progress = CompactionProgress {
+            kind,
+            started_at: chrono::Utc::now().timestamp_millis() as u64,
+            files_in: inputs.len(),
+            bytes_in: inputs.iter().map(|path| fs::metadata(path).map(|m| m.len())).sum::<IoResult<u64>>()?,
         };
-        let mut ms = self.memstore.lock().unwrap();
-        ms.append(entry)?;
-        if ms.len() > 10_000 {
-            drop(ms);
-            self.flush()?;
+        stats.lock().unwrap().ongoing = Some(progress.clone());
+        Ok(Self { stats, progress, started: Instant::now(), finished: false })
+    }
+
+    fn finish(
+        mut self,
+        bytes_written: u64,
+        files_out: usize,
+        entries_in: usize,
+        entries_out: usize,
+        dropped: DroppedEntries,
+    ) -> CompactionRecord {
+        self.finished = true;
+        let record = CompactionRecord {
+            kind: self.progress.kind,
+            started_at: self.progress.started_at,
+            duration_ms: self.started.elapsed().as_millis() as u64,
+            files_in: self.progress.files_in,
+            bytes_in: self.progress.bytes_in,
+            files_out,
+            bytes_written,
+            entries_in: entries_in as u64,
+            entries_out: entries_out as u64,
+            dropped,
+        };
+        let mut stats = self.stats.lock().unwrap();
+        stats.ongoing = None;
+        stats.completed += 1;
+        stats.bytes_written += bytes_written;
+        if stats.history.len() == COMPACTION_HISTORY_LEN {
+            stats.history.remove(0);
+        }
+        stats.history.push(record.clone());
+        record
+    }
+}
+
+impl Drop for CompactionTracker<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let mut stats = self.stats.lock().unwrap();
+            stats.ongoing = None;
+            stats.failed += 1;
+        }
+    }
+}
+
+/// Retention settings and size limits of a column family, declared when it is created
+/// and persisted in the table's schema (see `schema`).
+///
+/// Reads never return versions beyond `max_versions` or older than `ttl_ms`, and every
+/// compaction drops them, so no explicit `compact_with_max_versions` calls are needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ColumnFamilyOptions {
+    /// Maximum number of versions kept per cell, counting delete markers
+    pub max_versions: Option<usize>,
+    /// Time-to-live of every version in milliseconds, measured from its timestamp
+    pub ttl_ms: Option<u64>,
+    /// Limits on the keys and values of every write
+    #[serde(default)]
+    pub limits: SizeLimits,
+    /// Bloom filters written for new SSTables (see `bloom`)
+    #[serde(default)]
+    pub bloom_filter: BloomFilterOptions,
+}
+
+impl ColumnFamilyOptions {
+    /// Whether a version written at timestamp has expired at now.
+    pub fn expired(&self, timestamp: Timestamp, now: Timestamp) -> bool {
+        self.ttl_ms.is_some_and(|ttl| now.saturating_sub(timestamp) > ttl)
+    }
+
+    pub(crate) fn validate(&self) -> IoResult<()> {
+        if self.max_versions == Some(0) {
+            return Err(Error::new(ErrorKind::InvalidInput, "max_versions must be at least 1"));
+        }
+        self.bloom_filter.validate()?;
+        self.limits.validate()
+    }
+}
+
+/// The tighter of two optional limits.
+fn tighter<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Options for a scan that returns its rows in size-limited batches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanOptions {
+    /// Maximum number of versions to return per column
+    pub max_versions: usize,
+    /// End the batch before its estimated serialized size (row keys, columns, timestamps
+    /// and values) passes this many bytes. A batch always holds at least one row.
+    pub max_response_bytes: Option<usize>,
+    /// End the batch at the first row boundary past this instant, marking it timed out
+    pub deadline: Option<Instant>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            max_versions: 1,
+            max_response_bytes: None,
+            deadline: None,
+        }
+    }
+}
+
+/// One batch of rows returned by `ColumnFamily::scan_with_options`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ScanBatch {
+    pub rows: RangeVersions,
+    /// If the batch was truncated, the position to pass to `resume_scan` to continue
+    pub continuation: Option<ResumeToken>,
+    /// Whether the batch was cut short by `ScanOptions::deadline` rather than its size;
+    /// the rows it holds are complete and the continuation resumes after them
+    pub timed_out: bool,
+}
+
+/// Fail with `TimedOut` if deadline has passed; what names the interrupted operation.
+fn check_deadline(deadline: Option<Instant>, what: &str) -> IoResult<()> {
+    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        return Err(Error::new(ErrorKind::TimedOut, format!("{} exceeded its deadline", what)));
+    }
+    Ok(())
+}
+
+/// Limits past which writers should back off until flushes and compactions catch up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteStallThresholds {
+    /// Stall once a column family has this many SSTables awaiting compaction
+    pub max_sst_files: usize,
+    /// Stall once the MemStore holds this many entries (e.g. because flushes are failing)
+    pub max_memstore_entries: usize,
+}
+
+impl Default for WriteStallThresholds {
+    fn default() -> Self {
+        Self {
+            max_sst_files: 32,
+            max_memstore_entries: 20_000,
+        }
+    }
+}
+
+/// Throttling of writes while flushes and compactions fall behind, so that sustained
+/// write load cannot pile up SSTables (and read amplification) without bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteThrottle {
+    /// Past these thresholds, each write first waits for `delay`
+    pub slowdown: WriteStallThresholds,
+    /// How long a write waits while the column family is past `slowdown`
+    pub delay: Duration,
+    /// Past these thresholds, writes fail with `ResourceBusy`
+    pub stop: WriteStallThresholds,
+}
+
+impl Default for WriteThrottle {
+    fn default() -> Self {
+        Self {
+            slowdown: WriteStallThresholds {
+                max_sst_files: 16,
+                max_memstore_entries: 15_000,
+            },
+            delay: Duration::from_millis(10),
+            stop: WriteStallThresholds::default(),
+        }
+    }
+}
+
+/// When a column family flushes its MemStore on its own. A table can also cap the
+/// MemStores of all its column families together, see `Table::set_memstore_limit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlushPolicy {
+    /// Flush on the writing thread once the MemStore holds more entries than this
+    pub max_entries: usize,
+    /// Flush in the background once the entries written since the last flush take
+    /// this many bytes, so that a few large values cannot fill memory
+    pub max_bytes: u64,
+}
+
+impl Default for FlushPolicy {
+    fn default() -> Self {
+        Self {
+            max_entries: 10_000,
+            max_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// How often a table checks its MemStores against its memory limit.
+const MEMSTORE_LIMIT_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// When to merge the small SSTables left by frequent flushes, without waiting for a
+/// compaction. Merging only combines files; versions and tombstones are kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmallFileMergePolicy {
+    /// SSTables of at most this many bytes count as small
+    pub max_file_bytes: u64,
+    /// Merge once at least this many of the newest SSTables are small
+    pub min_files: usize,
+    /// Merge at most this many bytes at once
+    pub max_merge_bytes: u64,
+}
+
+impl Default for SmallFileMergePolicy {
+    fn default() -> Self {
+        Self {
+            max_file_bytes: 4 * 1024 * 1024,
+            min_files: 8,
+            max_merge_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl SmallFileMergePolicy {
+    /// The run of newest SSTables in sst_files (oldest first) that should be merged,
+    /// or an empty list if the trigger is not met.
+    fn select(&self, sst_files: &[PathBuf]) -> IoResult<Vec<PathBuf>> {
+        let mut run = Vec::new();
+        let mut total = 0u64;
+        for path in sst_files.iter().rev() {
+            let size = fs::metadata(path)?.len();
+            if size > self.max_file_bytes || total + size > self.max_merge_bytes {
+                break;
+            }
+            total += size;
+            run.push(path.clone());
+        }
+        if run.len() < self.min_files.max(2) {
+            return Ok(Vec::new());
+        }
+        run.reverse();
+        Ok(run)
+    }
+}
+
+/// How a compaction of a large column family is split into partitions of disjoint rows,
+/// which are pruned in parallel, and how many output SSTables are written at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactionParallelism {
+    /// Prune at most this many partitions, and write at most this many SSTables, at once
+    pub max_threads: usize,
+    /// Split only inputs of at least twice this many bytes, into partitions of at
+    /// least this many input bytes
+    pub min_partition_bytes: u64,
+}
+
+impl Default for CompactionParallelism {
+    fn default() -> Self {
+        Self {
+            max_threads: thread::available_parallelism().map_or(1, |n| n.get()).min(4),
+            min_partition_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl CompactionParallelism {
+    /// Number of partitions for a compaction of input_bytes.
+    fn partitions(&self, input_bytes: u64) -> usize {
+        let by_size = input_bytes / self.min_partition_bytes.max(1);
+        (by_size.min(self.max_threads as u64) as usize).max(1)
+    }
+}
+
+/// Why writes to a column family are currently stalled
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum WriteStall {
+    /// Too many SSTables; compaction is behind
+    TooManySstables { count: usize, limit: usize },
+    /// The MemStore has grown past its limit
+    MemstoreFull { entries: usize, limit: usize },
+    /// The MemStores of the table take more bytes than its whole memory budget
+    MemoryBudget { used: u64, limit: u64 },
+}
+
+impl std::fmt::Display for WriteStall {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteStall::TooManySstables { count, limit } => {
+                write!(f, "{} SSTables awaiting compaction (limit {})", count, limit)
+            }
+            WriteStall::MemstoreFull { entries, limit } => {
+                write!(f, "{} MemStore entries (limit {})", entries, limit)
+            }
+            WriteStall::MemoryBudget { used, limit } => {
+                write!(f, "{} bytes in the table's MemStores (memory budget {})", used, limit)
+            }
+        }
+    }
+}
+
+/// Lexicographically‐ordered key for each versioned cell: (row, column, timestamp, seq).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EntryKey {
+    pub row: RowKey,
+    pub column: Column,
+    pub timestamp: Timestamp,
+    /// Sequence number the MemStore assigned when the entry was appended to the WAL,
+    /// which orders versions written in the same millisecond. 0 for entries that never
+    /// went through a WAL (bulk loads) or were written before sequence numbers existed.
+    /// Left out of the serde representation; SSTables and WAL records store it after
+    /// the key (see `storage::serialize_key`).
+    #[serde(skip)]
+    pub seq: Seq,
+}
+
+/// An Entry couples an EntryKey with a CellValue (Put or Delete).
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entry {
+    pub key: EntryKey,
+    pub value: CellValue,
+}
+
+/// A single ColumnFamily inside a Table, with MVCC support and version filtering.
+///
+/// - *MemStore*: in‐memory BTreeMap + WAL (append‐only).
+/// - *SSTables*: on‐disk files (immutable, each is a sorted list of (EntryKey, CellValue)).
+/// - *Compaction*: runs periodically to merge SSTables (we keep all versions in compaction).
+/// - *MVCC reads*: get_versions(...) and scan_row_versions(...) let you fetch multiple versions.
+#[derive(Clone)]
+pub struct ColumnFamily {
+    name: String,
+    path: PathBuf,
+    memstore: Arc<Mutex<MemStore>>,
+    sst_files: Arc<Mutex<Vec<PathBuf>>>,
+    frozen: Arc<AtomicBool>,
+    /// Archive of rotated WALs, if WAL archiving is on
+    wal_archive: Arc<Mutex<Option<WalArchive>>>,
+    changelog: Arc<Mutex<Option<ChangelogWriter>>>,
+    range_tombstones: Arc<Mutex<Vec<RangeTombstone>>>,
+    subscribers: Arc<Mutex<Vec<UnboundedSender<Entry>>>>,
+    validators: Arc<Mutex<HashMap<Column, Vec<Validator>>>>,
+    observers: Arc<Mutex<Vec<Arc<dyn Observer>>>>,
+    small_file_merge: Arc<Mutex<Option<SmallFileMergePolicy>>>,
+    write_throttle: Arc<Mutex<Option<WriteThrottle>>>,
+    compaction_throttle: Arc<Mutex<CompactionThrottle>>,
+    compaction_parallelism: Arc<Mutex<CompactionParallelism>>,
+    compaction_stats: Arc<Mutex<CompactionStats>>,
+    write_stats: Arc<Mutex<WriteStats>>,
+    /// Set while a background small-file merge is running
+    merging: Arc<AtomicBool>,
+    /// Held while compacting or merging, so that they never pick the same SSTables
+    compaction_lock: Arc<Mutex<()>>,
+    /// Held by a flush from freezing the MemStore until its SSTable is live
+    flush_lock: Arc<Mutex<()>>,
+    flush_policy: Arc<Mutex<FlushPolicy>>,
+    /// Set while a background flush is scheduled or running
+    flush_scheduled: Arc<AtomicBool>,
+    /// Memory budget of the table the column family belongs to, once it has been checked
+    memory_gate: Arc<OnceLock<Arc<MemoryGate>>>,
+    /// Timestamps the writes made now
+    clock: Arc<HybridClock>,
+    block_cache: Arc<BlockCache>,
+    /// Opened readers of the live SSTables
+    readers: Arc<ReaderCache>,
+    /// Edit log of the live SSTable set; locked after sst_files
+    manifest: Arc<Mutex<Manifest>>,
+    /// SSTables in use by reads, deleted only once they are done
+    file_refs: Arc<FileRefs>,
+    durability: Arc<Mutex<Durability>>,
+    options: Arc<Mutex<ColumnFamilyOptions>>,
+    /// Writer thread of the async write path, started on first use
+    writer: Arc<OnceLock<WriteQueue>>,
+    expiry: Arc<ExpirySchedule>,
+    /// Background compaction thread, set once it is spawned
+    compaction_thread: Arc<OnceLock<thread::JoinHandle<()>>>,
+    /// What opening the column family recovered
+    recovery: Arc<CfRecovery>,
+}
+
+impl ColumnFamily {
+    /// Open (or create) a column family at table_path/colfam_name with the options the
+    /// table's schema records for it (see `TableSchema::cf_options`).
+    ///
+    /// Spawns a background thread that runs compact() every 60 seconds.
+    pub fn open(table_path: &Path, colfam_name: &str) -> IoResult<Self> {
+        let options = TableSchema::cf_options(table_path, colfam_name)?;
+        Self::open_with_options(table_path, colfam_name, options)
+    }
+
+    /// Open (or create) a column family at table_path/colfam_name with the given options.
+    /// The options are not persisted here; `Table` records them in its schema.
+    #[instrument(level = "debug", skip(table_path), fields(table = %table_path.display()))]
+    pub fn open_with_options(table_path: &Path, colfam_name: &str, options: ColumnFamilyOptions) -> IoResult<Self> {
+        options.validate()?;
+        let cf_path = table_path.join(colfam_name);
+        fs::create_dir_all(&cf_path)?;
+
+        // A WAL left by a crash can hold more than fits in memory: what outgrows a MemStore
+        // is flushed as it is replayed
+        let mut manifest = Manifest::open(&cf_path)?;
+        let truncated_seq = manifest.truncated_seq();
+        let mut records_skipped = 0;
+        let mut mem = MemStore::open_bounded(
+            Arc::new(LocalFs),
+            cf_path.join("wal.log"),
+            FlushPolicy::default().max_bytes,
+            |entries| flush_replayed(&cf_path, &mut manifest, entries, truncated_seq, options.bloom_filter).map(|skipped| records_skipped += skipped),
+        )?;
+        records_skipped += mem.discard_through(truncated_seq) as u64;
+        mem.advance_seq(manifest.last_seq());
+        let logged = mem.iter().map(|(key, _)| key.timestamp).max().unwrap_or(0);
+        let clock = HybridClock::new(manifest.last_timestamp().max(logged));
+        let sst_files = manifest.live_files();
+        let recovery = CfRecovery {
+            column_family: colfam_name.to_string(),
+            wal: mem.replay_stats(),
+            records_skipped,
+            sstables_live: sst_files.len(),
+            sstables_ignored: ignored_sstables(&cf_path, &sst_files)?,
+            problems: Vec::new(),
+        };
+        if recovery.records_skipped > 0 || recovery.wal.torn_bytes > 0 || !recovery.sstables_ignored.is_empty() {
+            info!(%recovery, "recovered column family");
+        }
+        let frozen = cf_path.join(FROZEN_MARKER).exists();
+        let wal_archive = WalArchive::open(&cf_path)?;
+        let changelog_path = cf_path.join(CHANGELOG_FILE);
+        let changelog = if changelog_path.exists() {
+            Some(ChangelogWriter::open(changelog_path)?)
+        } else {
+            None
+        };
+        let range_tombstones_path = cf_path.join(RANGE_TOMBSTONES_FILE);
+        let range_tombstones: Vec<RangeTombstone> = if range_tombstones_path.exists() {
+            serde_json::from_slice(&fs::read(range_tombstones_path)?)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e))?
+        } else {
+            Vec::new()
+        };
+        debug!(sst_count = sst_files.len(), frozen, range_tombstones = range_tombstones.len(), "opened column family");
+
+        let cf = ColumnFamily {
+            name: colfam_name.to_string(),
+            path: cf_path.clone(),
+            memstore: Arc::new(Mutex::new(mem)),
+            sst_files: Arc::new(Mutex::new(sst_files)),
+            frozen: Arc::new(AtomicBool::new(frozen)),
+            wal_archive: Arc::new(Mutex::new(wal_archive)),
+            changelog: Arc::new(Mutex::new(changelog)),
+            range_tombstones: Arc::new(Mutex::new(range_tombstones)),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+            validators: Arc::new(Mutex::new(HashMap::new())),
+            observers: Arc::new(Mutex::new(Vec::new())),
+            small_file_merge: Arc::new(Mutex::new(Some(SmallFileMergePolicy::default()))),
+            write_throttle: Arc::new(Mutex::new(None)),
+            compaction_throttle: Arc::new(Mutex::new(CompactionThrottle::default())),
+            compaction_parallelism: Arc::new(Mutex::new(CompactionParallelism::default())),
+            compaction_stats: shared_stats(&COMPACTION_STATS, &cf_path),
+            write_stats: shared_stats(&WRITE_STATS, &cf_path),
+            merging: Arc::new(AtomicBool::new(false)),
+            compaction_lock: Arc::new(Mutex::new(())),
+            flush_lock: Arc::new(Mutex::new(())),
+            flush_policy: Arc::new(Mutex::new(FlushPolicy::default())),
+            flush_scheduled: Arc::new(AtomicBool::new(false)),
+            memory_gate: Arc::new(OnceLock::new()),
+            clock: Arc::new(clock),
+            block_cache: Arc::new(BlockCache::new(DEFAULT_BLOCK_CACHE_BYTES)),
+            readers: Arc::new(ReaderCache::new()),
+            manifest: Arc::new(Mutex::new(manifest)),
+            file_refs: Arc::new(FileRefs::new()),
+            durability: Arc::new(Mutex::new(Durability::default())),
+            options: Arc::new(Mutex::new(options)),
+            writer: Arc::new(OnceLock::new()),
+            expiry: Arc::new(ExpirySchedule::default()),
+            compaction_thread: Arc::new(OnceLock::new()),
+            recovery: Arc::new(recovery),
+        };
+
+        {
+            let cf_clone = cf.clone();
+            let handle = thread::spawn(move || {
+                loop {
+                    thread::sleep(Duration::from_secs(60));
+                    if let Err(err) = cf_clone.compact() {
+                        error!(cf = %cf_clone.name, error = %err, "background compaction failed");
+                    }
+                }
+            });
+            let _ = cf.compaction_thread.set(handle);
+        }
+
+        Ok(cf)
+    }
+
+    /// Retention settings of this column family.
+    pub fn options(&self) -> ColumnFamilyOptions {
+        *self.options.lock().unwrap()
+    }
+
+    /// Apply new retention settings to reads and later compactions (see `Table::alter_cf`).
+    pub(crate) fn set_options(&self, options: ColumnFamilyOptions) {
+        *self.options.lock().unwrap() = options;
+    }
+
+    /// Name of this column family.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// What opening this column family recovered: the WAL replayed, the entries a
+    /// truncation had discarded, and the SSTables ignored (see `recovery`).
+    pub fn recovery(&self) -> CfRecovery {
+        (*self.recovery).clone()
+    }
+
+    /// Whether the background compaction thread is still running. It only stops if it
+    /// panicked, after which this column family is no longer compacted on its own.
+    pub fn background_thread_alive(&self) -> bool {
+        self.compaction_thread.get().is_none_or(|handle| !handle.is_finished())
+    }
+
+    /// Number of live SSTables.
+    pub fn sst_file_count(&self) -> usize {
+        self.sst_files.lock().unwrap().len()
+    }
+
+    /// Total size in bytes of the live SSTables.
+    pub fn sst_bytes(&self) -> IoResult<u64> {
+        self.sst_files.lock().unwrap()
+            .iter()
+            .map(|path| fs::metadata(path).map(|m| m.len()))
+            .sum()
+    }
+
+    /// SSTable, MemStore, row and tombstone counts and sizes. SSTables are summed from
+    /// their footers, without reading their entries.
+    pub fn stats(&self) -> IoResult<ColumnFamilyStats> {
+        let mut stats = ColumnFamilyStats::default();
+        {
+            let ms = self.memstore.lock().unwrap();
+            let mut last_row: Option<&[u8]> = None;
+            for (key, cell) in ms.iter() {
+                stats.memstore_entries += 1;
+                stats.memstore_bytes += entry_bytes(key, cell);
+                if matches!(cell, CellValue::Delete(_)) {
+                    stats.tombstones += 1;
+                }
+                if last_row != Some(key.row.as_slice()) {
+                    stats.approx_rows += 1;
+                    last_row = Some(&key.row);
+                }
+            }
+        }
+        // Pinned after the MemStore is read, so that a flush in between is seen
+        for path in self.pin_sst_files().iter() {
+            let reader = self.readers.get(path)?;
+            let footer = reader.footer();
+            stats.sst_files += 1;
+            stats.sst_bytes += fs::metadata(path)?.len();
+            stats.approx_rows += footer.rows;
+            stats.tombstones += footer.tombstones;
+            match &footer.bloom {
+                Some(bloom) => stats.bloom_filter_bytes += bloom.size_bytes() as u64,
+                None => stats.unfiltered_sst_files += 1,
+            }
+            if let Some(bloom) = &footer.prefix_bloom {
+                stats.bloom_filter_bytes += bloom.size_bytes() as u64;
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Estimate the size of rows [start_row, end_row] without reading them, to decide
+    /// whether to paginate, split or parallelize a scan of the range.
+    ///
+    /// Each SSTable overlapping the range contributes its entries in the range, found
+    /// by binary search, and the same share of its bytes and rows. The estimate counts
+    /// every stored version and tombstone, so it errs on the large side.
+    pub fn estimate_range_size(&self, start_row: &[u8], end_row: &[u8]) -> IoResult<RangeSizeEstimate> {
+        let mut estimate = RangeSizeEstimate::default();
+        {
+            let ms = self.memstore.lock().unwrap();
+            let mut last_row: Option<&[u8]> = None;
+            for (key, cell) in ms.range_iter(start_row, end_row) {
+                estimate.entries += 1;
+                estimate.bytes += entry_bytes(key, cell);
+                if last_row != Some(key.row.as_slice()) {
+                    estimate.approx_rows += 1;
+                    last_row = Some(&key.row);
+                }
+            }
+        }
+        for path in self.pin_sst_files().iter() {
+            let reader = self.readers.get(path)?;
+            let footer = reader.footer();
+            if !footer.overlaps_rows(start_row, end_row) {
+                continue;
+            }
+            let in_range = reader.range_len(start_row, end_row) as u64;
+            if in_range == 0 {
+                continue;
+            }
+            let share = in_range as f64 / footer.entries as f64;
+            estimate.sst_files += 1;
+            estimate.entries += in_range;
+            estimate.bytes += (fs::metadata(path)?.len() as f64 * share).ceil() as u64;
+            estimate.approx_rows += ((footer.rows as f64 * share).ceil() as u64).max(1);
+        }
+        debug!(?estimate, "estimated range size");
+        Ok(estimate)
+    }
+
+    /// Stop using this column family after its data was moved elsewhere (e.g. by a
+    /// region split): reject further writes and stop background compaction from
+    /// touching its files. Nothing is written to disk.
+    pub(crate) fn retire(&self) {
+        let _ms = self.memstore.lock().unwrap();
+        self.frozen.store(true, Ordering::SeqCst);
+        self.sst_files.lock().unwrap().clear();
+        self.readers.clear();
+    }
+
+    /// Number of entries currently buffered in the MemStore.
+    pub fn memstore_entries(&self) -> usize {
+        self.memstore.lock().unwrap().len()
+    }
+
+    /// Approximate memory taken by the entries buffered in the MemStore, including
+    /// those a running flush is writing out.
+    pub fn memstore_bytes(&self) -> u64 {
+        self.memstore.lock().unwrap().size_bytes()
+    }
+
+    /// Approximate memory taken by the MemStore entries no flush is writing out yet.
+    pub(crate) fn unflushed_bytes(&self) -> u64 {
+        self.memstore.lock().unwrap().active_bytes()
+    }
+
+    /// Report whether writes should currently stall under the given thresholds.
+    pub fn write_stall(&self, thresholds: &WriteStallThresholds) -> Option<WriteStall> {
+        let count = self.sst_file_count();
+        if count >= thresholds.max_sst_files {
+            return Some(WriteStall::TooManySstables { count, limit: thresholds.max_sst_files });
+        }
+        let entries = self.memstore_entries();
+        if entries >= thresholds.max_memstore_entries {
+            return Some(WriteStall::MemstoreFull { entries, limit: thresholds.max_memstore_entries });
+        }
+        None
+    }
+
+    /// Throttling applied to writes, if any.
+    pub fn write_throttle(&self) -> Option<WriteThrottle> {
+        *self.write_throttle.lock().unwrap()
+    }
+
+    /// Delay or reject writes while the column family is past the thresholds of throttle,
+    /// or stop throttling with `None` (the default). Lives in memory only.
+    pub fn set_write_throttle(&self, throttle: Option<WriteThrottle>) {
+        *self.write_throttle.lock().unwrap() = throttle;
+    }
+
+    /// Apply the write throttle before a write: wait while past its slowdown thresholds,
+    /// and fail with `ResourceBusy` while past its stop thresholds.
+    fn throttle_write(&self) -> IoResult<()> {
+        if let Some(stall) = self.memory_gate.get().and_then(|gate| gate.stall()) {
+            warn!(cf = %self.name, %stall, "rejected write");
+            self.write_stats.lock().unwrap().writes_rejected += 1;
+            return Err(Error::new(
+                ErrorKind::ResourceBusy,
+                format!("Writes to column family {} are stalled: {}", self.name, stall),
+            ));
+        }
+        let Some(throttle) = self.write_throttle() else {
+            return Ok(());
+        };
+        if let Some(stall) = self.write_stall(&throttle.stop) {
+            warn!(cf = %self.name, %stall, "rejected write");
+            self.write_stats.lock().unwrap().writes_rejected += 1;
+            return Err(Error::new(
+                ErrorKind::ResourceBusy,
+                format!("Writes to column family {} are stalled: {}", self.name, stall),
+            ));
+        }
+        if let Some(stall) = self.write_stall(&throttle.slowdown) {
+            debug!(cf = %self.name, %stall, delay_ms = throttle.delay.as_millis() as u64, "delaying write");
+            thread::sleep(throttle.delay);
+            let mut stats = self.write_stats.lock().unwrap();
+            stats.writes_delayed += 1;
+            stats.write_stall_ms += throttle.delay.as_millis() as u64;
         }
         Ok(())
     }
 
-    /// Execute a Put operation with multiple columns.
-    /// This is similar to the HBase/Java Put API.
-    pub fn execute_put(&self, put: Put) -> IoResult<()> {
-        let ts = chrono::Utc::now().timestamp_millis() as u64;
+    /// Whether this column family has been frozen and no longer accepts writes.
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.load(Ordering::SeqCst)
+    }
+
+    /// Fail with `PermissionDenied` if the column family is frozen.
+    /// Called with the MemStore lock held so that no write can slip in behind `freeze`.
+    fn check_writable(&self) -> IoResult<()> {
+        if self.is_frozen() {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!("Column family {} is frozen", self.name),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Start recording every mutation in a changelog that other processes can tail
+    /// with `changelog::ChangelogReader`. Idempotent; once enabled, the changelog stays
+    /// enabled across reopens.
+    pub fn enable_changelog(&self) -> IoResult<()> {
+        let _ms = self.memstore.lock().unwrap();
+        let mut changelog = self.changelog.lock().unwrap();
+        if changelog.is_none() {
+            *changelog = Some(ChangelogWriter::open(self.path.join(CHANGELOG_FILE))?);
+            info!(cf = %self.name, "enabled changelog");
+        }
+        Ok(())
+    }
+
+    /// Whether mutations are being recorded in the changelog.
+    pub fn changelog_enabled(&self) -> bool {
+        self.changelog.lock().unwrap().is_some()
+    }
+
+    /// Keep every WAL rotated by a flush in `wal_archive/` instead of deleting it, for
+    /// `recover_to`. Idempotent; once enabled, archiving stays on across reopens. If the
+    /// column family is empty, the archive holds its whole history; otherwise recoveries
+    /// need a snapshot taken after this call as their base.
+    pub fn enable_wal_archive(&self) -> IoResult<()> {
+        let ms = self.memstore.lock().unwrap();
+        let mut archive = self.wal_archive.lock().unwrap();
+        if archive.is_none() {
+            let complete = ms.is_empty() && self.sst_files.lock().unwrap().is_empty();
+            *archive = Some(WalArchive::create(&self.path, complete)?);
+            info!(cf = %self.name, complete, "enabled WAL archiving");
+        }
+        Ok(())
+    }
+
+    /// Stop archiving WALs and delete the archive, which could no longer cover every
+    /// write if archiving resumed later.
+    pub fn disable_wal_archive(&self) -> IoResult<()> {
+        let _ms = self.memstore.lock().unwrap();
+        if let Some(archive) = self.wal_archive.lock().unwrap().take() {
+            archive.remove()?;
+            info!(cf = %self.name, "disabled WAL archiving");
+        }
+        Ok(())
+    }
+
+    /// When WAL archiving began and the recoveries made since, or `None` if it is off.
+    pub fn wal_archive_state(&self) -> Option<WalArchiveState> {
+        self.wal_archive.lock().unwrap().as_ref().map(|archive| archive.state().clone())
+    }
+
+    /// Register a validator that every value written to column must pass.
+    /// Validators live in memory only and must be registered again after reopening.
+    pub fn add_validator(&self, column: &[u8], validator: Validator) {
+        self.validators.lock().unwrap().entry(column.to_vec()).or_default().push(validator);
+    }
+
+    /// Remove all validators of column.
+    pub fn clear_validators(&self, column: &[u8]) {
+        self.validators.lock().unwrap().remove(column);
+    }
+
+    /// Register an observer of this column family's operations (see `observer`), called
+    /// after those registered before it. Observers live in memory only and must be
+    /// registered again after reopening.
+    pub fn add_observer(&self, observer: impl Observer + 'static) {
+        self.observers.lock().unwrap().push(Arc::new(observer));
+    }
+
+    /// Remove all observers.
+    pub fn clear_observers(&self) {
+        self.observers.lock().unwrap().clear();
+    }
+
+    /// The registered observers, copied so that hooks run without holding the list.
+    fn observers(&self) -> Vec<Arc<dyn Observer>> {
+        self.observers.lock().unwrap().clone()
+    }
+
+    /// Run the `pre_scan` hooks of the observers for a scan of [start_row, end_row].
+    fn observe_scan(&self, start_row: &[u8], end_row: &[u8]) -> IoResult<()> {
+        self.observers().iter().try_for_each(|observer| observer.pre_scan(self, start_row, end_row))
+    }
+
+    /// Run the `post_compact` hooks of the observers.
+    fn observe_compaction(&self, record: &CompactionRecord) {
+        for observer in self.observers() {
+            observer.post_compact(self, record);
+        }
+    }
+
+    /// Check value against the validators of column, as a put would.
+    /// Fails with `InvalidInput` wrapping a `ValidationError`.
+    pub fn validate(&self, row: &[u8], column: &[u8], value: &[u8]) -> IoResult<()> {
+        let validators = self.validators.lock().unwrap();
+        for validator in validators.get(column).into_iter().flatten() {
+            if let Err(message) = validator.check(value) {
+                return Err(ValidationError {
+                    row: String::from_utf8_lossy(row).to_string(),
+                    column: String::from_utf8_lossy(column).to_string(),
+                    rule: validator.rule().to_string(),
+                    message,
+                }.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Directory holding this column family's files.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record entries in the changelog (if enabled), then in the WAL (with a single
+    /// write) and MemStore, and finally publish them to subscribers.
+    /// The changelog is written first so that tailers see every acknowledged write.
+    fn append_entries(&self, ms: &mut MemStore, entries: Vec<Entry>, request_ids: Vec<String>) -> IoResult<()> {
+        if let Some(changelog) = self.changelog.lock().unwrap().as_mut() {
+            entries.iter().try_for_each(|entry| changelog.append(entry))?;
+        }
+
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let logged = ms.wal_bytes_written();
+        if subscribers.is_empty() {
+            ms.append_requests(entries, request_ids)?;
+        } else {
+            ms.append_requests(entries.clone(), request_ids)?;
+            // Dropped streams are pruned here
+            subscribers.retain(|tx| entries.iter().all(|entry| tx.unbounded_send(entry.clone()).is_ok()));
+        }
+        self.write_stats.lock().unwrap().wal_bytes_written += ms.wal_bytes_written() - logged;
+        Ok(())
+    }
+
+    /// Subscribe to a change data capture stream of this column family.
+    ///
+    /// The stream yields every put and delete once it is in the WAL, in commit order,
+    /// starting with the first write after the call; dropping the stream unsubscribes.
+    /// Data loaded with `bulk_load`, `ingest_external_file` or `import_cf`, range
+    /// deletes and snapshot restores bypass the WAL and are not published. Pending
+    /// entries are buffered without limit, so a stream that is no longer polled should
+    /// be dropped; for durable, resumable tailing use the changelog instead.
+    pub fn subscribe(&self) -> impl Stream<Item = Entry> {
+        let (tx, rx) = unbounded();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    fn snapshot_path(&self, name: &str) -> PathBuf {
+        self.path.join(SNAPSHOT_DIR).join(name)
+    }
+
+    /// Take a named snapshot of the column family while it stays online.
+    ///
+    /// The MemStore is flushed first, then the current SSTables are hard-linked (or
+    /// copied) into `snapshots/<name>` together with a manifest. SSTables are immutable,
+    /// so the snapshot costs no extra space until compaction replaces the live files.
+    #[instrument(level = "debug", skip(self), fields(cf = %self.name))]
+    pub fn snapshot(&self, name: &str) -> IoResult<SnapshotManifest> {
+        validate_snapshot_name(name)?;
+        let snapshot_path = self.snapshot_path(name);
+        if snapshot_path.exists() {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("Snapshot {} already exists", name),
+            ));
+        }
+
+        self.flush()?;
+
+        // Holding the list lock keeps compaction from deleting files while they are linked
+        let sst_files = self.sst_files.lock().unwrap();
+        fs::create_dir_all(&snapshot_path)?;
+
+        let mut manifest = SnapshotManifest {
+            name: name.to_string(),
+            column_family: self.name.clone(),
+            created_at: chrono::Utc::now().timestamp_millis() as u64,
+            sst_files: Vec::new(),
+            range_tombstones: self.range_tombstones(),
+        };
+        for sst in sst_files.iter() {
+            let file_name = sst.file_name().unwrap();
+            link_or_copy(sst, &snapshot_path.join(file_name))?;
+            manifest.sst_files.push(file_name.to_string_lossy().to_string());
+        }
+
+        // The manifest is written last: a snapshot without one is incomplete
+        let json = serde_json::to_vec_pretty(&manifest).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        fs::write(snapshot_path.join(SNAPSHOT_MANIFEST), json)?;
+
+        info!(snapshot = name, sst_files = manifest.sst_files.len(), "took snapshot");
+        Ok(manifest)
+    }
+
+    /// Read the manifest of a named snapshot.
+    pub fn snapshot_manifest(&self, name: &str) -> IoResult<SnapshotManifest> {
+        validate_snapshot_name(name)?;
+        let json = fs::read(self.snapshot_path(name).join(SNAPSHOT_MANIFEST))?;
+        serde_json::from_slice(&json).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Names of all complete snapshots of this column family, in sorted order.
+    pub fn list_snapshots(&self) -> IoResult<Vec<String>> {
+        let dir = self.path.join(SNAPSHOT_DIR);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut names = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.path().join(SNAPSHOT_MANIFEST).exists() {
+                names.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Delete a named snapshot. Live SSTables are unaffected.
+    pub fn delete_snapshot(&self, name: &str) -> IoResult<()> {
+        validate_snapshot_name(name)?;
+        fs::remove_dir_all(self.snapshot_path(name))
+    }
+
+    /// Roll the column family back to a named snapshot.
+    ///
+    /// Unflushed writes and all SSTables written since the snapshot are discarded.
+    /// The snapshot itself is kept, so it can be restored again. Restores are not
+    /// recorded in the changelog, and a frozen column family cannot be restored.
+    #[instrument(level = "debug", skip(self), fields(cf = %self.name))]
+    pub fn restore_snapshot(&self, name: &str) -> IoResult<()> {
+        let manifest = self.snapshot_manifest(name)?;
+        let snapshot_path = self.snapshot_path(name);
+
         let mut ms = self.memstore.lock().unwrap();
+        self.check_writable()?;
+        let mut sst_files = self.sst_files.lock().unwrap();
 
-        // Process each column in the Put object using iterators
-        put.columns().iter().try_for_each(|(column, value)| {
-            let entry = Entry {
-                key: EntryKey { 
-                    row: put.row().clone(), 
-                    column: column.clone(), 
-                    timestamp: ts 
-                },
-                value: CellValue::Put(value.clone()),
-            };
-            ms.append(entry)
+        let discarded = ms.drain_all()?.len();
+
+        // Restored files get new names, so they never collide with files still being read
+        let mut sources = manifest.sst_files.clone();
+        sources.sort();
+        let mut manifest_guard = self.manifest.lock().unwrap();
+        let first = manifest_guard.allocate(sources.len());
+        let mut restored = Vec::with_capacity(sources.len());
+        for (seq, file_name) in (first..).zip(&sources) {
+            let dst = manifest_guard.file_path(seq);
+            link_or_copy(&snapshot_path.join(file_name), &dst)?;
+            restored.push(dst);
+        }
+        self.sync_new_files(&restored)?;
+        manifest_guard.replace(&restored)?;
+        drop(manifest_guard);
+        let replaced = std::mem::replace(&mut *sst_files, restored);
+        self.delete_obsolete(&replaced);
+        self.readers.retain(&sst_files);
+
+        let mut range_tombstones = self.range_tombstones.lock().unwrap();
+        self.save_range_tombstones(&manifest.range_tombstones)?;
+        *range_tombstones = manifest.range_tombstones;
+
+        info!(snapshot = name, sst_files = sst_files.len(), discarded, "restored snapshot");
+        Ok(())
+    }
+
+    /// Roll the column family back to its state at timestamp, from the WAL archive (see
+    /// `wal_archive`). Returns the number of entries recovered.
+    ///
+    /// The newest usable snapshot taken since archiving began is the base, or nothing if
+    /// archiving began on an empty column family; every archived write (and unflushed
+    /// one) with a timestamp of at most timestamp is replayed over it, and the result
+    /// replaces the SSTables and MemStore. The writes undone stay archived, so a later
+    /// recovery can still be made. Data written around the WAL (bulk loads, ingested
+    /// and imported files) is not recovered, and range deletes are kept only as far
+    /// as the base or the current ones hold them. Fails with `NotFound` if archiving is
+    /// off or there is no base.
+    #[instrument(level = "debug", skip(self), fields(cf = %self.name))]
+    pub fn recover_to(&self, timestamp: Timestamp) -> IoResult<usize> {
+        let mut ms = self.memstore.lock().unwrap();
+        self.check_writable()?;
+        let mut archive_guard = self.wal_archive.lock().unwrap();
+        let archive = archive_guard.as_mut().ok_or_else(|| {
+            Error::new(ErrorKind::NotFound, format!("WAL archiving is not enabled for {}", self.name))
         })?;
 
-        if ms.len() > 10_000 {
-            drop(ms);
-            self.flush()?;
+        let mut base = None;
+        for name in self.list_snapshots()? {
+            let snapshot = self.snapshot_manifest(&name)?;
+            if archive.usable_base(snapshot.created_at, timestamp)
+                && base.as_ref().is_none_or(|b: &SnapshotManifest| b.created_at < snapshot.created_at)
+            {
+                base = Some(snapshot);
+            }
+        }
+        if base.is_none() && !archive.state().complete {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("No snapshot of {} taken between the start of WAL archiving and {}", self.name, timestamp),
+            ));
+        }
+
+        let mut recovered: BTreeMap<EntryKey, CellValue> = BTreeMap::new();
+        if let Some(base) = &base {
+            for file_name in &base.sst_files {
+                let reader = SSTableReader::open(self.snapshot_path(&base.name).join(file_name))?;
+                recovered.extend(reader.all_entries().iter().filter(|(key, _)| key.timestamp <= timestamp).cloned());
+            }
+        }
+        let replayed = archive.replay(timestamp)?;
+        recovered.extend(replayed.into_iter().map(|entry| (entry.key, entry.value)));
+        recovered.extend(ms.entries().into_iter().filter(|entry| entry.key.timestamp <= timestamp).map(|entry| (entry.key, entry.value)));
+        let entries: Vec<Entry> = recovered.into_iter().map(|(key, value)| Entry { key, value }).collect();
+
+        let mut sst_files = self.sst_files.lock().unwrap();
+        let mut manifest = self.manifest.lock().unwrap();
+        let mut written = Vec::new();
+        if !entries.is_empty() {
+            let path = manifest.allocate_file();
+            SSTable::create_with_bloom_filter(&path, &entries, self.durability(), self.options().bloom_filter)?;
+            written.push(path);
+        }
+        // The unflushed writes are archived too, and undone like the archived ones
+        ms.archive_to(&archive.next_segment())?;
+        archive.record_recovery(timestamp)?;
+        manifest.replace(&written)?;
+        drop(manifest);
+        let replaced = std::mem::replace(&mut *sst_files, written);
+        self.delete_obsolete(&replaced);
+        self.readers.retain(&sst_files);
+
+        let mut range_tombstones = self.range_tombstones.lock().unwrap();
+        let kept: Vec<RangeTombstone> = base.map_or_else(|| range_tombstones.clone(), |base| base.range_tombstones)
+            .into_iter()
+            .filter(|tombstone| tombstone.timestamp <= timestamp)
+            .collect();
+        self.save_range_tombstones(&kept)?;
+        *range_tombstones = kept;
+
+        info!(timestamp, entries = entries.len(), "recovered column family from the WAL archive");
+        Ok(entries.len())
+    }
+
+    /// Capture the column family at one point for a backup: the content of its WAL,
+    /// its range tombstones, and its SSTables, pinned so that compactions cannot delete
+    /// them before they are copied. Writes wait only while the WAL is read.
+    pub(crate) fn backup_capture(&self) -> IoResult<CfBackup> {
+        let ms = self.memstore.lock().unwrap();
+        let sst_files = self.file_refs.pin(self.sst_files.lock().unwrap().clone());
+        let wal = ms.wal_contents()?;
+        let range_tombstones = self.range_tombstones();
+        Ok(CfBackup { wal, sst_files, range_tombstones })
+    }
+
+    /// Hard-link (or copy) the live SSTables and the range tombstones of the column
+    /// family into dir, after a flush, and write a manifest of them there. Returns the
+    /// number of SSTables.
+    pub(crate) fn clone_files_to(&self, dir: &Path) -> IoResult<usize> {
+        self.flush()?;
+
+        // Holding the list lock keeps compaction from deleting files while they are linked
+        let sst_files = self.sst_files.lock().unwrap();
+        fs::create_dir_all(dir)?;
+        for sst in sst_files.iter() {
+            link_or_copy(sst, &dir.join(sst.file_name().unwrap()))?;
+        }
+        let range_tombstones = self.range_tombstones();
+        if !range_tombstones.is_empty() {
+            let json = serde_json::to_vec_pretty(&range_tombstones).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            fs::write(dir.join(RANGE_TOMBSTONES_FILE), json)?;
+        }
+        Manifest::open(dir)?;
+        Ok(sst_files.len())
+    }
+
+    /// A unique temporary path for an SSTable that is not yet part of the column family.
+    fn staging_path(&self) -> PathBuf {
+        let id = STAGING_COUNTER.fetch_add(1, Ordering::Relaxed);
+        self.path.join(format!("staging-{}-{}.sst.tmp", std::process::id(), id))
+    }
+
+    /// Load pre-sorted entries straight into new SSTables, bypassing the WAL,
+    /// MemStore and changelog. Intended for fast initial imports.
+    ///
+    /// Entries must be in strictly increasing EntryKey order (row, column, then
+    /// timestamp ascending) and keep their own timestamps. They are streamed into
+    /// SSTables of at most one million entries each, which only become visible once
+    /// all of them are written. Returns the number of entries loaded.
+    #[instrument(level = "debug", skip_all, fields(cf = %self.name))]
+    pub fn bulk_load(&self, entries: impl IntoIterator<Item = Entry>) -> IoResult<u64> {
+        {
+            let _ms = self.memstore.lock().unwrap();
+            self.check_writable()?;
+        }
+        let started = Instant::now();
+
+        // Stage files under temporary names so a failed load leaves nothing behind
+        let mut staged: Vec<PathBuf> = Vec::new();
+        let mut writer: Option<SSTableWriter> = None;
+        let mut last_key: Option<EntryKey> = None;
+        let mut total = 0u64;
+
+        let result = (|| -> IoResult<()> {
+            for entry in entries {
+                // Check ordering across file boundaries too
+                if last_key.as_ref().is_some_and(|last| *last >= entry.key) {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "Bulk load entries must be in strictly increasing key order",
+                    ));
+                }
+                if writer.as_ref().is_none_or(|w| w.len() >= BULK_LOAD_ENTRIES_PER_SST) {
+                    if let Some(full) = writer.take() {
+                        full.finish()?;
+                    }
+                    let path = self.staging_path();
+                    writer = Some(SSTableWriter::create_with(&path, self.durability())?.with_bloom_filter(self.options().bloom_filter));
+                    staged.push(path);
+                }
+                writer.as_mut().unwrap().append(&entry)?;
+                last_key = Some(entry.key);
+                total += 1;
+            }
+            if let Some(last) = writer.take() {
+                last.finish()?;
+            }
+            Ok(())
+        })();
+
+        if let Err(err) = result {
+            for path in &staged {
+                let _ = fs::remove_file(path);
+            }
+            return Err(err);
+        }
+
+        let mut sst_files = self.sst_files.lock().unwrap();
+        let mut manifest = self.manifest.lock().unwrap();
+        let first_seq = manifest.allocate(staged.len());
+        let mut added = Vec::with_capacity(staged.len());
+        for (seq, path) in (first_seq..).zip(staged.iter()) {
+            let final_path = manifest.file_path(seq);
+            fs::rename(path, &final_path)?;
+            added.push(final_path);
+        }
+        self.durability().sync_dir(&self.path)?;
+        manifest.record(&added, &[])?;
+        drop(manifest);
+        sst_files.extend(added);
+        sst_files.sort();
+
+        info!(
+            entries = total,
+            sst_files = staged.len(),
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "bulk loaded"
+        );
+        Ok(total)
+    }
+
+    /// Add an SSTable built elsewhere (e.g. with `SSTableWriter`) to this column family.
+    ///
+    /// The file is validated and copied into the column family directory; like
+    /// `bulk_load`, this bypasses the WAL, MemStore and changelog. Returns the number
+    /// of entries ingested.
+    #[instrument(level = "debug", skip_all, fields(cf = %self.name, path = %path.as_ref().display()))]
+    pub fn ingest_external_file(&self, path: impl AsRef<Path>) -> IoResult<u64> {
+        {
+            let _ms = self.memstore.lock().unwrap();
+            self.check_writable()?;
+        }
+
+        let reader = SSTableReader::open(&path)?;
+        if !reader.is_sorted() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "External SSTable entries are not in strictly increasing key order",
+            ));
+        }
+
+        let staged = self.staging_path();
+        if let Err(err) = fs::copy(&path, &staged).and_then(|_| self.sync_new_files(std::slice::from_ref(&staged))) {
+            let _ = fs::remove_file(&staged);
+            return Err(err);
+        }
+
+        let mut sst_files = self.sst_files.lock().unwrap();
+        let mut manifest = self.manifest.lock().unwrap();
+        let final_path = manifest.allocate_file();
+        fs::rename(&staged, &final_path)?;
+        self.durability().sync_dir(&self.path)?;
+        manifest.record(std::slice::from_ref(&final_path), &[])?;
+        drop(manifest);
+        sst_files.push(final_path);
+        sst_files.sort();
+
+        info!(entries = reader.len(), "ingested external SSTable");
+        Ok(reader.len() as u64)
+    }
+
+    /// Adopt the SSTables of another column family directory (or of a snapshot
+    /// directory) into this column family, hard-linking them when both live on the
+    /// same filesystem and copying otherwise.
+    ///
+    /// The source is validated first: it must not hold unflushed WAL entries or
+    /// unresolved range deletes, and every SSTable listed (by its snapshot manifest, if present, otherwise by directory
+    /// listing) must be readable and sorted. The source is left untouched. Like
+    /// `bulk_load`, adopted data bypasses the WAL and changelog. Returns the number of
+    /// SSTables adopted.
+    #[instrument(level = "debug", skip_all, fields(cf = %self.name, source = %source_dir.as_ref().display()))]
+    pub fn import_cf(&self, source_dir: impl AsRef<Path>) -> IoResult<usize> {
+        let source_dir = source_dir.as_ref();
+        {
+            let _ms = self.memstore.lock().unwrap();
+            self.check_writable()?;
+        }
+        if fs::canonicalize(source_dir)? == fs::canonicalize(&self.path)? {
+            return Err(Error::new(ErrorKind::InvalidInput, "Cannot import a column family into itself"));
+        }
+
+        let wal = source_dir.join("wal.log");
+        let unflushed = |wal: &Path| fs::metadata(wal).is_ok_and(|m| m.len() > 0);
+        if unflushed(&wal) || wal_segments(&LocalFs, &wal)?.iter().any(|segment| unflushed(segment)) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{} has unflushed WAL entries; flush it before importing", source_dir.display()),
+            ));
+        }
+
+        let pending_range_deletes = Error::new(
+            ErrorKind::InvalidData,
+            format!("{} has unresolved range deletes; major compact it before importing", source_dir.display()),
+        );
+        let manifest_path = source_dir.join(SNAPSHOT_MANIFEST);
+        let mut sources: Vec<PathBuf> = if manifest_path.exists() {
+            let manifest: SnapshotManifest = serde_json::from_slice(&fs::read(&manifest_path)?)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            if !manifest.range_tombstones.is_empty() {
+                return Err(pending_range_deletes);
+            }
+            manifest.sst_files.iter().map(|name| source_dir.join(name)).collect()
+        } else if source_dir.join(RANGE_TOMBSTONES_FILE).exists() {
+            return Err(pending_range_deletes);
+        } else if let Some(live) = Manifest::read_live_files(source_dir)? {
+            live
+        } else {
+            fs::read_dir(source_dir)?
+                .map(|entry| entry.map(|e| e.path()))
+                .filter(|path| path.as_ref().map_or(true, |p| p.extension().is_some_and(|ext| ext == "sst")))
+                .collect::<IoResult<_>>()?
+        };
+        sources.sort();
+
+        for source in &sources {
+            let reader = SSTableReader::open(source).map_err(|e| {
+                Error::new(ErrorKind::InvalidData, format!("Unreadable SSTable {}: {}", source.display(), e))
+            })?;
+            if !reader.is_sorted() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("SSTable {} is not sorted", source.display()),
+                ));
+            }
         }
+
+        let mut staged = Vec::with_capacity(sources.len());
+        for source in &sources {
+            let path = self.staging_path();
+            if let Err(err) = link_or_copy(source, &path) {
+                staged.iter().for_each(|p| { let _ = fs::remove_file(p); });
+                return Err(err);
+            }
+            staged.push(path);
+        }
+        if let Err(err) = self.sync_new_files(&staged) {
+            staged.iter().for_each(|p| { let _ = fs::remove_file(p); });
+            return Err(err);
+        }
+
+        let mut sst_files = self.sst_files.lock().unwrap();
+        let mut manifest = self.manifest.lock().unwrap();
+        let first_seq = manifest.allocate(staged.len());
+        let mut added = Vec::with_capacity(staged.len());
+        for (seq, path) in (first_seq..).zip(staged.iter()) {
+            let final_path = manifest.file_path(seq);
+            fs::rename(path, &final_path)?;
+            added.push(final_path);
+        }
+        self.durability().sync_dir(&self.path)?;
+        manifest.record(&added, &[])?;
+        drop(manifest);
+        sst_files.extend(added);
+        sst_files.sort();
+
+        info!(sst_files = staged.len(), "imported column family");
+        Ok(staged.len())
+    }
+
+    /// Freeze the column family for read-only serving, e.g. after a bulk load.
+    ///
+    /// New writes are rejected from this point on, the MemStore is flushed, and a
+    /// final major compaction merges everything into a single SSTable so that reads
+    /// consult one file. The frozen state is recorded on disk and survives reopening;
+    /// later compactions (including the background one) become no-ops.
+    #[instrument(level = "debug", skip(self), fields(cf = %self.name))]
+    pub fn freeze(&self) -> IoResult<()> {
+        {
+            let _ms = self.memstore.lock().unwrap();
+            if self.frozen.swap(true, Ordering::SeqCst) {
+                return Ok(());
+            }
+        }
+
+        self.flush()?;
+        self.compact_sstables(CompactionOptions {
+            compaction_type: CompactionType::Major,
+            ..CompactionOptions::default()
+        }, None)?;
+        fs::write(self.path.join(FROZEN_MARKER), b"")?;
+
+        info!("froze column family");
         Ok(())
     }
 
+    /// Timestamp for a write made now, from the column family's hybrid logical clock
+    /// (see `crate::clock`): the wall clock's time, unless the column family already
+    /// holds a later write.
+    pub fn now(&self) -> Timestamp {
+        self.clock.now()
+    }
+
+    /// How many milliseconds the column family's clock is ahead of the wall clock,
+    /// e.g. after the wall clock was set back; 0 while it follows it.
+    pub fn clock_drift_ms(&self) -> u64 {
+        self.clock.drift_ms()
+    }
+
+    /// Write a new versioned cell (row, column) = value with a fresh timestamp.
+    #[instrument(level = "debug", skip_all, fields(cf = %self.name))]
+    pub fn put(&self, row: RowKey, column: Column, value: Vec<u8>) -> IoResult<()> {
+        let ts = self.clock.now();
+        self.write_entries([Entry {
+            key: EntryKey { row, column, timestamp: ts, seq: 0 },
+            value: CellValue::Put(value),
+        }])
+    }
+
+    /// Execute a Put operation with multiple columns.
+    /// This is similar to the HBase/Java Put API.
+    #[instrument(level = "debug", skip_all, fields(cf = %self.name, columns = put.columns().len()))]
+    pub fn execute_put(&self, put: Put) -> IoResult<()> {
+        // write_entries validates every column first, so a rejected put writes nothing
+        let ts = self.clock.now();
+        self.write_entries(put.into_entries(ts))
+    }
+
     /// Mark (row, column) as deleted by writing a tombstone at the current timestamp.
     /// The tombstone will never expire (no TTL).
     pub fn delete(&self, row: RowKey, column: Column) -> IoResult<()> {
@@ -275,92 +2328,508 @@ impl ColumnFamily {
     /// * `row` - The row key
     /// * `column` - The column name
     /// * `ttl_ms` - Optional TTL in milliseconds. If None, the tombstone never expires.
+    #[instrument(level = "debug", skip(self, row, column), fields(cf = %self.name))]
     pub fn delete_with_ttl(&self, row: RowKey, column: Column, ttl_ms: Option<u64>) -> IoResult<()> {
-        let ts = chrono::Utc::now().timestamp_millis() as u64;
-        let entry = Entry {
-            key: EntryKey { row, column, timestamp: ts },
+        let ts = self.clock.now();
+        self.write_entries([Entry {
+            key: EntryKey { row, column, timestamp: ts, seq: 0 },
             value: CellValue::Delete(ttl_ms),
-        };
+        }])
+    }
+
+    /// Delete every cell of row written up to now, in any column, by writing a single
+    /// `DeleteFamily` marker instead of one tombstone per column.
+    ///
+    /// Cells written to the row afterwards are read as usual. A compaction drops the
+    /// cells the marker hides, and a major compaction drops the marker too.
+    #[instrument(level = "debug", skip(self, row), fields(cf = %self.name))]
+    pub fn delete_row(&self, row: RowKey) -> IoResult<()> {
+        let ts = self.clock.now();
+        self.write_entries([Entry {
+            key: EntryKey { row, column: FAMILY_DELETE_COLUMN.to_vec(), timestamp: ts, seq: 0 },
+            value: CellValue::DeleteFamily,
+        }])
+    }
+
+    /// Delete every cell of the rows in [start_row, end_row) with a single range
+    /// tombstone instead of one tombstone per cell.
+    ///
+    /// The tombstone hides all cells written up to now and is applied by every read.
+    /// A major compaction drops the covered cells and then the tombstone. Range deletes
+    /// are not recorded in the changelog.
+    #[instrument(level = "debug", skip_all, fields(cf = %self.name))]
+    pub fn delete_range(&self, start_row: &[u8], end_row: &[u8]) -> IoResult<()> {
+        if start_row >= end_row {
+            return Err(Error::new(ErrorKind::InvalidInput, "start_row must be less than end_row"));
+        }
+
+        let _ms = self.memstore.lock().unwrap();
+        self.check_writable()?;
+        let mut range_tombstones = self.range_tombstones.lock().unwrap();
+
+        let mut updated = range_tombstones.clone();
+        updated.push(RangeTombstone {
+            start_row: start_row.to_vec(),
+            end_row: end_row.to_vec(),
+            timestamp: self.clock.now(),
+            column: None,
+        });
+        self.save_range_tombstones(&updated)?;
+        *range_tombstones = updated;
+        Ok(())
+    }
+
+    /// Delete every version of (row, column) written at or before timestamp, e.g. to
+    /// erase the history of a cell while keeping its later versions, with a range
+    /// tombstone limited to the column.
+    ///
+    /// Like those of `delete_range`, the tombstone is applied by every read, a major
+    /// compaction purges the versions it covers and then the tombstone, and it is not
+    /// recorded in the changelog. Fails with `InvalidInput` if timestamp is ahead of the
+    /// column family's clock, since the tombstone would hide writes not made yet.
+    #[instrument(level = "debug", skip_all, fields(cf = %self.name))]
+    pub fn delete_before(&self, row: &[u8], column: &[u8], timestamp: Timestamp) -> IoResult<()> {
+        let _ms = self.memstore.lock().unwrap();
+        self.check_writable()?;
+        if timestamp > self.clock.now() {
+            return Err(Error::new(ErrorKind::InvalidInput, format!("Timestamp {} is in the future", timestamp)));
+        }
+        let mut range_tombstones = self.range_tombstones.lock().unwrap();
+
+        let mut end_row = row.to_vec();
+        end_row.push(0);
+        let mut updated = range_tombstones.clone();
+        updated.push(RangeTombstone {
+            start_row: row.to_vec(),
+            end_row,
+            timestamp,
+            column: Some(column.to_vec()),
+        });
+        self.save_range_tombstones(&updated)?;
+        *range_tombstones = updated;
+        Ok(())
+    }
+
+    /// Delete every cell of the column family at once: the MemStore and its WAL, every
+    /// SSTable and the range tombstones are dropped. The manifest edit emptying the
+    /// SSTable set also records the last write discarded, so after a crash the WAL is
+    /// replayed without the writes it held then. The column family stays open and
+    /// writable, and its snapshots, options and background threads are kept.
+    ///
+    /// A running compaction is waited for, and a running flush discards its SSTable, as
+    /// after a restore. Request IDs are forgotten like the writes they named, and a
+    /// truncation is not recorded in the changelog. Fails with `PermissionDenied` if the
+    /// column family is frozen.
+    #[instrument(level = "debug", skip(self), fields(cf = %self.name))]
+    pub fn truncate(&self) -> IoResult<()> {
+        // Compactions record their outputs in place of their inputs, which must stay live
+        let _compacting = self.compaction_lock.lock().unwrap();
         let mut ms = self.memstore.lock().unwrap();
-        ms.append(entry)?;
-        if ms.len() > 10_000 {
-            drop(ms);
-            self.flush()?;
+        self.check_writable()?;
+        let mut sst_files = self.sst_files.lock().unwrap();
+
+        let discarded = ms.len();
+        {
+            let mut manifest = self.manifest.lock().unwrap();
+            // The clock must not go back once the WAL holding the latest writes is gone
+            manifest.advance_timestamp(self.clock.last());
+            manifest.truncate(ms.last_seq())?;
         }
+        ms.clear()?;
+        let dropped = std::mem::take(&mut *sst_files);
+        self.delete_obsolete(&dropped);
+        self.readers.retain(&sst_files);
+
+        let mut range_tombstones = self.range_tombstones.lock().unwrap();
+        self.save_range_tombstones(&[])?;
+        range_tombstones.clear();
+
+        info!(sst_files = dropped.len(), discarded, "truncated column family");
         Ok(())
     }
 
+    /// Range tombstones that have not been resolved by a major compaction yet.
+    pub fn range_tombstones(&self) -> Vec<RangeTombstone> {
+        self.range_tombstones.lock().unwrap().clone()
+    }
+
+    /// Persist the range tombstone list, replacing the file atomically.
+    fn save_range_tombstones(&self, tombstones: &[RangeTombstone]) -> IoResult<()> {
+        let path = self.path.join(RANGE_TOMBSTONES_FILE);
+        if tombstones.is_empty() {
+            return match fs::remove_file(&path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+        let json = serde_json::to_vec_pretty(tombstones).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, &path)
+    }
+
     /// *Get* the single latest value for (row, column).
     /// If the latest version is a tombstone, returns Ok(None).
     /// Otherwise returns Ok(Some(value_bytes)).
+    #[instrument(level = "debug", skip_all, fields(cf = %self.name))]
     pub fn get(&self, row: &[u8], column: &[u8]) -> IoResult<Option<Vec<u8>>> {
-        let ms = self.memstore.lock().unwrap();
-        if let Some(cell) = ms.get_full(row, column) {
-            return match cell {
-                CellValue::Put(data) => Ok(Some(data.clone())),
-                CellValue::Delete(_) => Ok(None),
-            };
+        Ok(self.read_latest(&[(row, column)], |value| value.to_vec())?.pop().flatten())
+    }
+
+    /// *Get* the latest value for (row, column) decoded as a T, failing with
+    /// `InvalidData` if it is not one.
+    pub fn get_typed<T: TypedValue>(&self, row: &[u8], column: &[u8]) -> IoResult<Option<T>> {
+        self.get(row, column)?.map(|value| T::decode(&value)).transpose()
+    }
+
+    /// Whether (row, column) has a live value, i.e. `get` would return Some, without
+    /// copying the value.
+    #[instrument(level = "debug", skip_all, fields(cf = %self.name))]
+    pub fn exists(&self, row: &[u8], column: &[u8]) -> IoResult<bool> {
+        Ok(self.read_latest(&[(row, column)], |_| ())?.pop().flatten().is_some())
+    }
+
+    /// *Get* the latest value of each (row, column) in cells, in order, as `get` would.
+    /// The lookups share one MemStore lock and one pin of the live SSTables.
+    #[instrument(level = "debug", skip_all, fields(cf = %self.name, cells = cells.len()))]
+    pub fn multi_get(&self, cells: &[(RowKey, Column)]) -> IoResult<Vec<Option<Vec<u8>>>> {
+        let cells: Vec<(&[u8], &[u8])> = cells.iter().map(|(row, column)| (row.as_slice(), column.as_slice())).collect();
+        self.read_latest(&cells, |value| value.to_vec())
+    }
+
+    /// Pass the latest value of each (row, column) in cells to read, or return None for
+    /// cells whose latest version is a tombstone, range-deleted, family-deleted or expired.
+    fn read_latest<T>(&self, cells: &[(&[u8], &[u8])], read: impl Fn(&[u8]) -> T) -> IoResult<Vec<Option<T>>> {
+        let range_tombstones = self.range_tombstones();
+        let options = self.options();
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        let mut family_deletes: HashMap<&[u8], Option<Timestamp>> = HashMap::new();
+        for (row, _) in cells {
+            if !family_deletes.contains_key(row) {
+                family_deletes.insert(row, self.family_deleted_at(row)?);
+            }
         }
-        drop(ms);
+        let latest = |row: &[u8], column: &[u8], ts: Timestamp, cell: &CellValue| match cell {
+            CellValue::Put(data)
+                if !range_tombstones.iter().any(|t| t.covers(row, column, ts))
+                    && family_deletes[row].is_none_or(|deleted_at| ts > deleted_at)
+                    && !options.expired(ts, now) => Some(read(data)),
+            _ => None,
+        };
 
-        let sst_list = self.sst_files.lock().unwrap();
-        for sst_path in sst_list.iter().rev() {
-            let mut reader = SSTableReader::open(sst_path)?;
-            if let Some(cell) = reader.get_full(row, column)? {
-                return match cell {
-                    CellValue::Put(data) => Ok(Some(data)),
-                    CellValue::Delete(_) => Ok(None),
-                };
+        // None until the cell's latest version is found
+        let mut results: Vec<Option<Option<T>>> = {
+            let ms = self.memstore.lock().unwrap();
+            cells.iter()
+                .map(|(row, column)| ms.get_latest(row, column).map(|(ts, cell)| latest(row, column, ts, cell)))
+                .collect()
+        };
+
+        // Pinned after the MemStore lookups, so that a flush in between is seen
+        if results.iter().any(Option::is_none) {
+            let sst_list = self.pin_sst_files();
+            for ((row, column), result) in cells.iter().zip(results.iter_mut()) {
+                if result.is_some() {
+                    continue;
+                }
+                for sst_path in sst_list.iter().rev() {
+                    if !self.sst_may_contain(sst_path, row, Some(column))? {
+                        continue;
+                    }
+                    let entries = self.block_cache.get_or_load(sst_path, row, Some(column), || self.readers.get(sst_path))?;
+                    if let Some((key, cell)) = entries.last() {
+                        *result = Some(latest(row, column, key.timestamp, cell));
+                        break;
+                    }
+                }
             }
         }
-        Ok(None)
+        Ok(results.into_iter().map(Option::flatten).collect())
+    }
+
+    /// Entries of (row, column), or of the whole row if column is None, in every
+    /// SSTable that may hold the row from newest to oldest, served from the block cache
+    /// where possible.
+    fn sst_entries(&self, row: &[u8], column: Option<&[u8]>) -> IoResult<Vec<CachedEntries>> {
+        let mut entries = Vec::new();
+        for sst_path in self.pin_sst_files().iter().rev() {
+            if self.sst_may_contain(sst_path, row, column)? {
+                entries.push(self.block_cache.get_or_load(sst_path, row, column, || self.readers.get(sst_path))?);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// The timestamp of the newest `DeleteFamily` marker of row, if it has one. Only the
+    /// SSTables holding delete markers are read.
+    fn family_deleted_at(&self, row: &[u8]) -> IoResult<Option<Timestamp>> {
+        let memstore_entries = self.memstore.lock().unwrap().cell_entries(row, FAMILY_DELETE_COLUMN);
+        // Pinned after the MemStore is read, so that a flush in between is seen
+        let mut deleted_at = family_deletes(memstore_entries.iter().map(|(key, cell)| (key, cell))).remove(row);
+        for sst_path in self.pin_sst_files().iter() {
+            let reader = self.readers.get(sst_path)?;
+            let footer = reader.footer();
+            if footer.tombstones == 0 || !footer.may_contain_cell(row, FAMILY_DELETE_COLUMN) {
+                continue;
+            }
+            let entries = self.block_cache.get_or_load(sst_path, row, Some(FAMILY_DELETE_COLUMN), || self.readers.get(sst_path))?;
+            if let Some(ts) = family_deletes(entries.iter().map(|(key, cell)| (key, cell))).remove(row) {
+                deleted_at = deleted_at.max(Some(ts));
+            }
+        }
+        Ok(deleted_at)
+    }
+
+    /// Whether the SSTable at path may hold entries of (row, column), or of row if
+    /// column is None, going by its footer and bloom filter.
+    fn sst_may_contain(&self, path: &Path, row: &[u8], column: Option<&[u8]>) -> IoResult<bool> {
+        let reader = self.readers.get(path)?;
+        Ok(match column {
+            Some(column) => reader.footer().may_contain_cell(row, column),
+            None => reader.footer().may_contain_row(row),
+        })
+    }
+
+    /// Pin the live SSTables, so that they are not deleted while they are read.
+    fn pin_sst_files(&self) -> PinnedFiles {
+        let sst_files = self.sst_files.lock().unwrap();
+        self.file_refs.pin(sst_files.clone())
+    }
+
+    /// Delete SSTables that left the live set, once no read uses them any more.
+    fn delete_obsolete(&self, paths: &[PathBuf]) {
+        let durability = self.durability();
+        for path in paths {
+            self.file_refs.delete_when_unused(path, durability);
+        }
+    }
+
+    /// Sync SSTables linked or copied into the column family directory, and the directory.
+    fn sync_new_files(&self, paths: &[PathBuf]) -> IoResult<()> {
+        let durability = self.durability();
+        if durability == Durability::Buffered {
+            return Ok(());
+        }
+        for path in paths {
+            durability.sync_file(&fs::File::open(path)?)?;
+        }
+        durability.sync_dir(&self.path)
+    }
+
+    /// Check every live SSTable for corruption (see `verify`). With quarantine, the
+    /// corrupt ones are also removed from the live set and moved to the quarantine
+    /// directory, so that reads stop failing on them, at the cost of the cells they held.
+    #[instrument(level = "debug", skip(self), fields(cf = %self.name))]
+    pub fn verify(&self, quarantine: bool) -> IoResult<VerifyReport> {
+        let pinned = self.pin_sst_files();
+        let mut sstables: Vec<SSTableCheck> = pinned.iter().map(|path| check_sstable(path)).collect();
+        drop(pinned);
+        for check in sstables.iter().filter(|check| !check.is_ok()) {
+            error!(sst = %check.path.display(), problem = check.problem.as_deref().unwrap_or_default(), "corrupt SSTable");
+        }
+
+        if quarantine && sstables.iter().any(|check| !check.is_ok()) {
+            // Compactions record their outputs in place of their inputs, which must stay live
+            let _compacting = self.compaction_lock.lock().unwrap();
+            let mut sst_files = self.sst_files.lock().unwrap();
+            let quarantine_dir = self.path.join(QUARANTINE_DIR);
+            fs::create_dir_all(&quarantine_dir)?;
+            // Those compacted away since they were checked are gone already
+            for check in sstables.iter_mut().filter(|check| !check.is_ok()) {
+                if !sst_files.contains(&check.path) {
+                    continue;
+                }
+                self.manifest.lock().unwrap().record(&[], std::slice::from_ref(&check.path))?;
+                sst_files.retain(|path| *path != check.path);
+                let quarantined = quarantine_dir.join(check.path.file_name().unwrap());
+                fs::rename(&check.path, &quarantined)?;
+                warn!(sst = %check.path.display(), to = %quarantined.display(), "quarantined corrupt SSTable");
+                check.quarantined_to = Some(quarantined);
+            }
+            self.durability().sync_dir(&self.path)?;
+            self.readers.retain(&sst_files);
+        }
+        Ok(VerifyReport { column_family: self.name.clone(), sstables })
+    }
+
+    /// How far SSTable writes and removals are synced to disk.
+    pub fn durability(&self) -> Durability {
+        *self.durability.lock().unwrap()
+    }
+
+    /// Set how far SSTable writes and removals are synced to disk (not persisted).
+    ///
+    /// `Durability::Sync` (the default) fsyncs every new SSTable and the column family
+    /// directory; `Durability::Buffered` trades that for speed, e.g. for bulk imports
+    /// that can be redone after a power loss.
+    pub fn set_durability(&self, durability: Durability) {
+        *self.durability.lock().unwrap() = durability;
+    }
+
+    /// Version of the live SSTable set recorded in the manifest.
+    pub fn manifest_version(&self) -> u64 {
+        self.manifest.lock().unwrap().version()
+    }
+
+    /// Capacity of the block cache caching SSTable reads, in bytes.
+    pub fn block_cache_capacity(&self) -> usize {
+        self.block_cache.capacity()
+    }
+
+    /// Set the capacity of the block cache in bytes (not persisted); 0 disables caching.
+    pub fn set_block_cache_capacity(&self, capacity_bytes: usize) {
+        self.block_cache.set_capacity(capacity_bytes);
+    }
+
+    /// Usage and hit counters of the block cache.
+    pub fn block_cache_stats(&self) -> BlockCacheStats {
+        self.block_cache.stats()
+    }
+
+    /// Evict the least recently used blocks until the block cache holds at most bytes,
+    /// keeping its capacity.
+    pub(crate) fn shrink_block_cache(&self, bytes: usize) {
+        self.block_cache.shrink_to(bytes);
+    }
+
+    /// Subject writes to the memory budget of the table behind gate, unless they are already.
+    pub(crate) fn attach_memory_gate(&self, gate: &Arc<MemoryGate>) {
+        let _ = self.memory_gate.set(gate.clone());
+    }
+
+    /// Number of SSTable readers kept open for reads.
+    pub fn open_reader_count(&self) -> usize {
+        self.readers.len()
     }
 
     /// *MVCC read*: return up to max_versions recent (timestamp, value) for (row, column).
     /// - Versions are sorted descending by timestamp.
     /// - Tombstone versions (CellValue::Delete) are skipped entirely.
+    #[instrument(level = "debug", skip(self, row, column), fields(cf = %self.name))]
     pub fn get_versions(
         &self,
         row: &[u8],
         column: &[u8],
         max_versions: usize,
     ) -> IoResult<Vec<(Timestamp, Vec<u8>)>> {
-        let mut all_versions: Vec<(Timestamp, CellValue)> = Vec::new();
-
-        // Collect versions from memstore
-        {
-            let ms = self.memstore.lock().unwrap();
-            all_versions.extend(ms.get_versions_full(row, column));
-        }
-
-        // Collect versions from SSTable files
-        let sst_list = self.sst_files.lock().unwrap();
-        // Use map and collect to handle IoResult properly
-        let readers: IoResult<Vec<_>> = sst_list.iter()
-            .map(|sst_path| SSTableReader::open(sst_path))
-            .collect();
+        self.read_versions(row, column, max_versions, (0, Timestamp::MAX))
+    }
 
-        // Process each reader
-        for mut reader in readers? {
-            all_versions.extend(reader.get_versions_full(row, column)?);
+    /// Merge the versions of (row, column) from the MemStore and SSTables newest first,
+    /// returning up to max_versions puts whose timestamp lies in time_range (inclusive).
+    /// Versions beyond the column family's `max_versions` or past its TTL are never returned.
+    ///
+    /// Only the newest MemStore versions the read may need are copied; if it runs past
+    /// them (e.g. because of delete markers), it is retried with every MemStore version.
+    fn read_versions(
+        &self,
+        row: &[u8],
+        column: &[u8],
+        max_versions: usize,
+        (start_time, end_time): (Timestamp, Timestamp),
+    ) -> IoResult<Vec<(Timestamp, Vec<u8>)>> {
+        if max_versions == 0 {
+            return Ok(Vec::new());
         }
+        let deleted_at = self.family_deleted_at(row)?;
+        let sst_entries = self.sst_entries(row, Some(column))?;
+        let range_tombstones = self.range_tombstones();
+        let options = self.options();
+        let schema_max_versions = options.max_versions.unwrap_or(usize::MAX);
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        // Versions newer than the range still count towards the column family's limit
+        let max_timestamp = if options.max_versions.is_some() { Timestamp::MAX } else { end_time };
 
-        // Sort by timestamp (descending)
-        all_versions.sort_by(|a, b| b.0.cmp(&a.0));
+        let mut limit = max_versions.min(schema_max_versions);
+        loop {
+            let memstore_entries = self.memstore.lock().unwrap()
+                .cell_entries_bounded(row, column, max_timestamp, limit);
+            // Older MemStore versions than this one may have been left out
+            let boundary = (memstore_entries.len() == limit).then(|| memstore_entries[0].0.clone());
 
-        // Filter for Put values and limit to max_versions
-        let result = all_versions.into_iter()
-            .filter_map(|(ts, cell)| {
+            let sources = std::iter::once(memstore_entries.as_slice())
+                .chain(sst_entries.iter().map(|entries| entries.as_slice()));
+            let mut result = Vec::new();
+            let mut seen = 0;
+            let mut complete = boundary.is_none();
+            for (key, cell) in MergeIter::new(sources) {
+                if boundary.as_ref().is_some_and(|boundary| key < boundary) {
+                    break;
+                }
+                // This and every older version are hidden by the row's delete marker
+                if deleted_at.is_some_and(|deleted_at| key.timestamp <= deleted_at) {
+                    complete = true;
+                    break;
+                }
+                if key.timestamp > max_timestamp || range_deleted(&range_tombstones, key) {
+                    continue;
+                }
+                seen += 1;
+                if seen > schema_max_versions {
+                    complete = true;
+                    break;
+                }
+                if key.timestamp < start_time || key.timestamp > end_time || options.expired(key.timestamp, now) {
+                    continue;
+                }
                 if let CellValue::Put(v) = cell {
-                    Some((ts, v))
-                } else {
-                    None
+                    result.push((key.timestamp, v.clone()));
+                    if result.len() == max_versions {
+                        complete = true;
+                        break;
+                    }
                 }
-            })
-            .take(max_versions)
-            .collect();
+            }
+            if complete || limit == usize::MAX {
+                return Ok(result);
+            }
+            limit = usize::MAX;
+        }
+    }
 
-        Ok(result)
+    /// *Get* the value (row, column) had at timestamp ts, i.e. that of its newest version
+    /// written at or before ts. Returns Ok(None) if that version is a tombstone or if the
+    /// cell had no version yet.
+    ///
+    /// Like every read, this never returns versions that are range-deleted, beyond the
+    /// column family's `max_versions` (counting newer versions) or past its TTL.
+    #[instrument(level = "debug", skip_all, fields(cf = %self.name))]
+    pub fn get_at(&self, row: &[u8], column: &[u8], ts: Timestamp) -> IoResult<Option<Vec<u8>>> {
+        let options = self.options();
+        let schema_max_versions = options.max_versions.unwrap_or(usize::MAX);
+        // Without a version limit, only the newest MemStore version up to ts matters
+        let (max_timestamp, limit) = match options.max_versions {
+            Some(_) => (Timestamp::MAX, usize::MAX),
+            None => (ts, 1),
+        };
+        let deleted_at = self.family_deleted_at(row)?;
+        let memstore_entries = self.memstore.lock().unwrap().cell_entries_bounded(row, column, max_timestamp, limit);
+        // Read after the MemStore, so that a flush in between is seen
+        let sst_entries = self.sst_entries(row, Some(column))?;
+        let range_tombstones = self.range_tombstones();
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+
+        let sources = std::iter::once(memstore_entries.as_slice())
+            .chain(sst_entries.iter().map(|entries| entries.as_slice()));
+        let mut seen = 0;
+        for (key, cell) in MergeIter::new(sources) {
+            // A range tombstone or the row's delete marker hides every older version too
+            let deleted = range_deleted(&range_tombstones, key)
+                || deleted_at.is_some_and(|deleted_at| key.timestamp <= deleted_at);
+            if key.timestamp > ts {
+                seen += usize::from(!deleted);
+                continue;
+            }
+            seen += 1;
+            if deleted || seen > schema_max_versions || options.expired(key.timestamp, now) {
+                return Ok(None);
+            }
+            return Ok(match cell {
+                CellValue::Put(value) => Some(value.clone()),
+                CellValue::Delete(_) | CellValue::DeleteFamily => None,
+            });
+        }
+        Ok(None)
     }
 
     /// *MVCC read with time range*: return versions within a specific time range.
@@ -375,178 +2844,490 @@ impl ColumnFamily {
         start_time: Timestamp,
         end_time: Timestamp,
     ) -> IoResult<Vec<(Timestamp, Vec<u8>)>> {
-        let mut all_versions: Vec<(Timestamp, CellValue)> = Vec::new();
+        self.read_versions(row, column, max_versions, (start_time, end_time))
+    }
 
-        // Collect versions from memstore
-        {
-            let ms = self.memstore.lock().unwrap();
-            all_versions.extend(ms.get_versions_full(row, column));
+    /// Every version written between start_time and end_time (inclusive) in any row, newest
+    /// first per column. Tombstones are skipped, and versions are hidden as by
+    /// `get_versions_with_time_range`.
+    ///
+    /// SSTables whose footer shows no entry in the time range are not read, unless the
+    /// column family's `max_versions` needs their newer versions counted.
+    #[instrument(level = "debug", skip(self), fields(cf = %self.name))]
+    pub fn scan_time_range(&self, start_time: Timestamp, end_time: Timestamp) -> IoResult<RangeVersions> {
+        let options = self.options();
+        let schema_max_versions = options.max_versions.unwrap_or(usize::MAX);
+        let memstore_entries = self.memstore.lock().unwrap().scan_all();
+        // Read after the MemStore, so that a flush in between is seen
+        let pinned = self.pin_sst_files();
+        let mut readers = Vec::new();
+        for path in pinned.iter().rev() {
+            let reader = self.readers.get(path)?;
+            let footer = reader.footer();
+            // Older versions never hide newer ones, but newer ones count towards max_versions,
+            // and newer row delete markers hide them
+            let needed = footer.overlaps_time_range(start_time, end_time)
+                || (options.max_versions.is_some() && footer.time_range.is_some_and(|(_, max)| max > end_time))
+                || (footer.tombstones > 0 && footer.time_range.is_some_and(|(_, max)| max >= start_time));
+            if needed {
+                readers.push(reader);
+            }
         }
+        debug!(read = readers.len(), skipped = pinned.len() - readers.len(), "scanning time range");
+        let sst_entries: Vec<_> = readers.iter().map(|reader| reader.all_entries()).collect();
+        let range_tombstones = self.range_tombstones();
+        let now = chrono::Utc::now().timestamp_millis() as u64;
 
-        // Collect versions from SSTable files
-        let sst_list = self.sst_files.lock().unwrap();
-        // Use map and collect to handle IoResult properly
-        let readers: IoResult<Vec<_>> = sst_list.iter()
-            .map(|sst_path| SSTableReader::open(sst_path))
+        let sources: Vec<&[(EntryKey, CellValue)]> = std::iter::once(memstore_entries.as_slice())
+            .chain(sst_entries.iter().map(|entries| entries.as_ref()))
             .collect();
+        let family_deletes = family_deletes(sources.iter().flat_map(|entries| entries.iter().map(|(key, cell)| (key, cell))));
+        let mut result = RangeVersions::new();
+        // Cell being merged and its versions seen so far (delete markers included)
+        let mut current: Option<(&EntryKey, usize)> = None;
+        for (key, cell) in MergeIter::new(sources) {
+            if current.is_none_or(|(cell_key, _)| cell_key.row != key.row || cell_key.column != key.column) {
+                current = Some((key, 0));
+            }
+            let (_, seen) = current.as_mut().unwrap();
+            if range_deleted(&range_tombstones, key) || family_deleted(&family_deletes, key, cell) {
+                continue;
+            }
+            *seen += 1;
+            if *seen > schema_max_versions
+                || key.timestamp < start_time
+                || key.timestamp > end_time
+                || options.expired(key.timestamp, now)
+            {
+                continue;
+            }
+            if let CellValue::Put(value) = cell {
+                result.entry(key.row.clone()).or_default()
+                    .entry(key.column.clone()).or_default()
+                    .push((key.timestamp, value.clone()));
+            }
+        }
+        Ok(result)
+    }
 
-        // Process each reader
-        for mut reader in readers? {
-            all_versions.extend(reader.get_versions_full(row, column)?);
+    /// Execute a Get operation to retrieve data for a specific row.
+    /// This is similar to the HBase/Java Get API.
+    pub fn execute_get(&self, get: &Get) -> IoResult<RowVersions> {
+        if !self.is_selected(get.families()) {
+            return Ok(RowVersions::new());
         }
+        let row = get.row();
+        let max_versions = get.max_versions().unwrap_or(1);
 
-        // Sort by timestamp (descending)
-        all_versions.sort_by(|a, b| b.0.cmp(&a.0));
+        let time_range = get.time_range().unwrap_or((0, Timestamp::MAX));
+        self.read_columns(row, get.columns(), max_versions, time_range)
+    }
 
-        // Filter for Put values within time range and limit to max_versions
-        let result = all_versions.into_iter()
-            .filter(|(ts, _)| *ts >= start_time && *ts <= end_time)
-            .filter_map(|(ts, cell)| {
-                if let CellValue::Put(v) = cell {
-                    Some((ts, v))
-                } else {
-                    None
-                }
-            })
-            .take(max_versions)
-            .collect();
+    /// Whether a Get or Scan selecting families reads this column family.
+    fn is_selected(&self, families: &BTreeSet<String>) -> bool {
+        families.is_empty() || families.contains(&self.name)
+    }
 
-        Ok(result)
+    /// Like `read_row_versions`, for the given columns only, or every column if columns
+    /// is empty. Each selected column is looked up on its own, so the other cells of a
+    /// wide row are never read.
+    pub(crate) fn read_columns(
+        &self,
+        row: &[u8],
+        columns: &BTreeSet<Column>,
+        max_versions_per_column: usize,
+        time_range: (Timestamp, Timestamp),
+    ) -> IoResult<RowVersions> {
+        if columns.is_empty() {
+            return self.read_row_versions(row, max_versions_per_column, time_range);
+        }
+        let mut row_versions = RowVersions::new();
+        for column in columns {
+            let versions = self.read_versions(row, column, max_versions_per_column, time_range)?;
+            if !versions.is_empty() {
+                row_versions.insert(column.clone(), versions);
+            }
+        }
+        Ok(row_versions)
     }
 
-    /// Execute a Get operation to retrieve data for a specific row.
-    /// This is similar to the HBase/Java Get API.
-    pub fn execute_get(&self, get: &Get) -> IoResult<BTreeMap<Column, Vec<(Timestamp, Vec<u8>)>>> {
+    /// Execute a Get operation for a specific column.
+    /// This is a convenience method that returns only the versions for a single column.
+    pub fn execute_get_column(&self, get: &Get, column: &[u8]) -> IoResult<Vec<(Timestamp, Vec<u8>)>> {
         let row = get.row();
         let max_versions = get.max_versions().unwrap_or(1);
 
         // If time range is specified, use it to filter versions
         if let Some((start_time, end_time)) = get.time_range() {
-            // Scan the row and filter by time range
-            // Use a larger max_versions to ensure we get all versions that might be in the time range
-            let mut result = BTreeMap::new();
-            let row_data = self.scan_row_versions(row, max_versions * 10)?;
-
-            for (column, versions) in row_data {
-                let filtered_versions: Vec<(Timestamp, Vec<u8>)> = versions
-                    .into_iter()
-                    .filter(|(ts, _)| *ts >= start_time && *ts <= end_time)
-                    .take(max_versions)
-                    .collect();
+            self.get_versions_with_time_range(row, column, max_versions, start_time, end_time)
+        } else {
+            // No time range specified, just use max_versions
+            self.get_versions(row, column, max_versions)
+        }
+    }
+
+    /// Execute a Get operation for a specific column, decoding the versions as T.
+    pub fn execute_get_column_typed<T: TypedValue>(&self, get: &Get, column: &[u8]) -> IoResult<Vec<(Timestamp, T)>> {
+        self.execute_get_column(get, column)?
+            .into_iter()
+            .map(|(timestamp, value)| Ok((timestamp, T::decode(&value)?)))
+            .collect()
+    }
+
+    /// *MVCC scan*: for each column under row, return up to max_versions_per_column recent (timestamp, value).
+    /// - Tombstone versions are skipped.
+    /// - If a column has fewer than max_versions_per_column puts, you get as many as exist.
+    #[instrument(level = "debug", skip(self, row), fields(cf = %self.name))]
+    pub fn scan_row_versions(
+        &self,
+        row: &[u8],
+        max_versions_per_column: usize,
+    ) -> IoResult<RowVersions> {
+        self.read_row_versions(row, max_versions_per_column, (0, Timestamp::MAX))
+    }
+
+    /// Like `read_versions`, for every column under row: up to max_versions_per_column
+    /// puts per column whose timestamp lies in time_range (inclusive).
+    fn read_row_versions(
+        &self,
+        row: &[u8],
+        max_versions_per_column: usize,
+        (start_time, end_time): (Timestamp, Timestamp),
+    ) -> IoResult<RowVersions> {
+        if max_versions_per_column == 0 {
+            return Ok(RowVersions::new());
+        }
+        let deleted_at = self.family_deleted_at(row)?;
+        let sst_entries = self.sst_entries(row, None)?;
+        let range_tombstones = self.range_tombstones();
+        let options = self.options();
+        let schema_max_versions = options.max_versions.unwrap_or(usize::MAX);
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        // Versions newer than the range still count towards the column family's limit
+        let max_timestamp = if options.max_versions.is_some() { Timestamp::MAX } else { end_time };
+
+        let mut limit = max_versions_per_column.min(schema_max_versions);
+        loop {
+            let memstore_entries = self.memstore.lock().unwrap().scan_row_bounded(row, max_timestamp, limit);
+            // Columns whose older MemStore versions may have been left out, with the oldest copied one
+            let boundaries: HashMap<&[u8], &EntryKey> = memstore_entries
+                .chunk_by(|a, b| a.0.column == b.0.column)
+                .filter(|versions| versions.len() == limit)
+                .map(|versions| (versions[0].0.column.as_slice(), &versions[0].0))
+                .collect();
 
-                if !filtered_versions.is_empty() {
-                    result.insert(column, filtered_versions);
+            // Each column's versions arrive newest first; once a column has enough, skip the rest
+            let sources = std::iter::once(memstore_entries.as_slice())
+                .chain(sst_entries.iter().map(|entries| entries.as_slice()));
+            let mut result = RowVersions::new();
+            // Column being merged, its versions seen so far (delete markers included) and
+            // whether it needs no more versions
+            let mut current: Option<(&[u8], usize, bool)> = None;
+            let mut complete = true;
+            for (key, cell) in MergeIter::new(sources) {
+                if let Some((column, _, done)) = current {
+                    if column != key.column.as_slice() {
+                        if !done && boundaries.contains_key(column) {
+                            complete = false;
+                            break;
+                        }
+                        current = None;
+                    }
+                }
+                let (column, seen, done) = current.get_or_insert((key.column.as_slice(), 0, false));
+                if *done {
+                    continue;
+                }
+                if boundaries.get(column).is_some_and(|boundary| key < *boundary) {
+                    complete = false;
+                    break;
                 }
+                if key.timestamp > max_timestamp
+                    || range_deleted(&range_tombstones, key)
+                    || deleted_at.is_some_and(|deleted_at| key.timestamp <= deleted_at)
+                {
+                    continue;
+                }
+                *seen += 1;
+                if *seen > schema_max_versions {
+                    *done = true;
+                    continue;
+                }
+                if key.timestamp < start_time || key.timestamp > end_time || options.expired(key.timestamp, now) {
+                    continue;
+                }
+                let CellValue::Put(value) = cell else {
+                    continue;
+                };
+                let versions = result.entry(key.column.clone()).or_default();
+                versions.push((key.timestamp, value.clone()));
+                *done = versions.len() == max_versions_per_column;
+            }
+            if current.is_some_and(|(column, _, done)| !done && boundaries.contains_key(column)) {
+                complete = false;
+            }
+            if complete || limit == usize::MAX {
+                return Ok(result);
+            }
+            limit = usize::MAX;
+        }
+    }
+
+    /// Flush the MemStore into a new SSTable file, then clear the MemStore + WAL.
+    ///
+    /// The MemStore is only locked to freeze its entries and to swap them for the
+    /// SSTable: writes go on into a fresh WAL while it is built, and reads see the frozen
+    /// entries until it is live. Flushes of a column family run one at a time.
+    #[instrument(level = "debug", skip(self), fields(cf = %self.name))]
+    pub fn flush(&self) -> IoResult<()> {
+        let _flushing = self.flush_lock.lock().unwrap();
+        let frozen = {
+            let mut ms = self.memstore.lock().unwrap();
+            if ms.is_empty() {
+                return Ok(());
             }
+            ms.freeze()?
+        };
+        let started = Instant::now();
 
-            Ok(result)
-        } else {
-            // No time range specified, just use max_versions
-            self.scan_row_versions(row, max_versions)
+        // Numbered only once it is built, so that it sorts after the outputs of any
+        // compaction that started meanwhile, which cannot hold its newer entries
+        let staged = self.staging_path();
+        if let Err(err) = SSTable::create_with_bloom_filter(&staged, &frozen, self.durability(), self.options().bloom_filter) {
+            let _ = fs::remove_file(&staged);
+            self.memstore.lock().unwrap().thaw();
+            self.write_stats.lock().unwrap().failed_flushes += 1;
+            return Err(err);
         }
+
+        let mut ms = self.memstore.lock().unwrap();
+        if !ms.is_frozen(&frozen) {
+            // A restore or recovery replaced the MemStore meanwhile
+            drop(ms);
+            fs::remove_file(&staged)?;
+            return Ok(());
+        }
+        // The frozen entries are only dropped once the SSTable is durable and recorded in
+        // the manifest; a crash in between replays entries it already holds, which is harmless
+        let mut sst_files = self.sst_files.lock().unwrap();
+        let mut manifest = self.manifest.lock().unwrap();
+        let sst_path = manifest.allocate_file();
+        manifest.advance_seq(ms.last_seq());
+        manifest.advance_timestamp(self.clock.last());
+        let recorded = fs::rename(&staged, &sst_path)
+            .and_then(|_| self.durability().sync_dir(&self.path))
+            .and_then(|_| manifest.record(std::slice::from_ref(&sst_path), &[]));
+        if let Err(err) = recorded {
+            drop(manifest);
+            drop(sst_files);
+            ms.thaw();
+            self.write_stats.lock().unwrap().failed_flushes += 1;
+            return Err(err);
+        }
+        drop(manifest);
+        sst_files.push(sst_path.clone());
+        drop(sst_files);
+        match self.wal_archive.lock().unwrap().as_mut() {
+            Some(archive) => ms.release_frozen(Some(&archive.next_segment()))?,
+            None => ms.release_frozen(None)?,
+        }
+        drop(ms);
+
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        {
+            let mut stats = self.write_stats.lock().unwrap();
+            stats.flushes += 1;
+            stats.flush_ms += elapsed_ms;
+            stats.bytes_flushed += fs::metadata(&sst_path).map_or(0, |m| m.len());
+        }
+        info!(
+            sst = %sst_path.file_name().unwrap().to_string_lossy(),
+            entries = frozen.len(),
+            elapsed_ms,
+            "flushed memstore"
+        );
+        self.schedule_small_file_merge();
+        Ok(())
+    }
+
+    /// Policy for merging small SSTables after flushes; `None` if merging is disabled.
+    pub fn small_file_merge(&self) -> Option<SmallFileMergePolicy> {
+        *self.small_file_merge.lock().unwrap()
+    }
+
+    /// The running compaction, the latest finished ones and totals, for compactions and
+    /// small-file merges of this column family since the process opened it.
+    pub fn compaction_stats(&self) -> CompactionStats {
+        self.compaction_stats.lock().unwrap().clone()
+    }
+
+    /// Flush counts, durations and bytes, WAL bytes written, and writes delayed or
+    /// rejected by stalls, for this column family since the process opened it.
+    pub fn write_stats(&self) -> WriteStats {
+        *self.write_stats.lock().unwrap()
+    }
+
+    /// Limits on the I/O rate and concurrency of compactions and small-file merges.
+    pub fn compaction_throttle(&self) -> CompactionThrottle {
+        *self.compaction_throttle.lock().unwrap()
+    }
+
+    /// Limit the compactions and small-file merges of this column family, so that they
+    /// leave disk bandwidth to foreground reads (see `throttle`). Applies from the next
+    /// compaction; lives in memory only.
+    pub fn set_compaction_throttle(&self, throttle: CompactionThrottle) {
+        *self.compaction_throttle.lock().unwrap() = throttle;
+    }
+
+    /// How compactions of this column family are split to run in parallel.
+    pub fn compaction_parallelism(&self) -> CompactionParallelism {
+        *self.compaction_parallelism.lock().unwrap()
+    }
+
+    /// Split large compactions of this column family into up to `max_threads` partitions
+    /// of disjoint rows, pruned in parallel, and write up to `max_threads` of their
+    /// output SSTables at once. A `max_threads` of 1 compacts on the calling thread.
+    /// Applies from the next compaction; lives in memory only.
+    pub fn set_compaction_parallelism(&self, parallelism: CompactionParallelism) {
+        *self.compaction_parallelism.lock().unwrap() = parallelism;
+    }
+
+    /// When the MemStore is flushed without an explicit `flush`.
+    pub fn flush_policy(&self) -> FlushPolicy {
+        *self.flush_policy.lock().unwrap()
+    }
+
+    /// Set when the MemStore is flushed without an explicit `flush`. The policy lives in
+    /// memory only; reopened column families use the default policy.
+    pub fn set_flush_policy(&self, policy: FlushPolicy) {
+        *self.flush_policy.lock().unwrap() = policy;
+    }
+
+    /// Size at which the active WAL is sealed as a segment; see `MemStore`.
+    pub fn wal_segment_bytes(&self) -> u64 {
+        self.memstore.lock().unwrap().wal_segment_bytes()
     }
 
-    /// Execute a Get operation for a specific column.
-    /// This is a convenience method that returns only the versions for a single column.
-    pub fn execute_get_column(&self, get: &Get, column: &[u8]) -> IoResult<Vec<(Timestamp, Vec<u8>)>> {
-        let row = get.row();
-        let max_versions = get.max_versions().unwrap_or(1);
+    /// Seal the active WAL as a segment once it holds bytes (not persisted). Smaller
+    /// segments are deleted sooner by flushes and truncations; the default is
+    /// `DEFAULT_WAL_SEGMENT_BYTES`.
+    pub fn set_wal_segment_bytes(&self, bytes: u64) {
+        self.memstore.lock().unwrap().set_wal_segment_bytes(bytes);
+    }
 
-        // If time range is specified, use it to filter versions
-        if let Some((start_time, end_time)) = get.time_range() {
-            self.get_versions_with_time_range(row, column, max_versions, start_time, end_time)
-        } else {
-            // No time range specified, just use max_versions
-            self.get_versions(row, column, max_versions)
+    /// Number of sealed WAL segments not yet deleted by a flush.
+    pub fn wal_segment_count(&self) -> usize {
+        self.memstore.lock().unwrap().segment_count()
+    }
+
+    /// Start a background flush unless one is scheduled or running already.
+    fn schedule_flush(&self) {
+        if self.flush_scheduled.swap(true, Ordering::SeqCst) {
+            return;
         }
+        let cf = self.clone();
+        thread::spawn(move || {
+            if let Err(err) = cf.flush() {
+                error!(cf = %cf.name, error = %err, "background flush failed");
+            }
+            cf.flush_scheduled.store(false, Ordering::SeqCst);
+        });
     }
 
-    /// *MVCC scan*: for each column under row, return up to max_versions_per_column recent (timestamp, value).
-    /// - Tombstone versions are skipped.
-    /// - If a column has fewer than max_versions_per_column puts, you get as many as exist.
-    pub fn scan_row_versions(
-        &self,
-        row: &[u8],
-        max_versions_per_column: usize,
-    ) -> IoResult<BTreeMap<Column, Vec<(Timestamp, Vec<u8>)>>> {
-        let mut per_column: BTreeMap<Column, Vec<(Timestamp, CellValue)>> = BTreeMap::new();
-        {
-            let sst_list = self.sst_files.lock().unwrap();
-            // Use map and collect to handle IoResult properly
-            let readers: IoResult<Vec<_>> = sst_list.iter()
-                .map(|sst_path| SSTableReader::open(sst_path))
-                .collect();
+    /// Set the policy for merging small SSTables after flushes, or disable merging with `None`.
+    /// The policy lives in memory only; reopened column families use the default policy.
+    pub fn set_small_file_merge(&self, policy: Option<SmallFileMergePolicy>) {
+        *self.small_file_merge.lock().unwrap() = policy;
+    }
 
-            // Process each reader
-            for mut reader in readers? {
-                // Use iterator methods to process scan_row_full results
-                reader.scan_row_full(row)?.into_iter().for_each(|(col, ts, cell)| {
-                    per_column.entry(col.clone()).or_default().push((ts, cell.clone()));
-                });
-            }
+    /// Start a background merge of small SSTables if the policy's trigger is met
+    /// and no merge is running yet.
+    fn schedule_small_file_merge(&self) {
+        let Some(policy) = self.small_file_merge() else {
+            return;
+        };
+        let sst_files = self.sst_files.lock().unwrap().clone();
+        if !policy.select(&sst_files).is_ok_and(|run| !run.is_empty()) {
+            return;
         }
-
-        {
-            let ms = self.memstore.lock().unwrap();
-            // Use iterator methods to process memstore entries
-            ms.scan_row_full(row).into_iter().for_each(|(entry_key, cell)| {
-                per_column
-                    .entry(entry_key.column.clone())
-                    .or_default()
-                    .push((entry_key.timestamp, cell.clone()));
-            });
+        if self.merging.swap(true, Ordering::SeqCst) {
+            return;
         }
 
-        // Process each column's versions using iterators
-        let result: BTreeMap<Column, Vec<(Timestamp, Vec<u8>)>> = per_column
-            .into_iter()
-            .filter_map(|(col, mut versions)| {
-                // Sort by timestamp (descending)
-                versions.sort_by(|a, b| b.0.cmp(&a.0));
-
-                // Filter for Put values and limit to max_versions_per_column
-                let kept: Vec<(Timestamp, Vec<u8>)> = versions.into_iter()
-                    .filter_map(|(ts, cell)| {
-                        if let CellValue::Put(v) = cell {
-                            Some((ts, v))
-                        } else {
-                            None
-                        }
-                    })
-                    .take(max_versions_per_column)
-                    .collect();
-
-                // Only include non-empty columns
-                if !kept.is_empty() {
-                    Some((col.clone(), kept))
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        Ok(result)
+        let cf = self.clone();
+        thread::spawn(move || {
+            if let Err(err) = cf.merge_small_sstables() {
+                error!(cf = %cf.name, error = %err, "small SSTable merge failed");
+            }
+            cf.merging.store(false, Ordering::SeqCst);
+        });
     }
 
-    /// Flush the MemStore into a new SSTable file, then clear the MemStore + WAL.
-    pub fn flush(&self) -> IoResult<()> {
-        let mut ms = self.memstore.lock().unwrap();
-        if ms.is_empty() {
-            return Ok(());
+    /// Merge the newest SSTables into one if the small-file merge policy's trigger is met.
+    /// Unlike a compaction, all versions and tombstones are kept. Returns the number of
+    /// SSTables merged, or 0 if there was nothing to do.
+    #[instrument(level = "debug", skip(self), fields(cf = %self.name))]
+    pub fn merge_small_sstables(&self) -> IoResult<usize> {
+        let Some(policy) = self.small_file_merge() else {
+            return Ok(0);
+        };
+        if self.is_frozen() {
+            return Ok(0);
         }
-
-        let sst_seq = {
-            let existing = self.sst_files.lock().unwrap();
-            existing.len() + 1
+        let compacting = self.compaction_lock.lock().unwrap();
+        let inputs = policy.select(&self.sst_files.lock().unwrap().clone())?;
+        let Some(newest) = inputs.last() else {
+            return Ok(0);
         };
-        let sst_name = format!("{:010}.sst", sst_seq as u64);
-        let sst_path = self.path.join(&sst_name);
+        let throttle = self.compaction_throttle();
+        let _slot = CompactionSlot::acquire(throttle.max_concurrent);
+        let mut limiter = RateLimiter::new(throttle.max_bytes_per_sec);
+        let tracker = CompactionTracker::start(&self.compaction_stats, CompactionKind::SmallFileMerge, &inputs)?;
+        let started = Instant::now();
 
-        let entries = ms.drain_all()?;
-        SSTable::create(&sst_path, &entries)?;
+        let _pinned = self.file_refs.pin(inputs.clone());
+        let mut merged: Vec<Entry> = Vec::new();
+        for path in &inputs {
+            limiter.consume(fs::metadata(path)?.len());
+            let reader = self.readers.get(path)?;
+            merged.extend(reader.scan_all()?.into_iter().map(|(key, value)| Entry { key, value }));
+        }
+        merged.sort_by(|a, b| a.key.cmp(&b.key));
 
-        self.sst_files.lock().unwrap().push(sst_path);
-        Ok(())
+        // The merged file takes the place of the newest input, so it keeps its position
+        // relative to SSTables flushed in the meantime
+        let tmp = newest.with_extension("tmp");
+        SSTable::create_paced(&tmp, &merged, self.durability(), self.options().bloom_filter, |bytes| limiter.consume(bytes))?;
+        let bytes_written = fs::metadata(&tmp)?.len();
+
+        let mut list_guard = self.sst_files.lock().unwrap();
+        if !inputs.iter().all(|path| list_guard.contains(path)) {
+            // A restore or split replaced the SSTables while merging
+            drop(list_guard);
+            fs::remove_file(&tmp)?;
+            return Ok(0);
+        }
+        // Until the edit is recorded, the merged file only duplicates the other inputs
+        fs::rename(&tmp, newest)?;
+        self.durability().sync_dir(&self.path)?;
+        let merged_away = &inputs[..inputs.len() - 1];
+        self.manifest.lock().unwrap().record(&[], merged_away)?;
+        list_guard.retain(|path| path == newest || !inputs.contains(path));
+        self.delete_obsolete(merged_away);
+        self.readers.retain(&list_guard);
+
+        info!(
+            inputs = inputs.len(),
+            output = %newest.display(),
+            entries = merged.len(),
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "merged small SSTables"
+        );
+        let record = tracker.finish(bytes_written, 1, merged.len(), merged.len(), DroppedEntries::default());
+        drop(list_guard);
+        drop(compacting);
+        self.observe_compaction(&record);
+        Ok(inputs.len())
     }
 
 
@@ -561,9 +3342,10 @@ impl ColumnFamily {
     /// Run a major compaction that merges all SSTables into one.
     /// This is more aggressive than the default compact() method, which only does minor compaction.
     pub fn major_compact(&self) -> IoResult<()> {
-        let mut options = CompactionOptions::default();
-        options.compaction_type = CompactionType::Major;
-        self.compact_with_options(options)
+        self.compact_with_options(CompactionOptions {
+            compaction_type: CompactionType::Major,
+            ..CompactionOptions::default()
+        })
     }
 
     /// Run a compaction with version cleanup, keeping only the specified number of versions.
@@ -571,9 +3353,10 @@ impl ColumnFamily {
     /// # Arguments
     /// * `max_versions` - Maximum number of versions to keep per cell
     pub fn compact_with_max_versions(&self, max_versions: usize) -> IoResult<()> {
-        let mut options = CompactionOptions::default();
-        options.max_versions = Some(max_versions);
-        self.compact_with_options(options)
+        self.compact_with_options(CompactionOptions {
+            max_versions: Some(max_versions),
+            ..CompactionOptions::default()
+        })
     }
 
     /// Run a compaction with age-based cleanup, removing versions older than the specified age.
@@ -581,9 +3364,10 @@ impl ColumnFamily {
     /// # Arguments
     /// * `max_age_ms` - Maximum age of versions to keep (in milliseconds)
     pub fn compact_with_max_age(&self, max_age_ms: u64) -> IoResult<()> {
-        let mut options = CompactionOptions::default();
-        options.max_age_ms = Some(max_age_ms);
-        self.compact_with_options(options)
+        self.compact_with_options(CompactionOptions {
+            max_age_ms: Some(max_age_ms),
+            ..CompactionOptions::default()
+        })
     }
 
     /// Get a value with a filter applied
@@ -615,64 +3399,407 @@ impl ColumnFamily {
         &self,
         row: &[u8],
         filter_set: &FilterSet,
-    ) -> IoResult<BTreeMap<Column, Vec<(Timestamp, Vec<u8>)>>> {
+    ) -> IoResult<RowVersions> {
         let max_versions = filter_set.max_versions.unwrap_or(usize::MAX);
-        let mut result = self.scan_row_versions(row, max_versions)?;
+        let result = self.scan_row_versions(row, max_versions)?;
+        Ok(apply_filter_set(filter_set, result))
+    }
 
-        if !filter_set.column_filters.is_empty() {
-            let filter_columns: Vec<Vec<u8>> = filter_set.column_filters
-                .iter()
-                .map(|cf| cf.column.clone())
-                .collect();
+    /// Scan multiple rows with a filter set applied
+    /// 
+    /// # Arguments
+    /// * `start_row` - The starting row key (inclusive)
+    /// * `end_row` - The ending row key (inclusive)
+    /// * `filter_set` - The filter set to apply
+    pub fn scan_with_filter(
+        &self,
+        start_row: &[u8],
+        end_row: &[u8],
+        filter_set: &FilterSet,
+    ) -> IoResult<RangeVersions> {
+        let mut scan = Scan::new(start_row.to_vec(), end_row.to_vec());
+        scan.set_max_versions(filter_set.max_versions.unwrap_or(usize::MAX)).set_filter(filter_set.clone());
+        self.execute_scan(&scan)
+    }
 
-            result.retain(|column, _| filter_columns.contains(column));
+    /// Like `scan_with_filter`, for analytical scans of large ranges: the SSTables
+    /// overlapping the range are read concurrently and the merged rows filtered in
+    /// parallel, on a pool of at most max_threads threads.
+    ///
+    /// Unlike `scan_with_filter`, which reads one row at a time, the whole range is held
+    /// in memory while it is merged.
+    #[instrument(level = "debug", skip(self, filter_set), fields(cf = %self.name))]
+    pub fn par_scan_with_filter(
+        &self,
+        start_row: &[u8],
+        end_row: &[u8],
+        filter_set: &FilterSet,
+        max_threads: usize,
+    ) -> IoResult<RangeVersions> {
+        self.observe_scan(start_row, end_row)?;
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(max_threads.max(1))
+            .thread_name(|i| format!("redbase-scan-{}", i))
+            .build()
+            .map_err(Error::other)?;
+        let max_versions = filter_set.max_versions.unwrap_or(usize::MAX);
+        if max_versions == 0 {
+            return Ok(RangeVersions::new());
         }
+        let options = self.options();
+        let schema_max_versions = options.max_versions.unwrap_or(usize::MAX);
 
-        for column_filter in &filter_set.column_filters {
-            if let Some(versions) = result.get_mut(&column_filter.column) {
-                let filtered_versions: Vec<(Timestamp, Vec<u8>)> = versions
-                    .iter()
-                    .filter(|(ts, value)| {
-                        filter_set.timestamp_matches(*ts) && column_filter.filter.matches(value)
-                    })
-                    .cloned()
-                    .collect();
+        let memstore_entries = self.memstore.lock().unwrap().scan_range(start_row, end_row);
+        // Pinned after the MemStore is read, so that a flush in between is seen
+        let pinned = self.pin_sst_files();
+        let mut readers = Vec::new();
+        for path in pinned.iter().rev() {
+            let reader = self.readers.get(path)?;
+            if reader.footer().overlaps_rows(start_row, end_row) {
+                readers.push(reader);
+            }
+        }
+        let sst_entries: Vec<_> = pool.install(|| {
+            readers.par_iter().map(|reader| reader.range_entries(start_row, end_row)).collect()
+        });
+        let range_tombstones = self.range_tombstones();
+        let now = chrono::Utc::now().timestamp_millis() as u64;
 
-                if filtered_versions.is_empty() {
-                    result.remove(&column_filter.column);
-                } else {
-                    *versions = filtered_versions;
-                }
+        // Versions visible to `scan_row_versions`, row by row
+        let sources: Vec<&[(EntryKey, CellValue)]> = std::iter::once(memstore_entries.as_slice())
+            .chain(sst_entries.iter().map(|entries| entries.as_ref()))
+            .collect();
+        let family_deletes = family_deletes(sources.iter().flat_map(|entries| entries.iter().map(|(key, cell)| (key, cell))));
+        let mut rows: Vec<(RowKey, RowVersions)> = Vec::new();
+        // Cell being merged and its versions seen so far (delete markers included)
+        let mut current: Option<(&EntryKey, usize)> = None;
+        for (key, cell) in MergeIter::new(sources) {
+            if current.is_none_or(|(cell_key, _)| cell_key.row != key.row || cell_key.column != key.column) {
+                current = Some((key, 0));
+            }
+            let (_, seen) = current.as_mut().unwrap();
+            if range_deleted(&range_tombstones, key) || family_deleted(&family_deletes, key, cell) {
+                continue;
+            }
+            *seen += 1;
+            if *seen > schema_max_versions || options.expired(key.timestamp, now) {
+                continue;
+            }
+            let CellValue::Put(value) = cell else {
+                continue;
+            };
+            if rows.last().is_none_or(|(row, _)| *row != key.row) {
+                rows.push((key.row.clone(), RowVersions::new()));
+            }
+            let versions = rows.last_mut().unwrap().1.entry(key.column.clone()).or_default();
+            if versions.len() < max_versions {
+                versions.push((key.timestamp, value.clone()));
             }
         }
+        debug!(ssts = readers.len(), rows = rows.len(), "merged parallel scan");
 
-        Ok(result)
+        let filtered: Vec<_> = pool.install(|| {
+            rows.into_par_iter()
+                .map(|(row, versions)| (row, apply_filter_set(filter_set, versions)))
+                .filter(|(_, versions)| !versions.is_empty())
+                .collect()
+        });
+        Ok(filtered.into_iter().collect())
     }
 
-    /// Scan multiple rows with a filter set applied
-    /// 
+    /// Stream the rows in [start_row, end_row], reading one row at a time with up to
+    /// max_versions versions per column. See `scan::ScanExt` for adapters over the rows.
+    pub fn scanner(&self, start_row: &[u8], end_row: &[u8], max_versions: usize) -> IoResult<Scanner> {
+        let mut scan = Scan::new(start_row.to_vec(), end_row.to_vec());
+        scan.set_max_versions(max_versions);
+        self.open_scanner(&scan)
+    }
+
+    /// Stream the rows of a Scan operation, reading one row at a time. Rows left
+    /// without any cell by its column selection, time range or filter are skipped.
+    /// Fails with `InvalidInput` for a raw scan, whose delete markers only `scan_rows`
+    /// can return.
+    #[instrument(level = "debug", skip_all, fields(cf = %self.name))]
+    pub fn open_scanner(&self, scan: &Scan) -> IoResult<Scanner> {
+        if scan.is_raw() {
+            return Err(Error::new(ErrorKind::InvalidInput, "Raw scans return delete markers; use scan_rows"));
+        }
+        self.observe_scan(scan.start_row(), scan.stop_row())?;
+        let mut rows = match self.is_selected(scan.families()) {
+            true => self.get_row_keys_in_range(scan.start_row(), scan.stop_row())?,
+            false => Vec::new(),
+        };
+        if scan.is_reversed() {
+            rows.reverse();
+        }
+        Ok(Scanner::new(self.clone(), rows, scan, None))
+    }
+
+    /// Execute a Scan operation: the rows in its range that have any of the selected
+    /// columns, with up to its maximum number of versions (1 by default) of each. Its
+    /// caching, batch and order only matter to `open_scanner`.
+    pub fn execute_scan(&self, scan: &Scan) -> IoResult<RangeVersions> {
+        let mut rows = RangeVersions::new();
+        for row in self.open_scanner(scan)? {
+            let row = row?;
+            rows.entry(row.row).or_default().extend(row.columns);
+        }
+        Ok(rows)
+    }
+
+    /// Execute a Get operation, returning its cells as a `Row`.
+    pub fn get_row(&self, get: &Get) -> IoResult<Row> {
+        Ok(Row::from_versions(get.row().clone(), self.execute_get(get)?))
+    }
+
+    /// Execute a Scan operation, returning its rows in scan order as `Row`s; a row split
+    /// by the scan's batch comes back as several. A raw scan also returns delete
+    /// markers and the versions they hide, up to its maximum number of versions (1 by
+    /// default) per column, delete markers included; it fails with `InvalidInput` if
+    /// the scan has a filter set, which only applies to values.
+    pub fn scan_rows(&self, scan: &Scan) -> IoResult<Vec<Row>> {
+        if !scan.is_raw() {
+            return self.open_scanner(scan)?.map(|row| row.map(Row::from)).collect();
+        }
+        if scan.filter().is_some() {
+            return Err(Error::new(ErrorKind::InvalidInput, "Raw scans cannot have a filter set"));
+        }
+        self.observe_scan(scan.start_row(), scan.stop_row())?;
+        if !self.is_selected(scan.families()) {
+            return Ok(Vec::new());
+        }
+        let (start_row, stop_row) = (scan.start_row().as_slice(), scan.stop_row().as_slice());
+        let max_versions = scan.max_versions().unwrap_or(1);
+        let (start_time, end_time) = scan.time_range().unwrap_or((0, Timestamp::MAX));
+
+        let memstore_entries = self.memstore.lock().unwrap().scan_range(start_row, stop_row);
+        // Pinned after the MemStore is read, so that a flush in between is seen
+        let pinned = self.pin_sst_files();
+        let mut readers = Vec::new();
+        for path in pinned.iter().rev() {
+            let reader = self.readers.get(path)?;
+            if reader.footer().overlaps_rows(start_row, stop_row) {
+                readers.push(reader);
+            }
+        }
+        let sst_entries: Vec<_> = readers.iter().map(|reader| reader.range_entries(start_row, stop_row)).collect();
+        let range_tombstones = self.range_tombstones();
+
+        let sources = std::iter::once(memstore_entries.as_slice())
+            .chain(sst_entries.iter().map(|entries| entries.as_ref()));
+        // Rows come out of the merge last first, and the versions of a cell newest first
+        let mut rows: Vec<(RowKey, Vec<Cell>)> = Vec::new();
+        let mut current: Option<(&EntryKey, usize)> = None;
+        for (key, cell) in MergeIter::new(sources) {
+            if !scan.columns().is_empty() && !scan.columns().contains(&key.column) {
+                continue;
+            }
+            if key.timestamp < start_time || key.timestamp > end_time || range_deleted(&range_tombstones, key) {
+                continue;
+            }
+            if current.is_none_or(|(cell_key, _)| cell_key.row != key.row || cell_key.column != key.column) {
+                current = Some((key, 0));
+            }
+            let (_, returned) = current.as_mut().unwrap();
+            if *returned == max_versions {
+                continue;
+            }
+            *returned += 1;
+            let cell = match cell {
+                CellValue::Put(value) => Cell::put(key.row.clone(), key.column.clone(), key.timestamp, value.clone()),
+                CellValue::Delete(_) | CellValue::DeleteFamily => Cell::delete(key.row.clone(), key.column.clone(), key.timestamp),
+            };
+            if rows.last().is_none_or(|(row, _)| *row != key.row) {
+                rows.push((key.row.clone(), Vec::new()));
+            }
+            rows.last_mut().unwrap().1.push(cell);
+        }
+        if !scan.is_reversed() {
+            rows.reverse();
+        }
+        Ok(rows.into_iter().map(|(row, cells)| Row::new(row, cells)).collect())
+    }
+
+    /// Continue a scan of the rows up to end_row at the position of token, as returned
+    /// by `Scanner::resume_token` or in a `ScanBatch`.
+    pub fn resume_scanner(&self, token: &ResumeToken, end_row: &[u8], max_versions: usize) -> IoResult<Scanner> {
+        self.observe_scan(&token.row, end_row)?;
+        let rows = self.get_row_keys_in_range(&token.row, end_row)?;
+        let mut scan = Scan::new(token.row.clone(), end_row.to_vec());
+        scan.set_max_versions(max_versions);
+        Ok(Scanner::new(self.clone(), rows, &scan, Some(token.clone())))
+    }
+
+    /// Stream the rows whose key starts with prefix, reading one row at a time with up
+    /// to max_versions versions per column. SSTables whose prefix bloom filter, if the
+    /// column family writes them, rules out prefix are not read.
+    pub fn scan_prefix(&self, prefix: &[u8], max_versions: usize) -> IoResult<Scanner> {
+        // The rows with prefix sort before its successor, or anywhere up to the last row
+        // for a prefix of 0xFF bytes only
+        let end_row = match prefix.iter().rposition(|&byte| byte < 0xFF) {
+            Some(i) => [&prefix[..i], &[prefix[i] + 1]].concat(),
+            None => self.last_row_key()?.filter(|last| last.as_slice() > prefix).unwrap_or_else(|| prefix.to_vec()),
+        };
+        self.observe_scan(prefix, &end_row)?;
+        let rows = self.row_keys_with_prefix(prefix, &end_row)?;
+        let mut scan = Scan::new(prefix.to_vec(), end_row);
+        scan.set_max_versions(max_versions);
+        Ok(Scanner::new(self.clone(), rows, &scan, None))
+    }
+
+    /// Scan the rows in [start_row, end_row] into one batch, truncated at
+    /// `options.max_response_bytes`. Continue a truncated scan with `resume_scan` and
+    /// the batch's continuation token.
+    pub fn scan_with_options(&self, start_row: &[u8], end_row: &[u8], options: &ScanOptions) -> IoResult<ScanBatch> {
+        self.resume_scan(&ResumeToken::at_row(start_row), end_row, options)
+    }
+
+    /// Like `scan_with_options`, starting at the position of token. A batch holds at
+    /// least one version: a single row larger than `options.max_response_bytes` is split
+    /// between columns or versions, and the next batch continues inside it. Once
+    /// `options.deadline` passes, the batch ends after the current row and is returned
+    /// as a partial result, with `timed_out` set.
+    #[instrument(level = "debug", skip_all, fields(cf = %self.name))]
+    pub fn resume_scan(&self, token: &ResumeToken, end_row: &[u8], options: &ScanOptions) -> IoResult<ScanBatch> {
+        let mut batch = ScanBatch::default();
+        let mut bytes = 0usize;
+        let mut scanner = self.resume_scanner(token, end_row, options.max_versions)?;
+        loop {
+            if !batch.rows.is_empty() && check_deadline(options.deadline, "Scan").is_err() {
+                batch.continuation = scanner.resume_token();
+                batch.timed_out = batch.continuation.is_some();
+                debug!(rows = batch.rows.len(), bytes, timed_out = batch.timed_out, "scan batch reached its deadline");
+                break;
+            }
+            let Some(row) = scanner.next() else { break };
+            let mut row = row?;
+            let size = row.estimated_bytes();
+            match options.max_response_bytes {
+                Some(max) if !batch.rows.is_empty() && bytes + size > max => {
+                    debug!(rows = batch.rows.len(), bytes, "truncated scan batch");
+                    batch.continuation = Some(ResumeToken::at_row(&row.row));
+                    break;
+                }
+                Some(max) if size > max => {
+                    if let Some(after) = row.truncate_to(max) {
+                        debug!(row_bytes = size, "split scanned row");
+                        batch.continuation = Some(ResumeToken { row: row.row.clone(), after: Some(after) });
+                        batch.rows.insert(row.row, row.columns);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            bytes += size;
+            batch.rows.insert(row.row, row.columns);
+        }
+        Ok(batch)
+    }
+
+    /// Count the rows in [start_row, end_row] that have at least one visible cell.
+    ///
+    /// Without a filter set this is a key-only pass: cells are walked in key order and
+    /// each row stops at its first column whose newest version is a Put, so no values
+    /// are copied and no per-row maps are built. With a filter set, value predicates
+    /// must be evaluated, so each row is scanned with `scan_row_with_filter`.
+    ///
     /// # Arguments
     /// * `start_row` - The starting row key (inclusive)
     /// * `end_row` - The ending row key (inclusive)
-    /// * `filter_set` - The filter set to apply
-    pub fn scan_with_filter(
+    /// * `filter_set` - Optional filter set a row must match to be counted
+    pub fn count_rows(
         &self,
         start_row: &[u8],
         end_row: &[u8],
-        filter_set: &FilterSet,
-    ) -> IoResult<BTreeMap<RowKey, BTreeMap<Column, Vec<(Timestamp, Vec<u8>)>>>> {
-        let mut result = BTreeMap::new();
+        filter_set: Option<&FilterSet>,
+    ) -> IoResult<u64> {
+        self.count_rows_within(start_row, end_row, filter_set, None)
+    }
+
+    /// Like `count_rows`, but give up with `TimedOut` once deadline has passed.
+    pub fn count_rows_until(
+        &self,
+        start_row: &[u8],
+        end_row: &[u8],
+        filter_set: Option<&FilterSet>,
+        deadline: Instant,
+    ) -> IoResult<u64> {
+        self.count_rows_within(start_row, end_row, filter_set, Some(deadline))
+    }
+
+    #[instrument(level = "debug", skip_all, fields(cf = %self.name))]
+    fn count_rows_within(
+        &self,
+        start_row: &[u8],
+        end_row: &[u8],
+        filter_set: Option<&FilterSet>,
+        deadline: Option<Instant>,
+    ) -> IoResult<u64> {
+        if let Some(fs) = filter_set {
+            let mut count = 0;
+            for row_key in self.get_row_keys_in_range(start_row, end_row)? {
+                check_deadline(deadline, "Count")?;
+                if !self.scan_row_with_filter(&row_key, fs)?.is_empty() {
+                    count += 1;
+                }
+            }
+            return Ok(count);
+        }
 
-        let mut row_keys = self.get_row_keys_in_range(start_row, end_row)?;
+        let range_tombstones = self.range_tombstones();
+        let options = self.options();
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        let (mem_keys, mut deleted_rows) = {
+            let ms = self.memstore.lock().unwrap();
+            (ms.scan_range_keys(start_row, end_row), family_deletes(ms.range_iter(start_row, end_row)))
+        };
 
-        for row_key in row_keys {
-            let row_result = self.scan_row_with_filter(&row_key, filter_set)?;
-            if !row_result.is_empty() {
-                result.insert(row_key, row_result);
+        let mut readers: Vec<Arc<SSTableReader>> = self.pin_sst_files()
+            .iter()
+            .map(|path| self.readers.get(path))
+            .collect::<IoResult<_>>()?;
+        readers.retain(|reader| reader.footer().overlaps_rows(start_row, end_row));
+
+        // (key, is_put) for every cell version in range, sorted so that the last
+        // version of each (row, column) is its newest
+        let ranges: Vec<_> = readers.iter().map(|r| r.range_entries(start_row, end_row)).collect();
+        for (row, ts) in family_deletes(ranges.iter().flat_map(|entries| entries.iter().map(|(key, cell)| (key, cell)))) {
+            let deleted_at = deleted_rows.entry(row).or_default();
+            *deleted_at = (*deleted_at).max(ts);
+        }
+        let mut cells: Vec<(&EntryKey, bool)> = ranges.iter()
+            .flat_map(|entries| {
+                entries
+                    .iter()
+                    .map(|(k, v)| (k, matches!(v, CellValue::Put(_))))
+            })
+            .chain(mem_keys.iter().map(|(k, is_put)| (k, *is_put)))
+            .collect();
+        cells.sort_by(|a, b| a.0.cmp(b.0));
+
+        let mut count = 0;
+        let mut counted_row: Option<&[u8]> = None;
+        for (i, (key, is_put)) in cells.iter().enumerate() {
+            if i % 4096 == 0 {
+                check_deadline(deadline, "Count")?;
+            }
+            if counted_row == Some(key.row.as_slice()) {
+                continue;
+            }
+            let newest_of_column = cells.get(i + 1)
+                .is_none_or(|(next, _)| next.row != key.row || next.column != key.column);
+            if newest_of_column
+                && *is_put
+                && !range_deleted(&range_tombstones, key)
+                && deleted_rows.get(key.row.as_slice()).is_none_or(|&deleted_at| key.timestamp > deleted_at)
+                && !options.expired(key.timestamp, now)
+            {
+                count += 1;
+                counted_row = Some(key.row.as_slice());
             }
         }
 
-        Ok(result)
+        Ok(count)
     }
 
     /// Helper method to get all row keys in a range
@@ -687,9 +3814,11 @@ impl ColumnFamily {
             }
         }
 
-        let sst_list = self.sst_files.lock().unwrap();
-        for sst_path in sst_list.iter() {
-            let mut reader = SSTableReader::open(sst_path)?;
+        for sst_path in self.pin_sst_files().iter() {
+            let reader = self.readers.get(sst_path)?;
+            if !reader.footer().overlaps_rows(start_row, end_row) {
+                continue;
+            }
             for row_key in reader.get_row_keys_in_range(start_row, end_row)? {
                 row_keys.insert(row_key, ());
             }
@@ -698,6 +3827,205 @@ impl ColumnFamily {
         Ok(row_keys.into_keys().collect())
     }
 
+    /// The row keys starting with prefix, all of which are in [prefix, end_row], skipping
+    /// the SSTables that cannot hold any.
+    fn row_keys_with_prefix(&self, prefix: &[u8], end_row: &[u8]) -> IoResult<Vec<RowKey>> {
+        let mut row_keys = BTreeSet::new();
+        row_keys.extend(self.memstore.lock().unwrap().get_row_keys_in_range(prefix, end_row));
+        for sst_path in self.pin_sst_files().iter() {
+            let reader = self.readers.get(sst_path)?;
+            if reader.footer().may_contain_prefix(prefix) {
+                row_keys.extend(reader.get_row_keys_in_range(prefix, end_row)?);
+            }
+        }
+        row_keys.retain(|row| row.starts_with(prefix));
+        Ok(row_keys.into_iter().collect())
+    }
+
+    /// The largest row key stored in the MemStore or an SSTable, counting rows that are
+    /// deleted but not compacted away yet; `None` if the column family is empty.
+    pub(crate) fn last_row_key(&self) -> IoResult<Option<RowKey>> {
+        let mut last = self.memstore.lock().unwrap().last_row().map(<[u8]>::to_vec);
+        for sst_path in self.pin_sst_files().iter() {
+            let reader = self.readers.get(sst_path)?;
+            if let Some((_, max)) = &reader.footer().row_range {
+                if last.as_ref().is_none_or(|last| max > last) {
+                    last = Some(max.clone());
+                }
+            }
+        }
+        Ok(last)
+    }
+
+    /// Every stored cell version, including tombstones, in EntryKey order.
+    /// Where the same key exists more than once, the MemStore wins over SSTables
+    /// and newer SSTables win over older ones. Cells deleted by a range tombstone
+    /// are left out.
+    pub fn raw_entries(&self) -> IoResult<Vec<Entry>> {
+        let range_tombstones = self.range_tombstones();
+        let mut merged = BTreeMap::new();
+        for sst_path in self.pin_sst_files().iter() {
+            merged.extend(self.readers.get(sst_path)?.scan_all()?);
+        }
+        merged.extend(self.memstore.lock().unwrap().scan_all());
+
+        Ok(merged.into_iter()
+            .filter(|(key, _)| !range_deleted(&range_tombstones, key))
+            .map(|(key, value)| Entry { key, value })
+            .collect())
+    }
+
+    /// Write entries with their own timestamps, e.g. when importing data.
+    /// Goes through the regular write path (validators, changelog, WAL and MemStore);
+    /// nothing is written if any Put fails validation. The entries are appended under
+    /// one MemStore lock with a single WAL write, and the MemStore is flushed at most
+    /// once, afterwards.
+    pub(crate) fn write_entries(&self, entries: impl IntoIterator<Item = Entry>) -> IoResult<()> {
+        self.write_request(entries, None)
+    }
+
+    /// Like `write_entries`, for a write with a client-supplied request ID: if a write
+    /// with the same ID was already applied, e.g. when a client retries after a timeout,
+    /// nothing is written and Ok is returned as the first time. The MemStore remembers
+    /// the IDs of the last `REQUEST_WINDOW` writes that had one, across restarts.
+    pub(crate) fn write_request(&self, entries: impl IntoIterator<Item = Entry>, request_id: Option<String>) -> IoResult<()> {
+        let entries: Vec<Entry> = entries.into_iter().collect();
+        self.validate_entries(&entries)?;
+        self.append_validated(vec![(request_id, entries)])
+    }
+
+    /// Like `write_request`, but without blocking the caller: the entries are queued to
+    /// this column family's writer thread (see `writer`), which is started on first use
+    /// and applies queued writes in groups. Each write is validated on its own, so a
+    /// rejected write does not fail the others of its group.
+    pub(crate) fn submit_entries(&self, entries: Vec<Entry>, request_id: Option<String>) -> impl Future<Output = IoResult<()>> + Send + 'static {
+        let writer = self.writer.get_or_init(|| {
+            let cf = self.clone();
+            WriteQueue::start(&self.name, move |group| cf.write_group(group))
+        });
+        writer.submit(entries, request_id)
+    }
+
+    /// Apply a group of queued writes, answering each of them.
+    fn write_group(&self, group: Vec<WriteRequest>) {
+        let mut writes = Vec::new();
+        let mut replies = Vec::with_capacity(group.len());
+        for request in group {
+            match self.validate_entries(&request.entries) {
+                Ok(()) => {
+                    writes.push((request.request_id, request.entries));
+                    replies.push(request.reply);
+                }
+                Err(e) => {
+                    let _ = request.reply.send(Err(e));
+                }
+            }
+        }
+        if replies.is_empty() {
+            return;
+        }
+
+        let result = self.append_validated(writes);
+        if let Err(e) = &result {
+            warn!(cf = %self.name, writes = replies.len(), error = %e, "group write failed");
+        }
+        for reply in replies {
+            // The caller may have stopped waiting; its write is applied regardless
+            let _ = reply.send(result.as_ref().map(|_| ()).map_err(copy_error));
+        }
+    }
+
+    /// Check entries against the size limits, then every Put against the validators of
+    /// its column, then every entry against the `pre_put` or `pre_delete` hooks.
+    fn validate_entries(&self, entries: &[Entry]) -> IoResult<()> {
+        self.options().limits.check(entries)?;
+        entries.iter().try_for_each(|entry| match &entry.value {
+            CellValue::Put(value) => self.validate(&entry.key.row, &entry.key.column, value),
+            CellValue::Delete(_) | CellValue::DeleteFamily => Ok(()),
+        })?;
+        let observers = self.observers();
+        entries.iter().try_for_each(|entry| {
+            observers.iter().try_for_each(|observer| match &entry.value {
+                CellValue::Put(_) => observer.pre_put(self, entry),
+                CellValue::Delete(_) | CellValue::DeleteFamily => observer.pre_delete(self, entry),
+            })
+        })
+    }
+
+    /// Check a part of a row mutation before it is logged: the checks of
+    /// `write_entries`, the write throttle, and that the column family is not frozen.
+    pub(crate) fn prepare_mutation(&self, entries: &[Entry]) -> IoResult<()> {
+        if self.is_frozen() {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!("Column family {} is frozen", self.name),
+            ));
+        }
+        self.validate_entries(entries)?;
+        self.throttle_write()
+    }
+
+    /// Apply the part of a logged row mutation (see `mutation`), which was checked by
+    /// `prepare_mutation` before it was logged, unless it was applied before.
+    pub(crate) fn apply_mutation(&self, entries: Vec<Entry>, mutation_id: String) -> IoResult<()> {
+        self.append_writes(vec![(Some(mutation_id), entries)])
+    }
+
+    /// Append the entries of writes that passed validation, see `append_writes`. The
+    /// write throttle applies once to the whole append.
+    fn append_validated(&self, writes: Vec<(Option<String>, Vec<Entry>)>) -> IoResult<()> {
+        self.throttle_write()?;
+        self.append_writes(writes)
+    }
+
+    /// Append the entries of writes under one MemStore lock, flushing at most once
+    /// afterwards, then run the `post_put` hooks. Writes with the request ID of one
+    /// applied before, or of another write of the group, are skipped.
+    fn append_writes(&self, writes: Vec<(Option<String>, Vec<Entry>)>) -> IoResult<()> {
+        let observers = self.observers();
+        let mut ms = self.memstore.lock().unwrap();
+        self.check_writable()?;
+        let mut entries = Vec::new();
+        let mut request_ids: Vec<String> = Vec::new();
+        let mut skipped = 0;
+        for (request_id, write) in writes {
+            if let Some(id) = request_id {
+                if ms.has_request(&id) || request_ids.contains(&id) {
+                    debug!(cf = %self.name, request_id = %id, "skipping a write already applied");
+                    skipped += 1;
+                    continue;
+                }
+                request_ids.push(id);
+            }
+            entries.extend(write);
+        }
+        if skipped > 0 && entries.is_empty() && request_ids.is_empty() {
+            return Ok(());
+        }
+        let observed = if observers.is_empty() { Vec::new() } else { entries.clone() };
+        if let Some(latest) = entries.iter().map(|entry| entry.key.timestamp).max() {
+            self.clock.observe(latest);
+        }
+        let before = ms.size_bytes();
+        self.append_entries(&mut ms, entries, request_ids)?;
+        if let Some(gate) = self.memory_gate.get() {
+            gate.record_write(ms.size_bytes().saturating_sub(before));
+        }
+        let policy = self.flush_policy();
+        let full = ms.active_len() > policy.max_entries;
+        let large = ms.active_bytes() >= policy.max_bytes;
+        drop(ms);
+        if full {
+            self.flush()?;
+        } else if large {
+            self.schedule_flush();
+        }
+        for entry in observed.iter().filter(|entry| matches!(entry.value, CellValue::Put(_))) {
+            observers.iter().for_each(|observer| observer.post_put(self, entry));
+        }
+        Ok(())
+    }
+
     /// Perform aggregations on query results
     /// 
     /// # Arguments
@@ -732,12 +4060,37 @@ impl ColumnFamily {
         end_row: &[u8],
         filter_set: Option<&FilterSet>,
         aggregation_set: &AggregationSet,
+    ) -> IoResult<BTreeMap<RowKey, BTreeMap<Column, AggregationResult>>> {
+        self.aggregate_range_within(start_row, end_row, filter_set, aggregation_set, None)
+    }
+
+    /// Like `aggregate_range`, but give up with `TimedOut` once deadline has passed.
+    pub fn aggregate_range_until(
+        &self,
+        start_row: &[u8],
+        end_row: &[u8],
+        filter_set: Option<&FilterSet>,
+        aggregation_set: &AggregationSet,
+        deadline: Instant,
+    ) -> IoResult<BTreeMap<RowKey, BTreeMap<Column, AggregationResult>>> {
+        self.aggregate_range_within(start_row, end_row, filter_set, aggregation_set, Some(deadline))
+    }
+
+    #[instrument(level = "debug", skip_all, fields(cf = %self.name))]
+    fn aggregate_range_within(
+        &self,
+        start_row: &[u8],
+        end_row: &[u8],
+        filter_set: Option<&FilterSet>,
+        aggregation_set: &AggregationSet,
+        deadline: Option<Instant>,
     ) -> IoResult<BTreeMap<RowKey, BTreeMap<Column, AggregationResult>>> {
         let mut result = BTreeMap::new();
 
         let row_keys = self.get_row_keys_in_range(start_row, end_row)?;
 
         for row_key in row_keys {
+            check_deadline(deadline, "Aggregation")?;
             let row_result = self.aggregate(&row_key, filter_set, aggregation_set)?;
             if !row_result.is_empty() {
                 result.insert(row_key, row_result);
@@ -751,33 +4104,147 @@ impl ColumnFamily {
     /// 
     /// # Arguments
     /// * `options` - Options controlling the compaction process
+    #[instrument(level = "debug", skip_all, fields(cf = %self.name, compaction_type = ?options.compaction_type))]
     pub fn compact_with_options(&self, options: CompactionOptions) -> IoResult<()> {
-        let current_paths = {
-            let guard = self.sst_files.lock().unwrap();
-            guard.clone()
-        };
-
-        if current_paths.len() <= 1 && options.compaction_type == CompactionType::Minor {
+        // A frozen column family was fully compacted by freeze() and never changes
+        if self.is_frozen() {
             return Ok(());
         }
+        self.compact_sstables(options, None).map(|_| ())
+    }
 
-        let mut max_seq: u64 = 0;
-        for path in current_paths.iter() {
-            if let Some(fname) = path.file_name().and_then(|os| os.to_str()) {
-                if let Some(stripped) = fname.strip_suffix(".sst") {
-                    if let Ok(seq) = stripped.parse::<u64>() {
-                        max_seq = max_seq.max(seq);
-                    }
-                }
+    /// Drop every expired version (per the column family's `ttl_ms`) and every tombstone
+    /// past its TTL from the SSTables now, rather than waiting for a compaction to reach
+    /// them. Returns the number of entries dropped.
+    ///
+    /// This is a major compaction that also drops delete markers shadowed by newer
+    /// versions; it is skipped if no SSTable holds anything expired. Expired cells still
+    /// in the MemStore stay until flushed.
+    #[instrument(level = "debug", skip_all, fields(cf = %self.name))]
+    pub fn expire_now(&self) -> IoResult<u64> {
+        if self.is_frozen() || !self.has_expired_entries()? {
+            return Ok(0);
+        }
+        let record = self.compact_sstables(CompactionOptions {
+            compaction_type: CompactionType::Major,
+            cleanup_tombstones: true,
+            ..CompactionOptions::default()
+        }, None)?;
+        let expired = record.map_or(0, |r| r.dropped.expired + r.dropped.tombstones);
+        info!(expired, "expired entries");
+        Ok(expired)
+    }
+
+    /// Whether any SSTable holds a version past the column family's TTL or a tombstone
+    /// past its own.
+    fn has_expired_entries(&self) -> IoResult<bool> {
+        let schema = self.options();
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        let pinned = self.pin_sst_files();
+        for path in pinned.iter() {
+            let reader = self.readers.get(path)?;
+            // Only tombstones and, with a TTL, the oldest entries can have expired
+            let footer = reader.footer();
+            let oldest = footer.time_range.map_or(now, |(min, _)| min);
+            if footer.tombstones == 0 && !schema.expired(oldest, now) {
+                continue;
+            }
+            let expired = reader.scan_all()?.iter().any(|(key, value)| match value {
+                CellValue::Put(_) => schema.expired(key.timestamp, now),
+                CellValue::Delete(ttl) => ttl.is_some_and(|ttl_ms| key.timestamp + ttl_ms <= now),
+                CellValue::DeleteFamily => false,
+            });
+            if expired {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// The interval of scheduled `expire_now` sweeps, if any.
+    pub fn expiry_interval(&self) -> Option<Duration> {
+        self.expiry.interval()
+    }
+
+    /// Run `expire_now` every interval on a background thread, or stop with `None`
+    /// (not persisted).
+    pub fn set_expiry_interval(&self, interval: Option<Duration>) {
+        let cf = self.clone();
+        self.expiry.set(&self.name, interval, move || {
+            if let Err(err) = cf.expire_now() {
+                error!(cf = %cf.name, error = %err, "scheduled expiry failed");
             }
+        });
+    }
+
+    /// Compact only the SSTables holding rows in [start_row, end_row), e.g. to reclaim
+    /// the space of a deleted tenant without rewriting every SSTable.
+    ///
+    /// The overlapping SSTables are merged into one, whole. The cells they hold under
+    /// range tombstones are dropped, and so are range tombstones inside the range once
+    /// nothing under them is left. Versions and tombstones are pruned per options only
+    /// for rows in the range; `compaction_type` is ignored.
+    #[instrument(level = "debug", skip_all, fields(cf = %self.name))]
+    pub fn compact_range(&self, start_row: &[u8], end_row: &[u8], options: CompactionOptions) -> IoResult<()> {
+        if start_row >= end_row {
+            return Err(Error::new(ErrorKind::InvalidInput, "start_row must be less than end_row"));
+        }
+        if self.is_frozen() {
+            return Ok(());
+        }
+        self.compact_sstables(options, Some((start_row, end_row))).map(|_| ())
+    }
+
+    /// Compact per options, or only the SSTables overlapping range if it is set, then
+    /// run the `post_compact` hooks. Returns the record of the compaction, or `None` if
+    /// there was nothing to compact.
+    fn compact_sstables(&self, options: CompactionOptions, range: Option<(&[u8], &[u8])>) -> IoResult<Option<CompactionRecord>> {
+        let record = self.run_compaction(options, range)?;
+        if let Some(record) = &record {
+            self.observe_compaction(record);
         }
-        let new_seq = max_seq + 1;
+        Ok(record)
+    }
+
+    fn run_compaction(&self, options: CompactionOptions, range: Option<(&[u8], &[u8])>) -> IoResult<Option<CompactionRecord>> {
+        // Versions the column family's options hide from reads are dropped by every compaction
+        let schema = self.options();
+        let options = CompactionOptions {
+            max_versions: tighter(options.max_versions, schema.max_versions),
+            max_age_ms: tighter(options.max_age_ms, schema.ttl_ms),
+            ..options
+        };
+        let _compacting = self.compaction_lock.lock().unwrap();
+        // The outputs must sort before SSTables flushed while compacting, which hold newer
+        // cells, so the first output number is taken with the inputs and the rest once
+        // their count is known
+        let (current_paths, new_seq) = {
+            let guard = self.sst_files.lock().unwrap();
+            if range.is_none() && guard.len() <= 1 && options.compaction_type == CompactionType::Minor {
+                return Ok(None);
+            }
+            (guard.clone(), self.manifest.lock().unwrap().allocate(1))
+        };
         let new_fname = format!("{:010}.sst", new_seq);
-        let new_sst_path = self.path.join(&new_fname);
 
-        let tables_to_compact = match options.compaction_type {
-            CompactionType::Major => current_paths.clone(),
-            CompactionType::Minor => {
+        let tables_to_compact = match (range, options.compaction_type) {
+            (Some((start_row, end_row)), _) => {
+                let mut tables = Vec::new();
+                for path in &current_paths {
+                    let reader = self.readers.get(path)?;
+                    // The footer rules most files out without searching their entries
+                    let overlaps = reader.footer().overlaps_rows(start_row, end_row)
+                        && reader.range_entries(start_row, end_row)
+                            .first()
+                            .is_some_and(|(key, _)| key.row.as_slice() < end_row);
+                    if overlaps {
+                        tables.push(path.clone());
+                    }
+                }
+                tables
+            }
+            (None, CompactionType::Major) => current_paths.clone(),
+            (None, CompactionType::Minor) => {
                 let mut tables = current_paths.clone();
                 tables.sort();
                 let count = (tables.len() / 2).max(2).min(tables.len());
@@ -786,8 +4253,20 @@ impl ColumnFamily {
         };
 
         if tables_to_compact.is_empty() {
-            return Ok(());
+            return Ok(None);
         }
+        let throttle = self.compaction_throttle();
+        let _slot = CompactionSlot::acquire(throttle.max_concurrent);
+        let mut limiter = RateLimiter::new(throttle.max_bytes_per_sec);
+        let kind = match (range, options.compaction_type) {
+            (Some(_), _) => CompactionKind::Range,
+            (None, CompactionType::Minor) => CompactionKind::Minor,
+            (None, CompactionType::Major) => CompactionKind::Major,
+        };
+        let tracker = CompactionTracker::start(&self.compaction_stats, kind, &tables_to_compact)?;
+        let started = Instant::now();
+        let range_tombstones = self.range_tombstones();
+        let _pinned = self.file_refs.pin(tables_to_compact.clone());
 
         // Collect entries from all tables to compact
         let mut merged: Vec<Entry> = Vec::new();
@@ -795,7 +4274,8 @@ impl ColumnFamily {
             // Use flat_map to process all tables
             let entries: IoResult<Vec<_>> = tables_to_compact.iter()
                 .map(|path| {
-                    let mut reader = SSTableReader::open(path)?;
+                    limiter.consume(fs::metadata(path)?.len());
+                    let reader = self.readers.get(path)?;
                     // Map each (entry_key, cell) to an Entry
                     let table_entries: Vec<Entry> = reader.scan_all()?
                         .into_iter()
@@ -812,141 +4292,821 @@ impl ColumnFamily {
             merged.extend(entries?.into_iter().flatten());
         }
 
-        merged.sort_by(|a, b| a.key.cmp(&b.key));
-
-        if options.max_versions.is_some() || options.max_age_ms.is_some() || options.cleanup_tombstones {
-            let now = chrono::Utc::now().timestamp_millis() as u64;
-
-            // Group entries by row and column using iterators
-            let grouped: BTreeMap<(Vec<u8>, Vec<u8>), Vec<Entry>> = merged
-                .into_iter()
-                .fold(BTreeMap::new(), |mut acc, entry| {
-                    let key = (entry.key.row.clone(), entry.key.column.clone());
-                    acc.entry(key).or_default().push(entry);
-                    acc
-                });
-
-            // Process each group of entries using iterators
-            let filtered: Vec<Entry> = grouped.into_iter()
-                .flat_map(|(_, mut entries)| {
-                    // Sort by timestamp (descending)
-                    entries.sort_by(|a, b| b.key.timestamp.cmp(&a.key.timestamp));
-
-                    // Use fold to maintain state while filtering entries
-                    entries.into_iter()
-                        .fold((Vec::new(), false), |(mut kept, mut seen_non_tombstone), entry| {
-                            let keep = match &entry.value {
-                                CellValue::Put(_) => {
-                                    let within_version_limit = options.max_versions
-                                        .map(|max| kept.len() < max)
-                                        .unwrap_or(true);
-
-                                    let within_age_limit = options.max_age_ms
-                                        .map(|max_age| now - entry.key.timestamp <= max_age)
-                                        .unwrap_or(true);
-
-                                    within_version_limit && within_age_limit
-                                },
-                                CellValue::Delete(ttl) => {
-                                    if options.cleanup_tombstones {
-                                        match ttl {
-                                            Some(ttl_ms) => {
-                                                entry.key.timestamp + ttl_ms > now
-                                            },
-                                            None => {
-                                                !seen_non_tombstone
-                                            }
-                                        }
-                                    } else {
-                                        true
-                                    }
-                                }
-                            };
-
-                            if keep {
-                                if let CellValue::Put(_) = entry.value {
-                                    seen_non_tombstone = true;
-                                }
-                                kept.push(entry);
-                            }
+        let entries_in = merged.len();
+        let parallelism = self.compaction_parallelism();
+        let parts = parallelism.partitions(tracker.progress.bytes_in);
+        if parts > 1 {
+            merged.par_sort_by(|a, b| a.key.cmp(&b.key));
+        } else {
+            merged.sort_by(|a, b| a.key.cmp(&b.key));
+        }
 
-                            (kept, seen_non_tombstone)
-                        })
-                        .0  // Return just the kept entries
-                })
-                .collect();
+        // Partitions hold disjoint rows, so each is pruned on its own, then cut into
+        // output SSTables of the target size
+        let partitions = split_rows(merged, parts);
+        let now = chrono::Utc::now().timestamp_millis() as u64;
+        let prune = |entries| prune_entries(entries, &range_tombstones, &options, range, now);
+        let pruned: Vec<_> = if partitions.len() > 1 {
+            compaction_pool(partitions.len())?.install(|| partitions.into_par_iter().map(prune).collect())
+        } else {
+            partitions.into_iter().map(prune).collect()
+        };
+        let mut files = Vec::new();
+        let mut dropped = DroppedEntries::default();
+        for (entries, partition_dropped) in pruned {
+            files.extend(split_by_size(entries, options.target_file_size_bytes));
+            dropped += partition_dropped;
+        }
+        if files.len() > 1 {
+            let _guard = self.sst_files.lock().unwrap();
+            if !self.manifest.lock().unwrap().extend_allocation(new_seq + 1, files.len() - 1) {
+                // A flush took the following number; the files hold disjoint rows in
+                // order, so they are written as one instead
+                files = vec![files.concat()];
+            }
+        }
+        let entries_out = files.iter().map(Vec::len).sum::<usize>();
+        let outputs: Vec<PathBuf> = (new_seq..)
+            .take(files.len())
+            .map(|seq| self.path.join(format!("{:010}.sst", seq)))
+            .collect();
 
-            merged = filtered;
+        // Writers running at once share the throttle's rate
+        let writers = parallelism.max_threads.max(1).min(files.len());
+        let mut limiters = vec![limiter];
+        if files.len() > 1 {
+            let rate = throttle.max_bytes_per_sec.map(|rate| rate / writers as u64);
+            limiters = (0..files.len()).map(|_| RateLimiter::new(rate)).collect();
         }
+        let durability = self.durability();
+        let bloom = self.options().bloom_filter;
+        let write = |((entries, mut limiter), path): ((Vec<Entry>, RateLimiter), &PathBuf)| {
+            SSTable::create_paced(path, &entries, durability, bloom, |bytes| limiter.consume(bytes))?;
+            fs::metadata(path).map(|m| m.len())
+        };
+        let jobs = files.into_iter().zip(limiters).zip(&outputs);
+        let sizes: Vec<u64> = if writers > 1 {
+            compaction_pool(writers)?.install(|| jobs.par_bridge().map(write).collect::<IoResult<_>>())?
+        } else {
+            jobs.map(write).collect::<IoResult<_>>()?
+        };
+        let bytes_written = sizes.iter().sum();
 
-        SSTable::create(&new_sst_path, &merged)?;
+        info!(
+            inputs = tables_to_compact.len(),
+            outputs = outputs.len(),
+            first_output = %new_fname,
+            entries_in,
+            entries_out,
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "compaction finished"
+        );
 
+        // Range tombstones whose cells all lie in the inputs: every one for a major compaction,
+        // those inside the range for a range compaction
+        let resolvable: Vec<RangeTombstone> = match range {
+            Some((start_row, end_row)) => range_tombstones.iter()
+                .filter(|t| start_row <= t.start_row.as_slice() && t.end_row.as_slice() <= end_row)
+                .cloned()
+                .collect(),
+            None if options.compaction_type == CompactionType::Major => range_tombstones,
+            None => Vec::new(),
+        };
+        // Resolving range tombstones needs the MemStore too; lock it first, as flush does
+        let ms = (!resolvable.is_empty()).then(|| self.memstore.lock().unwrap());
         let mut list_guard = self.sst_files.lock().unwrap();
 
-        // Remove old SSTable files using iterators
-        tables_to_compact.iter().for_each(|old_path| {
-            let _ = std::fs::remove_file(old_path);
-        });
+        // Swap the inputs for the output in one edit; a crash before it leaves the output
+        // unrecorded and the inputs live
+        self.manifest.lock().unwrap().record(&outputs, &tables_to_compact)?;
 
-        if options.compaction_type == CompactionType::Major {
-            *list_guard = vec![new_sst_path];
-        } else {
-            list_guard.retain(|path| !tables_to_compact.contains(path));
-            list_guard.push(new_sst_path);
-            list_guard.sort(); 
+        if !resolvable.is_empty() {
+            // Once no SSTable or MemStore cell is left under them, the tombstones are resolved.
+            // A flush during the compaction may have added covered cells, so keep them then.
+            let resolved = ms.is_some_and(|ms| {
+                list_guard.iter().all(|path| current_paths.contains(path))
+                    && !resolvable.iter().any(|t| {
+                        ms.scan_range_keys(&t.start_row, &t.end_row)
+                            .iter()
+                            .any(|(key, _)| t.covers(&key.row, &key.column, key.timestamp))
+                    })
+            });
+            if resolved {
+                let mut live = self.range_tombstones.lock().unwrap();
+                let remaining: Vec<RangeTombstone> = live.iter()
+                    .filter(|t| !resolvable.contains(t))
+                    .cloned()
+                    .collect();
+                self.save_range_tombstones(&remaining)?;
+                info!(resolved = live.len() - remaining.len(), "resolved range tombstones");
+                *live = remaining;
+            }
         }
+        // SSTables flushed while compacting stay live
+        list_guard.retain(|path| !tables_to_compact.contains(path));
+        list_guard.extend_from_slice(&outputs);
+        list_guard.sort();
+        self.delete_obsolete(&tables_to_compact);
+        self.readers.retain(&list_guard);
+        drop(list_guard);
 
-        Ok(())
+        Ok(Some(tracker.finish(bytes_written, outputs.len(), entries_in, entries_out, dropped)))
     }
 }
 
 /// A Table is a directory containing one or more ColumnFamily subdirectories.
+/// Column families partitioned into regions are kept apart, see `region`.
+///
+/// Clones share their column families: one created through any clone is visible to all.
 #[derive(Clone)]
 pub struct Table {
     path: PathBuf,
-    column_families: BTreeMap<String, ColumnFamily>,
+    column_families: Arc<RwLock<ColumnFamilies>>,
+    /// Cap on the MemStores of all column families together, if any
+    memstore_limit: Arc<Mutex<Option<u64>>>,
+    memory_budget: Arc<Mutex<Option<MemoryBudget>>>,
+    memory_gate: Arc<MemoryGate>,
+    /// Held while a row mutation is logged and applied, see `mutation`
+    mutation_log: Arc<Mutex<MutationLog>>,
+}
+
+/// Column families of a table, by name. Names are unique across both maps.
+#[derive(Default)]
+struct ColumnFamilies {
+    plain: BTreeMap<String, ColumnFamily>,
+    regioned: BTreeMap<String, RegionedColumnFamily>,
+}
+
+impl ColumnFamilies {
+    /// Fail with `AlreadyExists` if a column family named cf_name exists.
+    fn check_absent(&self, cf_name: &str) -> IoResult<()> {
+        if self.plain.contains_key(cf_name) || self.regioned.contains_key(cf_name) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("ColumnFamily {} already exists", cf_name),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Every plain column family and the column family of every region.
+    fn all(&self) -> Vec<ColumnFamily> {
+        let mut all: Vec<ColumnFamily> = self.plain.values().cloned().collect();
+        for regioned in self.regioned.values() {
+            all.extend(regioned.column_families());
+        }
+        all
+    }
 }
 
 impl Table {
     /// Open (or create) a table directory.
+    ///
+    /// Column families are opened with the settings recorded in `schema.json`; directories
+    /// it does not list yet are adopted with the options of their legacy `options.json`,
+    /// which move into the schema, or else with default settings. Fails with `NotFound`
+    /// if a column family in the schema has no directory.
+    #[instrument(level = "debug", skip_all, fields(table = %table_dir.as_ref().display()))]
     pub fn open(table_dir: impl AsRef<Path>) -> IoResult<Self> {
         let tbl_path = table_dir.as_ref().to_path_buf();
         fs::create_dir_all(&tbl_path)?;
+        let schema = TableSchema::load(&tbl_path)?.unwrap_or_default();
         // Process directory entries using iterators
         let mut cfs = BTreeMap::new();
+        let mut regioned = BTreeMap::new();
+        let mut adopted = BTreeMap::new();
 
         // Use try_fold to handle errors properly
         fs::read_dir(&tbl_path)?.try_for_each(|entry_result| -> IoResult<()> {
             let entry = entry_result?;
             if entry.file_type()?.is_dir() {
                 let name = entry.file_name().into_string().unwrap();
-                let cf = ColumnFamily::open(&tbl_path, &name)?;
-                cfs.insert(name, cf);
+                let descriptor = schema.column_families.get(&name).copied();
+                if entry.path().join(REGIONS_MANIFEST).exists() {
+                    regioned.insert(name.clone(), RegionedColumnFamily::open(&tbl_path, &name)?);
+                    if descriptor.is_none() {
+                        adopted.insert(name, ColumnFamilyDescriptor { regioned: true, ..Default::default() });
+                    }
+                } else {
+                    let options = match descriptor {
+                        Some(descriptor) => descriptor.options,
+                        None => legacy_options(&entry.path())?.unwrap_or_default(),
+                    };
+                    let cf = ColumnFamily::open_with_options(&tbl_path, &name, options)?;
+                    cfs.insert(name.clone(), cf);
+                    if descriptor.is_none() {
+                        adopted.insert(name, ColumnFamilyDescriptor { options, regioned: false });
+                    }
+                }
             }
             Ok(())
         })?;
 
-        Ok(Table {
+        if let Some(missing) = schema.column_families.keys()
+            .find(|name| !cfs.contains_key(*name) && !regioned.contains_key(*name))
+        {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Column family {} is in the schema but its directory is missing", missing),
+            ));
+        }
+        if !adopted.is_empty() {
+            info!(column_families = adopted.len(), "adopted column families into the schema");
+            let names: Vec<_> = adopted.keys().cloned().collect();
+            TableSchema::update(&tbl_path, |schema| {
+                for (name, descriptor) in adopted {
+                    schema.column_families.entry(name).or_insert(descriptor);
+                }
+                Ok(())
+            })?;
+            // The schema now holds what the legacy options files did
+            for name in names {
+                let path = tbl_path.join(name).join(LEGACY_OPTIONS_FILE);
+                if path.exists() {
+                    fs::remove_file(path)?;
+                }
+            }
+        }
+
+        let mutation_log = MutationLog::new(&tbl_path);
+        let table = Table {
             path: tbl_path,
-            column_families: cfs,
-        })
+            column_families: Arc::new(RwLock::new(ColumnFamilies { plain: cfs, regioned })),
+            memstore_limit: Arc::new(Mutex::new(None)),
+            memory_budget: Arc::new(Mutex::new(None)),
+            memory_gate: Arc::new(MemoryGate::default()),
+            mutation_log: Arc::new(Mutex::new(mutation_log)),
+        };
+        table.complete_mutation(&table.mutation_log.lock().unwrap())?;
+        table.spawn_memstore_flusher();
+        Ok(table)
+    }
+
+    /// Open (or create) a table directory like `open`, and report what opening it
+    /// recovered (see `recovery`).
+    pub fn open_with_report(table_dir: impl AsRef<Path>) -> IoResult<(Self, RecoveryReport)> {
+        let table = Self::open(table_dir)?;
+        let report = table.recovery_report();
+        Ok((table, report))
+    }
+
+    /// What opening each column family, and each region of the regioned ones, recovered.
+    /// Column families created since the table was opened report an empty recovery.
+    pub fn recovery_report(&self) -> RecoveryReport {
+        let column_families = self.column_families.read().unwrap();
+        let mut report = RecoveryReport::default();
+        report.column_families.extend(column_families.plain.values().map(ColumnFamily::recovery));
+        for (name, regioned) in &column_families.regioned {
+            report.column_families.extend(regioned.column_families().iter().map(|cf| {
+                let recovery = cf.recovery();
+                CfRecovery { column_family: format!("{}/{}", name, recovery.column_family), ..recovery }
+            }));
+        }
+        report.column_families.sort_by(|a, b| a.column_family.cmp(&b.column_family));
+        report
+    }
+
+    /// Start the thread enforcing the memstore limit and the memory budget, which ends
+    /// once every clone of the table is dropped.
+    fn spawn_memstore_flusher(&self) {
+        let column_families = Arc::downgrade(&self.column_families);
+        let limit = self.memstore_limit.clone();
+        let budget = self.memory_budget.clone();
+        let gate = self.memory_gate.clone();
+        thread::spawn(move || loop {
+            thread::sleep(MEMSTORE_LIMIT_CHECK_INTERVAL);
+            let Some(column_families) = column_families.upgrade() else {
+                return;
+            };
+            let limit = *limit.lock().unwrap();
+            let budget = *budget.lock().unwrap();
+            if limit.is_none() && budget.is_none() {
+                continue;
+            }
+            let cfs = column_families.read().unwrap().all();
+            drop(column_families);
+            if let Some(limit) = limit {
+                flush_to_limit(&cfs, limit);
+            }
+            // Column families created since the last check are attached here
+            cfs.iter().for_each(|cf| cf.attach_memory_gate(&gate));
+            memory::enforce(&cfs, budget.as_ref(), &gate);
+        });
+    }
+
+    /// Cap the approximate memory taken by the MemStores of all column families (and
+    /// regions) together, or lift the cap with `None` (the default). A background
+    /// thread checks the cap every 100 ms and flushes the column families with the
+    /// most unflushed bytes until the MemStores fit again. Lives in memory only.
+    pub fn set_memstore_limit(&self, limit: Option<u64>) {
+        *self.memstore_limit.lock().unwrap() = limit;
+    }
+
+    /// The cap on the MemStores of all column families together, if any.
+    pub fn memstore_limit(&self) -> Option<u64> {
+        *self.memstore_limit.lock().unwrap()
+    }
+
+    /// Approximate memory taken by the MemStores of all column families together.
+    pub fn memstore_bytes(&self) -> u64 {
+        self.column_families.read().unwrap().all().iter().map(ColumnFamily::memstore_bytes).sum()
+    }
+
+    /// Cap the memory taken by the MemStores and block caches of all column families
+    /// (and regions) together, or lift the cap with `None` (the default). A background
+    /// thread checks the budget every 100 ms and flushes or evicts as its policy says;
+    /// while the MemStores alone exceed it, writes fail with `ResourceBusy`. Lives in
+    /// memory only.
+    pub fn set_memory_budget(&self, budget: Option<MemoryBudget>) {
+        *self.memory_budget.lock().unwrap() = budget;
+        if budget.is_none() {
+            memory::enforce(&[], None, &self.memory_gate);
+        }
+    }
+
+    /// The memory budget of the table, if any.
+    pub fn memory_budget(&self) -> Option<MemoryBudget> {
+        *self.memory_budget.lock().unwrap()
+    }
+
+    /// Memory taken by the MemStores and block caches of all column families together.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let cfs = self.column_families.read().unwrap().all();
+        MemoryUsage::of(&cfs, self.memory_budget().map(|budget| budget.limit_bytes))
     }
 
     /// Create a new column family named cf_name. Fails if it already exists.
-    pub fn create_cf(&mut self, cf_name: &str) -> IoResult<()> {
-        if self.column_families.contains_key(cf_name) {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::AlreadyExists,
-                format!("ColumnFamily {} already exists", cf_name),
+    pub fn create_cf(&self, cf_name: &str) -> IoResult<()> {
+        self.create_cf_with_options(cf_name, ColumnFamilyOptions::default())
+    }
+
+    /// Create a new column family named cf_name with retention options, which are
+    /// persisted and enforced by reads and compactions. Fails if it already exists.
+    #[instrument(level = "debug", skip(self), fields(table = %self.path.display()))]
+    pub fn create_cf_with_options(&self, cf_name: &str, options: ColumnFamilyOptions) -> IoResult<()> {
+        // Held until the column family is registered, so concurrent creations of one name fail
+        let mut cfs = self.column_families.write().unwrap();
+        cfs.check_absent(cf_name)?;
+        let cf = ColumnFamily::open_with_options(&self.path, cf_name, options)?;
+        TableSchema::update(&self.path, |schema| {
+            schema.column_families.insert(cf_name.to_string(), ColumnFamilyDescriptor { options, regioned: false });
+            Ok(())
+        })?;
+        cfs.plain.insert(cf_name.to_string(), cf);
+        info!(cf = cf_name, "created column family");
+        Ok(())
+    }
+
+    /// Change the retention settings of column family cf_name. They are persisted in the
+    /// schema first and then apply to reads and compactions right away.
+    #[instrument(level = "debug", skip(self), fields(table = %self.path.display()))]
+    pub fn alter_cf(&self, cf_name: &str, options: ColumnFamilyOptions) -> IoResult<()> {
+        options.validate()?;
+        if self.column_families.read().unwrap().regioned.contains_key(cf_name) {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("Column family {} is regioned; its options cannot be altered", cf_name),
             ));
         }
-        let cf = ColumnFamily::open(&self.path, cf_name)?;
-        self.column_families.insert(cf_name.to_string(), cf);
+        let cf = self.cf(cf_name).ok_or_else(|| {
+            Error::new(ErrorKind::NotFound, format!("ColumnFamily {} not found", cf_name))
+        })?;
+        TableSchema::update(&self.path, |schema| {
+            schema.column_families.entry(cf_name.to_string()).or_default().options = options;
+            Ok(())
+        })?;
+        cf.set_options(options);
+        info!(cf = cf_name, ?options, "altered column family");
         Ok(())
     }
 
+    /// The persisted schema of this table.
+    pub fn schema(&self) -> IoResult<TableSchema> {
+        Ok(TableSchema::load(&self.path)?.unwrap_or_default())
+    }
+
     /// Retrieve a handle to an existing ColumnFamily (or None if it doesn’t exist).
     pub fn cf(&self, cf_name: &str) -> Option<ColumnFamily> {
-        self.column_families.get(cf_name).cloned()
+        self.column_families.read().unwrap().plain.get(cf_name).cloned()
+    }
+
+    /// Create a column family named cf_name that is partitioned into regions,
+    /// pre-split at split_points. Fails if a column family with that name exists.
+    #[instrument(level = "debug", skip(self, split_points), fields(table = %self.path.display()))]
+    pub fn create_regioned_cf(&self, cf_name: &str, config: RegionConfig, split_points: &[RowKey]) -> IoResult<()> {
+        let mut cfs = self.column_families.write().unwrap();
+        cfs.check_absent(cf_name)?;
+        let cf = RegionedColumnFamily::create(&self.path, cf_name, config, split_points)?;
+        TableSchema::update(&self.path, |schema| {
+            schema.column_families.insert(cf_name.to_string(), ColumnFamilyDescriptor { regioned: true, ..Default::default() });
+            Ok(())
+        })?;
+        cfs.regioned.insert(cf_name.to_string(), cf);
+        info!(cf = cf_name, regions = split_points.len() + 1, "created regioned column family");
+        Ok(())
+    }
+
+    /// Retrieve a handle to an existing regioned column family.
+    pub fn regioned_cf(&self, cf_name: &str) -> Option<RegionedColumnFamily> {
+        self.column_families.read().unwrap().regioned.get(cf_name).cloned()
+    }
+
+    /// Names of all column families in this table, in sorted order.
+    pub fn cf_names(&self) -> Vec<String> {
+        self.column_families.read().unwrap().plain.keys().cloned().collect()
+    }
+
+    /// Names of all regioned column families in this table, in sorted order.
+    pub fn regioned_cf_names(&self) -> Vec<String> {
+        self.column_families.read().unwrap().regioned.keys().cloned().collect()
+    }
+
+    /// Names of the column families (plain or regioned) whose background threads have
+    /// stopped, in sorted order.
+    pub fn stopped_background_threads(&self) -> Vec<String> {
+        let cfs = self.column_families.read().unwrap();
+        let plain = cfs.plain.iter().filter(|(_, cf)| !cf.background_thread_alive());
+        let regioned = cfs.regioned.iter().filter(|(_, cf)| !cf.background_threads_alive());
+        let mut names: Vec<String> = plain.map(|(name, _)| name.clone()).chain(regioned.map(|(name, _)| name.clone())).collect();
+        names.sort();
+        names
+    }
+
+    /// Path of the table directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Handles of the column families that are not regioned, so that long operations on
+    /// them do not hold the table's lock.
+    fn plain_cfs(&self) -> Vec<ColumnFamily> {
+        self.column_families.read().unwrap().plain.values().cloned().collect()
+    }
+
+    /// *Get* the latest value of each (column family, column) in cells under row, in
+    /// order, as `ColumnFamily::get` would; a regioned column family is read from the
+    /// region holding row. The cells of each column family are looked up together, see
+    /// `ColumnFamily::multi_get`. Fails with `NotFound` if a column family does not exist.
+    ///
+    /// The column families are read one after another, so a write to several of them
+    /// made meanwhile may be seen in some of them only.
+    #[instrument(level = "debug", skip_all, fields(table = %self.path.display(), cells = cells.len()))]
+    pub fn get(&self, row: &[u8], cells: &[(String, Column)]) -> IoResult<Vec<Option<Vec<u8>>>> {
+        let mut by_cf: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (i, (cf_name, _)) in cells.iter().enumerate() {
+            by_cf.entry(cf_name.as_str()).or_default().push(i);
+        }
+        let mut values = vec![None; cells.len()];
+        for (cf_name, indices) in by_cf {
+            let (plain, regioned) = {
+                let cfs = self.column_families.read().unwrap();
+                (cfs.plain.get(cf_name).cloned(), cfs.regioned.get(cf_name).cloned())
+            };
+            let found: Vec<Option<Vec<u8>>> = match (plain, regioned) {
+                (Some(cf), _) => {
+                    let cf_cells: Vec<(RowKey, Column)> = indices.iter().map(|&i| (row.to_vec(), cells[i].1.clone())).collect();
+                    cf.multi_get(&cf_cells)?
+                }
+                (None, Some(cf)) => indices.iter().map(|&i| cf.get(row, &cells[i].1)).collect::<IoResult<_>>()?,
+                (None, None) => {
+                    return Err(Error::new(ErrorKind::NotFound, format!("ColumnFamily {} not found", cf_name)));
+                }
+            };
+            for (i, value) in indices.into_iter().zip(found) {
+                values[i] = value;
+            }
+        }
+        Ok(values)
+    }
+
+    /// Execute a Get operation in every column family it selects (all of them if it
+    /// selects none), leaving out those without any of its columns. Fails with
+    /// `NotFound` if a selected column family does not exist.
+    pub fn execute_get(&self, get: &Get) -> IoResult<FamilyVersions> {
+        let (plain_cfs, regioned_cfs) = {
+            let cfs = self.column_families.read().unwrap();
+            (cfs.plain.clone(), cfs.regioned.clone())
+        };
+        if let Some(missing) = get.families().iter().find(|name| !plain_cfs.contains_key(*name) && !regioned_cfs.contains_key(*name)) {
+            return Err(Error::new(ErrorKind::NotFound, format!("ColumnFamily {} not found", missing)));
+        }
+        let mut families = FamilyVersions::new();
+        for (name, cf) in &plain_cfs {
+            families.insert(name.clone(), cf.execute_get(get)?);
+        }
+        for (name, cf) in regioned_cfs.iter().filter(|(name, _)| get.families().is_empty() || get.families().contains(*name)) {
+            families.insert(name.clone(), cf.execute_get(get)?);
+        }
+        families.retain(|_, columns| !columns.is_empty());
+        Ok(families)
+    }
+
+    /// Apply the puts and deletes of mutation to their column families atomically: after
+    /// a crash, either all of them or none are applied (see `RowMutation`). They are all
+    /// written at one timestamp. Nothing is applied if any put fails validation or any
+    /// column family is frozen or stalled. Fails with `NotFound` if a column family does
+    /// not exist and `InvalidInput` if it is regioned.
+    ///
+    /// If applying a committed mutation fails, e.g. on a full disk, it is applied again
+    /// before the next mutation or when the table is next opened.
+    #[instrument(level = "debug", skip_all, fields(table = %self.path.display(), operations = mutation.len()))]
+    pub fn mutate_row(&self, mutation: &RowMutation) -> IoResult<()> {
+        let log = self.mutation_log.lock().unwrap();
+        self.complete_mutation(&log)?;
+        if mutation.is_empty() {
+            return Ok(());
+        }
+
+        let mut cfs = BTreeMap::new();
+        {
+            let families = self.column_families.read().unwrap();
+            for (cf_name, _) in mutation.parts(0) {
+                let cf = match families.plain.get(&cf_name) {
+                    Some(cf) => cf.clone(),
+                    None if families.regioned.contains_key(&cf_name) => {
+                        return Err(Error::new(
+                            ErrorKind::InvalidInput,
+                            format!("Row mutations cannot span regioned column family {}", cf_name),
+                        ));
+                    }
+                    None => return Err(Error::new(ErrorKind::NotFound, format!("ColumnFamily {} not found", cf_name))),
+                };
+                cfs.insert(cf_name, cf);
+            }
+        }
+        // The latest clock of the column families, so that the mutation is newer in all
+        let timestamp = cfs.values().map(ColumnFamily::now).max().unwrap();
+        let parts = mutation.parts(timestamp);
+        for (cf_name, entries) in &parts {
+            cfs[cf_name].prepare_mutation(entries)?;
+        }
+
+        let logged = LoggedMutation::new(parts);
+        log.commit(&logged)?;
+        debug!(id = %logged.id, column_families = cfs.len(), "committed row mutation");
+        for (cf_name, entries) in logged.parts {
+            cfs[&cf_name].apply_mutation(entries, logged.id.clone())?;
+        }
+        log.clear()
+    }
+
+    /// Apply the mutation of log committed but maybe not applied, then clear it.
+    fn complete_mutation(&self, log: &MutationLog) -> IoResult<()> {
+        let Some(logged) = log.pending()? else {
+            return Ok(());
+        };
+        info!(id = %logged.id, "completing a logged row mutation");
+        for (cf_name, entries) in logged.parts {
+            match self.cf(&cf_name) {
+                Some(cf) => cf.apply_mutation(entries, logged.id.clone())?,
+                None => warn!(id = %logged.id, cf = %cf_name, "column family of a logged row mutation is gone"),
+            }
+        }
+        log.clear()
+    }
+
+    /// The latest value of every column of row in every column family, plain and
+    /// regioned, by column family; see `scan_row_versions`.
+    pub fn scan_row(&self, row: &[u8]) -> IoResult<FamilyVersions> {
+        self.scan_row_versions(row, 1)
+    }
+
+    /// Up to max_versions_per_column versions of every column of row in every column
+    /// family, plain and regioned, as `ColumnFamily::scan_row_versions` returns them,
+    /// by column family. Column families without a value in row are left out. Like
+    /// `get`, the column families are read one after another.
+    #[instrument(level = "debug", skip(self, row), fields(table = %self.path.display()))]
+    pub fn scan_row_versions(&self, row: &[u8], max_versions_per_column: usize) -> IoResult<FamilyVersions> {
+        let (plain_cfs, regioned_cfs) = {
+            let cfs = self.column_families.read().unwrap();
+            (cfs.plain.clone(), cfs.regioned.clone())
+        };
+        let mut families = FamilyVersions::new();
+        for (name, cf) in &plain_cfs {
+            families.insert(name.clone(), cf.scan_row_versions(row, max_versions_per_column)?);
+        }
+        for (name, cf) in &regioned_cfs {
+            families.insert(name.clone(), cf.scan_row_versions(row, max_versions_per_column)?);
+        }
+        families.retain(|_, columns| !columns.is_empty());
+        Ok(families)
+    }
+
+    /// Run a SQL-like query (see `query`) against the column family it names. Fails
+    /// with `InvalidInput` if the query does not parse and `NotFound` if the column
+    /// family does not exist.
+    #[instrument(level = "debug", skip(self), fields(table = %self.path.display()))]
+    pub fn query(&self, sql: &str) -> IoResult<QueryResult> {
+        self.query_within(sql, None)
+    }
+
+    /// Like `query`, but give up with `TimedOut` once deadline has passed.
+    pub fn query_until(&self, sql: &str, deadline: Instant) -> IoResult<QueryResult> {
+        self.query_within(sql, Some(deadline))
+    }
+
+    fn query_within(&self, sql: &str, deadline: Option<Instant>) -> IoResult<QueryResult> {
+        let query = Query::parse(sql)?;
+        let cf = self.cf(query.column_family()).ok_or_else(|| {
+            Error::new(ErrorKind::NotFound, format!("ColumnFamily {} not found", query.column_family()))
+        })?;
+        match deadline {
+            Some(deadline) => query.execute_until(&cf, deadline),
+            None => query.execute(&cf),
+        }
+    }
+
+    /// Flush the MemStore of every column family, plain and regioned. Returns the names
+    /// of the column families, in sorted order.
+    #[instrument(level = "debug", skip(self), fields(table = %self.path.display()))]
+    pub fn flush_all(&self) -> IoResult<Vec<String>> {
+        self.for_each_cf(ColumnFamily::flush, RegionedColumnFamily::flush)
+    }
+
+    /// Run a major compaction of every column family, plain and regioned. Returns the
+    /// names of the column families, in sorted order.
+    #[instrument(level = "debug", skip(self), fields(table = %self.path.display()))]
+    pub fn major_compact(&self) -> IoResult<Vec<String>> {
+        self.for_each_cf(ColumnFamily::major_compact, RegionedColumnFamily::major_compact)
+    }
+
+    /// Verify the SSTables of every column family, plain and regioned, quarantining the
+    /// corrupt ones if quarantine is set. Returns one report per column family or
+    /// region, in name order.
+    #[instrument(level = "debug", skip(self), fields(table = %self.path.display()))]
+    pub fn verify(&self, quarantine: bool) -> IoResult<Vec<VerifyReport>> {
+        let (plain_cfs, regioned_cfs) = {
+            let cfs = self.column_families.read().unwrap();
+            (cfs.plain.clone(), cfs.regioned.clone())
+        };
+        let mut reports = Vec::new();
+        for cf in plain_cfs.values() {
+            reports.push(cf.verify(quarantine)?);
+        }
+        for cf in regioned_cfs.values() {
+            reports.extend(cf.verify(quarantine)?);
+        }
+        reports.sort_by(|a, b| a.column_family.cmp(&b.column_family));
+        Ok(reports)
+    }
+
+    /// Apply plain or regioned to every column family in name order, without holding
+    /// the lock on the column families meanwhile.
+    fn for_each_cf(
+        &self,
+        plain: impl Fn(&ColumnFamily) -> IoResult<()>,
+        regioned: impl Fn(&RegionedColumnFamily) -> IoResult<()>,
+    ) -> IoResult<Vec<String>> {
+        let (plain_cfs, regioned_cfs) = {
+            let cfs = self.column_families.read().unwrap();
+            (cfs.plain.clone(), cfs.regioned.clone())
+        };
+        let mut names = Vec::new();
+        for (name, cf) in &plain_cfs {
+            plain(cf)?;
+            names.push(name.clone());
+        }
+        for (name, cf) in &regioned_cfs {
+            regioned(cf)?;
+            names.push(name.clone());
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Take a snapshot named name of every column family. See `ColumnFamily::snapshot`.
+    pub fn snapshot(&self, name: &str) -> IoResult<()> {
+        self.plain_cfs().iter().try_for_each(|cf| cf.snapshot(name).map(|_| ()))
+    }
+
+    /// Roll back every column family that has a snapshot named name.
+    /// Fails with `NotFound` if no column family has such a snapshot.
+    #[instrument(level = "debug", skip(self), fields(table = %self.path.display()))]
+    pub fn restore_snapshot(&self, name: &str) -> IoResult<()> {
+        let mut restored = 0;
+        for cf in self.plain_cfs() {
+            if cf.list_snapshots()?.iter().any(|s| s == name) {
+                cf.restore_snapshot(name)?;
+                restored += 1;
+            }
+        }
+
+        if restored == 0 {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Snapshot not found: {}", name),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Back the table up to a single archive at path (see `backup`): its schema and, for
+    /// every column family, its SSTables, range tombstones and unflushed WAL entries.
+    ///
+    /// The column families are captured one after the other before the archive is
+    /// written, which happens while writes continue. Fails with `AlreadyExists` if
+    /// path exists, and with `Unsupported` if the table has regioned column families.
+    #[instrument(level = "debug", skip_all, fields(table = %self.path.display(), backup = %path.as_ref().display()))]
+    pub fn backup_to(&self, path: impl AsRef<Path>) -> IoResult<BackupManifest> {
+        self.write_backup(path.as_ref(), None)
+    }
+
+    /// Back the table up into the backup directory backup_dir, creating it: a full
+    /// backup the first time, then incremental ones holding only the SSTables and WAL
+    /// entries that the previous backup did not (see `backup`). Archives are named
+    /// `backup-NNNNNN.tar` and listed in the directory's `backups.json`.
+    #[instrument(level = "debug", skip_all, fields(table = %self.path.display(), backup_dir = %backup_dir.as_ref().display()))]
+    pub fn backup_incremental(&self, backup_dir: impl AsRef<Path>) -> IoResult<BackupManifest> {
+        let backup_dir = backup_dir.as_ref();
+        fs::create_dir_all(backup_dir)?;
+        let mut catalog = BackupCatalog::load(backup_dir)?;
+        let archive = backup_dir.join(format!("backup-{:06}.tar", catalog.backups.len() + 1));
+        let manifest = self.write_backup(&archive, catalog.backups.last())?;
+        catalog.backups.push(manifest.clone());
+        catalog.save(backup_dir)?;
+        Ok(manifest)
+    }
+
+    fn write_backup(&self, path: &Path, base: Option<&BackupManifest>) -> IoResult<BackupManifest> {
+        if !self.regioned_cf_names().is_empty() {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "Tables with regioned column families cannot be backed up to an archive",
+            ));
+        }
+        let mut writer = BackupWriter::create(path, base)?;
+        let schema = serde_json::to_vec_pretty(&self.schema()?).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let captures = self.plain_cfs()
+            .into_iter()
+            .map(|cf| cf.backup_capture().map(|capture| (cf.name, capture)))
+            .collect::<IoResult<Vec<_>>>()?;
+
+        writer.add_bytes(SCHEMA_FILE, &schema)?;
+        for (name, capture) in &captures {
+            writer.add_cf(name, capture)?;
+        }
+        writer.finish()
+    }
+
+    /// Restore the backup archive at archive into table_dir and open the table there.
+    /// An incremental backup is restored with the backups it builds on; a backup
+    /// directory restores its latest backup.
+    ///
+    /// table_dir must be missing or empty, or this fails with `AlreadyExists`. Archives
+    /// are extracted beside it and checked against their manifests before being moved
+    /// into place, so a damaged archive fails with `InvalidData` and leaves nothing behind.
+    #[instrument(level = "debug", skip_all, fields(backup = %archive.as_ref().display(), table = %table_dir.as_ref().display()))]
+    pub fn restore_from(archive: impl AsRef<Path>, table_dir: impl AsRef<Path>) -> IoResult<Table> {
+        let table_dir = table_dir.as_ref();
+        let staging = new_table_staging(table_dir, "restoring")?;
+        let chain = backup::restore_chain(archive.as_ref())?;
+        let manifest = backup::extract(&chain, &staging)?;
+        fs::rename(&staging, table_dir)?;
+        Durability::Sync.sync_parent(table_dir)?;
+        info!(archives = chain.len(), backup = %manifest.archive, "restored backup");
+        Table::open(table_dir)
+    }
+
+    /// Create a copy-on-write clone of this table at new_path and open it.
+    ///
+    /// Every column family is flushed, then its SSTables are hard-linked (or copied
+    /// where links are unsupported) into the clone, which starts with an empty MemStore.
+    /// SSTables are immutable, so the clone costs no extra space until either table
+    /// compacts, and writes, flushes and compactions on one table never affect the
+    /// other. new_path must be missing or empty, or this fails with `AlreadyExists`.
+    #[instrument(level = "debug", skip_all, fields(table = %self.path.display(), clone = %new_path.as_ref().display()))]
+    pub fn clone_to(&self, new_path: impl AsRef<Path>) -> IoResult<Table> {
+        if !self.regioned_cf_names().is_empty() {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "Tables with regioned column families cannot be cloned",
+            ));
+        }
+        let new_path = new_path.as_ref();
+        let staging = new_table_staging(new_path, "cloning")?;
+        let sst_files = match self.clone_files_to(&staging) {
+            Ok(sst_files) => sst_files,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&staging);
+                return Err(e);
+            }
+        };
+        fs::rename(&staging, new_path)?;
+        Durability::Sync.sync_parent(new_path)?;
+        info!(sst_files, "cloned table");
+        Table::open(new_path)
+    }
+
+    /// Write the schema of the table into dir and clone every column family into it.
+    /// Returns the number of SSTables linked.
+    fn clone_files_to(&self, dir: &Path) -> IoResult<usize> {
+        fs::create_dir_all(dir)?;
+        let schema = serde_json::to_vec_pretty(&self.schema()?).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        fs::write(dir.join(SCHEMA_FILE), schema)?;
+        let mut sst_files = 0;
+        for cf in self.plain_cfs() {
+            sst_files += cf.clone_files_to(&dir.join(&cf.name))?;
+        }
+        Ok(sst_files)
     }
 }
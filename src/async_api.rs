@@ -1,20 +1,39 @@
 use std::{
     collections::BTreeMap,
+    future::Future,
     io::Result as IoResult,
-    path::{Path, PathBuf},
+    path::Path,
     sync::Arc,
+    time::{Duration, Instant},
 };
+use futures::Stream;
 use tokio::task;
-use futures::future::{self, Future};
 
 use crate::api::{
     Table as SyncTable, 
     ColumnFamily as SyncColumnFamily,
-    RowKey, Column, Timestamp, CellValue, CompactionOptions, Put, Get
+    RowKey, Column, Timestamp, RowVersions, RangeVersions, FamilyVersions, CompactionOptions, Put, Get, Scan,
+    SnapshotManifest, Entry, EntryKey, CellValue, FAMILY_DELETE_COLUMN, WriteStall, WriteStallThresholds, WriteThrottle, SmallFileMergePolicy, FlushPolicy,
+    ScanOptions, ScanBatch, ColumnFamilyOptions, CompactionStats, ColumnFamilyStats, WriteStats,
+    RangeSizeEstimate, CompactionParallelism,
 };
 use crate::aggregation::AggregationResult;
+use crate::result::Row;
+use crate::validation::Validator;
+use crate::types::TypedValue;
+use crate::observer::Observer;
+use crate::mutation::RowMutation;
+use crate::cache::BlockCacheStats;
+use crate::memory::{MemoryBudget, MemoryUsage};
+use crate::storage::Durability;
+use crate::schema::TableSchema;
+use crate::backup::BackupManifest;
+use crate::wal_archive::WalArchiveState;
 use crate::filter::{Filter, FilterSet};
+use crate::scan::ResumeToken;
+use crate::throttle::CompactionThrottle;
 use crate::aggregation::AggregationSet;
+use crate::query::QueryResult;
 
 /// Async wrapper around the synchronous ColumnFamily
 #[derive(Clone)]
@@ -31,35 +50,65 @@ impl ColumnFamily {
     }
 
     /// Write a new versioned cell (row, column) = value with a fresh timestamp.
+    ///
+    /// Writes do not occupy a blocking thread: they are queued to the column family's
+    /// writer thread, which appends concurrent writes together (see `crate::writer`).
     pub async fn put(&self, row: RowKey, column: Column, value: Vec<u8>) -> IoResult<()> {
-        let cf = self.inner.clone();
-        task::spawn_blocking(move || {
-            cf.put(row, column, value)
-        }).await.unwrap()
+        self.write_at_now(|timestamp| vec![Entry {
+            key: EntryKey { row, column, timestamp, seq: 0 },
+            value: CellValue::Put(value),
+        }]).await
     }
 
     /// Execute a Put operation with multiple columns.
     /// This is similar to the HBase/Java Put API.
     pub async fn execute_put(&self, put: Put) -> IoResult<()> {
-        let cf = self.inner.clone();
-        task::spawn_blocking(move || {
-            cf.execute_put(put)
-        }).await.unwrap()
+        self.write_at_now(|timestamp| put.into_entries(timestamp)).await
     }
 
     /// Mark (row, column) as deleted by writing a tombstone at the current timestamp.
     pub async fn delete(&self, row: RowKey, column: Column) -> IoResult<()> {
+        self.delete_with_ttl(row, column, None).await
+    }
+
+    /// Mark (row, column) as deleted by writing a tombstone with a specified TTL.
+    pub async fn delete_with_ttl(&self, row: RowKey, column: Column, ttl_ms: Option<u64>) -> IoResult<()> {
+        self.write_at_now(|timestamp| vec![Entry {
+            key: EntryKey { row, column, timestamp, seq: 0 },
+            value: CellValue::Delete(ttl_ms),
+        }]).await
+    }
+
+    /// Delete every cell of row written up to now with a `DeleteFamily` marker.
+    pub async fn delete_row(&self, row: RowKey) -> IoResult<()> {
+        self.write_at_now(|timestamp| vec![Entry {
+            key: EntryKey { row, column: FAMILY_DELETE_COLUMN.to_vec(), timestamp, seq: 0 },
+            value: CellValue::DeleteFamily,
+        }]).await
+    }
+
+    /// Write the entries built for the current timestamp through the writer thread.
+    fn write_at_now(&self, entries: impl FnOnce(Timestamp) -> Vec<Entry>) -> impl Future<Output = IoResult<()>> + Send + 'static {
+        self.inner.submit_entries(entries(self.inner.now()), None)
+    }
+
+    /// Delete every cell of the rows in [start_row, end_row) with a range tombstone.
+    pub async fn delete_range(&self, start_row: &[u8], end_row: &[u8]) -> IoResult<()> {
         let cf = self.inner.clone();
+        let start_row = start_row.to_vec();
+        let end_row = end_row.to_vec();
         task::spawn_blocking(move || {
-            cf.delete(row, column)
+            cf.delete_range(&start_row, &end_row)
         }).await.unwrap()
     }
 
-    /// Mark (row, column) as deleted by writing a tombstone with a specified TTL.
-    pub async fn delete_with_ttl(&self, row: RowKey, column: Column, ttl_ms: Option<u64>) -> IoResult<()> {
+    /// Delete every version of (row, column) written at or before timestamp.
+    pub async fn delete_before(&self, row: &[u8], column: &[u8], timestamp: Timestamp) -> IoResult<()> {
         let cf = self.inner.clone();
+        let row = row.to_vec();
+        let column = column.to_vec();
         task::spawn_blocking(move || {
-            cf.delete_with_ttl(row, column, ttl_ms)
+            cf.delete_before(&row, &column, timestamp)
         }).await.unwrap()
     }
 
@@ -73,6 +122,45 @@ impl ColumnFamily {
         }).await.unwrap()
     }
 
+    /// Get the latest value for (row, column) decoded as a T.
+    pub async fn get_typed<T: TypedValue + Send + 'static>(&self, row: &[u8], column: &[u8]) -> IoResult<Option<T>> {
+        let cf = self.inner.clone();
+        let row = row.to_vec();
+        let column = column.to_vec();
+        task::spawn_blocking(move || {
+            cf.get_typed(&row, &column)
+        }).await.unwrap()
+    }
+
+    /// Whether (row, column) has a live value, without copying it.
+    pub async fn exists(&self, row: &[u8], column: &[u8]) -> IoResult<bool> {
+        let cf = self.inner.clone();
+        let row = row.to_vec();
+        let column = column.to_vec();
+        task::spawn_blocking(move || {
+            cf.exists(&row, &column)
+        }).await.unwrap()
+    }
+
+    /// Get the latest value of each (row, column) in cells, in order.
+    pub async fn multi_get(&self, cells: Vec<(RowKey, Column)>) -> IoResult<Vec<Option<Vec<u8>>>> {
+        let cf = self.inner.clone();
+        task::spawn_blocking(move || {
+            cf.multi_get(&cells)
+        }).await.unwrap()
+    }
+
+    /// Get the value (row, column) had at timestamp ts: that of its newest version at or
+    /// before ts, or None if that is a tombstone.
+    pub async fn get_at(&self, row: &[u8], column: &[u8], ts: Timestamp) -> IoResult<Option<Vec<u8>>> {
+        let cf = self.inner.clone();
+        let row = row.to_vec();
+        let column = column.to_vec();
+        task::spawn_blocking(move || {
+            cf.get_at(&row, &column, ts)
+        }).await.unwrap()
+    }
+
     /// Return up to max_versions recent (timestamp, value) for (row, column).
     pub async fn get_versions(
         &self,
@@ -105,14 +193,30 @@ impl ColumnFamily {
         }).await.unwrap()
     }
 
+    /// Every version written between start_time and end_time in any row.
+    pub async fn scan_time_range(&self, start_time: Timestamp, end_time: Timestamp) -> IoResult<RangeVersions> {
+        let cf = self.inner.clone();
+        task::spawn_blocking(move || {
+            cf.scan_time_range(start_time, end_time)
+        }).await.unwrap()
+    }
+
     /// Execute a Get operation to retrieve data for a specific row.
-    pub async fn execute_get(&self, get: Get) -> IoResult<BTreeMap<Column, Vec<(Timestamp, Vec<u8>)>>> {
+    pub async fn execute_get(&self, get: Get) -> IoResult<RowVersions> {
         let cf = self.inner.clone();
         task::spawn_blocking(move || {
             cf.execute_get(&get)
         }).await.unwrap()
     }
 
+    /// Execute a Get operation, returning its cells as a `Row`.
+    pub async fn get_row(&self, get: Get) -> IoResult<Row> {
+        let cf = self.inner.clone();
+        task::spawn_blocking(move || {
+            cf.get_row(&get)
+        }).await.unwrap()
+    }
+
     /// Execute a Get operation for a specific column.
     pub async fn execute_get_column(&self, get: Get, column: &[u8]) -> IoResult<Vec<(Timestamp, Vec<u8>)>> {
         let cf = self.inner.clone();
@@ -122,12 +226,21 @@ impl ColumnFamily {
         }).await.unwrap()
     }
 
+    /// Execute a Get operation for a specific column, decoding the versions as T.
+    pub async fn execute_get_column_typed<T: TypedValue + Send + 'static>(&self, get: Get, column: &[u8]) -> IoResult<Vec<(Timestamp, T)>> {
+        let cf = self.inner.clone();
+        let column = column.to_vec();
+        task::spawn_blocking(move || {
+            cf.execute_get_column_typed(&get, &column)
+        }).await.unwrap()
+    }
+
     /// For each column under row, return up to max_versions_per_column recent (timestamp, value).
     pub async fn scan_row_versions(
         &self,
         row: &[u8],
         max_versions_per_column: usize,
-    ) -> IoResult<BTreeMap<Column, Vec<(Timestamp, Vec<u8>)>>> {
+    ) -> IoResult<RowVersions> {
         let cf = self.inner.clone();
         let row = row.to_vec();
         task::spawn_blocking(move || {
@@ -143,6 +256,188 @@ impl ColumnFamily {
         }).await.unwrap()
     }
 
+    /// Report whether writes should currently stall under the given thresholds.
+    pub async fn write_stall(&self, thresholds: WriteStallThresholds) -> Option<WriteStall> {
+        let cf = self.inner.clone();
+        task::spawn_blocking(move || {
+            cf.write_stall(&thresholds)
+        }).await.unwrap()
+    }
+
+    /// Number of live SSTables and MemStore entries.
+    pub async fn storage_counts(&self) -> (usize, usize) {
+        let cf = self.inner.clone();
+        task::spawn_blocking(move || {
+            (cf.sst_file_count(), cf.memstore_entries())
+        }).await.unwrap()
+    }
+
+    /// SSTable, MemStore, row and tombstone counts and sizes.
+    pub async fn stats(&self) -> IoResult<ColumnFamilyStats> {
+        let cf = self.inner.clone();
+        task::spawn_blocking(move || {
+            cf.stats()
+        }).await.unwrap()
+    }
+
+    /// Estimate the size of rows [start_row, end_row] without reading them.
+    pub async fn estimate_range_size(&self, start_row: &[u8], end_row: &[u8]) -> IoResult<RangeSizeEstimate> {
+        let cf = self.inner.clone();
+        let start_row = start_row.to_vec();
+        let end_row = end_row.to_vec();
+        task::spawn_blocking(move || {
+            cf.estimate_range_size(&start_row, &end_row)
+        }).await.unwrap()
+    }
+
+    /// Load pre-sorted entries straight into new SSTables, bypassing the WAL and MemStore.
+    pub async fn bulk_load(&self, entries: Vec<Entry>) -> IoResult<u64> {
+        let cf = self.inner.clone();
+        task::spawn_blocking(move || {
+            cf.bulk_load(entries)
+        }).await.unwrap()
+    }
+
+    /// Add an externally built SSTable to the column family.
+    pub async fn ingest_external_file(&self, path: impl AsRef<Path>) -> IoResult<u64> {
+        let cf = self.inner.clone();
+        let path = path.as_ref().to_path_buf();
+        task::spawn_blocking(move || {
+            cf.ingest_external_file(path)
+        }).await.unwrap()
+    }
+
+    /// Register a validator that every value written to column must pass.
+    pub fn add_validator(&self, column: &[u8], validator: Validator) {
+        self.inner.add_validator(column, validator)
+    }
+
+    /// Remove all validators of column.
+    pub fn clear_validators(&self, column: &[u8]) {
+        self.inner.clear_validators(column)
+    }
+
+    /// Register an observer of this column family's operations. Its hooks receive the
+    /// synchronous column family and run on blocking threads, never on the runtime.
+    pub fn add_observer(&self, observer: impl Observer + 'static) {
+        self.inner.add_observer(observer)
+    }
+
+    /// Remove all observers.
+    pub fn clear_observers(&self) {
+        self.inner.clear_observers()
+    }
+
+    /// Check value against the validators of column, as a put would.
+    pub fn validate(&self, row: &[u8], column: &[u8], value: &[u8]) -> IoResult<()> {
+        self.inner.validate(row, column, value)
+    }
+
+    /// Write entries with their own timestamps, e.g. when applying replicated changes.
+    pub(crate) async fn write_entries(&self, entries: Vec<Entry>) -> IoResult<()> {
+        self.inner.submit_entries(entries, None).await
+    }
+
+    /// Write entries sent with a request ID, see the sync `write_request`.
+    pub(crate) async fn write_request(&self, entries: Vec<Entry>, request_id: Option<String>) -> IoResult<()> {
+        self.inner.submit_entries(entries, request_id).await
+    }
+
+    /// Subscribe to a stream of every put and delete, in commit order.
+    pub fn subscribe(&self) -> impl Stream<Item = Entry> {
+        self.inner.subscribe()
+    }
+
+    /// Directory of this column family.
+    pub fn path(&self) -> &Path {
+        self.inner.path()
+    }
+
+    /// Adopt the SSTables of another column family or snapshot directory.
+    pub async fn import_cf(&self, source_dir: impl AsRef<Path>) -> IoResult<usize> {
+        let cf = self.inner.clone();
+        let source_dir = source_dir.as_ref().to_path_buf();
+        task::spawn_blocking(move || {
+            cf.import_cf(source_dir)
+        }).await.unwrap()
+    }
+
+    /// Take a named snapshot of the column family's SSTables.
+    pub async fn snapshot(&self, name: &str) -> IoResult<SnapshotManifest> {
+        let cf = self.inner.clone();
+        let name = name.to_string();
+        task::spawn_blocking(move || {
+            cf.snapshot(&name)
+        }).await.unwrap()
+    }
+
+    /// Roll the column family back to a named snapshot.
+    pub async fn restore_snapshot(&self, name: &str) -> IoResult<()> {
+        let cf = self.inner.clone();
+        let name = name.to_string();
+        task::spawn_blocking(move || {
+            cf.restore_snapshot(&name)
+        }).await.unwrap()
+    }
+
+    /// Delete every cell of the column family at once, keeping it open.
+    pub async fn truncate(&self) -> IoResult<()> {
+        let cf = self.inner.clone();
+        task::spawn_blocking(move || {
+            cf.truncate()
+        }).await.unwrap()
+    }
+
+    /// Start recording mutations in a changelog that other processes can tail.
+    pub async fn enable_changelog(&self) -> IoResult<()> {
+        let cf = self.inner.clone();
+        task::spawn_blocking(move || {
+            cf.enable_changelog()
+        }).await.unwrap()
+    }
+
+    /// Keep every rotated WAL in the WAL archive asynchronously, for `recover_to`.
+    pub async fn enable_wal_archive(&self) -> IoResult<()> {
+        let cf = self.inner.clone();
+        task::spawn_blocking(move || {
+            cf.enable_wal_archive()
+        }).await.unwrap()
+    }
+
+    /// Stop archiving WALs and delete the archive asynchronously.
+    pub async fn disable_wal_archive(&self) -> IoResult<()> {
+        let cf = self.inner.clone();
+        task::spawn_blocking(move || {
+            cf.disable_wal_archive()
+        }).await.unwrap()
+    }
+
+    /// When WAL archiving began and the recoveries made since, or `None` if it is off.
+    pub fn wal_archive_state(&self) -> Option<WalArchiveState> {
+        self.inner.wal_archive_state()
+    }
+
+    /// Roll the column family back to its state at timestamp from the WAL archive asynchronously.
+    pub async fn recover_to(&self, timestamp: Timestamp) -> IoResult<usize> {
+        let cf = self.inner.clone();
+        task::spawn_blocking(move || {
+            cf.recover_to(timestamp)
+        }).await.unwrap()
+    }
+
+    /// Freeze the column family: reject further writes, flush and run a final major compaction.
+    pub async fn freeze(&self) -> IoResult<()> {
+        let cf = self.inner.clone();
+        task::spawn_blocking(move || {
+            cf.freeze()
+        }).await.unwrap()
+    }
+
+    /// Whether the column family is frozen.
+    pub fn is_frozen(&self) -> bool {
+        self.inner.is_frozen()
+    }
+
     /// Compact all on-disk SSTables into one, preserving all versions (no dropping).
     pub async fn compact(&self) -> IoResult<()> {
         let cf = self.inner.clone();
@@ -151,6 +446,118 @@ impl ColumnFamily {
         }).await.unwrap()
     }
 
+    /// When the MemStore is flushed without an explicit `flush`.
+    pub fn flush_policy(&self) -> FlushPolicy {
+        self.inner.flush_policy()
+    }
+
+    /// Set when the MemStore is flushed without an explicit `flush` (not persisted).
+    pub fn set_flush_policy(&self, policy: FlushPolicy) {
+        self.inner.set_flush_policy(policy)
+    }
+
+    /// Size at which the active WAL is sealed as a segment.
+    pub fn wal_segment_bytes(&self) -> u64 {
+        self.inner.wal_segment_bytes()
+    }
+
+    /// Seal the active WAL as a segment once it holds bytes (not persisted).
+    pub fn set_wal_segment_bytes(&self, bytes: u64) {
+        self.inner.set_wal_segment_bytes(bytes)
+    }
+
+    /// Approximate memory taken by the entries buffered in the MemStore.
+    pub fn memstore_bytes(&self) -> u64 {
+        self.inner.memstore_bytes()
+    }
+
+    /// Timestamp for a write made now, from the column family's hybrid logical clock.
+    pub fn now(&self) -> Timestamp {
+        self.inner.now()
+    }
+
+    /// Set the policy for merging small SSTables after flushes, or disable merging with `None`.
+    pub fn set_small_file_merge(&self, policy: Option<SmallFileMergePolicy>) {
+        self.inner.set_small_file_merge(policy)
+    }
+
+    /// Throttling applied to writes, if any.
+    pub fn write_throttle(&self) -> Option<WriteThrottle> {
+        self.inner.write_throttle()
+    }
+
+    /// Delay or reject writes while compactions fall behind (not persisted). A group of
+    /// queued writes waits once, holding up the writes queued behind it.
+    pub fn set_write_throttle(&self, throttle: Option<WriteThrottle>) {
+        self.inner.set_write_throttle(throttle)
+    }
+
+    /// The running compaction, the latest finished ones and totals since the process opened
+    /// this column family.
+    pub fn compaction_stats(&self) -> CompactionStats {
+        self.inner.compaction_stats()
+    }
+
+    /// Flushes, WAL bytes written and write stalls since the process opened this column
+    /// family.
+    pub fn write_stats(&self) -> WriteStats {
+        self.inner.write_stats()
+    }
+
+    /// Limits on the I/O rate and concurrency of compactions and small-file merges.
+    pub fn compaction_throttle(&self) -> CompactionThrottle {
+        self.inner.compaction_throttle()
+    }
+
+    /// Limit the compactions and small-file merges of this column family (not persisted).
+    pub fn set_compaction_throttle(&self, throttle: CompactionThrottle) {
+        self.inner.set_compaction_throttle(throttle)
+    }
+
+    /// How compactions of this column family are split to run in parallel.
+    pub fn compaction_parallelism(&self) -> CompactionParallelism {
+        self.inner.compaction_parallelism()
+    }
+
+    /// Split large compactions into partitions compacted in parallel (not persisted).
+    pub fn set_compaction_parallelism(&self, parallelism: CompactionParallelism) {
+        self.inner.set_compaction_parallelism(parallelism)
+    }
+
+    /// Set the capacity of the block cache in bytes (not persisted); 0 disables caching.
+    pub fn set_block_cache_capacity(&self, capacity_bytes: usize) {
+        self.inner.set_block_cache_capacity(capacity_bytes)
+    }
+
+    /// Usage and hit counters of the block cache.
+    pub fn block_cache_stats(&self) -> BlockCacheStats {
+        self.inner.block_cache_stats()
+    }
+
+    /// Retention settings this column family was created with.
+    pub fn options(&self) -> ColumnFamilyOptions {
+        self.inner.options()
+    }
+
+    /// How far SSTable writes and removals are synced to disk.
+    pub fn durability(&self) -> Durability {
+        self.inner.durability()
+    }
+
+    /// Set how far SSTable writes and removals are synced to disk (not persisted).
+    pub fn set_durability(&self, durability: Durability) {
+        self.inner.set_durability(durability)
+    }
+
+    /// Merge the newest small SSTables into one if the merge policy's trigger is met.
+    /// Returns the number of SSTables merged.
+    pub async fn merge_small_sstables(&self) -> IoResult<usize> {
+        let cf = self.inner.clone();
+        task::spawn_blocking(move || {
+            cf.merge_small_sstables()
+        }).await.unwrap()
+    }
+
     /// Run a major compaction that merges all SSTables into one.
     pub async fn major_compact(&self) -> IoResult<()> {
         let cf = self.inner.clone();
@@ -191,7 +598,7 @@ impl ColumnFamily {
         &self,
         row: &[u8],
         filter_set: &FilterSet,
-    ) -> IoResult<BTreeMap<Column, Vec<(Timestamp, Vec<u8>)>>> {
+    ) -> IoResult<RowVersions> {
         let cf = self.inner.clone();
         let row = row.to_vec();
         let filter_set = filter_set.clone();
@@ -206,7 +613,7 @@ impl ColumnFamily {
         start_row: &[u8],
         end_row: &[u8],
         filter_set: &FilterSet,
-    ) -> IoResult<BTreeMap<RowKey, BTreeMap<Column, Vec<(Timestamp, Vec<u8>)>>>> {
+    ) -> IoResult<RangeVersions> {
         let cf = self.inner.clone();
         let start_row = start_row.to_vec();
         let end_row = end_row.to_vec();
@@ -216,6 +623,114 @@ impl ColumnFamily {
         }).await.unwrap()
     }
 
+    /// Like `scan_with_filter`, reading SSTables and filtering rows on up to max_threads threads.
+    pub async fn par_scan_with_filter(
+        &self,
+        start_row: &[u8],
+        end_row: &[u8],
+        filter_set: &FilterSet,
+        max_threads: usize,
+    ) -> IoResult<RangeVersions> {
+        let cf = self.inner.clone();
+        let start_row = start_row.to_vec();
+        let end_row = end_row.to_vec();
+        let filter_set = filter_set.clone();
+        task::spawn_blocking(move || {
+            cf.par_scan_with_filter(&start_row, &end_row, &filter_set, max_threads)
+        }).await.unwrap()
+    }
+
+    /// Execute a Scan operation.
+    pub async fn execute_scan(&self, scan: Scan) -> IoResult<RangeVersions> {
+        let cf = self.inner.clone();
+        task::spawn_blocking(move || {
+            cf.execute_scan(&scan)
+        }).await.unwrap()
+    }
+
+    /// Execute a Scan operation, returning its rows as `Row`s.
+    pub async fn scan_rows(&self, scan: Scan) -> IoResult<Vec<Row>> {
+        let cf = self.inner.clone();
+        task::spawn_blocking(move || {
+            cf.scan_rows(&scan)
+        }).await.unwrap()
+    }
+
+    /// Read the rows whose key starts with prefix as `Row`s, skipping the SSTables
+    /// whose prefix bloom filter rules it out.
+    pub async fn scan_prefix(&self, prefix: &[u8], max_versions: usize) -> IoResult<Vec<Row>> {
+        let cf = self.inner.clone();
+        let prefix = prefix.to_vec();
+        task::spawn_blocking(move || {
+            cf.scan_prefix(&prefix, max_versions)?.map(|row| row.map(Row::from)).collect()
+        }).await.unwrap()
+    }
+
+    /// Scan a range of rows into one batch, truncated at `options.max_response_bytes`
+    pub async fn scan_with_options(
+        &self,
+        start_row: &[u8],
+        end_row: &[u8],
+        options: &ScanOptions,
+    ) -> IoResult<ScanBatch> {
+        let cf = self.inner.clone();
+        let start_row = start_row.to_vec();
+        let end_row = end_row.to_vec();
+        let options = *options;
+        task::spawn_blocking(move || {
+            cf.scan_with_options(&start_row, &end_row, &options)
+        }).await.unwrap()
+    }
+
+    /// Continue a batched scan at the position of token, see `scan_with_options`
+    pub async fn resume_scan(
+        &self,
+        token: &ResumeToken,
+        end_row: &[u8],
+        options: &ScanOptions,
+    ) -> IoResult<ScanBatch> {
+        let cf = self.inner.clone();
+        let token = token.clone();
+        let end_row = end_row.to_vec();
+        let options = *options;
+        task::spawn_blocking(move || {
+            cf.resume_scan(&token, &end_row, &options)
+        }).await.unwrap()
+    }
+
+    /// Count the rows in a range that have at least one visible cell
+    pub async fn count_rows(
+        &self,
+        start_row: &[u8],
+        end_row: &[u8],
+        filter_set: Option<&FilterSet>,
+    ) -> IoResult<u64> {
+        let cf = self.inner.clone();
+        let start_row = start_row.to_vec();
+        let end_row = end_row.to_vec();
+        let filter_set = filter_set.cloned();
+        task::spawn_blocking(move || {
+            cf.count_rows(&start_row, &end_row, filter_set.as_ref())
+        }).await.unwrap()
+    }
+
+    /// Count the rows in a range, failing with `TimedOut` once deadline has passed
+    pub async fn count_rows_until(
+        &self,
+        start_row: &[u8],
+        end_row: &[u8],
+        filter_set: Option<&FilterSet>,
+        deadline: Instant,
+    ) -> IoResult<u64> {
+        let cf = self.inner.clone();
+        let start_row = start_row.to_vec();
+        let end_row = end_row.to_vec();
+        let filter_set = filter_set.cloned();
+        task::spawn_blocking(move || {
+            cf.count_rows_until(&start_row, &end_row, filter_set.as_ref(), deadline)
+        }).await.unwrap()
+    }
+
     /// Perform aggregations on query results
     pub async fn aggregate(
         &self,
@@ -250,6 +765,25 @@ impl ColumnFamily {
         }).await.unwrap()
     }
 
+    /// Perform aggregations on multiple rows, failing with `TimedOut` once deadline has passed
+    pub async fn aggregate_range_until(
+        &self,
+        start_row: &[u8],
+        end_row: &[u8],
+        filter_set: Option<&FilterSet>,
+        aggregation_set: &AggregationSet,
+        deadline: Instant,
+    ) -> IoResult<BTreeMap<RowKey, BTreeMap<Column, AggregationResult>>> {
+        let cf = self.inner.clone();
+        let start_row = start_row.to_vec();
+        let end_row = end_row.to_vec();
+        let filter_set = filter_set.cloned();
+        let aggregation_set = aggregation_set.clone();
+        task::spawn_blocking(move || {
+            cf.aggregate_range_until(&start_row, &end_row, filter_set.as_ref(), &aggregation_set, deadline)
+        }).await.unwrap()
+    }
+
     /// Compact SSTables with the specified options.
     pub async fn compact_with_options(&self, options: CompactionOptions) -> IoResult<()> {
         let cf = self.inner.clone();
@@ -257,12 +791,40 @@ impl ColumnFamily {
             cf.compact_with_options(options)
         }).await.unwrap()
     }
+
+    /// Drop expired versions and tombstones past their TTL from the SSTables now.
+    pub async fn expire_now(&self) -> IoResult<u64> {
+        let cf = self.inner.clone();
+        task::spawn_blocking(move || {
+            cf.expire_now()
+        }).await.unwrap()
+    }
+
+    /// The interval of scheduled expiry sweeps, if any.
+    pub fn expiry_interval(&self) -> Option<Duration> {
+        self.inner.expiry_interval()
+    }
+
+    /// Sweep expired entries every interval on a background thread, or stop with `None`
+    /// (not persisted).
+    pub fn set_expiry_interval(&self, interval: Option<Duration>) {
+        self.inner.set_expiry_interval(interval)
+    }
+
+    /// Compact only the SSTables holding rows in [start_row, end_row).
+    pub async fn compact_range(&self, start_row: &[u8], end_row: &[u8], options: CompactionOptions) -> IoResult<()> {
+        let cf = self.inner.clone();
+        let start_row = start_row.to_vec();
+        let end_row = end_row.to_vec();
+        task::spawn_blocking(move || {
+            cf.compact_range(&start_row, &end_row, options)
+        }).await.unwrap()
+    }
 }
 
 /// Async wrapper around the synchronous Table
 #[derive(Clone)]
 pub struct Table {
-    path: PathBuf,
     inner: Arc<SyncTable>,
 }
 
@@ -270,14 +832,12 @@ impl Table {
     /// Open (or create) a table directory asynchronously.
     pub async fn open(table_dir: impl AsRef<Path>) -> IoResult<Self> {
         let path = table_dir.as_ref().to_path_buf();
-        let path_clone = path.clone();
 
         let inner = task::spawn_blocking(move || {
-            SyncTable::open(path_clone)
+            SyncTable::open(path)
         }).await.unwrap()?;
 
         Ok(Self {
-            path,
             inner: Arc::new(inner),
         })
     }
@@ -288,30 +848,207 @@ impl Table {
         let cf_name = cf_name.to_string();
 
         task::spawn_blocking(move || {
-            let mut table = inner.as_ref().clone();
-            table.create_cf(&cf_name)
+            inner.create_cf(&cf_name)
         }).await.unwrap()
     }
 
-    /// Retrieve a handle to an existing ColumnFamily (or None if it doesn't exist).
-    /// If the column family doesn't exist but was created earlier in the same process,
-    /// this method will attempt to find it by opening the table directory again.
-    pub async fn cf(&self, cf_name: &str) -> Option<ColumnFamily> {
+    /// Create a new column family named cf_name with retention options asynchronously.
+    /// Fails if it already exists.
+    pub async fn create_cf_with_options(&self, cf_name: &str, options: ColumnFamilyOptions) -> IoResult<()> {
         let inner = self.inner.clone();
         let cf_name = cf_name.to_string();
-        let path = self.path.clone();
 
-        let sync_cf = task::spawn_blocking(move || {
-            if let Some(cf) = inner.as_ref().clone().cf(&cf_name) {
-                return Some(cf);
-            }
+        task::spawn_blocking(move || {
+            inner.create_cf_with_options(&cf_name, options)
+        }).await.unwrap()
+    }
+
+    /// Change the retention settings of column family cf_name asynchronously.
+    pub async fn alter_cf(&self, cf_name: &str, options: ColumnFamilyOptions) -> IoResult<()> {
+        let inner = self.inner.clone();
+        let cf_name = cf_name.to_string();
+
+        task::spawn_blocking(move || {
+            inner.alter_cf(&cf_name, options)
+        }).await.unwrap()
+    }
+
+    /// The persisted schema of this table.
+    pub async fn schema(&self) -> IoResult<TableSchema> {
+        let inner = self.inner.clone();
+        task::spawn_blocking(move || inner.schema()).await.unwrap()
+    }
+
+    /// Names of all column families in this table, in sorted order.
+    pub async fn cf_names(&self) -> IoResult<Vec<String>> {
+        Ok(self.inner.cf_names())
+    }
+
+    /// Names of the column families whose background threads have stopped, in sorted order.
+    pub fn stopped_background_threads(&self) -> Vec<String> {
+        self.inner.stopped_background_threads()
+    }
+
+    /// Cap the memory taken by the MemStores of all column families together, or lift
+    /// the cap with `None` (not persisted).
+    pub fn set_memstore_limit(&self, limit: Option<u64>) {
+        self.inner.set_memstore_limit(limit)
+    }
+
+    /// The cap on the MemStores of all column families together, if any.
+    pub fn memstore_limit(&self) -> Option<u64> {
+        self.inner.memstore_limit()
+    }
+
+    /// Approximate memory taken by the MemStores of all column families together.
+    pub fn memstore_bytes(&self) -> u64 {
+        self.inner.memstore_bytes()
+    }
+
+    /// Cap the memory taken by the MemStores and block caches of all column families
+    /// together, or lift the cap with `None` (not persisted).
+    pub fn set_memory_budget(&self, budget: Option<MemoryBudget>) {
+        self.inner.set_memory_budget(budget)
+    }
+
+    /// The memory budget of the table, if any.
+    pub fn memory_budget(&self) -> Option<MemoryBudget> {
+        self.inner.memory_budget()
+    }
+
+    /// Memory taken by the MemStores and block caches of all column families together.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.inner.memory_usage()
+    }
+
+    /// Run a SQL-like query against the column family it names asynchronously.
+    pub async fn query(&self, sql: &str) -> IoResult<QueryResult> {
+        let inner = self.inner.clone();
+        let sql = sql.to_string();
+        task::spawn_blocking(move || {
+            inner.query(&sql)
+        }).await.unwrap()
+    }
+
+    /// Like `query`, but give up with `TimedOut` once deadline has passed.
+    pub async fn query_until(&self, sql: &str, deadline: Instant) -> IoResult<QueryResult> {
+        let inner = self.inner.clone();
+        let sql = sql.to_string();
+        task::spawn_blocking(move || {
+            inner.query_until(&sql, deadline)
+        }).await.unwrap()
+    }
+
+    /// Apply the puts and deletes of mutation to their column families atomically.
+    pub async fn mutate_row(&self, mutation: RowMutation) -> IoResult<()> {
+        let inner = self.inner.clone();
+        task::spawn_blocking(move || {
+            inner.mutate_row(&mutation)
+        }).await.unwrap()
+    }
+
+    /// Get the latest value of each (column family, column) in cells under row, in order.
+    pub async fn get(&self, row: &[u8], cells: Vec<(String, Column)>) -> IoResult<Vec<Option<Vec<u8>>>> {
+        let inner = self.inner.clone();
+        let row = row.to_vec();
+        task::spawn_blocking(move || {
+            inner.get(&row, &cells)
+        }).await.unwrap()
+    }
+
+    /// Execute a Get operation in every column family it selects.
+    pub async fn execute_get(&self, get: Get) -> IoResult<FamilyVersions> {
+        let inner = self.inner.clone();
+        task::spawn_blocking(move || {
+            inner.execute_get(&get)
+        }).await.unwrap()
+    }
+
+    /// The latest value of every column of row in every column family.
+    pub async fn scan_row(&self, row: &[u8]) -> IoResult<FamilyVersions> {
+        self.scan_row_versions(row, 1).await
+    }
+
+    /// Up to max_versions_per_column versions of every column of row in every column family.
+    pub async fn scan_row_versions(&self, row: &[u8], max_versions_per_column: usize) -> IoResult<FamilyVersions> {
+        let inner = self.inner.clone();
+        let row = row.to_vec();
+        task::spawn_blocking(move || {
+            inner.scan_row_versions(&row, max_versions_per_column)
+        }).await.unwrap()
+    }
+
+    /// Flush the MemStore of every column family asynchronously.
+    pub async fn flush_all(&self) -> IoResult<Vec<String>> {
+        let inner = self.inner.clone();
+        task::spawn_blocking(move || inner.flush_all()).await.unwrap()
+    }
 
-            match SyncTable::open(&path) {
-                Ok(fresh_table) => fresh_table.cf(&cf_name),
-                Err(_) => None
-            }
-        }).await.unwrap();
+    /// Run a major compaction of every column family asynchronously.
+    pub async fn major_compact(&self) -> IoResult<Vec<String>> {
+        let inner = self.inner.clone();
+        task::spawn_blocking(move || inner.major_compact()).await.unwrap()
+    }
+
+    /// Take a snapshot named name of every column family asynchronously.
+    pub async fn snapshot(&self, name: &str) -> IoResult<()> {
+        let inner = self.inner.clone();
+        let name = name.to_string();
+        task::spawn_blocking(move || {
+            inner.snapshot(&name)
+        }).await.unwrap()
+    }
 
-        sync_cf.map(ColumnFamily::new)
+    /// Roll back every column family that has a snapshot named name asynchronously.
+    pub async fn restore_snapshot(&self, name: &str) -> IoResult<()> {
+        let inner = self.inner.clone();
+        let name = name.to_string();
+        task::spawn_blocking(move || {
+            inner.restore_snapshot(&name)
+        }).await.unwrap()
+    }
+
+    /// Back the table up to a single archive at path asynchronously.
+    pub async fn backup_to(&self, path: impl AsRef<Path>) -> IoResult<BackupManifest> {
+        let inner = self.inner.clone();
+        let path = path.as_ref().to_path_buf();
+        task::spawn_blocking(move || {
+            inner.backup_to(path)
+        }).await.unwrap()
+    }
+
+    /// Back the table up into the backup directory backup_dir asynchronously, incrementally
+    /// after the first backup.
+    pub async fn backup_incremental(&self, backup_dir: impl AsRef<Path>) -> IoResult<BackupManifest> {
+        let inner = self.inner.clone();
+        let backup_dir = backup_dir.as_ref().to_path_buf();
+        task::spawn_blocking(move || {
+            inner.backup_incremental(backup_dir)
+        }).await.unwrap()
+    }
+
+    /// Restore the backup archive at archive into table_dir asynchronously, and open it.
+    pub async fn restore_from(archive: impl AsRef<Path>, table_dir: impl AsRef<Path>) -> IoResult<Self> {
+        let archive = archive.as_ref().to_path_buf();
+        let table_dir = table_dir.as_ref().to_path_buf();
+        let inner = task::spawn_blocking(move || {
+            SyncTable::restore_from(archive, table_dir)
+        }).await.unwrap()?;
+        Ok(Self { inner: Arc::new(inner) })
+    }
+
+    /// Create a copy-on-write clone of the table at new_path asynchronously, and open it.
+    pub async fn clone_to(&self, new_path: impl AsRef<Path>) -> IoResult<Self> {
+        let inner = self.inner.clone();
+        let new_path = new_path.as_ref().to_path_buf();
+        let cloned = task::spawn_blocking(move || {
+            inner.clone_to(new_path)
+        }).await.unwrap()?;
+        Ok(Self { inner: Arc::new(cloned) })
+    }
+
+    /// Retrieve a handle to an existing ColumnFamily (or None if it doesn't exist).
+    pub async fn cf(&self, cf_name: &str) -> Option<ColumnFamily> {
+        self.inner.cf(cf_name).map(ColumnFamily::new)
     }
 }
@@ -0,0 +1,468 @@
+//! Backups of a table to a single archive file.
+//!
+//! Snapshots live inside the column family directories, on the same disk as the data.
+//! `Table::backup_to` writes one tar archive holding the table's schema and, for every
+//! column family, its SSTables, range tombstones and a copy of its WAL taken under the
+//! MemStore lock, so each column family is captured at one point without a flush. A
+//! `BACKUP.json` manifest ends the archive, listing every file with its size and CRC32;
+//! `Table::restore_from` extracts into a staging directory and checks them all before
+//! the restored table appears under its final name.
+//!
+//! Copying every SSTable each night is wasteful when most of them have not changed.
+//! `Table::backup_incremental` keeps numbered archives in a backup directory, listed in
+//! a `backups.json` catalog. The first is a full backup; each later one records the
+//! whole live SSTable set of every column family but only holds the SSTables that the
+//! previous backup did not have (they are immutable and named by sequence, so a name
+//! and size identify one) and the bytes appended to the WAL since, unless it was
+//! rotated by a flush. Restoring one replays the chain of archives it builds on.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufWriter, Error, ErrorKind, Read, Result as IoResult, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::api::RangeTombstone;
+use crate::manifest::PinnedFiles;
+use crate::storage::Durability;
+
+/// Name of the manifest, the last member of a backup archive.
+pub const BACKUP_MANIFEST: &str = "BACKUP.json";
+
+/// Catalog of the archives in a backup directory, see `BackupCatalog`.
+pub const BACKUP_CATALOG: &str = "backups.json";
+
+/// The content of a backup archive, for checking it on restore and for taking the next
+/// incremental backup.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BackupManifest {
+    /// When the backup was taken (ms since the epoch)
+    pub created_at: u64,
+    /// File name of the archive
+    pub archive: String,
+    /// File name of the archive this one is incremental to, in the same directory, or
+    /// `None` for a full backup
+    pub base: Option<String>,
+    /// What was captured of each column family, by name
+    pub column_families: BTreeMap<String, CfBackupState>,
+    /// Every other member of the archive, in archive order
+    pub files: Vec<BackupFile>,
+}
+
+/// The state of a column family when it was backed up.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct CfBackupState {
+    /// Size of every live SSTable, by file name, whether copied into this archive or not
+    pub sst_files: BTreeMap<String, u64>,
+    /// Length and CRC32 of the whole WAL
+    pub wal_len: u64,
+    pub wal_crc32: u32,
+    /// Offset in the WAL of the bytes in the archive: 0 if it holds the whole WAL as
+    /// `wal.log`, otherwise it holds those appended since the base backup as `wal.delta`
+    pub wal_from: u64,
+}
+
+/// One member of a backup archive.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BackupFile {
+    /// Path in the archive, relative to the table directory, with `/` separators
+    pub path: String,
+    pub size: u64,
+    pub crc32: u32,
+}
+
+/// What a backup holds of a column family, captured at one point: the content of its
+/// WAL and its range tombstones, and its SSTables, pinned until this is dropped.
+pub(crate) struct CfBackup {
+    pub wal: Vec<u8>,
+    pub sst_files: PinnedFiles,
+    pub range_tombstones: Vec<RangeTombstone>,
+}
+
+/// Reader computing the CRC32 of what it reads.
+struct CrcReader<R> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+    size: u64,
+}
+
+impl<R: Read> Read for CrcReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.size += n as u64;
+        Ok(n)
+    }
+}
+
+/// Streams files into a backup archive, written under a temporary name and renamed
+/// into place by `finish`.
+pub(crate) struct BackupWriter {
+    builder: tar::Builder<BufWriter<File>>,
+    manifest: BackupManifest,
+    /// Column families of the base backup
+    base: BTreeMap<String, CfBackupState>,
+    path: PathBuf,
+    tmp: PathBuf,
+}
+
+impl BackupWriter {
+    /// Start an archive at path, incremental to base if any. Fails with `AlreadyExists`
+    /// if there is a file there.
+    pub fn create(path: &Path, base: Option<&BackupManifest>) -> IoResult<Self> {
+        if path.exists() {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("Backup {} already exists", path.display()),
+            ));
+        }
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let builder = tar::Builder::new(BufWriter::new(File::create(&tmp)?));
+        Ok(Self {
+            builder,
+            manifest: BackupManifest {
+                created_at: chrono::Utc::now().timestamp_millis() as u64,
+                archive: path.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+                base: base.map(|base| base.archive.clone()),
+                column_families: BTreeMap::new(),
+                files: Vec::new(),
+            },
+            base: base.map(|base| base.column_families.clone()).unwrap_or_default(),
+            path: path.to_path_buf(),
+            tmp,
+        })
+    }
+
+    /// Add data as the member name.
+    pub fn add_bytes(&mut self, name: &str, data: &[u8]) -> IoResult<()> {
+        self.append(name, data.len() as u64, data)
+    }
+
+    /// Add the file at src as the member name.
+    pub fn add_file(&mut self, name: &str, src: &Path) -> IoResult<()> {
+        let file = File::open(src)?;
+        let size = file.metadata()?.len();
+        self.append(name, size, file)
+    }
+
+    /// Add a column family's capture as the members under cf_name: all of it, or only
+    /// what the base backup does not hold.
+    pub fn add_cf(&mut self, cf_name: &str, capture: &CfBackup) -> IoResult<()> {
+        let base = self.base.get(cf_name).cloned().unwrap_or_default();
+        let mut state = CfBackupState {
+            wal_len: capture.wal.len() as u64,
+            wal_crc32: crc32fast::hash(&capture.wal),
+            ..Default::default()
+        };
+        for sst in capture.sst_files.iter() {
+            let file_name = sst.file_name().unwrap().to_string_lossy().into_owned();
+            let size = fs::metadata(sst)?.len();
+            if base.sst_files.get(&file_name) != Some(&size) {
+                self.add_file(&format!("{}/{}", cf_name, file_name), sst)?;
+            }
+            state.sst_files.insert(file_name, size);
+        }
+
+        // The WAL only grows until a flush rotates it, after which it no longer starts
+        // with the bytes the base backup saw
+        let appended = base.wal_len > 0
+            && capture.wal.len() as u64 >= base.wal_len
+            && crc32fast::hash(&capture.wal[..base.wal_len as usize]) == base.wal_crc32;
+        if appended {
+            state.wal_from = base.wal_len;
+            self.add_bytes(&format!("{}/wal.delta", cf_name), &capture.wal[base.wal_len as usize..])?;
+        } else {
+            self.add_bytes(&format!("{}/wal.log", cf_name), &capture.wal)?;
+        }
+
+        let json = serde_json::to_vec_pretty(&capture.range_tombstones).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        self.add_bytes(&format!("{}/range_tombstones.json", cf_name), &json)?;
+        self.manifest.column_families.insert(cf_name.to_string(), state);
+        Ok(())
+    }
+
+    fn append(&mut self, name: &str, size: u64, data: impl Read) -> IoResult<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(size);
+        header.set_mode(0o644);
+        header.set_mtime(self.manifest.created_at / 1000);
+        let mut reader = CrcReader { inner: data.take(size), hasher: crc32fast::Hasher::new(), size: 0 };
+        self.builder.append_data(&mut header, name, &mut reader)?;
+        if reader.size != size {
+            return Err(Error::new(ErrorKind::UnexpectedEof, format!("{} shrank while it was backed up", name)));
+        }
+        self.manifest.files.push(BackupFile { path: name.to_string(), size, crc32: reader.hasher.finalize() });
+        Ok(())
+    }
+
+    /// Write the manifest, sync the archive and move it into place.
+    pub fn finish(mut self) -> IoResult<BackupManifest> {
+        let json = serde_json::to_vec_pretty(&self.manifest).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(json.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(self.manifest.created_at / 1000);
+        self.builder.append_data(&mut header, BACKUP_MANIFEST, json.as_slice())?;
+        self.builder.finish()?;
+        self.builder.get_mut().flush()?;
+        self.builder.get_ref().get_ref().sync_all()?;
+        fs::rename(&self.tmp, &self.path)?;
+        Durability::Sync.sync_parent(&self.path)?;
+        info!(backup = %self.path.display(), files = self.manifest.files.len(), "wrote backup");
+        Ok(self.manifest.clone())
+    }
+}
+
+impl Drop for BackupWriter {
+    fn drop(&mut self) {
+        // Once finished the archive has been renamed; otherwise discard the partial one
+        let _ = fs::remove_file(&self.tmp);
+    }
+}
+
+/// The path of an archive member under dir. Fails with `InvalidData` unless name is
+/// relative and stays inside dir.
+fn member_path(dir: &Path, name: &Path) -> IoResult<PathBuf> {
+    if name.as_os_str().is_empty() || !name.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Invalid path {} in backup archive", name.display()),
+        ));
+    }
+    Ok(dir.join(name))
+}
+
+/// Extract a chain of backup archives, a full backup followed by the incremental
+/// backups built on it in order, into dir, creating it. Every member is checked against
+/// the manifest of its archive, and every column family against the state recorded by
+/// the last one. Fails with `InvalidData` if a check fails; dir is removed then.
+pub(crate) fn extract(chain: &[PathBuf], dir: &Path) -> IoResult<BackupManifest> {
+    fs::create_dir_all(dir)?;
+    let result = extract_chain(chain, dir);
+    if result.is_err() {
+        let _ = fs::remove_dir_all(dir);
+    }
+    result
+}
+
+fn extract_chain(chain: &[PathBuf], dir: &Path) -> IoResult<BackupManifest> {
+    let mut last = None;
+    for archive in chain {
+        let bad = |reason: String| Error::new(ErrorKind::InvalidData, format!("Bad backup {}: {}", archive.display(), reason));
+        let manifest = extract_checked(archive, dir)?;
+        // Append each WAL delta to the WAL restored so far
+        for (cf_name, state) in manifest.column_families.iter().filter(|(_, state)| state.wal_from > 0) {
+            let wal_path = dir.join(cf_name).join("wal.log");
+            let delta_path = dir.join(cf_name).join("wal.delta");
+            let mut wal = fs::read(&wal_path).unwrap_or_default();
+            if wal.len() as u64 != state.wal_from {
+                return Err(bad(format!("the WAL of {} does not follow the previous backup", cf_name)));
+            }
+            wal.extend_from_slice(&fs::read(&delta_path)?);
+            fs::write(&wal_path, &wal)?;
+            fs::remove_file(&delta_path)?;
+        }
+        last = Some((archive, manifest));
+    }
+    let Some((archive, manifest)) = last else {
+        return Err(Error::new(ErrorKind::InvalidInput, "No backup to restore"));
+    };
+    let bad = |reason: String| Error::new(ErrorKind::InvalidData, format!("Bad backup {}: {}", archive.display(), reason));
+
+    // Keep what the last backup saw: column families and SSTables since dropped go
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if entry.file_type()?.is_dir() && !manifest.column_families.contains_key(&name) {
+            fs::remove_dir_all(entry.path())?;
+        }
+    }
+    for (cf_name, state) in &manifest.column_families {
+        let cf_dir = dir.join(cf_name);
+        for entry in fs::read_dir(&cf_dir)? {
+            let path = entry?.path();
+            let file_name = path.file_name().unwrap().to_string_lossy().into_owned();
+            if path.extension().is_some_and(|ext| ext == "sst") && !state.sst_files.contains_key(&file_name) {
+                fs::remove_file(&path)?;
+            }
+        }
+        for (file_name, &size) in &state.sst_files {
+            match fs::metadata(cf_dir.join(file_name)) {
+                Ok(metadata) if metadata.len() == size => {}
+                _ => return Err(bad(format!("{}/{} is missing from the backup chain", cf_name, file_name))),
+            }
+        }
+        let wal = fs::read(cf_dir.join("wal.log"))?;
+        if (wal.len() as u64, crc32fast::hash(&wal)) != (state.wal_len, state.wal_crc32) {
+            return Err(bad(format!("the restored WAL of {} does not match its checksum", cf_name)));
+        }
+    }
+    Ok(manifest)
+}
+
+/// Backups taken into a directory by `Table::backup_incremental`, oldest first.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct BackupCatalog {
+    pub backups: Vec<BackupManifest>,
+}
+
+impl BackupCatalog {
+    /// Read the catalog of the backup directory dir, empty if it has none.
+    pub fn load(dir: &Path) -> IoResult<Self> {
+        let path = dir.join(BACKUP_CATALOG);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        serde_json::from_slice(&fs::read(&path)?).map_err(|e| {
+            Error::new(ErrorKind::InvalidData, format!("Corrupt {}: {}", path.display(), e))
+        })
+    }
+
+    /// Replace the catalog file of the backup directory dir.
+    pub(crate) fn save(&self, dir: &Path) -> IoResult<()> {
+        let json = serde_json::to_vec_pretty(self).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let path = dir.join(BACKUP_CATALOG);
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&json)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        Durability::Sync.sync_dir(dir)
+    }
+
+    /// The backups needed to restore archive, oldest first: the full backup it builds
+    /// on, then every incremental one up to it. Fails with `NotFound` if one is not in
+    /// the catalog.
+    pub fn chain(&self, archive: &str) -> IoResult<Vec<&BackupManifest>> {
+        let find = |name: &str| {
+            self.backups.iter().find(|backup| backup.archive == name).ok_or_else(|| {
+                Error::new(ErrorKind::NotFound, format!("Backup {} is not in the catalog", name))
+            })
+        };
+        let mut chain = vec![find(archive)?];
+        while let Some(base) = &chain.last().unwrap().base {
+            chain.push(find(base)?);
+        }
+        chain.reverse();
+        Ok(chain)
+    }
+}
+
+/// The archives to extract to restore path: an archive, or a backup directory whose
+/// latest backup is restored. An archive listed in the catalog of its directory brings
+/// the chain it builds on; any other is restored on its own.
+pub(crate) fn restore_chain(path: &Path) -> IoResult<Vec<PathBuf>> {
+    let (dir, archive) = if path.is_dir() {
+        let catalog = BackupCatalog::load(path)?;
+        let latest = catalog.backups.last().ok_or_else(|| {
+            Error::new(ErrorKind::NotFound, format!("No backups in {}", path.display()))
+        })?;
+        (path, latest.archive.clone())
+    } else {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        (dir, path.file_name().unwrap_or_default().to_string_lossy().into_owned())
+    };
+    let catalog = BackupCatalog::load(dir)?;
+    if !catalog.backups.iter().any(|backup| backup.archive == archive) {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    Ok(catalog.chain(&archive)?.into_iter().map(|backup| dir.join(&backup.archive)).collect())
+}
+
+fn extract_checked(archive: &Path, dir: &Path) -> IoResult<BackupManifest> {
+    let bad = |reason: String| Error::new(ErrorKind::InvalidData, format!("Bad backup {}: {}", archive.display(), reason));
+    let mut tar = tar::Archive::new(io::BufReader::new(File::open(archive)?));
+    let mut found: BTreeMap<String, (u64, u32)> = BTreeMap::new();
+    let mut manifest: Option<BackupManifest> = None;
+
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.into_owned();
+        let key = name.to_string_lossy().replace('\\', "/");
+        if key == BACKUP_MANIFEST {
+            let mut json = Vec::new();
+            entry.read_to_end(&mut json)?;
+            manifest = Some(serde_json::from_slice(&json).map_err(|e| bad(e.to_string()))?);
+            continue;
+        }
+        let dst = member_path(dir, &name)?;
+        if let Some(parent) = dst.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut reader = CrcReader { inner: &mut entry, hasher: crc32fast::Hasher::new(), size: 0 };
+        let mut file = File::create(&dst)?;
+        io::copy(&mut reader, &mut file)?;
+        file.sync_all()?;
+        found.insert(key, (reader.size, reader.hasher.finalize()));
+    }
+
+    let manifest = manifest.ok_or_else(|| bad(format!("no {}", BACKUP_MANIFEST)))?;
+    for file in &manifest.files {
+        match found.remove(&file.path) {
+            None => return Err(bad(format!("{} is missing", file.path))),
+            Some((size, crc32)) if (size, crc32) != (file.size, file.crc32) => {
+                return Err(bad(format!("{} does not match its checksum", file.path)))
+            }
+            Some(_) => {}
+        }
+    }
+    if let Some(extra) = found.keys().next() {
+        return Err(bad(format!("{} is not in the manifest", extra)));
+    }
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_archive_round_trip_and_checks() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("0000000001.sst");
+        fs::write(&src, b"sstable bytes").unwrap();
+        let archive = dir.path().join("backup.tar");
+        let capture = CfBackup {
+            wal: b"wal records".to_vec(),
+            sst_files: std::sync::Arc::new(crate::manifest::FileRefs::new()).pin(vec![src]),
+            range_tombstones: Vec::new(),
+        };
+
+        let mut writer = BackupWriter::create(&archive, None).unwrap();
+        writer.add_bytes("schema.json", b"{}").unwrap();
+        writer.add_cf("cf", &capture).unwrap();
+        let manifest = writer.finish().unwrap();
+        assert_eq!(manifest.archive, "backup.tar");
+        assert_eq!(manifest.files.len(), 4);
+        assert_eq!(manifest.files[1].crc32, crc32fast::hash(b"sstable bytes"));
+        assert_eq!(manifest.column_families["cf"].wal_len, 11);
+        assert_eq!(BackupWriter::create(&archive, None).err().unwrap().kind(), ErrorKind::AlreadyExists);
+
+        let restored = dir.path().join("restored");
+        assert_eq!(extract(std::slice::from_ref(&archive), &restored).unwrap(), manifest);
+        assert_eq!(fs::read(restored.join("cf/0000000001.sst")).unwrap(), b"sstable bytes");
+        assert_eq!(fs::read(restored.join("cf/wal.log")).unwrap(), b"wal records");
+
+        // A flipped byte in a member fails its checksum, and nothing is left behind
+        let mut data = fs::read(&archive).unwrap();
+        let at = data.windows(7).position(|w| w == b"sstable").unwrap();
+        data[at] ^= 0xff;
+        let corrupt = dir.path().join("corrupt.tar");
+        fs::write(&corrupt, &data).unwrap();
+        let err = extract(&[corrupt], &dir.path().join("bad")).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().contains("checksum"));
+        assert!(!dir.path().join("bad").exists());
+
+        // Members may not escape the directory
+        assert!(member_path(dir.path(), Path::new("../x")).is_err());
+        assert!(member_path(dir.path(), Path::new("/x")).is_err());
+    }
+}
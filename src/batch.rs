@@ -1,10 +1,9 @@
 use std::{
     collections::VecDeque,
     io::Result as IoResult,
-    sync::Arc,
 };
 
-use crate::api::{ColumnFamily as SyncColumnFamily, RowKey, Column};
+use crate::api::{ColumnFamily as SyncColumnFamily, CellValue, Column, Entry, EntryKey, RowKey, Timestamp};
 use crate::async_api::ColumnFamily as AsyncColumnFamily;
 
 /// Represents a single operation in a batch
@@ -18,15 +17,30 @@ pub enum BatchOperation {
 #[derive(Debug, Clone)]
 pub struct Batch {
     operations: VecDeque<BatchOperation>,
+    request_id: Option<String>,
 }
 
 impl Batch {
     pub fn new() -> Self {
         Self {
             operations: VecDeque::new(),
+            request_id: None,
         }
     }
 
+    /// Make the batch idempotent: once a batch with request ID id was executed, the
+    /// batches executed with the same ID do nothing, so a client can retry a batch that
+    /// timed out without writing it twice. IDs are remembered by the column family for
+    /// the last `memstore::REQUEST_WINDOW` batches that had one, across restarts.
+    pub fn set_request_id(&mut self, id: impl Into<String>) -> &mut Self {
+        self.request_id = Some(id.into());
+        self
+    }
+
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+
     pub fn put(&mut self, row: RowKey, column: Column, value: Vec<u8>) -> &mut Self {
         self.operations.push_back(BatchOperation::Put(row, column, value));
         self
@@ -50,8 +64,27 @@ impl Batch {
         self.operations.is_empty()
     }
 
+    /// Remove every operation, and the request ID.
     pub fn clear(&mut self) {
         self.operations.clear();
+        self.request_id = None;
+    }
+
+    /// The operations as entries written at timestamp, in order.
+    fn entries(&self, timestamp: Timestamp) -> Vec<Entry> {
+        self.operations.iter()
+            .map(|op| {
+                let (row, column, value) = match op {
+                    BatchOperation::Put(row, column, value) => (row, column, CellValue::Put(value.clone())),
+                    BatchOperation::Delete(row, column) => (row, column, CellValue::Delete(None)),
+                    BatchOperation::DeleteWithTTL(row, column, ttl_ms) => (row, column, CellValue::Delete(*ttl_ms)),
+                };
+                Entry {
+                    key: EntryKey { row: row.clone(), column: column.clone(), timestamp, seq: 0 },
+                    value,
+                }
+            })
+            .collect()
     }
 }
 
@@ -62,48 +95,27 @@ impl Default for Batch {
 }
 
 pub trait SyncBatchExt {
+    /// Apply every operation of batch at the current timestamp, under one MemStore lock
+    /// and with a single WAL write. Nothing is applied if any put fails validation, or
+    /// if a batch with the same request ID was applied before.
     fn execute_batch(&self, batch: &Batch) -> IoResult<()>;
 }
 
 impl SyncBatchExt for SyncColumnFamily {
     fn execute_batch(&self, batch: &Batch) -> IoResult<()> {
-        for op in &batch.operations {
-            match op {
-                BatchOperation::Put(row, column, value) => {
-                    self.put(row.clone(), column.clone(), value.clone())?;
-                }
-                BatchOperation::Delete(row, column) => {
-                    self.delete(row.clone(), column.clone())?;
-                }
-                BatchOperation::DeleteWithTTL(row, column, ttl_ms) => {
-                    self.delete_with_ttl(row.clone(), column.clone(), *ttl_ms)?;
-                }
-            }
-        }
-        Ok(())
+        self.write_request(batch.entries(self.now()), batch.request_id.clone())
     }
 }
 
+#[allow(async_fn_in_trait)]
 pub trait AsyncBatchExt {
+    /// Apply every operation of batch at the current timestamp, see `SyncBatchExt`.
     async fn execute_batch(&self, batch: &Batch) -> IoResult<()>;
 }
 
 impl AsyncBatchExt for AsyncColumnFamily {
     async fn execute_batch(&self, batch: &Batch) -> IoResult<()> {
-        for op in &batch.operations {
-            match op {
-                BatchOperation::Put(row, column, value) => {
-                    self.put(row.clone(), column.clone(), value.clone()).await?;
-                }
-                BatchOperation::Delete(row, column) => {
-                    self.delete(row.clone(), column.clone()).await?;
-                }
-                BatchOperation::DeleteWithTTL(row, column, ttl_ms) => {
-                    self.delete_with_ttl(row.clone(), column.clone(), *ttl_ms).await?;
-                }
-            }
-        }
-        Ok(())
+        self.write_request(batch.entries(self.now()), batch.request_id.clone()).await
     }
 }
 
@@ -118,7 +130,7 @@ mod tests {
         let dir = tempdir().unwrap();
         let table_path = dir.path();
 
-        let mut table = Table::open(table_path).unwrap();
+        let table = Table::open(table_path).unwrap();
         table.create_cf("test_cf").unwrap();
         let cf = table.cf("test_cf").unwrap();
 
@@ -144,6 +156,64 @@ mod tests {
         assert_eq!(cf.get(b"row2", b"col1").unwrap().unwrap(), b"value3");
     }
 
+    #[test]
+    fn test_batch_is_applied_in_order_and_replayed() {
+        let dir = tempdir().unwrap();
+        {
+            let table = Table::open(dir.path()).unwrap();
+            table.create_cf("test_cf").unwrap();
+            let cf = table.cf("test_cf").unwrap();
+
+            let mut batch = Batch::new();
+            batch.put(b"row1".to_vec(), b"col1".to_vec(), b"value1".to_vec())
+                 .delete(b"row1".to_vec(), b"col1".to_vec())
+                 .put(b"row2".to_vec(), b"col1".to_vec(), b"value2".to_vec());
+            cf.execute_batch(&batch).unwrap();
+            assert!(cf.get(b"row1", b"col1").unwrap().is_none());
+        }
+
+        // The batch was written to the WAL and is replayed on reopen
+        let table = Table::open(dir.path()).unwrap();
+        let cf = table.cf("test_cf").unwrap();
+        assert!(cf.get(b"row1", b"col1").unwrap().is_none());
+        assert_eq!(cf.get(b"row2", b"col1").unwrap().unwrap(), b"value2");
+    }
+
+    #[test]
+    fn test_batch_with_request_id_is_applied_once() {
+        let dir = tempdir().unwrap();
+        {
+            let table = Table::open(dir.path()).unwrap();
+            table.create_cf("test_cf").unwrap();
+            let cf = table.cf("test_cf").unwrap();
+
+            let mut batch = Batch::new();
+            batch.set_request_id("req-1").put(b"row1".to_vec(), b"col1".to_vec(), b"value1".to_vec());
+            cf.execute_batch(&batch).unwrap();
+            // A retry succeeds without writing a second version
+            cf.execute_batch(&batch).unwrap();
+            assert_eq!(cf.get_versions(b"row1", b"col1", 10).unwrap().len(), 1);
+
+            // Batches without an ID are applied every time
+            let mut batch = Batch::new();
+            batch.put(b"row1".to_vec(), b"col1".to_vec(), b"value2".to_vec());
+            cf.execute_batch(&batch).unwrap();
+            cf.execute_batch(&batch).unwrap();
+            assert_eq!(cf.get_versions(b"row1", b"col1", 10).unwrap().len(), 3);
+            cf.flush().unwrap();
+        }
+
+        // The ID is remembered after a flush and a restart
+        let table = Table::open(dir.path()).unwrap();
+        let cf = table.cf("test_cf").unwrap();
+        let mut batch = Batch::new();
+        batch.set_request_id("req-1").put(b"row1".to_vec(), b"col1".to_vec(), b"value3".to_vec());
+        cf.execute_batch(&batch).unwrap();
+        assert_eq!(cf.get(b"row1", b"col1").unwrap().unwrap(), b"value2");
+        batch.clear();
+        assert_eq!(batch.request_id(), None);
+    }
+
     #[tokio::test]
     async fn test_async_batch_operations() {
         use crate::async_api::Table as AsyncTable;
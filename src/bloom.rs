@@ -0,0 +1,342 @@
+//! Bloom filters stored in SSTable footers, by which reads skip SSTables that hold no
+//! key of the row, cell or row prefix they look for.
+
+use std::io::{Error, ErrorKind, Result as IoResult};
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::EntryKey;
+
+/// Target false-positive rate of new bloom filters, unless a column family sets one.
+pub const DEFAULT_FALSE_POSITIVE_RATE: f64 = 0.01;
+
+/// Most hash functions a filter uses, however low its target false-positive rate.
+const MAX_HASHES: u32 = 30;
+
+/// What the bloom filters of a column family hold a key for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BloomKeyMode {
+    /// No bloom filters are written
+    Disabled,
+    /// Every row, which also serves whole-row reads
+    #[default]
+    Row,
+    /// Every cell, i.e. row and column: more selective for reads of single cells of wide
+    /// rows, but serves nothing else
+    RowColumn,
+}
+
+/// Bloom filters written for the SSTables of a column family. SSTables are written with
+/// the options current at the time, so a change applies to those written by later
+/// flushes and compactions.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BloomFilterOptions {
+    pub key_mode: BloomKeyMode,
+    /// Share of the keys not in an SSTable that its filter should still report, in (0, 1).
+    /// Lower rates take more memory, held with the footers of open SSTables: 1% takes
+    /// about 10 bits per key, 0.1% about 15.
+    pub false_positive_rate: f64,
+    /// Length of the row prefixes a second filter holds, if any, for row keys that start
+    /// with a fixed-length entity ID: `ColumnFamily::scan_prefix` skips the SSTables
+    /// without the prefix, and gets those without the prefix of their row. Rows shorter
+    /// than this are left out, as prefixes that short cannot be looked up.
+    pub prefix_len: Option<usize>,
+}
+
+// The rate is checked to be in (0, 1) before it is used, so it is never NaN
+impl Eq for BloomFilterOptions {}
+
+impl Default for BloomFilterOptions {
+    fn default() -> Self {
+        Self { key_mode: BloomKeyMode::Row, false_positive_rate: DEFAULT_FALSE_POSITIVE_RATE, prefix_len: None }
+    }
+}
+
+impl BloomFilterOptions {
+    /// No bloom filters.
+    pub fn disabled() -> Self {
+        Self { key_mode: BloomKeyMode::Disabled, ..Self::default() }
+    }
+
+    /// Fail with `InvalidInput` unless the false-positive rate is in (0, 1) and the
+    /// prefix length, if any, is at least 1.
+    pub(crate) fn validate(&self) -> IoResult<()> {
+        if self.prefix_len == Some(0) {
+            return Err(Error::new(ErrorKind::InvalidInput, "Bloom filter prefix_len must be at least 1"));
+        }
+        if !(self.false_positive_rate > 0.0 && self.false_positive_rate < 1.0) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Bloom filter false_positive_rate must be between 0 and 1, not {}", self.false_positive_rate),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A bloom filter over the rows or cells of an SSTable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BloomFilter {
+    key_mode: BloomKeyMode,
+    /// Number of hash functions, i.e. bits set per key
+    hashes: u32,
+    /// Number of distinct keys added
+    keys: u64,
+    bits: Vec<u8>,
+}
+
+impl BloomFilter {
+    /// What the filter holds a key for.
+    pub fn key_mode(&self) -> BloomKeyMode {
+        self.key_mode
+    }
+
+    /// Memory the filter takes.
+    pub fn size_bytes(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// The rate of false positives expected for the keys added.
+    pub fn false_positive_rate(&self) -> f64 {
+        let bits = (self.bits.len() * 8) as f64;
+        let hashes = self.hashes as f64;
+        (1.0 - (-hashes * self.keys as f64 / bits).exp()).powf(hashes)
+    }
+
+    /// Whether the SSTable may hold entries of row. Always true for a filter of cells.
+    pub fn may_contain_row(&self, row: &[u8]) -> bool {
+        self.key_mode != BloomKeyMode::Row || self.contains(key_hash(BloomKeyMode::Row, row, &[]))
+    }
+
+    /// Whether the SSTable may hold entries of (row, column).
+    pub fn may_contain_cell(&self, row: &[u8], column: &[u8]) -> bool {
+        match self.key_mode {
+            BloomKeyMode::Disabled => true,
+            mode => self.contains(key_hash(mode, row, column)),
+        }
+    }
+
+    fn contains(&self, hash: u64) -> bool {
+        self.probes(hash).all(|bit| self.bits[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    /// The bits a key with hash sets, by double hashing.
+    fn probes(&self, hash: u64) -> impl Iterator<Item = usize> {
+        let len = self.bits.len() as u64 * 8;
+        let (h1, h2) = (hash & 0xFFFF_FFFF, hash >> 32);
+        (0..self.hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % len) as usize)
+    }
+}
+
+/// A bloom filter over the fixed-length row prefixes of an SSTable.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrefixBloomFilter {
+    prefix_len: usize,
+    /// A filter of rows, holding the prefixes
+    filter: BloomFilter,
+}
+
+impl PrefixBloomFilter {
+    /// Length of the prefixes the filter holds.
+    pub fn prefix_len(&self) -> usize {
+        self.prefix_len
+    }
+
+    /// Memory the filter takes.
+    pub fn size_bytes(&self) -> usize {
+        self.filter.size_bytes()
+    }
+
+    /// Whether the SSTable may hold rows starting with prefix. Always true for a prefix
+    /// shorter than those the filter holds.
+    pub fn may_contain_prefix(&self, prefix: &[u8]) -> bool {
+        prefix.get(..self.prefix_len).is_none_or(|prefix| self.filter.may_contain_row(prefix))
+    }
+}
+
+/// Collects the keys of an SSTable as its entries are written, in key order.
+#[derive(Debug, Clone)]
+pub(crate) struct BloomFilterBuilder {
+    options: BloomFilterOptions,
+    /// Hashes of the distinct keys added
+    hashes: Vec<u64>,
+    /// Hashes of the distinct row prefixes added
+    prefixes: Vec<u64>,
+}
+
+impl BloomFilterBuilder {
+    pub fn new(options: BloomFilterOptions) -> Self {
+        Self { options, hashes: Vec::new(), prefixes: Vec::new() }
+    }
+
+    /// Account for the next entry in key order.
+    pub fn add(&mut self, key: &EntryKey) {
+        // The versions of a row or cell, and the rows of a prefix, follow each other
+        if self.options.key_mode != BloomKeyMode::Disabled {
+            push_distinct(&mut self.hashes, key_hash(self.options.key_mode, &key.row, &key.column));
+        }
+        if let Some(prefix) = self.options.prefix_len.and_then(|len| key.row.get(..len)) {
+            push_distinct(&mut self.prefixes, key_hash(BloomKeyMode::Row, prefix, &[]));
+        }
+    }
+
+    /// The filters of the keys and prefixes added; `None` for one that is disabled or
+    /// got no key.
+    pub fn finish(self) -> (Option<BloomFilter>, Option<PrefixBloomFilter>) {
+        let rate = self.options.false_positive_rate;
+        let filter = build(self.options.key_mode, rate, self.hashes);
+        let prefix_filter = self.options.prefix_len
+            .and_then(|prefix_len| Some(PrefixBloomFilter { prefix_len, filter: build(BloomKeyMode::Row, rate, self.prefixes)? }));
+        (filter, prefix_filter)
+    }
+}
+
+fn push_distinct(hashes: &mut Vec<u64>, hash: u64) {
+    if hashes.last() != Some(&hash) {
+        hashes.push(hash);
+    }
+}
+
+/// A filter of key_mode holding the keys with hashes at the false-positive rate, `None`
+/// if it is disabled or there are no keys.
+fn build(key_mode: BloomKeyMode, rate: f64, hashes: Vec<u64>) -> Option<BloomFilter> {
+    if key_mode == BloomKeyMode::Disabled || hashes.is_empty() {
+        return None;
+    }
+    let keys = hashes.len() as f64;
+    let rate = rate.clamp(f64::MIN_POSITIVE, 0.5);
+    let bits = (keys * -rate.ln() / (2f64.ln() * 2f64.ln())).ceil().max(64.0);
+    let hash_count = ((bits / keys) * 2f64.ln()).round().clamp(1.0, MAX_HASHES as f64) as u32;
+    let mut filter = BloomFilter {
+        key_mode,
+        hashes: hash_count,
+        keys: hashes.len() as u64,
+        bits: vec![0; (bits as usize).div_ceil(8)],
+    };
+    for hash in hashes {
+        for bit in filter.probes(hash).collect::<Vec<_>>() {
+            filter.bits[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+    Some(filter)
+}
+
+/// Hash of the key of row (and column, for a filter of cells). The row is prefixed with
+/// its length, so that ("ab", "c") and ("a", "bc") differ. FNV-1a, then mixed so that
+/// both halves are usable for double hashing; stable across builds, as filters are
+/// persisted.
+fn key_hash(mode: BloomKeyMode, row: &[u8], column: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    let mut feed = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash = (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    };
+    match mode {
+        BloomKeyMode::RowColumn => {
+            feed(&(row.len() as u32).to_be_bytes());
+            feed(row);
+            feed(column);
+        }
+        _ => feed(row),
+    }
+    // The finalizer of MurmurHash3
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(row: &str, column: &str) -> EntryKey {
+        EntryKey { row: row.as_bytes().to_vec(), column: column.as_bytes().to_vec(), timestamp: 1, seq: 0 }
+    }
+
+    fn build_with(options: BloomFilterOptions, keys: &[EntryKey]) -> (Option<BloomFilter>, Option<PrefixBloomFilter>) {
+        let mut builder = BloomFilterBuilder::new(options);
+        keys.iter().for_each(|key| builder.add(key));
+        builder.finish()
+    }
+
+    fn build(key_mode: BloomKeyMode, false_positive_rate: f64, keys: &[EntryKey]) -> Option<BloomFilter> {
+        build_with(BloomFilterOptions { key_mode, false_positive_rate, prefix_len: None }, keys).0
+    }
+
+    #[test]
+    fn test_bloom_filter() {
+        let keys: Vec<_> = (0..10_000).map(|i| key(&format!("row{:05}", i), "col")).collect();
+        let filter = build(BloomKeyMode::Row, 0.01, &keys).unwrap();
+        assert!(keys.iter().all(|key| filter.may_contain_row(&key.row)));
+        let false_positives = (10_000..20_000).filter(|i| filter.may_contain_row(format!("row{:05}", i).as_bytes())).count();
+        assert!(false_positives < 200, "{} false positives", false_positives);
+        assert!((filter.false_positive_rate() - 0.01).abs() < 0.002);
+        // About 10 bits per key
+        assert!((11_000..13_000).contains(&filter.size_bytes()));
+
+        // A lower rate takes more memory
+        let tight = build(BloomKeyMode::Row, 0.001, &keys).unwrap();
+        assert!(tight.size_bytes() > filter.size_bytes());
+        assert!((10_000..20_000).filter(|i| tight.may_contain_row(format!("row{:05}", i).as_bytes())).count() < 30);
+
+        // Versions of a key count once
+        let versions: Vec<_> = (0..3).map(|_| key("row1", "col")).collect();
+        assert_eq!(build(BloomKeyMode::Row, 0.01, &versions).unwrap().keys, 1);
+
+        assert_eq!(build(BloomKeyMode::Disabled, 0.01, &keys), None);
+        assert_eq!(build(BloomKeyMode::Row, 0.01, &[]), None);
+    }
+
+    #[test]
+    fn test_bloom_filter_of_cells() {
+        let keys = [key("a", "bc"), key("row1", "col1"), key("row1", "col2")];
+        let filter = build(BloomKeyMode::RowColumn, 0.01, &keys).unwrap();
+        assert!(keys.iter().all(|key| filter.may_contain_cell(&key.row, &key.column)));
+        assert!(!filter.may_contain_cell(b"ab", b"c"));
+        assert!(!filter.may_contain_cell(b"row1", b"col3"));
+        // Rows cannot be looked up in a filter of cells
+        assert!(filter.may_contain_row(b"row2"));
+
+        let rows = build(BloomKeyMode::Row, 0.01, &keys).unwrap();
+        assert!(rows.may_contain_cell(b"row1", b"col3"));
+        assert!(!rows.may_contain_cell(b"row2", b"col1"));
+    }
+
+    #[test]
+    fn test_prefix_bloom_filter() {
+        let keys: Vec<_> = (0..1000).flat_map(|i| (0..3).map(move |j| key(&format!("user{:04}:{}", i, j), "col"))).collect();
+        let options = BloomFilterOptions { key_mode: BloomKeyMode::Disabled, prefix_len: Some(8), ..BloomFilterOptions::default() };
+        let (filter, prefixes) = build_with(options, &keys);
+        assert_eq!(filter, None);
+        let prefixes = prefixes.unwrap();
+        assert_eq!((prefixes.prefix_len(), prefixes.filter.keys), (8, 1000));
+        assert!(keys.iter().all(|key| prefixes.may_contain_prefix(&key.row)));
+        assert!(prefixes.may_contain_prefix(b"user0042:"));
+        let false_positives = (1000..2000).filter(|i| prefixes.may_contain_prefix(format!("user{:04}", i).as_bytes())).count();
+        assert!(false_positives < 30, "{} false positives", false_positives);
+        // Shorter prefixes cannot be looked up
+        assert!(prefixes.may_contain_prefix(b"user9"));
+
+        // Rows shorter than the prefix are left out
+        let (_, prefixes) = build_with(options, &[key("user", "col")]);
+        assert_eq!(prefixes, None);
+        let options = BloomFilterOptions { prefix_len: Some(0), ..BloomFilterOptions::default() };
+        assert_eq!(options.validate().unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_bloom_filter_options() {
+        assert!(BloomFilterOptions::default().validate().is_ok());
+        for rate in [0.0, 1.0, -0.5, f64::NAN] {
+            let options = BloomFilterOptions { false_positive_rate: rate, ..BloomFilterOptions::default() };
+            assert_eq!(options.validate().unwrap_err().kind(), ErrorKind::InvalidInput);
+        }
+        let options: BloomFilterOptions = serde_json::from_str(r#"{"key_mode": "row_column"}"#).unwrap();
+        assert_eq!(options, BloomFilterOptions { key_mode: BloomKeyMode::RowColumn, false_positive_rate: 0.01, prefix_len: None });
+    }
+}
@@ -0,0 +1,334 @@
+//! LRU cache of SSTable data shared by the reads of a column family.
+//!
+//! SSTables are read whole, so a cold read of one cell deserializes every file of the
+//! column family. The cache keeps the entries of recently read cells (and whole rows)
+//! per file, including the empty results of files that do not hold them, so repeated
+//! reads of hot rows touch no SSTable at all. Files are identified by path, length and
+//! modification time, so a file replaced under the same name (e.g. by a merge) is never
+//! served from stale entries; entries of deleted files simply age out.
+//!
+//! Below it, `ReaderCache` keeps the opened `SSTableReader` of every live file, so that
+//! cache misses, scans and compactions do not re-read and deserialize files either.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::Result as IoResult,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+
+use serde::Serialize;
+
+use crate::api::{CellValue, Column, EntryKey, RowKey};
+use crate::storage::SSTableReader;
+
+/// Default capacity of a column family's block cache, in bytes.
+pub const DEFAULT_BLOCK_CACHE_BYTES: usize = 32 * 1024 * 1024;
+
+/// Sorted entries of one cell or row in one SSTable.
+pub type CachedEntries = Arc<Vec<(EntryKey, CellValue)>>;
+
+/// Bookkeeping cost of a cached item on top of its data, in bytes.
+const ITEM_OVERHEAD: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct FileId {
+    path: PathBuf,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileId {
+    fn of(path: &Path) -> IoResult<Self> {
+        let metadata = fs::metadata(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    file: FileId,
+    row: RowKey,
+    /// None caches the whole row
+    column: Option<Column>,
+}
+
+struct CacheItem {
+    entries: CachedEntries,
+    bytes: usize,
+    tick: u64,
+}
+
+#[derive(Default)]
+struct Lru {
+    items: HashMap<CacheKey, CacheItem>,
+    /// Items by last use, oldest first
+    order: BTreeMap<u64, CacheKey>,
+    tick: u64,
+    bytes: usize,
+}
+
+/// Usage counters of a block cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BlockCacheStats {
+    pub capacity_bytes: usize,
+    pub used_bytes: usize,
+    pub items: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// LRU cache of SSTable entries, bounded in bytes.
+pub struct BlockCache {
+    capacity: AtomicU64,
+    lru: Mutex<Lru>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl BlockCache {
+    /// Create a cache holding up to capacity_bytes; 0 disables caching.
+    pub fn new(capacity_bytes: usize) -> Self {
+        Self {
+            capacity: AtomicU64::new(capacity_bytes as u64),
+            lru: Mutex::new(Lru::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Maximum number of bytes held.
+    pub fn capacity(&self) -> usize {
+        self.capacity.load(Ordering::Relaxed) as usize
+    }
+
+    /// Change the capacity, evicting the least recently used items that no longer fit.
+    pub fn set_capacity(&self, capacity_bytes: usize) {
+        self.capacity.store(capacity_bytes as u64, Ordering::Relaxed);
+        self.lru.lock().unwrap().evict_to(capacity_bytes);
+    }
+
+    /// Evict the least recently used items until at most bytes are held, keeping the
+    /// capacity.
+    pub fn shrink_to(&self, bytes: usize) {
+        self.lru.lock().unwrap().evict_to(bytes);
+    }
+
+    /// Drop every cached item.
+    pub fn clear(&self) {
+        *self.lru.lock().unwrap() = Lru::default();
+    }
+
+    /// Current usage and hit counters.
+    pub fn stats(&self) -> BlockCacheStats {
+        let lru = self.lru.lock().unwrap();
+        BlockCacheStats {
+            capacity_bytes: self.capacity(),
+            used_bytes: lru.bytes,
+            items: lru.items.len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Entries of (row, column) in the SSTable at path, oldest version first; with no
+    /// column, the entries of the whole row in key order. On a miss, the entries are read
+    /// from the reader returned by open.
+    pub fn get_or_load(
+        &self,
+        path: &Path,
+        row: &[u8],
+        column: Option<&[u8]>,
+        open: impl FnOnce() -> IoResult<Arc<SSTableReader>>,
+    ) -> IoResult<CachedEntries> {
+        let key = CacheKey {
+            file: FileId::of(path)?,
+            row: row.to_vec(),
+            column: column.map(<[u8]>::to_vec),
+        };
+        if let Some(entries) = self.lru.lock().unwrap().touch(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(entries);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let reader = open()?;
+        let entries = match column {
+            Some(column) => reader.cell_entries(row, column),
+            None => reader.range_entries(row, row),
+        };
+        let entries: CachedEntries = Arc::new(entries.into_owned());
+
+        let capacity = self.capacity();
+        let bytes = ITEM_OVERHEAD + key.row.len() + key.column.as_ref().map_or(0, Vec::len)
+            + entries.iter().map(|(key, value)| entry_bytes(key, value)).sum::<usize>();
+        if bytes <= capacity {
+            let mut lru = self.lru.lock().unwrap();
+            lru.insert(key, entries.clone(), bytes);
+            lru.evict_to(capacity);
+        }
+        Ok(entries)
+    }
+}
+
+/// Opened SSTable readers of a column family, by path.
+///
+/// A reader is reopened if its file was replaced since it was opened. Readers of files
+/// that are no longer live are dropped by `retain` after flushes, merges and compactions.
+#[derive(Default)]
+pub struct ReaderCache {
+    readers: Mutex<HashMap<PathBuf, (FileId, Arc<SSTableReader>)>>,
+}
+
+impl ReaderCache {
+    /// Create an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The reader of the SSTable at path, opening it if it is not cached yet.
+    pub fn get(&self, path: &Path) -> IoResult<Arc<SSTableReader>> {
+        let id = FileId::of(path)?;
+        if let Some((cached_id, reader)) = self.readers.lock().unwrap().get(path) {
+            if *cached_id == id {
+                return Ok(reader.clone());
+            }
+        }
+
+        let reader = Arc::new(SSTableReader::open(path)?);
+        self.readers.lock().unwrap().insert(path.to_path_buf(), (id, reader.clone()));
+        Ok(reader)
+    }
+
+    /// Drop the readers of files not in live.
+    pub fn retain(&self, live: &[PathBuf]) {
+        self.readers.lock().unwrap().retain(|path, _| live.contains(path));
+    }
+
+    /// Drop every reader.
+    pub fn clear(&self) {
+        self.readers.lock().unwrap().clear();
+    }
+
+    /// Number of open readers.
+    pub fn len(&self) -> usize {
+        self.readers.lock().unwrap().len()
+    }
+
+    /// Whether no reader is open.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn entry_bytes(key: &EntryKey, value: &CellValue) -> usize {
+    let value_len = match value {
+        CellValue::Put(data) => data.len(),
+        CellValue::Delete(_) | CellValue::DeleteFamily => 0,
+    };
+    key.row.len() + key.column.len() + value_len + 32
+}
+
+impl Lru {
+    fn touch(&mut self, key: &CacheKey) -> Option<CachedEntries> {
+        self.tick += 1;
+        let item = self.items.get_mut(key)?;
+        self.order.remove(&item.tick);
+        item.tick = self.tick;
+        self.order.insert(self.tick, key.clone());
+        Some(item.entries.clone())
+    }
+
+    fn insert(&mut self, key: CacheKey, entries: CachedEntries, bytes: usize) {
+        self.tick += 1;
+        if let Some(old) = self.items.remove(&key) {
+            self.order.remove(&old.tick);
+            self.bytes -= old.bytes;
+        }
+        self.order.insert(self.tick, key.clone());
+        self.items.insert(key, CacheItem { entries, bytes, tick: self.tick });
+        self.bytes += bytes;
+    }
+
+    fn evict_to(&mut self, capacity: usize) {
+        while self.bytes > capacity {
+            let Some((_, key)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some(item) = self.items.remove(&key) {
+                self.bytes -= item.bytes;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Entry;
+    use crate::storage::SSTable;
+    use tempfile::tempdir;
+
+    fn put(row: &str, column: &str, timestamp: u64, value: &str) -> Entry {
+        Entry {
+            key: EntryKey {
+                row: row.as_bytes().to_vec(),
+                column: column.as_bytes().to_vec(),
+                timestamp,
+                seq: 0,
+            },
+            value: CellValue::Put(value.as_bytes().to_vec()),
+        }
+    }
+
+    #[test]
+    fn test_block_cache_hits_and_eviction() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("0000000001.sst");
+        SSTable::create(&path, &[put("r1", "a", 1, "v1"), put("r1", "a", 2, "v2"), put("r2", "a", 1, "w")]).unwrap();
+
+        let readers = ReaderCache::new();
+        let open = || readers.get(&path);
+        let cache = BlockCache::new(1024 * 1024);
+        let cell = cache.get_or_load(&path, b"r1", Some(b"a"), open).unwrap();
+        assert_eq!(cell.len(), 2);
+        assert_eq!(cache.get_or_load(&path, b"r1", Some(b"a"), open).unwrap(), cell);
+        assert_eq!(cache.get_or_load(&path, b"r2", None, open).unwrap().len(), 1);
+        // Misses are cached too
+        assert!(cache.get_or_load(&path, b"r3", Some(b"a"), open).unwrap().is_empty());
+        assert!(cache.get_or_load(&path, b"r3", Some(b"a"), open).unwrap().is_empty());
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.items), (2, 3, 3));
+
+        // Shrinking evicts the least recently used items first
+        cache.get_or_load(&path, b"r1", Some(b"a"), open).unwrap();
+        cache.set_capacity(cache.stats().used_bytes - 1);
+        assert_eq!(cache.stats().items, 2);
+        cache.get_or_load(&path, b"r1", Some(b"a"), open).unwrap();
+        cache.get_or_load(&path, b"r2", None, open).unwrap();
+        assert_eq!((cache.stats().hits, cache.stats().misses), (4, 4));
+        // So does shrinking to a size, which keeps the capacity
+        let capacity = cache.capacity();
+        cache.shrink_to(cache.stats().used_bytes - 1);
+        assert_eq!((cache.stats().items, cache.capacity()), (1, capacity));
+
+        // A file replaced under the same name is read again
+        SSTable::create(&path, &[put("r1", "a", 3, "v3-but-longer")]).unwrap();
+        assert_eq!(cache.get_or_load(&path, b"r1", Some(b"a"), open).unwrap().len(), 1);
+
+        cache.set_capacity(0);
+        assert_eq!(cache.stats().used_bytes, 0);
+        assert_eq!(readers.len(), 1);
+        readers.retain(&[]);
+        assert!(readers.is_empty());
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufReader, Error, ErrorKind, Read, Result as IoResult, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::api::Entry;
+
+/// Name of the changelog file inside a column family directory.
+pub const CHANGELOG_FILE: &str = "changelog.log";
+
+/// Appends every mutation of a column family to its changelog.
+///
+/// Unlike the WAL, the changelog is never truncated by a flush, so byte offsets
+/// stay valid and can be used as positions by readers in other processes.
+/// Format: a sequence of records, each `[u32 big-endian length][bincode(Entry)]`.
+pub struct ChangelogWriter {
+    file: File,
+}
+
+impl ChangelogWriter {
+    /// Open (or create) the changelog at path for appending.
+    pub fn open(path: impl AsRef<Path>) -> IoResult<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Self { file })
+    }
+
+    /// Append one entry as a single write, so readers never observe a torn length prefix.
+    pub fn append(&mut self, entry: &Entry) -> IoResult<()> {
+        let payload = bincode::serialize(entry)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let mut record = Vec::with_capacity(4 + payload.len());
+        record.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        record.extend_from_slice(&payload);
+        self.file.write_all(&record)?;
+        self.file.flush()
+    }
+}
+
+/// A mutation read from the changelog.
+#[derive(Debug, Clone)]
+pub struct ChangeRecord {
+    /// Position just past this record; pass it to `seek` to resume after it.
+    pub position: u64,
+    /// The mutation (a Put or a Delete marker).
+    pub entry: Entry,
+}
+
+/// Read-only tailer over a column family's changelog.
+///
+/// Intended for sidecar processes such as secondary indexers: it never writes to the
+/// column family directory and can run while the owning process keeps writing.
+/// The current position can be persisted to a checkpoint file with `commit`.
+pub struct ChangelogReader {
+    file: File,
+    position: u64,
+    checkpoint: Option<PathBuf>,
+}
+
+impl ChangelogReader {
+    /// Open the changelog of the column family at cf_path, starting at position.
+    /// Fails with `NotFound` if the changelog was never enabled for the column family.
+    pub fn open(cf_path: impl AsRef<Path>, position: u64) -> IoResult<Self> {
+        let file = File::open(cf_path.as_ref().join(CHANGELOG_FILE))?;
+        Ok(Self {
+            file,
+            position,
+            checkpoint: None,
+        })
+    }
+
+    /// Open the changelog, resuming from the position stored in checkpoint_path
+    /// (or from the beginning if the checkpoint does not exist yet).
+    pub fn open_with_checkpoint(cf_path: impl AsRef<Path>, checkpoint_path: impl AsRef<Path>) -> IoResult<Self> {
+        let checkpoint_path = checkpoint_path.as_ref().to_path_buf();
+        let position = match fs::read_to_string(&checkpoint_path) {
+            Ok(contents) => contents.trim().parse::<u64>().map_err(|e| {
+                Error::new(ErrorKind::InvalidData, format!("Invalid changelog checkpoint: {}", e))
+            })?,
+            Err(e) if e.kind() == ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+
+        let mut reader = Self::open(cf_path, position)?;
+        reader.checkpoint = Some(checkpoint_path);
+        Ok(reader)
+    }
+
+    /// Position of the next record to be read.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Move to position, which must be 0 or a `ChangeRecord::position` returned earlier.
+    pub fn seek(&mut self, position: u64) {
+        self.position = position;
+    }
+
+    /// Read up to max_records complete records from the current position.
+    /// Returns an empty vector when the reader has caught up with the writer; a record
+    /// that is still being written is left for the next call.
+    pub fn poll(&mut self, max_records: usize) -> IoResult<Vec<ChangeRecord>> {
+        let len = self.file.metadata()?.len();
+        if self.position >= len {
+            return Ok(Vec::new());
+        }
+
+        self.file.seek(SeekFrom::Start(self.position))?;
+        let mut reader = BufReader::new(&self.file);
+        let mut position = self.position;
+        let mut records = Vec::new();
+
+        while records.len() < max_records && position + 4 <= len {
+            let mut len_buf = [0u8; 4];
+            reader.read_exact(&mut len_buf)?;
+            let payload_len = u32::from_be_bytes(len_buf) as u64;
+            if position + 4 + payload_len > len {
+                break;
+            }
+
+            let mut payload = vec![0u8; payload_len as usize];
+            reader.read_exact(&mut payload)?;
+            let entry: Entry = bincode::deserialize(&payload).map_err(|e| {
+                Error::new(ErrorKind::InvalidData, format!("Corrupt changelog record at {}: {}", position, e))
+            })?;
+
+            position += 4 + payload_len;
+            records.push(ChangeRecord { position, entry });
+        }
+
+        self.position = position;
+        Ok(records)
+    }
+
+    /// Persist the current position to the checkpoint file, replacing it atomically.
+    pub fn commit(&self) -> IoResult<()> {
+        let checkpoint = self.checkpoint.as_ref().ok_or_else(|| {
+            Error::new(ErrorKind::InvalidInput, "Reader was opened without a checkpoint file")
+        })?;
+        let tmp = checkpoint.with_extension("tmp");
+        fs::write(&tmp, self.position.to_string())?;
+        fs::rename(&tmp, checkpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{CellValue, EntryKey};
+    use tempfile::tempdir;
+
+    fn entry(row: &str, value: CellValue) -> Entry {
+        Entry {
+            key: EntryKey {
+                row: row.as_bytes().to_vec(),
+                column: b"col1".to_vec(),
+                timestamp: 100,
+                seq: 0,
+            },
+            value,
+        }
+    }
+
+    #[test]
+    fn test_changelog_tail_and_checkpoint() {
+        let dir = tempdir().unwrap();
+        let checkpoint = dir.path().join("indexer.pos");
+        let mut writer = ChangelogWriter::open(dir.path().join(CHANGELOG_FILE)).unwrap();
+
+        writer.append(&entry("row1", CellValue::Put(b"v1".to_vec()))).unwrap();
+        writer.append(&entry("row2", CellValue::Delete(None))).unwrap();
+
+        let mut reader = ChangelogReader::open_with_checkpoint(dir.path(), &checkpoint).unwrap();
+        let records = reader.poll(1).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].entry.key.row, b"row1");
+        reader.commit().unwrap();
+
+        let records = reader.poll(10).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].entry.value, CellValue::Delete(None));
+        assert!(reader.poll(10).unwrap().is_empty());
+
+        // A new reader resumes from the committed checkpoint
+        let mut resumed = ChangelogReader::open_with_checkpoint(dir.path(), &checkpoint).unwrap();
+        let records = resumed.poll(10).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].entry.key.row, b"row2");
+    }
+
+    #[test]
+    fn test_changelog_partial_record() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(CHANGELOG_FILE);
+        let mut writer = ChangelogWriter::open(&path).unwrap();
+        writer.append(&entry("row1", CellValue::Put(b"v1".to_vec()))).unwrap();
+
+        // Simulate a record that is only partially on disk
+        let payload = bincode::serialize(&entry("row2", CellValue::Put(b"v2".to_vec()))).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&(payload.len() as u32).to_be_bytes()).unwrap();
+        file.write_all(&payload[..payload.len() / 2]).unwrap();
+
+        let mut reader = ChangelogReader::open(dir.path(), 0).unwrap();
+        assert_eq!(reader.poll(10).unwrap().len(), 1);
+        let position = reader.position();
+
+        // Once the rest arrives, the record is returned
+        file.write_all(&payload[payload.len() / 2..]).unwrap();
+        let records = reader.poll(10).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].entry.key.row, b"row2");
+        assert!(reader.position() > position);
+    }
+
+    #[test]
+    fn test_changelog_not_enabled() {
+        let dir = tempdir().unwrap();
+        let err = ChangelogReader::open(dir.path(), 0).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,1021 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Error, ErrorKind, Result as IoResult, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::api::{Put, RangeVersions, Table, Timestamp};
+use crate::backend::LocalFs;
+use crate::memstore::wal_segments;
+use crate::filter::FilterSet;
+use crate::tabular::{self, ImportMapping, TextFormat};
+use crate::migrate::{self, MigrationReport};
+use crate::recovery::{self, RecoveryReport};
+use crate::verify::VerifyReport;
+
+/// Command-line administration tool for RedBase tables.
+///
+/// Operates either on a local table directory (`--table-dir`) or on a remote
+/// REST server (`--url`, with `--table` selecting the table).
+#[derive(Parser, Debug)]
+#[command(name = "redbase-cli", version, about = "Administer RedBase tables")]
+pub struct Cli {
+    /// Local table directory to operate on
+    #[arg(long, global = true, conflicts_with = "url")]
+    pub table_dir: Option<PathBuf>,
+    /// Base URL of a RedBase REST server, e.g. http://127.0.0.1:8080
+    #[arg(long, global = true)]
+    pub url: Option<String>,
+    /// Table name on the remote server
+    #[arg(long, global = true, default_value = "default")]
+    pub table: String,
+    /// Output format
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+/// Output format for command results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned text columns
+    Table,
+    /// Pretty-printed JSON
+    Json,
+}
+
+/// File format for import and export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FileFormat {
+    Csv,
+    Ndjson,
+}
+
+impl From<FileFormat> for TextFormat {
+    fn from(format: FileFormat) -> Self {
+        match format {
+            FileFormat::Csv => TextFormat::Csv,
+            FileFormat::Ndjson => TextFormat::Ndjson,
+        }
+    }
+}
+
+/// CLI subcommands
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Write a value
+    Put {
+        cf: String,
+        row: String,
+        column: String,
+        value: String,
+    },
+    /// Read the latest value (or several versions) of a cell
+    Get {
+        cf: String,
+        row: String,
+        column: String,
+        /// Number of versions to return
+        #[arg(long, default_value_t = 1)]
+        versions: usize,
+    },
+    /// Scan an inclusive range of rows
+    Scan {
+        cf: String,
+        start_row: String,
+        end_row: String,
+        /// Number of versions to return per column
+        #[arg(long, default_value_t = 1)]
+        versions: usize,
+    },
+    /// Write a tombstone for a cell
+    Delete {
+        cf: String,
+        row: String,
+        column: String,
+        /// Optional tombstone TTL in milliseconds
+        #[arg(long)]
+        ttl_ms: Option<u64>,
+    },
+    /// Create a column family
+    CreateCf { name: String },
+    /// Flush a column family's MemStore to an SSTable
+    Flush { cf: String },
+    /// Compact a column family's SSTables
+    Compact {
+        cf: String,
+        /// Merge all SSTables instead of a subset
+        #[arg(long)]
+        major: bool,
+    },
+    /// Import rows from a CSV (with header) or NDJSON file
+    Import {
+        cf: String,
+        file: PathBuf,
+        /// Input format (inferred from the file extension by default)
+        #[arg(long, value_enum)]
+        file_format: Option<FileFormat>,
+        /// Field(s) forming the row key; several fields are joined with --key-separator
+        #[arg(long, value_delimiter = ',', required = true)]
+        row_key: Vec<String>,
+        /// Separator between composite row key parts
+        #[arg(long, default_value = ":")]
+        key_separator: String,
+        /// FIELD=COLUMN mappings; by default every non-key field is imported under its own name
+        #[arg(long = "map", value_parser = parse_mapping)]
+        mappings: Vec<(String, String)>,
+    },
+    /// Export the latest values of a row range to a CSV or NDJSON file
+    Export {
+        cf: String,
+        start_row: String,
+        end_row: String,
+        file: PathBuf,
+        /// Output format (inferred from the file extension by default)
+        #[arg(long, value_enum)]
+        file_format: Option<FileFormat>,
+        /// Name of the field holding the row key
+        #[arg(long, default_value = "row")]
+        row_key_field: String,
+    },
+    /// Make a column family read-only after a final major compaction
+    Freeze { cf: String },
+    /// Show on-disk statistics for one or all column families
+    Stats { cf: Option<String> },
+    /// Check the SSTables of one or all column families for corruption
+    Verify {
+        cf: Option<String>,
+        /// Move corrupt SSTables out of the live set into the quarantine directory
+        #[arg(long)]
+        quarantine: bool,
+    },
+    /// List the table's column families
+    Describe,
+    /// Rewrite a table directory written by an earlier version in the current formats,
+    /// keeping the original files until the migration is finished or rolled back. The
+    /// table must not be open elsewhere.
+    Migrate {
+        /// Put back the original files of the last migration
+        #[arg(long, conflicts_with = "finish")]
+        rollback: bool,
+        /// Keep the last migration, dropping the original files
+        #[arg(long)]
+        finish: bool,
+    },
+    /// Report what opening a table directory would recover, and any WAL record or
+    /// SSTable that would fail it, without changing anything. The table should not be
+    /// open elsewhere.
+    Fsck,
+    /// Start an interactive shell
+    Shell {
+        /// File used to persist command history (defaults to ~/.redbase_history)
+        #[arg(long)]
+        history_file: Option<PathBuf>,
+    },
+}
+
+fn parse_mapping(arg: &str) -> Result<(String, String), String> {
+    arg.split_once('=')
+        .map(|(field, column)| (field.to_string(), column.to_string()))
+        .ok_or_else(|| format!("expected FIELD=COLUMN, got {:?}", arg))
+}
+
+/// Pick the explicit format, or infer it from the file extension.
+fn file_format(explicit: Option<FileFormat>, file: &Path) -> IoResult<TextFormat> {
+    explicit.map(TextFormat::from)
+        .or_else(|| TextFormat::from_path(file))
+        .ok_or_else(|| Error::new(
+            ErrorKind::InvalidInput,
+            format!("Cannot infer the format of {}; pass --file-format", file.display()),
+        ))
+}
+
+/// A single cell version in command output
+#[derive(Debug, Clone, Serialize)]
+pub struct CellRecord {
+    pub row: String,
+    pub column: String,
+    pub timestamp: Timestamp,
+    pub value: String,
+}
+
+/// On-disk statistics for a column family
+#[derive(Debug, Clone, Serialize)]
+pub struct CfStatsRecord {
+    pub column_family: String,
+    pub sst_files: usize,
+    pub sst_bytes: u64,
+    /// Size of the WAL files; the REST server does not report it
+    pub wal_bytes: Option<u64>,
+}
+
+/// A table and its column families
+#[derive(Debug, Clone, Serialize)]
+pub struct TableDescription {
+    pub table: String,
+    pub column_families: Vec<String>,
+}
+
+/// Result of executing a command
+#[derive(Debug, Clone)]
+pub enum Output {
+    /// A command that only reports success
+    Status(String),
+    /// Cell versions returned by a read
+    Cells(Vec<CellRecord>),
+    /// Column family statistics
+    Stats(Vec<CfStatsRecord>),
+    /// Table layout
+    Description(TableDescription),
+    /// SSTable verification results
+    Verify(Vec<VerifyReport>),
+    /// Files changed by a migration
+    Migration(MigrationReport),
+    /// What opening a table would recover
+    Recovery(RecoveryReport),
+}
+
+impl Output {
+    /// Whether the command found a problem, e.g. a corrupt SSTable, that the exit status
+    /// should report.
+    pub fn is_failure(&self) -> bool {
+        match self {
+            Output::Verify(reports) => reports.iter().any(|report| !report.is_ok()),
+            Output::Recovery(report) => !report.is_ok(),
+            _ => false,
+        }
+    }
+
+    /// Render the output in the requested format.
+    pub fn render(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Json => {
+                let value = match self {
+                    Output::Status(message) => json!({ "status": "ok", "message": message }),
+                    Output::Cells(cells) => json!(cells),
+                    Output::Stats(stats) => json!(stats),
+                    Output::Description(description) => json!(description),
+                    Output::Verify(reports) => json!(reports),
+                    Output::Migration(report) => json!(report),
+                    Output::Recovery(report) => json!(report),
+                };
+                serde_json::to_string_pretty(&value).unwrap_or_default()
+            }
+            OutputFormat::Table => match self {
+                Output::Status(message) => message.clone(),
+                Output::Cells(cells) => render_table(
+                    &["ROW", "COLUMN", "TIMESTAMP", "VALUE"],
+                    cells.iter()
+                        .map(|c| vec![c.row.clone(), c.column.clone(), c.timestamp.to_string(), c.value.clone()])
+                        .collect(),
+                ),
+                Output::Stats(stats) => render_table(
+                    &["COLUMN_FAMILY", "SST_FILES", "SST_BYTES", "WAL_BYTES"],
+                    stats.iter()
+                        .map(|s| vec![
+                            s.column_family.clone(),
+                            s.sst_files.to_string(),
+                            s.sst_bytes.to_string(),
+                            s.wal_bytes.map_or("-".to_string(), |bytes| bytes.to_string()),
+                        ])
+                        .collect(),
+                ),
+                Output::Description(description) => render_table(
+                    &["TABLE", "COLUMN_FAMILY"],
+                    description.column_families.iter()
+                        .map(|cf| vec![description.table.clone(), cf.clone()])
+                        .collect(),
+                ),
+                Output::Verify(reports) => render_table(
+                    &["COLUMN_FAMILY", "SSTABLE", "ENTRIES", "STATUS"],
+                    reports.iter()
+                        .flat_map(|report| report.sstables.iter().map(|check| {
+                            let status = match (&check.problem, &check.quarantined_to) {
+                                (None, _) => "ok".to_string(),
+                                (Some(problem), None) => format!("corrupt: {}", problem),
+                                (Some(problem), Some(_)) => format!("quarantined: {}", problem),
+                            };
+                            vec![
+                                report.column_family.clone(),
+                                check.path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                                check.entries.to_string(),
+                                status,
+                            ]
+                        }))
+                        .collect(),
+                ),
+                Output::Migration(report) => render_table(
+                    &["FILE", "CHANGE"],
+                    report.rewritten.iter().map(|path| (path, "rewritten"))
+                        .chain(report.created.iter().map(|path| (path, "created")))
+                        .map(|(path, change)| vec![path.display().to_string(), change.to_string()])
+                        .collect(),
+                ),
+                Output::Recovery(report) => render_table(
+                    &["COLUMN_FAMILY", "WAL_FILES", "WAL_RECORDS", "SKIPPED", "TORN_BYTES", "SSTABLES", "IGNORED", "STATUS"],
+                    report.column_families.iter()
+                        .map(|cf| vec![
+                            cf.column_family.clone(),
+                            cf.wal.files.to_string(),
+                            cf.wal.records.to_string(),
+                            cf.records_skipped.to_string(),
+                            cf.wal.torn_bytes.to_string(),
+                            cf.sstables_live.to_string(),
+                            cf.sstables_ignored.len().to_string(),
+                            if cf.is_ok() { "ok".to_string() } else { cf.problems.join("; ") },
+                        ])
+                        .collect(),
+                ),
+            },
+        }
+    }
+}
+
+/// Render rows as whitespace-aligned columns under a header line.
+fn render_table(headers: &[&str], rows: Vec<Vec<String>>) -> String {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in &rows {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
+        }
+    }
+
+    let format_line = |cells: Vec<String>| -> String {
+        cells.iter()
+            .enumerate()
+            .map(|(i, cell)| format!("{:<width$}", cell, width = widths[i]))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    let mut lines = vec![format_line(headers.iter().map(|h| h.to_string()).collect())];
+    lines.extend(rows.into_iter().map(format_line));
+    lines.join("\n")
+}
+
+/// Operations the CLI needs from a table, implemented locally and over REST.
+pub trait Backend {
+    fn put(&mut self, cf: &str, row: &str, column: &str, value: &str) -> IoResult<()>;
+    fn put_row(&mut self, cf: &str, row: &[u8], columns: Vec<(Vec<u8>, Vec<u8>)>) -> IoResult<()>;
+    fn get(&mut self, cf: &str, row: &str, column: &str, versions: usize) -> IoResult<Vec<(Timestamp, Vec<u8>)>>;
+    fn scan(&mut self, cf: &str, start_row: &str, end_row: &str, versions: usize) -> IoResult<RangeVersions>;
+    fn delete(&mut self, cf: &str, row: &str, column: &str, ttl_ms: Option<u64>) -> IoResult<()>;
+    fn create_cf(&mut self, name: &str) -> IoResult<()>;
+    fn flush(&mut self, cf: &str) -> IoResult<()>;
+    fn compact(&mut self, cf: &str, major: bool) -> IoResult<()>;
+    fn freeze(&mut self, cf: &str) -> IoResult<()>;
+    fn stats(&mut self, cf: Option<&str>) -> IoResult<Vec<CfStatsRecord>>;
+    fn verify(&mut self, cf: Option<&str>, quarantine: bool) -> IoResult<Vec<VerifyReport>>;
+    fn describe(&mut self) -> IoResult<TableDescription>;
+}
+
+fn cf_not_found(cf: &str) -> Error {
+    Error::new(ErrorKind::NotFound, format!("Column family not found: {}", cf))
+}
+
+/// Backend operating directly on a table directory.
+pub struct LocalBackend {
+    table: Table,
+}
+
+impl LocalBackend {
+    /// Open (or create) the table directory.
+    pub fn open(table_dir: impl AsRef<Path>) -> IoResult<Self> {
+        Ok(Self { table: Table::open(table_dir)? })
+    }
+
+    /// The underlying table.
+    pub fn table(&self) -> &Table {
+        &self.table
+    }
+}
+
+impl Backend for LocalBackend {
+    fn put(&mut self, cf: &str, row: &str, column: &str, value: &str) -> IoResult<()> {
+        let cf = self.table.cf(cf).ok_or_else(|| cf_not_found(cf))?;
+        cf.put(row.as_bytes().to_vec(), column.as_bytes().to_vec(), value.as_bytes().to_vec())
+    }
+
+    fn put_row(&mut self, cf: &str, row: &[u8], columns: Vec<(Vec<u8>, Vec<u8>)>) -> IoResult<()> {
+        let cf = self.table.cf(cf).ok_or_else(|| cf_not_found(cf))?;
+        let mut put = Put::new(row.to_vec());
+        for (column, value) in columns {
+            put.add_column(column, value);
+        }
+        cf.execute_put(put)
+    }
+
+    fn get(&mut self, cf: &str, row: &str, column: &str, versions: usize) -> IoResult<Vec<(Timestamp, Vec<u8>)>> {
+        let cf = self.table.cf(cf).ok_or_else(|| cf_not_found(cf))?;
+        // get_versions skips tombstones, so check that the cell is still visible first
+        if cf.get(row.as_bytes(), column.as_bytes())?.is_none() {
+            return Ok(Vec::new());
+        }
+        cf.get_versions(row.as_bytes(), column.as_bytes(), versions)
+    }
+
+    fn scan(&mut self, cf: &str, start_row: &str, end_row: &str, versions: usize) -> IoResult<RangeVersions> {
+        let cf = self.table.cf(cf).ok_or_else(|| cf_not_found(cf))?;
+        let mut filter_set = FilterSet::new();
+        filter_set.with_max_versions(versions);
+        cf.scan_with_filter(start_row.as_bytes(), end_row.as_bytes(), &filter_set)
+    }
+
+    fn delete(&mut self, cf: &str, row: &str, column: &str, ttl_ms: Option<u64>) -> IoResult<()> {
+        let cf = self.table.cf(cf).ok_or_else(|| cf_not_found(cf))?;
+        cf.delete_with_ttl(row.as_bytes().to_vec(), column.as_bytes().to_vec(), ttl_ms)
+    }
+
+    fn create_cf(&mut self, name: &str) -> IoResult<()> {
+        self.table.create_cf(name)
+    }
+
+    fn flush(&mut self, cf: &str) -> IoResult<()> {
+        self.table.cf(cf).ok_or_else(|| cf_not_found(cf))?.flush()
+    }
+
+    fn compact(&mut self, cf: &str, major: bool) -> IoResult<()> {
+        let cf = self.table.cf(cf).ok_or_else(|| cf_not_found(cf))?;
+        if major {
+            cf.major_compact()
+        } else {
+            cf.compact()
+        }
+    }
+
+    fn freeze(&mut self, cf: &str) -> IoResult<()> {
+        self.table.cf(cf).ok_or_else(|| cf_not_found(cf))?.freeze()
+    }
+
+    fn stats(&mut self, cf: Option<&str>) -> IoResult<Vec<CfStatsRecord>> {
+        let names = match cf {
+            Some(name) => vec![name.to_string()],
+            None => {
+                let mut names = self.table.cf_names();
+                names.extend(self.table.regioned_cf_names());
+                names.sort();
+                names
+            }
+        };
+
+        names.into_iter()
+            .map(|name| {
+                // A regioned column family sums up its regions
+                let cfs = match (self.table.cf(&name), self.table.regioned_cf(&name)) {
+                    (Some(cf), _) => vec![cf],
+                    (None, Some(regioned)) => regioned.column_families(),
+                    (None, None) => return Err(cf_not_found(&name)),
+                };
+                let (mut sst_files, mut sst_bytes, mut wal_bytes) = (0, 0, 0);
+                for cf in cfs {
+                    let stats = cf.stats()?;
+                    sst_files += stats.sst_files;
+                    sst_bytes += stats.sst_bytes;
+                    let wal_path = cf.path().join("wal.log");
+                    let mut wal_files = wal_segments(&LocalFs, &wal_path)?;
+                    wal_files.extend(wal_path.exists().then_some(wal_path));
+                    for path in wal_files {
+                        wal_bytes += fs::metadata(path)?.len();
+                    }
+                }
+                Ok(CfStatsRecord { column_family: name, sst_files, sst_bytes, wal_bytes: Some(wal_bytes) })
+            })
+            .collect()
+    }
+
+    fn verify(&mut self, cf: Option<&str>, quarantine: bool) -> IoResult<Vec<VerifyReport>> {
+        match cf {
+            Some(name) => Ok(vec![self.table.cf(name).ok_or_else(|| cf_not_found(name))?.verify(quarantine)?]),
+            None => self.table.verify(quarantine),
+        }
+    }
+
+    fn describe(&mut self) -> IoResult<TableDescription> {
+        let table = self.table.path()
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Ok(TableDescription {
+            table,
+            column_families: self.table.cf_names(),
+        })
+    }
+}
+
+/// Backend talking to a RedBase REST server.
+pub struct RemoteBackend {
+    base_url: String,
+    table: String,
+}
+
+impl RemoteBackend {
+    /// Create a backend for `table` on the server at `base_url`.
+    pub fn new(base_url: &str, table: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            table: table.to_string(),
+        }
+    }
+
+    fn table_url(&self) -> String {
+        format!("{}/tables/{}/cf", self.base_url, self.table)
+    }
+
+    fn cf_url(&self, cf: &str, op: &str) -> String {
+        format!("{}/tables/{}/cf/{}/{}", self.base_url, self.table, cf, op)
+    }
+
+    /// GET a JSON response, mapping 404 to `NotFound`.
+    fn get_json(&self, url: &str) -> IoResult<Value> {
+        match ureq::get(url).call() {
+            Ok(resp) => resp.into_json::<Value>(),
+            Err(ureq::Error::Status(code, resp)) => {
+                let kind = if code == 404 { ErrorKind::NotFound } else { ErrorKind::Other };
+                Err(Error::new(kind, format!("Server returned {}: {}", code, resp.into_string().unwrap_or_default())))
+            }
+            Err(e) => Err(Error::new(ErrorKind::ConnectionRefused, e.to_string())),
+        }
+    }
+
+    fn post(&self, url: &str, body: Value) -> IoResult<Value> {
+        self.try_post(url, body)?.ok_or_else(|| Error::new(ErrorKind::NotFound, "Cell not found"))
+    }
+
+    /// POST a JSON body, mapping a `{"status": "not_found"}` response to `Ok(None)`.
+    fn try_post(&self, url: &str, body: Value) -> IoResult<Option<Value>> {
+        match ureq::post(url).send_json(body) {
+            Ok(resp) => resp.into_json::<Value>().map(Some),
+            Err(ureq::Error::Status(code, resp)) => {
+                let message = resp.into_string().unwrap_or_default();
+                let missing_cell = serde_json::from_str::<Value>(&message)
+                    .is_ok_and(|v| v["status"] == "not_found");
+                if missing_cell {
+                    return Ok(None);
+                }
+                let kind = if code == 404 { ErrorKind::NotFound } else { ErrorKind::Other };
+                Err(Error::new(kind, format!("Server returned {}: {}", code, message)))
+            }
+            Err(e) => Err(Error::new(ErrorKind::ConnectionRefused, e.to_string())),
+        }
+    }
+}
+
+/// Parse a `[{"timestamp": .., "value": ..}]` JSON array returned by the REST server.
+fn parse_versions(value: &Value) -> Vec<(Timestamp, Vec<u8>)> {
+    value.as_array()
+        .map(|versions| {
+            versions.iter()
+                .map(|v| (
+                    v["timestamp"].as_u64().unwrap_or(0),
+                    v["value"].as_str().unwrap_or_default().as_bytes().to_vec(),
+                ))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl Backend for RemoteBackend {
+    fn put(&mut self, cf: &str, row: &str, column: &str, value: &str) -> IoResult<()> {
+        self.post(&self.cf_url(cf, "put"), json!({ "row": row, "column": column, "value": value }))?;
+        Ok(())
+    }
+
+    fn put_row(&mut self, cf: &str, row: &[u8], columns: Vec<(Vec<u8>, Vec<u8>)>) -> IoResult<()> {
+        let row = String::from_utf8_lossy(row);
+        let operations: Vec<Value> = columns.iter()
+            .map(|(column, value)| json!({
+                "type": "Put",
+                "data": {
+                    "row": row,
+                    "column": String::from_utf8_lossy(column),
+                    "value": String::from_utf8_lossy(value),
+                }
+            }))
+            .collect();
+        self.post(&self.cf_url(cf, "batch"), json!({ "operations": operations }))?;
+        Ok(())
+    }
+
+    fn get(&mut self, cf: &str, row: &str, column: &str, versions: usize) -> IoResult<Vec<(Timestamp, Vec<u8>)>> {
+        // A plain get honours tombstones; the versioned form is only used for visible cells
+        let latest = self.try_post(&self.cf_url(cf, "get"), json!({ "row": row, "column": column }))?;
+        if latest.is_none() {
+            return Ok(Vec::new());
+        }
+        let resp = self.post(
+            &self.cf_url(cf, "get"),
+            json!({ "row": row, "column": column, "max_versions": versions }),
+        )?;
+        Ok(parse_versions(&resp))
+    }
+
+    fn scan(&mut self, cf: &str, start_row: &str, end_row: &str, versions: usize) -> IoResult<RangeVersions> {
+        let resp = self.post(
+            &self.cf_url(cf, "scan_range"),
+            json!({ "start_row": start_row, "end_row": end_row, "max_versions_per_column": versions }),
+        )?;
+
+        let mut result = RangeVersions::new();
+        if let Some(rows) = resp.as_object() {
+            for (row, columns) in rows {
+                let row_data = result.entry(row.as_bytes().to_vec()).or_default();
+                for (column, versions) in columns.as_object().into_iter().flatten() {
+                    row_data.insert(column.as_bytes().to_vec(), parse_versions(versions));
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn delete(&mut self, cf: &str, row: &str, column: &str, ttl_ms: Option<u64>) -> IoResult<()> {
+        self.post(&self.cf_url(cf, "delete"), json!({ "row": row, "column": column, "ttl_ms": ttl_ms }))?;
+        Ok(())
+    }
+
+    fn create_cf(&mut self, name: &str) -> IoResult<()> {
+        self.post(&self.table_url(), json!({ "name": name }))?;
+        Ok(())
+    }
+
+    fn flush(&mut self, cf: &str) -> IoResult<()> {
+        self.post(&self.cf_url(cf, "flush"), json!({}))?;
+        Ok(())
+    }
+
+    fn compact(&mut self, cf: &str, major: bool) -> IoResult<()> {
+        let body = if major { json!({ "type": "major" }) } else { json!({}) };
+        self.post(&self.cf_url(cf, "compact"), body)?;
+        Ok(())
+    }
+
+    fn freeze(&mut self, cf: &str) -> IoResult<()> {
+        self.post(&self.cf_url(cf, "freeze"), json!({}))?;
+        Ok(())
+    }
+
+    fn stats(&mut self, cf: Option<&str>) -> IoResult<Vec<CfStatsRecord>> {
+        let names = match cf {
+            Some(name) => vec![name.to_string()],
+            None => self.describe()?.column_families,
+        };
+        names.into_iter()
+            .map(|name| {
+                let resp = self.get_json(&self.cf_url(&name, "stats"))?;
+                Ok(CfStatsRecord {
+                    column_family: name,
+                    sst_files: resp["sst_files"].as_u64().unwrap_or(0) as usize,
+                    sst_bytes: resp["sst_bytes"].as_u64().unwrap_or(0),
+                    wal_bytes: None,
+                })
+            })
+            .collect()
+    }
+
+    fn verify(&mut self, _cf: Option<&str>, _quarantine: bool) -> IoResult<Vec<VerifyReport>> {
+        Err(Error::new(ErrorKind::Unsupported, "Verification is not available over REST"))
+    }
+
+    fn describe(&mut self) -> IoResult<TableDescription> {
+        let resp = self.get_json(&self.table_url())?;
+        let column_families = resp["column_families"].as_array()
+            .map(|names| names.iter().filter_map(|n| n.as_str().map(str::to_string)).collect())
+            .unwrap_or_default();
+        Ok(TableDescription {
+            table: self.table.clone(),
+            column_families,
+        })
+    }
+}
+
+/// Open the backend selected by the command-line flags.
+pub fn connect(cli: &Cli) -> IoResult<Box<dyn Backend>> {
+    match (&cli.url, &cli.table_dir) {
+        (Some(url), _) => Ok(Box::new(RemoteBackend::new(url, &cli.table))),
+        (None, Some(dir)) => Ok(Box::new(LocalBackend::open(dir)?)),
+        (None, None) => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Either --table-dir or --url must be given",
+        )),
+    }
+}
+
+fn cell_records(row: &str, column: &str, versions: Vec<(Timestamp, Vec<u8>)>) -> Vec<CellRecord> {
+    versions.into_iter()
+        .map(|(timestamp, value)| CellRecord {
+            row: row.to_string(),
+            column: column.to_string(),
+            timestamp,
+            value: String::from_utf8_lossy(&value).to_string(),
+        })
+        .collect()
+}
+
+/// Execute a single command against a backend.
+pub fn execute(backend: &mut dyn Backend, command: &Command) -> IoResult<Output> {
+    match command {
+        Command::Put { cf, row, column, value } => {
+            backend.put(cf, row, column, value)?;
+            Ok(Output::Status(format!("Put {}:{} in {}", row, column, cf)))
+        }
+        Command::Get { cf, row, column, versions } => {
+            let result = backend.get(cf, row, column, *versions)?;
+            Ok(Output::Cells(cell_records(row, column, result)))
+        }
+        Command::Scan { cf, start_row, end_row, versions } => {
+            let result = backend.scan(cf, start_row, end_row, *versions)?;
+            let cells = result.into_iter()
+                .flat_map(|(row, columns)| {
+                    let row = String::from_utf8_lossy(&row).to_string();
+                    columns.into_iter()
+                        .flat_map(move |(column, versions)| {
+                            cell_records(&row, &String::from_utf8_lossy(&column), versions)
+                        })
+                })
+                .collect();
+            Ok(Output::Cells(cells))
+        }
+        Command::Delete { cf, row, column, ttl_ms } => {
+            backend.delete(cf, row, column, *ttl_ms)?;
+            Ok(Output::Status(format!("Deleted {}:{} in {}", row, column, cf)))
+        }
+        Command::CreateCf { name } => {
+            backend.create_cf(name)?;
+            Ok(Output::Status(format!("Created column family {}", name)))
+        }
+        Command::Flush { cf } => {
+            backend.flush(cf)?;
+            Ok(Output::Status(format!("Flushed {}", cf)))
+        }
+        Command::Compact { cf, major } => {
+            backend.compact(cf, *major)?;
+            let kind = if *major { "major" } else { "minor" };
+            Ok(Output::Status(format!("Ran {} compaction on {}", kind, cf)))
+        }
+        Command::Import { cf, file, file_format: format, row_key, key_separator, mappings } => {
+            let mapping = ImportMapping {
+                row_key_fields: row_key.clone(),
+                row_key_separator: key_separator.clone(),
+                columns: mappings.clone(),
+                skip_empty: true,
+            };
+            let format = file_format(*format, file)?;
+            let records = tabular::read_records(&mut BufReader::new(File::open(file)?), format, &mapping)?;
+
+            let (mut rows, mut cells) = (0, 0);
+            for record in records.into_iter().filter(|r| !r.columns.is_empty()) {
+                cells += record.columns.len();
+                backend.put_row(cf, &record.row, record.columns)?;
+                rows += 1;
+            }
+            Ok(Output::Status(format!("Imported {} rows ({} cells) into {}", rows, cells, cf)))
+        }
+        Command::Export { cf, start_row, end_row, file, file_format: format, row_key_field } => {
+            let rows = backend.scan(cf, start_row, end_row, 1)?;
+            let format = file_format(*format, file)?;
+            let mut writer = BufWriter::new(File::create(file)?);
+            let written = tabular::write_rows(&mut writer, format, &rows, row_key_field)?;
+            writer.flush()?;
+            Ok(Output::Status(format!("Exported {} rows from {}", written, cf)))
+        }
+        Command::Freeze { cf } => {
+            backend.freeze(cf)?;
+            Ok(Output::Status(format!("Froze {}", cf)))
+        }
+        Command::Stats { cf } => Ok(Output::Stats(backend.stats(cf.as_deref())?)),
+        Command::Verify { cf, quarantine } => Ok(Output::Verify(backend.verify(cf.as_deref(), *quarantine)?)),
+        Command::Describe => Ok(Output::Description(backend.describe()?)),
+        Command::Shell { .. } => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "The shell can only be started from the command line",
+        )),
+        Command::Migrate { .. } => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Migrations can only be run from the command line, on a table that is not open",
+        )),
+        Command::Fsck => Err(Error::new(
+            ErrorKind::InvalidInput,
+            "fsck can only be run from the command line, on a table that is not open",
+        )),
+    }
+}
+
+/// Connect to the selected backend and execute the parsed command.
+///
+/// `shell` runs the interactive loop until the user exits and then reports a status.
+pub fn run(cli: &Cli) -> IoResult<Output> {
+    // Migrations and checks work on the files of a table that is not open
+    if let Command::Fsck = &cli.command {
+        let Some(dir) = cli.table_dir.as_deref().filter(|_| cli.url.is_none()) else {
+            return Err(Error::new(ErrorKind::InvalidInput, "fsck needs --table-dir"));
+        };
+        return Ok(Output::Recovery(recovery::inspect_table(dir)?));
+    }
+    if let Command::Migrate { rollback, finish } = &cli.command {
+        let Some(dir) = cli.table_dir.as_deref().filter(|_| cli.url.is_none()) else {
+            return Err(Error::new(ErrorKind::InvalidInput, "migrate needs --table-dir"));
+        };
+        let report = match (rollback, finish) {
+            (true, _) => migrate::rollback(dir)?,
+            (_, true) => migrate::finish(dir)?,
+            _ => migrate::migrate(dir)?,
+        };
+        return Ok(Output::Migration(report));
+    }
+    let mut backend = connect(cli)?;
+    if let Command::Shell { history_file } = &cli.command {
+        crate::shell::run_shell(backend.as_mut(), cli.format, history_file.clone())?;
+        return Ok(Output::Status("Bye".to_string()));
+    }
+    execute(backend.as_mut(), &cli.command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn run_local(dir: &Path, args: &[&str]) -> Output {
+        let mut argv = vec!["redbase-cli", "--table-dir", dir.to_str().unwrap()];
+        argv.extend_from_slice(args);
+        run(&Cli::try_parse_from(argv).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_local_commands() {
+        let dir = tempdir().unwrap();
+
+        run_local(dir.path(), &["create-cf", "default"]);
+        run_local(dir.path(), &["put", "default", "row1", "col1", "value1"]);
+        run_local(dir.path(), &["put", "default", "row2", "col1", "value2"]);
+
+        match run_local(dir.path(), &["get", "default", "row1", "col1"]) {
+            Output::Cells(cells) => {
+                assert_eq!(cells.len(), 1);
+                assert_eq!(cells[0].value, "value1");
+            }
+            other => panic!("Expected cells, got {:?}", other),
+        }
+
+        run_local(dir.path(), &["flush", "default"]);
+
+        match run_local(dir.path(), &["scan", "default", "row1", "row2"]) {
+            Output::Cells(cells) => assert_eq!(cells.len(), 2),
+            other => panic!("Expected cells, got {:?}", other),
+        }
+
+        match run_local(dir.path(), &["stats", "default"]) {
+            Output::Stats(stats) => {
+                assert_eq!(stats.len(), 1);
+                assert_eq!(stats[0].sst_files, 1);
+            }
+            other => panic!("Expected stats, got {:?}", other),
+        }
+
+        match run_local(dir.path(), &["verify", "default", "--quarantine"]) {
+            Output::Verify(reports) => {
+                assert_eq!(reports.len(), 1);
+                assert_eq!(reports[0].sstables.len(), 1);
+                assert!(reports[0].is_ok());
+            }
+            other => panic!("Expected verify reports, got {:?}", other),
+        }
+
+        match run_local(dir.path(), &["describe"]) {
+            Output::Description(description) => assert_eq!(description.column_families, vec!["default"]),
+            other => panic!("Expected description, got {:?}", other),
+        }
+
+        // Everything is in the current format already
+        match run_local(dir.path(), &["migrate"]) {
+            Output::Migration(report) => assert!(report.is_empty()),
+            other => panic!("Expected a migration report, got {:?}", other),
+        }
+        assert!(matches!(run_local(dir.path(), &["migrate", "--finish"]), Output::Migration(_)));
+
+        match run_local(dir.path(), &["fsck"]) {
+            Output::Recovery(report) => {
+                assert!(report.is_ok());
+                assert_eq!(report.column_families[0].column_family, "default");
+                assert_eq!(report.column_families[0].sstables_live, 1);
+            }
+            other => panic!("Expected a recovery report, got {:?}", other),
+        }
+
+        run_local(dir.path(), &["delete", "default", "row1", "col1"]);
+        match run_local(dir.path(), &["get", "default", "row1", "col1"]) {
+            Output::Cells(cells) => assert!(cells.is_empty()),
+            other => panic!("Expected cells, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_local_stats() {
+        let dir = tempdir().unwrap();
+        {
+            let table = Table::open(dir.path()).unwrap();
+            table.create_cf("default").unwrap();
+            let cf = table.cf("default").unwrap();
+            cf.put(b"row1".to_vec(), b"col1".to_vec(), b"v".to_vec()).unwrap();
+            cf.flush().unwrap();
+            cf.put(b"row2".to_vec(), b"col1".to_vec(), b"v".to_vec()).unwrap();
+            table.create_regioned_cf("events", crate::region::RegionConfig::default(), &[b"m".to_vec()]).unwrap();
+            let events = table.regioned_cf("events").unwrap();
+            for row in ["a", "z"] {
+                events.put(row.as_bytes().to_vec(), b"col1".to_vec(), b"v".to_vec()).unwrap();
+            }
+            events.flush().unwrap();
+        }
+        // An SSTable a crash left unrecorded is not counted
+        let cf_dir = dir.path().join("default");
+        fs::copy(cf_dir.join("0000000001.sst"), cf_dir.join("0000000009.sst")).unwrap();
+
+        match run_local(dir.path(), &["stats"]) {
+            Output::Stats(stats) => {
+                let counts: Vec<(&str, usize)> = stats.iter().map(|s| (s.column_family.as_str(), s.sst_files)).collect();
+                assert_eq!(counts, vec![("default", 1), ("events", 2)]);
+                assert_eq!(stats[0].sst_bytes, fs::metadata(cf_dir.join("0000000001.sst")).unwrap().len());
+                // row2 is only in the WAL
+                assert!(stats[0].wal_bytes.unwrap() > 0);
+            }
+            other => panic!("Expected stats, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_import_export_commands() {
+        let dir = tempdir().unwrap();
+        let table_dir = dir.path().join("table");
+        let input = dir.path().join("users.csv");
+        let output = dir.path().join("users.ndjson");
+        fs::write(&input, "id,name,age\nu1,alice,30\nu2,bob,41\n").unwrap();
+
+        run_local(&table_dir, &["create-cf", "users"]);
+        match run_local(&table_dir, &["import", "users", input.to_str().unwrap(), "--row-key", "id", "--map", "name=n"]) {
+            Output::Status(message) => assert_eq!(message, "Imported 2 rows (2 cells) into users"),
+            other => panic!("Expected status, got {:?}", other),
+        }
+
+        run_local(&table_dir, &["export", "users", "u0", "u9", output.to_str().unwrap(), "--row-key-field", "id"]);
+        assert_eq!(
+            fs::read_to_string(&output).unwrap(),
+            "{\"id\":\"u1\",\"n\":\"alice\"}\n{\"id\":\"u2\",\"n\":\"bob\"}\n"
+        );
+    }
+
+    #[test]
+    fn test_remote_compact_and_stats() {
+        use crate::rest::{routes, AppState, RestConfig};
+        use actix_web::{web, App, HttpServer};
+
+        let dir = tempdir().unwrap();
+        let config = RestConfig { base_dir: dir.path().to_path_buf(), pool_size: 2, ..RestConfig::default() };
+        let (addr_tx, addr_rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            actix_web::rt::System::new().block_on(async move {
+                let state = web::Data::new(AppState::new(&config));
+                let server = HttpServer::new(move || App::new().app_data(state.clone()).configure(routes))
+                    .workers(1)
+                    .bind("127.0.0.1:0")
+                    .unwrap();
+                addr_tx.send(server.addrs()[0]).unwrap();
+                server.run().await
+            })
+        });
+        let mut backend = RemoteBackend::new(&format!("http://{}", addr_rx.recv().unwrap()), "t");
+
+        backend.create_cf("default").unwrap();
+        for row in ["row1", "row2"] {
+            backend.put("default", row, "col1", "v").unwrap();
+            backend.flush("default").unwrap();
+        }
+        assert_eq!(backend.stats(Some("default")).unwrap()[0].sst_files, 2);
+        backend.compact("default", true).unwrap();
+        let stats = backend.stats(None).unwrap();
+        assert_eq!((stats[0].column_family.as_str(), stats[0].sst_files, stats[0].wal_bytes), ("default", 1, None));
+        assert_eq!(backend.stats(Some("missing")).unwrap_err().kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_render_formats() {
+        let output = Output::Cells(vec![CellRecord {
+            row: "row1".to_string(),
+            column: "col1".to_string(),
+            timestamp: 42,
+            value: "v".to_string(),
+        }]);
+
+        let table = output.render(OutputFormat::Table);
+        assert!(table.starts_with("ROW"));
+        assert!(table.contains("row1  col1    42         v"));
+
+        let parsed: Value = serde_json::from_str(&output.render(OutputFormat::Json)).unwrap();
+        assert_eq!(parsed[0]["timestamp"], 42);
+    }
+}
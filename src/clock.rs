@@ -0,0 +1,81 @@
+//! `HybridClock`, the hybrid logical clock timestamping the writes of a column family.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::api::Timestamp;
+
+/// The wall clock's time in milliseconds since the Unix epoch.
+pub fn wall_clock() -> Timestamp {
+    chrono::Utc::now().timestamp_millis() as u64
+}
+
+/// Clock that never goes backwards, even when NTP steps the wall clock back. It hands
+/// out the wall clock's time unless it has handed out or seen a later one; then it holds
+/// at that time until the wall clock catches up, so timestamps stay close to real time
+/// and TTLs and time ranges keep their meaning.
+///
+/// Writes sharing a millisecond are ordered by the sequence number the WAL gives them
+/// (see `MemStore`), the logical part of the clock. The clock also moves forward to the
+/// timestamps of entries written with their own (replicated or imported ones), and the
+/// column family restores it on open from the manifest and the WAL.
+#[derive(Debug, Default)]
+pub struct HybridClock {
+    /// Latest timestamp handed out or seen
+    last: AtomicU64,
+}
+
+impl HybridClock {
+    /// A clock that hands out nothing before last.
+    pub fn new(last: Timestamp) -> Self {
+        Self { last: AtomicU64::new(last) }
+    }
+
+    /// Timestamp for a write made now: the wall clock's time, or the latest timestamp
+    /// handed out or seen if that is later.
+    pub fn now(&self) -> Timestamp {
+        let wall = wall_clock();
+        self.last.fetch_max(wall, Ordering::SeqCst).max(wall)
+    }
+
+    /// Never hand out a timestamp before timestamp from now on.
+    pub fn observe(&self, timestamp: Timestamp) {
+        self.last.fetch_max(timestamp, Ordering::SeqCst);
+    }
+
+    /// Latest timestamp handed out or seen.
+    pub fn last(&self) -> Timestamp {
+        self.last.load(Ordering::SeqCst)
+    }
+
+    /// How many milliseconds the clock is ahead of the wall clock, e.g. since the wall
+    /// clock was set back; 0 while it follows it.
+    pub fn drift_ms(&self) -> u64 {
+        self.last().saturating_sub(wall_clock())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_never_goes_backwards() {
+        let clock = HybridClock::default();
+        let first = clock.now();
+        assert!(first >= wall_clock() - 1000);
+        assert!(clock.now() >= first);
+        assert_eq!(clock.drift_ms(), 0);
+
+        // A wall clock set back an hour: the clock holds at the latest time it saw
+        let ahead = wall_clock() + 3_600_000;
+        clock.observe(ahead);
+        clock.observe(first);
+        assert_eq!(clock.now(), ahead);
+        assert_eq!(clock.now(), ahead);
+        assert_eq!(clock.last(), ahead);
+        assert!(clock.drift_ms() > 3_500_000);
+
+        // Restarted from a recorded time
+        assert_eq!(HybridClock::new(ahead).now(), ahead);
+    }
+}
@@ -0,0 +1,61 @@
+//! Scheduled expiry sweeps of a column family.
+//!
+//! Expired versions and tombstones past their TTL are hidden from reads but keep using
+//! disk until a compaction drops them, and the background compaction is minor: cells in
+//! the older SSTables may wait a long time. `ColumnFamily::expire_now` drops them on
+//! demand; an `ExpirySchedule` runs it periodically on a thread of its own, started the
+//! first time an interval is set.
+
+use std::{
+    sync::{Arc, Condvar, Mutex, OnceLock},
+    thread,
+    time::Duration,
+};
+
+/// Interval of the expiry sweeps of a column family, if any.
+#[derive(Default)]
+pub(crate) struct ExpirySchedule {
+    interval: Mutex<Option<Duration>>,
+    changed: Condvar,
+    started: OnceLock<()>,
+}
+
+impl ExpirySchedule {
+    /// The interval between sweeps, or `None` if sweeps are off.
+    pub fn interval(&self) -> Option<Duration> {
+        *self.interval.lock().unwrap()
+    }
+
+    /// Sweep every interval, or stop sweeping with `None`. The first interval set starts a
+    /// thread named after name that calls sweep; later calls only change the interval,
+    /// which restarts the wait for the next sweep.
+    pub fn set(self: &Arc<Self>, name: &str, interval: Option<Duration>, sweep: impl Fn() + Send + 'static) {
+        *self.interval.lock().unwrap() = interval;
+        self.changed.notify_all();
+        if interval.is_some() && self.started.set(()).is_ok() {
+            let schedule = Arc::clone(self);
+            thread::Builder::new()
+                .name(format!("redbase-expiry-{}", name))
+                .spawn(move || schedule.run(sweep))
+                .expect("failed to spawn expiry thread");
+        }
+    }
+
+    fn run(&self, sweep: impl Fn()) {
+        let mut interval = self.interval.lock().unwrap();
+        loop {
+            match *interval {
+                None => interval = self.changed.wait(interval).unwrap(),
+                Some(period) => {
+                    let (guard, wait) = self.changed.wait_timeout(interval, period).unwrap();
+                    interval = guard;
+                    if wait.timed_out() && interval.is_some() {
+                        drop(interval);
+                        sweep();
+                        interval = self.interval.lock().unwrap();
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use regex::Regex as RegexPattern;
 
+use crate::script;
+
 /// Filter represents a predicate that can be applied to cell values
 /// to determine if they should be included in query results.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +20,11 @@ pub enum Filter {
     /// The value must be valid UTF-8 and the pattern must be a valid regex
     /// Returns false if the value is not valid UTF-8 or the pattern is not a valid regex
     Regex(String),
+    /// Match values for which the given Rhai script returns true. The script sees the
+    /// value as the BLOB `value`, and as the string `text` if it is valid UTF-8 (unit
+    /// otherwise). Scripts are sandboxed and time-limited; one that fails matches
+    /// nothing. See `script`.
+    Script(String),
     /// Combine multiple filters with AND logic (all must match)
     And(Vec<Filter>),
     /// Combine multiple filters with OR logic (any must match)
@@ -50,11 +57,22 @@ impl Filter {
                     false
                 }
             },
+            Filter::Script(source) => script::matches(source, value),
             Filter::And(filters) => filters.iter().all(|f| f.matches(value)),
             Filter::Or(filters) => filters.iter().any(|f| f.matches(value)),
             Filter::Not(filter) => !filter.matches(value),
         }
     }
+
+    /// Check that every script in the filter compiles, returning the first error.
+    pub fn check_scripts(&self) -> Result<(), String> {
+        match self {
+            Filter::Script(source) => script::check(source),
+            Filter::And(filters) | Filter::Or(filters) => filters.iter().try_for_each(Filter::check_scripts),
+            Filter::Not(filter) => filter.check_scripts(),
+            _ => Ok(()),
+        }
+    }
 }
 
 fn contains_subsequence(value: &[u8], subsequence: &[u8]) -> bool {
@@ -112,8 +130,8 @@ impl FilterSet {
 
     pub fn timestamp_matches(&self, timestamp: u64) -> bool {
         if let Some((min, max)) = self.timestamp_range {
-            let min_match = min.map_or(true, |min_ts| timestamp >= min_ts);
-            let max_match = max.map_or(true, |max_ts| timestamp <= max_ts);
+            let min_match = min.is_none_or(|min_ts| timestamp >= min_ts);
+            let max_match = max.is_none_or(|max_ts| timestamp <= max_ts);
             min_match && max_match
         } else {
             true
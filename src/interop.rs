@@ -0,0 +1,278 @@
+//! Export and import of column families as HBase `KeyValue` records, in a simple
+//! container rather than an HFile (see `export_cf` for its layout).
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Read, Result as IoResult, Write},
+    path::Path,
+};
+
+use crate::api::{CellValue, ColumnFamily, Entry, EntryKey};
+
+/// Magic bytes at the start of every export file.
+pub const EXPORT_MAGIC: &[u8; 8] = b"RBKVEXP1";
+
+/// HBase `KeyValue.Type` codes.
+const TYPE_PUT: u8 = 4;
+const TYPE_DELETE: u8 = 8;
+const TYPE_DELETE_COLUMN: u8 = 12;
+const TYPE_DELETE_FAMILY: u8 = 14;
+
+/// Length of the fixed-size fields of a KeyValue key (row length, family length, timestamp, type).
+const KEY_INFRASTRUCTURE_SIZE: usize = 2 + 1 + 8 + 1;
+
+fn invalid_data(message: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidData, message.into())
+}
+
+/// Encode one cell as an HBase `KeyValue`, including its two length prefixes. Puts use
+/// type 4. RedBase tombstones hide every older version of a column, which is HBase's
+/// DeleteColumn (type 12); their TTL has no HBase equivalent and is not exported. Family
+/// delete markers are DeleteFamily (14).
+pub fn encode_key_value(family: &[u8], entry: &Entry) -> IoResult<Vec<u8>> {
+    if entry.key.row.len() > i16::MAX as usize {
+        return Err(Error::new(ErrorKind::InvalidInput, "Row key is longer than HBase allows"));
+    }
+    if family.len() > u8::MAX as usize {
+        return Err(Error::new(ErrorKind::InvalidInput, "Family name is longer than HBase allows"));
+    }
+
+    let (kv_type, value): (u8, &[u8]) = match &entry.value {
+        CellValue::Put(value) => (TYPE_PUT, value),
+        CellValue::Delete(_) => (TYPE_DELETE_COLUMN, &[]),
+        CellValue::DeleteFamily => (TYPE_DELETE_FAMILY, &[]),
+    };
+
+    let key_len = KEY_INFRASTRUCTURE_SIZE + entry.key.row.len() + family.len() + entry.key.column.len();
+    let mut buf = Vec::with_capacity(8 + key_len + value.len());
+    buf.extend_from_slice(&(key_len as u32).to_be_bytes());
+    buf.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    buf.extend_from_slice(&(entry.key.row.len() as u16).to_be_bytes());
+    buf.extend_from_slice(&entry.key.row);
+    buf.push(family.len() as u8);
+    buf.extend_from_slice(family);
+    buf.extend_from_slice(&entry.key.column);
+    buf.extend_from_slice(&entry.key.timestamp.to_be_bytes());
+    buf.push(kv_type);
+    buf.extend_from_slice(value);
+    Ok(buf)
+}
+
+/// Decode the key and value of an HBase `KeyValue` (without its length prefixes).
+/// Delete (8) and DeleteColumn (12) become tombstones, and DeleteFamily (14)
+/// `CellValue::DeleteFamily`.
+pub fn decode_key_value(key: &[u8], value: Vec<u8>) -> IoResult<Entry> {
+    if key.len() < KEY_INFRASTRUCTURE_SIZE {
+        return Err(invalid_data("KeyValue key is too short"));
+    }
+
+    let row_len = u16::from_be_bytes([key[0], key[1]]) as usize;
+    let family_len_at = 2 + row_len;
+    let family_len = *key.get(family_len_at).ok_or_else(|| invalid_data("KeyValue row overruns key"))? as usize;
+    let qualifier_at = family_len_at + 1 + family_len;
+    let timestamp_at = key.len() - 9;
+    if qualifier_at > timestamp_at {
+        return Err(invalid_data("KeyValue family overruns key"));
+    }
+
+    let mut timestamp = [0u8; 8];
+    timestamp.copy_from_slice(&key[timestamp_at..timestamp_at + 8]);
+    let value = match key[key.len() - 1] {
+        TYPE_PUT => CellValue::Put(value),
+        TYPE_DELETE | TYPE_DELETE_COLUMN => CellValue::Delete(None),
+        TYPE_DELETE_FAMILY => CellValue::DeleteFamily,
+        other => return Err(invalid_data(format!("Unsupported KeyValue type {}", other))),
+    };
+
+    Ok(Entry {
+        key: EntryKey {
+            row: key[2..family_len_at].to_vec(),
+            column: key[qualifier_at..timestamp_at].to_vec(),
+            timestamp: u64::from_be_bytes(timestamp),
+            seq: 0,
+        },
+        value,
+    })
+}
+
+/// Export every cell version of cf, including tombstones, to path.
+/// Returns the number of records written.
+///
+/// File layout (all integers big-endian):
+///
+/// 1) Header: the 8 magic bytes `RBKVEXP1`, then `[u16: family length][family bytes]`.
+/// 2) Records until end of file, each an HBase `KeyValue`, in HBase order (by row, then
+///    qualifier, newest timestamp first):
+///    a) `[u32: key length][u32: value length]`
+///    b) key: `[u16: row length][row][u8: family length][family][qualifier][u64: timestamp][u8: type]`
+///    c) value bytes
+///
+/// On the HBase side each record can be handed to `new KeyValue(bytes, 0, length)` and
+/// written out with `HFile.Writer` or a bulk-load job; records exported from HBase
+/// (`KeyValue.getBuffer()`) can be imported unchanged.
+pub fn export_cf(cf: &ColumnFamily, path: impl AsRef<Path>) -> IoResult<u64> {
+    let mut entries = cf.raw_entries()?;
+    entries.sort_by(|a, b| {
+        (&a.key.row, &a.key.column)
+            .cmp(&(&b.key.row, &b.key.column))
+            .then(b.key.timestamp.cmp(&a.key.timestamp))
+    });
+
+    let family = cf.name().as_bytes();
+    let mut w = BufWriter::new(File::create(path)?);
+    w.write_all(EXPORT_MAGIC)?;
+    w.write_all(&(family.len() as u16).to_be_bytes())?;
+    w.write_all(family)?;
+
+    for entry in &entries {
+        w.write_all(&encode_key_value(family, entry)?)?;
+    }
+    w.flush()?;
+    Ok(entries.len() as u64)
+}
+
+/// Import an export file into cf, keeping the original timestamps.
+/// The family recorded in the file is ignored, so data can be imported under a new name.
+/// Returns the number of records imported.
+pub fn import_cf(cf: &ColumnFamily, path: impl AsRef<Path>) -> IoResult<u64> {
+    let mut r = BufReader::new(File::open(path)?);
+
+    let mut magic = [0u8; 8];
+    r.read_exact(&mut magic)?;
+    if &magic != EXPORT_MAGIC {
+        return Err(invalid_data("Not a RedBase export file"));
+    }
+    let mut buf2 = [0u8; 2];
+    r.read_exact(&mut buf2)?;
+    let mut family = vec![0u8; u16::from_be_bytes(buf2) as usize];
+    r.read_exact(&mut family)?;
+
+    let mut entries = Vec::new();
+    let mut lengths = [0u8; 8];
+    // Only the end of the file between records ends the import; a record cut short
+    // anywhere, its length prefixes included, is an error
+    while !r.fill_buf()?.is_empty() {
+        read_record_part(&mut r, &mut lengths)?;
+        let key_len = u32::from_be_bytes([lengths[0], lengths[1], lengths[2], lengths[3]]) as usize;
+        let value_len = u32::from_be_bytes([lengths[4], lengths[5], lengths[6], lengths[7]]) as usize;
+
+        let mut key = vec![0u8; key_len];
+        read_record_part(&mut r, &mut key)?;
+        let mut value = vec![0u8; value_len];
+        read_record_part(&mut r, &mut value)?;
+        entries.push(decode_key_value(&key, value)?);
+    }
+
+    let count = entries.len() as u64;
+    cf.write_entries(entries)?;
+    Ok(count)
+}
+
+/// Fill buf from r, failing with `InvalidData` if the file ends first.
+fn read_record_part(r: &mut impl Read, buf: &mut [u8]) -> IoResult<()> {
+    r.read_exact(buf).map_err(|e| match e.kind() {
+        ErrorKind::UnexpectedEof => invalid_data("Truncated record"),
+        _ => e,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Table;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_key_value_layout() {
+        let entry = Entry {
+            key: EntryKey {
+                row: b"r".to_vec(),
+                column: b"q".to_vec(),
+                timestamp: 1,
+                seq: 0,
+            },
+            value: CellValue::Put(b"v".to_vec()),
+        };
+        let encoded = encode_key_value(b"f", &entry).unwrap();
+
+        let expected: Vec<u8> = [
+            &[0, 0, 0, 15][..],       // key length
+            &[0, 0, 0, 1],            // value length
+            &[0, 1], b"r",            // row
+            &[1], b"f",               // family
+            b"q",                     // qualifier
+            &[0, 0, 0, 0, 0, 0, 0, 1], // timestamp
+            &[TYPE_PUT],
+            b"v",
+        ].concat();
+        assert_eq!(encoded, expected);
+
+        let decoded = decode_key_value(&encoded[8..23], encoded[23..].to_vec()).unwrap();
+        assert_eq!(decoded.key, entry.key);
+        assert_eq!(decoded.value, entry.value);
+    }
+
+    #[test]
+    fn test_export_import_roundtrip() {
+        let dir = tempdir().unwrap();
+        let export_path = dir.path().join("users.kv");
+        let table = Table::open(dir.path().join("table")).unwrap();
+        table.create_cf("users").unwrap();
+        table.create_cf("restored").unwrap();
+        let source = table.cf("users").unwrap();
+        let target = table.cf("restored").unwrap();
+
+        source.put(b"row1".to_vec(), b"name".to_vec(), b"alice".to_vec()).unwrap();
+        source.put(b"row2".to_vec(), b"name".to_vec(), b"bob".to_vec()).unwrap();
+        source.flush().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        source.put(b"row1".to_vec(), b"name".to_vec(), b"alicia".to_vec()).unwrap();
+        source.delete(b"row2".to_vec(), b"name".to_vec()).unwrap();
+
+        assert_eq!(export_cf(&source, &export_path).unwrap(), 4);
+        assert_eq!(import_cf(&target, &export_path).unwrap(), 4);
+
+        assert_eq!(target.get(b"row1", b"name").unwrap(), Some(b"alicia".to_vec()));
+        assert_eq!(target.get(b"row2", b"name").unwrap(), None);
+        assert_eq!(
+            target.get_versions(b"row1", b"name", 10).unwrap(),
+            source.get_versions(b"row1", b"name", 10).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_import_rejects_other_files() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("bogus");
+        std::fs::write(&path, b"not an export").unwrap();
+
+        let table = Table::open(dir.path().join("table")).unwrap();
+        table.create_cf("default").unwrap();
+        let err = import_cf(&table.cf("default").unwrap(), &path).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_import_rejects_truncated_records() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("users.kv");
+        let table = Table::open(dir.path().join("table")).unwrap();
+        table.create_cf("users").unwrap();
+        table.create_cf("restored").unwrap();
+        let source = table.cf("users").unwrap();
+        let target = table.cf("restored").unwrap();
+        source.put(b"row1".to_vec(), b"name".to_vec(), b"alice".to_vec()).unwrap();
+        source.put(b"row2".to_vec(), b"name".to_vec(), b"bob".to_vec()).unwrap();
+        export_cf(&source, &path).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        let record_len = 8 + KEY_INFRASTRUCTURE_SIZE + 4 + 5 + 4 + 3;
+
+        // Cut inside the length prefixes of the last record, and inside its key
+        for cut in [3, 8 + 2] {
+            std::fs::write(&path, &data[..data.len() - record_len + cut]).unwrap();
+            let err = import_cf(&target, &path).unwrap_err();
+            assert_eq!((err.kind(), err.to_string()), (ErrorKind::InvalidData, "Truncated record".to_string()));
+        }
+        assert_eq!(target.get(b"row1", b"name").unwrap(), None);
+    }
+}
@@ -1,9 +1,44 @@
+#![allow(non_snake_case)]
+
 pub mod api;
 pub mod storage;
+pub mod bloom;
+pub mod types;
+pub mod backend;
+pub mod backup;
+pub mod merge;
+pub mod throttle;
+pub mod cache;
+pub mod clock;
+pub mod manifest;
+pub mod verify;
+pub mod migrate;
+pub mod recovery;
+pub mod schema;
+pub mod namespace;
+pub mod scan;
+pub mod result;
+pub mod region;
+pub mod memory;
 pub mod memstore;
+pub mod mutation;
+pub mod wal_archive;
+pub(crate) mod writer;
+pub(crate) mod expiry;
+pub mod changelog;
+pub mod replication;
+pub mod interop;
+pub mod tabular;
+pub mod validation;
+pub mod observer;
 pub mod filter;
+pub mod script;
 pub mod aggregation;
+pub mod query;
 pub mod async_api;
 pub mod batch;
 pub mod pool;
 pub mod rest;
+pub mod telemetry;
+pub mod cli;
+pub mod shell;
@@ -0,0 +1,572 @@
+//! Versioned edit log of the live SSTables of a column family (`Manifest`), and the
+//! reference counts that keep SSTables removed from it until their readers are done.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::{self, File, OpenOptions},
+    io::{Error, ErrorKind, Result as IoResult, Write},
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::api::{Seq, Timestamp};
+use crate::storage::Durability;
+
+/// Manifest file (inside each column family) recording its live SSTables.
+pub const MANIFEST_FILE: &str = "MANIFEST";
+
+/// Edits after which the manifest is rewritten as a single snapshot when it is opened.
+const MANIFEST_MAX_EDITS: usize = 1000;
+
+/// One change to the SSTable set. File names are relative to the column family directory.
+///
+/// Edits also record the highest sequence number the MemStore had handed out and the
+/// time of the column family's clock when they were made, so that the writes after a
+/// restart are numbered and timestamped after those already flushed.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifestEdit {
+    /// Version of the set after this edit
+    pub version: u64,
+    /// Replace the whole set with these files (written by rewrites)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<Vec<String>>,
+    /// Files added to the set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub add: Vec<String>,
+    /// Files removed from the set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remove: Vec<String>,
+    /// Lowest SSTable number not yet allocated; absent in manifests written before it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_file: Option<u64>,
+    /// Highest sequence number assigned to a write; absent in manifests written before it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seq: Option<Seq>,
+    /// Latest time of the column family's clock; absent in manifests written before it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_timestamp: Option<Timestamp>,
+    /// Sequence number of the last write discarded by a truncation, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncated_seq: Option<Seq>,
+}
+
+/// The live SSTable set of a column family, backed by its `MANIFEST` edit log.
+///
+/// Every change to the set (a flush, merge, compaction, bulk load, restore, ...) is
+/// appended to the log as one JSON line and synced before it takes effect, so the set
+/// changes atomically: an SSTable written but not yet recorded (e.g. the output of a
+/// compaction interrupted by a crash) is ignored on open, and one recorded as removed is
+/// never read again even if deleting it failed.
+pub struct Manifest {
+    dir: PathBuf,
+    version: u64,
+    live: BTreeSet<String>,
+    next_file: u64,
+    last_seq: Seq,
+    last_timestamp: Timestamp,
+    truncated_seq: Seq,
+}
+
+fn file_name(path: &Path) -> IoResult<String> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(str::to_string)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("Invalid SSTable path {}", path.display())))
+}
+
+/// Number of an SSTable named `{:010}.sst`.
+fn file_number(name: &str) -> Option<u64> {
+    name.strip_suffix(".sst")?.parse().ok()
+}
+
+/// Number one past the highest numbered SSTable among names.
+fn next_after<'a>(names: impl IntoIterator<Item = &'a String>) -> u64 {
+    names.into_iter().filter_map(|name| file_number(name)).max().map_or(1, |n| n + 1)
+}
+
+/// Name of the SSTable numbered number.
+fn numbered_file(number: u64) -> String {
+    format!("{:010}.sst", number)
+}
+
+impl Manifest {
+    /// Open the manifest of the column family directory dir. If there is none, the
+    /// `.sst` files in dir become the live set and a manifest is written for them.
+    pub fn open(dir: &Path) -> IoResult<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        let mut manifest = Self {
+            dir: dir.to_path_buf(),
+            version: 0,
+            live: BTreeSet::new(),
+            next_file: 1,
+            last_seq: 0,
+            last_timestamp: 0,
+            truncated_seq: 0,
+        };
+
+        // Files never recorded (e.g. written by a compaction cut short) keep their
+        // numbers too, so that they are not overwritten
+        let mut on_disk = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "sst") {
+                on_disk.push(file_name(&path)?);
+            }
+        }
+        let past_on_disk = next_after(&on_disk);
+
+        if !path.exists() {
+            manifest.live.extend(on_disk);
+            manifest.next_file = past_on_disk;
+            manifest.rewrite()?;
+            debug!(sst_files = manifest.live.len(), "created manifest from directory listing");
+            return Ok(manifest);
+        }
+
+        let (edits, torn) = read_edits(&path)?;
+        for edit in &edits {
+            manifest.apply(edit);
+        }
+        manifest.next_file = manifest.next_file.max(next_after(&manifest.live)).max(past_on_disk);
+        let missing: Vec<&String> = manifest.live.iter().filter(|name| !dir.join(name).exists()).collect();
+        if !missing.is_empty() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("SSTables listed in {} are missing: {:?}", path.display(), missing),
+            ));
+        }
+        if torn || edits.len() > MANIFEST_MAX_EDITS {
+            manifest.rewrite()?;
+        }
+        debug!(version = manifest.version, sst_files = manifest.live.len(), edits = edits.len(), "opened manifest");
+        Ok(manifest)
+    }
+
+    /// The live SSTable set recorded in the manifest of dir, if it has one.
+    pub fn read_live_files(dir: &Path) -> IoResult<Option<Vec<PathBuf>>> {
+        Ok(Self::read(dir)?.map(|manifest| manifest.live_files()))
+    }
+
+    /// The manifest of dir, if it has one, read without changing anything: it is not
+    /// created, rewritten or checked against the files in dir. Edits made to it are
+    /// appended as usual.
+    pub fn read(dir: &Path) -> IoResult<Option<Self>> {
+        let path = dir.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let mut manifest = Self {
+            dir: dir.to_path_buf(),
+            version: 0,
+            live: BTreeSet::new(),
+            next_file: 1,
+            last_seq: 0,
+            last_timestamp: 0,
+            truncated_seq: 0,
+        };
+        for edit in &read_edits(&path)?.0 {
+            manifest.apply(edit);
+        }
+        Ok(Some(manifest))
+    }
+
+    /// Paths of the live SSTables, sorted by name.
+    pub fn live_files(&self) -> Vec<PathBuf> {
+        self.live.iter().map(|name| self.dir.join(name)).collect()
+    }
+
+    /// Version of the live set; every edit increments it.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Allocate count consecutive SSTable numbers and return the first, from a counter
+    /// that every edit records and that never goes back, so that a name is not handed out
+    /// twice: not to a flush racing a compaction, and not after a restart, even once the
+    /// file that had it was compacted away. Numbers whose file exists already, e.g. written by another handle on the directory, are
+    /// skipped. The allocation is persisted with the next edit; numbers allocated but
+    /// never recorded may be handed out again after a restart if their file was not
+    /// written.
+    pub fn allocate(&mut self, count: usize) -> u64 {
+        let mut first = self.next_file;
+        while let Some(taken) = (first..first + count as u64).rev().find(|&n| self.dir.join(numbered_file(n)).exists()) {
+            first = taken + 1;
+        }
+        self.next_file = first + count as u64;
+        first
+    }
+
+    /// Allocate one SSTable number and return the path of its file.
+    pub fn allocate_file(&mut self) -> PathBuf {
+        let number = self.allocate(1);
+        self.file_path(number)
+    }
+
+    /// Extend the allocation that ended at end (exclusive) by count more numbers, if
+    /// nothing was allocated since. Returns whether it was.
+    pub fn extend_allocation(&mut self, end: u64, count: usize) -> bool {
+        let extended = end..end + count as u64;
+        if self.next_file != end || extended.clone().any(|n| self.dir.join(numbered_file(n)).exists()) {
+            return false;
+        }
+        self.next_file = extended.end;
+        true
+    }
+
+    /// Highest sequence number recorded by an edit, or since advanced to.
+    pub fn last_seq(&self) -> Seq {
+        self.last_seq
+    }
+
+    /// Record seq as handed out, with the next edit.
+    pub fn advance_seq(&mut self, seq: Seq) {
+        self.last_seq = self.last_seq.max(seq);
+    }
+
+    /// Latest clock time recorded by an edit, or since advanced to.
+    pub fn last_timestamp(&self) -> Timestamp {
+        self.last_timestamp
+    }
+
+    /// Record timestamp as the clock's time, with the next edit.
+    pub fn advance_timestamp(&mut self, timestamp: Timestamp) {
+        self.last_timestamp = self.last_timestamp.max(timestamp);
+    }
+
+    /// Sequence number of the last write discarded by a truncation, 0 if there was none.
+    pub fn truncated_seq(&self) -> Seq {
+        self.truncated_seq
+    }
+
+    /// Record that the set is now empty and that every write up to seq was discarded,
+    /// as one atomic edit. Writes up to seq that a crash left in the WAL are dropped
+    /// again when it is replayed.
+    pub fn truncate(&mut self, seq: Seq) -> IoResult<()> {
+        self.advance_seq(seq);
+        let edit = ManifestEdit {
+            version: self.version + 1,
+            snapshot: Some(Vec::new()),
+            next_file: Some(self.next_file),
+            last_seq: Some(self.last_seq),
+            last_timestamp: Some(self.last_timestamp),
+            truncated_seq: Some(seq),
+            ..ManifestEdit::default()
+        };
+        self.append(&edit)?;
+        self.apply(&edit);
+        Ok(())
+    }
+
+    /// Path of the SSTable numbered number.
+    pub fn file_path(&self, number: u64) -> PathBuf {
+        self.dir.join(numbered_file(number))
+    }
+
+    /// Record that added joined the set and removed left it, as one atomic edit.
+    pub fn record(&mut self, added: &[PathBuf], removed: &[PathBuf]) -> IoResult<()> {
+        let edit = ManifestEdit {
+            version: self.version + 1,
+            snapshot: None,
+            add: added.iter().map(|path| file_name(path)).collect::<IoResult<_>>()?,
+            remove: removed.iter().map(|path| file_name(path)).collect::<IoResult<_>>()?,
+            next_file: Some(self.next_file),
+            last_seq: Some(self.last_seq),
+            last_timestamp: Some(self.last_timestamp),
+            truncated_seq: None,
+        };
+        self.append(&edit)?;
+        self.apply(&edit);
+        Ok(())
+    }
+
+    /// Record that the set is now exactly files, as one atomic edit.
+    pub fn replace(&mut self, files: &[PathBuf]) -> IoResult<()> {
+        let edit = ManifestEdit {
+            version: self.version + 1,
+            snapshot: Some(files.iter().map(|path| file_name(path)).collect::<IoResult<_>>()?),
+            next_file: Some(self.next_file),
+            last_seq: Some(self.last_seq),
+            last_timestamp: Some(self.last_timestamp),
+            ..ManifestEdit::default()
+        };
+        self.append(&edit)?;
+        self.apply(&edit);
+        Ok(())
+    }
+
+    fn apply(&mut self, edit: &ManifestEdit) {
+        if let Some(files) = &edit.snapshot {
+            self.live = files.iter().cloned().collect();
+        }
+        for name in &edit.remove {
+            self.live.remove(name);
+        }
+        self.live.extend(edit.add.iter().cloned());
+        self.version = edit.version;
+        self.next_file = self.next_file.max(edit.next_file.unwrap_or(0));
+        self.last_seq = self.last_seq.max(edit.last_seq.unwrap_or(0));
+        self.last_timestamp = self.last_timestamp.max(edit.last_timestamp.unwrap_or(0));
+        self.truncated_seq = self.truncated_seq.max(edit.truncated_seq.unwrap_or(0));
+    }
+
+    /// Append edit and sync it. The file is reopened for every edit, so that appends
+    /// always land in the current manifest even after another handle rewrote it.
+    fn append(&self, edit: &ManifestEdit) -> IoResult<()> {
+        let mut line = serde_json::to_vec(edit).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new().create(true).append(true).open(self.dir.join(MANIFEST_FILE))?;
+        file.write_all(&line)?;
+        file.sync_data()
+    }
+
+    /// Replace the edit log with a single snapshot of the live set.
+    fn rewrite(&self) -> IoResult<()> {
+        let edit = ManifestEdit {
+            version: self.version,
+            snapshot: Some(self.live.iter().cloned().collect()),
+            next_file: Some(self.next_file),
+            last_seq: Some(self.last_seq),
+            last_timestamp: Some(self.last_timestamp),
+            truncated_seq: (self.truncated_seq > 0).then_some(self.truncated_seq),
+            ..ManifestEdit::default()
+        };
+        let mut line = serde_json::to_vec(&edit).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        line.push(b'\n');
+
+        let path = self.dir.join(MANIFEST_FILE);
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&line)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        Durability::Sync.sync_dir(&self.dir)
+    }
+}
+
+/// Parse the edits of a manifest file. A final line cut short by a crash is skipped,
+/// which is reported by the returned flag; any other unreadable line is an error.
+fn read_edits(path: &Path) -> IoResult<(Vec<ManifestEdit>, bool)> {
+    let data = fs::read(path)?;
+    let lines: Vec<&[u8]> = data.split(|b| *b == b'\n').filter(|line| !line.is_empty()).collect();
+    let mut edits = Vec::with_capacity(lines.len());
+    let mut torn = false;
+    for (i, line) in lines.iter().enumerate() {
+        match serde_json::from_slice(line) {
+            Ok(edit) => edits.push(edit),
+            Err(err) if i + 1 == lines.len() && !data.ends_with(b"\n") => {
+                warn!(path = %path.display(), error = %err, "skipping torn manifest edit");
+                torn = true;
+            }
+            Err(err) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Corrupt edit {} in {}: {}", i + 1, path.display(), err),
+                ));
+            }
+        }
+    }
+    // An edit cut short right before its newline would run into the next append
+    torn |= !data.is_empty() && !data.ends_with(b"\n");
+    Ok((edits, torn))
+}
+
+#[derive(Default)]
+struct RefsInner {
+    pins: HashMap<PathBuf, usize>,
+    /// Files to delete once unpinned, with how far to sync their removal
+    obsolete: HashMap<PathBuf, Durability>,
+}
+
+/// Reference counts of the SSTables in use by readers of a column family, so that files
+/// removed from the set are deleted only once the last reader is done with them.
+#[derive(Default)]
+pub struct FileRefs {
+    inner: Mutex<RefsInner>,
+}
+
+impl FileRefs {
+    /// Create a registry with nothing pinned.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep paths from being deleted until the returned guard is dropped.
+    pub fn pin(self: &Arc<Self>, paths: Vec<PathBuf>) -> PinnedFiles {
+        let mut inner = self.inner.lock().unwrap();
+        for path in &paths {
+            *inner.pins.entry(path.clone()).or_insert(0) += 1;
+        }
+        PinnedFiles {
+            refs: self.clone(),
+            paths,
+        }
+    }
+
+    /// Delete the file at path now if no reader pins it, or else when the last one is done.
+    /// With `Durability::Sync`, the directory is synced after the removal.
+    pub fn delete_when_unused(&self, path: &Path, durability: Durability) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.pins.contains_key(path) {
+            inner.obsolete.insert(path.to_path_buf(), durability);
+        } else {
+            remove_obsolete(path, durability);
+        }
+    }
+
+    /// Number of readers currently pinning path.
+    pub fn pin_count(&self, path: &Path) -> usize {
+        self.inner.lock().unwrap().pins.get(path).copied().unwrap_or(0)
+    }
+}
+
+fn remove_obsolete(path: &Path, durability: Durability) {
+    if let Err(err) = fs::remove_file(path) {
+        if err.kind() != ErrorKind::NotFound {
+            warn!(path = %path.display(), error = %err, "failed to remove obsolete SSTable");
+        }
+        return;
+    }
+    // The manifest already dropped the file; an unsynced removal only leaves an orphan
+    if let Err(err) = durability.sync_parent(path) {
+        warn!(path = %path.display(), error = %err, "failed to sync removal of obsolete SSTable");
+    }
+}
+
+/// SSTables pinned by a reader; obsolete ones are deleted when the last guard is dropped.
+pub struct PinnedFiles {
+    refs: Arc<FileRefs>,
+    paths: Vec<PathBuf>,
+}
+
+impl Deref for PinnedFiles {
+    type Target = [PathBuf];
+
+    fn deref(&self) -> &[PathBuf] {
+        &self.paths
+    }
+}
+
+impl Drop for PinnedFiles {
+    fn drop(&mut self) {
+        let mut inner = self.refs.inner.lock().unwrap();
+        for path in &self.paths {
+            let Some(count) = inner.pins.get_mut(path) else {
+                continue;
+            };
+            *count -= 1;
+            if *count == 0 {
+                inner.pins.remove(path);
+                if let Some(durability) = inner.obsolete.remove(path) {
+                    remove_obsolete(path, durability);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_manifest_edits_and_recovery() {
+        let dir = tempdir().unwrap();
+        let sst = |n: u32| dir.path().join(format!("{:010}.sst", n));
+        for n in 1..=3 {
+            fs::write(sst(n), b"").unwrap();
+        }
+
+        // Adopted from the directory listing
+        let mut manifest = Manifest::open(dir.path()).unwrap();
+        assert_eq!(manifest.live_files(), vec![sst(1), sst(2), sst(3)]);
+
+        fs::write(sst(4), b"").unwrap();
+        manifest.record(&[sst(4)], &[sst(1), sst(2)]).unwrap();
+        assert_eq!(manifest.version(), 1);
+
+        // An SSTable never recorded is not part of the set, and a torn edit is skipped
+        fs::write(sst(5), b"").unwrap();
+        let mut file = OpenOptions::new().append(true).open(dir.path().join(MANIFEST_FILE)).unwrap();
+        file.write_all(br#"{"version":2,"add":["00000"#).unwrap();
+        let mut reopened = Manifest::open(dir.path()).unwrap();
+        assert_eq!(reopened.live_files(), vec![sst(3), sst(4)]);
+        assert_eq!(reopened.version(), 1);
+
+        reopened.replace(&[sst(4), sst(5)]).unwrap();
+        assert_eq!(Manifest::read_live_files(dir.path()).unwrap(), Some(vec![sst(4), sst(5)]));
+
+        // A listed SSTable that went missing is an error
+        fs::remove_file(sst(5)).unwrap();
+        assert_eq!(Manifest::open(dir.path()).err().unwrap().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_allocated_numbers_persist() {
+        let dir = tempdir().unwrap();
+        let mut manifest = Manifest::open(dir.path()).unwrap();
+        assert_eq!(manifest.allocate(2), 1);
+
+        // Numbers whose file exists are skipped
+        fs::write(manifest.file_path(4), b"").unwrap();
+        assert_eq!(manifest.allocate(2), 5);
+        assert!(manifest.extend_allocation(7, 1));
+        assert!(!manifest.extend_allocation(7, 1));
+
+        let newest = manifest.allocate_file();
+        assert_eq!(newest, dir.path().join("0000000008.sst"));
+        fs::write(&newest, b"").unwrap();
+        manifest.advance_seq(42);
+        manifest.advance_seq(7);
+        manifest.advance_timestamp(1_000);
+        manifest.record(std::slice::from_ref(&newest), &[]).unwrap();
+        manifest.record(&[], std::slice::from_ref(&newest)).unwrap();
+        fs::remove_file(&newest).unwrap();
+        fs::remove_file(manifest.file_path(4)).unwrap();
+
+        // Nothing is live or on disk anymore, but the recorded counter is kept
+        let mut reopened = Manifest::open(dir.path()).unwrap();
+        assert!(reopened.live_files().is_empty());
+        assert_eq!(reopened.allocate(1), 9);
+        assert_eq!(reopened.last_seq(), 42);
+        assert_eq!(reopened.last_timestamp(), 1_000);
+        assert_eq!(reopened.truncated_seq(), 0);
+
+        // A truncation empties the set and is remembered across a rewrite
+        fs::write(reopened.file_path(9), b"").unwrap();
+        reopened.record(&[reopened.file_path(9)], &[]).unwrap();
+        reopened.truncate(50).unwrap();
+        assert!(reopened.live_files().is_empty());
+        reopened.rewrite().unwrap();
+        let reopened = Manifest::open(dir.path()).unwrap();
+        assert!(reopened.live_files().is_empty());
+        assert_eq!((reopened.truncated_seq(), reopened.last_seq()), (50, 50));
+    }
+
+    #[test]
+    fn test_file_refs_defer_deletion() {
+        let dir = tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.sst"), dir.path().join("b.sst"));
+        fs::write(&a, b"").unwrap();
+        fs::write(&b, b"").unwrap();
+
+        let refs = Arc::new(FileRefs::new());
+        let pinned = refs.pin(vec![a.clone()]);
+        let pinned_again = refs.pin(vec![a.clone()]);
+        assert_eq!(refs.pin_count(&a), 2);
+
+        refs.delete_when_unused(&a, Durability::Sync);
+        refs.delete_when_unused(&b, Durability::Buffered);
+        assert!(a.exists() && !b.exists());
+
+        drop(pinned);
+        assert!(a.exists());
+        drop(pinned_again);
+        assert!(!a.exists());
+        assert_eq!(refs.pin_count(&a), 0);
+    }
+}
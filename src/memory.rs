@@ -0,0 +1,168 @@
+//! Memory budget shared by the MemStores and block caches of a table.
+//!
+//! Each column family bounds its own MemStore (`FlushPolicy`) and block cache, but with
+//! many column families and regions those bounds add up to more than a process can
+//! afford. A `MemoryBudget` caps them together: the table's background thread checks
+//! the budget every 100 ms and, while it is exceeded, flushes MemStores and evicts
+//! cached blocks in the order its `MemoryPolicy` says.
+//!
+//! Cached blocks can be dropped at once, but a MemStore only shrinks once its flush is
+//! done. So that writes cannot outrun the flushes, writes to the table fail with
+//! `ResourceBusy` while its MemStores alone take more than the whole budget: every
+//! write adds its size to the MemStore total the check last measured, until the next
+//! check measures it again.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use serde::Serialize;
+use tracing::{error, info, warn};
+
+use crate::api::{ColumnFamily, WriteStall};
+
+/// Cap on the memory taken by the MemStores and block caches of a table together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryBudget {
+    /// Bytes the MemStores and block caches may take together
+    pub limit_bytes: u64,
+    /// What gives way first while the budget is exceeded
+    pub policy: MemoryPolicy,
+}
+
+/// What gives way first while a table is over its memory budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MemoryPolicy {
+    /// Evict cached blocks first, and flush MemStores only once the caches are empty;
+    /// writes are buffered longer at the cost of colder reads
+    #[default]
+    EvictFirst,
+    /// Flush MemStores first, largest first, and evict cached blocks only if that is not
+    /// enough; reads stay warm at the cost of more, smaller flushes
+    FlushFirst,
+}
+
+/// Memory taken by the MemStores and block caches of a table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct MemoryUsage {
+    pub memstore_bytes: u64,
+    pub block_cache_bytes: u64,
+    /// The budget's limit, if the table has one
+    pub limit_bytes: Option<u64>,
+}
+
+impl MemoryUsage {
+    /// Usage of the column families cfs.
+    pub(crate) fn of(cfs: &[ColumnFamily], limit_bytes: Option<u64>) -> Self {
+        Self {
+            memstore_bytes: cfs.iter().map(ColumnFamily::memstore_bytes).sum(),
+            block_cache_bytes: cfs.iter().map(|cf| cf.block_cache_stats().used_bytes as u64).sum(),
+            limit_bytes,
+        }
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.memstore_bytes + self.block_cache_bytes
+    }
+}
+
+/// Shared by a table and its column families: the MemStore total of the table against
+/// its memory budget, which writes are rejected past.
+#[derive(Debug, Default)]
+pub(crate) struct MemoryGate {
+    /// MemStore bytes at the last check, plus those written since
+    used: AtomicU64,
+    /// Limit of the budget, 0 without one
+    limit: AtomicU64,
+    /// Whether the last check found the MemStores over the limit, for logging changes
+    closed: AtomicBool,
+}
+
+impl MemoryGate {
+    fn update(&self, memstore_bytes: u64, limit: Option<u64>) {
+        self.used.store(memstore_bytes, Ordering::Relaxed);
+        self.limit.store(limit.unwrap_or(0), Ordering::Relaxed);
+        let closed = self.stall().is_some();
+        if self.closed.swap(closed, Ordering::Relaxed) != closed {
+            match closed {
+                true => warn!(memstore_bytes, limit, "MemStores exceed the memory budget; rejecting writes"),
+                false => info!(memstore_bytes, limit, "MemStores fit the memory budget again"),
+            }
+        }
+    }
+
+    /// Count bytes just written to a MemStore.
+    pub(crate) fn record_write(&self, bytes: u64) {
+        self.used.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Why writes are rejected, if they are.
+    pub(crate) fn stall(&self) -> Option<WriteStall> {
+        let (used, limit) = (self.used.load(Ordering::Relaxed), self.limit.load(Ordering::Relaxed));
+        (limit > 0 && used > limit).then_some(WriteStall::MemoryBudget { used, limit })
+    }
+}
+
+/// Flush the column families holding the most unflushed MemStore bytes until all of
+/// them together take at most limit.
+pub(crate) fn flush_to_limit(cfs: &[ColumnFamily], limit: u64) {
+    let mut total: u64 = cfs.iter().map(ColumnFamily::memstore_bytes).sum();
+    let mut flushed = vec![false; cfs.len()];
+    while total > limit {
+        let Some((i, bytes)) = cfs.iter()
+            .enumerate()
+            .filter(|(i, _)| !flushed[*i])
+            .map(|(i, cf)| (i, cf.unflushed_bytes()))
+            .max_by_key(|(_, bytes)| *bytes)
+            .filter(|(_, bytes)| *bytes > 0)
+        else {
+            return;
+        };
+        let cf = &cfs[i];
+        info!(cf = %cf.name(), bytes, total, limit, "flushing to stay under the memory limit");
+        if let Err(err) = cf.flush() {
+            error!(cf = %cf.name(), error = %err, "flush for the memory limit failed");
+        }
+        flushed[i] = true;
+        total = cfs.iter().map(ColumnFamily::memstore_bytes).sum();
+    }
+}
+
+/// Evict the least recently used blocks of every cache until they take at most limit
+/// together, taking from each in proportion to its size.
+fn evict_to_limit(cfs: &[ColumnFamily], limit: u64) {
+    let used: Vec<u64> = cfs.iter().map(|cf| cf.block_cache_stats().used_bytes as u64).collect();
+    let total: u64 = used.iter().sum();
+    if total <= limit {
+        return;
+    }
+    info!(block_cache_bytes = total, limit, "evicting cached blocks to stay under the memory budget");
+    for (cf, used) in cfs.iter().zip(used) {
+        let keep = (used as u128 * limit as u128 / total as u128) as usize;
+        cf.shrink_block_cache(keep);
+    }
+}
+
+/// Bring the column families of a table back under budget, or record in gate that they
+/// are still over it; with no budget, open the gate.
+pub(crate) fn enforce(cfs: &[ColumnFamily], budget: Option<&MemoryBudget>, gate: &MemoryGate) {
+    let Some(budget) = budget else {
+        gate.update(0, None);
+        return;
+    };
+    let limit = budget.limit_bytes;
+    let usage = MemoryUsage::of(cfs, Some(limit));
+    if usage.total_bytes() > limit {
+        match budget.policy {
+            MemoryPolicy::EvictFirst => {
+                evict_to_limit(cfs, limit.saturating_sub(usage.memstore_bytes));
+                let block_cache_bytes = MemoryUsage::of(cfs, Some(limit)).block_cache_bytes;
+                flush_to_limit(cfs, limit.saturating_sub(block_cache_bytes));
+            }
+            MemoryPolicy::FlushFirst => {
+                flush_to_limit(cfs, limit.saturating_sub(usage.block_cache_bytes));
+                let memstore_bytes = MemoryUsage::of(cfs, Some(limit)).memstore_bytes;
+                evict_to_limit(cfs, limit.saturating_sub(memstore_bytes));
+            }
+        }
+    }
+    gate.update(cfs.iter().map(ColumnFamily::memstore_bytes).sum(), Some(limit));
+}
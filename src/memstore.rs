@@ -1,56 +1,568 @@
 use bincode;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::BTreeMap,
-    fs::{File, OpenOptions},
-    io::{BufReader, Read, Result as IoResult, Seek, SeekFrom, Write},
-    path::Path,
+    collections::{BTreeMap, HashSet, VecDeque},
+    ffi::OsStr,
+    ops::Bound,
+    io::{Error, ErrorKind, Result as IoResult, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
 };
-use crate::api::{CellValue, Entry, EntryKey, Timestamp};
+use tracing::{debug, instrument, warn};
+use crate::api::{CellValue, Entry, EntryKey, Seq, Timestamp};
+use crate::storage::trailing_seq;
+use crate::backend::{LocalFs, StorageBackend, WriteFile};
 
-/// A single WAL record: binary‐encoded Entry.
+/// A single WAL record: binary‐encoded Entry, followed by the sequence number of its
+/// key (8 big-endian bytes; absent in records written before sequence numbers).
 #[derive(Serialize, Deserialize, Debug)]
 pub struct WalEntry(Entry);
 
+/// Request IDs of writes logged to the WAL, binary-encoded in a record of their own.
+/// Its length prefix has `REQUEST_IDS_RECORD` set to tell it from entry records.
+#[derive(Serialize, Deserialize, Debug)]
+struct WalRequestIds(Vec<String>);
+
+/// Flag of the length prefix of `WalRequestIds` records.
+const REQUEST_IDS_RECORD: u32 = 1 << 31;
+
+/// Default size at which the active WAL is sealed as a segment; see `MemStore`.
+pub const DEFAULT_WAL_SEGMENT_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Request IDs the MemStore remembers, so that a write retried with the same ID is
+/// applied only once.
+pub const REQUEST_WINDOW: usize = 10_000;
+
+/// The request IDs of the last `REQUEST_WINDOW` writes that had one, oldest first.
+#[derive(Debug, Default)]
+struct RequestWindow {
+    order: VecDeque<String>,
+    ids: HashSet<String>,
+}
+
+impl RequestWindow {
+    fn contains(&self, id: &str) -> bool {
+        self.ids.contains(id)
+    }
+
+    fn insert(&mut self, id: String) {
+        if !self.ids.insert(id.clone()) {
+            return;
+        }
+        self.order.push_back(id);
+        if self.order.len() > REQUEST_WINDOW {
+            let oldest = self.order.pop_front().unwrap();
+            self.ids.remove(&oldest);
+        }
+    }
+}
+
+/// What replaying the WAL found when a MemStore was opened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ReplayStats {
+    /// WAL files replayed: the sealed segments, then the active WAL
+    pub files: usize,
+    /// Entry records replayed
+    pub records: u64,
+    /// Bytes at the end of WAL files short of a whole record, left by writes cut short
+    /// and ignored: too few for a length prefix, or, in the active WAL, a record whose
+    /// length runs past the end, which is cut off before anything is appended
+    pub torn_bytes: u64,
+    /// Times the replayed entries were flushed to keep memory bounded (see
+    /// `MemStore::open_bounded`)
+    pub flushes: usize,
+}
+
+/// Whether the entry numbered seq was discarded by a truncation that discarded the
+/// entries numbered up to truncated_seq. With truncated_seq 0 nothing was truncated, and
+/// entries logged before sequence numbers existed, numbered 0, are kept.
+pub(crate) fn truncated(seq: Seq, truncated_seq: Seq) -> bool {
+    truncated_seq > 0 && seq <= truncated_seq
+}
+
 /// MemStore holds an in‐memory BTreeMap<EntryKey, CellValue> plus an append‐only WAL file.
+///
+/// The WAL is a series of segments: writes go to the active one at the WAL's path, which
+/// is sealed as the next numbered segment beside it (`wal.log.0000000001`, ...) once it
+/// holds `wal_segment_bytes`, and a fresh one is started. Open replays the sealed
+/// segments in order, then the active one, reading one file at a time.
+///
+/// A flush freezes the entries it writes out: the active WAL is sealed and writes go on
+/// into a fresh one while the SSTable is built. The frozen entries stay readable from
+/// the map until `release_frozen` drops them, once the SSTable is live, and deletes the
+/// segments sealed until the freeze; entries written since, and their segments, are
+/// kept.
+///
+/// Every entry appended gets the next sequence number, which orders versions of a cell
+/// written in the same millisecond: the later write is the newer version.
 pub struct MemStore {
     map: BTreeMap<EntryKey, CellValue>,
-    wal: File,
-    wal_path: String,
+    backend: Arc<dyn StorageBackend>,
+    wal: Box<dyn WriteFile>,
+    /// Kept as a path rather than a string, so that paths that are not valid UTF-8 work
+    wal_path: PathBuf,
+    /// Sealed segments of the WAL, oldest first
+    segments: Vec<PathBuf>,
+    /// How many of the oldest segments hold the frozen entries
+    frozen_segments: usize,
+    /// Number of the next segment sealed
+    next_segment: u64,
+    /// Bytes written to the active WAL, and the size at which it is sealed
+    wal_bytes: u64,
+    segment_bytes: u64,
+    /// Bytes appended by writes since the MemStore was opened, across segments
+    wal_bytes_written: u64,
+    /// Entries being flushed, from `freeze` until `release_frozen` or `thaw`
+    frozen: Option<Arc<Vec<Entry>>>,
+    /// Approximate bytes taken by the entries in map, and by the frozen ones among them
+    bytes: u64,
+    frozen_bytes: u64,
+    /// Sequence number of the next entry appended
+    next_seq: Seq,
+    /// Request IDs of the latest writes, logged with them and carried over to every
+    /// fresh WAL
+    requests: RequestWindow,
+    /// What the replay on open found
+    replay: ReplayStats,
+}
+
+/// Approximate memory taken by a MemStore entry.
+pub(crate) fn entry_bytes(key: &EntryKey, cell: &CellValue) -> u64 {
+    let value_len = match cell {
+        CellValue::Put(value) => value.len(),
+        CellValue::Delete(_) | CellValue::DeleteFamily => 0,
+    };
+    (key.row.len() + key.column.len() + 8 + value_len) as u64
+}
+
+/// The immutable WAL beside wal_path, where versions before WAL segments moved the
+/// entries of a flush until its SSTable was live. One left behind is replayed as the
+/// oldest segment.
+pub(crate) fn immutable_wal_path(wal_path: &Path) -> PathBuf {
+    let mut name = wal_path.as_os_str().to_owned();
+    name.push(".immutable");
+    PathBuf::from(name)
+}
+
+/// The sealed segment of the WAL at wal_path numbered number.
+fn segment_path(wal_path: &Path, number: u64) -> PathBuf {
+    let mut name = wal_path.as_os_str().to_owned();
+    name.push(format!(".{:010}", number));
+    PathBuf::from(name)
+}
+
+/// The number of the sealed segment of the WAL at wal_path named name, if it is one.
+fn segment_number(wal_path: &Path, name: &OsStr) -> Option<u64> {
+    let number = name.as_encoded_bytes()
+        .strip_prefix(wal_path.file_name()?.as_encoded_bytes())?
+        .strip_prefix(b".")?;
+    if number.len() != 10 || !number.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    std::str::from_utf8(number).ok()?.parse().ok()
+}
+
+/// The sealed segments of the WAL at wal_path in backend, oldest first: an immutable
+/// WAL left behind (see `immutable_wal_path`), then the numbered segments.
+pub(crate) fn wal_segments(backend: &dyn StorageBackend, wal_path: &Path) -> IoResult<Vec<PathBuf>> {
+    let dir = match wal_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut numbers: Vec<u64> = match backend.list(dir) {
+        Ok(paths) => paths.iter().filter_map(|path| segment_number(wal_path, path.file_name()?)).collect(),
+        Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    numbers.sort_unstable();
+    let immutable = immutable_wal_path(wal_path);
+    let immutable = backend.exists(&immutable).then_some(immutable);
+    Ok(immutable.into_iter().chain(numbers.into_iter().map(|number| segment_path(wal_path, number))).collect())
+}
+
+/// Where a cleared WAL beside wal_path is moved until it is deleted; one left behind by
+/// a clear cut short is deleted on open.
+fn discarded_wal_path(wal_path: &Path) -> PathBuf {
+    let mut name = wal_path.as_os_str().to_owned();
+    name.push(".discarded");
+    PathBuf::from(name)
+}
+
+/// Where the active WAL at wal_path is rewritten without a torn record at its end
+/// before it replaces it; one left behind by an open cut short is deleted on open.
+fn repaired_wal_path(wal_path: &Path) -> PathBuf {
+    let mut name = wal_path.as_os_str().to_owned();
+    name.push(".repaired");
+    PathBuf::from(name)
+}
+
+/// Stands in for the WAL while it is rotated, its file closed: writes fail until a
+/// fresh WAL is opened.
+struct ClosedWal;
+
+impl ClosedWal {
+    fn error() -> Error {
+        Error::other("The WAL is closed for rotation")
+    }
+}
+
+impl Write for ClosedWal {
+    fn write(&mut self, _buf: &[u8]) -> IoResult<usize> {
+        Err(Self::error())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        Ok(())
+    }
+}
+
+impl Seek for ClosedWal {
+    fn seek(&mut self, _pos: SeekFrom) -> IoResult<u64> {
+        Err(Self::error())
+    }
+}
+
+impl WriteFile for ClosedWal {
+    fn sync(&mut self) -> IoResult<()> {
+        Err(Self::error())
+    }
+}
+
+/// Error for the WAL record at position of the WAL at path, which cannot be decoded.
+fn corrupt_record(path: &str, position: u64, reason: impl std::fmt::Display) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Corrupt WAL record at {} in {}: {}", position, path, reason),
+    )
+}
+
+/// Decode the entry records of data, the content of the WAL at path, in order.
+/// Fails with `InvalidData` if a record cannot be decoded.
+pub(crate) fn decode_wal(path: &str, data: &[u8]) -> IoResult<Vec<Entry>> {
+    decode_wal_records(path, data).map(|(entries, _)| entries)
+}
+
+/// Decode the records of data, the content of the WAL at path: the entries and the
+/// request IDs, each in order. Fails with `InvalidData` if a record cannot be decoded.
+fn decode_wal_records(path: &str, data: &[u8]) -> IoResult<(Vec<Entry>, Vec<String>)> {
+    let mut entries = Vec::new();
+    let mut request_ids = Vec::new();
+    for (position, prefix, buf) in split_wal_records(path, data, false)? {
+        match decode_record(path, position, prefix, buf)? {
+            WalRecord::Entry(entry) => entries.push(entry),
+            WalRecord::RequestIds(ids) => request_ids.extend(ids),
+        }
+    }
+    Ok((entries, request_ids))
+}
+
+/// A decoded WAL record.
+enum WalRecord {
+    Entry(Entry),
+    RequestIds(Vec<String>),
+}
+
+/// Decode buf, the content of the record at position of the WAL at path, with length
+/// prefix prefix.
+fn decode_record(path: &str, position: u64, prefix: u32, buf: &[u8]) -> IoResult<WalRecord> {
+    if prefix & REQUEST_IDS_RECORD != 0 {
+        let WalRequestIds(ids) = bincode::deserialize(buf).map_err(|e| corrupt_record(path, position, e))?;
+        return Ok(WalRecord::RequestIds(ids));
+    }
+    let WalEntry(mut entry) = bincode::deserialize(buf).map_err(|e| corrupt_record(path, position, e))?;
+    entry.key.seq = bincode::serialized_size(&entry)
+        .and_then(|used| trailing_seq(buf, used))
+        .map_err(|e| corrupt_record(path, position, e))?;
+    Ok(WalRecord::Entry(entry))
+}
+
+/// The records of data, the content of the WAL at path: the position, length prefix
+/// and content of each. Bytes too few for a length prefix at the end, left by a write
+/// cut short, are ignored, and so is a record whose length runs past the end if path is
+/// the active WAL: only its last write can have been cut short.
+fn split_wal_records<'a>(path: &str, data: &'a [u8], active: bool) -> IoResult<Vec<(u64, u32, &'a [u8])>> {
+    let wal_len = data.len() as u64;
+    let mut records = Vec::new();
+    let mut position = 0u64;
+    while position + 4 <= wal_len {
+        let start = position as usize;
+        let prefix = u32::from_be_bytes(data[start..start + 4].try_into().unwrap());
+        let len = (prefix & !REQUEST_IDS_RECORD) as u64;
+        if position + 4 + len > wal_len {
+            if active {
+                break;
+            }
+            return Err(corrupt_record(path, position, "length past the end of the file"));
+        }
+        records.push((position, prefix, &data[start + 4..start + 4 + len as usize]));
+        position += 4 + len;
+    }
+    Ok(records)
+}
+
+/// Bytes at the end of data, the content of a WAL, past its records.
+fn torn_bytes(data: &[u8], records: &[(u64, u32, &[u8])]) -> u64 {
+    records.last().map_or(data.len() as u64, |(position, _, buf)| data.len() as u64 - position - 4 - buf.len() as u64)
+}
+
+/// The content of the active WAL at wal_path in backend and the number of bytes torn
+/// off its end: those are cut off, by writing the rest to repaired and renaming it over
+/// the WAL.
+fn cut_torn_record(backend: &dyn StorageBackend, wal_path: &Path, repaired: &Path) -> IoResult<(Vec<u8>, u64)> {
+    let mut data = backend.read(wal_path)?;
+    let torn = torn_bytes(&data, &split_wal_records(&wal_path.to_string_lossy(), &data, true)?);
+    if torn > 0 {
+        warn!(wal = %wal_path.display(), torn, "cutting off a torn WAL record");
+        data.truncate(data.len() - torn as usize);
+        let mut file = backend.create(repaired)?;
+        file.write_all(&data)?;
+        file.sync()?;
+        drop(file);
+        backend.rename(repaired, wal_path)?;
+    }
+    Ok((data, torn))
+}
+
+/// Decode every record of the WAL at wal_path in backend, its sealed segments first,
+/// without opening it: what a replay would find, but for flushes, and how many of the
+/// entries it would drop as truncated up to truncated_seq. Fails with `InvalidData` if
+/// a record cannot be decoded.
+pub(crate) fn inspect_wal(backend: &dyn StorageBackend, wal_path: &Path, truncated_seq: Seq) -> IoResult<(ReplayStats, u64)> {
+    let mut files: Vec<(PathBuf, bool)> = wal_segments(backend, wal_path)?.into_iter().map(|path| (path, false)).collect();
+    if backend.exists(wal_path) {
+        files.push((wal_path.to_path_buf(), true));
+    }
+    let mut stats = ReplayStats::default();
+    let mut skipped = 0;
+    for (file, active) in files {
+        let data = backend.read(&file)?;
+        let path = file.to_string_lossy();
+        let records = split_wal_records(&path, &data, active)?;
+        stats.files += 1;
+        stats.torn_bytes += torn_bytes(&data, &records);
+        for (position, prefix, buf) in records {
+            if let WalRecord::Entry(entry) = decode_record(&path, position, prefix, buf)? {
+                stats.records += 1;
+                skipped += truncated(entry.key.seq, truncated_seq) as u64;
+            }
+        }
+    }
+    Ok((stats, skipped))
+}
+
+/// Rewrite data, the content of the WAL at path, in the current record format: entry
+/// records written before sequence numbers get one, the 0 they are replayed with.
+/// `None` if every record is current already.
+pub(crate) fn upgrade_wal(path: &str, data: &[u8]) -> IoResult<Option<Vec<u8>>> {
+    let mut upgraded = Vec::with_capacity(data.len());
+    let mut changed = false;
+    for (position, prefix, buf) in split_wal_records(path, data, false)? {
+        let legacy = prefix & REQUEST_IDS_RECORD == 0 && {
+            let WalEntry(entry) = bincode::deserialize(buf).map_err(|e| corrupt_record(path, position, e))?;
+            bincode::serialized_size(&entry).map_err(|e| corrupt_record(path, position, e))? == buf.len() as u64
+        };
+        if legacy {
+            upgraded.extend_from_slice(&(buf.len() as u32 + 8).to_be_bytes());
+            upgraded.extend_from_slice(buf);
+            upgraded.extend_from_slice(&0u64.to_be_bytes());
+            changed = true;
+        } else {
+            upgraded.extend_from_slice(&prefix.to_be_bytes());
+            upgraded.extend_from_slice(buf);
+        }
+    }
+    Ok(changed.then_some(upgraded))
+}
+
+/// A `WalRequestIds` record of ids, length prefix included.
+fn request_ids_record(ids: Vec<String>) -> IoResult<Vec<u8>> {
+    let buf = bincode::serialize(&WalRequestIds(ids)).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    let mut record = ((buf.len() as u32) | REQUEST_IDS_RECORD).to_be_bytes().to_vec();
+    record.extend_from_slice(&buf);
+    Ok(record)
 }
 
 impl MemStore {
-    /// Open (or create) a WAL at wal_path and replay it to rebuild map.
+    /// Open (or create) a WAL at wal_path and replay it, its sealed segments first, to
+    /// rebuild map. Fails with `InvalidData` if a record cannot be decoded.
     pub fn open(wal_path: impl AsRef<Path>) -> IoResult<Self> {
-        let path_str = wal_path.as_ref().to_string_lossy().into_owned();
-        let wal = OpenOptions::new()
-            .create(true)
-            .read(true)
-            .append(true)
-            .open(&wal_path)?;
+        Self::open_in(Arc::new(LocalFs), wal_path)
+    }
+
+    /// Open (or create) a WAL at wal_path of backend and replay it, its sealed segments
+    /// first, to rebuild map. Fails with `InvalidData` if a record cannot be decoded.
+    pub fn open_in(backend: Arc<dyn StorageBackend>, wal_path: impl AsRef<Path>) -> IoResult<Self> {
+        Self::open_bounded(backend, wal_path, u64::MAX, |_| Ok(()))
+    }
+
+    /// Like `open_in`, keeping the memory taken by the replayed entries bounded: once
+    /// they take more than max_bytes, they are handed to flush, sorted by key, which
+    /// must write them out before it returns, and dropped. The sealed segments replayed
+    /// completely by then are deleted, as a flush would. Once that happened, the entries
+    /// left at the end are handed to flush as well, the remaining segments deleted and
+    /// the active WAL started afresh. A WAL left by a crash can then be larger than
+    /// memory.
+    #[instrument(level = "debug", skip_all, fields(wal = %wal_path.as_ref().display(), max_bytes))]
+    pub fn open_bounded(
+        backend: Arc<dyn StorageBackend>,
+        wal_path: impl AsRef<Path>,
+        max_bytes: u64,
+        mut flush: impl FnMut(Vec<Entry>) -> IoResult<()>,
+    ) -> IoResult<Self> {
+        let wal_path = wal_path.as_ref();
+        let path_str = wal_path.to_string_lossy().into_owned();
+        let discarded = discarded_wal_path(wal_path);
+        if backend.exists(&discarded) {
+            backend.remove(&discarded)?;
+        }
+        let repaired = repaired_wal_path(wal_path);
+        if backend.exists(&repaired) {
+            backend.remove(&repaired)?;
+        }
+        // The active WAL is read before it is opened for appending, so that a record torn
+        // at its end is cut off rather than followed by new ones
+        let active = match backend.exists(wal_path) {
+            true => Some(cut_torn_record(backend.as_ref(), wal_path, &repaired)?),
+            false => None,
+        };
+        let segments = wal_segments(backend.as_ref(), wal_path)?;
+        let next_segment = segments.iter()
+            .filter_map(|path| segment_number(wal_path, path.file_name()?))
+            .max()
+            .unwrap_or(0) + 1;
+        let wal = backend.append(wal_path)?;
         let mut store = MemStore {
             map: BTreeMap::new(),
+            backend,
             wal,
-            wal_path: path_str.clone(),
+            wal_path: wal_path.to_path_buf(),
+            segments: Vec::new(),
+            frozen_segments: 0,
+            next_segment,
+            wal_bytes: 0,
+            segment_bytes: DEFAULT_WAL_SEGMENT_BYTES,
+            wal_bytes_written: 0,
+            frozen: None,
+            bytes: 0,
+            frozen_bytes: 0,
+            next_seq: 1,
+            requests: RequestWindow::default(),
+            replay: ReplayStats::default(),
         };
 
-        let mut reader = BufReader::new(store.wal.try_clone()?);
-        loop {
-            let mut len_buf = [0u8; 4];
-            if reader.read_exact(&mut len_buf).is_err() {
-                break;
-            }
-            let len = u32::from_be_bytes(len_buf) as usize;
-            let mut buf = vec![0u8; len];
-            reader.read_exact(&mut buf)?;
-            let WalEntry(entry) = bincode::deserialize(&buf).unwrap();
-            store.map.insert(entry.key, entry.value);
+        // Sealed segments hold older entries than the active WAL; one is read at a time
+        for segment in segments {
+            let data = store.backend.read(&segment)?;
+            store.replay(&segment.to_string_lossy(), &data, max_bytes, &mut flush)?;
+            store.segments.push(segment);
+        }
+        if let Some((data, torn)) = active {
+            store.wal_bytes = data.len() as u64;
+            store.replay.torn_bytes += torn;
+            store.replay(&path_str, &data, max_bytes, &mut flush)?;
         }
-        store.wal.seek(SeekFrom::End(0))?;
+        if store.replay.flushes > 0 {
+            // The WAL files left may hold entries written out already: the rest are
+            // written out too and the WAL is started afresh, so that none is replayed twice
+            store.flush_replayed(&mut flush)?;
+            let discarded = discarded_wal_path(wal_path);
+            store.rotate_wal(|backend, wal_path| backend.rename(wal_path, &discarded))?;
+            store.backend.remove(&discarded)?;
+            store.wal_bytes = 0;
+            store.log_request_window()?;
+        }
+        let ReplayStats { files, records, torn_bytes, flushes } = store.replay;
+        debug!(files, records, torn_bytes, flushes, bytes = store.bytes, next_seq = store.next_seq, "replayed WAL");
         Ok(store)
     }
 
-    /// Number of entries in the in-memory map
+    /// What the replay on open found.
+    pub fn replay_stats(&self) -> ReplayStats {
+        self.replay
+    }
+
+    /// Add the entries and request IDs of data, the content of the WAL file at path, to
+    /// map, flushing the entries whenever they take more than max_bytes (see
+    /// `open_bounded`), and count what was found in the replay stats.
+    fn replay(
+        &mut self,
+        path: &str,
+        data: &[u8],
+        max_bytes: u64,
+        flush: &mut dyn FnMut(Vec<Entry>) -> IoResult<()>,
+    ) -> IoResult<()> {
+        let records = split_wal_records(path, data, false)?;
+        self.replay.files += 1;
+        self.replay.torn_bytes += torn_bytes(data, &records);
+        for (position, prefix, buf) in records {
+            let entry = match decode_record(path, position, prefix, buf)? {
+                WalRecord::Entry(entry) => entry,
+                WalRecord::RequestIds(ids) => {
+                    ids.into_iter().for_each(|id| self.requests.insert(id));
+                    continue;
+                }
+            };
+            self.replay.records += 1;
+            self.advance_seq(entry.key.seq);
+            self.bytes += entry_bytes(&entry.key, &entry.value);
+            if let Some(replaced) = self.map.insert(entry.key.clone(), entry.value) {
+                self.bytes -= entry_bytes(&entry.key, &replaced);
+            }
+            if self.bytes > max_bytes {
+                self.flush_replayed(flush)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand every replayed entry to flush and drop them, then delete the segments
+    /// replayed completely, whose entries are now all written out.
+    fn flush_replayed(&mut self, flush: &mut dyn FnMut(Vec<Entry>) -> IoResult<()>) -> IoResult<()> {
+        let entries = std::mem::take(&mut self.map).into_iter().map(|(key, value)| Entry { key, value }).collect();
+        self.bytes = 0;
+        flush(entries)?;
+        self.replay.flushes += 1;
+        if !self.segments.is_empty() {
+            self.remove_segments(self.segments.len(), None)?;
+            self.log_request_window()?;
+        }
+        Ok(())
+    }
+
+    /// Log the remembered request IDs to the active WAL, so that they outlive the
+    /// segments being deleted.
+    fn log_request_window(&mut self) -> IoResult<()> {
+        if self.requests.order.is_empty() {
+            return Ok(());
+        }
+        let record = request_ids_record(self.requests.order.iter().cloned().collect())?;
+        self.wal.write_all(&record)?;
+        self.wal.flush()?;
+        self.wal_bytes += record.len() as u64;
+        Ok(())
+    }
+
+    /// Bytes appended to the WAL by writes since the MemStore was opened.
+    pub fn wal_bytes_written(&self) -> u64 {
+        self.wal_bytes_written
+    }
+
+    /// Size at which the active WAL is sealed as a segment and a fresh one started.
+    pub fn wal_segment_bytes(&self) -> u64 {
+        self.segment_bytes
+    }
+
+    /// Seal the active WAL once it holds bytes, from the next write on.
+    pub fn set_wal_segment_bytes(&mut self, bytes: u64) {
+        self.segment_bytes = bytes;
+    }
+
+    /// Number of sealed WAL segments, those of a running flush included.
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Number of entries in the in-memory map, frozen ones included
     pub fn len(&self) -> usize {
         self.map.len()
     }
@@ -59,15 +571,151 @@ impl MemStore {
         self.map.is_empty()
     }
 
+    /// Number of entries written since the running flush froze the others
+    pub fn active_len(&self) -> usize {
+        self.map.len() - self.frozen.as_ref().map_or(0, |frozen| frozen.len())
+    }
+
+    /// Approximate memory taken by the entries, frozen ones included
+    pub fn size_bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Approximate memory taken by the entries written since the running flush froze
+    /// the others
+    pub fn active_bytes(&self) -> u64 {
+        self.bytes.saturating_sub(self.frozen_bytes)
+    }
+
+    /// Freeze every entry for a flush, sorted by key, and seal the active WAL, so that
+    /// the writes made meanwhile go to a fresh one. The entries of a flush that failed
+    /// are frozen again, with those written since.
+    #[instrument(level = "debug", skip(self), fields(wal = %self.wal_path.display(), entries = self.map.len()))]
+    pub fn freeze(&mut self) -> IoResult<Arc<Vec<Entry>>> {
+        if self.frozen.is_some() {
+            return Err(Error::new(ErrorKind::WouldBlock, "A flush of the MemStore is already running"));
+        }
+        if self.wal_bytes > 0 {
+            self.seal_segment()?;
+        }
+        self.frozen_segments = self.segments.len();
+        // The sealed segments are deleted by the flush
+        self.log_request_window()?;
+
+        let frozen = Arc::new(self.entries());
+        self.frozen = Some(frozen.clone());
+        self.frozen_bytes = self.bytes;
+        Ok(frozen)
+    }
+
+    /// Whether frozen is what the running flush froze, i.e. the MemStore was not cleared
+    /// since.
+    pub fn is_frozen(&self, frozen: &Arc<Vec<Entry>>) -> bool {
+        self.frozen.as_ref().is_some_and(|current| Arc::ptr_eq(current, frozen))
+    }
+
+    /// Give up the running flush; its entries are frozen again by the next one.
+    pub fn thaw(&mut self) {
+        self.frozen = None;
+        self.frozen_segments = 0;
+        self.frozen_bytes = 0;
+    }
+
+    /// Drop the frozen entries, now in an SSTable, except those overwritten since, and
+    /// delete the segments holding them, or move them, concatenated, to archive_path if
+    /// set.
+    #[instrument(level = "debug", skip(self), fields(wal = %self.wal_path.display()))]
+    pub fn release_frozen(&mut self, archive_path: Option<&Path>) -> IoResult<()> {
+        let Some(frozen) = self.frozen.take() else {
+            return Ok(());
+        };
+        let released = std::mem::take(&mut self.frozen_segments);
+        self.remove_segments(released, archive_path)?;
+        for entry in frozen.iter() {
+            if self.map.get(&entry.key) == Some(&entry.value) {
+                self.map.remove(&entry.key);
+                self.bytes -= entry_bytes(&entry.key, &entry.value);
+            }
+        }
+        self.frozen_bytes = 0;
+        Ok(())
+    }
+
+    /// Drop the entries numbered up to seq, e.g. replayed writes that a truncation
+    /// discarded before a crash left them in the WAL. Their records stay in the WAL
+    /// until it is next released or cleared. With seq 0 nothing was truncated, and
+    /// entries logged before sequence numbers existed, numbered 0, are kept. Returns the
+    /// number of entries dropped.
+    pub fn discard_through(&mut self, seq: Seq) -> usize {
+        let before = self.map.len();
+        self.map.retain(|key, _| !truncated(key.seq, seq));
+        self.bytes = self.map.iter().map(|(key, cell)| entry_bytes(key, cell)).sum();
+        before - self.map.len()
+    }
+
+    /// Sequence number of the last entry appended (or advanced to), 0 before any.
+    pub fn last_seq(&self) -> Seq {
+        self.next_seq - 1
+    }
+
+    /// Number the entries appended from now on after seq, e.g. the last sequence number
+    /// of entries already flushed.
+    pub fn advance_seq(&mut self, seq: Seq) {
+        self.next_seq = self.next_seq.max(seq + 1);
+    }
+
     /// Append one Entry to both the WAL file (on disk) and map (in memory).
     pub fn append(&mut self, entry: Entry) -> IoResult<()> {
-        let buf = bincode::serialize(&WalEntry(entry.clone())).unwrap();
-        let len = (buf.len() as u32).to_be_bytes();
-        self.wal.write_all(&len)?;
-        self.wal.write_all(&buf)?;
+        self.append_many(vec![entry])
+    }
+
+    /// Append entries to the WAL with a single write, then to map, in order. Each entry
+    /// gets the next sequence number, replacing the one it had.
+    pub fn append_many(&mut self, entries: Vec<Entry>) -> IoResult<()> {
+        self.append_requests(entries, Vec::new())
+    }
+
+    /// Whether a write with request ID id was appended, among the last `REQUEST_WINDOW`
+    /// that had one.
+    pub fn has_request(&self, id: &str) -> bool {
+        self.requests.contains(id)
+    }
+
+    /// Like `append_many`, also logging the request IDs of the writes the entries belong
+    /// to with the same WAL write, for `has_request`.
+    pub fn append_requests(&mut self, mut entries: Vec<Entry>, request_ids: Vec<String>) -> IoResult<()> {
+        let mut records = Vec::new();
+        let mut seq = self.next_seq;
+        for entry in &mut entries {
+            entry.key.seq = seq;
+            seq += 1;
+            let mut buf = bincode::serialize(&WalEntry(entry.clone())).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+            buf.extend_from_slice(&entry.key.seq.to_be_bytes());
+            records.extend_from_slice(&(buf.len() as u32).to_be_bytes());
+            records.extend_from_slice(&buf);
+        }
+        if !request_ids.is_empty() {
+            records.extend(request_ids_record(request_ids.clone())?);
+        }
+        self.wal.write_all(&records)?;
         self.wal.flush()?;
+        self.wal_bytes += records.len() as u64;
+        self.wal_bytes_written += records.len() as u64;
+        self.next_seq = seq;
+        request_ids.into_iter().for_each(|id| self.requests.insert(id));
 
-        self.map.insert(entry.key, entry.value);
+        for entry in entries {
+            self.bytes += entry_bytes(&entry.key, &entry.value);
+            if let Some(replaced) = self.map.insert(entry.key.clone(), entry.value) {
+                self.bytes -= entry_bytes(&entry.key, &replaced);
+            }
+        }
+        if self.wal_bytes >= self.segment_bytes {
+            // The write is logged either way; a seal that failed is retried by the next one
+            if let Err(err) = self.seal_segment() {
+                warn!(wal = %self.wal_path.display(), error = %err, "failed to seal the WAL segment");
+            }
+        }
         Ok(())
     }
 
@@ -77,11 +725,13 @@ impl MemStore {
             row: row.to_vec(),
             column: column.to_vec(),
             timestamp: 0,
+            seq: 0,
         };
         let range_end = EntryKey {
             row: row.to_vec(),
             column: column.to_vec(),
             timestamp: u64::MAX,
+            seq: Seq::MAX,
         };
         self.map
             .range(range_start..=range_end)
@@ -89,68 +739,273 @@ impl MemStore {
             .map(|(_k, v)| v)
     }
 
-    /// *MVCC helper*: return all versions (timestamp + CellValue) for (row, column), sorted descending by timestamp.
-    pub fn get_versions_full(&self, row: &[u8], column: &[u8]) -> Vec<(Timestamp, CellValue)> {
+    /// Return the newest version (timestamp + CellValue) for (row, column).
+    pub fn get_latest(&self, row: &[u8], column: &[u8]) -> Option<(Timestamp, &CellValue)> {
         let range_start = EntryKey {
             row: row.to_vec(),
             column: column.to_vec(),
             timestamp: 0,
+            seq: 0,
         };
         let range_end = EntryKey {
             row: row.to_vec(),
             column: column.to_vec(),
             timestamp: u64::MAX,
+            seq: Seq::MAX,
         };
-        let mut versions: Vec<(Timestamp, CellValue)> = self.map
+        self.map
             .range(range_start..=range_end)
-            .map(|(k, v)| (k.timestamp, v.clone()))
+            .last()
+            .map(|(k, v)| (k.timestamp, v))
+    }
+
+    /// Copy the entries for (row, column), oldest version first.
+    pub fn cell_entries(&self, row: &[u8], column: &[u8]) -> Vec<(EntryKey, CellValue)> {
+        self.cell_entries_bounded(row, column, Timestamp::MAX, usize::MAX)
+    }
+
+    /// Copy the newest limit entries for (row, column) with a timestamp of at most
+    /// max_timestamp, oldest version first. Older versions are not visited.
+    pub fn cell_entries_bounded(
+        &self,
+        row: &[u8],
+        column: &[u8],
+        max_timestamp: Timestamp,
+        limit: usize,
+    ) -> Vec<(EntryKey, CellValue)> {
+        let mut entries: Vec<(EntryKey, CellValue)> = self.cell_range(row, column, max_timestamp)
+            .take(limit)
+            .map(|(k, v)| (k.clone(), v.clone()))
             .collect();
+        entries.reverse();
+        entries
+    }
 
-        versions.sort_by(|a, b| b.0.cmp(&a.0));
-        versions
+    /// *MVCC helper*: return all versions (timestamp + CellValue) for (row, column), sorted descending by timestamp.
+    pub fn get_versions_full(&self, row: &[u8], column: &[u8]) -> Vec<(Timestamp, CellValue)> {
+        self.get_versions_bounded(row, column, Timestamp::MAX, usize::MAX)
     }
 
-    pub fn drain_all(&mut self) -> IoResult<Vec<Entry>> {
-        // Use map to transform the iterator
-        let mut all: Vec<Entry> = self.map.iter()
+    /// Return the newest limit versions for (row, column) with a timestamp of at most
+    /// max_timestamp, sorted descending by timestamp. Older versions are not visited.
+    pub fn get_versions_bounded(
+        &self,
+        row: &[u8],
+        column: &[u8],
+        max_timestamp: Timestamp,
+        limit: usize,
+    ) -> Vec<(Timestamp, CellValue)> {
+        self.cell_range(row, column, max_timestamp)
+            .take(limit)
+            .map(|(k, v)| (k.timestamp, v.clone()))
+            .collect()
+    }
+
+    /// The versions of (row, column) with a timestamp of at most max_timestamp, newest first.
+    fn cell_range(
+        &self,
+        row: &[u8],
+        column: &[u8],
+        max_timestamp: Timestamp,
+    ) -> impl Iterator<Item = (&EntryKey, &CellValue)> {
+        let range_start = EntryKey {
+            row: row.to_vec(),
+            column: column.to_vec(),
+            timestamp: 0,
+            seq: 0,
+        };
+        let range_end = EntryKey {
+            row: row.to_vec(),
+            column: column.to_vec(),
+            timestamp: max_timestamp,
+            seq: Seq::MAX,
+        };
+        self.map.range(range_start..=range_end).rev()
+    }
+
+    /// Copy every entry, sorted by key, without clearing the MemStore or its WAL.
+    pub fn entries(&self) -> Vec<Entry> {
+        self.map.iter()
             .map(|(k, v)| Entry {
                 key: k.clone(),
                 value: v.clone(),
             })
-            .collect();
+            .collect()
+    }
+
+    /// The content of the WAL: the records of every entry appended since it was last
+    /// cleared, those of the sealed segments first.
+    pub fn wal_contents(&self) -> IoResult<Vec<u8>> {
+        let mut contents = Vec::new();
+        for segment in &self.segments {
+            contents.extend(self.backend.read(segment)?);
+        }
+        contents.extend(self.backend.read(&self.wal_path)?);
+        Ok(contents)
+    }
 
-        all.sort_by(|a, b| a.key.cmp(&b.key));
+    /// Clear the in-memory map and start a new, empty WAL, deleting the sealed segments.
+    /// A running flush no longer `is_frozen`, so it must not make its SSTable live.
+    /// Request IDs are forgotten, as the writes are discarded.
+    #[instrument(level = "debug", skip(self), fields(wal = %self.wal_path.display(), entries = self.map.len()))]
+    pub fn clear(&mut self) -> IoResult<()> {
         self.map.clear();
+        self.thaw();
+        self.bytes = 0;
+        self.requests = RequestWindow::default();
+        self.remove_segments(self.segments.len(), None)?;
+        // Renamed aside, and deleted once the fresh WAL is open
+        let discarded = discarded_wal_path(&self.wal_path);
+        self.rotate_wal(|backend, wal_path| backend.rename(wal_path, &discarded))?;
+        self.wal_bytes = 0;
+        self.backend.remove(&discarded)
+    }
 
-        drop(&self.wal);
-        std::fs::remove_file(&self.wal_path)?;
-        self.wal = OpenOptions::new()
-            .create(true)
-            .read(true)
-            .append(true)
-            .open(&self.wal_path)?;
+    /// Clear the in-memory map and start a new, empty WAL, moving the old one, with the
+    /// records of the sealed segments first, to archive_path instead of deleting it.
+    /// Request IDs are forgotten, like by `clear`.
+    #[instrument(level = "debug", skip(self), fields(wal = %self.wal_path.display(), entries = self.map.len()))]
+    pub fn archive_to(&mut self, archive_path: &Path) -> IoResult<()> {
+        self.wal.flush()?;
+        let contents = match self.segments.is_empty() {
+            false => Some(self.wal_contents()?),
+            true => None,
+        };
+        self.rotate_wal(|backend, wal_path| match contents {
+            Some(contents) => {
+                let mut archive = backend.create(archive_path)?;
+                archive.write_all(&contents)?;
+                archive.sync()?;
+                backend.remove(wal_path)
+            }
+            None => backend.rename(wal_path, archive_path),
+        })?;
+        self.wal_bytes = 0;
+        self.remove_segments(self.segments.len(), None)?;
+        self.map.clear();
+        self.thaw();
+        self.bytes = 0;
+        self.requests = RequestWindow::default();
+        Ok(())
+    }
+
+    /// Seal the active WAL as the next numbered segment and start a fresh one.
+    fn seal_segment(&mut self) -> IoResult<()> {
+        let segment = segment_path(&self.wal_path, self.next_segment);
+        let mut sealed = false;
+        let rotated = self.rotate_wal(|backend, wal_path| {
+            backend.rename(wal_path, &segment)?;
+            sealed = true;
+            Ok(())
+        });
+        if sealed {
+            self.segments.push(segment);
+            self.next_segment += 1;
+            self.wal_bytes = 0;
+        }
+        rotated
+    }
+
+    /// Delete the count oldest sealed segments, or move them, concatenated, to
+    /// archive_path if set. A segment leaves the list once it is gone, so that one left
+    /// by a failure is removed by the next call.
+    fn remove_segments(&mut self, count: usize, archive_path: Option<&Path>) -> IoResult<()> {
+        match (archive_path, &self.segments[..count]) {
+            (Some(archive_path), [segment]) => {
+                self.backend.rename(segment, archive_path)?;
+                self.segments.remove(0);
+                return Ok(());
+            }
+            (Some(archive_path), segments) if !segments.is_empty() => {
+                let mut archive = self.backend.create(archive_path)?;
+                for segment in segments {
+                    archive.write_all(&self.backend.read(segment)?)?;
+                }
+                archive.sync()?;
+            }
+            _ => {}
+        }
+        for _ in 0..count {
+            self.backend.remove(&self.segments[0])?;
+            self.segments.remove(0);
+        }
+        Ok(())
+    }
+
+    /// Close the WAL, let rotate move or remove its files, and open a fresh WAL at its
+    /// path. The file is closed first because Windows neither renames nor deletes a file
+    /// that is open, nor creates one where a file is pending deletion. Should rotate
+    /// fail, the WAL left at the path is reopened, and should that fail too, writes fail
+    /// until the MemStore is reopened.
+    fn rotate_wal(&mut self, rotate: impl FnOnce(&dyn StorageBackend, &Path) -> IoResult<()>) -> IoResult<()> {
+        self.wal.flush()?;
+        drop(std::mem::replace(&mut self.wal, Box::new(ClosedWal)));
+        let rotated = rotate(self.backend.as_ref(), &self.wal_path);
+        match self.backend.append(&self.wal_path) {
+            Ok(wal) => self.wal = wal,
+            // The rotation's error, if any, says more than the open's
+            Err(e) => return rotated.and(Err(e)),
+        }
+        rotated
+    }
+
+    /// Take every entry, sorted by key, and clear the MemStore and its WAL.
+    pub fn drain_all(&mut self) -> IoResult<Vec<Entry>> {
+        let all = self.entries();
+        self.clear()?;
         Ok(all)
     }
 
     /// For scanning: return all (EntryKey, CellValue) for a given row (in-memory).  
     /// Useful to merge with SSTables when doing versioned scans.
     pub fn scan_row_full(&self, row: &[u8]) -> Vec<(EntryKey, CellValue)> {
+        self.scan_row_bounded(row, Timestamp::MAX, usize::MAX)
+    }
+
+    /// Copy, for each column of row, the newest limit_per_column entries with a timestamp
+    /// of at most max_timestamp, sorted by key. Older versions of a column are skipped.
+    pub fn scan_row_bounded(&self, row: &[u8], max_timestamp: Timestamp, limit_per_column: usize) -> Vec<(EntryKey, CellValue)> {
+        if limit_per_column == 0 {
+            return Vec::new();
+        }
         let range_start = EntryKey {
             row: row.to_vec(),
             column: vec![],
             timestamp: 0,
+            seq: 0,
         };
         let range_end = EntryKey {
             row: row.to_vec(),
             column: vec![0xFF],
             timestamp: u64::MAX,
+            seq: Seq::MAX,
         };
 
-        // Use filter_map to transform and filter the range iterator
-        self.map.range(range_start..=range_end)
-            .filter(|(k, _)| k.row == row)
-            .map(|(k, v)| (k.clone(), v.clone()))
-            .collect()
+        // Walk newest first; once a column has enough versions, seek past its older ones
+        let mut entries = Vec::new();
+        let mut upper = Bound::Included(range_end);
+        'columns: loop {
+            let mut taken = 0;
+            let mut column: Option<&[u8]> = None;
+            for (k, v) in self.map.range((Bound::Included(range_start.clone()), upper.clone())).rev() {
+                if column != Some(k.column.as_slice()) {
+                    column = Some(k.column.as_slice());
+                    taken = 0;
+                }
+                if k.row != row || k.timestamp > max_timestamp {
+                    continue;
+                }
+                entries.push((k.clone(), v.clone()));
+                taken += 1;
+                if taken == limit_per_column {
+                    upper = Bound::Excluded(EntryKey { row: row.to_vec(), column: k.column.clone(), timestamp: 0, seq: 0 });
+                    continue 'columns;
+                }
+            }
+            break;
+        }
+        entries.reverse();
+        entries
     }
 
     /// Scan a range of rows and return all (EntryKey, CellValue) pairs.
@@ -160,11 +1015,13 @@ impl MemStore {
             row: start_row.to_vec(),
             column: vec![],
             timestamp: 0,
+            seq: 0,
         };
         let range_end = EntryKey {
             row: end_row.to_vec(),
             column: vec![0xFF],
             timestamp: u64::MAX,
+            seq: Seq::MAX,
         };
 
         // Use filter and map to transform the range iterator
@@ -174,6 +1031,51 @@ impl MemStore {
             .collect()
     }
 
+    /// The largest row key in the MemStore, tombstones included.
+    pub fn last_row(&self) -> Option<&[u8]> {
+        self.map.last_key_value().map(|(key, _)| key.row.as_slice())
+    }
+
+    /// Iterate over every entry in the MemStore, in key order, without cloning them.
+    pub fn iter(&self) -> impl Iterator<Item = (&EntryKey, &CellValue)> {
+        self.map.iter()
+    }
+
+    /// Iterate over the entries whose row lies in [start_row, end_row], in key order,
+    /// without cloning them.
+    pub fn range_iter<'a>(&'a self, start_row: &[u8], end_row: &'a [u8]) -> impl Iterator<Item = (&'a EntryKey, &'a CellValue)> {
+        let range_start = EntryKey {
+            row: start_row.to_vec(),
+            column: vec![],
+            timestamp: 0,
+            seq: 0,
+        };
+        self.map.range(range_start..).take_while(move |(k, _)| k.row.as_slice() <= end_row)
+    }
+
+    /// Return every (EntryKey, CellValue) in the MemStore, in key order.
+    pub fn scan_all(&self) -> Vec<(EntryKey, CellValue)> {
+        self.map.iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+
+    /// Key-only range scan: return each EntryKey in [start_row, end_row] and whether it is a Put,
+    /// without cloning cell values.
+    pub fn scan_range_keys(&self, start_row: &[u8], end_row: &[u8]) -> Vec<(EntryKey, bool)> {
+        let range_start = EntryKey {
+            row: start_row.to_vec(),
+            column: vec![],
+            timestamp: 0,
+            seq: 0,
+        };
+
+        self.map.range(range_start..)
+            .take_while(|(k, _)| k.row.as_slice() <= end_row)
+            .map(|(k, v)| (k.clone(), matches!(v, CellValue::Put(_))))
+            .collect()
+    }
+
     /// Get all unique row keys in a range.
     pub fn get_row_keys_in_range(&self, start_row: &[u8], end_row: &[u8]) -> Vec<Vec<u8>> {
         // Use fold to collect unique row keys into a BTreeSet
@@ -192,7 +1094,7 @@ impl MemStore {
 mod tests {
     use super::*;
     use crate::api::{CellValue, Entry, EntryKey};
-    use std::fs;
+    
     use std::path::PathBuf;
     use tempfile::tempdir;
 
@@ -222,6 +1124,7 @@ mod tests {
                 row: b"row1".to_vec(),
                 column: b"col1".to_vec(),
                 timestamp: 100,
+                seq: 0,
             },
             value: CellValue::Put(b"value1".to_vec()),
         };
@@ -255,6 +1158,7 @@ mod tests {
                     row: b"row1".to_vec(),
                     column: b"col1".to_vec(),
                     timestamp: i * 100,
+                    seq: 0,
                 },
                 value: CellValue::Put(format!("value{}", i).into_bytes()),
             };
@@ -277,6 +1181,70 @@ mod tests {
         drop(dir);
     }
 
+    #[test]
+    fn test_memstore_append_many() {
+        let (dir, wal_path) = temp_wal_path();
+        let entry = |column: &[u8], value: CellValue| Entry {
+            key: EntryKey { row: b"row1".to_vec(), column: column.to_vec(), timestamp: 100, seq: 0 },
+            value,
+        };
+        {
+            let mut store = MemStore::open(&wal_path).unwrap();
+            store.append_many(vec![
+                entry(b"col1", CellValue::Put(b"value1".to_vec())),
+                entry(b"col2", CellValue::Put(b"value2".to_vec())),
+                // Later entries for the same timestamp are newer versions
+                entry(b"col1", CellValue::Delete(None)),
+            ]).unwrap();
+            assert_eq!(store.len(), 3);
+            assert_eq!(store.last_seq(), 3);
+            store.append_many(Vec::new()).unwrap();
+        }
+
+        let store = MemStore::open(&wal_path).unwrap();
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.last_seq(), 3);
+        assert_eq!(store.get_full(b"row1", b"col1"), Some(&CellValue::Delete(None)));
+        let versions = store.get_versions_full(b"row1", b"col1");
+        assert_eq!(versions, vec![(100, CellValue::Delete(None)), (100, CellValue::Put(b"value1".to_vec()))]);
+        assert_eq!(store.get_full(b"row1", b"col2"), Some(&CellValue::Put(b"value2".to_vec())));
+
+        drop(store);
+        drop(dir);
+    }
+
+    #[test]
+    fn test_memstore_bounded_versions() {
+        let (dir, wal_path) = temp_wal_path();
+        let mut store = MemStore::open(&wal_path).unwrap();
+        for column in [b"col1", b"col2"] {
+            for ts in 1..=5 {
+                store.append(Entry {
+                    key: EntryKey { row: b"row1".to_vec(), column: column.to_vec(), timestamp: ts * 100, seq: 0 },
+                    value: CellValue::Put(format!("v{}", ts).into_bytes()),
+                }).unwrap();
+            }
+        }
+
+        let versions = store.get_versions_bounded(b"row1", b"col1", 400, 2);
+        assert_eq!(versions.iter().map(|v| v.0).collect::<Vec<_>>(), vec![400, 300]);
+        let entries = store.cell_entries_bounded(b"row1", b"col1", Timestamp::MAX, 2);
+        assert_eq!(entries.iter().map(|e| e.0.timestamp).collect::<Vec<_>>(), vec![400, 500]);
+
+        // Each column keeps its newest versions up to the bound, sorted by key
+        let row = store.scan_row_bounded(b"row1", 300, 2);
+        let keys: Vec<_> = row.iter().map(|(k, _)| (k.column.clone(), k.timestamp)).collect();
+        assert_eq!(keys, vec![
+            (b"col1".to_vec(), 200), (b"col1".to_vec(), 300),
+            (b"col2".to_vec(), 200), (b"col2".to_vec(), 300),
+        ]);
+        assert_eq!(store.scan_row_bounded(b"row1", Timestamp::MAX, usize::MAX), store.scan_row_full(b"row1"));
+        assert!(store.scan_row_bounded(b"row1", Timestamp::MAX, 0).is_empty());
+
+        drop(store);
+        drop(dir);
+    }
+
     #[test]
     fn test_memstore_drain_all() {
         let (dir, wal_path) = temp_wal_path();
@@ -288,6 +1256,7 @@ mod tests {
                     row: format!("row{}", i).into_bytes(),
                     column: b"col1".to_vec(),
                     timestamp: 100,
+                    seq: 0,
                 },
                 value: CellValue::Put(format!("value{}", i).into_bytes()),
             };
@@ -320,6 +1289,7 @@ mod tests {
                     row: b"row1".to_vec(),
                     column: format!("col{}", i).into_bytes(),
                     timestamp: 100 + i as u64,
+                    seq: 0,
                 },
                 value: CellValue::Put(format!("value{}", i).into_bytes()),
             };
@@ -331,6 +1301,7 @@ mod tests {
                 row: b"row2".to_vec(),
                 column: b"col1".to_vec(),
                 timestamp: 100,
+                seq: 0,
             },
             value: CellValue::Put(b"other_value".to_vec()),
         };
@@ -365,6 +1336,7 @@ mod tests {
                         row: b"row1".to_vec(),
                         column: format!("col{}", i).into_bytes(),
                         timestamp: 100 + i as u64,
+                        seq: 0,
                     },
                     value: CellValue::Put(format!("value{}", i).into_bytes()),
                 };
@@ -391,6 +1363,405 @@ mod tests {
         drop(dir);
     }
 
+    #[test]
+    fn test_memstore_in_memory_backend() {
+        let backend = crate::backend::MemoryBackend::new();
+        let wal_path = Path::new("/cf/wal.log");
+        let entry = |i: u64| Entry {
+            key: EntryKey { row: b"row1".to_vec(), column: b"col1".to_vec(), timestamp: i, seq: 0 },
+            value: CellValue::Put(format!("value{}", i).into_bytes()),
+        };
+        {
+            let mut store = MemStore::open_in(Arc::new(backend.clone()), wal_path).unwrap();
+            store.append_many(vec![entry(1), entry(2)]).unwrap();
+        }
+
+        let mut store = MemStore::open_in(Arc::new(backend.clone()), wal_path).unwrap();
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.get_full(b"row1", b"col1"), Some(&CellValue::Put(b"value2".to_vec())));
+
+        store.drain_all().unwrap();
+        store.append(entry(3)).unwrap();
+        assert_eq!(MemStore::open_in(Arc::new(backend), wal_path).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_memstore_freeze_and_release() {
+        let (dir, wal_path) = temp_wal_path();
+        let entry = |row: &str, value: &str| Entry {
+            key: EntryKey { row: row.as_bytes().to_vec(), column: b"col1".to_vec(), timestamp: 1, seq: 0 },
+            value: CellValue::Put(value.as_bytes().to_vec()),
+        };
+        let mut store = MemStore::open(&wal_path).unwrap();
+        store.append_many(vec![entry("row1", "a"), entry("row2", "b")]).unwrap();
+
+        let frozen = store.freeze().unwrap();
+        assert_eq!(frozen.len(), 2);
+        assert!(store.is_frozen(&frozen));
+        assert_eq!(store.freeze().err().unwrap().kind(), ErrorKind::WouldBlock);
+
+        // Writes go on into a fresh WAL; frozen entries stay readable
+        store.append_many(vec![entry("row2", "c"), entry("row3", "d")]).unwrap();
+        assert_eq!((store.len(), store.active_len()), (4, 2));
+        assert_eq!(store.get_full(b"row1", b"col1"), Some(&CellValue::Put(b"a".to_vec())));
+        assert!(segment_path(&wal_path, 1).exists());
+
+        // A crash before the release replays the sealed segment, then the active WAL
+        let reopened = MemStore::open(&wal_path).unwrap();
+        assert_eq!(reopened.len(), 4);
+        assert_eq!(reopened.last_seq(), 4);
+        assert_eq!(reopened.get_full(b"row2", b"col1"), Some(&CellValue::Put(b"c".to_vec())));
+        drop(reopened);
+
+        // A failed flush is retried with the entries written since
+        store.thaw();
+        let frozen = store.freeze().unwrap();
+        assert_eq!(frozen.len(), 4);
+        store.append(entry("row4", "e")).unwrap();
+        store.release_frozen(None).unwrap();
+        assert_eq!(store.segment_count(), 0);
+        assert!(!segment_path(&wal_path, 1).exists() && !segment_path(&wal_path, 2).exists());
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.get_full(b"row4", b"col1"), Some(&CellValue::Put(b"e".to_vec())));
+        assert_eq!(MemStore::open(&wal_path).unwrap().len(), 1);
+
+        drop(dir);
+    }
+
+    #[test]
+    fn test_memstore_wal_segments() {
+        let (dir, wal_path) = temp_wal_path();
+        let entry = |row: &str, value: &str| Entry {
+            key: EntryKey { row: row.as_bytes().to_vec(), column: b"col1".to_vec(), timestamp: 1, seq: 0 },
+            value: CellValue::Put(value.as_bytes().to_vec()),
+        };
+        let mut store = MemStore::open(&wal_path).unwrap();
+        assert_eq!(store.wal_segment_bytes(), DEFAULT_WAL_SEGMENT_BYTES);
+        store.set_wal_segment_bytes(1);
+        store.append(entry("row1", "a")).unwrap();
+        store.append(entry("row2", "b")).unwrap();
+        store.append(entry("row1", "c")).unwrap();
+        assert_eq!(store.segment_count(), 3);
+        assert!(segment_path(&wal_path, 3).exists());
+        assert_eq!(std::fs::metadata(&wal_path).unwrap().len(), 0);
+        assert_eq!(decode_wal("wal", &store.wal_contents().unwrap()).unwrap().len(), 3);
+
+        // The segments are replayed in order, so the later version of row1 is the newest
+        let reopened = MemStore::open(&wal_path).unwrap();
+        assert_eq!((reopened.len(), reopened.segment_count(), reopened.last_seq()), (3, 3, 3));
+        assert_eq!(reopened.get_full(b"row1", b"col1"), Some(&CellValue::Put(b"c".to_vec())));
+        drop(reopened);
+
+        // Segments sealed during a flush are kept by its release
+        let frozen = store.freeze().unwrap();
+        assert_eq!((frozen.len(), store.segment_count()), (3, 3));
+        store.append(entry("row3", "d")).unwrap();
+        store.release_frozen(None).unwrap();
+        assert_eq!((store.len(), store.segment_count()), (1, 1));
+        assert!(!segment_path(&wal_path, 3).exists() && segment_path(&wal_path, 4).exists());
+        let reopened = MemStore::open(&wal_path).unwrap();
+        assert_eq!(reopened.len(), 1);
+        drop(reopened);
+
+        // An immutable WAL left by an older version is replayed first, and released
+        store.clear().unwrap();
+        assert_eq!(store.segment_count(), 0);
+        store.set_wal_segment_bytes(DEFAULT_WAL_SEGMENT_BYTES);
+        store.append(entry("row1", "old")).unwrap();
+        drop(store);
+        std::fs::rename(&wal_path, immutable_wal_path(&wal_path)).unwrap();
+        let mut store = MemStore::open(&wal_path).unwrap();
+        store.append(entry("row1", "new")).unwrap();
+        assert_eq!(store.segment_count(), 1);
+        assert_eq!(store.get_full(b"row1", b"col1"), Some(&CellValue::Put(b"new".to_vec())));
+        store.freeze().unwrap();
+        assert!(segment_path(&wal_path, 1).exists());
+        store.release_frozen(None).unwrap();
+        assert!(!immutable_wal_path(&wal_path).exists() && !segment_path(&wal_path, 1).exists());
+        assert!(store.is_empty());
+
+        drop(dir);
+    }
+
+    #[test]
+    fn test_memstore_bounded_replay() {
+        let (dir, wal_path) = temp_wal_path();
+        let entry = |i: usize| Entry {
+            key: EntryKey { row: format!("row{:02}", i).into_bytes(), column: b"col1".to_vec(), timestamp: 1, seq: 0 },
+            value: CellValue::Put(b"value".to_vec()),
+        };
+        let mut store = MemStore::open(&wal_path).unwrap();
+        store.set_wal_segment_bytes(200);
+        store.append_requests(vec![entry(0)], vec!["req-1".to_string()]).unwrap();
+        for i in 1..30 {
+            store.append(entry(i)).unwrap();
+        }
+        let sealed = store.segment_count();
+        assert!(sealed > 2);
+        drop(store);
+
+        // A WAL that fits is replayed as by open
+        let store = MemStore::open_bounded(Arc::new(LocalFs), &wal_path, u64::MAX, |_| panic!("flushed")).unwrap();
+        assert_eq!((store.len(), store.segment_count()), (30, sealed));
+        drop(store);
+
+        // A failed flush fails the open, and nothing is lost
+        let err = MemStore::open_bounded(Arc::new(LocalFs), &wal_path, 0, |_| Err(Error::other("disk full")));
+        assert_eq!(err.err().unwrap().to_string(), "disk full");
+        assert_eq!(MemStore::open(&wal_path).unwrap().len(), 30);
+
+        // The entries are written out whenever they take more than max_bytes, then the
+        // rest, and the WAL starts afresh
+        let mut flushed: Vec<Vec<Entry>> = Vec::new();
+        let store = MemStore::open_bounded(Arc::new(LocalFs), &wal_path, 100, |entries| {
+            flushed.push(entries);
+            Ok(())
+        }).unwrap();
+        assert!(flushed.len() > 2);
+        assert!(flushed.iter().all(|entries| entries.windows(2).all(|pair| pair[0].key < pair[1].key)));
+        assert_eq!(flushed.iter().map(Vec::len).sum::<usize>(), 30);
+        assert_eq!((store.len(), store.segment_count(), store.last_seq()), (0, 0, 30));
+        assert!(wal_segments(&LocalFs, &wal_path).unwrap().is_empty());
+        drop(store);
+
+        // Nothing is replayed twice; request IDs are kept
+        let reopened = MemStore::open(&wal_path).unwrap();
+        assert!(reopened.is_empty());
+        assert!(reopened.has_request("req-1"));
+
+        drop(dir);
+    }
+
+    #[test]
+    fn test_memstore_request_ids() {
+        let (dir, wal_path) = temp_wal_path();
+        let entry = |row: &str| Entry {
+            key: EntryKey { row: row.as_bytes().to_vec(), column: b"col1".to_vec(), timestamp: 1, seq: 0 },
+            value: CellValue::Put(b"v".to_vec()),
+        };
+        let mut store = MemStore::open(&wal_path).unwrap();
+        store.append_requests(vec![entry("row1")], vec!["req-1".to_string()]).unwrap();
+        store.append_requests(vec![entry("row2"), entry("row3")], vec!["req-2".to_string(), "req-3".to_string()]).unwrap();
+        store.append(entry("row4")).unwrap();
+        assert!(store.has_request("req-1") && store.has_request("req-3"));
+        assert!(!store.has_request("req-4"));
+
+        // The IDs are replayed with the entries, which other readers of the WAL still decode
+        let reopened = MemStore::open(&wal_path).unwrap();
+        assert_eq!(reopened.len(), 4);
+        assert_eq!(reopened.last_seq(), 4);
+        assert!(reopened.has_request("req-2"));
+        assert_eq!(decode_wal("wal", &reopened.wal_contents().unwrap()).unwrap().len(), 4);
+        drop(reopened);
+
+        // They outlive the WAL of a flush
+        store.freeze().unwrap();
+        store.release_frozen(None).unwrap();
+        assert!(store.is_empty());
+        let mut reopened = MemStore::open(&wal_path).unwrap();
+        assert!(reopened.is_empty());
+        assert!(reopened.has_request("req-1") && reopened.has_request("req-3"));
+
+        // Only the latest REQUEST_WINDOW are remembered
+        for i in 0..REQUEST_WINDOW {
+            reopened.append_requests(Vec::new(), vec![format!("new-{}", i)]).unwrap();
+        }
+        assert!(!reopened.has_request("req-1"));
+        assert!(reopened.has_request("new-0"));
+
+        // Clearing forgets them
+        store.clear().unwrap();
+        assert!(!store.has_request("req-1"));
+
+        drop(dir);
+    }
+
+    #[test]
+    fn test_memstore_size_bytes() {
+        let (dir, wal_path) = temp_wal_path();
+        let entry = |row: &str, value: &[u8]| Entry {
+            key: EntryKey { row: row.as_bytes().to_vec(), column: b"col1".to_vec(), timestamp: 1, seq: 0 },
+            value: CellValue::Put(value.to_vec()),
+        };
+        let mut store = MemStore::open(&wal_path).unwrap();
+        store.append(entry("row1", &[0; 100])).unwrap();
+        assert_eq!(store.size_bytes(), 4 + 4 + 8 + 100);
+
+        // Another version of the cell adds its own size
+        store.append(entry("row1", &[0; 10])).unwrap();
+        assert_eq!(store.size_bytes(), 2 * (4 + 4 + 8) + 100 + 10);
+        store.append(entry("row2", &[0; 50])).unwrap();
+        assert_eq!(MemStore::open(&wal_path).unwrap().size_bytes(), store.size_bytes());
+
+        // Only entries written since the freeze are active
+        store.freeze().unwrap();
+        store.append(entry("row3", &[0; 20])).unwrap();
+        assert_eq!(store.active_bytes(), 4 + 4 + 8 + 20);
+        store.release_frozen(None).unwrap();
+        assert_eq!((store.size_bytes(), store.active_bytes()), (36, 36));
+        store.clear().unwrap();
+        assert_eq!(store.size_bytes(), 0);
+
+        drop(dir);
+    }
+
+    #[test]
+    fn test_memstore_corrupt_wal() {
+        let (dir, wal_path) = temp_wal_path();
+        {
+            let mut store = MemStore::open(&wal_path).unwrap();
+            store.append(Entry {
+                key: EntryKey { row: b"row1".to_vec(), column: b"col1".to_vec(), timestamp: 1, seq: 0 },
+                value: CellValue::Put(b"value1".to_vec()),
+            }).unwrap();
+        }
+        let valid = std::fs::read(&wal_path).unwrap();
+
+        // A record whose payload does not decode
+        let mut corrupted = valid.clone();
+        corrupted.extend_from_slice(&4u32.to_be_bytes());
+        corrupted.extend_from_slice(&[0xff; 4]);
+        std::fs::write(&wal_path, &corrupted).unwrap();
+        let err = MemStore::open(&wal_path).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().contains(&format!("at {}", valid.len())));
+
+        // A length past the end of a sealed segment
+        let mut corrupted = valid.clone();
+        corrupted.extend_from_slice(&8u32.to_be_bytes());
+        corrupted.extend_from_slice(&[0; 3]);
+        std::fs::write(segment_path(&wal_path, 1), &corrupted).unwrap();
+        std::fs::write(&wal_path, &valid).unwrap();
+        assert_eq!(MemStore::open(&wal_path).err().unwrap().kind(), ErrorKind::InvalidData);
+        std::fs::remove_file(segment_path(&wal_path, 1)).unwrap();
+
+        // At the end of the active WAL it is a record torn by a write cut short: it is
+        // skipped and cut off, so that the records appended next replay
+        std::fs::write(&wal_path, &corrupted).unwrap();
+        {
+            let mut store = MemStore::open(&wal_path).unwrap();
+            assert_eq!((store.replay_stats().records, store.replay_stats().torn_bytes), (1, 7));
+            assert_eq!(std::fs::read(&wal_path).unwrap(), valid);
+            store.append(Entry {
+                key: EntryKey { row: b"row2".to_vec(), column: b"col1".to_vec(), timestamp: 2, seq: 0 },
+                value: CellValue::Put(b"value2".to_vec()),
+            }).unwrap();
+        }
+        let reopened = MemStore::open(&wal_path).unwrap();
+        assert_eq!((reopened.replay_stats().records, reopened.replay_stats().torn_bytes), (2, 0));
+
+        drop(dir);
+    }
+
+    #[test]
+    fn test_memstore_wal_rotation() {
+        let (dir, wal_path) = temp_wal_path();
+        let entry = |row: &str| Entry {
+            key: EntryKey { row: row.as_bytes().to_vec(), column: b"col1".to_vec(), timestamp: 1, seq: 0 },
+            value: CellValue::Put(b"v".to_vec()),
+        };
+        let reopened_rows = || -> Vec<Vec<u8>> {
+            MemStore::open(&wal_path).unwrap().entries().into_iter().map(|e| e.key.row).collect()
+        };
+        let mut store = MemStore::open(&wal_path).unwrap();
+
+        // Every rotation leaves a WAL that later writes go to
+        store.append(entry("row1")).unwrap();
+        store.freeze().unwrap();
+        store.append(entry("row2")).unwrap();
+        assert_eq!(reopened_rows(), vec![b"row1".to_vec(), b"row2".to_vec()]);
+        store.release_frozen(None).unwrap();
+        store.clear().unwrap();
+        assert!(!discarded_wal_path(&wal_path).exists());
+        store.append(entry("row3")).unwrap();
+        assert_eq!(reopened_rows(), vec![b"row3".to_vec()]);
+        let archive = dir.path().join("archived.wal");
+        store.archive_to(&archive).unwrap();
+        store.append(entry("row4")).unwrap();
+        assert_eq!(reopened_rows(), vec![b"row4".to_vec()]);
+        assert_eq!(decode_wal("archived.wal", &std::fs::read(&archive).unwrap()).unwrap().len(), 1);
+        drop(store);
+
+        // A WAL a clear cut short left aside is deleted on open
+        std::fs::copy(&archive, discarded_wal_path(&wal_path)).unwrap();
+        assert_eq!(reopened_rows(), vec![b"row4".to_vec()]);
+        assert!(!discarded_wal_path(&wal_path).exists());
+    }
+
+    /// Windows cannot rename or delete an open file, so rotations must close the WAL
+    /// first; on Linux, check that no rotated file is left open.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_memstore_rotation_closes_wal() {
+        let (dir, wal_path) = temp_wal_path();
+        let open_files = || -> Vec<String> {
+            std::fs::read_dir("/proc/self/fd").unwrap()
+                .filter_map(|fd| std::fs::read_link(fd.ok()?.path()).ok())
+                .map(|target| target.to_string_lossy().to_string())
+                .filter(|target| target.starts_with(&*dir.path().to_string_lossy()))
+                .collect()
+        };
+        let mut store = MemStore::open(&wal_path).unwrap();
+        store.append(Entry {
+            key: EntryKey { row: b"row1".to_vec(), column: b"col1".to_vec(), timestamp: 1, seq: 0 },
+            value: CellValue::Put(b"v".to_vec()),
+        }).unwrap();
+        store.freeze().unwrap();
+        assert_eq!(open_files(), vec![wal_path.to_string_lossy().to_string()]);
+        store.clear().unwrap();
+        assert_eq!(open_files(), vec![wal_path.to_string_lossy().to_string()]);
+        store.archive_to(&dir.path().join("archived.wal")).unwrap();
+        assert_eq!(open_files(), vec![wal_path.to_string_lossy().to_string()]);
+    }
+
+    /// Unix paths need not be valid UTF-8; the WAL must rotate at its real path.
+    #[cfg(unix)]
+    #[test]
+    fn test_memstore_non_utf8_wal_path() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempdir().unwrap();
+        let wal_path = dir.path().join(std::ffi::OsStr::from_bytes(b"wal-\xff.log"));
+        let mut store = MemStore::open(&wal_path).unwrap();
+        store.append(Entry {
+            key: EntryKey { row: b"row1".to_vec(), column: b"col1".to_vec(), timestamp: 1, seq: 0 },
+            value: CellValue::Put(b"v".to_vec()),
+        }).unwrap();
+        store.freeze().unwrap();
+        assert!(segment_path(&wal_path, 1).exists());
+        store.release_frozen(None).unwrap();
+        store.clear().unwrap();
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert!(wal_path.exists());
+    }
+
+    #[test]
+    fn test_memstore_discard_through() {
+        let (dir, wal_path) = temp_wal_path();
+        // A record logged before sequence numbers, then a numbered one
+        let legacy = Entry {
+            key: EntryKey { row: b"row1".to_vec(), column: b"col1".to_vec(), timestamp: 1, seq: 0 },
+            value: CellValue::Put(b"legacy".to_vec()),
+        };
+        let buf = bincode::serialize(&legacy).unwrap();
+        let mut wal = (buf.len() as u32).to_be_bytes().to_vec();
+        wal.extend_from_slice(&buf);
+        std::fs::write(&wal_path, &wal).unwrap();
+        let mut store = MemStore::open(&wal_path).unwrap();
+        store.append(Entry {
+            key: EntryKey { row: b"row2".to_vec(), column: b"col1".to_vec(), timestamp: 1, seq: 0 },
+            value: CellValue::Put(b"numbered".to_vec()),
+        }).unwrap();
+
+        store.discard_through(0);
+        assert_eq!(store.len(), 2);
+        store.discard_through(store.last_seq());
+        assert!(store.is_empty());
+        assert_eq!(store.size_bytes(), 0);
+
+        drop(dir);
+    }
+
     #[test]
     fn test_memstore_tombstone() {
         let (dir, wal_path) = temp_wal_path();
@@ -401,6 +1772,7 @@ mod tests {
                 row: b"row1".to_vec(),
                 column: b"col1".to_vec(),
                 timestamp: 100,
+                seq: 0,
             },
             value: CellValue::Put(b"value1".to_vec()),
         };
@@ -411,6 +1783,7 @@ mod tests {
                 row: b"row1".to_vec(),
                 column: b"col1".to_vec(),
                 timestamp: 200,
+                seq: 0,
             },
             value: CellValue::Delete(None),
         };
@@ -0,0 +1,126 @@
+//! K-way merge of the sorted entries of the MemStore and SSTables.
+//!
+//! Every source (a MemStore or SSTable slice) is sorted by EntryKey. `MergeIter` walks
+//! them backwards and merges them with a heap, so it yields entries in descending key
+//! order: cells in reverse order and, within a cell, the newest version first. Readers
+//! can therefore stop as soon as they have the versions they need instead of collecting
+//! and sorting every version first.
+
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    iter::Rev,
+    slice,
+};
+
+use crate::api::{CellValue, EntryKey};
+
+type Source<'a> = Rev<slice::Iter<'a, (EntryKey, CellValue)>>;
+
+/// Next entry of one source, ordered by key and then by source priority.
+struct Head<'a> {
+    entry: &'a (EntryKey, CellValue),
+    source: usize,
+}
+
+impl Ord for Head<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Max-heap: the largest key first, and for equal keys the earliest source
+        self.entry.0.cmp(&other.entry.0).then_with(|| other.source.cmp(&self.source))
+    }
+}
+
+impl PartialOrd for Head<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head<'_> {}
+
+/// Iterator over several sorted entry slices in descending key order.
+///
+/// Sources are given newest first (the MemStore, then SSTables from newest to oldest).
+/// When several sources hold the same key, only the entry of the newest source is yielded.
+pub struct MergeIter<'a> {
+    sources: Vec<Source<'a>>,
+    heap: BinaryHeap<Head<'a>>,
+    last: Option<&'a EntryKey>,
+}
+
+impl<'a> MergeIter<'a> {
+    /// Merge sources, each sorted in ascending key order, given newest first.
+    pub fn new(sources: impl IntoIterator<Item = &'a [(EntryKey, CellValue)]>) -> Self {
+        let mut sources: Vec<Source<'a>> = sources.into_iter().map(|s| s.iter().rev()).collect();
+        let heap = sources.iter_mut()
+            .enumerate()
+            .filter_map(|(source, iter)| Some(Head { entry: iter.next()?, source }))
+            .collect();
+        Self {
+            sources,
+            heap,
+            last: None,
+        }
+    }
+}
+
+impl<'a> Iterator for MergeIter<'a> {
+    type Item = (&'a EntryKey, &'a CellValue);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Head { entry, source } = self.heap.pop()?;
+            if let Some(next) = self.sources[source].next() {
+                self.heap.push(Head { entry: next, source });
+            }
+            if self.last == Some(&entry.0) {
+                continue;
+            }
+            self.last = Some(&entry.0);
+            return Some((&entry.0, &entry.1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(row: &str, column: &str, timestamp: u64, value: &str) -> (EntryKey, CellValue) {
+        (
+            EntryKey {
+                row: row.as_bytes().to_vec(),
+                column: column.as_bytes().to_vec(),
+                timestamp,
+                seq: 0,
+            },
+            CellValue::Put(value.as_bytes().to_vec()),
+        )
+    }
+
+    #[test]
+    fn test_merge_newest_first() {
+        let memstore = [entry("r1", "a", 5, "mem"), entry("r1", "b", 9, "mem")];
+        let newer = [entry("r1", "a", 3, "newer"), entry("r1", "a", 4, "newer")];
+        let older = [entry("r1", "a", 1, "older"), entry("r1", "a", 4, "older"), entry("r2", "a", 2, "older")];
+
+        let merged: Vec<(String, u64, CellValue)> = MergeIter::new([&memstore[..], &newer[..], &older[..]])
+            .map(|(key, value)| (format!("{}/{}", String::from_utf8_lossy(&key.row), String::from_utf8_lossy(&key.column)), key.timestamp, value.clone()))
+            .collect();
+
+        let expected = [("r2/a", 2, "older"), ("r1/b", 9, "mem"), ("r1/a", 5, "mem"), ("r1/a", 4, "newer"), ("r1/a", 3, "newer"), ("r1/a", 1, "older")];
+        assert_eq!(merged.len(), expected.len());
+        for ((cell, ts, value), (want_cell, want_ts, want_value)) in merged.iter().zip(expected) {
+            assert_eq!((cell.as_str(), *ts), (want_cell, want_ts));
+            assert_eq!(*value, CellValue::Put(want_value.as_bytes().to_vec()));
+        }
+
+        assert_eq!(MergeIter::new(Vec::<&[(EntryKey, CellValue)]>::new()).count(), 0);
+    }
+}
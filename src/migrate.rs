@@ -0,0 +1,334 @@
+//! Migration of table directories written by earlier versions to the current formats.
+//!
+//! Readers handle every earlier format (see `SSTable` and `MemStore`), so old files keep
+//! working, but only files in the current format get its guarantees: SSTables without a
+//! header have no checksums or footer to check, and keeping every format readable
+//! forever holds back changing them. `migrate` rewrites a table directory in place:
+//! SSTables of an earlier format are rewritten in the current one, WAL records written
+//! before sequence numbers get one, and column families without a manifest get one.
+//!
+//! Every file is linked (or copied) into the `migration_backup` directory of its column
+//! family, and listed in the `MIGRATION.json` record there, before it is replaced, so
+//! `rollback` can put the original files back, even after a migration cut short by a
+//! crash, and `finish` drops the backups once the migrated table is known to work.
+//! Writes made since the migration are lost by a rollback. The table must not be open
+//! while it is migrated or rolled back.
+
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result as IoResult},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::api::Entry;
+use crate::manifest::{Manifest, MANIFEST_FILE};
+use crate::memstore::{immutable_wal_path, upgrade_wal};
+use crate::region::REGIONS_MANIFEST;
+use crate::storage::{Durability, FormatVersion, SSTableReader, SSTableWriter};
+
+/// Directory of a column family holding the files a migration replaced.
+pub const MIGRATION_BACKUP_DIR: &str = "migration_backup";
+
+/// File in `MIGRATION_BACKUP_DIR` listing what the migration changed.
+const MIGRATION_RECORD: &str = "MIGRATION.json";
+
+/// What a migration changed in a column family, by file name.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MigrationRecord {
+    /// Files replaced, whose originals are in the backup directory
+    rewritten: Vec<String>,
+    /// Files created
+    created: Vec<String>,
+}
+
+/// Files changed by `migrate`, or restored by `rollback`, or whose backups `finish`
+/// dropped.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MigrationReport {
+    /// Files rewritten in the current format (restored, by a rollback)
+    pub rewritten: Vec<PathBuf>,
+    /// Files created, such as manifests (removed, by a rollback)
+    pub created: Vec<PathBuf>,
+}
+
+impl MigrationReport {
+    pub fn is_empty(&self) -> bool {
+        self.rewritten.is_empty() && self.created.is_empty()
+    }
+
+    fn add(&mut self, dir: &Path, record: &MigrationRecord) {
+        self.rewritten.extend(record.rewritten.iter().map(|name| dir.join(name)));
+        self.created.extend(record.created.iter().map(|name| dir.join(name)));
+    }
+}
+
+/// The column family directories of the table at table_dir: its plain column families
+/// and the regions of its regioned ones.
+pub(crate) fn column_family_dirs(table_dir: &Path) -> IoResult<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(table_dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        if !entry.path().join(REGIONS_MANIFEST).exists() {
+            dirs.push(entry.path());
+            continue;
+        }
+        for region in fs::read_dir(entry.path())? {
+            let region = region?;
+            if region.file_type()?.is_dir() && region.file_name().to_string_lossy().starts_with("region-") {
+                dirs.push(region.path());
+            }
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// Migrate the table at table_dir to the current formats; see the module documentation.
+/// Fails with `AlreadyExists` if an earlier migration was neither finished nor rolled
+/// back.
+pub fn migrate(table_dir: &Path) -> IoResult<MigrationReport> {
+    let dirs = column_family_dirs(table_dir)?;
+    if let Some(dir) = dirs.iter().find(|dir| dir.join(MIGRATION_BACKUP_DIR).exists()) {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!("{} holds an earlier migration; finish or roll it back first", dir.display()),
+        ));
+    }
+    let mut report = MigrationReport::default();
+    for dir in dirs {
+        let record = migrate_column_family(&dir)?;
+        report.add(&dir, &record);
+    }
+    info!(table = %table_dir.display(), rewritten = report.rewritten.len(), created = report.created.len(), "migrated table");
+    Ok(report)
+}
+
+/// Migrate the column family directory dir.
+fn migrate_column_family(dir: &Path) -> IoResult<MigrationRecord> {
+    let backup_dir = dir.join(MIGRATION_BACKUP_DIR);
+    let mut record = MigrationRecord::default();
+
+    let mut sst_paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "sst") {
+            sst_paths.push(path);
+        }
+    }
+    sst_paths.sort();
+    for path in sst_paths {
+        let reader = SSTableReader::load(&path)?;
+        if reader.format_version() == FormatVersion::CURRENT {
+            continue;
+        }
+        back_up(&backup_dir, &path, &mut record)?;
+        let staging = staging_path(&path);
+        let mut writer = SSTableWriter::create_with(&staging, Durability::Sync)?;
+        for (key, value) in reader.all_entries().iter() {
+            writer.append(&Entry { key: key.clone(), value: value.clone() })?;
+        }
+        writer.finish()?;
+        fs::rename(&staging, &path)?;
+    }
+
+    let wal_path = dir.join("wal.log");
+    for path in [immutable_wal_path(&wal_path), wal_path] {
+        if !path.exists() {
+            continue;
+        }
+        if let Some(upgraded) = upgrade_wal(&path.to_string_lossy(), &fs::read(&path)?)? {
+            back_up(&backup_dir, &path, &mut record)?;
+            let staging = staging_path(&path);
+            fs::write(&staging, upgraded)?;
+            fs::File::open(&staging)?.sync_all()?;
+            fs::rename(&staging, &path)?;
+        }
+    }
+
+    if !dir.join(MANIFEST_FILE).exists() {
+        // Recorded first, so that a rollback removes it even if the migration stops here
+        record.created.push(MANIFEST_FILE.to_string());
+        write_record(&backup_dir, &record)?;
+        Manifest::open(dir)?;
+    }
+    Durability::Sync.sync_dir(dir)?;
+    Ok(record)
+}
+
+/// Where the migrated version of the file at path is written before it replaces it.
+fn staging_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".migrating");
+    PathBuf::from(name)
+}
+
+/// Keep the file at path in backup_dir, and list it in record, before it is replaced.
+fn back_up(backup_dir: &Path, path: &Path, record: &mut MigrationRecord) -> IoResult<()> {
+    let name = path.file_name().unwrap().to_string_lossy().to_string();
+    fs::create_dir_all(backup_dir)?;
+    let backup = backup_dir.join(&name);
+    // The file is replaced by a rename, so a hard link keeps the original intact
+    if fs::hard_link(path, &backup).is_err() {
+        fs::copy(path, &backup)?;
+        fs::File::open(&backup)?.sync_all()?;
+    }
+    record.rewritten.push(name);
+    write_record(backup_dir, record)
+}
+
+fn write_record(backup_dir: &Path, record: &MigrationRecord) -> IoResult<()> {
+    fs::create_dir_all(backup_dir)?;
+    let path = backup_dir.join(MIGRATION_RECORD);
+    let staging = path.with_extension("json.tmp");
+    fs::write(&staging, serde_json::to_vec_pretty(record)?)?;
+    fs::File::open(&staging)?.sync_all()?;
+    fs::rename(&staging, &path)?;
+    Durability::Sync.sync_dir(backup_dir)
+}
+
+/// The migration record of the column family directory dir, if a migration left one.
+fn read_record(dir: &Path) -> IoResult<Option<MigrationRecord>> {
+    let path = dir.join(MIGRATION_BACKUP_DIR).join(MIGRATION_RECORD);
+    if !path.exists() {
+        return Ok(None);
+    }
+    serde_json::from_slice(&fs::read(&path)?).map(Some).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+}
+
+/// Undo the migration of the table at table_dir: put back the files it replaced and
+/// remove those it created. Writes made since the migration are lost.
+pub fn rollback(table_dir: &Path) -> IoResult<MigrationReport> {
+    let mut report = MigrationReport::default();
+    for dir in column_family_dirs(table_dir)? {
+        let backup_dir = dir.join(MIGRATION_BACKUP_DIR);
+        let Some(record) = read_record(&dir)? else {
+            continue;
+        };
+        for name in &record.rewritten {
+            fs::rename(backup_dir.join(name), dir.join(name))?;
+        }
+        for name in &record.created {
+            match fs::remove_file(dir.join(name)) {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+                _ => (),
+            }
+        }
+        // Files a migration cut short was writing
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "migrating") {
+                fs::remove_file(path)?;
+            }
+        }
+        Durability::Sync.sync_dir(&dir)?;
+        fs::remove_dir_all(&backup_dir)?;
+        report.add(&dir, &record);
+    }
+    info!(table = %table_dir.display(), restored = report.rewritten.len(), removed = report.created.len(), "rolled back migration");
+    Ok(report)
+}
+
+/// Keep the migration of the table at table_dir for good, dropping the original files.
+pub fn finish(table_dir: &Path) -> IoResult<MigrationReport> {
+    let mut report = MigrationReport::default();
+    for dir in column_family_dirs(table_dir)? {
+        if let Some(record) = read_record(&dir)? {
+            report.add(&dir, &record);
+        }
+        let backup_dir = dir.join(MIGRATION_BACKUP_DIR);
+        if backup_dir.exists() {
+            fs::remove_dir_all(&backup_dir)?;
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{CellValue, EntryKey, Table};
+    use crate::storage::SSTable;
+    use tempfile::tempdir;
+
+    /// An SSTable as written before headers, footers and checksums existed.
+    fn legacy_sstable(path: &Path, rows: &[&str]) {
+        let mut data = (rows.len() as u32).to_be_bytes().to_vec();
+        for row in rows {
+            let key = EntryKey { row: row.as_bytes().to_vec(), column: b"col1".to_vec(), timestamp: 1, seq: 0 };
+            for field in [bincode::serialize(&key).unwrap(), bincode::serialize(&CellValue::Put(b"old".to_vec())).unwrap()] {
+                data.extend_from_slice(&(field.len() as u32).to_be_bytes());
+                data.extend_from_slice(&field);
+            }
+        }
+        fs::write(path, data).unwrap();
+    }
+
+    /// A WAL record as written before sequence numbers existed.
+    fn legacy_wal_record(row: &str) -> Vec<u8> {
+        let entry = Entry {
+            key: EntryKey { row: row.as_bytes().to_vec(), column: b"col1".to_vec(), timestamp: 2, seq: 0 },
+            value: CellValue::Put(b"logged".to_vec()),
+        };
+        let buf = bincode::serialize(&entry).unwrap();
+        [(buf.len() as u32).to_be_bytes().to_vec(), buf].concat()
+    }
+
+    #[test]
+    fn test_migrate_and_rollback() {
+        let dir = tempdir().unwrap();
+        let cf_dir = dir.path().join("cf");
+        fs::create_dir_all(&cf_dir).unwrap();
+        let sst = cf_dir.join("0000000001.sst");
+        legacy_sstable(&sst, &["row1", "row2"]);
+        let wal = cf_dir.join("wal.log");
+        fs::write(&wal, legacy_wal_record("row3")).unwrap();
+        let original_sst = fs::read(&sst).unwrap();
+        let original_wal = fs::read(&wal).unwrap();
+
+        let report = migrate(dir.path()).unwrap();
+        assert_eq!(report.rewritten, vec![sst.clone(), wal.clone()]);
+        assert_eq!(report.created, vec![cf_dir.join(MANIFEST_FILE)]);
+        assert_eq!(SSTableReader::load(&sst).unwrap().format_version(), FormatVersion::CURRENT);
+        assert_eq!(SSTableReader::load(&sst).unwrap().len(), 2);
+        assert_eq!(upgrade_wal("wal.log", &fs::read(&wal).unwrap()).unwrap(), None);
+        assert_eq!(migrate(dir.path()).err().unwrap().kind(), ErrorKind::AlreadyExists);
+
+        let report = rollback(dir.path()).unwrap();
+        assert_eq!(report.rewritten.len(), 2);
+        assert_eq!(fs::read(&sst).unwrap(), original_sst);
+        assert_eq!(fs::read(&wal).unwrap(), original_wal);
+        assert!(!cf_dir.join(MANIFEST_FILE).exists());
+        assert!(!cf_dir.join(MIGRATION_BACKUP_DIR).exists());
+
+        // Migrated again and kept, the table reads as before
+        migrate(dir.path()).unwrap();
+        assert_eq!(finish(dir.path()).unwrap().rewritten.len(), 2);
+        assert!(!cf_dir.join(MIGRATION_BACKUP_DIR).exists());
+        assert!(migrate(dir.path()).unwrap().is_empty());
+        let table = Table::open(dir.path()).unwrap();
+        let cf = table.cf("cf").unwrap();
+        assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"old".to_vec()));
+        assert_eq!(cf.get(b"row3", b"col1").unwrap(), Some(b"logged".to_vec()));
+    }
+
+    #[test]
+    fn test_migrate_skips_current_files() {
+        let dir = tempdir().unwrap();
+        let cf_dir = dir.path().join("cf");
+        fs::create_dir_all(&cf_dir).unwrap();
+        let entry = Entry {
+            key: EntryKey { row: b"row1".to_vec(), column: b"col1".to_vec(), timestamp: 1, seq: 1 },
+            value: CellValue::Put(b"v".to_vec()),
+        };
+        SSTable::create(cf_dir.join("0000000001.sst"), &[entry]).unwrap();
+        Manifest::open(&cf_dir).unwrap();
+        assert!(migrate(dir.path()).unwrap().is_empty());
+        assert!(!cf_dir.join(MIGRATION_BACKUP_DIR).exists());
+    }
+}
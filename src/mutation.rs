@@ -0,0 +1,216 @@
+//! Row mutations spanning several column families of a table (`RowMutation`), committed
+//! through the table's mutation log.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{Error, ErrorKind, Result as IoResult, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::api::{CellValue, Column, Entry, EntryKey, RowKey, Timestamp};
+use crate::clock::wall_clock;
+use crate::storage::Durability;
+
+/// File in the table directory holding the mutation being applied, if any.
+pub const MUTATION_LOG: &str = "mutations.log";
+
+/// Puts and deletes of one row across several column families of a table, applied
+/// together by `Table::mutate_row`.
+///
+/// Every column family has its own WAL, so a mutation is first logged as a whole to the
+/// table's mutation log and synced; that is its commit point. Its part in each
+/// column family is then appended with the mutation's ID as request ID (see
+/// `Batch::set_request_id`), and the log is removed. If the process dies in between,
+/// the logged mutation is applied again when the table is opened, or before the next
+/// mutation: the column families that already have their part skip it by its ID. A
+/// mutation whose log record was cut short was never committed and is dropped.
+///
+/// Mutations are atomic across crashes but not isolated: a read made while one is
+/// applied may see it in some column families only. The mutations of a table are
+/// logged and applied one at a time, and only span plain column families.
+#[derive(Debug, Clone)]
+pub struct RowMutation {
+    row: RowKey,
+    operations: Vec<(String, Column, CellValue)>,
+}
+
+impl RowMutation {
+    pub fn new(row: RowKey) -> Self {
+        Self { row, operations: Vec::new() }
+    }
+
+    pub fn row(&self) -> &RowKey {
+        &self.row
+    }
+
+    pub fn put(&mut self, cf_name: &str, column: Column, value: Vec<u8>) -> &mut Self {
+        self.operations.push((cf_name.to_string(), column, CellValue::Put(value)));
+        self
+    }
+
+    pub fn delete(&mut self, cf_name: &str, column: Column) -> &mut Self {
+        self.delete_with_ttl(cf_name, column, None)
+    }
+
+    pub fn delete_with_ttl(&mut self, cf_name: &str, column: Column, ttl_ms: Option<u64>) -> &mut Self {
+        self.operations.push((cf_name.to_string(), column, CellValue::Delete(ttl_ms)));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// The operations as entries written at timestamp, by column family, in order.
+    pub(crate) fn parts(&self, timestamp: Timestamp) -> BTreeMap<String, Vec<Entry>> {
+        let mut parts: BTreeMap<String, Vec<Entry>> = BTreeMap::new();
+        for (cf_name, column, value) in &self.operations {
+            parts.entry(cf_name.clone()).or_default().push(Entry {
+                key: EntryKey { row: self.row.clone(), column: column.clone(), timestamp, seq: 0 },
+                value: value.clone(),
+            });
+        }
+        parts
+    }
+}
+
+/// A committed mutation: its ID and its entries in each column family.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct LoggedMutation {
+    pub id: String,
+    pub parts: BTreeMap<String, Vec<Entry>>,
+}
+
+impl LoggedMutation {
+    /// Give parts a fresh ID, unique across restarts.
+    pub fn new(parts: BTreeMap<String, Vec<Entry>>) -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let id = format!("row-mutation-{}-{}", wall_clock(), COUNTER.fetch_add(1, Ordering::Relaxed));
+        Self { id, parts }
+    }
+}
+
+/// The mutation log of a table: a length-prefixed, binary-encoded `LoggedMutation`
+/// while one is being applied, no file otherwise.
+#[derive(Debug)]
+pub(crate) struct MutationLog {
+    path: PathBuf,
+}
+
+impl MutationLog {
+    pub fn new(table_path: &Path) -> Self {
+        Self { path: table_path.join(MUTATION_LOG) }
+    }
+
+    /// The mutation committed but maybe not applied, if any.
+    pub fn pending(&self) -> IoResult<Option<LoggedMutation>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let data = fs::read(&self.path)?;
+        let record = data.get(..4)
+            .map(|len| u32::from_be_bytes(len.try_into().unwrap()) as usize)
+            .and_then(|len| data.get(4..4 + len));
+        match record.map(bincode::deserialize::<LoggedMutation>) {
+            Some(Ok(mutation)) => Ok(Some(mutation)),
+            _ => {
+                warn!(log = %self.path.display(), bytes = data.len(), "dropping a row mutation that was not completely logged");
+                self.clear()?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Log mutation and sync it, committing it.
+    pub fn commit(&self, mutation: &LoggedMutation) -> IoResult<()> {
+        let buf = bincode::serialize(mutation).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let mut file = File::create(&self.path)?;
+        file.write_all(&(buf.len() as u32).to_be_bytes())?;
+        file.write_all(&buf)?;
+        file.sync_all()?;
+        Durability::Sync.sync_dir(self.path.parent().unwrap())
+    }
+
+    /// Remove the log once its mutation is applied.
+    pub fn clear(&self) -> IoResult<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_mutation_log() {
+        let dir = tempdir().unwrap();
+        let log = MutationLog::new(dir.path());
+        assert!(log.pending().unwrap().is_none());
+
+        let mut mutation = RowMutation::new(b"row1".to_vec());
+        mutation.put("a", b"col1".to_vec(), b"v".to_vec())
+            .delete("b", b"col1".to_vec())
+            .put("a", b"col2".to_vec(), b"w".to_vec());
+        let parts = mutation.parts(42);
+        assert_eq!(parts.keys().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(parts["a"].len(), 2);
+        assert!(parts.values().flatten().all(|entry| entry.key.timestamp == 42 && entry.key.row == b"row1"));
+
+        let logged = LoggedMutation::new(parts);
+        assert_ne!(logged.id, LoggedMutation::new(BTreeMap::new()).id);
+        log.commit(&logged).unwrap();
+        let pending = log.pending().unwrap().unwrap();
+        assert_eq!(pending.id, logged.id);
+        assert_eq!(pending.parts["a"][1].value, CellValue::Put(b"w".to_vec()));
+        log.clear().unwrap();
+        assert!(log.pending().unwrap().is_none());
+        log.clear().unwrap();
+
+        // A record cut short was never committed
+        log.commit(&logged).unwrap();
+        let path = dir.path().join(MUTATION_LOG);
+        let len = fs::metadata(&path).unwrap().len();
+        File::options().write(true).open(&path).unwrap().set_len(len - 3).unwrap();
+        assert!(log.pending().unwrap().is_none());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_logged_mutation_is_completed_on_open() {
+        use crate::api::Table;
+
+        let dir = tempdir().unwrap();
+        {
+            let table = Table::open(dir.path()).unwrap();
+            table.create_cf("a").unwrap();
+            table.create_cf("b").unwrap();
+
+            // A crash after the mutation was logged and applied to "a" only
+            let mut mutation = RowMutation::new(b"row1".to_vec());
+            mutation.put("a", b"col1".to_vec(), b"v".to_vec()).put("b", b"col1".to_vec(), b"w".to_vec());
+            let mut logged = LoggedMutation::new(mutation.parts(table.cf("a").unwrap().now()));
+            MutationLog::new(dir.path()).commit(&logged).unwrap();
+            let part = logged.parts.remove("a").unwrap();
+            table.cf("a").unwrap().apply_mutation(part, logged.id.clone()).unwrap();
+            assert_eq!(table.cf("b").unwrap().get(b"row1", b"col1").unwrap(), None);
+        }
+
+        let table = Table::open(dir.path()).unwrap();
+        assert!(!dir.path().join(MUTATION_LOG).exists());
+        assert_eq!(table.cf("a").unwrap().get_versions(b"row1", b"col1", 10).unwrap().len(), 1);
+        assert_eq!(table.cf("b").unwrap().get(b"row1", b"col1").unwrap(), Some(b"w".to_vec()));
+    }
+}
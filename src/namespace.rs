@@ -0,0 +1,213 @@
+//! Namespaces grouping tables, like HBase namespaces.
+//!
+//! A namespace is a directory under a root directory, marked by its `namespace.json`
+//! descriptor, with one table directory per table. This gives multi-tenant layouts a
+//! level above `Table`: tenants get their own namespace and can name their tables freely.
+//! The `default` namespace is created on first use.
+
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result as IoResult},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument};
+
+use crate::api::Table;
+
+/// Namespace that always exists, created on first use.
+pub const DEFAULT_NAMESPACE: &str = "default";
+/// Descriptor file marking a namespace directory.
+const NAMESPACE_FILE: &str = "namespace.json";
+
+/// Describes a namespace; stored in its directory.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NamespaceDescriptor {
+    pub name: String,
+    /// Creation time in milliseconds since the epoch
+    pub created_at: u64,
+}
+
+/// Check that name can be used as a namespace or table name: ASCII letters, digits,
+/// '_', '-' and '.', not starting with '.'.
+pub(crate) fn validate_name(kind: &str, name: &str) -> IoResult<()> {
+    let valid_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.');
+    if name.is_empty() || name.starts_with('.') || !name.chars().all(valid_char) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid {} name: {:?}", kind, name),
+        ));
+    }
+    Ok(())
+}
+
+/// Create the namespace name under root. Fails with `AlreadyExists` if it exists.
+#[instrument(level = "debug", skip(root), fields(root = %root.as_ref().display()))]
+pub fn create_namespace(root: impl AsRef<Path>, name: &str) -> IoResult<Namespace> {
+    validate_name("namespace", name)?;
+    let path = root.as_ref().join(name);
+    if path.join(NAMESPACE_FILE).exists() {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!("Namespace {} already exists", name),
+        ));
+    }
+
+    fs::create_dir_all(&path)?;
+    let descriptor = NamespaceDescriptor {
+        name: name.to_string(),
+        created_at: chrono::Utc::now().timestamp_millis() as u64,
+    };
+    let json = serde_json::to_vec_pretty(&descriptor).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    // The descriptor is written last: a directory without one is not a namespace
+    let tmp = path.join(NAMESPACE_FILE).with_extension("tmp");
+    fs::write(&tmp, json)?;
+    fs::rename(&tmp, path.join(NAMESPACE_FILE))?;
+
+    info!(namespace = name, "created namespace");
+    Ok(Namespace { name: name.to_string(), path })
+}
+
+/// Names of the namespaces under root, in sorted order.
+pub fn list_namespaces(root: impl AsRef<Path>) -> IoResult<Vec<String>> {
+    let root = root.as_ref();
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+    let mut names = Vec::new();
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        if entry.path().join(NAMESPACE_FILE).exists() {
+            names.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Names of the tables in namespace ns under root, in sorted order.
+pub fn list_tables(root: impl AsRef<Path>, ns: &str) -> IoResult<Vec<String>> {
+    Namespace::open(root, ns)?.list_tables()
+}
+
+/// A namespace: a directory of tables.
+#[derive(Debug, Clone)]
+pub struct Namespace {
+    name: String,
+    path: PathBuf,
+}
+
+impl Namespace {
+    /// Open the namespace name under root. Fails with `NotFound` if it does not exist,
+    /// except for the default namespace, which is created.
+    pub fn open(root: impl AsRef<Path>, name: &str) -> IoResult<Self> {
+        validate_name("namespace", name)?;
+        let path = root.as_ref().join(name);
+        if path.join(NAMESPACE_FILE).exists() {
+            return Ok(Self { name: name.to_string(), path });
+        }
+        if name == DEFAULT_NAMESPACE {
+            return create_namespace(root, name);
+        }
+        Err(Error::new(ErrorKind::NotFound, format!("Namespace not found: {}", name)))
+    }
+
+    /// Name of this namespace.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Path of the namespace directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read the descriptor of this namespace.
+    pub fn descriptor(&self) -> IoResult<NamespaceDescriptor> {
+        let json = fs::read(self.path.join(NAMESPACE_FILE))?;
+        serde_json::from_slice(&json).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Names of the tables in this namespace, in sorted order.
+    pub fn list_tables(&self) -> IoResult<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.path)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                names.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Path of the table named name in this namespace, whether or not it exists.
+    pub fn table_path(&self, name: &str) -> IoResult<PathBuf> {
+        validate_name("table", name)?;
+        Ok(self.path.join(name))
+    }
+
+    /// Whether a table named name exists in this namespace.
+    pub fn has_table(&self, name: &str) -> IoResult<bool> {
+        Ok(self.table_path(name)?.is_dir())
+    }
+
+    /// Create the table name in this namespace. Fails with `AlreadyExists` if it exists.
+    #[instrument(level = "debug", skip(self), fields(namespace = %self.name))]
+    pub fn create_table(&self, name: &str) -> IoResult<Table> {
+        let path = self.table_path(name)?;
+        fs::create_dir(&path).map_err(|e| match e.kind() {
+            ErrorKind::AlreadyExists => Error::new(
+                ErrorKind::AlreadyExists,
+                format!("Table {}:{} already exists", self.name, name),
+            ),
+            _ => e,
+        })?;
+        info!(table = name, "created table");
+        Table::open(path)
+    }
+
+    /// Open the existing table name in this namespace. Fails with `NotFound` if it does not exist.
+    pub fn table(&self, name: &str) -> IoResult<Table> {
+        if !self.has_table(name)? {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Table not found: {}:{}", self.name, name),
+            ));
+        }
+        Table::open(self.table_path(name)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_namespaces_and_tables() {
+        let dir = tempdir().unwrap();
+        assert!(list_namespaces(dir.path()).unwrap().is_empty());
+
+        let tenant = create_namespace(dir.path(), "tenant-a").unwrap();
+        assert_eq!(tenant.descriptor().unwrap().name, "tenant-a");
+        assert_eq!(create_namespace(dir.path(), "tenant-a").unwrap_err().kind(), ErrorKind::AlreadyExists);
+        assert_eq!(create_namespace(dir.path(), "../escape").unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(Namespace::open(dir.path(), "missing").unwrap_err().kind(), ErrorKind::NotFound);
+
+        let table = tenant.create_table("users").unwrap();
+        table.create_cf("profile").unwrap();
+        assert_eq!(tenant.create_table("users").err().unwrap().kind(), ErrorKind::AlreadyExists);
+        assert!(tenant.table("users").unwrap().cf("profile").is_some());
+        assert_eq!(tenant.table("orders").err().unwrap().kind(), ErrorKind::NotFound);
+
+        // The default namespace exists on first use
+        let default = Namespace::open(dir.path(), DEFAULT_NAMESPACE).unwrap();
+        default.create_table("users").unwrap();
+
+        assert_eq!(list_namespaces(dir.path()).unwrap(), vec!["default", "tenant-a"]);
+        assert_eq!(list_tables(dir.path(), "tenant-a").unwrap(), vec!["users"]);
+        assert_eq!(list_tables(dir.path(), DEFAULT_NAMESPACE).unwrap(), vec!["users"]);
+    }
+}
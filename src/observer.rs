@@ -0,0 +1,48 @@
+//! Observer hooks on the operations of a column family, like HBase's coprocessors.
+//!
+//! Validators check single values; an `Observer` sees whole operations and can run
+//! arbitrary code inside the database: reject writes that break a cross-column rule,
+//! audit who reads which ranges, or keep a secondary index in another column family up
+//! to date. Observers are registered on a column family with
+//! `ColumnFamily::add_observer` and are called in registration order.
+//!
+//! `pre_*` hooks run before anything is done and can refuse the operation by returning
+//! an error, which the caller receives unchanged. `post_*` hooks run once the operation
+//! is done, outside the column family's locks, so they may read and write it (or other
+//! column families) themselves; they cannot fail it anymore.
+
+use std::io::Result as IoResult;
+
+use crate::api::{ColumnFamily, CompactionRecord, Entry};
+
+/// Hooks called around the operations of a column family. Every hook does nothing by
+/// default, so an observer only implements the ones it needs.
+///
+/// Puts and deletes are observed on every path that validates them: single writes,
+/// `Put`s, batches, the async write queue and replicated entries. Bulk loads, ingested
+/// and imported files, range deletes and snapshot restores bypass them.
+pub trait Observer: Send + Sync {
+    /// Called for each Put before it is written. An error rejects the whole write,
+    /// together with the other entries of its batch.
+    fn pre_put(&self, _cf: &ColumnFamily, _entry: &Entry) -> IoResult<()> {
+        Ok(())
+    }
+
+    /// Called for each Put once it is in the WAL and MemStore.
+    fn post_put(&self, _cf: &ColumnFamily, _entry: &Entry) {}
+
+    /// Called for each delete marker before it is written. An error rejects the whole
+    /// write, together with the other entries of its batch.
+    fn pre_delete(&self, _cf: &ColumnFamily, _entry: &Entry) -> IoResult<()> {
+        Ok(())
+    }
+
+    /// Called before a range scan of [start_row, end_row] (`scanner`, `scan_with_options`,
+    /// `scan_with_filter` and `par_scan_with_filter`). An error fails the scan.
+    fn pre_scan(&self, _cf: &ColumnFamily, _start_row: &[u8], _end_row: &[u8]) -> IoResult<()> {
+        Ok(())
+    }
+
+    /// Called after a compaction or small-file merge has replaced its input SSTables.
+    fn post_compact(&self, _cf: &ColumnFamily, _record: &CompactionRecord) {}
+}
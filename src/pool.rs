@@ -1,13 +1,476 @@
 use std::{
-    io::Result as IoResult,
+    collections::HashMap,
+    fmt,
+    fs,
+    io::{Error, ErrorKind, Result as IoResult},
     path::{Path, PathBuf},
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
 };
-use deadpool::managed::{Manager, Object, Pool, PoolError};
+use deadpool::managed::{Hook, HookError, HookErrorCause, Manager, Metrics, Object, Pool, PoolError, RecycleError, TimeoutType};
 use async_trait::async_trait;
+use serde::Serialize;
+use tokio::sync::Notify;
+use tracing::{info, warn};
 
 use crate::api::Table as SyncTable;
 use crate::async_api::Table as AsyncTable;
+use crate::namespace::validate_name;
+
+/// Sequence numbers given to the writes made through a pool, used as read-your-writes tokens.
+///
+/// Each connection opens its own handle on the table, which only sees the writes made
+/// through other connections once it is reopened. A write is given the next sequence
+/// number after it succeeds, so every number up to `last()` belongs to a finished write.
+/// Numbers start from the clock in microseconds, so tokens handed out before a restart
+/// are already visible afterwards.
+pub struct CommitSequence {
+    last: AtomicU64,
+    committed: Notify,
+}
+
+impl CommitSequence {
+    /// Create a sequence starting from the current time.
+    pub fn new() -> Self {
+        Self {
+            last: AtomicU64::new(chrono::Utc::now().timestamp_micros() as u64),
+            committed: Notify::new(),
+        }
+    }
+
+    /// The newest sequence number handed out.
+    pub fn last(&self) -> u64 {
+        self.last.load(Ordering::SeqCst)
+    }
+
+    /// Record a finished write and return its sequence number.
+    pub fn commit(&self) -> u64 {
+        let seq = self.last.fetch_add(1, Ordering::SeqCst) + 1;
+        self.committed.notify_waiters();
+        seq
+    }
+
+    /// Wait up to timeout until seq has been handed out. Returns whether it was.
+    pub async fn wait_for(&self, seq: u64, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let committed = self.committed.notified();
+            if self.last() >= seq {
+                return true;
+            }
+            if tokio::time::timeout_at(deadline, committed).await.is_err() {
+                return self.last() >= seq;
+            }
+        }
+    }
+}
+
+impl Default for CommitSequence {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// When a pool's circuit breaker trips and how long it stays open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive connection or IO failures that open the circuit
+    pub failure_threshold: u32,
+    /// How long an open circuit fails fast before letting requests through again
+    pub cool_down: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cool_down: Duration::from_secs(30),
+        }
+    }
+}
+
+/// How long a pool keeps its connections, and how long and how often `get` tries to
+/// obtain one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// Connections unused for longer are closed instead of reused
+    pub max_idle: Option<Duration>,
+    /// Connections opened longer ago are closed instead of reused
+    pub max_age: Option<Duration>,
+    /// How long `get` waits for a connection, including opening it, before failing with
+    /// a wait timeout
+    pub wait_timeout: Option<Duration>,
+    /// Requests that may wait for a connection of one table once all are in use; `get`
+    /// fails with `PoolExhausted` beyond that
+    pub max_waiting: Option<usize>,
+    /// Times `get` retries opening a connection after a transient error
+    pub retries: u32,
+    /// Wait before the first retry, doubled before each further one
+    pub retry_backoff: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_idle: Some(Duration::from_secs(5 * 60)),
+            max_age: Some(Duration::from_secs(60 * 60)),
+            wait_timeout: Some(Duration::from_secs(10)),
+            max_waiting: Some(64),
+            retries: 2,
+            retry_backoff: Duration::from_millis(50),
+        }
+    }
+}
+
+impl PoolConfig {
+    /// Why a connection opened at created and last handed out at last_used must be
+    /// closed, if it must.
+    fn expired(&self, created: Instant, last_used: Instant) -> Option<Eviction> {
+        if self.max_age.is_some_and(|max_age| created.elapsed() > max_age) {
+            Some(Eviction::Age)
+        } else if self.max_idle.is_some_and(|max_idle| last_used.elapsed() > max_idle) {
+            Some(Eviction::Idle)
+        } else {
+            None
+        }
+    }
+}
+
+/// Why the pooled connection described by metrics must be closed, if it must. Its idle
+/// time counts from when it was last handed out.
+fn expired(config: &PoolConfig, metrics: &Metrics) -> Option<Eviction> {
+    config.expired(metrics.created, metrics.recycled.unwrap_or(metrics.created))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Eviction {
+    Idle,
+    Age,
+}
+
+/// Counters of the connections a pool opened, reused and closed, for metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct PoolUsage {
+    /// Connections opened
+    pub created: u64,
+    /// Connections that passed their checks and were handed out again
+    pub reused: u64,
+    /// Connections closed after sitting unused for longer than `max_idle`
+    pub evicted_idle: u64,
+    /// Connections closed for being older than `max_age`
+    pub evicted_age: u64,
+    /// Connections closed because their table directory was gone or could not be reopened
+    pub failed_checks: u64,
+    /// Requests that gave up waiting for a connection after `wait_timeout`
+    pub wait_timeouts: u64,
+    /// Requests turned away because `max_waiting` requests were already waiting
+    pub queue_rejections: u64,
+    /// Retries of opening a connection after a transient error
+    pub retries: u64,
+}
+
+#[derive(Default)]
+struct UsageCounters {
+    created: AtomicU64,
+    reused: AtomicU64,
+    evicted_idle: AtomicU64,
+    evicted_age: AtomicU64,
+    failed_checks: AtomicU64,
+    wait_timeouts: AtomicU64,
+    queue_rejections: AtomicU64,
+    retries: AtomicU64,
+}
+
+impl UsageCounters {
+    fn evicted(&self, eviction: Eviction) {
+        match eviction {
+            Eviction::Idle => &self.evicted_idle,
+            Eviction::Age => &self.evicted_age,
+        }.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn usage(&self) -> PoolUsage {
+        PoolUsage {
+            created: self.created.load(Ordering::Relaxed),
+            reused: self.reused.load(Ordering::Relaxed),
+            evicted_idle: self.evicted_idle.load(Ordering::Relaxed),
+            evicted_age: self.evicted_age.load(Ordering::Relaxed),
+            failed_checks: self.failed_checks.load(Ordering::Relaxed),
+            wait_timeouts: self.wait_timeouts.load(Ordering::Relaxed),
+            queue_rejections: self.queue_rejections.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Connections of the pool of one table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TablePoolStatus {
+    pub table: String,
+    pub max_size: usize,
+    /// Connections open, idle or in use
+    pub size: usize,
+    /// Connections waiting in the pool
+    pub idle: usize,
+    /// Connections handed out
+    pub in_use: usize,
+    /// Requests waiting for a connection
+    pub waiting: usize,
+}
+
+/// State of a circuit breaker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Requests go through
+    Closed,
+    /// Requests fail fast until the cool-down ends
+    Open,
+    /// The cool-down ended; the next outcome closes or reopens the circuit
+    HalfOpen,
+}
+
+/// Counters of a circuit breaker, for metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CircuitBreakerStats {
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    /// Times the circuit opened
+    pub opened: u64,
+    /// Times the cool-down ended and the circuit became half-open
+    pub half_opened: u64,
+    /// Times the circuit closed again after recovering
+    pub closed: u64,
+    /// Requests that failed fast while the circuit was open
+    pub rejected: u64,
+}
+
+/// The error a pool returns while its circuit is open.
+///
+/// It is wrapped in an `std::io::Error` of kind `ResourceBusy`, and can be recovered
+/// with `CircuitOpen::from_io`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitOpen {
+    /// Time left until the circuit becomes half-open
+    pub retry_after: Duration,
+}
+
+impl CircuitOpen {
+    /// The open circuit that caused err, if any.
+    pub fn from_io(err: &Error) -> Option<&CircuitOpen> {
+        err.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Circuit breaker is open after repeated failures; retry in {:.1}s", self.retry_after.as_secs_f64())
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+impl From<CircuitOpen> for Error {
+    fn from(err: CircuitOpen) -> Self {
+        Error::new(ErrorKind::ResourceBusy, err)
+    }
+}
+
+/// The error a pool returns when every connection of a table is in use and
+/// `max_waiting` requests are already waiting for one.
+///
+/// It is wrapped in an `std::io::Error` of kind `ResourceBusy`, and can be recovered
+/// with `PoolExhausted::from_io`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolExhausted {
+    /// Connections of the table, all in use
+    pub max_size: usize,
+    /// Requests waiting for one of them
+    pub waiting: usize,
+}
+
+impl PoolExhausted {
+    /// The exhausted pool that caused err, if any.
+    pub fn from_io(err: &Error) -> Option<&PoolExhausted> {
+        err.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for PoolExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "All {} connections are in use and {} requests are waiting", self.max_size, self.waiting)
+    }
+}
+
+impl std::error::Error for PoolExhausted {}
+
+impl From<PoolExhausted> for Error {
+    fn from(err: PoolExhausted) -> Self {
+        Error::new(ErrorKind::ResourceBusy, err)
+    }
+}
+
+struct BreakerInner {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+/// Fails requests fast after repeated connection or IO failures (e.g. a full disk), so
+/// that clients retrying during an outage do not pile more work onto a failing store.
+///
+/// After `failure_threshold` consecutive failures the circuit opens and `check` fails for
+/// `cool_down`. Then it becomes half-open and lets requests through: the next success
+/// closes it, the next failure opens it again.
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    inner: Mutex<BreakerInner>,
+    opened: AtomicU64,
+    half_opened: AtomicU64,
+    closed: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl CircuitBreaker {
+    /// Create a closed circuit breaker.
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            inner: Mutex::new(BreakerInner {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+            }),
+            opened: AtomicU64::new(0),
+            half_opened: AtomicU64::new(0),
+            closed: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// The configuration of this breaker.
+    pub fn config(&self) -> CircuitBreakerConfig {
+        self.config
+    }
+
+    /// Current state, ending the cool-down if it is over.
+    pub fn state(&self) -> CircuitState {
+        let mut inner = self.inner.lock().unwrap();
+        self.end_cool_down(&mut inner);
+        inner.state
+    }
+
+    /// Fail with `CircuitOpen` while the circuit is open.
+    pub fn check(&self) -> IoResult<()> {
+        let mut inner = self.inner.lock().unwrap();
+        self.end_cool_down(&mut inner);
+        match (inner.state, inner.opened_at) {
+            (CircuitState::Open, Some(opened_at)) => {
+                self.rejected.fetch_add(1, Ordering::Relaxed);
+                let retry_after = self.config.cool_down.saturating_sub(opened_at.elapsed());
+                Err(CircuitOpen { retry_after }.into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Record a successful request, closing a half-open circuit.
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures = 0;
+        if inner.state != CircuitState::Closed {
+            inner.state = CircuitState::Closed;
+            inner.opened_at = None;
+            self.closed.fetch_add(1, Ordering::Relaxed);
+            info!("pool circuit breaker closed");
+        }
+    }
+
+    /// Record a failed request, opening the circuit once the threshold is reached.
+    pub fn record_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+        let trips = match inner.state {
+            CircuitState::Closed => inner.consecutive_failures >= self.config.failure_threshold,
+            CircuitState::HalfOpen => true,
+            CircuitState::Open => false,
+        };
+        if trips {
+            inner.state = CircuitState::Open;
+            inner.opened_at = Some(Instant::now());
+            self.opened.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                failures = inner.consecutive_failures,
+                cool_down_ms = self.config.cool_down.as_millis() as u64,
+                "pool circuit breaker opened"
+            );
+        }
+    }
+
+    /// Record the outcome of a request. Errors caused by the request itself (invalid
+    /// input, missing column families, frozen column families, ...) are not failures.
+    pub fn record<T>(&self, result: &IoResult<T>) {
+        match result {
+            Ok(_) => self.record_success(),
+            Err(err) => self.record_error(err),
+        }
+    }
+
+    /// Record a failed request, unless err was caused by the request itself.
+    pub fn record_error(&self, err: &Error) {
+        if is_outage(err) {
+            self.record_failure();
+        }
+    }
+
+    /// Current state and transition counters.
+    pub fn stats(&self) -> CircuitBreakerStats {
+        let mut inner = self.inner.lock().unwrap();
+        self.end_cool_down(&mut inner);
+        CircuitBreakerStats {
+            state: inner.state,
+            consecutive_failures: inner.consecutive_failures,
+            opened: self.opened.load(Ordering::Relaxed),
+            half_opened: self.half_opened.load(Ordering::Relaxed),
+            closed: self.closed.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+
+    fn end_cool_down(&self, inner: &mut BreakerInner) {
+        if inner.state == CircuitState::Open
+            && inner.opened_at.is_some_and(|opened_at| opened_at.elapsed() >= self.config.cool_down)
+        {
+            inner.state = CircuitState::HalfOpen;
+            self.half_opened.fetch_add(1, Ordering::Relaxed);
+            info!("pool circuit breaker half-open");
+        }
+    }
+}
+
+/// Whether opening a connection may succeed if simply tried again.
+fn is_transient(err: &Error) -> bool {
+    matches!(err.kind(), ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::TimedOut)
+        || (err.kind() == ErrorKind::ResourceBusy
+            && CircuitOpen::from_io(err).is_none()
+            && PoolExhausted::from_io(err).is_none())
+}
+
+/// Whether err points at a failing store rather than at a bad request.
+fn is_outage(err: &Error) -> bool {
+    !matches!(
+        err.kind(),
+        ErrorKind::InvalidInput
+            | ErrorKind::NotFound
+            | ErrorKind::AlreadyExists
+            | ErrorKind::PermissionDenied
+            | ErrorKind::Unsupported
+            | ErrorKind::ResourceBusy
+    )
+}
 
 /// A connection to a RedBase table
 #[derive(Clone)]
@@ -16,19 +479,54 @@ pub struct Connection {
     pub path: PathBuf,
     /// The async table handle
     pub table: AsyncTable,
+    /// Writes with sequence numbers up to this one are visible through this connection
+    pub visible_seq: u64,
 }
 
-/// A manager for RedBase connections
+impl Connection {
+    /// Reopen the table handle so that it sees every write committed so far.
+    pub async fn refresh(&mut self, commits: &CommitSequence) -> IoResult<()> {
+        let visible_seq = commits.last();
+        self.table = AsyncTable::open(&self.path).await?;
+        self.visible_seq = visible_seq;
+        Ok(())
+    }
+
+    /// Make the write with sequence number seq visible through this connection, waiting
+    /// up to timeout for it to be committed. Returns false if it was not committed in time.
+    pub async fn await_seq(&mut self, commits: &CommitSequence, seq: u64, timeout: Duration) -> IoResult<bool> {
+        if self.visible_seq >= seq {
+            return Ok(true);
+        }
+        if !commits.wait_for(seq, timeout).await {
+            return Ok(false);
+        }
+        self.refresh(commits).await?;
+        Ok(true)
+    }
+}
+
+/// A manager for RedBase connections to one table
 pub struct ConnectionManager {
-    /// The base directory for tables
-    base_dir: PathBuf,
+    /// The directory of the table
+    table_dir: PathBuf,
+    /// Sequence numbers of the writes made through the pool
+    commits: Arc<CommitSequence>,
+    counters: Arc<UsageCounters>,
 }
 
 impl ConnectionManager {
-    /// Create a new connection manager with the given base directory
-    pub fn new<P: AsRef<Path>>(base_dir: P) -> Self {
+    /// Create a new connection manager for the table in the given directory
+    pub fn new<P: AsRef<Path>>(table_dir: P) -> Self {
+        Self::with_commits(table_dir, Arc::new(CommitSequence::new()), Arc::default())
+    }
+
+    /// Create a connection manager whose writes are numbered by commits
+    fn with_commits<P: AsRef<Path>>(table_dir: P, commits: Arc<CommitSequence>, counters: Arc<UsageCounters>) -> Self {
         Self {
-            base_dir: base_dir.as_ref().to_path_buf(),
+            table_dir: table_dir.as_ref().to_path_buf(),
+            commits,
+            counters,
         }
     }
 }
@@ -39,43 +537,236 @@ impl Manager for ConnectionManager {
     type Error = std::io::Error;
 
     async fn create(&self) -> Result<Connection, Self::Error> {
-        let table_path = self.base_dir.clone();
+        let table_path = self.table_dir.clone();
+        let visible_seq = self.commits.last();
         let table = AsyncTable::open(&table_path).await?;
+        self.counters.created.fetch_add(1, Ordering::Relaxed);
 
         Ok(Connection {
             path: table_path,
             table,
+            visible_seq,
         })
     }
 
-    async fn recycle(&self, conn: &mut Connection) -> Result<(), deadpool::managed::RecycleError<Self::Error>> {
-        match AsyncTable::open(&conn.path).await {
-            Ok(_) => Ok(()),
-            Err(e) => Err(deadpool::managed::RecycleError::Backend(e)),
-        }
+    /// Check that the table directory still exists and reopen the table, so that a
+    /// connection taken from the pool sees every finished write. A connection failing
+    /// either is closed and replaced by a new one.
+    async fn recycle(&self, conn: &mut Connection) -> Result<(), RecycleError<Self::Error>> {
+        let result = if conn.path.is_dir() {
+            conn.refresh(&self.commits).await.map_err(RecycleError::Backend)
+        } else {
+            Err(RecycleError::StaticMessage("table directory is gone"))
+        };
+        match &result {
+            Ok(()) => self.counters.reused.fetch_add(1, Ordering::Relaxed),
+            Err(_) => self.counters.failed_checks.fetch_add(1, Ordering::Relaxed),
+        };
+        result
     }
 }
 
-/// A pool of RedBase connections
+/// A pool of RedBase connections to the tables under a base directory.
+///
+/// Each table is the directory of its name under the base directory, created on first
+/// use, and has its own pool of up to `size` connections. The tables share the sequence
+/// numbers of their writes and the circuit breaker.
 pub struct ConnectionPool {
-    pool: Pool<ConnectionManager>,
+    base_dir: PathBuf,
+    size: usize,
+    config: PoolConfig,
+    pools: Mutex<HashMap<String, Pool<ConnectionManager>>>,
+    commits: Arc<CommitSequence>,
+    breaker: CircuitBreaker,
+    counters: Arc<UsageCounters>,
 }
 
 impl ConnectionPool {
     /// Create a new connection pool with the given base directory and size
     pub fn new<P: AsRef<Path>>(base_dir: P, size: usize) -> Self {
-        let manager = ConnectionManager::new(base_dir);
-        let pool = Pool::builder(manager)
-            .max_size(size)
-            .build()
-            .expect("Failed to create connection pool");
+        Self::with_circuit_breaker(base_dir, size, CircuitBreakerConfig::default())
+    }
+
+    /// Create a new connection pool whose circuit breaker uses the given configuration
+    pub fn with_circuit_breaker<P: AsRef<Path>>(base_dir: P, size: usize, breaker: CircuitBreakerConfig) -> Self {
+        Self::with_config(base_dir, size, PoolConfig::default(), breaker)
+    }
+
+    /// Create a new connection pool keeping its connections as config says, and whose
+    /// circuit breaker uses the given configuration
+    pub fn with_config<P: AsRef<Path>>(base_dir: P, size: usize, config: PoolConfig, breaker: CircuitBreakerConfig) -> Self {
+        Self {
+            base_dir: base_dir.as_ref().to_path_buf(),
+            size,
+            config,
+            pools: Mutex::new(HashMap::new()),
+            commits: Arc::new(CommitSequence::new()),
+            breaker: CircuitBreaker::new(breaker),
+            counters: Arc::default(),
+        }
+    }
+
+    /// Get a connection to the table named table from the pool, creating the table if
+    /// it does not exist. Table names follow the rules of namespaces (see `namespace`).
+    ///
+    /// Fails fast with a `CircuitOpen` backend error while the circuit breaker is open,
+    /// and with a `PoolExhausted` backend error while `max_waiting` requests are already
+    /// waiting. Otherwise waits up to `wait_timeout` for a connection, failing with
+    /// `PoolError::Timeout`, and retries transient errors opening one with backoff. A
+    /// failure to open a connection counts towards tripping the circuit breaker.
+    pub async fn get(&self, table: &str) -> Result<Object<ConnectionManager>, PoolError<std::io::Error>> {
+        self.breaker.check().map_err(PoolError::Backend)?;
+        validate_name("table", table).map_err(PoolError::Backend)?;
+        let pool = self.table_pool(table);
+        let mut backoff = self.config.retry_backoff;
+        let mut attempt = 0;
+        loop {
+            let result = self.try_get(&pool).await;
+            match &result {
+                Err(PoolError::Backend(err)) if attempt < self.config.retries && is_transient(err) => {
+                    attempt += 1;
+                    self.counters.retries.fetch_add(1, Ordering::Relaxed);
+                    warn!(table, attempt, error = %err, "retrying pool connection");
+                    tokio::time::sleep(backoff).await;
+                    backoff = backoff.saturating_mul(2);
+                }
+                Err(PoolError::Backend(err)) => {
+                    self.breaker.record_error(err);
+                    return result;
+                }
+                _ => return result,
+            }
+        }
+    }
+
+    /// One attempt of `get`, bounded by the wait queue limit and timeout.
+    async fn try_get(&self, pool: &Pool<ConnectionManager>) -> Result<Object<ConnectionManager>, PoolError<std::io::Error>> {
+        if let Some(max_waiting) = self.config.max_waiting {
+            let status = pool.status();
+            let waiting = (-status.available).max(0) as usize;
+            if status.size >= status.max_size && status.available <= 0 && waiting >= max_waiting {
+                self.counters.queue_rejections.fetch_add(1, Ordering::Relaxed);
+                return Err(PoolError::Backend(PoolExhausted { max_size: status.max_size, waiting }.into()));
+            }
+        }
+        let Some(wait_timeout) = self.config.wait_timeout else {
+            return pool.get().await;
+        };
+        match tokio::time::timeout(wait_timeout, pool.get()).await {
+            Ok(result) => result,
+            Err(_) => {
+                self.counters.wait_timeouts.fetch_add(1, Ordering::Relaxed);
+                Err(PoolError::Timeout(TimeoutType::Wait))
+            }
+        }
+    }
+
+    /// The pool of connections to the table named table.
+    fn table_pool(&self, table: &str) -> Pool<ConnectionManager> {
+        let mut pools = self.pools.lock().unwrap();
+        pools
+            .entry(table.to_string())
+            .or_insert_with(|| {
+                let manager = ConnectionManager::with_commits(self.base_dir.join(table), self.commits.clone(), self.counters.clone());
+                let (config, counters) = (self.config, self.counters.clone());
+                Pool::builder(manager)
+                    .max_size(self.size)
+                    .pre_recycle(Hook::sync_fn(move |_, metrics| match expired(&config, metrics) {
+                        Some(eviction) => {
+                            counters.evicted(eviction);
+                            Err(HookError::Continue(Some(HookErrorCause::StaticMessage("connection expired"))))
+                        }
+                        None => Ok(()),
+                    }))
+                    .build()
+                    .expect("Failed to create connection pool")
+            })
+            .clone()
+    }
+
+    /// Close the idle connections that have outlived `max_idle` or `max_age`, rather than
+    /// waiting for a request to find them. Returns the number closed.
+    pub fn evict_expired(&self) -> usize {
+        let pools: Vec<_> = self.pools.lock().unwrap().values().cloned().collect();
+        let evicted = std::cell::Cell::new(0);
+        for pool in pools {
+            pool.retain(|_, metrics| match expired(&self.config, &metrics) {
+                Some(eviction) => {
+                    self.counters.evicted(eviction);
+                    evicted.set(evicted.get() + 1);
+                    false
+                }
+                None => true,
+            });
+        }
+        evicted.get()
+    }
+
+    /// How the connections of this pool are kept.
+    pub fn config(&self) -> PoolConfig {
+        self.config
+    }
+
+    /// Connections of the pool of every table opened so far, in table name order.
+    pub fn status(&self) -> Vec<TablePoolStatus> {
+        let pools = self.pools.lock().unwrap();
+        let mut status: Vec<_> = pools.iter().map(|(table, pool)| {
+            let status = pool.status();
+            let idle = status.available.max(0) as usize;
+            TablePoolStatus {
+                table: table.clone(),
+                max_size: status.max_size,
+                size: status.size,
+                idle,
+                in_use: status.size.saturating_sub(idle),
+                waiting: (-status.available).max(0) as usize,
+            }
+        }).collect();
+        status.sort_by(|a, b| a.table.cmp(&b.table));
+        status
+    }
+
+    /// Counters of the connections opened, reused and closed by this pool.
+    pub fn usage(&self) -> PoolUsage {
+        self.counters.usage()
+    }
+
+    /// Names of the tables under the base directory, in sorted order.
+    pub fn table_names(&self) -> IoResult<Vec<String>> {
+        if !self.base_dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.base_dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.file_type()?.is_dir() && validate_name("table", &name).is_ok() {
+                names.push(name);
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// The base directory holding the tables.
+    pub fn base_dir(&self) -> &Path {
+        &self.base_dir
+    }
 
-        Self { pool }
+    /// Record the outcome of an operation made through a connection of this pool, so
+    /// that repeated IO failures trip the circuit breaker.
+    pub fn record<T>(&self, result: &IoResult<T>) {
+        self.breaker.record(result)
     }
 
-    /// Get a connection from the pool
-    pub async fn get(&self) -> Result<Object<ConnectionManager>, PoolError<std::io::Error>> {
-        self.pool.get().await
+    /// The circuit breaker guarding this pool.
+    pub fn circuit_breaker(&self) -> &CircuitBreaker {
+        &self.breaker
+    }
+
+    /// Sequence numbers of the writes made through this pool.
+    pub fn commits(&self) -> &CommitSequence {
+        &self.commits
     }
 }
 
@@ -85,6 +776,8 @@ pub struct SyncConnection {
     pub path: PathBuf,
     /// The sync table handle
     pub table: SyncTable,
+    /// When the connection was opened
+    pub created: Instant,
 }
 
 /// A synchronous manager for RedBase connections
@@ -113,51 +806,80 @@ impl SyncConnectionManager {
         Ok(SyncConnection {
             path: table_path,
             table,
+            created: Instant::now(),
         })
     }
 
-    /// Check if a connection is still valid
+    /// Check that a connection is still usable: its table directory exists and the
+    /// table's schema can be read through it.
     pub fn recycle(&self, conn: &mut SyncConnection) -> IoResult<()> {
-        match SyncTable::open(&conn.path) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(e),
+        if !conn.path.is_dir() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Table directory {} is gone", conn.path.display()),
+            ));
         }
+        conn.table.schema().map(|_| ())
     }
 }
 
+/// A connection waiting in a `SyncConnectionPool`.
+struct IdleConnection {
+    conn: SyncConnection,
+    returned: Instant,
+}
+
 /// A simple synchronous connection pool
 pub struct SyncConnectionPool {
     manager: SyncConnectionManager,
-    connections: Arc<Mutex<Vec<SyncConnection>>>,
+    connections: Arc<Mutex<Vec<IdleConnection>>>,
     max_size: usize,
+    config: PoolConfig,
+    counters: UsageCounters,
 }
 
 impl SyncConnectionPool {
     /// Create a new synchronous connection pool with the given base directory and size
     pub fn new<P: AsRef<Path>>(base_dir: P, size: usize) -> Self {
+        Self::with_config(base_dir, size, PoolConfig::default())
+    }
+
+    /// Create a new synchronous connection pool keeping its connections as config says.
+    /// Only `max_idle` and `max_age` apply: `get` opens a new connection rather than wait.
+    pub fn with_config<P: AsRef<Path>>(base_dir: P, size: usize, config: PoolConfig) -> Self {
         let manager = SyncConnectionManager::new(base_dir);
 
         Self {
             manager,
             connections: Arc::new(Mutex::new(Vec::with_capacity(size))),
             max_size: size,
+            config,
+            counters: UsageCounters::default(),
         }
     }
 
-    /// Get a connection from the pool
+    /// Get a connection from the pool, closing the pooled connections that have
+    /// expired or fail their checks on the way, or open a new one.
     pub fn get(&self) -> IoResult<SyncConnection> {
         let mut connections = self.connections.lock().unwrap();
 
-        if let Some(conn) = connections.pop() {
-            if self.manager.recycle(&mut SyncConnection { 
-                path: conn.path.clone(), 
-                table: conn.table.clone() 
-            }).is_ok() {
-                return Ok(conn);
+        while let Some(IdleConnection { mut conn, returned }) = connections.pop() {
+            if let Some(eviction) = self.config.expired(conn.created, returned) {
+                self.counters.evicted(eviction);
+                continue;
             }
+            if self.manager.recycle(&mut conn).is_err() {
+                self.counters.failed_checks.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            self.counters.reused.fetch_add(1, Ordering::Relaxed);
+            return Ok(conn);
         }
+        drop(connections);
 
-        self.manager.create()
+        let conn = self.manager.create()?;
+        self.counters.created.fetch_add(1, Ordering::Relaxed);
+        Ok(conn)
     }
 
     /// Return a connection to the pool
@@ -165,9 +887,34 @@ impl SyncConnectionPool {
         let mut connections = self.connections.lock().unwrap();
 
         if connections.len() < self.max_size {
-            connections.push(conn);
+            connections.push(IdleConnection { conn, returned: Instant::now() });
         }
     }
+
+    /// Close the idle connections that have outlived `max_idle` or `max_age`. Returns
+    /// the number closed.
+    pub fn evict_expired(&self) -> usize {
+        let mut connections = self.connections.lock().unwrap();
+        let before = connections.len();
+        connections.retain(|idle| match self.config.expired(idle.conn.created, idle.returned) {
+            Some(eviction) => {
+                self.counters.evicted(eviction);
+                false
+            }
+            None => true,
+        });
+        before - connections.len()
+    }
+
+    /// Number of connections waiting in the pool.
+    pub fn idle(&self) -> usize {
+        self.connections.lock().unwrap().len()
+    }
+
+    /// Counters of the connections opened, reused and closed by this pool.
+    pub fn usage(&self) -> PoolUsage {
+        self.counters.usage()
+    }
 }
 
 #[cfg(test)]
@@ -182,12 +929,189 @@ mod tests {
 
         let pool = ConnectionPool::new(table_path, 5);
 
-        let conn1 = pool.get().await.unwrap();
+        let conn1 = pool.get("t").await.unwrap();
 
         drop(conn1);
 
-        let _conn2 = pool.get().await.unwrap();
+        let _conn2 = pool.get("t").await.unwrap();
+
+    }
+
+    #[tokio::test]
+    async fn test_connection_pool_tables() {
+        let dir = tempdir().unwrap();
+        let pool = ConnectionPool::new(dir.path(), 2);
+
+        let users = pool.get("users").await.unwrap();
+        users.table.create_cf("profile").await.unwrap();
+        let orders = pool.get("orders").await.unwrap();
+        assert!(orders.table.cf("profile").await.is_none());
+        assert_eq!(orders.path, dir.path().join("orders"));
+        drop((users, orders));
+
+        assert!(pool.get("users").await.unwrap().table.cf("profile").await.is_some());
+        assert_eq!(pool.table_names().unwrap(), vec!["orders", "users"]);
+        let Err(PoolError::Backend(err)) = pool.get("../escape").await else {
+            panic!("expected an invalid table name to be rejected");
+        };
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn test_commit_sequence_wait() {
+        let dir = tempdir().unwrap();
+        let pool = ConnectionPool::new(dir.path(), 2);
+        let mut conn = pool.get("t").await.unwrap();
+        let commits = pool.commits();
+
+        let seq = commits.commit();
+        assert!(conn.visible_seq < seq);
+        assert!(conn.await_seq(commits, seq, Duration::from_millis(10)).await.unwrap());
+        assert_eq!(conn.visible_seq, seq);
+
+        // A sequence number that is never handed out times out
+        assert!(!conn.await_seq(commits, seq + 1, Duration::from_millis(10)).await.unwrap());
+        assert!(!commits.wait_for(seq + 1, Duration::ZERO).await);
+        commits.commit();
+        assert!(commits.wait_for(seq + 1, Duration::ZERO).await);
+    }
 
+    #[tokio::test]
+    async fn test_circuit_breaker() {
+        let dir = tempdir().unwrap();
+        let config = CircuitBreakerConfig { failure_threshold: 2, cool_down: Duration::from_millis(50) };
+        let pool = ConnectionPool::with_circuit_breaker(dir.path(), 2, config);
+        let breaker = pool.circuit_breaker();
+        let disk_full: IoResult<()> = Err(Error::new(ErrorKind::StorageFull, "No space left on device"));
+
+        // Bad requests and interleaved successes do not trip it
+        pool.record(&disk_full);
+        pool.record::<()>(&Err(Error::new(ErrorKind::InvalidInput, "bad value")));
+        pool.record(&Ok(()));
+        pool.record(&disk_full);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        pool.record(&disk_full);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        let Err(PoolError::Backend(err)) = pool.get("t").await else {
+            panic!("expected the open circuit to fail fast");
+        };
+        assert!(CircuitOpen::from_io(&err).unwrap().retry_after <= config.cool_down);
+
+        // After the cool-down a failure reopens it and a success closes it
+        tokio::time::sleep(config.cool_down).await;
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        pool.record(&disk_full);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        tokio::time::sleep(config.cool_down).await;
+        assert!(pool.get("t").await.is_ok());
+        pool.record(&Ok(()));
+
+        let stats = breaker.stats();
+        assert_eq!(stats.state, CircuitState::Closed);
+        assert_eq!((stats.opened, stats.half_opened, stats.closed, stats.rejected), (2, 2, 1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_connection_checks_and_eviction() {
+        let dir = tempdir().unwrap();
+        let config = PoolConfig { max_idle: Some(Duration::from_millis(50)), max_age: None, ..PoolConfig::default() };
+        let pool = ConnectionPool::with_config(dir.path(), 2, config, CircuitBreakerConfig::default());
+
+        let conn = pool.get("t").await.unwrap();
+        let status = &pool.status()[0];
+        assert_eq!((status.table.as_str(), status.size, status.idle, status.in_use), ("t", 1, 0, 1));
+        drop(conn);
+        drop(pool.get("t").await.unwrap());
+        assert_eq!(pool.status()[0].idle, 1);
+        assert_eq!(pool.evict_expired(), 0);
+
+        // Idle connections are closed, in the background or when a request finds them
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert_eq!(pool.evict_expired(), 1);
+        assert_eq!(pool.status()[0].size, 0);
+        drop(pool.get("t").await.unwrap());
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        drop(pool.get("t").await.unwrap());
+
+        // A connection whose table directory is gone is replaced
+        fs::remove_dir_all(dir.path().join("t")).unwrap();
+        drop(pool.get("t").await.unwrap());
+        assert_eq!(
+            pool.usage(),
+            PoolUsage { created: 4, reused: 1, evicted_idle: 2, failed_checks: 1, ..PoolUsage::default() }
+        );
+
+        // Connections older than max_age are replaced however busy they are
+        let config = PoolConfig { max_idle: None, max_age: Some(Duration::from_millis(50)), ..PoolConfig::default() };
+        let pool = ConnectionPool::with_config(dir.path(), 2, config, CircuitBreakerConfig::default());
+        drop(pool.get("t").await.unwrap());
+        drop(pool.get("t").await.unwrap());
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        drop(pool.get("t").await.unwrap());
+        let usage = pool.usage();
+        assert_eq!((usage.created, usage.reused, usage.evicted_age), (2, 1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_get_timeout_and_wait_queue_limit() {
+        let dir = tempdir().unwrap();
+        let config = PoolConfig {
+            wait_timeout: Some(Duration::from_millis(100)),
+            max_waiting: Some(1),
+            ..PoolConfig::default()
+        };
+        let pool = ConnectionPool::with_config(dir.path(), 1, config, CircuitBreakerConfig::default());
+        let held = pool.get("t").await.unwrap();
+
+        // One request waits and times out, the next one finds the queue full
+        let (waited, rejected) = tokio::join!(pool.get("t"), async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            pool.get("t").await
+        });
+        assert!(matches!(waited, Err(PoolError::Timeout(TimeoutType::Wait))));
+        let Err(PoolError::Backend(err)) = rejected else {
+            panic!("expected the full wait queue to turn the request away");
+        };
+        assert_eq!(PoolExhausted::from_io(&err), Some(&PoolExhausted { max_size: 1, waiting: 1 }));
+        let usage = pool.usage();
+        assert_eq!((usage.wait_timeouts, usage.queue_rejections, usage.retries), (1, 1, 0));
+        // Neither is a failure of the store
+        assert_eq!(pool.circuit_breaker().stats().consecutive_failures, 0);
+
+        drop(held);
+        assert!(pool.get("t").await.is_ok());
+
+        assert!(is_transient(&Error::new(ErrorKind::WouldBlock, "locked")));
+        assert!(is_transient(&Error::new(ErrorKind::ResourceBusy, "busy")));
+        assert!(!is_transient(&err));
+        assert!(!is_transient(&CircuitOpen { retry_after: Duration::ZERO }.into()));
+        assert!(!is_transient(&Error::new(ErrorKind::InvalidData, "corrupt")));
+    }
+
+    #[test]
+    fn test_sync_connection_checks_and_eviction() {
+        let dir = tempdir().unwrap();
+        let table_path = dir.path().join("t");
+        let config = PoolConfig { max_idle: Some(Duration::from_millis(50)), max_age: None, ..PoolConfig::default() };
+        let pool = SyncConnectionPool::with_config(&table_path, 2, config);
+
+        pool.put(pool.get().unwrap());
+        pool.put(pool.get().unwrap());
+        std::thread::sleep(Duration::from_millis(80));
+        pool.put(pool.get().unwrap());
+        assert_eq!(pool.idle(), 1);
+
+        fs::remove_dir_all(&table_path).unwrap();
+        let conn = pool.get().unwrap();
+        assert_eq!(
+            pool.usage(),
+            PoolUsage { created: 3, reused: 1, evicted_idle: 1, failed_checks: 1, ..PoolUsage::default() }
+        );
+        pool.put(conn);
+        std::thread::sleep(Duration::from_millis(80));
+        assert_eq!(pool.evict_expired(), 1);
+        assert_eq!(pool.idle(), 0);
     }
 
     #[test]
@@ -197,7 +1121,7 @@ mod tests {
 
         let pool = SyncConnectionPool::new(table_path, 5);
 
-        let mut conn = pool.get().unwrap();
+        let conn = pool.get().unwrap();
 
         conn.table.create_cf("test_cf").unwrap();
 
@@ -0,0 +1,774 @@
+//! A minimal SQL-like query language over a column family: `Query` parses a `SELECT`
+//! and runs it as one filtered scan with aggregations.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    io::{Error, ErrorKind, Result as IoResult},
+    time::Instant,
+};
+
+use crate::aggregation::{AggregationResult, AggregationSet, AggregationType};
+use crate::api::{Column, ColumnFamily, RowKey};
+use crate::filter::Filter;
+use crate::scan::ScanRow;
+
+/// A value in a query result.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryValue {
+    /// The row has no such column, or an aggregate had no values
+    Null,
+    Bytes(Vec<u8>),
+    Integer(i64),
+    Float(f64),
+}
+
+impl fmt::Display for QueryValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryValue::Null => write!(f, "NULL"),
+            QueryValue::Bytes(bytes) => write!(f, "{}", String::from_utf8_lossy(bytes)),
+            QueryValue::Integer(n) => write!(f, "{}", n),
+            QueryValue::Float(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+/// The result of a query: named columns, and rows holding a value for each of them.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<QueryValue>>,
+}
+
+/// An aggregate function of a select list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Aggregate {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "COUNT" => Some(Aggregate::Count),
+            "SUM" => Some(Aggregate::Sum),
+            "AVG" => Some(Aggregate::Avg),
+            "MIN" => Some(Aggregate::Min),
+            "MAX" => Some(Aggregate::Max),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Aggregate::Count => "COUNT",
+            Aggregate::Sum => "SUM",
+            Aggregate::Avg => "AVG",
+            Aggregate::Min => "MIN",
+            Aggregate::Max => "MAX",
+        }
+    }
+
+    fn aggregation_type(self) -> AggregationType {
+        match self {
+            Aggregate::Count => AggregationType::Count,
+            Aggregate::Sum => AggregationType::Sum,
+            Aggregate::Avg => AggregationType::Average,
+            Aggregate::Min => AggregationType::Min,
+            Aggregate::Max => AggregationType::Max,
+        }
+    }
+}
+
+/// An item of a select list.
+#[derive(Debug, Clone, PartialEq)]
+enum SelectItem {
+    /// Every column of the matching rows, after the row key
+    All,
+    Row,
+    Column(Column),
+    /// An aggregate of a column, or of the rows with `None` (`COUNT(*)`)
+    Aggregate(Aggregate, Option<Column>),
+}
+
+impl SelectItem {
+    fn header(&self) -> String {
+        match self {
+            SelectItem::All => "*".to_string(),
+            SelectItem::Row => "row".to_string(),
+            SelectItem::Column(column) => String::from_utf8_lossy(column).into_owned(),
+            SelectItem::Aggregate(aggregate, column) => {
+                let arg = column.as_ref().map_or("*".into(), |c| String::from_utf8_lossy(c));
+                format!("{}({})", aggregate.name(), arg)
+            }
+        }
+    }
+}
+
+/// Comparison of a numeric condition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    fn holds(self, a: f64, b: f64) -> bool {
+        match self {
+            CmpOp::Eq => a == b,
+            CmpOp::Ne => a != b,
+            CmpOp::Lt => a < b,
+            CmpOp::Le => a <= b,
+            CmpOp::Gt => a > b,
+            CmpOp::Ge => a >= b,
+        }
+    }
+
+    fn filter(self, target: Vec<u8>) -> Filter {
+        match self {
+            CmpOp::Eq => Filter::Equal(target),
+            CmpOp::Ne => Filter::NotEqual(target),
+            CmpOp::Lt => Filter::LessThan(target),
+            CmpOp::Le => Filter::LessThanOrEqual(target),
+            CmpOp::Gt => Filter::GreaterThan(target),
+            CmpOp::Ge => Filter::GreaterThanOrEqual(target),
+        }
+    }
+}
+
+/// A predicate on a row key or value.
+#[derive(Debug, Clone)]
+enum Predicate {
+    /// Compared as bytes
+    Filter(Filter),
+    /// Compared as numbers; values that are not numbers never match
+    Numeric(CmpOp, f64),
+}
+
+impl Predicate {
+    fn matches(&self, value: &[u8]) -> bool {
+        match self {
+            Predicate::Filter(filter) => filter.matches(value),
+            Predicate::Numeric(op, target) => std::str::from_utf8(value)
+                .ok()
+                .and_then(|text| text.trim().parse::<f64>().ok())
+                .is_some_and(|number| op.holds(number, *target)),
+        }
+    }
+}
+
+/// A condition of a `WHERE` clause.
+#[derive(Debug, Clone)]
+enum Condition {
+    /// On the row key, always compared as bytes
+    Row(Filter),
+    /// On the newest value of a column
+    Column(Column, Predicate),
+}
+
+/// A parsed query, ready to run against the column family it names.
+///
+/// For example:
+///
+/// ```text
+/// SELECT city, COUNT(*), AVG(age) FROM users
+/// WHERE row BETWEEN 'user000' AND 'user999' AND age >= 18
+/// GROUP BY city LIMIT 10
+/// ```
+///
+/// is compiled to a scan of the rows the row conditions allow, column predicates
+/// (`Filter`s for string literals, numeric comparisons for numbers) evaluated against
+/// the newest value of each column, and one `AggregationSet` per group. The supported
+/// subset:
+///
+/// - `SELECT *`, or a list of columns, `row` (the row key), and `COUNT(*)`,
+///   `COUNT(col)`, `SUM(col)`, `AVG(col)`, `MIN(col)` and `MAX(col)`
+/// - `WHERE` conditions joined with `AND`: `=`, `!=` (or `<>`), `<`, `<=`, `>`, `>=`,
+///   `BETWEEN .. AND ..` and `LIKE` (with `%` only at the ends) on a column or `row`
+/// - `GROUP BY` a single column; `LIMIT` on the rows (or groups) returned
+///
+/// Keywords are case-insensitive; column and column family names are not, and can be
+/// quoted with `"` or `` ` `` if they clash with a keyword or hold other characters.
+/// Rows without a column a condition refers to do not match it.
+#[derive(Debug, Clone)]
+pub struct Query {
+    column_family: String,
+    select: Vec<SelectItem>,
+    conditions: Vec<Condition>,
+    group_by: Option<Column>,
+    limit: Option<usize>,
+}
+
+fn invalid(message: impl fmt::Display) -> Error {
+    Error::new(ErrorKind::InvalidInput, format!("Invalid query: {}", message))
+}
+
+impl Query {
+    /// Parse a query. Fails with `InvalidInput` on a syntax error or an unsupported
+    /// construct.
+    pub fn parse(sql: &str) -> IoResult<Self> {
+        let mut parser = Parser { tokens: tokenize(sql)?, pos: 0 };
+        let query = parser.query()?;
+        query.check()?;
+        Ok(query)
+    }
+
+    /// Name of the column family the query reads.
+    pub fn column_family(&self) -> &str {
+        &self.column_family
+    }
+
+    /// Whether the select list holds aggregates, so the query returns one row per group.
+    fn aggregates(&self) -> bool {
+        self.group_by.is_some() || self.select.iter().any(|item| matches!(item, SelectItem::Aggregate(..)))
+    }
+
+    fn check(&self) -> IoResult<()> {
+        if !self.aggregates() {
+            return Ok(());
+        }
+        for item in &self.select {
+            match item {
+                SelectItem::Aggregate(..) => {}
+                SelectItem::Column(column) if Some(column) == self.group_by.as_ref() => {}
+                SelectItem::All => return Err(invalid("* cannot be selected with aggregates or GROUP BY")),
+                other => return Err(invalid(format!("{} must be aggregated or be the GROUP BY column", other.header()))),
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the query against cf, which should be the column family it names.
+    pub fn execute(&self, cf: &ColumnFamily) -> IoResult<QueryResult> {
+        self.execute_within(cf, None)
+    }
+
+    /// Like `execute`, but give up with `TimedOut` once deadline has passed.
+    pub fn execute_until(&self, cf: &ColumnFamily, deadline: Instant) -> IoResult<QueryResult> {
+        self.execute_within(cf, Some(deadline))
+    }
+
+    fn execute_within(&self, cf: &ColumnFamily, deadline: Option<Instant>) -> IoResult<QueryResult> {
+        let rows = self.matching_rows(cf, deadline)?;
+        if self.aggregates() {
+            self.aggregate(rows)
+        } else {
+            Ok(self.project(rows))
+        }
+    }
+
+    /// Scan bounds implied by the row conditions, or `None` if no row can match.
+    fn scan_bounds(&self, cf: &ColumnFamily) -> IoResult<Option<(RowKey, RowKey)>> {
+        let mut start: RowKey = Vec::new();
+        let mut end: Option<RowKey> = None;
+        for condition in &self.conditions {
+            let Condition::Row(filter) = condition else { continue };
+            let (lower, upper) = match filter {
+                Filter::Equal(key) => (Some(key), Some(key)),
+                Filter::GreaterThan(key) | Filter::GreaterThanOrEqual(key) | Filter::StartsWith(key) => (Some(key), None),
+                Filter::LessThan(key) | Filter::LessThanOrEqual(key) => (None, Some(key)),
+                _ => (None, None),
+            };
+            if let Some(lower) = lower.filter(|lower| **lower > start) {
+                start = lower.clone();
+            }
+            if let Some(upper) = upper.filter(|upper| end.as_ref().is_none_or(|end| *upper < end)) {
+                end = Some(upper.clone());
+            }
+        }
+        let end = match end {
+            Some(end) => end,
+            None => match cf.last_row_key()? {
+                Some(last) => last,
+                None => return Ok(None),
+            },
+        };
+        Ok((start <= end).then_some((start, end)))
+    }
+
+    /// The rows matching every condition, with the newest version of each column.
+    fn matching_rows(&self, cf: &ColumnFamily, deadline: Option<Instant>) -> IoResult<Vec<ScanRow>> {
+        let Some((start, end)) = self.scan_bounds(cf)? else {
+            return Ok(Vec::new());
+        };
+        // Without aggregates, only the first LIMIT rows are needed
+        let wanted = if self.aggregates() { None } else { self.limit };
+        let mut rows = Vec::new();
+        for row in cf.scanner(&start, &end, 1)? {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(Error::new(ErrorKind::TimedOut, "Query exceeded its deadline"));
+            }
+            if wanted.is_some_and(|wanted| rows.len() >= wanted) {
+                break;
+            }
+            let row = row?;
+            let matches = self.conditions.iter().all(|condition| match condition {
+                Condition::Row(filter) => filter.matches(&row.row),
+                Condition::Column(column, predicate) => row.latest(column).is_some_and(|value| predicate.matches(value)),
+            });
+            if matches {
+                rows.push(row);
+            }
+        }
+        Ok(rows)
+    }
+
+    /// One result row per matching row, with the selected columns.
+    fn project(&self, rows: Vec<ScanRow>) -> QueryResult {
+        let mut items = Vec::new();
+        for item in &self.select {
+            if *item == SelectItem::All {
+                let columns: BTreeSet<&Column> = rows.iter().flat_map(|row| row.columns.keys()).collect();
+                items.push(SelectItem::Row);
+                items.extend(columns.into_iter().map(|column| SelectItem::Column(column.clone())));
+            } else {
+                items.push(item.clone());
+            }
+        }
+
+        let values = rows.iter().map(|row| {
+            items.iter().map(|item| match item {
+                SelectItem::Row => QueryValue::Bytes(row.row.clone()),
+                SelectItem::Column(column) => row.latest(column).map_or(QueryValue::Null, |v| QueryValue::Bytes(v.to_vec())),
+                _ => unreachable!("aggregates are not projected"),
+            }).collect()
+        }).collect();
+        QueryResult {
+            columns: items.iter().map(SelectItem::header).collect(),
+            rows: values,
+        }
+    }
+
+    /// One result row per group (a single group without `GROUP BY`), in group order.
+    fn aggregate(&self, rows: Vec<ScanRow>) -> IoResult<QueryResult> {
+        let aggregated: BTreeSet<&Column> = self.select.iter()
+            .filter_map(|item| match item {
+                SelectItem::Aggregate(_, Some(column)) => Some(column),
+                _ => None,
+            })
+            .collect();
+
+        // Rows without the GROUP BY column form the group NULL, first
+        let mut groups: BTreeMap<Option<Vec<u8>>, Group> = BTreeMap::new();
+        if self.group_by.is_none() {
+            groups.insert(None, Group::default());
+        }
+        for row in &rows {
+            let key = self.group_by.as_ref().and_then(|column| row.latest(column)).map(<[u8]>::to_vec);
+            let group = groups.entry(key).or_default();
+            group.rows += 1;
+            for column in &aggregated {
+                if let Some(versions) = row.columns.get(*column) {
+                    group.values.entry((*column).clone()).or_default().push(versions[0].clone());
+                }
+            }
+        }
+
+        let mut result_rows = Vec::new();
+        for (key, group) in groups.into_iter().take(self.limit.unwrap_or(usize::MAX)) {
+            let mut values = Vec::with_capacity(self.select.len());
+            for item in &self.select {
+                values.push(match item {
+                    SelectItem::Column(_) => key.clone().map_or(QueryValue::Null, QueryValue::Bytes),
+                    SelectItem::Aggregate(Aggregate::Count, None) => QueryValue::Integer(group.rows as i64),
+                    SelectItem::Aggregate(aggregate, Some(column)) => group.aggregate(*aggregate, column)?,
+                    _ => unreachable!("checked by Query::check"),
+                });
+            }
+            result_rows.push(values);
+        }
+        Ok(QueryResult {
+            columns: self.select.iter().map(SelectItem::header).collect(),
+            rows: result_rows,
+        })
+    }
+}
+
+/// The rows of a group and the newest values of its aggregated columns.
+#[derive(Default)]
+struct Group {
+    rows: u64,
+    values: BTreeMap<Column, Vec<(u64, Vec<u8>)>>,
+}
+
+impl Group {
+    fn aggregate(&self, aggregate: Aggregate, column: &Column) -> IoResult<QueryValue> {
+        if !self.values.contains_key(column) {
+            return Ok(match aggregate {
+                Aggregate::Count => QueryValue::Integer(0),
+                _ => QueryValue::Null,
+            });
+        }
+        let mut set = AggregationSet::new();
+        set.add_aggregation(column.clone(), aggregate.aggregation_type());
+        let result = set.apply(&self.values).remove(column);
+        Ok(match result {
+            Some(AggregationResult::Count(n)) => QueryValue::Integer(n as i64),
+            Some(AggregationResult::Sum(n)) => QueryValue::Integer(n),
+            Some(AggregationResult::SumFloat(x) | AggregationResult::Average(x)) => QueryValue::Float(x),
+            Some(AggregationResult::Min(v) | AggregationResult::Max(v)) => QueryValue::Bytes(v),
+            Some(AggregationResult::Error(e)) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{}({}) failed: {}", aggregate.name(), String::from_utf8_lossy(column), e),
+                ))
+            }
+            None => QueryValue::Null,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A keyword or unquoted name
+    Word(String),
+    /// A name quoted with `"` or `` ` ``
+    Name(String),
+    /// A string literal, quoted with `'`
+    Str(String),
+    Number(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) | Token::Number(word) => write!(f, "{}", word),
+            Token::Name(name) => write!(f, "\"{}\"", name),
+            Token::Str(s) => write!(f, "'{}'", s),
+            Token::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+const SYMBOLS: &[&str] = &["<=", ">=", "!=", "<>", "=", "<", ">", ",", "(", ")", "*", ";"];
+
+fn tokenize(sql: &str) -> IoResult<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = sql.char_indices().peekable();
+    while let Some(&(i, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '\'' || c == '"' || c == '`' {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    // A doubled quote stands for the quote itself
+                    Some((_, q)) if q == c => {
+                        if chars.peek().is_some_and(|&(_, next)| next == c) {
+                            chars.next();
+                            text.push(c);
+                        } else {
+                            break;
+                        }
+                    }
+                    Some((_, other)) => text.push(other),
+                    None => return Err(invalid(format!("unterminated quote at {}", i))),
+                }
+            }
+            tokens.push(if c == '\'' { Token::Str(text) } else { Token::Name(text) });
+        } else if c.is_ascii_digit() || (c == '-' || c == '.') && sql[i + 1..].starts_with(|n: char| n.is_ascii_digit()) {
+            let mut end = i + c.len_utf8();
+            chars.next();
+            while let Some(&(j, d)) = chars.peek() {
+                if !(d.is_ascii_digit() || d == '.' || d == 'e' || d == 'E') {
+                    break;
+                }
+                end = j + 1;
+                chars.next();
+            }
+            tokens.push(Token::Number(sql[i..end].to_string()));
+        } else if c.is_alphanumeric() || c == '_' {
+            let mut end = i;
+            while let Some(&(j, d)) = chars.peek() {
+                if !(d.is_alphanumeric() || d == '_' || d == '.' || d == '-' || d == ':') {
+                    break;
+                }
+                end = j + d.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Word(sql[i..end].to_string()));
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| sql[i..].starts_with(**symbol)) {
+            for _ in 0..symbol.len() {
+                chars.next();
+            }
+            tokens.push(Token::Symbol(symbol));
+        } else {
+            return Err(invalid(format!("unexpected character '{}' at {}", c, i)));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Words that cannot be used as unquoted names.
+const KEYWORDS: &[&str] = &["SELECT", "FROM", "WHERE", "AND", "BETWEEN", "LIKE", "GROUP", "BY", "LIMIT"];
+
+/// A literal of a condition.
+enum Literal {
+    Str(String),
+    Number(String, f64),
+}
+
+impl Literal {
+    fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Literal::Str(s) | Literal::Number(s, _) => s.into_bytes(),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> IoResult<Token> {
+        let token = self.tokens.get(self.pos).cloned().ok_or_else(|| invalid("unexpected end of query"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    /// Consume keyword if it comes next.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> IoResult<()> {
+        if self.keyword(keyword) {
+            return Ok(());
+        }
+        Err(match self.peek() {
+            Some(token) => invalid(format!("expected {}, found {}", keyword, token)),
+            None => invalid(format!("expected {}", keyword)),
+        })
+    }
+
+    /// Consume symbol if it comes next.
+    fn symbol(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> IoResult<()> {
+        if self.symbol(symbol) {
+            return Ok(());
+        }
+        Err(invalid(format!("expected {}", symbol)))
+    }
+
+    fn name(&mut self) -> IoResult<String> {
+        match self.next()? {
+            Token::Word(word) if !KEYWORDS.iter().any(|k| word.eq_ignore_ascii_case(k)) => Ok(word),
+            Token::Name(name) => Ok(name),
+            other => Err(invalid(format!("expected a name, found {}", other))),
+        }
+    }
+
+    fn literal(&mut self) -> IoResult<Literal> {
+        match self.next()? {
+            Token::Str(s) => Ok(Literal::Str(s)),
+            Token::Number(text) => {
+                let number = text.parse().map_err(|_| invalid(format!("invalid number {}", text)))?;
+                Ok(Literal::Number(text, number))
+            }
+            other => Err(invalid(format!("expected a literal, found {}", other))),
+        }
+    }
+
+    fn query(&mut self) -> IoResult<Query> {
+        self.expect_keyword("SELECT")?;
+        let mut select = vec![self.select_item()?];
+        while self.symbol(",") {
+            select.push(self.select_item()?);
+        }
+        self.expect_keyword("FROM")?;
+        let column_family = self.name()?;
+
+        let mut conditions = Vec::new();
+        if self.keyword("WHERE") {
+            self.condition(&mut conditions)?;
+            while self.keyword("AND") {
+                self.condition(&mut conditions)?;
+            }
+        }
+        let group_by = if self.keyword("GROUP") {
+            self.expect_keyword("BY")?;
+            Some(self.name()?.into_bytes())
+        } else {
+            None
+        };
+        let limit = if self.keyword("LIMIT") {
+            match self.next()? {
+                Token::Number(text) => Some(text.parse().map_err(|_| invalid(format!("invalid LIMIT {}", text)))?),
+                other => return Err(invalid(format!("expected a number after LIMIT, found {}", other))),
+            }
+        } else {
+            None
+        };
+        self.symbol(";");
+        if let Some(token) = self.peek() {
+            return Err(invalid(format!("unexpected {}", token)));
+        }
+        Ok(Query { column_family, select, conditions, group_by, limit })
+    }
+
+    fn select_item(&mut self) -> IoResult<SelectItem> {
+        if self.symbol("*") {
+            return Ok(SelectItem::All);
+        }
+        if let Some(Token::Word(word)) = self.peek() {
+            let word = word.clone();
+            let call = matches!(self.tokens.get(self.pos + 1), Some(Token::Symbol("(")));
+            if let (Some(aggregate), true) = (Aggregate::from_name(&word), call) {
+                self.pos += 2;
+                let column = if self.symbol("*") {
+                    if aggregate != Aggregate::Count {
+                        return Err(invalid(format!("{}(*) is not supported", aggregate.name())));
+                    }
+                    None
+                } else {
+                    Some(self.name()?.into_bytes())
+                };
+                self.expect_symbol(")")?;
+                return Ok(SelectItem::Aggregate(aggregate, column));
+            }
+            if word.eq_ignore_ascii_case("row") {
+                self.pos += 1;
+                return Ok(SelectItem::Row);
+            }
+        }
+        Ok(SelectItem::Column(self.name()?.into_bytes()))
+    }
+
+    /// Parse one condition, adding it (or the two halves of a `BETWEEN`) to conditions.
+    fn condition(&mut self, conditions: &mut Vec<Condition>) -> IoResult<()> {
+        let on_row = self.keyword("row");
+        let column = if on_row { Vec::new() } else { self.name()?.into_bytes() };
+        let condition = |op: CmpOp, literal: Literal| match (on_row, literal) {
+            (true, literal) => Condition::Row(op.filter(literal.into_bytes())),
+            (false, Literal::Number(_, number)) => Condition::Column(column.clone(), Predicate::Numeric(op, number)),
+            (false, Literal::Str(s)) => Condition::Column(column.clone(), Predicate::Filter(op.filter(s.into_bytes()))),
+        };
+
+        if self.keyword("BETWEEN") {
+            let low = self.literal()?;
+            self.expect_keyword("AND")?;
+            let high = self.literal()?;
+            conditions.push(condition(CmpOp::Ge, low));
+            conditions.push(condition(CmpOp::Le, high));
+            return Ok(());
+        }
+        if self.keyword("LIKE") {
+            let Literal::Str(pattern) = self.literal()? else {
+                return Err(invalid("LIKE needs a string pattern"));
+            };
+            let filter = like_filter(&pattern)?;
+            conditions.push(if on_row { Condition::Row(filter) } else { Condition::Column(column, Predicate::Filter(filter)) });
+            return Ok(());
+        }
+        let op = match self.next()? {
+            Token::Symbol("=") => CmpOp::Eq,
+            Token::Symbol("!=") | Token::Symbol("<>") => CmpOp::Ne,
+            Token::Symbol("<") => CmpOp::Lt,
+            Token::Symbol("<=") => CmpOp::Le,
+            Token::Symbol(">") => CmpOp::Gt,
+            Token::Symbol(">=") => CmpOp::Ge,
+            other => return Err(invalid(format!("expected a comparison, found {}", other))),
+        };
+        let literal = self.literal()?;
+        conditions.push(condition(op, literal));
+        Ok(())
+    }
+}
+
+/// The filter of a `LIKE` pattern, which may only have `%` wildcards at its ends.
+fn like_filter(pattern: &str) -> IoResult<Filter> {
+    let leading = pattern.starts_with('%');
+    let trailing = pattern.len() > 1 && pattern.ends_with('%');
+    let inner = &pattern[leading as usize..pattern.len() - trailing as usize];
+    if inner.contains('%') {
+        return Err(invalid(format!("LIKE '{}' has a % inside the pattern", pattern)));
+    }
+    let inner = inner.as_bytes().to_vec();
+    Ok(match (leading, trailing) {
+        (false, false) => Filter::Equal(inner),
+        (false, true) => Filter::StartsWith(inner),
+        (true, false) => Filter::EndsWith(inner),
+        (true, true) => Filter::Contains(inner),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_queries() {
+        let query = Query::parse(
+            "select city, count(*), AVG(age) from users where row between 'a' and 'b' \
+             and age > 10 and name like 'Jo%' group by city limit 5;",
+        ).unwrap();
+        assert_eq!(query.column_family(), "users");
+        assert_eq!(query.select, vec![
+            SelectItem::Column(b"city".to_vec()),
+            SelectItem::Aggregate(Aggregate::Count, None),
+            SelectItem::Aggregate(Aggregate::Avg, Some(b"age".to_vec())),
+        ]);
+        assert_eq!(query.conditions.len(), 4);
+        assert!(matches!(&query.conditions[0], Condition::Row(Filter::GreaterThanOrEqual(k)) if k == b"a"));
+        assert!(matches!(&query.conditions[2], Condition::Column(c, Predicate::Numeric(CmpOp::Gt, n)) if c == b"age" && *n == 10.0));
+        assert!(matches!(&query.conditions[3], Condition::Column(_, Predicate::Filter(Filter::StartsWith(p))) if p == b"Jo"));
+        assert_eq!(query.group_by, Some(b"city".to_vec()));
+        assert_eq!(query.limit, Some(5));
+
+        // Quoted names and escaped quotes
+        let query = Query::parse("SELECT \"from\", row FROM `my-cf` WHERE note = 'it''s'").unwrap();
+        assert_eq!(query.column_family(), "my-cf");
+        assert_eq!(query.select, vec![SelectItem::Column(b"from".to_vec()), SelectItem::Row]);
+        assert!(matches!(&query.conditions[0], Condition::Column(_, Predicate::Filter(Filter::Equal(v))) if v == b"it's"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for sql in [
+            "SELECT FROM users",
+            "SELECT a FROM users WHERE",
+            "SELECT a FROM users WHERE a ~ 1",
+            "SELECT a FROM users LIMIT ten",
+            "SELECT a FROM users extra",
+            "SELECT a, COUNT(*) FROM users",
+            "SELECT *, COUNT(*) FROM users",
+            "SELECT SUM(*) FROM users",
+            "SELECT a FROM users WHERE a LIKE 'x%y'",
+            "SELECT a FROM users WHERE a = 'open",
+        ] {
+            let err = Query::parse(sql).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput, "{}", sql);
+        }
+    }
+}
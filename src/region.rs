@@ -0,0 +1,628 @@
+//! Column families partitioned into regions by row key (`RegionedColumnFamily`), each
+//! region a complete `ColumnFamily` split in two once it grows past a threshold.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{Error, ErrorKind, Result as IoResult},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument, warn};
+
+use crate::api::{ColumnFamily, Get, Put, RangeVersions, RowKey, RowVersions, Timestamp};
+use crate::filter::FilterSet;
+use crate::verify::VerifyReport;
+
+/// Manifest file (inside a regioned column family directory) listing its regions.
+pub const REGIONS_MANIFEST: &str = "regions.json";
+
+/// Configuration of a regioned column family, stored in its manifest.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RegionConfig {
+    /// Split a region once its SSTables exceed this many bytes
+    pub split_threshold_bytes: u64,
+    /// Check whether a region needs splitting after this many writes to it
+    pub split_check_interval: u64,
+    /// Spread rows over this many salt buckets, set when the column family is created.
+    /// Each row key is prefixed with a one-byte bucket hashed from the row, and the
+    /// column family is pre-split at the bucket boundaries, so that monotonically
+    /// increasing row keys do not all land in the last region. Salting is transparent:
+    /// rows are read and written by their own keys, and range scans, counts and range
+    /// deletes fan out to every bucket and merge the results back into row order
+    #[serde(default)]
+    pub salt_buckets: Option<u8>,
+}
+
+impl Default for RegionConfig {
+    fn default() -> Self {
+        Self {
+            split_threshold_bytes: 256 * 1024 * 1024,
+            split_check_interval: 1000,
+            salt_buckets: None,
+        }
+    }
+}
+
+/// Boundaries of a region: rows in [start_row, end_row).
+/// An empty start_row is unbounded below and an empty end_row unbounded above.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RegionInfo {
+    pub id: u64,
+    pub start_row: RowKey,
+    pub end_row: RowKey,
+}
+
+impl RegionInfo {
+    fn dir_name(&self) -> String {
+        format!("region-{:06}", self.id)
+    }
+
+    /// Whether row falls into this region.
+    pub fn contains(&self, row: &[u8]) -> bool {
+        row >= self.start_row.as_slice() && (self.end_row.is_empty() || row < self.end_row.as_slice())
+    }
+
+    /// Whether any row in [start_row, end_row] falls into this region.
+    fn overlaps(&self, start_row: &[u8], end_row: &[u8]) -> bool {
+        end_row >= self.start_row.as_slice() && (self.end_row.is_empty() || start_row < self.end_row.as_slice())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct RegionManifest {
+    config: RegionConfig,
+    next_id: u64,
+    regions: Vec<RegionInfo>,
+}
+
+struct Region {
+    info: RegionInfo,
+    cf: ColumnFamily,
+    writes: AtomicU64,
+}
+
+/// A column family partitioned into regions by row key. Cloning shares the regions.
+///
+/// Each region is a complete `ColumnFamily` (MemStore, WAL and SSTables) in its own
+/// `region-NNNNNN` subdirectory, so writes to different regions do not contend on one
+/// MemStore. The region boundaries are recorded in `regions.json`, which is replaced
+/// atomically and is the commit point of a split. When a region's SSTables grow past the
+/// split threshold, it is split at its middle row into two new regions.
+///
+/// Splits and regions' own compactions run online; while a region is being split, all
+/// writes to the column family wait. Snapshots, changelogs and subscriptions work per
+/// region and are not carried over by a split.
+#[derive(Clone)]
+pub struct RegionedColumnFamily {
+    name: String,
+    path: PathBuf,
+    config: RegionConfig,
+    next_id: Arc<AtomicU64>,
+    /// Sorted by start_row. Writes hold the read lock; splits take the write lock.
+    regions: Arc<RwLock<Vec<Arc<Region>>>>,
+}
+
+impl RegionedColumnFamily {
+    /// Create a regioned column family at table_path/name, pre-split at split_points.
+    pub fn create(table_path: &Path, name: &str, config: RegionConfig, split_points: &[RowKey]) -> IoResult<Self> {
+        let path = table_path.join(name);
+        if path.exists() {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("ColumnFamily {} already exists", name),
+            ));
+        }
+
+        let mut bounds: Vec<RowKey> = split_points.iter().filter(|p| !p.is_empty()).cloned().collect();
+        match config.salt_buckets {
+            Some(0) => return Err(Error::new(ErrorKind::InvalidInput, "salt_buckets must be at least 1")),
+            // Every bucket is split at the split points, and buckets at their boundaries
+            Some(buckets) => {
+                bounds = (0..buckets)
+                    .flat_map(|bucket| {
+                        let salted = bounds.iter().map(move |point| salt(bucket, point));
+                        (bucket > 0).then(|| vec![bucket]).into_iter().chain(salted)
+                    })
+                    .collect();
+            }
+            None => {}
+        }
+        bounds.sort();
+        bounds.dedup();
+        let starts = std::iter::once(Vec::new()).chain(bounds.iter().cloned());
+        let ends = bounds.iter().cloned().chain(std::iter::once(Vec::new()));
+        let regions: Vec<RegionInfo> = starts.zip(ends)
+            .enumerate()
+            .map(|(i, (start_row, end_row))| RegionInfo { id: i as u64 + 1, start_row, end_row })
+            .collect();
+
+        fs::create_dir_all(&path)?;
+        let manifest = RegionManifest {
+            config,
+            next_id: regions.len() as u64 + 1,
+            regions,
+        };
+        write_manifest(&path, &manifest)?;
+        Self::open(table_path, name)
+    }
+
+    /// Open an existing regioned column family at table_path/name.
+    /// Region directories left behind by an interrupted split are removed.
+    pub fn open(table_path: &Path, name: &str) -> IoResult<Self> {
+        let path = table_path.join(name);
+        let manifest: RegionManifest = serde_json::from_slice(&fs::read(path.join(REGIONS_MANIFEST))?)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+        let live: Vec<String> = manifest.regions.iter().map(RegionInfo::dir_name).collect();
+        for entry in fs::read_dir(&path)? {
+            let entry = entry?;
+            let dir_name = entry.file_name().to_string_lossy().to_string();
+            if entry.file_type()?.is_dir() && dir_name.starts_with("region-") && !live.contains(&dir_name) {
+                warn!(cf = name, region = %dir_name, "removing region left by an interrupted split");
+                fs::remove_dir_all(entry.path())?;
+            }
+        }
+
+        let regions = manifest.regions.into_iter()
+            .map(|info| {
+                let cf = ColumnFamily::open(&path, &info.dir_name())?;
+                Ok(Arc::new(Region { info, cf, writes: AtomicU64::new(0) }))
+            })
+            .collect::<IoResult<Vec<_>>>()?;
+
+        Ok(Self {
+            name: name.to_string(),
+            path,
+            config: manifest.config,
+            next_id: Arc::new(AtomicU64::new(manifest.next_id)),
+            regions: Arc::new(RwLock::new(regions)),
+        })
+    }
+
+    /// Name of this column family.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Split configuration.
+    pub fn config(&self) -> &RegionConfig {
+        &self.config
+    }
+
+    /// Boundaries of the current regions, in row order; salted keys if the column family
+    /// is salted.
+    pub fn regions(&self) -> Vec<RegionInfo> {
+        self.regions.read().unwrap().iter().map(|r| r.info.clone()).collect()
+    }
+
+    /// The column family of every region, in row order.
+    pub(crate) fn column_families(&self) -> Vec<ColumnFamily> {
+        self.regions.read().unwrap().iter().map(|r| r.cf.clone()).collect()
+    }
+
+    /// Whether the background threads of every region are still running.
+    pub fn background_threads_alive(&self) -> bool {
+        self.regions.read().unwrap().iter().all(|r| r.cf.background_thread_alive())
+    }
+
+    /// The key row is stored under: row itself, or prefixed with its salt bucket.
+    fn key(&self, row: &[u8]) -> RowKey {
+        match self.config.salt_buckets {
+            Some(buckets) => salt((crc32fast::hash(row) % buckets as u32) as u8, row),
+            None => row.to_vec(),
+        }
+    }
+
+    /// The row stored under key.
+    fn row(&self, mut key: RowKey) -> RowKey {
+        if self.config.salt_buckets.is_some() {
+            key.remove(0);
+        }
+        key
+    }
+
+    /// The ranges of keys the rows in [start_row, end_row] are stored under, one per
+    /// salt bucket.
+    fn key_ranges(&self, start_row: &[u8], end_row: &[u8]) -> Vec<(RowKey, RowKey)> {
+        match self.config.salt_buckets {
+            Some(buckets) => (0..buckets).map(|bucket| (salt(bucket, start_row), salt(bucket, end_row))).collect(),
+            None => vec![(start_row.to_vec(), end_row.to_vec())],
+        }
+    }
+
+    fn route(regions: &[Arc<Region>], row: &[u8]) -> Arc<Region> {
+        let i = regions.partition_point(|r| r.info.start_row.as_slice() <= row);
+        // The first region starts at the empty key, so i is at least 1
+        regions[i - 1].clone()
+    }
+
+    /// Run a write against the region holding row, then split it if it grew too large.
+    fn write(&self, row: &[u8], op: impl FnOnce(&ColumnFamily) -> IoResult<()>) -> IoResult<()> {
+        let region = {
+            let regions = self.regions.read().unwrap();
+            let region = Self::route(&regions, row);
+            op(&region.cf)?;
+            region
+        };
+        let writes = region.writes.fetch_add(1, Ordering::Relaxed) + 1;
+        if writes % self.config.split_check_interval.max(1) == 0 {
+            self.split_if_needed(region.info.id)?;
+        }
+        Ok(())
+    }
+
+    fn overlapping(&self, start_row: &[u8], end_row: &[u8]) -> Vec<Arc<Region>> {
+        self.regions.read().unwrap()
+            .iter()
+            .filter(|r| r.info.overlaps(start_row, end_row))
+            .cloned()
+            .collect()
+    }
+
+    fn all(&self) -> Vec<Arc<Region>> {
+        self.regions.read().unwrap().clone()
+    }
+
+    pub fn put(&self, row: RowKey, column: Vec<u8>, value: Vec<u8>) -> IoResult<()> {
+        let key = self.key(&row);
+        self.write(&key.clone(), move |cf| cf.put(key, column, value))
+    }
+
+    pub fn execute_put(&self, put: Put) -> IoResult<()> {
+        let key = self.key(put.row());
+        self.write(&key.clone(), move |cf| cf.execute_put(put.with_row(key)))
+    }
+
+    pub fn delete(&self, row: RowKey, column: Vec<u8>) -> IoResult<()> {
+        self.delete_with_ttl(row, column, None)
+    }
+
+    pub fn delete_with_ttl(&self, row: RowKey, column: Vec<u8>, ttl_ms: Option<u64>) -> IoResult<()> {
+        let key = self.key(&row);
+        self.write(&key.clone(), move |cf| cf.delete_with_ttl(key, column, ttl_ms))
+    }
+
+    pub fn delete_row(&self, row: RowKey) -> IoResult<()> {
+        let key = self.key(&row);
+        self.write(&key.clone(), move |cf| cf.delete_row(key))
+    }
+
+    /// Delete every cell of the rows in [start_row, end_row), in every region it spans.
+    pub fn delete_range(&self, start_row: &[u8], end_row: &[u8]) -> IoResult<()> {
+        if start_row >= end_row {
+            return Err(Error::new(ErrorKind::InvalidInput, "start_row must be less than end_row"));
+        }
+        let regions = self.regions.read().unwrap();
+        for (start_key, end_key) in self.key_ranges(start_row, end_row) {
+            let (start_key, end_key) = (start_key.as_slice(), end_key.as_slice());
+            for region in regions.iter().filter(|r| r.info.overlaps(start_key, end_key) && r.info.start_row.as_slice() != end_key) {
+                let start = start_key.max(region.info.start_row.as_slice());
+                let end = if region.info.end_row.is_empty() { end_key } else { end_key.min(region.info.end_row.as_slice()) };
+                region.cf.delete_range(start, end)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, row: &[u8], column: &[u8]) -> IoResult<Option<Vec<u8>>> {
+        let key = self.key(row);
+        let region = Self::route(&self.regions.read().unwrap(), &key);
+        region.cf.get(&key, column)
+    }
+
+    pub fn get_versions(&self, row: &[u8], column: &[u8], max_versions: usize) -> IoResult<Vec<(Timestamp, Vec<u8>)>> {
+        let key = self.key(row);
+        let region = Self::route(&self.regions.read().unwrap(), &key);
+        region.cf.get_versions(&key, column, max_versions)
+    }
+
+    /// Execute a Get operation in the region holding its row. Its column family
+    /// selection is left to the caller.
+    pub fn execute_get(&self, get: &Get) -> IoResult<RowVersions> {
+        let key = self.key(get.row());
+        let region = Self::route(&self.regions.read().unwrap(), &key);
+        let time_range = get.time_range().unwrap_or((0, Timestamp::MAX));
+        region.cf.read_columns(&key, get.columns(), get.max_versions().unwrap_or(1), time_range)
+    }
+
+    pub fn scan_row_versions(&self, row: &[u8], max_versions_per_column: usize) -> IoResult<RowVersions> {
+        let key = self.key(row);
+        let region = Self::route(&self.regions.read().unwrap(), &key);
+        region.cf.scan_row_versions(&key, max_versions_per_column)
+    }
+
+    /// Scan rows in [start_row, end_row] across all regions, with up to max_versions per column.
+    pub fn scan(&self, start_row: &[u8], end_row: &[u8], max_versions: usize) -> IoResult<RangeVersions> {
+        let mut filter_set = FilterSet::new();
+        filter_set.max_versions = Some(max_versions);
+        self.scan_with_filter(start_row, end_row, &filter_set)
+    }
+
+    /// Scan rows in [start_row, end_row] across all regions with a filter set applied.
+    pub fn scan_with_filter(&self, start_row: &[u8], end_row: &[u8], filter_set: &FilterSet) -> IoResult<RangeVersions> {
+        let mut result = BTreeMap::new();
+        for (start_key, end_key) in self.key_ranges(start_row, end_row) {
+            for region in self.overlapping(&start_key, &end_key) {
+                let rows = region.cf.scan_with_filter(&start_key, &end_key, filter_set)?;
+                result.extend(rows.into_iter().map(|(key, row)| (self.row(key), row)));
+            }
+        }
+        Ok(result)
+    }
+
+    /// Count the rows in [start_row, end_row] with at least one visible cell.
+    pub fn count_rows(&self, start_row: &[u8], end_row: &[u8], filter_set: Option<&FilterSet>) -> IoResult<u64> {
+        self.key_ranges(start_row, end_row)
+            .iter()
+            .flat_map(|(start_key, end_key)| {
+                self.overlapping(start_key, end_key)
+                    .into_iter()
+                    .map(move |region| region.cf.count_rows(start_key, end_key, filter_set))
+            })
+            .sum()
+    }
+
+    /// Flush every region, then split those that have grown past the threshold.
+    pub fn flush(&self) -> IoResult<()> {
+        for region in self.all() {
+            region.cf.flush()?;
+            self.split_if_needed(region.info.id)?;
+        }
+        Ok(())
+    }
+
+    /// Run a minor compaction in every region.
+    pub fn compact(&self) -> IoResult<()> {
+        self.all().iter().try_for_each(|region| region.cf.compact())
+    }
+
+    /// Run a major compaction in every region.
+    pub fn major_compact(&self) -> IoResult<()> {
+        self.all().iter().try_for_each(|region| region.cf.major_compact())
+    }
+
+    /// Verify the SSTables of every region, see `ColumnFamily::verify`.
+    pub fn verify(&self, quarantine: bool) -> IoResult<Vec<VerifyReport>> {
+        self.all().iter().map(|region| region.cf.verify(quarantine)).collect()
+    }
+
+    /// Truncate every region, one at a time, keeping the regions themselves.
+    pub fn truncate(&self) -> IoResult<()> {
+        self.all().iter().try_for_each(|region| region.cf.truncate())
+    }
+
+    /// Split the region with the given id at its middle row if its SSTables exceed
+    /// the threshold. Returns whether a split happened.
+    pub fn split_if_needed(&self, region_id: u64) -> IoResult<bool> {
+        let Some(region) = self.all().into_iter().find(|r| r.info.id == region_id) else {
+            // Already split by another writer
+            return Ok(false);
+        };
+        if region.cf.sst_bytes()? <= self.config.split_threshold_bytes {
+            return Ok(false);
+        }
+        self.split(region_id, None)
+    }
+
+    /// Split the region with the given id in two, at split_row if given (which must lie
+    /// strictly inside the region, as a salted key if the column family is salted) or
+    /// else at its middle row. Returns false if the region
+    /// holds fewer than two rows and cannot be split.
+    #[instrument(level = "debug", skip(self, split_row), fields(cf = %self.name))]
+    pub fn split(&self, region_id: u64, split_row: Option<&[u8]>) -> IoResult<bool> {
+        let mut regions = self.regions.write().unwrap();
+        let Some(index) = regions.iter().position(|r| r.info.id == region_id) else {
+            return Err(Error::new(ErrorKind::NotFound, format!("Region {} not found", region_id)));
+        };
+        let parent = regions[index].clone();
+
+        parent.cf.flush()?;
+        let entries = parent.cf.raw_entries()?;
+
+        let split_row = match split_row {
+            Some(row) => {
+                if !parent.info.contains(row) || row == parent.info.start_row.as_slice() {
+                    return Err(Error::new(ErrorKind::InvalidInput, "Split row must lie inside the region"));
+                }
+                row.to_vec()
+            }
+            None => {
+                let mut rows: Vec<&RowKey> = entries.iter().map(|e| &e.key.row).collect();
+                rows.dedup();
+                if rows.len() < 2 {
+                    return Ok(false);
+                }
+                rows[rows.len() / 2].clone()
+            }
+        };
+
+        let left = RegionInfo {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            start_row: parent.info.start_row.clone(),
+            end_row: split_row.clone(),
+        };
+        let right = RegionInfo {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            start_row: split_row.clone(),
+            end_row: parent.info.end_row.clone(),
+        };
+
+        // Build both daughters before the manifest names them, so a crash leaves only
+        // orphaned directories that the next open removes
+        let (left_entries, right_entries): (Vec<_>, Vec<_>) = entries.into_iter()
+            .partition(|e| e.key.row < split_row);
+        let left_cf = ColumnFamily::open(&self.path, &left.dir_name())?;
+        left_cf.bulk_load(left_entries)?;
+        let right_cf = ColumnFamily::open(&self.path, &right.dir_name())?;
+        right_cf.bulk_load(right_entries)?;
+
+        let mut infos: Vec<RegionInfo> = regions.iter().map(|r| r.info.clone()).collect();
+        infos.splice(index..=index, [left.clone(), right.clone()]);
+        write_manifest(&self.path, &RegionManifest {
+            config: self.config.clone(),
+            next_id: self.next_id.load(Ordering::SeqCst),
+            regions: infos,
+        })?;
+
+        regions.splice(index..=index, [
+            Arc::new(Region { info: left.clone(), cf: left_cf, writes: AtomicU64::new(0) }),
+            Arc::new(Region { info: right.clone(), cf: right_cf, writes: AtomicU64::new(0) }),
+        ]);
+        drop(regions);
+
+        parent.cf.retire();
+        if let Err(err) = fs::remove_dir_all(self.path.join(parent.info.dir_name())) {
+            warn!(region = parent.info.id, error = %err, "failed to remove split region");
+        }
+
+        info!(
+            parent = parent.info.id,
+            left = left.id,
+            right = right.id,
+            split_row = %String::from_utf8_lossy(&split_row),
+            "split region"
+        );
+        Ok(true)
+    }
+}
+
+/// row prefixed with the salt bucket.
+fn salt(bucket: u8, row: &[u8]) -> RowKey {
+    let mut key = Vec::with_capacity(row.len() + 1);
+    key.push(bucket);
+    key.extend_from_slice(row);
+    key
+}
+
+fn write_manifest(path: &Path, manifest: &RegionManifest) -> IoResult<()> {
+    let json = serde_json::to_vec_pretty(manifest).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    let tmp = path.join(format!("{}.tmp", REGIONS_MANIFEST));
+    fs::write(&tmp, json)?;
+    fs::rename(&tmp, path.join(REGIONS_MANIFEST))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_presplit_routing() {
+        let dir = tempdir().unwrap();
+        let cf = RegionedColumnFamily::create(
+            dir.path(),
+            "users",
+            RegionConfig::default(),
+            &[b"m".to_vec(), b"f".to_vec()],
+        ).unwrap();
+
+        let regions = cf.regions();
+        assert_eq!(regions.len(), 3);
+        assert_eq!(regions[1].start_row, b"f");
+        assert_eq!(regions[1].end_row, b"m");
+
+        for row in ["apple", "fig", "melon", "zucchini"] {
+            cf.put(row.as_bytes().to_vec(), b"col".to_vec(), row.as_bytes().to_vec()).unwrap();
+        }
+        // Each row lands in its own region's directory
+        assert_eq!(cf.all()[1].cf.memstore_entries(), 1);
+        assert_eq!(cf.all()[2].cf.memstore_entries(), 2);
+
+        assert_eq!(cf.get(b"melon", b"col").unwrap(), Some(b"melon".to_vec()));
+        assert_eq!(cf.scan(b"a", b"zz", 1).unwrap().len(), 4);
+        assert_eq!(cf.count_rows(b"b", b"n", None).unwrap(), 2);
+
+        cf.delete_range(b"e", b"n").unwrap();
+        assert_eq!(cf.get(b"fig", b"col").unwrap(), None);
+        assert_eq!(cf.get(b"melon", b"col").unwrap(), None);
+        assert_eq!(cf.get(b"apple", b"col").unwrap(), Some(b"apple".to_vec()));
+    }
+
+    #[test]
+    fn test_automatic_split() {
+        let dir = tempdir().unwrap();
+        let config = RegionConfig {
+            split_threshold_bytes: 1,
+            split_check_interval: 1_000_000,
+            salt_buckets: None,
+        };
+        let cf = RegionedColumnFamily::create(dir.path(), "events", config, &[]).unwrap();
+        for i in 0..100 {
+            cf.put(format!("row{:03}", i).into_bytes(), b"col".to_vec(), b"v".to_vec()).unwrap();
+        }
+        cf.delete(b"row010".to_vec(), b"col".to_vec()).unwrap();
+
+        // Flushing pushes the single region past the threshold
+        cf.flush().unwrap();
+        let regions = cf.regions();
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0].end_row, b"row050");
+        assert!(!dir.path().join("events").join("region-000001").exists());
+
+        assert_eq!(cf.count_rows(b"row000", b"row999", None).unwrap(), 99);
+        assert_eq!(cf.get(b"row010", b"col").unwrap(), None);
+        assert_eq!(cf.get(b"row075", b"col").unwrap(), Some(b"v".to_vec()));
+
+        // Region boundaries survive reopening
+        drop(cf);
+        let cf = RegionedColumnFamily::open(dir.path(), "events").unwrap();
+        assert_eq!(cf.regions(), regions);
+        assert_eq!(cf.get(b"row075", b"col").unwrap(), Some(b"v".to_vec()));
+    }
+
+    #[test]
+    fn test_salted_rows_spread_over_regions() {
+        let dir = tempdir().unwrap();
+        let config = RegionConfig { salt_buckets: Some(4), ..Default::default() };
+        let cf = RegionedColumnFamily::create(dir.path(), "events", config.clone(), &[b"m".to_vec()]).unwrap();
+        // Each bucket is a region, split again at m
+        let regions = cf.regions();
+        assert_eq!(regions.len(), 8);
+        assert_eq!(regions[1].start_row, b"\x00m");
+        assert_eq!(regions[2].start_row, b"\x01");
+
+        // Increasing keys are written to every bucket rather than the last region (the
+        // rows sort after m, so they are in the second region of each bucket)
+        for i in 0..100 {
+            cf.put(format!("t{:03}", i).into_bytes(), b"col".to_vec(), i.to_string().into_bytes()).unwrap();
+        }
+        let mut put = Put::new(b"t050".to_vec());
+        put.add_column(b"other".to_vec(), b"x".to_vec());
+        cf.execute_put(put).unwrap();
+        for region in cf.all().iter().skip(1).step_by(2) {
+            assert!(region.cf.memstore_entries() > 0);
+        }
+
+        assert_eq!(cf.get(b"t042", b"col").unwrap(), Some(b"42".to_vec()));
+        assert_eq!(cf.get_versions(b"t050", b"col", 5).unwrap().len(), 1);
+        assert_eq!(cf.scan_row_versions(b"t050", 1).unwrap().len(), 2);
+
+        // Scans merge the buckets back into row order
+        let rows: Vec<RowKey> = cf.scan(b"t010", b"t020", 1).unwrap().into_keys().collect();
+        let expected: Vec<RowKey> = (10..=20).map(|i| format!("t{:03}", i).into_bytes()).collect();
+        assert_eq!(rows, expected);
+        assert_eq!(cf.count_rows(b"t000", b"t099", None).unwrap(), 100);
+
+        cf.delete(b"t015".to_vec(), b"col".to_vec()).unwrap();
+        cf.delete_range(b"t030", b"t040").unwrap();
+        assert_eq!(cf.count_rows(b"t000", b"t099", None).unwrap(), 89);
+        assert_eq!(cf.get(b"t039", b"col").unwrap(), None);
+        assert_eq!(cf.get(b"t040", b"col").unwrap(), Some(b"40".to_vec()));
+
+        // Salting is kept in the manifest
+        drop(cf);
+        let cf = RegionedColumnFamily::open(dir.path(), "events").unwrap();
+        assert_eq!(cf.config(), &config);
+        assert_eq!(cf.get(b"t042", b"col").unwrap(), Some(b"42".to_vec()));
+
+        let config = RegionConfig { salt_buckets: Some(0), ..Default::default() };
+        let err = RegionedColumnFamily::create(dir.path(), "none", config, &[]).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,264 @@
+//! Asynchronous replication of a column family to a warm standby.
+//!
+//! The source column family records its mutations in the changelog, which, unlike the
+//! WAL, is not truncated by flushes, so byte positions in it stay valid. A `Replicator`
+//! tails the changelog, ships batches of entries to a `ReplicationSink` and commits the
+//! position of the last acknowledged batch to a checkpoint file. After a restart or an
+//! outage of the standby it resumes from the checkpoint and catches up.
+//!
+//! Delivery is at-least-once: a batch may be shipped again if the checkpoint was not
+//! committed. Entries keep their original timestamps, so re-applying them is harmless.
+//! Only mutations written after the changelog was enabled are replicated; seed the
+//! standby first (e.g. from a snapshot with `ColumnFamily::import_cf`). Range deletes,
+//! bulk loads and snapshot restores bypass the changelog and are not replicated.
+
+use std::{
+    fs,
+    io::{Error, Result as IoResult},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use serde_json::json;
+use tracing::{debug, warn};
+
+use crate::api::{ColumnFamily, Entry};
+use crate::changelog::{ChangelogReader, CHANGELOG_FILE};
+
+/// Default number of entries shipped per batch.
+pub const DEFAULT_BATCH_SIZE: usize = 1000;
+
+/// Destination that replicated entries are shipped to.
+pub trait ReplicationSink: Send {
+    /// Apply entries, in order. Returning an error leaves the replication position
+    /// unchanged, so the same entries are shipped again on the next attempt.
+    fn ship(&mut self, entries: &[Entry]) -> IoResult<()>;
+}
+
+/// Sink writing into a column family opened in this process,
+/// e.g. a standby table on another disk.
+pub struct LocalSink {
+    cf: ColumnFamily,
+}
+
+impl LocalSink {
+    /// Ship into cf.
+    pub fn new(cf: ColumnFamily) -> Self {
+        Self { cf }
+    }
+}
+
+impl ReplicationSink for LocalSink {
+    fn ship(&mut self, entries: &[Entry]) -> IoResult<()> {
+        self.cf.write_entries(entries.iter().cloned())
+    }
+}
+
+/// Sink posting entries to the `/replicate` endpoint of a remote RedBase REST server.
+pub struct RestSink {
+    url: String,
+}
+
+impl RestSink {
+    /// Ship to column family cf of table on the server at base_url (e.g. `http://standby:8080`).
+    pub fn new(base_url: &str, table: &str, cf: &str) -> Self {
+        Self {
+            url: format!("{}/tables/{}/cf/{}/replicate", base_url.trim_end_matches('/'), table, cf),
+        }
+    }
+}
+
+impl ReplicationSink for RestSink {
+    fn ship(&mut self, entries: &[Entry]) -> IoResult<()> {
+        ureq::post(&self.url)
+            .send_json(json!({ "entries": entries }))
+            .map_err(|e| Error::other(format!("Failed to ship to {}: {}", self.url, e)))?;
+        Ok(())
+    }
+}
+
+/// Tails a column family's changelog and ships its entries to a sink.
+pub struct Replicator {
+    reader: ChangelogReader,
+    sink: Box<dyn ReplicationSink>,
+    batch_size: usize,
+    changelog_path: PathBuf,
+}
+
+impl Replicator {
+    /// Replicate source to sink, resuming from the position stored in checkpoint_path.
+    /// Enables the changelog on source if it is not enabled yet.
+    pub fn new(
+        source: &ColumnFamily,
+        checkpoint_path: impl AsRef<Path>,
+        sink: impl ReplicationSink + 'static,
+    ) -> IoResult<Self> {
+        source.enable_changelog()?;
+        let reader = ChangelogReader::open_with_checkpoint(source.path(), checkpoint_path)?;
+        Ok(Self {
+            reader,
+            sink: Box::new(sink),
+            batch_size: DEFAULT_BATCH_SIZE,
+            changelog_path: source.path().join(CHANGELOG_FILE),
+        })
+    }
+
+    /// Set the maximum number of entries shipped per batch.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Changelog position up to which entries have been shipped.
+    pub fn position(&self) -> u64 {
+        self.reader.position()
+    }
+
+    /// Bytes of changelog not yet shipped.
+    pub fn lag_bytes(&self) -> u64 {
+        let len = fs::metadata(&self.changelog_path).map_or(0, |m| m.len());
+        len.saturating_sub(self.position())
+    }
+
+    /// Ship at most one batch and commit its position. Returns the number of entries shipped.
+    pub fn run_once(&mut self) -> IoResult<usize> {
+        let start = self.reader.position();
+        let records = self.reader.poll(self.batch_size)?;
+        if records.is_empty() {
+            return Ok(0);
+        }
+
+        let entries: Vec<Entry> = records.into_iter().map(|r| r.entry).collect();
+        if let Err(e) = self.sink.ship(&entries) {
+            self.reader.seek(start);
+            return Err(e);
+        }
+        self.reader.commit()?;
+        debug!(entries = entries.len(), position = self.reader.position(), "shipped replication batch");
+        Ok(entries.len())
+    }
+
+    /// Ship batches until the standby has caught up. Returns the number of entries shipped.
+    pub fn catch_up(&mut self) -> IoResult<u64> {
+        let mut shipped = 0u64;
+        loop {
+            match self.run_once()? {
+                0 => return Ok(shipped),
+                n => shipped += n as u64,
+            }
+        }
+    }
+
+    /// Keep replicating on a background thread, polling every interval once caught up.
+    /// Failed batches are logged and retried on the next poll.
+    pub fn spawn(mut self, interval: Duration) -> ReplicationHandle {
+        let stop = Arc::new(AtomicBool::new(false));
+        let position = Arc::new(AtomicU64::new(self.position()));
+
+        let thread = {
+            let stop = stop.clone();
+            let position = position.clone();
+            thread::spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    if let Err(err) = self.catch_up() {
+                        warn!(error = %err, position = self.position(), "replication batch failed");
+                    }
+                    position.store(self.position(), Ordering::SeqCst);
+                    thread::sleep(interval);
+                }
+                self
+            })
+        };
+
+        ReplicationHandle { stop, position, thread }
+    }
+}
+
+/// Handle to a replicator running on a background thread.
+pub struct ReplicationHandle {
+    stop: Arc<AtomicBool>,
+    position: Arc<AtomicU64>,
+    thread: JoinHandle<Replicator>,
+}
+
+impl ReplicationHandle {
+    /// Changelog position up to which entries have been shipped.
+    pub fn position(&self) -> u64 {
+        self.position.load(Ordering::SeqCst)
+    }
+
+    /// Stop replicating after the current poll and return the replicator.
+    pub fn stop(self) -> Replicator {
+        self.stop.store(true, Ordering::SeqCst);
+        self.thread.join().expect("replication thread panicked")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Table;
+    use tempfile::tempdir;
+
+    struct FailingSink;
+
+    impl ReplicationSink for FailingSink {
+        fn ship(&mut self, _entries: &[Entry]) -> IoResult<()> {
+            Err(Error::other("standby unavailable"))
+        }
+    }
+
+    #[test]
+    fn test_replicate_and_resume() {
+        let dir = tempdir().unwrap();
+        let checkpoint = dir.path().join("standby.pos");
+        let primary = Table::open(dir.path().join("primary")).unwrap();
+        let standby = Table::open(dir.path().join("standby")).unwrap();
+        primary.create_cf("default").unwrap();
+        standby.create_cf("default").unwrap();
+        let source = primary.cf("default").unwrap();
+        let target = standby.cf("default").unwrap();
+
+        let mut replicator = Replicator::new(&source, &checkpoint, LocalSink::new(target.clone()))
+            .unwrap()
+            .with_batch_size(2);
+        for i in 0..5 {
+            source.put(format!("row{}", i).into_bytes(), b"col1".to_vec(), b"v".to_vec()).unwrap();
+        }
+        assert!(replicator.lag_bytes() > 0);
+        assert_eq!(replicator.catch_up().unwrap(), 5);
+        assert_eq!(replicator.lag_bytes(), 0);
+        assert_eq!(target.get(b"row4", b"col1").unwrap(), Some(b"v".to_vec()));
+        assert_eq!(
+            target.get_versions(b"row0", b"col1", 1).unwrap(),
+            source.get_versions(b"row0", b"col1", 1).unwrap()
+        );
+
+        // A new replicator resumes from the checkpoint after writes made while it was down
+        drop(replicator);
+        source.delete(b"row0".to_vec(), b"col1".to_vec()).unwrap();
+        let mut replicator = Replicator::new(&source, &checkpoint, LocalSink::new(target.clone())).unwrap();
+        assert_eq!(replicator.catch_up().unwrap(), 1);
+        assert_eq!(target.get(b"row0", b"col1").unwrap(), None);
+    }
+
+    #[test]
+    fn test_failed_batch_is_retried() {
+        let dir = tempdir().unwrap();
+        let checkpoint = dir.path().join("standby.pos");
+        let primary = Table::open(dir.path().join("primary")).unwrap();
+        primary.create_cf("default").unwrap();
+        let source = primary.cf("default").unwrap();
+
+        let mut replicator = Replicator::new(&source, &checkpoint, FailingSink).unwrap();
+        source.put(b"row1".to_vec(), b"col1".to_vec(), b"v".to_vec()).unwrap();
+        assert!(replicator.run_once().is_err());
+        assert_eq!(replicator.position(), 0);
+        assert!(!checkpoint.exists());
+    }
+}
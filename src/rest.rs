@@ -1,30 +1,67 @@
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
 
 use actix_web::{
-    web, App, HttpResponse, HttpServer, Responder,
+    web, App, HttpRequest, HttpResponse, HttpServer, Responder,
+    http::header,
     middleware::Logger,
-    error::{ErrorBadRequest, ErrorInternalServerError, ErrorNotFound},
+    error::{ErrorBadRequest, ErrorConflict, ErrorGatewayTimeout, ErrorInternalServerError, ErrorNotFound, InternalError},
 };
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
+use utoipa::{IntoParams, OpenApi, ToSchema};
 use serde_json::json;
-
-use crate::pool::{ConnectionPool, Connection};
+use deadpool::managed::PoolError;
+use tracing::{info, instrument, warn};
+
+use crate::api::{CellValue, CompactionOptions, CompactionType, Entry, RangeVersions, Scan, ScanOptions, WriteStallThresholds};
+use crate::result::Row;
+use crate::async_api::{ColumnFamily, Table as AsyncTable};
+use crate::namespace::{self, Namespace};
+use crate::scan::ResumeToken;
+use crate::changelog::{ChangeRecord, ChangelogReader, CHANGELOG_FILE};
+use crate::pool::{CircuitBreakerConfig, CircuitOpen, CircuitState, Connection, ConnectionPool, PoolConfig, PoolExhausted};
+use crate::validation::ValidationError;
 use crate::batch::{Batch, AsyncBatchExt};
 use crate::filter::{Filter, FilterSet};
 use crate::aggregation::{AggregationType, AggregationSet};
+use crate::query::QueryValue;
 
 /// Configuration for the REST server
 #[derive(Clone)]
 pub struct RestConfig {
-    /// The base directory for tables
+    /// The base directory for tables; `/tables/{table}` is its subdirectory `table`
     pub base_dir: PathBuf,
     /// The host to bind to
     pub host: String,
     /// The port to bind to
     pub port: u16,
-    /// The number of connections in the pool
+    /// The number of connections in the pool of each table
     pub pool_size: usize,
+    /// Thresholds past which writes are rejected with 503 Service Unavailable
+    pub write_stall: WriteStallThresholds,
+    /// Value of the Retry-After header (in seconds) sent with stalled writes
+    pub retry_after_secs: u64,
+    /// How long a read with `min_seq` waits for that write before failing, in milliseconds
+    pub max_read_wait_ms: u64,
+    /// Largest response of a range scan, in bytes; longer scans are continued in later requests
+    pub max_scan_response_bytes: usize,
+    /// Longest time a range scan or count may run, in milliseconds; requests may ask
+    /// for less with `timeout_ms`
+    pub max_request_ms: u64,
+    /// When the connection pool stops serving requests after repeated failures
+    pub circuit_breaker: CircuitBreakerConfig,
+    /// How long pooled connections are kept
+    pub pool: PoolConfig,
+    /// Root directory of the namespaces served under `/namespaces`
+    pub namespace_dir: PathBuf,
 }
 
 impl Default for RestConfig {
@@ -34,6 +71,14 @@ impl Default for RestConfig {
             host: "127.0.0.1".to_string(),
             port: 8080,
             pool_size: 10,
+            write_stall: WriteStallThresholds::default(),
+            retry_after_secs: 1,
+            max_read_wait_ms: 1000,
+            max_scan_response_bytes: 64 * 1024 * 1024,
+            max_request_ms: 30_000,
+            circuit_breaker: CircuitBreakerConfig::default(),
+            pool: PoolConfig::default(),
+            namespace_dir: PathBuf::from("./namespaces"),
         }
     }
 }
@@ -42,17 +87,212 @@ impl Default for RestConfig {
 pub struct AppState {
     /// The connection pool
     pub pool: ConnectionPool,
+    /// Thresholds past which writes are rejected
+    pub write_stall: WriteStallThresholds,
+    /// Retry-After value for stalled writes, in seconds
+    pub retry_after_secs: u64,
+    /// Number of writes rejected because of a stall
+    pub stall_rejections: AtomicU64,
+    /// How long a read with `min_seq` waits for that write
+    pub max_read_wait: Duration,
+    /// Largest response of a range scan, in bytes
+    pub max_scan_response_bytes: usize,
+    /// Longest time a range scan or count may run
+    pub max_request_time: Duration,
+    /// Tables in namespaces
+    pub namespaces: NamespaceTables,
+    /// Outcome of `recover`: the number of tables opened or why opening one failed, or
+    /// `None` while it has not finished
+    pub recovery: Mutex<Option<Result<usize, String>>>,
+}
+
+impl AppState {
+    /// Build the state for a server with the given configuration.
+    pub fn new(config: &RestConfig) -> Self {
+        Self {
+            pool: ConnectionPool::with_config(&config.base_dir, config.pool_size, config.pool, config.circuit_breaker),
+            write_stall: config.write_stall,
+            retry_after_secs: config.retry_after_secs,
+            stall_rejections: AtomicU64::new(0),
+            max_read_wait: Duration::from_millis(config.max_read_wait_ms),
+            max_scan_response_bytes: config.max_scan_response_bytes,
+            max_request_time: Duration::from_millis(config.max_request_ms),
+            namespaces: NamespaceTables::new(&config.namespace_dir),
+            recovery: Mutex::new(None),
+        }
+    }
+
+    /// Open every table under the base directory through the pool, replaying their WALs,
+    /// and record the outcome for the readiness probe. Tables in namespaces are still
+    /// opened on first use. Returns the number of tables opened.
+    pub async fn recover(&self) -> std::io::Result<usize> {
+        let result: std::io::Result<usize> = async {
+            let tables = self.pool.table_names()?;
+            for table in &tables {
+                let _conn = self.pool.get(table).await.map_err(|e| match e {
+                    PoolError::Backend(e) => e,
+                    e => std::io::Error::other(format!("Failed to open table {}: {}", table, e)),
+                })?;
+            }
+            Ok(tables.len())
+        }.await;
+        match &result {
+            Ok(tables) => info!(tables, "recovered tables"),
+            Err(e) => warn!(error = %e, "failed to recover tables"),
+        }
+        *self.recovery.lock().unwrap() = Some(result.as_ref().copied().map_err(|e| e.to_string()));
+        result
+    }
+
+    /// Deadline of a request asking to finish within timeout_ms, capped by the server's limit.
+    fn deadline(&self, timeout_ms: Option<u64>) -> Instant {
+        let limit = timeout_ms.map_or(self.max_request_time, |ms| Duration::from_millis(ms).min(self.max_request_time));
+        Instant::now() + limit
+    }
+
+    /// Record a successful write and return its sequence number.
+    fn committed(&self) -> u64 {
+        self.pool.circuit_breaker().record_success();
+        self.pool.commits().commit()
+    }
+}
+
+/// Tables in namespaces, opened on first use and shared by all requests.
+///
+/// Unlike the pooled table, every request uses the same handle of a namespaced table, so
+/// writes are visible to later reads right away.
+pub struct NamespaceTables {
+    root: PathBuf,
+    tables: Mutex<HashMap<(String, String), AsyncTable>>,
+}
+
+impl NamespaceTables {
+    /// Serve the namespaces under root.
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            tables: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Root directory of the namespaces.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The existing table name in namespace ns.
+    async fn table(&self, ns: &str, name: &str) -> Result<AsyncTable, actix_web::Error> {
+        let key = (ns.to_string(), name.to_string());
+        if let Some(table) = self.tables.lock().unwrap().get(&key) {
+            return Ok(table.clone());
+        }
+
+        let root = self.root.clone();
+        let (ns, name) = key.clone();
+        let path = tokio::task::spawn_blocking(move || -> std::io::Result<PathBuf> {
+            let namespace = Namespace::open(&root, &ns)?;
+            namespace.table(&name)?;
+            namespace.table_path(&name)
+        })
+        .await
+        .map_err(ErrorInternalServerError)?
+        .map_err(|e| namespace_error("Failed to open table", e))?;
+        let table = AsyncTable::open(path).await.map_err(|e| namespace_error("Failed to open table", e))?;
+        Ok(self.tables.lock().unwrap().entry(key).or_insert(table).clone())
+    }
+}
+
+/// Map a failed namespace or table operation to an HTTP error.
+fn namespace_error(context: &str, e: std::io::Error) -> actix_web::Error {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => ErrorNotFound(format!("{}: {}", context, e)),
+        std::io::ErrorKind::AlreadyExists => ErrorConflict(format!("{}: {}", context, e)),
+        std::io::ErrorKind::InvalidInput => ErrorBadRequest(format!("{}: {}", context, e)),
+        _ => ErrorInternalServerError(format!("{}: {}", context, e)),
+    }
+}
+
+/// Table addressed by a request: `/tables/{table}` is a pooled table under the base
+/// directory, `/namespaces/{namespace}/tables/{table}` a table in a namespace.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+struct TablePath {
+    #[param(ignore)]
+    namespace: Option<String>,
+    /// Name of the table
+    table: String,
+}
+
+/// Column family addressed by a request, see `TablePath`.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+struct CfPath {
+    #[param(ignore)]
+    namespace: Option<String>,
+    /// Name of the table
+    table: String,
+    /// Name of the column family
+    cf: String,
+}
+
+/// Handle of the table a request addresses. Pooled reads first make the write min_seq
+/// visible; namespaced tables see every write already.
+async fn table_handle(
+    state: &AppState,
+    namespace: Option<&str>,
+    table: &str,
+    min_seq: Option<u64>,
+) -> Result<AsyncTable, actix_web::Error> {
+    match namespace {
+        None => {
+            let mut conn = state.pool.get(table).await.map_err(pool_error)?;
+            await_visible(state, &mut conn, min_seq).await?;
+            Ok(conn.table.clone())
+        }
+        Some(ns) => state.namespaces.table(ns, table).await,
+    }
+}
+
+/// The column family a request addresses, see `table_handle`.
+async fn column_family(state: &AppState, path: &CfPath, min_seq: Option<u64>) -> Result<ColumnFamily, actix_web::Error> {
+    let table = table_handle(state, path.namespace.as_deref(), &path.table, min_seq).await?;
+    table.cf(&path.cf).await.ok_or_else(|| {
+        ErrorNotFound(format!("Column family not found: {}", path.cf))
+    })
+}
+
+/// Request body for creating a namespace or a table
+#[derive(Deserialize, ToSchema)]
+struct CreateNamedRequest {
+    /// The name of the namespace or table
+    name: String,
+}
+
+/// Request body for applying replicated entries
+#[derive(Deserialize, ToSchema)]
+struct ReplicateRequest {
+    /// Entries as read from a changelog, with their original timestamps
+    #[schema(value_type = Vec<Object>)]
+    entries: Vec<Entry>,
+}
+
+/// Query parameters accepted by reads
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ReadConsistency {
+    /// Only read once the write that returned this `seq` is visible
+    min_seq: Option<u64>,
 }
 
 /// Request body for creating a column family
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct CreateCfRequest {
     /// The name of the column family
     name: String,
 }
 
 /// Request body for put operation
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct PutRequest {
     /// The row key
     row: String,
@@ -63,7 +303,7 @@ struct PutRequest {
 }
 
 /// Request body for delete operation
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct DeleteRequest {
     /// The row key
     row: String,
@@ -74,14 +314,14 @@ struct DeleteRequest {
 }
 
 /// Request body for batch operations
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct BatchRequest {
     /// The operations to perform
     operations: Vec<BatchOperation>,
 }
 
 /// A single operation in a batch request
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 #[serde(tag = "type", content = "data")]
 enum BatchOperation {
     /// Put operation
@@ -90,8 +330,15 @@ enum BatchOperation {
     Delete(DeleteRequest),
 }
 
+/// Request body for putting several cells at once
+#[derive(Deserialize, ToSchema)]
+struct MultiPutRequest {
+    /// The cells to put, written atomically with one timestamp
+    cells: Vec<PutRequest>,
+}
+
 /// Request body for get operation
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct GetRequest {
     /// The row key
     row: String,
@@ -101,17 +348,125 @@ struct GetRequest {
     max_versions: Option<usize>,
 }
 
+/// Request body for an existence check
+#[derive(Deserialize, ToSchema)]
+struct ExistsRequest {
+    /// The row key
+    row: String,
+    /// The column name
+    column: String,
+}
+
+/// Request body for getting several cells at once
+#[derive(Deserialize, ToSchema)]
+struct MultiGetRequest {
+    /// The cells to get, answered in this order
+    cells: Vec<ExistsRequest>,
+}
+
 /// Request body for scan operation
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct ScanRequest {
     /// The row key
     row: String,
     /// Optional maximum number of versions per column
     max_versions_per_column: Option<usize>,
+    /// Also return delete markers, as `{"timestamp": ..., "deleted": true}`, and the
+    /// versions they hide
+    #[serde(default)]
+    raw: bool,
+}
+
+/// Request body for range scan operation
+#[derive(Deserialize, ToSchema)]
+struct ScanRangeRequest {
+    /// The first row key (inclusive)
+    start_row: String,
+    /// The last row key (inclusive)
+    end_row: String,
+    /// Optional maximum number of versions per column
+    max_versions_per_column: Option<usize>,
+    /// Optional limit on the response size in bytes, below the server's limit
+    max_response_bytes: Option<usize>,
+    /// Resume token of a truncated scan (the continuation header), replacing start_row
+    continuation: Option<String>,
+    /// Optional time limit in milliseconds, below the server's limit
+    timeout_ms: Option<u64>,
+}
+
+/// Request body for count operation
+#[derive(Deserialize, ToSchema)]
+struct CountRequest {
+    /// The first row key (inclusive)
+    start_row: String,
+    /// The last row key (inclusive)
+    end_row: String,
+    /// Optional filter set rows must match
+    filter_set: Option<FilterSetRequest>,
+    /// Optional time limit in milliseconds, below the server's limit
+    timeout_ms: Option<u64>,
+}
+
+/// Query parameters of a streaming scan
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct StreamRequest {
+    /// The first row key of the scan (inclusive); without a range, nothing is scanned
+    start_row: Option<String>,
+    /// The last row key of the scan (inclusive)
+    end_row: Option<String>,
+    /// Optional maximum number of versions per column
+    max_versions: Option<usize>,
+    /// Optional size of each message of rows in bytes, below the server's scan limit
+    batch_bytes: Option<usize>,
+    /// Once the scan is done, stream changes to rows starting with this prefix (empty for
+    /// every row) until the client closes the socket
+    subscribe: Option<String>,
+}
+
+/// Optional request body for compact operation; an empty body runs a minor compaction
+#[derive(Deserialize, ToSchema, Default)]
+struct CompactRequest {
+    /// `minor` (the default) merges some SSTables, `major` all of them
+    #[serde(default, rename = "type")]
+    #[schema(value_type = Option<String>)]
+    compaction_type: Option<CompactionType>,
+    /// Optional maximum number of versions kept per cell
+    max_versions: Option<usize>,
+    /// Optional maximum age of the versions kept, in milliseconds
+    max_age_ms: Option<u64>,
+    /// Whether to drop expired tombstones (true by default)
+    cleanup_tombstones: Option<bool>,
+}
+
+impl CompactRequest {
+    /// The compaction options asked for, rejecting `max_versions` of 0.
+    fn options(self) -> Result<CompactionOptions, actix_web::Error> {
+        if self.max_versions == Some(0) {
+            return Err(ErrorBadRequest("max_versions must be at least 1"));
+        }
+        let defaults = CompactionOptions::default();
+        Ok(CompactionOptions {
+            compaction_type: self.compaction_type.unwrap_or(defaults.compaction_type),
+            max_versions: self.max_versions,
+            max_age_ms: self.max_age_ms,
+            cleanup_tombstones: self.cleanup_tombstones.unwrap_or(defaults.cleanup_tombstones),
+            ..defaults
+        })
+    }
+}
+
+/// Request body for a query
+#[derive(Deserialize, ToSchema)]
+struct QueryRequest {
+    /// The query, e.g. `SELECT city, COUNT(*) FROM users GROUP BY city`
+    sql: String,
+    /// Optional time limit in milliseconds, below the server's limit
+    timeout_ms: Option<u64>,
 }
 
 /// Request body for filter operation
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct FilterRequest {
     /// The row key
     row: String,
@@ -120,27 +475,29 @@ struct FilterRequest {
 }
 
 /// Filter set for filter requests
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, ToSchema)]
 struct FilterSetRequest {
     /// Column filters
     column_filters: Vec<ColumnFilterRequest>,
     /// Optional timestamp range
+    #[schema(value_type = Option<Vec<Option<u64>>>)]
     timestamp_range: Option<(Option<u64>, Option<u64>)>,
     /// Optional maximum number of versions
     max_versions: Option<usize>,
 }
 
 /// Column filter for filter requests
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, ToSchema)]
 struct ColumnFilterRequest {
     /// The column name
     column: String,
     /// The filter to apply
+    #[schema(value_type = Object)]
     filter: Filter,
 }
 
 /// Request body for aggregation operation
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct AggregationRequest {
     /// The row key
     row: String,
@@ -151,14 +508,14 @@ struct AggregationRequest {
 }
 
 /// Aggregation set for aggregation requests
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, ToSchema)]
 struct AggregationSetRequest {
     /// The aggregations to perform
     aggregations: Vec<AggregationItemRequest>,
 }
 
 /// Aggregation item for aggregation requests
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, ToSchema)]
 struct AggregationItemRequest {
     /// The column name
     column: String,
@@ -166,11 +523,12 @@ struct AggregationItemRequest {
     aggregation_type: String,
 }
 
-/// Convert a filter set request to a filter set
-fn convert_filter_set(filter_set_req: FilterSetRequest) -> FilterSet {
+/// Convert a filter set request to a filter set, rejecting scripts that do not compile
+fn convert_filter_set(filter_set_req: FilterSetRequest) -> Result<FilterSet, actix_web::Error> {
     let mut filter_set = FilterSet::new();
 
     for column_filter in filter_set_req.column_filters {
+        column_filter.filter.check_scripts().map_err(ErrorBadRequest)?;
         filter_set.add_column_filter(
             column_filter.column.into_bytes(),
             column_filter.filter,
@@ -185,7 +543,7 @@ fn convert_filter_set(filter_set_req: FilterSetRequest) -> FilterSet {
         filter_set.with_max_versions(max_versions);
     }
 
-    filter_set
+    Ok(filter_set)
 }
 
 /// Convert an aggregation type string to an aggregation type
@@ -212,23 +570,250 @@ fn convert_aggregation_set(agg_set_req: AggregationSetRequest) -> Result<Aggrega
     Ok(agg_set)
 }
 
+/// Map a failed write to an HTTP error; writes to a frozen column family are a 409 Conflict,
+/// values rejected by a column validator a 422 with the structured error as body, and
+/// writes rejected by a column family's write throttle a 503 with Retry-After.
+/// Failures of the store itself count towards the pool's circuit breaker.
+fn write_error(state: &AppState, context: &str, e: std::io::Error) -> actix_web::Error {
+    state.pool.circuit_breaker().record_error(&e);
+    if let Some(invalid) = ValidationError::from_io(&e) {
+        let response = HttpResponse::UnprocessableEntity().json(json!({
+            "status": "invalid_value",
+            "error": invalid,
+        }));
+        InternalError::from_response(format!("{}: {}", context, invalid), response).into()
+    } else if e.kind() == std::io::ErrorKind::PermissionDenied {
+        ErrorConflict(format!("{}: {}", context, e))
+    } else if e.kind() == std::io::ErrorKind::ResourceBusy {
+        state.stall_rejections.fetch_add(1, Ordering::Relaxed);
+        let response = HttpResponse::ServiceUnavailable()
+            .insert_header((header::RETRY_AFTER, state.retry_after_secs.to_string()))
+            .json(json!({
+                "status": "write_stalled",
+                "message": e.to_string()
+            }));
+        InternalError::from_response(format!("{}: {}", context, e), response).into()
+    } else {
+        ErrorInternalServerError(format!("{}: {}", context, e))
+    }
+}
+
+/// Map a failure to get a pooled connection to an HTTP error; invalid table names are
+/// a 400, and while the pool's circuit breaker is open, its wait queue is full or after
+/// waiting too long for a connection, requests fail with 503 and Retry-After.
+fn pool_error(e: PoolError<std::io::Error>) -> actix_web::Error {
+    let (status, message, retry_after) = match &e {
+        PoolError::Backend(err) if err.kind() == std::io::ErrorKind::InvalidInput => {
+            return ErrorBadRequest(err.to_string());
+        }
+        PoolError::Backend(err) => match (CircuitOpen::from_io(err), PoolExhausted::from_io(err)) {
+            (Some(open), _) => ("circuit_open", open.to_string(), open.retry_after.as_secs_f64().ceil().max(1.0) as u64),
+            (None, Some(exhausted)) => ("pool_exhausted", exhausted.to_string(), 1),
+            (None, None) => return ErrorInternalServerError(format!("Failed to get connection from pool: {}", e)),
+        },
+        PoolError::Timeout(_) => ("pool_timeout", "Timed out waiting for a connection from the pool".to_string(), 1),
+        _ => return ErrorInternalServerError(format!("Failed to get connection from pool: {}", e)),
+    };
+
+    let response = HttpResponse::ServiceUnavailable()
+        .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+        .json(json!({
+            "status": status,
+            "retry_after_secs": retry_after,
+            "message": message
+        }));
+    InternalError::from_response(message, response).into()
+}
+
+/// Reject a write with 503 and Retry-After while the column family is stalled,
+/// rather than queueing work behind a compaction backlog.
+async fn check_write_stall(state: &AppState, cf_name: &str, cf: &ColumnFamily) -> Result<(), actix_web::Error> {
+    let Some(stall) = cf.write_stall(state.write_stall).await else {
+        return Ok(());
+    };
+
+    state.stall_rejections.fetch_add(1, Ordering::Relaxed);
+    warn!(cf = cf_name, %stall, "rejecting write during stall");
+    let response = HttpResponse::ServiceUnavailable()
+        .insert_header((header::RETRY_AFTER, state.retry_after_secs.to_string()))
+        .json(json!({
+            "status": "write_stalled",
+            "column_family": cf_name,
+            "stall": stall,
+            "message": stall.to_string()
+        }));
+    Err(InternalError::from_response(stall.to_string(), response).into())
+}
+
+/// Response header carrying the continuation token of a truncated range scan
+pub const CONTINUATION_HEADER: &str = "x-redbase-continuation";
+/// Response header marking a range scan cut short by its time limit
+pub const TIMED_OUT_HEADER: &str = "x-redbase-timed-out";
+/// Request header carrying the client-chosen ID of a write, which makes it idempotent
+pub const REQUEST_ID_HEADER: &str = "x-redbase-request-id";
+/// Longest request ID accepted, in bytes
+const MAX_REQUEST_ID_LEN: usize = 256;
+/// Default size of each message of rows sent by a streaming scan
+const STREAM_BATCH_BYTES: usize = 64 * 1024;
+/// How often a streaming subscription reads new changes from the changelog
+const CHANGE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+
+/// A batch carrying the request ID of the write request, if it has one. A write retried
+/// with the same ID, e.g. after a timeout, succeeds again without being applied twice.
+fn request_batch(http: &HttpRequest) -> Result<Batch, actix_web::Error> {
+    let mut batch = Batch::new();
+    if let Some(value) = http.headers().get(REQUEST_ID_HEADER) {
+        let id = value.to_str()
+            .ok()
+            .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+            .ok_or_else(|| ErrorBadRequest(format!("{} must be 1 to {} visible ASCII characters", REQUEST_ID_HEADER, MAX_REQUEST_ID_LEN)))?;
+        batch.set_request_id(id);
+    }
+    Ok(batch)
+}
+
+/// Make the write with sequence number min_seq visible through conn before a read,
+/// failing with 503 if it is not committed within the configured wait.
+async fn await_visible(state: &AppState, conn: &mut Connection, min_seq: Option<u64>) -> Result<(), actix_web::Error> {
+    let Some(min_seq) = min_seq else {
+        return Ok(());
+    };
+    let commits = state.pool.commits();
+    let visible = conn.await_seq(commits, min_seq, state.max_read_wait).await.map_err(|e| {
+        ErrorInternalServerError(format!("Failed to refresh connection: {}", e))
+    })?;
+    if visible {
+        return Ok(());
+    }
+
+    let message = format!("Write {} is not visible yet (last committed {})", min_seq, commits.last());
+    let response = HttpResponse::ServiceUnavailable()
+        .insert_header((header::RETRY_AFTER, state.retry_after_secs.to_string()))
+        .json(json!({
+            "status": "not_visible",
+            "min_seq": min_seq,
+            "last_seq": commits.last(),
+            "message": message
+        }));
+    Err(InternalError::from_response(message, response).into())
+}
+
 /// Health check endpoint
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "server",
+    responses((status = 200, description = "Server is up"))
+)]
 async fn health_check() -> impl Responder {
     HttpResponse::Ok().json(json!({ "status": "ok" }))
 }
 
+/// Name of the file written and removed to check that the base directory is writable.
+/// Table names cannot start with a dot, so it never clashes with a table.
+const READY_PROBE_FILE: &str = ".ready_probe";
+
+/// Readiness probe: whether the base directory is writable, the pool can produce a
+/// connection, the tables have been recovered and their background threads are running
+#[utoipa::path(
+    get,
+    path = "/ready",
+    tag = "server",
+    responses(
+        (status = 200, description = "Ready, with the result of every check"),
+        (status = 503, description = "Not ready, with the result of every check"),
+    )
+)]
+async fn readiness(state: web::Data<AppState>) -> impl Responder {
+    let mut checks = serde_json::Map::new();
+    let mut ready = true;
+    let mut check = |name: &str, result: Result<serde_json::Value, String>| {
+        let details = match result {
+            Ok(details) => json!({ "ok": true, "details": details }),
+            Err(error) => {
+                ready = false;
+                json!({ "ok": false, "error": error })
+            }
+        };
+        checks.insert(name.to_string(), details);
+    };
+
+    let base_dir = state.pool.base_dir().to_path_buf();
+    let writable = tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+        std::fs::create_dir_all(&base_dir)?;
+        let probe = base_dir.join(READY_PROBE_FILE);
+        std::fs::write(&probe, b"ok")?;
+        std::fs::remove_file(&probe)
+    }).await.unwrap();
+    check("data_dir", writable.map(|_| json!(state.pool.base_dir())).map_err(|e| e.to_string()));
+
+    // One connection shows the pool works; the background threads of its table and
+    // of the open namespaced tables are checked below
+    let mut tables = Vec::new();
+    let pool = match state.pool.table_names() {
+        Err(e) => Err(format!("Failed to list tables: {}", e)),
+        Ok(names) => match names.first() {
+            None => Ok(json!({ "tables": 0 })),
+            Some(name) => match state.pool.get(name).await {
+                Ok(conn) => {
+                    tables.push((name.clone(), conn.table.clone()));
+                    Ok(json!({ "tables": names.len() }))
+                }
+                Err(e) => Err(format!("Failed to get connection from pool: {}", e)),
+            },
+        },
+    };
+    check("pool", pool);
+
+    let recovery = match &*state.recovery.lock().unwrap() {
+        Some(Ok(tables)) => Ok(json!({ "tables": tables })),
+        Some(Err(e)) => Err(e.clone()),
+        None => Err("WAL replay has not finished".to_string()),
+    };
+    check("wal_replay", recovery);
+
+    for ((ns, name), table) in state.namespaces.tables.lock().unwrap().iter() {
+        tables.push((format!("{}/{}", ns, name), table.clone()));
+    }
+    let stopped: Vec<String> = tables.iter()
+        .flat_map(|(name, table)| table.stopped_background_threads().into_iter().map(move |cf| format!("{}/{}", name, cf)))
+        .collect();
+    let threads = if stopped.is_empty() {
+        Ok(json!({ "tables": tables.len() }))
+    } else {
+        Err(format!("Background threads stopped in {}", stopped.join(", ")))
+    };
+    check("background_threads", threads);
+
+    let status = if ready { "ready" } else { "not_ready" };
+    let body = json!({ "status": status, "checks": checks });
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
 /// Create a column family
+#[instrument(skip_all, fields(table = %path.table))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/cf",
+    tag = "tables",
+    request_body = CreateCfRequest,
+    params(TablePath),
+    responses((status = 201, description = "Column family created"))
+)]
 async fn create_cf(
     state: web::Data<AppState>,
-    path: web::Path<String>,
+    path: web::Path<TablePath>,
     req: web::Json<CreateCfRequest>,
 ) -> Result<impl Responder, actix_web::Error> {
-    let table_name = path.into_inner();
-    let conn = state.pool.get().await.map_err(|e| {
-        ErrorInternalServerError(format!("Failed to get connection from pool: {}", e))
-    })?;
+    let TablePath { namespace, table: table_name } = path.into_inner();
+    let table = table_handle(&state, namespace.as_deref(), &table_name, None).await?;
 
-    conn.table.create_cf(&req.name).await.map_err(|e| {
+    table.create_cf(&req.name).await.map_err(|e| {
         ErrorInternalServerError(format!("Failed to create column family: {}", e))
     })?;
 
@@ -239,95 +824,383 @@ async fn create_cf(
     })))
 }
 
+/// Storage and backpressure metrics in the Prometheus text format
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "server",
+    responses((status = 200, description = "Prometheus text format", content_type = "text/plain"), (status = 503, description = "Circuit open"))
+)]
+async fn metrics(state: web::Data<AppState>) -> Result<impl Responder, actix_web::Error> {
+    let mut sst_files = String::new();
+    let mut memstore_entries = String::new();
+    let mut stalled = String::new();
+    // Storage metrics need a connection, breaker metrics are reported while the circuit is open too
+    let breaker = state.pool.circuit_breaker().stats();
+    if breaker.state != CircuitState::Open {
+        let tables = state.pool.table_names().map_err(|e| {
+            ErrorInternalServerError(format!("Failed to list tables: {}", e))
+        })?;
+        for table in &tables {
+            let conn = state.pool.get(table).await.map_err(pool_error)?;
+            let names = conn.table.cf_names().await.map_err(|e| {
+                ErrorInternalServerError(format!("Failed to list column families: {}", e))
+            })?;
+
+            for name in &names {
+                let Some(cf) = conn.table.cf(name).await else {
+                    continue;
+                };
+                let (ssts, entries) = cf.storage_counts().await;
+                let is_stalled = cf.write_stall(state.write_stall).await.is_some();
+                let labels = format!("table=\"{}\",cf=\"{}\"", table, name);
+                let _ = writeln!(sst_files, "redbase_sst_files{{{}}} {}", labels, ssts);
+                let _ = writeln!(memstore_entries, "redbase_memstore_entries{{{}}} {}", labels, entries);
+                let _ = writeln!(stalled, "redbase_write_stalled{{{}}} {}", labels, is_stalled as u8);
+            }
+        }
+    }
+    let mut pool_connections = String::new();
+    let mut pool_waiting = String::new();
+    for status in state.pool.status() {
+        for (kind, count) in [("idle", status.idle), ("in_use", status.in_use)] {
+            let _ = writeln!(pool_connections, "redbase_pool_connections{{table=\"{}\",state=\"{}\"}} {}", status.table, kind, count);
+        }
+        let _ = writeln!(pool_waiting, "redbase_pool_waiting{{table=\"{}\"}} {}", status.table, status.waiting);
+    }
+    let usage = state.pool.usage();
+    let circuit_state = match breaker.state {
+        CircuitState::Closed => 0,
+        CircuitState::Open => 1,
+        CircuitState::HalfOpen => 2,
+    };
+
+    let body = format!(
+        "# HELP redbase_sst_files Live SSTables per column family\n\
+         # TYPE redbase_sst_files gauge\n{}\
+         # HELP redbase_memstore_entries Entries buffered in the MemStore per column family\n\
+         # TYPE redbase_memstore_entries gauge\n{}\
+         # HELP redbase_write_stalled Whether writes to the column family are being rejected\n\
+         # TYPE redbase_write_stalled gauge\n{}\
+         # HELP redbase_write_stall_rejections_total Writes rejected with 503 because of a stall\n\
+         # TYPE redbase_write_stall_rejections_total counter\n\
+         redbase_write_stall_rejections_total {}\n\
+         # HELP redbase_pool_circuit_state Pool circuit breaker state (0 closed, 1 open, 2 half-open)\n\
+         # TYPE redbase_pool_circuit_state gauge\n\
+         redbase_pool_circuit_state {}\n\
+         # HELP redbase_pool_circuit_transitions_total Pool circuit breaker state transitions\n\
+         # TYPE redbase_pool_circuit_transitions_total counter\n\
+         redbase_pool_circuit_transitions_total{{to=\"open\"}} {}\n\
+         redbase_pool_circuit_transitions_total{{to=\"half_open\"}} {}\n\
+         redbase_pool_circuit_transitions_total{{to=\"closed\"}} {}\n\
+         # HELP redbase_pool_circuit_rejections_total Requests failed fast while the circuit was open\n\
+         # TYPE redbase_pool_circuit_rejections_total counter\n\
+         redbase_pool_circuit_rejections_total {}\n\
+         # HELP redbase_pool_connections Pooled connections per table, idle or in use\n\
+         # TYPE redbase_pool_connections gauge\n{}\
+         # HELP redbase_pool_waiting Requests waiting for a pooled connection per table\n\
+         # TYPE redbase_pool_waiting gauge\n{}\
+         # HELP redbase_pool_connections_created_total Pooled connections opened\n\
+         # TYPE redbase_pool_connections_created_total counter\n\
+         redbase_pool_connections_created_total {}\n\
+         # HELP redbase_pool_connections_reused_total Pooled connections handed out again after their checks\n\
+         # TYPE redbase_pool_connections_reused_total counter\n\
+         redbase_pool_connections_reused_total {}\n\
+         # HELP redbase_pool_connections_closed_total Pooled connections closed, by reason\n\
+         # TYPE redbase_pool_connections_closed_total counter\n\
+         redbase_pool_connections_closed_total{{reason=\"idle\"}} {}\n\
+         redbase_pool_connections_closed_total{{reason=\"age\"}} {}\n\
+         redbase_pool_connections_closed_total{{reason=\"failed_check\"}} {}\n\
+         # HELP redbase_pool_requests_failed_total Requests that got no pooled connection, by reason\n\
+         # TYPE redbase_pool_requests_failed_total counter\n\
+         redbase_pool_requests_failed_total{{reason=\"timeout\"}} {}\n\
+         redbase_pool_requests_failed_total{{reason=\"queue_full\"}} {}\n\
+         # HELP redbase_pool_retries_total Retries of opening a pooled connection after a transient error\n\
+         # TYPE redbase_pool_retries_total counter\n\
+         redbase_pool_retries_total {}\n",
+        sst_files,
+        memstore_entries,
+        stalled,
+        state.stall_rejections.load(Ordering::Relaxed),
+        circuit_state,
+        breaker.opened,
+        breaker.half_opened,
+        breaker.closed,
+        breaker.rejected,
+        pool_connections,
+        pool_waiting,
+        usage.created,
+        usage.reused,
+        usage.evicted_idle,
+        usage.evicted_age,
+        usage.failed_checks,
+        usage.wait_timeouts,
+        usage.queue_rejections,
+        usage.retries,
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(body))
+}
+
+/// List the column families of a table
+#[instrument(skip_all, fields(table = %path.table))]
+#[utoipa::path(
+    get,
+    path = "/tables/{table}/cf",
+    tag = "tables",
+    params(TablePath),
+    responses((status = 200, description = "Names of the column families"))
+)]
+async fn list_cfs(
+    state: web::Data<AppState>,
+    path: web::Path<TablePath>,
+) -> Result<impl Responder, actix_web::Error> {
+    let TablePath { namespace, table: table_name } = path.into_inner();
+    let table = table_handle(&state, namespace.as_deref(), &table_name, None).await?;
+
+    let names = table.cf_names().await.map_err(|e| {
+        ErrorInternalServerError(format!("Failed to list column families: {}", e))
+    })?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "table": table_name,
+        "column_families": names
+    })))
+}
+
+/// List the namespaces
+#[utoipa::path(
+    get,
+    path = "/namespaces",
+    tag = "namespaces",
+    responses((status = 200, description = "Names of the namespaces"))
+)]
+async fn list_namespaces(state: web::Data<AppState>) -> Result<impl Responder, actix_web::Error> {
+    let root = state.namespaces.root().to_path_buf();
+    let names = web::block(move || namespace::list_namespaces(root))
+        .await?
+        .map_err(|e| namespace_error("Failed to list namespaces", e))?;
+
+    Ok(HttpResponse::Ok().json(json!({ "namespaces": names })))
+}
+
+/// Create a namespace
+#[instrument(skip_all, fields(namespace = %req.name))]
+#[utoipa::path(
+    post,
+    path = "/namespaces",
+    tag = "namespaces",
+    request_body = CreateNamedRequest,
+    responses((status = 201, description = "Namespace created"), (status = 400, description = "Invalid name"), (status = 409, description = "Namespace exists"))
+)]
+async fn create_namespace(
+    state: web::Data<AppState>,
+    req: web::Json<CreateNamedRequest>,
+) -> Result<impl Responder, actix_web::Error> {
+    let root = state.namespaces.root().to_path_buf();
+    let name = req.name.clone();
+    web::block(move || namespace::create_namespace(root, &name))
+        .await?
+        .map_err(|e| namespace_error("Failed to create namespace", e))?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "status": "created",
+        "namespace": req.name
+    })))
+}
+
+/// List the tables of a namespace
+#[instrument(skip_all, fields(namespace = %path))]
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/tables",
+    tag = "namespaces",
+    params(("namespace" = String, Path, description = "Name of the namespace")),
+    responses((status = 200, description = "Names of the tables"), (status = 404, description = "Namespace not found"))
+)]
+async fn list_tables(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<impl Responder, actix_web::Error> {
+    let ns = path.into_inner();
+    let root = state.namespaces.root().to_path_buf();
+    let names = {
+        let ns = ns.clone();
+        web::block(move || namespace::list_tables(root, &ns))
+            .await?
+            .map_err(|e| namespace_error("Failed to list tables", e))?
+    };
+
+    Ok(HttpResponse::Ok().json(json!({
+        "namespace": ns,
+        "tables": names
+    })))
+}
+
+/// Create a table in a namespace
+#[instrument(skip_all, fields(namespace = %path, table = %req.name))]
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/tables",
+    tag = "namespaces",
+    request_body = CreateNamedRequest,
+    params(("namespace" = String, Path, description = "Name of the namespace")),
+    responses((status = 201, description = "Table created"), (status = 404, description = "Namespace not found"), (status = 409, description = "Table exists"))
+)]
+async fn create_table(
+    state: web::Data<AppState>,
+    path: web::Path<String>,
+    req: web::Json<CreateNamedRequest>,
+) -> Result<impl Responder, actix_web::Error> {
+    let ns = path.into_inner();
+    let root = state.namespaces.root().to_path_buf();
+    {
+        let (ns, name) = (ns.clone(), req.name.clone());
+        web::block(move || Namespace::open(root, &ns)?.create_table(&name).map(|_| ()))
+            .await?
+            .map_err(|e| namespace_error("Failed to create table", e))?;
+    }
+
+    Ok(HttpResponse::Created().json(json!({
+        "status": "created",
+        "namespace": ns,
+        "table": req.name
+    })))
+}
+
 /// Put a value
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/cf/{cf}/put",
+    tag = "writes",
+    request_body = PutRequest,
+    params(CfPath, ("x-redbase-request-id" = Option<String>, Header, description = "Client-chosen ID making the write idempotent: retries with the same ID are not applied again")),
+    responses((status = 200, description = "Value written, with its commit token as seq"), (status = 404, description = "Column family not found"), (status = 409, description = "Column family is frozen"), (status = 422, description = "Rejected by a column validator or size limit"), (status = 503, description = "Writes stalled or throttled, or circuit open"))
+)]
 async fn put(
     state: web::Data<AppState>,
-    path: web::Path<(String, String)>,
+    path: web::Path<CfPath>,
+    http: HttpRequest,
     req: web::Json<PutRequest>,
 ) -> Result<impl Responder, actix_web::Error> {
-    let (table_name, cf_name) = path.into_inner();
-    let conn = state.pool.get().await.map_err(|e| {
-        ErrorInternalServerError(format!("Failed to get connection from pool: {}", e))
-    })?;
-
-    let cf = conn.table.cf(&cf_name).await.ok_or_else(|| {
-        ErrorNotFound(format!("Column family not found: {}", cf_name))
-    })?;
+    let mut batch = request_batch(&http)?;
+    let cf = column_family(&state, &path, None).await?;
+    let CfPath { table: table_name, cf: cf_name, .. } = path.into_inner();
+    check_write_stall(&state, &cf_name, &cf).await?;
 
-    cf.put(
+    batch.put(
         req.row.clone().into_bytes(),
         req.column.clone().into_bytes(),
         req.value.clone().into_bytes(),
-    ).await.map_err(|e| {
-        ErrorInternalServerError(format!("Failed to put value: {}", e))
-    })?;
+    );
+    cf.execute_batch(&batch).await.map_err(|e| write_error(&state, "Failed to put value", e))?;
 
+    let seq = state.committed();
     Ok(HttpResponse::Ok().json(json!({
         "status": "ok",
         "table": table_name,
         "column_family": cf_name,
         "row": req.row,
-        "column": req.column
+        "column": req.column,
+        "seq": seq
     })))
 }
 
 /// Delete a value
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/cf/{cf}/delete",
+    tag = "writes",
+    request_body = DeleteRequest,
+    params(CfPath, ("x-redbase-request-id" = Option<String>, Header, description = "Client-chosen ID making the write idempotent: retries with the same ID are not applied again")),
+    responses((status = 200, description = "Delete marker written, with its commit token as seq"), (status = 404, description = "Column family not found"), (status = 409, description = "Column family is frozen"), (status = 422, description = "Rejected by a column validator or size limit"), (status = 503, description = "Writes stalled or throttled, or circuit open"))
+)]
 async fn delete(
     state: web::Data<AppState>,
-    path: web::Path<(String, String)>,
+    path: web::Path<CfPath>,
+    http: HttpRequest,
     req: web::Json<DeleteRequest>,
 ) -> Result<impl Responder, actix_web::Error> {
-    let (table_name, cf_name) = path.into_inner();
-    let conn = state.pool.get().await.map_err(|e| {
-        ErrorInternalServerError(format!("Failed to get connection from pool: {}", e))
-    })?;
+    let mut batch = request_batch(&http)?;
+    let cf = column_family(&state, &path, None).await?;
+    let CfPath { table: table_name, cf: cf_name, .. } = path.into_inner();
+    check_write_stall(&state, &cf_name, &cf).await?;
 
-    let cf = conn.table.cf(&cf_name).await.ok_or_else(|| {
-        ErrorNotFound(format!("Column family not found: {}", cf_name))
-    })?;
-
-    if let Some(ttl_ms) = req.ttl_ms {
-        cf.delete_with_ttl(
-            req.row.clone().into_bytes(),
-            req.column.clone().into_bytes(),
-            Some(ttl_ms),
-        ).await.map_err(|e| {
-            ErrorInternalServerError(format!("Failed to delete value: {}", e))
-        })?;
-    } else {
-        cf.delete(
-            req.row.clone().into_bytes(),
-            req.column.clone().into_bytes(),
-        ).await.map_err(|e| {
-            ErrorInternalServerError(format!("Failed to delete value: {}", e))
-        })?;
-    }
+    batch.delete_with_ttl(
+        req.row.clone().into_bytes(),
+        req.column.clone().into_bytes(),
+        req.ttl_ms,
+    );
+    cf.execute_batch(&batch).await.map_err(|e| write_error(&state, "Failed to delete value", e))?;
 
+    let seq = state.committed();
     Ok(HttpResponse::Ok().json(json!({
         "status": "ok",
         "table": table_name,
         "column_family": cf_name,
         "row": req.row,
-        "column": req.column
+        "column": req.column,
+        "seq": seq
+    })))
+}
+
+/// Apply entries shipped by a replicator on another instance, keeping their timestamps
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf, entries = req.entries.len()))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/cf/{cf}/replicate",
+    tag = "writes",
+    request_body = ReplicateRequest,
+    params(CfPath),
+    responses((status = 200, description = "Entries applied, with their commit token as seq"), (status = 404, description = "Column family not found"), (status = 409, description = "Column family is frozen"), (status = 422, description = "Rejected by a column validator or size limit"), (status = 503, description = "Writes stalled or throttled, or circuit open"))
+)]
+async fn replicate(
+    state: web::Data<AppState>,
+    path: web::Path<CfPath>,
+    req: web::Json<ReplicateRequest>,
+) -> Result<impl Responder, actix_web::Error> {
+    let cf = column_family(&state, &path, None).await?;
+    let CfPath { table: table_name, cf: cf_name, .. } = path.into_inner();
+    check_write_stall(&state, &cf_name, &cf).await?;
+
+    let ReplicateRequest { entries } = req.into_inner();
+    let count = entries.len();
+    cf.write_entries(entries).await.map_err(|e| write_error(&state, "Failed to apply replicated entries", e))?;
+
+    let seq = state.committed();
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "ok",
+        "table": table_name,
+        "column_family": cf_name,
+        "entries_count": count,
+        "seq": seq
     })))
 }
 
 /// Execute a batch of operations
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/cf/{cf}/batch",
+    tag = "writes",
+    request_body = BatchRequest,
+    params(CfPath, ("x-redbase-request-id" = Option<String>, Header, description = "Client-chosen ID making the write idempotent: retries with the same ID are not applied again")),
+    responses((status = 200, description = "Batch written atomically, with its commit token as seq"), (status = 404, description = "Column family not found"), (status = 409, description = "Column family is frozen"), (status = 422, description = "Rejected by a column validator or size limit"), (status = 503, description = "Writes stalled or throttled, or circuit open"))
+)]
 async fn batch(
     state: web::Data<AppState>,
-    path: web::Path<(String, String)>,
+    path: web::Path<CfPath>,
+    http: HttpRequest,
     req: web::Json<BatchRequest>,
 ) -> Result<impl Responder, actix_web::Error> {
-    let (table_name, cf_name) = path.into_inner();
-    let conn = state.pool.get().await.map_err(|e| {
-        ErrorInternalServerError(format!("Failed to get connection from pool: {}", e))
-    })?;
-
-    let cf = conn.table.cf(&cf_name).await.ok_or_else(|| {
-        ErrorNotFound(format!("Column family not found: {}", cf_name))
-    })?;
-
-    let mut batch = Batch::new();
+    let mut batch = request_batch(&http)?;
+    let cf = column_family(&state, &path, None).await?;
+    let CfPath { table: table_name, cf: cf_name, .. } = path.into_inner();
+    check_write_stall(&state, &cf_name, &cf).await?;
 
     for op in &req.operations {
         match op {
@@ -355,32 +1228,74 @@ async fn batch(
         }
     }
 
-    cf.execute_batch(&batch).await.map_err(|e| {
-        ErrorInternalServerError(format!("Failed to execute batch: {}", e))
-    })?;
+    cf.execute_batch(&batch).await.map_err(|e| write_error(&state, "Failed to execute batch", e))?;
+
+    let seq = state.committed();
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "ok",
+        "table": table_name,
+        "column_family": cf_name,
+        "operations_count": req.operations.len(),
+        "seq": seq
+    })))
+}
 
+/// Put several cells in one batch
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf, cells = req.cells.len()))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/cf/{cf}/mput",
+    tag = "writes",
+    request_body = MultiPutRequest,
+    params(CfPath, ("x-redbase-request-id" = Option<String>, Header, description = "Client-chosen ID making the write idempotent: retries with the same ID are not applied again")),
+    responses((status = 200, description = "Cells written atomically, with their commit token as seq"), (status = 404, description = "Column family not found"), (status = 409, description = "Column family is frozen"), (status = 422, description = "Rejected by a column validator or size limit"), (status = 503, description = "Writes stalled or throttled, or circuit open"))
+)]
+async fn multi_put(
+    state: web::Data<AppState>,
+    path: web::Path<CfPath>,
+    http: HttpRequest,
+    req: web::Json<MultiPutRequest>,
+) -> Result<impl Responder, actix_web::Error> {
+    let mut batch = request_batch(&http)?;
+    let cf = column_family(&state, &path, None).await?;
+    let CfPath { table: table_name, cf: cf_name, .. } = path.into_inner();
+    check_write_stall(&state, &cf_name, &cf).await?;
+
+    let MultiPutRequest { cells } = req.into_inner();
+    let count = cells.len();
+    for cell in cells {
+        batch.put(cell.row.into_bytes(), cell.column.into_bytes(), cell.value.into_bytes());
+    }
+    cf.execute_batch(&batch).await.map_err(|e| write_error(&state, "Failed to put values", e))?;
+
+    let seq = state.committed();
     Ok(HttpResponse::Ok().json(json!({
         "status": "ok",
         "table": table_name,
         "column_family": cf_name,
-        "operations_count": req.operations.len()
+        "cells_count": count,
+        "seq": seq
     })))
 }
 
 /// Get a value
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/cf/{cf}/get",
+    tag = "reads",
+    request_body = GetRequest,
+    params(CfPath, ReadConsistency),
+    responses((status = 200, description = "Latest value, or the requested versions"), (status = 404, description = "Cell has no value, or column family not found"), (status = 503, description = "Write min_seq not visible in time, or circuit open"))
+)]
 async fn get(
     state: web::Data<AppState>,
-    path: web::Path<(String, String)>,
+    path: web::Path<CfPath>,
+    query: web::Query<ReadConsistency>,
     req: web::Json<GetRequest>,
 ) -> Result<impl Responder, actix_web::Error> {
-    let (table_name, cf_name) = path.into_inner();
-    let conn = state.pool.get().await.map_err(|e| {
-        ErrorInternalServerError(format!("Failed to get connection from pool: {}", e))
-    })?;
-
-    let cf = conn.table.cf(&cf_name).await.ok_or_else(|| {
-        ErrorNotFound(format!("Column family not found: {}", cf_name))
-    })?;
+    let cf = column_family(&state, &path, query.min_seq).await?;
+    let CfPath { table: table_name, cf: cf_name, .. } = path.into_inner();
 
     if let Some(max_versions) = req.max_versions {
         // Get multiple versions
@@ -426,64 +1341,444 @@ async fn get(
     }
 }
 
+/// Check whether a cell has a value
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/cf/{cf}/exists",
+    tag = "reads",
+    request_body = ExistsRequest,
+    params(CfPath, ReadConsistency),
+    responses((status = 200, description = "Whether the cell has a value"), (status = 404, description = "Column family not found"), (status = 503, description = "Write min_seq not visible in time, or circuit open"))
+)]
+async fn exists(
+    state: web::Data<AppState>,
+    path: web::Path<CfPath>,
+    query: web::Query<ReadConsistency>,
+    req: web::Json<ExistsRequest>,
+) -> Result<impl Responder, actix_web::Error> {
+    let cf = column_family(&state, &path, query.min_seq).await?;
+    let exists = cf.exists(req.row.as_bytes(), req.column.as_bytes()).await.map_err(|e| {
+        ErrorInternalServerError(format!("Failed to check value: {}", e))
+    })?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "row": req.row,
+        "column": req.column,
+        "exists": exists
+    })))
+}
+
+/// Get the latest values of several cells
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf, cells = req.cells.len()))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/cf/{cf}/mget",
+    tag = "reads",
+    request_body = MultiGetRequest,
+    params(CfPath, ReadConsistency),
+    responses((status = 200, description = "Latest values in request order, null for cells without one"), (status = 404, description = "Column family not found"), (status = 503, description = "Write min_seq not visible in time, or circuit open"))
+)]
+async fn multi_get(
+    state: web::Data<AppState>,
+    path: web::Path<CfPath>,
+    query: web::Query<ReadConsistency>,
+    req: web::Json<MultiGetRequest>,
+) -> Result<impl Responder, actix_web::Error> {
+    let cf = column_family(&state, &path, query.min_seq).await?;
+    let cells = req.cells.iter()
+        .map(|cell| (cell.row.as_bytes().to_vec(), cell.column.as_bytes().to_vec()))
+        .collect();
+    let values = cf.multi_get(cells).await.map_err(|e| {
+        ErrorInternalServerError(format!("Failed to get values: {}", e))
+    })?;
+
+    let results: Vec<_> = req.cells.iter().zip(values)
+        .map(|(cell, value)| {
+            json!({
+                "row": cell.row,
+                "column": cell.column,
+                "value": value.map(|v| String::from_utf8_lossy(&v).to_string())
+            })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(json!({ "results": results })))
+}
+
 /// Scan a row
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/cf/{cf}/scan",
+    tag = "reads",
+    request_body = ScanRequest,
+    params(CfPath, ReadConsistency),
+    responses((status = 200, description = "Versions of every column in the row"), (status = 404, description = "Column family not found"), (status = 503, description = "Write min_seq not visible in time, or circuit open"))
+)]
 async fn scan(
     state: web::Data<AppState>,
-    path: web::Path<(String, String)>,
+    path: web::Path<CfPath>,
+    query: web::Query<ReadConsistency>,
     req: web::Json<ScanRequest>,
 ) -> Result<impl Responder, actix_web::Error> {
-    let (table_name, cf_name) = path.into_inner();
-    let conn = state.pool.get().await.map_err(|e| {
-        ErrorInternalServerError(format!("Failed to get connection from pool: {}", e))
-    })?;
+    let cf = column_family(&state, &path, query.min_seq).await?;
 
-    let cf = conn.table.cf(&cf_name).await.ok_or_else(|| {
-        ErrorNotFound(format!("Column family not found: {}", cf_name))
+    let max_versions = req.max_versions_per_column.unwrap_or(1);
+    let row = req.row.as_bytes().to_vec();
+    let result = if req.raw {
+        let mut scan = Scan::new(row.clone(), row.clone());
+        scan.set_max_versions(max_versions).set_raw(true);
+        cf.scan_rows(scan).await.map(|rows| rows.into_iter().next().unwrap_or_default())
+    } else {
+        cf.scan_row_versions(&row, max_versions).await.map(|versions| Row::from_versions(row, versions))
+    };
+    let result = result.map_err(|e| {
+        ErrorInternalServerError(format!("Failed to scan row: {}", e))
     })?;
 
-    let max_versions = req.max_versions_per_column.unwrap_or(1);
-    let result = cf.scan_row_versions(
-        req.row.as_bytes(),
-        max_versions,
+    Ok(HttpResponse::Ok().json(row_json(&result)))
+}
+
+/// Scan a range of rows
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/cf/{cf}/scan_range",
+    tag = "reads",
+    request_body = ScanRangeRequest,
+    params(CfPath, ReadConsistency),
+    responses((status = 200, description = "Rows of the range; a truncated or timed out response carries an X-RedBase-Continuation header"), (status = 400, description = "Invalid continuation token"), (status = 404, description = "Column family not found"), (status = 503, description = "Write min_seq not visible in time, or circuit open"))
+)]
+async fn scan_range(
+    state: web::Data<AppState>,
+    path: web::Path<CfPath>,
+    query: web::Query<ReadConsistency>,
+    req: web::Json<ScanRangeRequest>,
+) -> Result<impl Responder, actix_web::Error> {
+    let cf = column_family(&state, &path, query.min_seq).await?;
+
+    let start = match &req.continuation {
+        Some(token) => ResumeToken::decode(token).map_err(|e| ErrorBadRequest(e.to_string()))?,
+        None => ResumeToken::at_row(req.start_row.as_bytes()),
+    };
+    let options = ScanOptions {
+        max_versions: req.max_versions_per_column.unwrap_or(1),
+        max_response_bytes: Some(req.max_response_bytes.map_or(state.max_scan_response_bytes, |max| {
+            max.min(state.max_scan_response_bytes)
+        })),
+        deadline: Some(state.deadline(req.timeout_ms)),
+    };
+    let batch = cf.resume_scan(
+        &start,
+        req.end_row.as_bytes(),
+        &options,
     ).await.map_err(|e| {
-        ErrorInternalServerError(format!("Failed to scan row: {}", e))
+        ErrorInternalServerError(format!("Failed to scan range: {}", e))
     })?;
 
-    let mut response = serde_json::Map::new();
+    let response = rows_json(batch.rows);
 
-    for (column, versions) in result {
-        let column_str = String::from_utf8_lossy(&column).to_string();
-        let versions_json: Vec<_> = versions.into_iter()
-            .map(|(ts, value)| {
-                json!({
-                    "timestamp": ts,
-                    "value": String::from_utf8_lossy(&value).to_string()
-                })
+    let mut builder = HttpResponse::Ok();
+    if let Some(token) = batch.continuation {
+        builder.insert_header((CONTINUATION_HEADER, token.encode()));
+    }
+    if batch.timed_out {
+        builder.insert_header((TIMED_OUT_HEADER, "true"));
+    }
+    Ok(builder.json(response))
+}
+
+/// Rows of a range scan as JSON: row -> column -> [{timestamp, value}], newest first
+fn rows_json(rows: RangeVersions) -> serde_json::Map<String, serde_json::Value> {
+    Row::from_range(rows)
+        .iter()
+        .map(|row| (String::from_utf8_lossy(row.row()).to_string(), json!(row_json(row))))
+        .collect()
+}
+
+/// Cells of a row as JSON: column -> [{timestamp, value}], newest first, with delete
+/// markers as {timestamp, deleted: true}
+fn row_json(row: &Row) -> serde_json::Map<String, serde_json::Value> {
+    let mut response = serde_json::Map::new();
+    for column in row.columns() {
+        let versions_json: Vec<_> = row.versions(column).iter()
+            .map(|cell| match cell.is_delete {
+                true => json!({ "timestamp": cell.timestamp, "deleted": true }),
+                false => json!({
+                    "timestamp": cell.timestamp,
+                    "value": String::from_utf8_lossy(&cell.value).to_string()
+                }),
             })
             .collect();
+        response.insert(String::from_utf8_lossy(column).to_string(), json!(versions_json));
+    }
+    response
+}
 
-        response.insert(column_str, json!(versions_json));
+/// Stream a range scan, then optionally live changes, over a WebSocket
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf))]
+#[utoipa::path(
+    get,
+    path = "/tables/{table}/cf/{cf}/stream",
+    tag = "reads",
+    params(CfPath, StreamRequest, ReadConsistency),
+    responses(
+        (status = 101, description = "WebSocket of JSON messages: rows, scan_complete, change and error"),
+        (status = 400, description = "Not a WebSocket upgrade, or only one of start_row and end_row"),
+        (status = 404, description = "Column family not found"),
+    )
+)]
+async fn stream(
+    state: web::Data<AppState>,
+    path: web::Path<CfPath>,
+    consistency: web::Query<ReadConsistency>,
+    query: web::Query<StreamRequest>,
+    req: HttpRequest,
+    body: web::Payload,
+) -> Result<impl Responder, actix_web::Error> {
+    let query = query.into_inner();
+    if query.start_row.is_some() != query.end_row.is_some() {
+        return Err(ErrorBadRequest("start_row and end_row must be given together"));
     }
+    let cf = column_family(&state, &path, consistency.min_seq).await?;
+    let (response, session, messages) = actix_ws::handle(&req, body)?;
+
+    // Start tailing before scanning, so no write between the two is missed. Every pooled
+    // connection has its own handle on the table, so changes are read from the changelog,
+    // which all of them write, rather than from one handle's subscribers.
+    let changes = match query.subscribe.clone() {
+        Some(prefix) => {
+            cf.enable_changelog().await.map_err(|e| {
+                ErrorInternalServerError(format!("Failed to enable the changelog: {}", e))
+            })?;
+            let cf_path = cf.path().to_path_buf();
+            let reader = tokio::task::spawn_blocking(move || {
+                let end = std::fs::metadata(cf_path.join(CHANGELOG_FILE))?.len();
+                ChangelogReader::open(&cf_path, end)
+            }).await.unwrap().map_err(|e| {
+                ErrorInternalServerError(format!("Failed to open the changelog: {}", e))
+            })?;
+            Some((prefix, reader))
+        }
+        None => None,
+    };
+
+    let batch_bytes = query.batch_bytes.map_or(STREAM_BATCH_BYTES, |bytes| bytes.min(state.max_scan_response_bytes));
+    actix_web::rt::spawn(async move {
+        let options = ScanOptions {
+            max_versions: query.max_versions.unwrap_or(1),
+            max_response_bytes: Some(batch_bytes),
+            deadline: None,
+        };
+        let session = match (query.start_row, query.end_row) {
+            (Some(start_row), Some(end_row)) => stream_scan(session, &cf, start_row, end_row, options).await,
+            _ => Some(session),
+        };
+        match (session, changes) {
+            (Some(session), Some((prefix, reader))) => stream_changes(session, messages, prefix, reader).await,
+            (Some(session), None) => {
+                let _ = session.close(None).await;
+            }
+            (None, _) => {}
+        }
+    });
+    Ok(response)
+}
 
-    Ok(HttpResponse::Ok().json(response))
+/// Send the rows of [start_row, end_row] in batches, then a scan_complete message.
+/// Returns the session unless it failed or the client went away.
+async fn stream_scan(
+    mut session: actix_ws::Session,
+    cf: &ColumnFamily,
+    start_row: String,
+    end_row: String,
+    options: ScanOptions,
+) -> Option<actix_ws::Session> {
+    let mut token = ResumeToken::at_row(start_row.as_bytes());
+    let mut rows = 0;
+    loop {
+        let batch = match cf.resume_scan(&token, end_row.as_bytes(), &options).await {
+            Ok(batch) => batch,
+            Err(e) => {
+                let message = json!({ "type": "error", "message": format!("Failed to scan range: {}", e) });
+                let _ = session.text(message.to_string()).await;
+                let _ = session.close(None).await;
+                return None;
+            }
+        };
+        rows += batch.rows.len();
+        if !batch.rows.is_empty() {
+            let message = json!({ "type": "rows", "rows": rows_json(batch.rows) });
+            session.text(message.to_string()).await.ok()?;
+        }
+        match batch.continuation {
+            Some(next) => token = next,
+            None => break,
+        }
+    }
+    session.text(json!({ "type": "scan_complete", "rows_count": rows }).to_string()).await.ok()?;
+    Some(session)
 }
 
-/// Filter a row
-async fn filter(
+/// Send every change to a row starting with prefix, as read from the changelog, until
+/// the client closes the socket.
+async fn stream_changes(
+    mut session: actix_ws::Session,
+    mut messages: actix_ws::MessageStream,
+    prefix: String,
+    mut reader: ChangelogReader,
+) {
+    use futures::StreamExt;
+
+    let mut poll = tokio::time::interval(CHANGE_POLL_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = poll.tick() => {
+                let (returned, records) = tokio::task::spawn_blocking(move || {
+                    let records = reader.poll(1000);
+                    (reader, records)
+                }).await.unwrap();
+                reader = returned;
+                let records = match records {
+                    Ok(records) => records,
+                    Err(e) => {
+                        let message = json!({ "type": "error", "message": format!("Failed to read changes: {}", e) });
+                        let _ = session.text(message.to_string()).await;
+                        break;
+                    }
+                };
+                for ChangeRecord { entry, .. } in records {
+                    if !entry.key.row.starts_with(prefix.as_bytes()) {
+                        continue;
+                    }
+                    let value = match &entry.value {
+                        CellValue::Put(value) => Some(String::from_utf8_lossy(value).to_string()),
+                        CellValue::Delete(_) | CellValue::DeleteFamily => None,
+                    };
+                    let message = json!({
+                        "type": "change",
+                        "row": String::from_utf8_lossy(&entry.key.row),
+                        "column": String::from_utf8_lossy(&entry.key.column),
+                        "timestamp": entry.key.timestamp,
+                        "value": value,
+                        "deleted": value.is_none()
+                    });
+                    if session.text(message.to_string()).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            message = messages.next() => match message {
+                Some(Ok(actix_ws::Message::Ping(bytes))) => {
+                    if session.pong(&bytes).await.is_err() {
+                        return;
+                    }
+                }
+                Some(Ok(actix_ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+    let _ = session.close(None).await;
+}
+
+/// Count the rows in a range
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/cf/{cf}/count",
+    tag = "reads",
+    request_body = CountRequest,
+    params(CfPath, ReadConsistency),
+    responses((status = 200, description = "Number of rows"), (status = 400, description = "Invalid filter"), (status = 404, description = "Column family not found"), (status = 503, description = "Write min_seq not visible in time, or circuit open"), (status = 504, description = "Count timed out"))
+)]
+async fn count(
     state: web::Data<AppState>,
-    path: web::Path<(String, String)>,
-    req: web::Json<FilterRequest>,
+    path: web::Path<CfPath>,
+    query: web::Query<ReadConsistency>,
+    req: web::Json<CountRequest>,
 ) -> Result<impl Responder, actix_web::Error> {
-    let (table_name, cf_name) = path.into_inner();
-    let conn = state.pool.get().await.map_err(|e| {
-        ErrorInternalServerError(format!("Failed to get connection from pool: {}", e))
+    let cf = column_family(&state, &path, query.min_seq).await?;
+    let CfPath { table: table_name, cf: cf_name, .. } = path.into_inner();
+
+    let filter_set = req.filter_set.as_ref().map(|fs| convert_filter_set(fs.clone())).transpose()?;
+    let count = cf.count_rows_until(
+        req.start_row.as_bytes(),
+        req.end_row.as_bytes(),
+        filter_set.as_ref(),
+        state.deadline(req.timeout_ms),
+    ).await.map_err(|e| match e.kind() {
+        std::io::ErrorKind::TimedOut => ErrorGatewayTimeout(e.to_string()),
+        _ => ErrorInternalServerError(format!("Failed to count rows: {}", e)),
     })?;
 
-    let cf = conn.table.cf(&cf_name).await.ok_or_else(|| {
-        ErrorNotFound(format!("Column family not found: {}", cf_name))
+    Ok(HttpResponse::Ok().json(json!({
+        "table": table_name,
+        "column_family": cf_name,
+        "count": count
+    })))
+}
+
+/// Run a SQL-like query against a column family of the table
+#[instrument(skip_all, fields(table = %path.table))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/query",
+    tag = "tables",
+    request_body = QueryRequest,
+    params(TablePath, ReadConsistency),
+    responses((status = 200, description = "Columns and rows of the result"), (status = 400, description = "Malformed query"), (status = 404, description = "Column family not found"), (status = 504, description = "Query timed out"))
+)]
+async fn query(
+    state: web::Data<AppState>,
+    path: web::Path<TablePath>,
+    consistency: web::Query<ReadConsistency>,
+    req: web::Json<QueryRequest>,
+) -> Result<impl Responder, actix_web::Error> {
+    let table = table_handle(&state, path.namespace.as_deref(), &path.table, consistency.min_seq).await?;
+
+    let result = table.query_until(&req.sql, state.deadline(req.timeout_ms)).await.map_err(|e| match e.kind() {
+        std::io::ErrorKind::InvalidInput | std::io::ErrorKind::InvalidData => ErrorBadRequest(e.to_string()),
+        std::io::ErrorKind::NotFound => ErrorNotFound(e.to_string()),
+        std::io::ErrorKind::TimedOut => ErrorGatewayTimeout(e.to_string()),
+        _ => ErrorInternalServerError(format!("Failed to run query: {}", e)),
     })?;
 
-    let filter_set = convert_filter_set(req.filter_set.clone());
+    let rows: Vec<Vec<serde_json::Value>> = result.rows.into_iter()
+        .map(|row| row.into_iter().map(|value| match value {
+            QueryValue::Null => serde_json::Value::Null,
+            QueryValue::Bytes(bytes) => json!(String::from_utf8_lossy(&bytes)),
+            QueryValue::Integer(n) => json!(n),
+            QueryValue::Float(x) => json!(x),
+        }).collect())
+        .collect();
+    Ok(HttpResponse::Ok().json(json!({
+        "table": path.table,
+        "columns": result.columns,
+        "rows": rows
+    })))
+}
+
+/// Filter a row
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/cf/{cf}/filter",
+    tag = "reads",
+    request_body = FilterRequest,
+    params(CfPath, ReadConsistency),
+    responses((status = 200, description = "Matching versions of the row"), (status = 400, description = "Invalid filter"), (status = 404, description = "Column family not found"), (status = 503, description = "Write min_seq not visible in time, or circuit open"))
+)]
+async fn filter(
+    state: web::Data<AppState>,
+    path: web::Path<CfPath>,
+    query: web::Query<ReadConsistency>,
+    req: web::Json<FilterRequest>,
+) -> Result<impl Responder, actix_web::Error> {
+    let cf = column_family(&state, &path, query.min_seq).await?;
+
+    let filter_set = convert_filter_set(req.filter_set.clone())?;
     let result = cf.scan_row_with_filter(
         req.row.as_bytes(),
         &filter_set,
@@ -511,21 +1806,24 @@ async fn filter(
 }
 
 /// Aggregate a row
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/cf/{cf}/aggregate",
+    tag = "reads",
+    request_body = AggregationRequest,
+    params(CfPath, ReadConsistency),
+    responses((status = 200, description = "Aggregation results by column"), (status = 400, description = "Invalid filter or aggregation"), (status = 404, description = "Column family not found"), (status = 503, description = "Write min_seq not visible in time, or circuit open"))
+)]
 async fn aggregate(
     state: web::Data<AppState>,
-    path: web::Path<(String, String)>,
+    path: web::Path<CfPath>,
+    query: web::Query<ReadConsistency>,
     req: web::Json<AggregationRequest>,
 ) -> Result<impl Responder, actix_web::Error> {
-    let (table_name, cf_name) = path.into_inner();
-    let conn = state.pool.get().await.map_err(|e| {
-        ErrorInternalServerError(format!("Failed to get connection from pool: {}", e))
-    })?;
+    let cf = column_family(&state, &path, query.min_seq).await?;
 
-    let cf = conn.table.cf(&cf_name).await.ok_or_else(|| {
-        ErrorNotFound(format!("Column family not found: {}", cf_name))
-    })?;
-
-    let filter_set = req.filter_set.as_ref().map(|fs| convert_filter_set(fs.clone()));
+    let filter_set = req.filter_set.as_ref().map(|fs| convert_filter_set(fs.clone())).transpose()?;
     let aggregation_set = convert_aggregation_set(req.aggregation_set.clone())?;
 
     let result = cf.aggregate(
@@ -550,20 +1848,24 @@ async fn aggregate(
 }
 
 /// Flush a column family
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/cf/{cf}/flush",
+    tag = "admin",
+    params(CfPath),
+    responses((status = 200, description = "MemStore flushed"), (status = 404, description = "Column family not found"))
+)]
 async fn flush(
     state: web::Data<AppState>,
-    path: web::Path<(String, String)>,
+    path: web::Path<CfPath>,
 ) -> Result<impl Responder, actix_web::Error> {
-    let (table_name, cf_name) = path.into_inner();
-    let conn = state.pool.get().await.map_err(|e| {
-        ErrorInternalServerError(format!("Failed to get connection from pool: {}", e))
-    })?;
-
-    let cf = conn.table.cf(&cf_name).await.ok_or_else(|| {
-        ErrorNotFound(format!("Column family not found: {}", cf_name))
-    })?;
+    let cf = column_family(&state, &path, None).await?;
+    let CfPath { table: table_name, cf: cf_name, .. } = path.into_inner();
 
-    cf.flush().await.map_err(|e| {
+    let result = cf.flush().await;
+    state.pool.record(&result);
+    result.map_err(|e| {
         ErrorInternalServerError(format!("Failed to flush column family: {}", e))
     })?;
 
@@ -574,55 +1876,1378 @@ async fn flush(
     })))
 }
 
-/// Compact a column family
+/// Freeze a column family for read-only serving
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/cf/{cf}/freeze",
+    tag = "admin",
+    params(CfPath),
+    responses((status = 200, description = "Column family frozen"), (status = 404, description = "Column family not found"))
+)]
+async fn freeze(
+    state: web::Data<AppState>,
+    path: web::Path<CfPath>,
+) -> Result<impl Responder, actix_web::Error> {
+    let cf = column_family(&state, &path, None).await?;
+    let CfPath { table: table_name, cf: cf_name, .. } = path.into_inner();
+
+    cf.freeze().await.map_err(|e| {
+        ErrorInternalServerError(format!("Failed to freeze column family: {}", e))
+    })?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "frozen",
+        "table": table_name,
+        "column_family": cf_name
+    })))
+}
+
+/// Compact a column family, minor unless the body asks otherwise
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/cf/{cf}/compact",
+    tag = "admin",
+    request_body(content = Option<CompactRequest>, description = "Compaction options; may be empty"),
+    params(CfPath),
+    responses(
+        (status = 200, description = "Compaction done"),
+        (status = 400, description = "Invalid compaction options"),
+        (status = 404, description = "Column family not found"),
+    )
+)]
 async fn compact(
     state: web::Data<AppState>,
-    path: web::Path<(String, String)>,
+    path: web::Path<CfPath>,
+    body: web::Bytes,
 ) -> Result<impl Responder, actix_web::Error> {
-    let (table_name, cf_name) = path.into_inner();
-    let conn = state.pool.get().await.map_err(|e| {
-        ErrorInternalServerError(format!("Failed to get connection from pool: {}", e))
+    let req: CompactRequest = if body.is_empty() {
+        CompactRequest::default()
+    } else {
+        serde_json::from_slice(&body).map_err(|e| ErrorBadRequest(format!("Invalid compaction options: {}", e)))?
+    };
+    let options = req.options()?;
+    let cf = column_family(&state, &path, None).await?;
+    let CfPath { table: table_name, cf: cf_name, .. } = path.into_inner();
+
+    let compaction_type = options.compaction_type;
+    let result = cf.compact_with_options(options).await;
+    state.pool.record(&result);
+    result.map_err(|e| {
+        ErrorInternalServerError(format!("Failed to compact column family: {}", e))
     })?;
 
-    let cf = conn.table.cf(&cf_name).await.ok_or_else(|| {
-        ErrorNotFound(format!("Column family not found: {}", cf_name))
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "ok",
+        "table": table_name,
+        "column_family": cf_name,
+        "type": compaction_type
+    })))
+}
+
+/// Flush the MemStore of every column family of a table
+#[instrument(skip_all, fields(table = %path.table))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/flush_all",
+    tag = "admin",
+    params(TablePath),
+    responses((status = 200, description = "Every MemStore flushed, with the names of the column families"))
+)]
+async fn flush_all(
+    state: web::Data<AppState>,
+    path: web::Path<TablePath>,
+) -> Result<impl Responder, actix_web::Error> {
+    let TablePath { namespace, table: table_name } = path.into_inner();
+    let table = table_handle(&state, namespace.as_deref(), &table_name, None).await?;
+
+    let result = table.flush_all().await;
+    state.pool.record(&result);
+    let names = result.map_err(|e| {
+        ErrorInternalServerError(format!("Failed to flush table: {}", e))
     })?;
 
-    cf.compact().await.map_err(|e| {
-        ErrorInternalServerError(format!("Failed to compact column family: {}", e))
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "ok",
+        "table": table_name,
+        "column_families": names
+    })))
+}
+
+/// Run a major compaction of every column family of a table
+#[instrument(skip_all, fields(table = %path.table))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/major_compact",
+    tag = "admin",
+    params(TablePath),
+    responses((status = 200, description = "Every column family compacted, with their names"))
+)]
+async fn major_compact(
+    state: web::Data<AppState>,
+    path: web::Path<TablePath>,
+) -> Result<impl Responder, actix_web::Error> {
+    let TablePath { namespace, table: table_name } = path.into_inner();
+    let table = table_handle(&state, namespace.as_deref(), &table_name, None).await?;
+
+    let result = table.major_compact().await;
+    state.pool.record(&result);
+    let names = result.map_err(|e| {
+        ErrorInternalServerError(format!("Failed to compact table: {}", e))
     })?;
 
     Ok(HttpResponse::Ok().json(json!({
         "status": "ok",
         "table": table_name,
-        "column_family": cf_name
+        "column_families": names
     })))
 }
 
+/// Drop expired versions and tombstones of a column family now
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf))]
+#[utoipa::path(
+    post,
+    path = "/tables/{table}/cf/{cf}/expire",
+    tag = "admin",
+    params(CfPath),
+    responses((status = 200, description = "Expired versions dropped"), (status = 404, description = "Column family not found"))
+)]
+async fn expire(
+    state: web::Data<AppState>,
+    path: web::Path<CfPath>,
+) -> Result<impl Responder, actix_web::Error> {
+    let cf = column_family(&state, &path, None).await?;
+    let CfPath { table: table_name, cf: cf_name, .. } = path.into_inner();
+
+    let expired = cf.expire_now().await.map_err(|e| {
+        ErrorInternalServerError(format!("Failed to expire column family: {}", e))
+    })?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "ok",
+        "table": table_name,
+        "column_family": cf_name,
+        "expired": expired
+    })))
+}
+
+/// Report the running and latest compactions of a column family
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf))]
+#[utoipa::path(
+    get,
+    path = "/tables/{table}/cf/{cf}/compaction_stats",
+    tag = "admin",
+    params(CfPath),
+    responses((status = 200, description = "Recent compactions"), (status = 404, description = "Column family not found"))
+)]
+async fn compaction_stats(
+    state: web::Data<AppState>,
+    path: web::Path<CfPath>,
+) -> Result<impl Responder, actix_web::Error> {
+    let cf = column_family(&state, &path, None).await?;
+    Ok(HttpResponse::Ok().json(cf.compaction_stats()))
+}
+
+/// Report the flushes, WAL writes and write stalls of a column family
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf))]
+#[utoipa::path(
+    get,
+    path = "/tables/{table}/cf/{cf}/write_stats",
+    tag = "admin",
+    params(CfPath),
+    responses((status = 200, description = "Flush and write stall counters"), (status = 404, description = "Column family not found"))
+)]
+async fn write_stats(
+    state: web::Data<AppState>,
+    path: web::Path<CfPath>,
+) -> Result<impl Responder, actix_web::Error> {
+    let cf = column_family(&state, &path, None).await?;
+    Ok(HttpResponse::Ok().json(cf.write_stats()))
+}
+
+/// Report the size and contents of a column family
+#[instrument(skip_all, fields(table = %path.table, cf = %path.cf))]
+#[utoipa::path(
+    get,
+    path = "/tables/{table}/cf/{cf}/stats",
+    tag = "admin",
+    params(CfPath),
+    responses((status = 200, description = "Storage statistics"), (status = 404, description = "Column family not found"))
+)]
+async fn stats(
+    state: web::Data<AppState>,
+    path: web::Path<CfPath>,
+) -> Result<impl Responder, actix_web::Error> {
+    let cf = column_family(&state, &path, None).await?;
+    let stats = cf.stats().await.map_err(|e| {
+        ErrorInternalServerError(format!("Failed to read column family stats: {}", e))
+    })?;
+    Ok(HttpResponse::Ok().json(stats))
+}
+
 /// Start the REST server
 pub async fn start_server(config: RestConfig) -> std::io::Result<()> {
-    let pool = ConnectionPool::new(&config.base_dir, config.pool_size);
-    let app_state = web::Data::new(AppState { pool });
+    let app_state = web::Data::new(AppState::new(&config));
 
-    println!("Starting RedBase REST server on {}:{}", config.host, config.port);
+    info!(host = %config.host, port = config.port, "starting RedBase REST server");
 
-    HttpServer::new(move || {
+    let state = app_state.clone();
+    let server = HttpServer::new(move || {
         App::new()
             .app_data(app_state.clone())
             .wrap(Logger::default())
-            .route("/health", web::get().to(health_check))
-            .route("/tables/{table}/cf", web::post().to(create_cf))
-            .route("/tables/{table}/cf/{cf}/put", web::post().to(put))
-            .route("/tables/{table}/cf/{cf}/delete", web::post().to(delete))
-            .route("/tables/{table}/cf/{cf}/batch", web::post().to(batch))
-            .route("/tables/{table}/cf/{cf}/get", web::post().to(get))
-            .route("/tables/{table}/cf/{cf}/scan", web::post().to(scan))
-            .route("/tables/{table}/cf/{cf}/filter", web::post().to(filter))
-            .route("/tables/{table}/cf/{cf}/aggregate", web::post().to(aggregate))
-            .route("/tables/{table}/cf/{cf}/flush", web::post().to(flush))
-            .route("/tables/{table}/cf/{cf}/compact", web::post().to(compact))
+            .configure(routes)
     })
     .bind(format!("{}:{}", config.host, config.port))?
-    .run()
-    .await
+    .run();
+
+    // Replay the WALs while already answering probes, which report not ready until done
+    let evicting = state.clone();
+    tokio::spawn(async move {
+        let _ = state.recover().await;
+    });
+    // Close idle connections that expired even when no request comes to find them
+    if let Some(period) = [config.pool.max_idle, config.pool.max_age].into_iter().flatten().min() {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period.clamp(Duration::from_secs(1), Duration::from_secs(60)));
+            loop {
+                interval.tick().await;
+                evicting.pool.evict_expired();
+            }
+        });
+    }
+    server.await
+}
+
+/// OpenAPI description of the REST routes; see `openapi`.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "RedBase REST API"),
+    paths(
+        health_check, readiness, metrics, list_namespaces, create_namespace, list_tables, create_table,
+        query, list_cfs, create_cf,
+        put, delete, batch, multi_put, replicate,
+        get, exists, multi_get, scan, scan_range, stream, count, filter, aggregate,
+        flush, compact, flush_all, major_compact, compaction_stats, write_stats, stats, expire, freeze,
+    ),
+    tags(
+        (name = "server", description = "Health, readiness and metrics"),
+        (name = "namespaces", description = "Namespaces and their tables"),
+        (name = "tables", description = "Column families and queries of a table"),
+        (name = "writes", description = "Writes to a column family"),
+        (name = "reads", description = "Reads from a column family"),
+        (name = "admin", description = "Maintenance of a column family"),
+    )
+)]
+struct ApiDoc;
+
+/// The OpenAPI document of the REST server, served at `/openapi.json`. Every
+/// `/tables/{table}` route is listed a second time under `/namespaces/{namespace}`.
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::openapi::{path::{ParameterBuilder, ParameterIn}, ObjectBuilder, Required, Type};
+
+    let mut doc = ApiDoc::openapi();
+    let namespace = ParameterBuilder::new()
+        .name("namespace")
+        .parameter_in(ParameterIn::Path)
+        .required(Required::True)
+        .description(Some("Name of the namespace"))
+        .schema(Some(ObjectBuilder::new().schema_type(Type::String)))
+        .build();
+    let namespaced: Vec<_> = doc.paths.paths.iter()
+        .filter(|(path, _)| path.starts_with("/tables/"))
+        .map(|(path, item)| {
+            let mut item = item.clone();
+            item.parameters.get_or_insert_with(Vec::new).push(namespace.clone());
+            for operation in [&mut item.get, &mut item.post].into_iter().flatten() {
+                operation.operation_id = operation.operation_id.as_ref().map(|id| format!("namespaced_{}", id));
+            }
+            (format!("/namespaces/{{namespace}}{}", path), item)
+        })
+        .collect();
+    doc.paths.paths.extend(namespaced);
+    doc
+}
+
+/// The OpenAPI document
+async fn openapi_json() -> impl Responder {
+    static DOC: OnceLock<String> = OnceLock::new();
+    let doc = DOC.get_or_init(|| openapi().to_json().expect("the OpenAPI document always serializes"));
+    HttpResponse::Ok().content_type("application/json").body(doc.as_str())
+}
+
+/// Swagger UI for the OpenAPI document. The page loads Swagger UI's scripts from a CDN.
+async fn swagger_ui() -> impl Responder {
+    HttpResponse::Ok().content_type("text/html; charset=utf-8").body(SWAGGER_UI)
+}
+
+const SWAGGER_UI: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>RedBase REST API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>
+"##;
+
+/// Register all REST routes.
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/health", web::get().to(health_check))
+        .route("/ready", web::get().to(readiness))
+        .route("/openapi.json", web::get().to(openapi_json))
+        .route("/swagger-ui", web::get().to(swagger_ui))
+        .route("/metrics", web::get().to(metrics))
+        .route("/namespaces", web::get().to(list_namespaces))
+        .route("/namespaces", web::post().to(create_namespace))
+        .route("/namespaces/{namespace}/tables", web::get().to(list_tables))
+        .route("/namespaces/{namespace}/tables", web::post().to(create_table));
+
+    // Tables in namespaces serve the same operations as the pooled table
+    for table in ["/tables/{table}", "/namespaces/{namespace}/tables/{table}"] {
+        cfg.route(&format!("{}/query", table), web::post().to(query))
+            .route(&format!("{}/cf", table), web::get().to(list_cfs))
+            .route(&format!("{}/cf", table), web::post().to(create_cf))
+            .route(&format!("{}/flush_all", table), web::post().to(flush_all))
+            .route(&format!("{}/major_compact", table), web::post().to(major_compact))
+            .route(&format!("{}/cf/{{cf}}/put", table), web::post().to(put))
+            .route(&format!("{}/cf/{{cf}}/delete", table), web::post().to(delete))
+            .route(&format!("{}/cf/{{cf}}/batch", table), web::post().to(batch))
+            .route(&format!("{}/cf/{{cf}}/replicate", table), web::post().to(replicate))
+            .route(&format!("{}/cf/{{cf}}/get", table), web::post().to(get))
+            .route(&format!("{}/cf/{{cf}}/exists", table), web::post().to(exists))
+            .route(&format!("{}/cf/{{cf}}/multi_get", table), web::post().to(multi_get))
+            .route(&format!("{}/cf/{{cf}}/mget", table), web::post().to(multi_get))
+            .route(&format!("{}/cf/{{cf}}/mput", table), web::post().to(multi_put))
+            .route(&format!("{}/cf/{{cf}}/scan", table), web::post().to(scan))
+            .route(&format!("{}/cf/{{cf}}/scan_range", table), web::post().to(scan_range))
+            .route(&format!("{}/cf/{{cf}}/stream", table), web::get().to(stream))
+            .route(&format!("{}/cf/{{cf}}/count", table), web::post().to(count))
+            .route(&format!("{}/cf/{{cf}}/filter", table), web::post().to(filter))
+            .route(&format!("{}/cf/{{cf}}/aggregate", table), web::post().to(aggregate))
+            .route(&format!("{}/cf/{{cf}}/flush", table), web::post().to(flush))
+            .route(&format!("{}/cf/{{cf}}/compact", table), web::post().to(compact))
+            .route(&format!("{}/cf/{{cf}}/compaction_stats", table), web::get().to(compaction_stats))
+            .route(&format!("{}/cf/{{cf}}/write_stats", table), web::get().to(write_stats))
+            .route(&format!("{}/cf/{{cf}}/stats", table), web::get().to(stats))
+            .route(&format!("{}/cf/{{cf}}/expire", table), web::post().to(expire))
+            .route(&format!("{}/cf/{{cf}}/freeze", table), web::post().to(freeze));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+    use tempfile::tempdir;
+
+    #[actix_web::test]
+    async fn test_write_stall_returns_503() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 1,
+            write_stall: WriteStallThresholds {
+                max_sst_files: 1,
+                ..WriteStallThresholds::default()
+            },
+            retry_after_secs: 7,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+        let put = || test::TestRequest::post()
+            .uri("/tables/t/cf/default/put")
+            .set_json(json!({ "row": "row1", "column": "col1", "value": "v" }))
+            .to_request();
+
+        let req = test::TestRequest::post().uri("/tables/t/cf").set_json(json!({ "name": "default" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        assert!(test::call_service(&app, put()).await.status().is_success());
+
+        // One SSTable reaches the limit, so further writes are rejected
+        let req = test::TestRequest::post().uri("/tables/t/cf/default/flush").to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let resp = test::call_service(&app, put()).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers().get(header::RETRY_AFTER).unwrap(), "7");
+
+        // Reads are unaffected
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/get")
+            .set_json(json!({ "row": "row1", "column": "col1" }))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("redbase_write_stalled{table=\"t\",cf=\"default\"} 1"));
+        assert!(body.contains("redbase_write_stall_rejections_total 1"));
+        assert!(body.contains("redbase_pool_connections{table=\"t\",state=\"idle\"} 1"));
+        assert!(body.contains("redbase_pool_connections_closed_total{reason=\"idle\"} 0"));
+    }
+
+    #[actix_web::test]
+    async fn test_tables_are_distinct() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 1,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+        for table in ["users", "orders"] {
+            let req = test::TestRequest::post().uri(&format!("/tables/{}/cf", table)).set_json(json!({ "name": "default" })).to_request();
+            assert!(test::call_service(&app, req).await.status().is_success());
+            let req = test::TestRequest::post()
+                .uri(&format!("/tables/{}/cf/default/put", table))
+                .set_json(json!({ "row": "row1", "column": "col1", "value": table }))
+                .to_request();
+            assert!(test::call_service(&app, req).await.status().is_success());
+        }
+        let req = test::TestRequest::post().uri("/tables/users/cf").set_json(json!({ "name": "profile" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::post()
+            .uri("/tables/orders/cf/default/get")
+            .set_json(json!({ "row": "row1", "column": "col1" }))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["value"], "orders");
+        let req = test::TestRequest::get().uri("/tables/orders/cf").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["column_families"], json!(["default"]));
+        assert!(dir.path().join("users").join("profile").is_dir());
+
+        let req = test::TestRequest::get().uri("/tables/.hidden/cf").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let body = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+        assert!(body.contains("redbase_memstore_entries{table=\"orders\",cf=\"default\"} 1"));
+        assert!(body.contains("redbase_memstore_entries{table=\"users\",cf=\"default\"} 1"));
+    }
+
+    #[actix_web::test]
+    async fn test_exists_and_multi_get_routes() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 1,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+        let req = test::TestRequest::post().uri("/tables/t/cf").set_json(json!({ "name": "default" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/put")
+            .set_json(json!({ "row": "row1", "column": "col1", "value": "v1" }))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/exists")
+            .set_json(json!({ "row": "row1", "column": "col1" }))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["exists"], true);
+
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/multi_get")
+            .set_json(json!({ "cells": [{ "row": "row2", "column": "col1" }, { "row": "row1", "column": "col1" }] }))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["results"][0]["value"], serde_json::Value::Null);
+        assert_eq!(body["results"][1]["value"], "v1");
+        assert_eq!(body["results"][1]["row"], "row1");
+    }
+
+    #[actix_web::test]
+    async fn test_raw_row_scan() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 1,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+        let req = test::TestRequest::post().uri("/tables/t/cf").set_json(json!({ "name": "default" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        for (uri, body) in [
+            ("/tables/t/cf/default/put", json!({ "row": "row1", "column": "col1", "value": "v1" })),
+            ("/tables/t/cf/default/put", json!({ "row": "row1", "column": "col2", "value": "v2" })),
+            ("/tables/t/cf/default/delete", json!({ "row": "row1", "column": "col1" })),
+        ] {
+            let req = test::TestRequest::post().uri(uri).set_json(body).to_request();
+            assert!(test::call_service(&app, req).await.status().is_success());
+        }
+
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/scan")
+            .set_json(json!({ "row": "row1" }))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["col2"][0]["value"], "v2");
+
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/scan")
+            .set_json(json!({ "row": "row1", "max_versions_per_column": 5, "raw": true }))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["col1"][0]["deleted"], true);
+        assert_eq!(body["col1"][1]["value"], "v1");
+        assert_eq!(body["col2"][0]["value"], "v2");
+    }
+
+    #[actix_web::test]
+    async fn test_mput_and_mget_routes() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 1,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+        let req = test::TestRequest::post().uri("/tables/t/cf").set_json(json!({ "name": "default" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/mput")
+            .set_json(json!({ "cells": [
+                { "row": "row1", "column": "col1", "value": "v1" },
+                { "row": "row1", "column": "col2", "value": "v2" },
+                { "row": "row2", "column": "col1", "value": "v3" }
+            ] }))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["cells_count"], 3);
+        let seq = body["seq"].as_u64().unwrap();
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/tables/t/cf/default/mget?min_seq={}", seq))
+            .set_json(json!({ "cells": [
+                { "row": "row2", "column": "col1" },
+                { "row": "row3", "column": "col1" },
+                { "row": "row1", "column": "col2" }
+            ] }))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let values: Vec<_> = body["results"].as_array().unwrap().iter().map(|r| r["value"].clone()).collect();
+        assert_eq!(values, vec![json!("v3"), serde_json::Value::Null, json!("v2")]);
+
+        // A cell over the size limits rejects the whole batch
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/mput")
+            .set_json(json!({ "cells": [
+                { "row": "row4", "column": "col1", "value": "v4" },
+                { "row": "r".repeat(40_000), "column": "col1", "value": "v" }
+            ] }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::UNPROCESSABLE_ENTITY);
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/exists")
+            .set_json(json!({ "row": "row4", "column": "col1" }))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["exists"], false);
+    }
+
+    #[actix_web::test]
+    async fn test_writes_with_request_id() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 1,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+        let req = test::TestRequest::post().uri("/tables/t/cf").set_json(json!({ "name": "default" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        let put = |id: &str, value: &str| test::TestRequest::post()
+            .uri("/tables/t/cf/default/put")
+            .insert_header((REQUEST_ID_HEADER, id))
+            .set_json(json!({ "row": "row1", "column": "col1", "value": value }))
+            .to_request();
+        let batch = |id: &str| test::TestRequest::post()
+            .uri("/tables/t/cf/default/batch")
+            .insert_header((REQUEST_ID_HEADER, id))
+            .set_json(json!({ "operations": [{ "type": "Put", "data": { "row": "row1", "column": "col1", "value": "b" } }] }))
+            .to_request();
+
+        // Retries succeed without writing again
+        for _ in 0..3 {
+            assert!(test::call_service(&app, put("put-1", "v1")).await.status().is_success());
+            assert!(test::call_service(&app, batch("batch-1")).await.status().is_success());
+        }
+        assert!(test::call_service(&app, put("put-2", "v2")).await.status().is_success());
+        assert!(test::call_service(&app, put("put-1", "v3")).await.status().is_success());
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/get")
+            .set_json(json!({ "row": "row1", "column": "col1", "max_versions": 10 }))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let values: Vec<_> = body.as_array().unwrap().iter().map(|v| v["value"].clone()).collect();
+        assert_eq!(values, vec![json!("v2"), json!("b"), json!("v1")]);
+
+        let resp = test::call_service(&app, put(&"x".repeat(MAX_REQUEST_ID_LEN + 1), "v")).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_stream_route() {
+        use futures::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message;
+
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 2,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+        let req = test::TestRequest::post().uri("/tables/t/cf").set_json(json!({ "name": "default" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        let cells: Vec<_> = (0..20)
+            .map(|i| json!({ "row": format!("row{:02}", i), "column": "col", "value": "x".repeat(100) }))
+            .collect();
+        let req = test::TestRequest::post().uri("/tables/t/cf/default/mput").set_json(json!({ "cells": cells })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let server_state = state.clone();
+        let server = HttpServer::new(move || App::new().app_data(server_state.clone()).configure(routes))
+            .workers(1)
+            .bind("127.0.0.1:0")
+            .unwrap();
+        let addr = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+
+        async fn next<S>(socket: &mut tokio_tungstenite::WebSocketStream<S>) -> serde_json::Value
+        where
+            S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+        {
+            let message = tokio::time::timeout(Duration::from_secs(5), socket.next()).await.unwrap();
+            let text = message.unwrap().unwrap().into_text().unwrap();
+            serde_json::from_str(&text).unwrap()
+        }
+
+        // The scan arrives in several batches, then its end
+        let url = format!(
+            "ws://{}/tables/t/cf/default/stream?start_row=row00&end_row=row99&batch_bytes=500&subscribe=row1",
+            addr
+        );
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        let mut rows = Vec::new();
+        let mut batches = 0;
+        loop {
+            let message = next(&mut socket).await;
+            match message["type"].as_str().unwrap() {
+                "rows" => {
+                    batches += 1;
+                    rows.extend(message["rows"].as_object().unwrap().keys().cloned());
+                }
+                "scan_complete" => {
+                    assert_eq!(message["rows_count"], 20);
+                    break;
+                }
+                other => panic!("unexpected {} message", other),
+            }
+        }
+        assert!(batches > 1);
+        assert_eq!(rows, (0..20).map(|i| format!("row{:02}", i)).collect::<Vec<_>>());
+
+        // Then the changes to rows with the prefix, whichever connection wrote them
+        let put = |row: &str| test::TestRequest::post()
+            .uri("/tables/t/cf/default/put")
+            .set_json(json!({ "row": row, "column": "col", "value": "new" }))
+            .to_request();
+        assert!(test::call_service(&app, put("row05")).await.status().is_success());
+        assert!(test::call_service(&app, put("row15")).await.status().is_success());
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/delete")
+            .set_json(json!({ "row": "row16", "column": "col" }))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let change = next(&mut socket).await;
+        assert_eq!(change["type"], "change");
+        assert_eq!(change["row"], "row15");
+        assert_eq!(change["value"], "new");
+        let change = next(&mut socket).await;
+        assert_eq!(change["row"], "row16");
+        assert_eq!(change["deleted"], true);
+        socket.send(Message::Close(None)).await.unwrap();
+
+        // Without a subscription the socket closes after the scan
+        let url = format!("ws://{}/tables/t/cf/default/stream?start_row=row19&end_row=row99", addr);
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        assert_eq!(next(&mut socket).await["type"], "rows");
+        assert_eq!(next(&mut socket).await["rows_count"], 1);
+        let closed = tokio::time::timeout(Duration::from_secs(5), socket.next()).await.unwrap();
+        assert!(matches!(closed, Some(Ok(Message::Close(_))) | None));
+
+        let req = test::TestRequest::get().uri("/tables/t/cf/default/stream?start_row=a").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), actix_web::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_namespace_routes() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().join("pooled"),
+            namespace_dir: dir.path().join("namespaces"),
+            pool_size: 1,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+        let req = test::TestRequest::post().uri("/namespaces").set_json(json!({ "name": "tenant" })).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), actix_web::http::StatusCode::CREATED);
+        let req = test::TestRequest::post().uri("/namespaces").set_json(json!({ "name": "tenant" })).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), actix_web::http::StatusCode::CONFLICT);
+        let req = test::TestRequest::post().uri("/namespaces/tenant/tables").set_json(json!({ "name": "users" })).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), actix_web::http::StatusCode::CREATED);
+        let req = test::TestRequest::post().uri("/namespaces/missing/tables").set_json(json!({ "name": "users" })).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), actix_web::http::StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::get().uri("/namespaces").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["namespaces"], json!(["tenant"]));
+        let req = test::TestRequest::get().uri("/namespaces/tenant/tables").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["tables"], json!(["users"]));
+
+        // Tables in namespaces serve the same operations as the pooled table
+        let base = "/namespaces/tenant/tables/users";
+        let req = test::TestRequest::post().uri(&format!("{}/cf", base)).set_json(json!({ "name": "profile" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        let req = test::TestRequest::post()
+            .uri(&format!("{}/cf/profile/put", base))
+            .set_json(json!({ "row": "row1", "column": "name", "value": "Ada" }))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        let req = test::TestRequest::post()
+            .uri(&format!("{}/cf/profile/get", base))
+            .set_json(json!({ "row": "row1", "column": "name" }))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["value"], "Ada");
+        let req = test::TestRequest::post()
+            .uri("/namespaces/tenant/tables/orders/cf/profile/get")
+            .set_json(json!({ "row": "row1", "column": "name" }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), actix_web::http::StatusCode::NOT_FOUND);
+
+        // The pooled table is separate
+        let req = test::TestRequest::get().uri("/tables/users/cf").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["column_families"], json!([]));
+    }
+
+    #[actix_web::test]
+    async fn test_replicate_endpoint() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 1,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+        let req = test::TestRequest::post().uri("/tables/t/cf").set_json(json!({ "name": "default" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let entry = Entry {
+            key: crate::api::EntryKey {
+                row: b"row1".to_vec(),
+                column: b"col1".to_vec(),
+                timestamp: 42,
+                seq: 0,
+            },
+            value: crate::api::CellValue::Put(b"replicated".to_vec()),
+        };
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/replicate")
+            .set_json(json!({ "entries": [entry] }))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        // The original timestamp is kept
+        let conn = state.pool.get("t").await.unwrap();
+        let cf = conn.table.cf("default").await.unwrap();
+        assert_eq!(
+            cf.get_versions(b"row1", b"col1", 1).await.unwrap(),
+            vec![(42, b"replicated".to_vec())]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_exhausted_pool_returns_503() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 1,
+            pool: PoolConfig {
+                wait_timeout: Some(Duration::from_millis(50)),
+                max_waiting: Some(0),
+                ..PoolConfig::default()
+            },
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+        let list = || test::TestRequest::get().uri("/tables/t/cf").to_request();
+
+        let held = state.pool.get("t").await.unwrap();
+        let resp = test::call_service(&app, list()).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers().get(header::RETRY_AFTER).unwrap(), "1");
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["status"], "pool_exhausted");
+
+        // Requests that may wait give up after the timeout
+        let config = RestConfig { pool: PoolConfig { max_waiting: None, ..config.pool }, ..config };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+        let held_too = state.pool.get("t").await.unwrap();
+        let resp = test::call_service(&app, list()).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["status"], "pool_timeout");
+
+        drop((held, held_too));
+        assert!(test::call_service(&app, list()).await.status().is_success());
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let body = String::from_utf8(test::call_and_read_body(&app, req).await.to_vec()).unwrap();
+        assert!(body.contains("redbase_pool_requests_failed_total{reason=\"timeout\"} 1"));
+    }
+
+    #[actix_web::test]
+    async fn test_read_your_writes_token() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 2,
+            max_read_wait_ms: 50,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+        let req = test::TestRequest::post().uri("/tables/t/cf").set_json(json!({ "name": "default" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        // Hold a connection opened before the write, so it does not see it yet
+        let mut stale = state.pool.get("t").await.unwrap();
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/put")
+            .set_json(json!({ "row": "row1", "column": "col1", "value": "v1" }))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let seq = body["seq"].as_u64().unwrap();
+
+        assert!(stale.visible_seq < seq);
+        let cf = stale.table.cf("default").await.unwrap();
+        assert_eq!(cf.get(b"row1", b"col1").await.unwrap(), None);
+        await_visible(&state, &mut stale, Some(seq)).await.unwrap();
+        let cf = stale.table.cf("default").await.unwrap();
+        assert_eq!(cf.get(b"row1", b"col1").await.unwrap(), Some(b"v1".to_vec()));
+        drop(stale);
+
+        let get = |min_seq: u64| test::TestRequest::post()
+            .uri(&format!("/tables/t/cf/default/get?min_seq={}", min_seq))
+            .set_json(json!({ "row": "row1", "column": "col1" }))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, get(seq)).await;
+        assert_eq!(body["value"], "v1");
+
+        // A token that is never committed times out
+        let resp = test::call_service(&app, get(seq + 1)).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[actix_web::test]
+    async fn test_scan_range_continuation() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 1,
+            max_scan_response_bytes: 100,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+        let req = test::TestRequest::post().uri("/tables/t/cf").set_json(json!({ "name": "default" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        let operations: Vec<_> = (0..5)
+            .map(|i| json!({ "type": "Put", "data": { "row": format!("row{}", i), "column": "col1", "value": "x".repeat(30) } }))
+            .collect();
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/batch")
+            .set_json(json!({ "operations": operations }))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        // Each row is about 46 bytes, so the server limit allows two rows per response
+        let mut rows = Vec::new();
+        let mut continuation: Option<String> = None;
+        loop {
+            let req = test::TestRequest::post()
+                .uri("/tables/t/cf/default/scan_range")
+                .set_json(json!({ "start_row": "row0", "end_row": "row9", "continuation": continuation }))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert!(resp.status().is_success());
+            continuation = resp.headers().get(CONTINUATION_HEADER).map(|v| v.to_str().unwrap().to_string());
+            let body: serde_json::Map<String, serde_json::Value> = test::read_body_json(resp).await;
+            assert!(body.len() <= 2);
+            rows.extend(body.keys().cloned());
+            if continuation.is_none() {
+                break;
+            }
+        }
+        assert_eq!(rows, vec!["row0", "row1", "row2", "row3", "row4"]);
+
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/scan_range")
+            .set_json(json!({ "start_row": "row0", "end_row": "row9", "continuation": "zz" }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), actix_web::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_request_timeout() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 1,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+        let req = test::TestRequest::post().uri("/tables/t/cf").set_json(json!({ "name": "default" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        let operations: Vec<_> = (0..3)
+            .map(|i| json!({ "type": "Put", "data": { "row": format!("row{}", i), "column": "col1", "value": "x" } }))
+            .collect();
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/batch")
+            .set_json(json!({ "operations": operations }))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        // A scan out of time returns what it has, marked as partial and resumable
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/scan_range")
+            .set_json(json!({ "start_row": "row0", "end_row": "row9", "timeout_ms": 0 }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        assert_eq!(resp.headers().get(TIMED_OUT_HEADER).unwrap(), "true");
+        assert!(resp.headers().contains_key(CONTINUATION_HEADER));
+        let body: serde_json::Map<String, serde_json::Value> = test::read_body_json(resp).await;
+        assert_eq!(body.len(), 1);
+
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/count")
+            .set_json(json!({ "start_row": "row0", "end_row": "row9", "timeout_ms": 0 }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), actix_web::http::StatusCode::GATEWAY_TIMEOUT);
+
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/scan_range")
+            .set_json(json!({ "start_row": "row0", "end_row": "row9" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(!resp.headers().contains_key(TIMED_OUT_HEADER));
+        let body: serde_json::Map<String, serde_json::Value> = test::read_body_json(resp).await;
+        assert_eq!(body.len(), 3);
+    }
+
+    #[actix_web::test]
+    async fn test_compaction_stats_endpoint() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 1,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+        let req = test::TestRequest::post().uri("/tables/t/cf").set_json(json!({ "name": "default" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        for value in ["v1", "v2"] {
+            let req = test::TestRequest::post()
+                .uri("/tables/t/cf/default/put")
+                .set_json(json!({ "row": "row1", "column": "col1", "value": value }))
+                .to_request();
+            assert!(test::call_service(&app, req).await.status().is_success());
+            let req = test::TestRequest::post().uri("/tables/t/cf/default/flush").to_request();
+            assert!(test::call_service(&app, req).await.status().is_success());
+        }
+
+        let req = test::TestRequest::get().uri("/tables/t/cf/default/compaction_stats").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["completed"], 0);
+        assert_eq!(body["ongoing"], serde_json::Value::Null);
+
+        // Shared with the handles the earlier requests flushed through
+        let req = test::TestRequest::get().uri("/tables/t/cf/default/write_stats").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["flushes"], 2);
+        assert!(body["wal_bytes_written"].as_u64().unwrap() > 0);
+
+        let req = test::TestRequest::post().uri("/tables/t/cf/default/compact").to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        let req = test::TestRequest::get().uri("/tables/t/cf/default/compaction_stats").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["completed"], 1);
+        assert_eq!(body["history"][0]["kind"], "minor");
+        assert_eq!(body["history"][0]["files_in"], 2);
+        assert_eq!(body["history"][0]["entries_in"], 2);
+        assert!(body["history"][0]["bytes_written"].as_u64().unwrap() > 0);
+    }
+
+    #[actix_web::test]
+    async fn test_stats_endpoint() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 1,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+        let req = test::TestRequest::post().uri("/tables/t/cf").set_json(json!({ "name": "default" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        for row in ["row1", "row2"] {
+            let req = test::TestRequest::post()
+                .uri("/tables/t/cf/default/put")
+                .set_json(json!({ "row": row, "column": "col1", "value": "v" }))
+                .to_request();
+            assert!(test::call_service(&app, req).await.status().is_success());
+        }
+        let req = test::TestRequest::post().uri("/tables/t/cf/default/flush").to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/delete")
+            .set_json(json!({ "row": "row3", "column": "col1" }))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::get().uri("/tables/t/cf/default/stats").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["sst_files"], 1);
+        assert!(body["sst_bytes"].as_u64().unwrap() > 0);
+        assert_eq!(body["memstore_entries"], 1);
+        assert_eq!(body["approx_rows"], 3);
+        assert_eq!(body["tombstones"], 1);
+
+        let req = test::TestRequest::get().uri("/tables/t/cf/missing/stats").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+    }
+
+    #[actix_web::test]
+    async fn test_expire_endpoint() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 1,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+        let req = test::TestRequest::post().uri("/tables/t/cf").set_json(json!({ "name": "default" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/delete")
+            .set_json(json!({ "row": "row1", "column": "col1", "ttl_ms": 1 }))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        let req = test::TestRequest::post().uri("/tables/t/cf/default/flush").to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let req = test::TestRequest::post().uri("/tables/t/cf/default/expire").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["expired"], 1);
+        let req = test::TestRequest::post().uri("/tables/t/cf/default/expire").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["expired"], 0);
+    }
+
+    #[actix_web::test]
+    async fn test_query_endpoint() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 1,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+        let req = test::TestRequest::post().uri("/tables/t/cf").set_json(json!({ "name": "users" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        for (row, age) in [("user1", "31"), ("user2", "17"), ("user3", "45")] {
+            let req = test::TestRequest::post()
+                .uri("/tables/t/cf/users/put")
+                .set_json(json!({ "row": row, "column": "age", "value": age }))
+                .to_request();
+            assert!(test::call_service(&app, req).await.status().is_success());
+        }
+
+        let query = |sql: &str| test::TestRequest::post().uri("/tables/t/query").set_json(json!({ "sql": sql })).to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, query("SELECT row, age FROM users WHERE age > 18")).await;
+        assert_eq!(body["columns"], json!(["row", "age"]));
+        assert_eq!(body["rows"], json!([["user1", "31"], ["user3", "45"]]));
+        let body: serde_json::Value = test::call_and_read_body_json(&app, query("SELECT COUNT(*), AVG(age) FROM users")).await;
+        assert_eq!(body["rows"], json!([[3, 31.0]]));
+
+        let resp = test::call_service(&app, query("SELECT FROM users")).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+        let resp = test::call_service(&app, query("SELECT age FROM missing")).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_readiness_probe() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().join("data"),
+            pool_size: 1,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+        let ready = || test::TestRequest::get().uri("/ready").to_request();
+
+        let req = test::TestRequest::post().uri("/tables/t/cf").set_json(json!({ "name": "default" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        // Not ready until the tables are recovered
+        let resp = test::call_service(&app, ready()).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["status"], "not_ready");
+        assert_eq!(body["checks"]["wal_replay"]["ok"], false);
+        assert_eq!(body["checks"]["data_dir"]["ok"], true);
+        assert_eq!(body["checks"]["pool"]["details"]["tables"], 1);
+
+        assert_eq!(state.recover().await.unwrap(), 1);
+        let resp = test::call_service(&app, ready()).await;
+        assert!(resp.status().is_success());
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["status"], "ready");
+        for check in ["data_dir", "pool", "wal_replay", "background_threads"] {
+            assert_eq!(body["checks"][check]["ok"], true, "{}", check);
+        }
+        assert!(!dir.path().join("data").join(READY_PROBE_FILE).exists());
+
+        // A base directory that cannot be written or read fails the probe
+        std::fs::write(dir.path().join("file"), b"").unwrap();
+        let config = RestConfig { base_dir: dir.path().join("file"), ..config };
+        let state = web::Data::new(AppState::new(&config));
+        assert!(state.recover().await.is_err());
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+        let resp = test::call_service(&app, ready()).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["checks"]["data_dir"]["ok"], false);
+        assert!(body["checks"]["data_dir"]["error"].is_string());
+        assert_eq!(body["checks"]["wal_replay"]["ok"], false);
+    }
+
+    #[actix_web::test]
+    async fn test_admin_endpoints() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 1,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+        let post = |uri: &str, body: serde_json::Value| test::TestRequest::post().uri(uri).set_json(body).to_request();
+
+        for cf in ["a", "b"] {
+            assert!(test::call_service(&app, post("/tables/t/cf", json!({ "name": cf }))).await.status().is_success());
+        }
+        let write_all = || async {
+            for cf in ["a", "b"] {
+                let req = post(&format!("/tables/t/cf/{}/put", cf), json!({ "row": "row1", "column": "col", "value": "v" }));
+                assert!(test::call_service(&app, req).await.status().is_success());
+            }
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        };
+        let sst_files = || async {
+            let conn = state.pool.get("t").await.unwrap();
+            let mut counts = Vec::new();
+            for cf in ["a", "b"] {
+                counts.push(conn.table.cf(cf).await.unwrap().storage_counts().await.0);
+            }
+            counts
+        };
+
+        // Every column family is flushed at once
+        write_all().await;
+        let req = test::TestRequest::post().uri("/tables/t/flush_all").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["column_families"], json!(["a", "b"]));
+        write_all().await;
+        let req = test::TestRequest::post().uri("/tables/t/flush_all").to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        assert_eq!(sst_files().await, vec![2, 2]);
+
+        // A major compaction with options merges everything and keeps one version
+        let resp = test::call_service(&app, post("/tables/t/cf/a/compact", json!({ "type": "major", "max_versions": 1 }))).await;
+        assert!(resp.status().is_success());
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["type"], "major");
+        assert_eq!(sst_files().await, vec![1, 2]);
+        let conn = state.pool.get("t").await.unwrap();
+        let cf = conn.table.cf("a").await.unwrap();
+        assert_eq!(cf.get_versions(b"row1", b"col", 10).await.unwrap().len(), 1);
+        drop(conn);
+
+        let resp = test::call_service(&app, post("/tables/t/cf/a/compact", json!({ "type": "full" }))).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+        let resp = test::call_service(&app, post("/tables/t/cf/a/compact", json!({ "max_versions": 0 }))).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+        // Without a body the compaction stays minor
+        let req = test::TestRequest::post().uri("/tables/t/cf/b/compact").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["type"], "minor");
+
+        let req = test::TestRequest::post().uri("/tables/t/major_compact").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["column_families"], json!(["a", "b"]));
+        assert_eq!(sst_files().await, vec![1, 1]);
+    }
+
+    #[actix_web::test]
+    async fn test_openapi_document() {
+        let app = test::init_service(App::new().configure(routes)).await;
+
+        let req = test::TestRequest::get().uri("/openapi.json").to_request();
+        let doc: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let paths = doc["paths"].as_object().unwrap();
+        let put = &paths["/tables/{table}/cf/{cf}/put"]["post"];
+        assert_eq!(put["requestBody"]["content"]["application/json"]["schema"]["$ref"], "#/components/schemas/PutRequest");
+        let params: Vec<_> = put["parameters"].as_array().unwrap().iter().map(|p| p["name"].as_str().unwrap()).collect();
+        assert_eq!(params, vec!["table", "cf", REQUEST_ID_HEADER]);
+        assert!(doc["components"]["schemas"]["ScanRangeRequest"]["properties"]["continuation"].is_object());
+
+        // Namespaced copies of the table routes, with their own operation ids
+        let namespaced = &paths["/namespaces/{namespace}/tables/{table}/cf/{cf}/mget"];
+        assert_eq!(namespaced["parameters"][0]["name"], "namespace");
+        assert_eq!(namespaced["post"]["operationId"], "namespaced_multi_get");
+        let mut ids: Vec<_> = paths.values()
+            .flat_map(|item| item.as_object().unwrap().values())
+            .filter_map(|operation| operation["operationId"].as_str())
+            .collect();
+        let count = ids.len();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), count);
+        // Every route is documented
+        assert_eq!(count, 7 + 2 * 26);
+
+        let req = test::TestRequest::get().uri("/swagger-ui").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+        assert!(String::from_utf8_lossy(&test::read_body(resp).await).contains("/openapi.json"));
+    }
+
+    #[actix_web::test]
+    async fn test_size_limits_rejected() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 1,
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+        let req = test::TestRequest::post().uri("/tables/t/cf").set_json(json!({ "name": "default" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/put")
+            .set_json(json!({ "row": "r".repeat(40_000), "column": "col1", "value": "v" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::UNPROCESSABLE_ENTITY);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"]["rule"], "max_key_bytes");
+    }
+
+    #[actix_web::test]
+    async fn test_circuit_breaker_fails_fast() {
+        let dir = tempdir().unwrap();
+        let config = RestConfig {
+            base_dir: dir.path().to_path_buf(),
+            pool_size: 1,
+            circuit_breaker: CircuitBreakerConfig {
+                failure_threshold: 2,
+                cool_down: Duration::from_secs(60),
+            },
+            ..RestConfig::default()
+        };
+        let state = web::Data::new(AppState::new(&config));
+        let app = test::init_service(App::new().app_data(state.clone()).configure(routes)).await;
+
+        let req = test::TestRequest::post().uri("/tables/t/cf").set_json(json!({ "name": "default" })).to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        // Simulate a disk failing twice in a row
+        let breaker = state.pool.circuit_breaker();
+        for _ in 0..2 {
+            breaker.record_error(&std::io::Error::new(std::io::ErrorKind::StorageFull, "No space left on device"));
+        }
+
+        let req = test::TestRequest::post()
+            .uri("/tables/t/cf/default/put")
+            .set_json(json!({ "row": "row1", "column": "col1", "value": "v" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers().get(header::RETRY_AFTER).unwrap(), "60");
+
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let body = test::call_and_read_body(&app, req).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("redbase_pool_circuit_state 1"));
+        assert!(body.contains("redbase_pool_circuit_transitions_total{to=\"open\"} 1"));
+        assert!(body.contains("redbase_pool_circuit_rejections_total 1"));
+    }
 }
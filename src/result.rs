@@ -0,0 +1,163 @@
+//! Rows and cells returned by gets and scans.
+//!
+//! Reads return their versions as nested maps (`RowVersions` by row in a
+//! `RangeVersions`): every caller unpacks them with nested loops, and they can only hold
+//! values, so a raw scan, which returns delete markers along with the puts, has nowhere
+//! to put them. A `Row` is instead a flat list of `Cell`s, each carrying its row,
+//! column, timestamp and value and whether it is a delete marker, sorted by column and
+//! newest first, with accessors for the usual lookups.
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::{Column, RangeVersions, RowKey, RowVersions, Timestamp};
+use crate::scan::ScanRow;
+
+/// One version of a cell: a put, or a delete marker returned by a raw scan.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cell {
+    pub row: RowKey,
+    pub column: Column,
+    pub timestamp: Timestamp,
+    /// The value put; empty for a delete marker
+    pub value: Vec<u8>,
+    pub is_delete: bool,
+}
+
+impl Cell {
+    pub fn put(row: RowKey, column: Column, timestamp: Timestamp, value: Vec<u8>) -> Self {
+        Self { row, column, timestamp, value, is_delete: false }
+    }
+
+    pub fn delete(row: RowKey, column: Column, timestamp: Timestamp) -> Self {
+        Self { row, column, timestamp, value: Vec::new(), is_delete: true }
+    }
+
+    /// The value as UTF-8, if it is a put of valid UTF-8.
+    pub fn value_str(&self) -> Option<&str> {
+        (!self.is_delete).then(|| std::str::from_utf8(&self.value).ok()).flatten()
+    }
+}
+
+/// The cells of one row returned by a get or scan, sorted by column, newest first.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Row {
+    row: RowKey,
+    cells: Vec<Cell>,
+}
+
+impl Row {
+    /// Row made of cells, which must all belong to row.
+    pub fn new(row: RowKey, mut cells: Vec<Cell>) -> Self {
+        cells.sort_by(|a, b| a.column.cmp(&b.column).then(b.timestamp.cmp(&a.timestamp)));
+        Self { row, cells }
+    }
+
+    /// The puts of versions as a row.
+    pub fn from_versions(row: RowKey, versions: RowVersions) -> Self {
+        let cells = versions.into_iter()
+            .flat_map(|(column, versions)| {
+                let row = row.clone();
+                versions.into_iter().map(move |(ts, value)| Cell::put(row.clone(), column.clone(), ts, value))
+            })
+            .collect();
+        Self { row, cells }
+    }
+
+    /// The rows of a range, in row order.
+    pub fn from_range(rows: RangeVersions) -> Vec<Self> {
+        rows.into_iter().map(|(row, versions)| Self::from_versions(row, versions)).collect()
+    }
+
+    pub fn row(&self) -> &RowKey {
+        &self.row
+    }
+
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    pub fn into_cells(self) -> Vec<Cell> {
+        self.cells
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// The columns that have cells, in order.
+    pub fn columns(&self) -> impl Iterator<Item = &Column> {
+        self.cells.chunk_by(|a, b| a.column == b.column).map(|cells| &cells[0].column)
+    }
+
+    /// The cells of column, newest first.
+    pub fn versions(&self, column: &[u8]) -> &[Cell] {
+        let start = self.cells.partition_point(|cell| cell.column.as_slice() < column);
+        let end = self.cells.partition_point(|cell| cell.column.as_slice() <= column);
+        &self.cells[start..end]
+    }
+
+    /// The newest cell of column, unless it is a delete marker.
+    pub fn latest(&self, column: &[u8]) -> Option<&Cell> {
+        self.versions(column).first().filter(|cell| !cell.is_delete)
+    }
+
+    /// The newest value of column, if it has one.
+    pub fn value(&self, column: &[u8]) -> Option<&[u8]> {
+        self.latest(column).map(|cell| cell.value.as_slice())
+    }
+
+    /// The newest value of column as UTF-8, if it has one and it is valid UTF-8.
+    pub fn value_str(&self, column: &[u8]) -> Option<&str> {
+        self.latest(column).and_then(Cell::value_str)
+    }
+
+    /// The puts of the row as versions by column, as the map-returning reads give them.
+    pub fn to_versions(&self) -> RowVersions {
+        let mut versions = RowVersions::new();
+        for cell in self.cells.iter().filter(|cell| !cell.is_delete) {
+            versions.entry(cell.column.clone()).or_default().push((cell.timestamp, cell.value.clone()));
+        }
+        versions
+    }
+}
+
+impl From<ScanRow> for Row {
+    fn from(row: ScanRow) -> Self {
+        Self::from_versions(row.row, row.columns)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row_accessors() {
+        let mut versions = RowVersions::new();
+        versions.insert(b"name".to_vec(), vec![(2, b"Alice B.".to_vec()), (1, b"Alice".to_vec())]);
+        versions.insert(b"age".to_vec(), vec![(1, vec![0xFF])]);
+        let row = Row::from_versions(b"user1".to_vec(), versions.clone());
+        assert_eq!(row.len(), 3);
+        assert_eq!(row.columns().cloned().collect::<Vec<_>>(), vec![b"age".to_vec(), b"name".to_vec()]);
+        assert_eq!(row.value_str(b"name"), Some("Alice B."));
+        assert_eq!(row.versions(b"name")[1].timestamp, 1);
+        assert_eq!(row.value(b"age"), Some(&[0xFF][..]));
+        assert_eq!(row.value_str(b"age"), None);
+        assert!(row.versions(b"email").is_empty());
+        assert_eq!(row.to_versions(), versions);
+
+        // A delete marker hides the older versions from latest, but not from versions
+        let row = Row::new(b"user1".to_vec(), vec![
+            Cell::put(b"user1".to_vec(), b"name".to_vec(), 1, b"Alice".to_vec()),
+            Cell::delete(b"user1".to_vec(), b"name".to_vec(), 2),
+        ]);
+        assert!(row.cells()[0].is_delete);
+        assert_eq!(row.latest(b"name"), None);
+        assert_eq!(row.versions(b"name").len(), 2);
+        assert_eq!(row.to_versions()[&b"name".to_vec()], vec![(1, b"Alice".to_vec())]);
+    }
+}
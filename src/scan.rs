@@ -0,0 +1,533 @@
+//! Streaming scans and composable adapters over their rows.
+//!
+//! `ColumnFamily::scanner` returns a `Scanner` that reads one row at a time, so a large
+//! range never has to be collected into a `RangeVersions` map. The `ScanExt` adapters
+//! work on any iterator of scanned rows and pass errors through unchanged:
+//!
+//! ```ignore
+//! use RedBase::scan::ScanExt;
+//!
+//! let active: Vec<User> = cf.scanner(b"user:", b"user:~", 1)?
+//!     .filter_rows(|row| row.latest_str(b"status") == Some("active"))
+//!     .typed::<User>()
+//!     .collect::<std::io::Result<_>>()?;
+//! ```
+//!
+//! A scan can be stopped and picked up later, e.g. by a REST client whose request would
+//! otherwise time out: `Scanner::resume_token` gives the position of the next row, and
+//! `ColumnFamily::resume_scanner` continues from it. Batched scans that split a huge row
+//! hand out tokens pointing inside the row, after the last version returned.
+
+use std::{
+    collections::{BTreeSet, VecDeque},
+    io::{Error, ErrorKind, Result as IoResult},
+    vec,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::{apply_filter_set, Column, ColumnFamily, RowKey, RowVersions, Scan, Timestamp};
+use crate::filter::FilterSet;
+
+/// Position at which a scan continues. `encode` turns it into an opaque string token.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ResumeToken {
+    /// Row at which the scan continues
+    pub row: RowKey,
+    /// Within that row, the last (column, timestamp) already returned; the scan
+    /// continues with the next older version, then the following columns
+    pub after: Option<(Column, Timestamp)>,
+}
+
+impl ResumeToken {
+    /// Continue at the start of row.
+    pub fn at_row(row: &[u8]) -> Self {
+        Self { row: row.to_vec(), after: None }
+    }
+
+    /// Serialize the position as an opaque token (hex-encoded).
+    pub fn encode(&self) -> String {
+        let bytes = bincode::serialize(self).expect("resume tokens always serialize");
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Parse a token produced by `encode`. Fails with `InvalidInput` if it is malformed.
+    pub fn decode(token: &str) -> IoResult<Self> {
+        let invalid = || Error::new(ErrorKind::InvalidInput, format!("Invalid resume token: {}", token));
+        if !token.len().is_multiple_of(2) || !token.is_ascii() {
+            return Err(invalid());
+        }
+        let bytes: Vec<u8> = (0..token.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&token[i..i + 2], 16).map_err(|_| invalid()))
+            .collect::<IoResult<_>>()?;
+        bincode::deserialize(&bytes).map_err(|_| invalid())
+    }
+
+    /// Whether the version (column, timestamp) of this token's row comes after the
+    /// position, i.e. has not been returned yet.
+    fn is_ahead(&self, column: &[u8], timestamp: Timestamp) -> bool {
+        match &self.after {
+            None => true,
+            Some((after_column, after_ts)) => {
+                column > after_column.as_slice() || (column == after_column.as_slice() && timestamp < *after_ts)
+            }
+        }
+    }
+}
+
+/// One row returned by a scan: its visible columns, each with its newest versions first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanRow {
+    pub row: RowKey,
+    pub columns: RowVersions,
+}
+
+impl ScanRow {
+    /// Newest value of column, if the row has one.
+    pub fn latest(&self, column: &[u8]) -> Option<&[u8]> {
+        self.columns.get(column)?.first().map(|(_, value)| value.as_slice())
+    }
+
+    /// Newest value of column as UTF-8, if the row has one and it is valid UTF-8.
+    pub fn latest_str(&self, column: &[u8]) -> Option<&str> {
+        self.latest(column).and_then(|value| std::str::from_utf8(value).ok())
+    }
+
+    /// All versions (timestamp, value) of column, newest first.
+    pub fn versions(&self, column: &[u8]) -> &[(Timestamp, Vec<u8>)] {
+        self.columns.get(column).map_or(&[], |versions| versions.as_slice())
+    }
+
+    /// Approximate serialized size of the row: its key, column names, timestamps and values.
+    pub fn estimated_bytes(&self) -> usize {
+        self.row.len() + self.columns.iter()
+            .map(|(column, versions)| {
+                column.len() + versions.iter().map(|(_, value)| 8 + value.len()).sum::<usize>()
+            })
+            .sum::<usize>()
+    }
+
+    /// Keep the leading versions (in column order, newest first) that fit in max_bytes
+    /// as measured by `estimated_bytes`, and at least one. Returns the position of the
+    /// last version kept if any were dropped.
+    pub(crate) fn truncate_to(&mut self, max_bytes: usize) -> Option<(Column, Timestamp)> {
+        let mut bytes = self.row.len();
+        let mut last: Option<(Column, Timestamp)> = None;
+        let mut kept = RowVersions::new();
+        for (column, versions) in std::mem::take(&mut self.columns) {
+            bytes += column.len();
+            let mut fitting = Vec::new();
+            for (ts, value) in versions {
+                bytes += 8 + value.len();
+                if bytes > max_bytes && last.is_some() {
+                    if !fitting.is_empty() {
+                        kept.insert(column, fitting);
+                    }
+                    self.columns = kept;
+                    return last;
+                }
+                last = Some((column.clone(), ts));
+                fitting.push((ts, value));
+            }
+            kept.insert(column, fitting);
+        }
+        self.columns = kept;
+        None
+    }
+}
+
+/// Iterator over the rows of a key range, reading one row at a time.
+///
+/// The row keys of the range are gathered when the scanner is created; the cells of
+/// each row are read when the row is reached. Rows without visible cells are skipped.
+pub struct Scanner {
+    cf: ColumnFamily,
+    rows: vec::IntoIter<RowKey>,
+    max_versions: usize,
+    time_range: (Timestamp, Timestamp),
+    /// Columns to read; every column when empty
+    columns: BTreeSet<Column>,
+    filter_set: Option<FilterSet>,
+    /// Rows returned per `next_batch`
+    caching: usize,
+    /// Maximum number of columns per returned row
+    batch: Option<usize>,
+    /// Parts of a row split by `batch` that are still to be returned
+    pending: VecDeque<ScanRow>,
+    /// Last (column, timestamp) returned of the row whose parts are pending
+    split_after: Option<(Column, Timestamp)>,
+    /// Position inside the first row, when resuming mid-row
+    resume: Option<ResumeToken>,
+}
+
+impl Scanner {
+    /// Scanner over rows, in the order given, reading them as scan says.
+    pub(crate) fn new(cf: ColumnFamily, rows: Vec<RowKey>, scan: &Scan, resume: Option<ResumeToken>) -> Self {
+        Self {
+            cf,
+            rows: rows.into_iter(),
+            max_versions: scan.max_versions().unwrap_or(1),
+            time_range: scan.time_range().unwrap_or((0, Timestamp::MAX)),
+            columns: scan.columns().clone(),
+            filter_set: scan.filter().cloned(),
+            caching: scan.caching().max(1),
+            batch: scan.batch().map(|columns| columns.max(1)),
+            pending: VecDeque::new(),
+            split_after: None,
+            resume: resume.filter(|token| token.after.is_some()),
+        }
+    }
+
+    /// Read only columns of each row, skipping rows that have none of them. Only the
+    /// selected cells of a wide row are read.
+    pub fn with_columns(mut self, columns: BTreeSet<Column>) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    /// Position of the next row to return, or None once the scan is complete. Pass it to
+    /// `ColumnFamily::resume_scanner` to continue the scan later. Only the rows after
+    /// the position are scanned again, so the token of a reversed scan does not resume
+    /// it.
+    pub fn resume_token(&self) -> Option<ResumeToken> {
+        if let Some(part) = self.pending.front() {
+            return Some(ResumeToken { row: part.row.clone(), after: self.split_after.clone() });
+        }
+        match (&self.resume, self.rows.as_slice().first()) {
+            (Some(token), Some(row)) if token.row == *row => Some(token.clone()),
+            (_, row) => row.map(|row| ResumeToken::at_row(row)),
+        }
+    }
+
+    /// The next rows, as many as the scan's caching (`DEFAULT_SCAN_CACHING` by
+    /// default); fewer only at the end of the scan, and none once it is complete.
+    pub fn next_batch(&mut self) -> IoResult<Vec<ScanRow>> {
+        let mut rows = Vec::new();
+        while rows.len() < self.caching {
+            match self.next() {
+                Some(row) => rows.push(row?),
+                None => break,
+            }
+        }
+        Ok(rows)
+    }
+
+    /// Return the first part of row, of at most batch columns, and keep the rest for
+    /// the following calls.
+    fn split(&mut self, row: ScanRow) -> ScanRow {
+        let Some(batch) = self.batch.filter(|batch| row.columns.len() > *batch) else {
+            return row;
+        };
+        let mut columns = row.columns.into_iter().peekable();
+        while columns.peek().is_some() {
+            let part: RowVersions = columns.by_ref().take(batch).collect();
+            self.pending.push_back(ScanRow { row: row.row.clone(), columns: part });
+        }
+        self.next_part()
+    }
+
+    /// Return the next pending part of a split row.
+    fn next_part(&mut self) -> ScanRow {
+        let part = self.pending.pop_front().unwrap();
+        self.split_after = part.columns.iter().next_back()
+            .and_then(|(column, versions)| versions.last().map(|(ts, _)| (column.clone(), *ts)));
+        part
+    }
+}
+
+impl Iterator for Scanner {
+    type Item = IoResult<ScanRow>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.pending.is_empty() {
+            return Some(Ok(self.next_part()));
+        }
+        while let Some(row) = self.rows.next() {
+            let resume = self.resume.take().filter(|token| token.row == row);
+            let read = self.cf.read_columns(&row, &self.columns, self.max_versions, self.time_range).map(|mut columns| {
+                // Drop the versions returned before the scan was interrupted
+                if let Some(token) = resume {
+                    columns.retain(|column, versions| {
+                        versions.retain(|(ts, _)| token.is_ahead(column, *ts));
+                        !versions.is_empty()
+                    });
+                }
+                match &self.filter_set {
+                    Some(filter_set) => apply_filter_set(filter_set, columns),
+                    None => columns,
+                }
+            });
+            match read {
+                Ok(columns) if columns.is_empty() => continue,
+                Ok(columns) => return Some(Ok(self.split(ScanRow { row, columns }))),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    }
+}
+
+/// Conversion of a scanned row into a typed value, used by `ScanExt::typed`.
+pub trait FromRow: Sized {
+    fn from_row(row: &ScanRow) -> IoResult<Self>;
+}
+
+/// Consecutive rows sharing a key prefix, produced by `ScanExt::chunk_by_prefix`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowChunk {
+    pub prefix: Vec<u8>,
+    pub rows: Vec<ScanRow>,
+}
+
+/// Adapters over iterators of scanned rows. Errors are passed through unchanged.
+pub trait ScanExt: Iterator<Item = IoResult<ScanRow>> + Sized {
+    /// Convert every row with `FromRow`.
+    fn typed<T: FromRow>(self) -> impl Iterator<Item = IoResult<T>> {
+        self.map(|row| row.and_then(|row| T::from_row(&row)))
+    }
+
+    /// Keep only the rows matching predicate.
+    fn filter_rows<P>(self, mut predicate: P) -> impl Iterator<Item = IoResult<ScanRow>>
+    where
+        P: FnMut(&ScanRow) -> bool,
+    {
+        self.filter(move |row| row.as_ref().map_or(true, &mut predicate))
+    }
+
+    /// Keep only the listed columns of each row, dropping rows left without any.
+    fn select_columns(self, columns: &[&[u8]]) -> impl Iterator<Item = IoResult<ScanRow>> {
+        let wanted: Vec<Column> = columns.iter().map(|c| c.to_vec()).collect();
+        self.filter_map(move |row| match row {
+            Ok(mut row) => {
+                row.columns.retain(|column, _| wanted.contains(column));
+                (!row.columns.is_empty()).then_some(Ok(row))
+            }
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    /// Group consecutive rows whose keys have the same prefix, as computed by prefix_of.
+    /// Rows are returned in key order, so every prefix that is a leading part of the key
+    /// (e.g. the part before the first `:`) forms exactly one chunk.
+    fn chunk_by_prefix<F>(self, prefix_of: F) -> ChunkByPrefix<Self, F>
+    where
+        F: FnMut(&[u8]) -> Vec<u8>,
+    {
+        ChunkByPrefix {
+            rows: self,
+            prefix_of,
+            pending: None,
+        }
+    }
+
+    /// Collect the rows into an Arrow `RecordBatch` with a binary `row` column and one
+    /// nullable binary column holding the newest value of each listed column.
+    #[cfg(feature = "arrow")]
+    fn into_record_batch(self, columns: &[&[u8]]) -> IoResult<arrow_array::RecordBatch> {
+        use std::sync::Arc;
+
+        use arrow_array::{builder::BinaryBuilder, ArrayRef, RecordBatch};
+        use arrow_schema::{DataType, Field, Schema};
+
+        let mut row_builder = BinaryBuilder::new();
+        let mut column_builders: Vec<BinaryBuilder> = columns.iter().map(|_| BinaryBuilder::new()).collect();
+        for row in self {
+            let row = row?;
+            row_builder.append_value(&row.row);
+            for (column, builder) in columns.iter().zip(column_builders.iter_mut()) {
+                builder.append_option(row.latest(column));
+            }
+        }
+
+        let mut fields = vec![Field::new("row", DataType::Binary, false)];
+        fields.extend(columns.iter().map(|c| Field::new(String::from_utf8_lossy(c), DataType::Binary, true)));
+        let mut arrays: Vec<ArrayRef> = vec![Arc::new(row_builder.finish())];
+        arrays.extend(column_builders.iter_mut().map(|b| Arc::new(b.finish()) as ArrayRef));
+
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl<I: Iterator<Item = IoResult<ScanRow>>> ScanExt for I {}
+
+/// Iterator returned by `ScanExt::chunk_by_prefix`.
+pub struct ChunkByPrefix<I, F> {
+    rows: I,
+    prefix_of: F,
+    pending: Option<(Vec<u8>, ScanRow)>,
+}
+
+impl<I, F> Iterator for ChunkByPrefix<I, F>
+where
+    I: Iterator<Item = IoResult<ScanRow>>,
+    F: FnMut(&[u8]) -> Vec<u8>,
+{
+    type Item = IoResult<RowChunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = match self.pending.take() {
+            Some((prefix, row)) => RowChunk { prefix, rows: vec![row] },
+            None => match self.rows.next()? {
+                Ok(row) => RowChunk { prefix: (self.prefix_of)(&row.row), rows: vec![row] },
+                Err(e) => return Some(Err(e)),
+            },
+        };
+
+        for row in self.rows.by_ref() {
+            let row = match row {
+                Ok(row) => row,
+                Err(e) => return Some(Err(e)),
+            };
+            let prefix = (self.prefix_of)(&row.row);
+            if prefix != chunk.prefix {
+                self.pending = Some((prefix, row));
+                break;
+            }
+            chunk.rows.push(row);
+        }
+        Some(Ok(chunk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Table;
+    use std::io::{Error, ErrorKind};
+    use tempfile::tempdir;
+
+    #[derive(Debug, PartialEq)]
+    struct User {
+        id: String,
+        name: String,
+    }
+
+    impl FromRow for User {
+        fn from_row(row: &ScanRow) -> IoResult<Self> {
+            let name = row.latest_str(b"name")
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "missing name"))?;
+            Ok(User {
+                id: String::from_utf8_lossy(&row.row).to_string(),
+                name: name.to_string(),
+            })
+        }
+    }
+
+    fn prefix_before_colon(row: &[u8]) -> Vec<u8> {
+        row.split(|b| *b == b':').next().unwrap_or_default().to_vec()
+    }
+
+    #[test]
+    fn test_scanner_adapters() {
+        let dir = tempdir().unwrap();
+        let table = Table::open(dir.path()).unwrap();
+        table.create_cf("default").unwrap();
+        let cf = table.cf("default").unwrap();
+
+        for (row, name, team) in [("a:1", "ann", "red"), ("a:2", "bob", "blue"), ("b:1", "cid", "red")] {
+            cf.put(row.as_bytes().to_vec(), b"name".to_vec(), name.as_bytes().to_vec()).unwrap();
+            cf.put(row.as_bytes().to_vec(), b"team".to_vec(), team.as_bytes().to_vec()).unwrap();
+        }
+        cf.delete(b"c:1".to_vec(), b"team".to_vec()).unwrap();
+
+        // Deleted rows are skipped
+        assert_eq!(cf.scanner(b"a", b"z", 1).unwrap().count(), 3);
+
+        let red: Vec<User> = cf.scanner(b"a", b"z", 1).unwrap()
+            .filter_rows(|row| row.latest_str(b"team") == Some("red"))
+            .typed::<User>()
+            .collect::<IoResult<_>>()
+            .unwrap();
+        assert_eq!(red, vec![
+            User { id: "a:1".to_string(), name: "ann".to_string() },
+            User { id: "b:1".to_string(), name: "cid".to_string() },
+        ]);
+
+        let chunks: Vec<RowChunk> = cf.scanner(b"a", b"z", 1).unwrap()
+            .chunk_by_prefix(prefix_before_colon)
+            .collect::<IoResult<_>>()
+            .unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].prefix, b"a");
+        assert_eq!(chunks[0].rows.len(), 2);
+        assert_eq!(chunks[1].rows[0].row, b"b:1");
+
+        // Rows without a selected column are dropped, so conversion errors surface
+        let teams_only = cf.scanner(b"a", b"z", 1).unwrap().select_columns(&[b"team"]);
+        assert!(teams_only.typed::<User>().all(|user| user.is_err()));
+    }
+
+    #[test]
+    fn test_scanner_for_scan() {
+        use crate::filter::Filter;
+
+        let dir = tempdir().unwrap();
+        let table = Table::open(dir.path()).unwrap();
+        table.create_cf("default").unwrap();
+        let cf = table.cf("default").unwrap();
+        for row in 0..5 {
+            for col in 0..5 {
+                let value = format!("{}{}", row, col).into_bytes();
+                cf.put(format!("row{}", row).into_bytes(), format!("col{}", col).into_bytes(), value).unwrap();
+            }
+        }
+        let old = cf.now();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        cf.put(b"row1".to_vec(), b"col0".to_vec(), b"new".to_vec()).unwrap();
+
+        // Reversed, in batches of two rows
+        let mut scan = Scan::new(b"row1".to_vec(), b"row3".to_vec());
+        scan.set_reversed(true).set_caching(2);
+        let mut scanner = cf.open_scanner(&scan).unwrap();
+        let rows: Vec<RowKey> = scanner.next_batch().unwrap().into_iter().map(|row| row.row).collect();
+        assert_eq!(rows, vec![b"row3".to_vec(), b"row2".to_vec()]);
+        assert_eq!(scanner.next_batch().unwrap().len(), 1);
+        assert!(scanner.next_batch().unwrap().is_empty());
+
+        // Wide rows split into parts of two columns, resumable between them
+        let mut scan = Scan::new(b"row0".to_vec(), b"row1".to_vec());
+        scan.set_batch(2);
+        let mut scanner = cf.open_scanner(&scan).unwrap();
+        let first = scanner.next().unwrap().unwrap();
+        assert_eq!(first.columns.keys().cloned().collect::<Vec<_>>(), vec![b"col0".to_vec(), b"col1".to_vec()]);
+        let token = scanner.resume_token().unwrap();
+        assert_eq!(token.row, b"row0");
+        let rest: Vec<ScanRow> = cf.resume_scanner(&token, b"row1", 1).unwrap().collect::<IoResult<_>>().unwrap();
+        assert_eq!(rest[0].columns.len(), 3);
+        assert_eq!(rest[1].row, b"row1");
+        assert_eq!(scanner.map(|row| row.unwrap().columns.len()).collect::<Vec<_>>(), vec![2, 1, 2, 2, 1]);
+        assert_eq!(cf.execute_scan(&scan).unwrap()[&b"row0".to_vec()].len(), 5);
+
+        // Time range and filter
+        let mut scan = Scan::new(b"row0".to_vec(), b"row9".to_vec());
+        scan.set_time_range(0, old).set_max_versions(3);
+        let rows = cf.execute_scan(&scan).unwrap();
+        assert_eq!(rows[&b"row1".to_vec()][&b"col0".to_vec()], vec![(rows[&b"row1".to_vec()][&b"col0".to_vec()][0].0, b"10".to_vec())]);
+        let mut filter_set = FilterSet::new();
+        filter_set.add_column_filter(b"col2".to_vec(), Filter::GreaterThanOrEqual(b"32".to_vec()));
+        scan.set_filter(filter_set);
+        let rows = cf.execute_scan(&scan).unwrap();
+        assert_eq!(rows.keys().cloned().collect::<Vec<_>>(), vec![b"row3".to_vec(), b"row4".to_vec()]);
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn test_into_record_batch() {
+        let dir = tempdir().unwrap();
+        let table = Table::open(dir.path()).unwrap();
+        table.create_cf("default").unwrap();
+        let cf = table.cf("default").unwrap();
+        cf.put(b"row1".to_vec(), b"name".to_vec(), b"ann".to_vec()).unwrap();
+        cf.put(b"row2".to_vec(), b"age".to_vec(), b"30".to_vec()).unwrap();
+
+        let batch = cf.scanner(b"row1", b"row2", 1).unwrap()
+            .into_record_batch(&[b"name", b"age"])
+            .unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.num_columns(), 3);
+        assert_eq!(batch.schema().field(1).name(), "name");
+        assert_eq!(batch.column(1).null_count(), 1);
+    }
+}
@@ -0,0 +1,142 @@
+//! Persisted schema of a table.
+//!
+//! `schema.json` in the table directory lists every column family with its settings, so
+//! that configuration declared at creation or changed by `Table::alter_cf` survives
+//! restarts. It is replaced atomically, and every change re-reads the file before
+//! writing it, so handles opened at different times never drop each other's changes.
+//! Column family directories that the schema does not list (e.g. tables written before
+//! it existed) are adopted when the table is opened: with the options their legacy
+//! `options.json` recorded, which then moves into the schema, or else with default
+//! settings.
+//!
+//! The schema records retention, size limits and bloom filters. Compression is not
+//! recorded, as SSTables are not compressed yet.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{Error, ErrorKind, Result as IoResult, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::ColumnFamilyOptions;
+use crate::storage::Durability;
+
+/// Schema file (inside each table directory) describing its column families.
+pub const SCHEMA_FILE: &str = "schema.json";
+
+/// File (inside each column family) in which column families created before the schema
+/// existed recorded their options.
+pub(crate) const LEGACY_OPTIONS_FILE: &str = "options.json";
+
+/// The options the column family directory cf_dir recorded in its legacy `options.json`,
+/// or None if it has none.
+pub(crate) fn legacy_options(cf_dir: &Path) -> IoResult<Option<ColumnFamilyOptions>> {
+    let path = cf_dir.join(LEGACY_OPTIONS_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let options = serde_json::from_slice(&fs::read(&path)?).map_err(|e| {
+        Error::new(ErrorKind::InvalidData, format!("Corrupt {}: {}", path.display(), e))
+    })?;
+    Ok(Some(options))
+}
+
+/// Settings of one column family.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ColumnFamilyDescriptor {
+    /// Retention settings enforced by reads and compactions
+    #[serde(flatten)]
+    pub options: ColumnFamilyOptions,
+    /// Whether the column family is partitioned into regions (see `region`)
+    #[serde(default)]
+    pub regioned: bool,
+}
+
+/// Column families of a table and their settings.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct TableSchema {
+    /// Incremented by every change
+    #[serde(default)]
+    pub version: u64,
+    pub column_families: BTreeMap<String, ColumnFamilyDescriptor>,
+}
+
+impl TableSchema {
+    /// Read the schema of the table at table_dir, or None if it has none yet.
+    pub fn load(table_dir: &Path) -> IoResult<Option<Self>> {
+        let path = table_dir.join(SCHEMA_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let schema = serde_json::from_slice(&fs::read(&path)?).map_err(|e| {
+            Error::new(ErrorKind::InvalidData, format!("Corrupt {}: {}", path.display(), e))
+        })?;
+        Ok(Some(schema))
+    }
+
+    /// The options of the column family name of the table at table_dir: those the schema
+    /// lists, or else those of its legacy `options.json`, or else the defaults.
+    pub fn cf_options(table_dir: &Path, name: &str) -> IoResult<ColumnFamilyOptions> {
+        if let Some(descriptor) = Self::load(table_dir)?.and_then(|schema| schema.column_families.get(name).copied()) {
+            return Ok(descriptor.options);
+        }
+        Ok(legacy_options(&table_dir.join(name))?.unwrap_or_default())
+    }
+
+    /// Apply change to the current schema of the table at table_dir and write it back,
+    /// returning the new schema.
+    pub fn update(table_dir: &Path, change: impl FnOnce(&mut TableSchema) -> IoResult<()>) -> IoResult<Self> {
+        let mut schema = Self::load(table_dir)?.unwrap_or_default();
+        change(&mut schema)?;
+        schema.version += 1;
+        schema.save(table_dir)?;
+        Ok(schema)
+    }
+
+    /// Replace the schema file of the table at table_dir with this schema.
+    fn save(&self, table_dir: &Path) -> IoResult<()> {
+        let json = serde_json::to_vec_pretty(self).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let path = table_dir.join(SCHEMA_FILE);
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&json)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        Durability::Sync.sync_dir(table_dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_schema_update_and_load() {
+        let dir = tempdir().unwrap();
+        assert_eq!(TableSchema::load(dir.path()).unwrap(), None);
+
+        let options = ColumnFamilyOptions { max_versions: Some(3), ttl_ms: None, ..Default::default() };
+        TableSchema::update(dir.path(), |schema| {
+            schema.column_families.insert("users".into(), ColumnFamilyDescriptor { options, regioned: false });
+            Ok(())
+        })
+        .unwrap();
+        let schema = TableSchema::update(dir.path(), |schema| {
+            schema.column_families.insert("events".into(), ColumnFamilyDescriptor { regioned: true, ..Default::default() });
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(schema.version, 2);
+        assert_eq!(TableSchema::load(dir.path()).unwrap(), Some(schema.clone()));
+        assert_eq!(schema.column_families["users"].options, options);
+
+        // A failed change leaves the file untouched
+        let err = TableSchema::update(dir.path(), |_| Err(Error::new(ErrorKind::InvalidInput, "no")));
+        assert!(err.is_err());
+        assert_eq!(TableSchema::load(dir.path()).unwrap().unwrap().version, 2);
+    }
+}
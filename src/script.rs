@@ -0,0 +1,161 @@
+//! Sandboxed [Rhai] predicates for `Filter::Script`, evaluated against each cell value
+//! when RedBase is built with the `scripting` feature (on by default).
+//!
+//! [Rhai]: https://rhai.rs
+
+use std::time::Duration;
+
+/// Operations one evaluation of a script may take.
+pub const MAX_OPERATIONS: u64 = 100_000;
+
+/// Wall-clock time one evaluation of a script may take.
+pub const TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Compile source, returning the error a script filter would fail with.
+pub fn check(source: &str) -> Result<(), String> {
+    imp::compiled(source).map(|_| ())
+}
+
+/// Whether the script source returns true for the cell value. Scripts cannot import
+/// modules, `eval` code or print; one that fails to compile, fails, runs out of
+/// `MAX_OPERATIONS` or `TIMEOUT` or does not return a boolean matches nothing, like an
+/// invalid regex, and so does every script without the `scripting` feature.
+pub(crate) fn matches(source: &str, value: &[u8]) -> bool {
+    imp::eval(source, value).unwrap_or_else(|e| {
+        tracing::debug!(error = %e, "script filter failed");
+        false
+    })
+}
+
+#[cfg(feature = "scripting")]
+mod imp {
+    use std::{
+        cell::Cell,
+        collections::HashMap,
+        sync::{Arc, Mutex, OnceLock},
+        time::Instant,
+    };
+
+    use rhai::{module_resolvers::DummyModuleResolver, Blob, Dynamic, Engine, Scope, AST};
+
+    use super::{MAX_OPERATIONS, TIMEOUT};
+
+    /// Compiled scripts are kept up to this many; the cache is emptied once it is full.
+    const CACHE_CAPACITY: usize = 256;
+
+    /// Largest string, array or BLOB a script may build, which also bounds the cell
+    /// values scripts can be evaluated against.
+    const MAX_VALUE_SIZE: usize = 16 * 1024 * 1024;
+
+    thread_local! {
+        /// Deadline of the evaluation running on this thread.
+        static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+    }
+
+    fn engine() -> &'static Engine {
+        static ENGINE: OnceLock<Engine> = OnceLock::new();
+        ENGINE.get_or_init(|| {
+            let mut engine = Engine::new();
+            engine
+                .set_module_resolver(DummyModuleResolver::new())
+                .disable_symbol("eval")
+                .on_print(|_| {})
+                .on_debug(|_, _, _| {})
+                .set_max_operations(MAX_OPERATIONS)
+                .set_max_call_levels(16)
+                .set_max_expr_depths(64, 32)
+                .set_max_string_size(MAX_VALUE_SIZE)
+                .set_max_array_size(MAX_VALUE_SIZE)
+                .set_max_map_size(1024)
+                .on_progress(|_| {
+                    let expired = DEADLINE.with(|deadline| deadline.get().is_some_and(|d| Instant::now() >= d));
+                    expired.then(|| Dynamic::from("timed out"))
+                });
+            engine
+        })
+    }
+
+    type Compiled = Result<Arc<AST>, String>;
+
+    /// The compiled script, from the cache if it was compiled before.
+    pub fn compiled(source: &str) -> Compiled {
+        static CACHE: OnceLock<Mutex<HashMap<String, Compiled>>> = OnceLock::new();
+        let cache = CACHE.get_or_init(Default::default);
+        if let Some(compiled) = cache.lock().unwrap().get(source) {
+            return compiled.clone();
+        }
+
+        let compiled = engine()
+            .compile(source)
+            .map(Arc::new)
+            .map_err(|e| format!("invalid script: {}", e));
+        let mut cache = cache.lock().unwrap();
+        if cache.len() >= CACHE_CAPACITY {
+            cache.clear();
+        }
+        cache.insert(source.to_string(), compiled.clone());
+        compiled
+    }
+
+    pub fn eval(source: &str, value: &[u8]) -> Result<bool, String> {
+        let ast = compiled(source)?;
+        let mut scope = Scope::new();
+        scope.push_constant("value", Blob::from(value));
+        match std::str::from_utf8(value) {
+            Ok(text) => scope.push_constant("text", text.to_string()),
+            Err(_) => scope.push_constant("text", ()),
+        };
+
+        DEADLINE.with(|deadline| deadline.set(Some(Instant::now() + TIMEOUT)));
+        let result = engine().eval_ast_with_scope::<Dynamic>(&mut scope, &ast);
+        DEADLINE.with(|deadline| deadline.set(None));
+
+        let result = result.map_err(|e| format!("script failed: {}", e))?;
+        result.as_bool().map_err(|type_name| format!("script returned {}, not a bool", type_name))
+    }
+}
+
+#[cfg(not(feature = "scripting"))]
+mod imp {
+    const DISABLED: &str = "script filters need the scripting feature";
+
+    pub fn compiled(_source: &str) -> Result<(), String> {
+        Err(DISABLED.to_string())
+    }
+
+    pub fn eval(_source: &str, _value: &[u8]) -> Result<bool, String> {
+        Err(DISABLED.to_string())
+    }
+}
+
+#[cfg(all(test, feature = "scripting"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_predicates() {
+        assert!(matches("text.parse_int() > 40", b"42"));
+        assert!(!matches("text.parse_int() > 40", b"7"));
+        assert!(matches("value.len() == 3 && value[0] == 0xff", &[0xff, 0, 1]));
+        // Statements are accepted too, with the last expression as the result
+        assert!(matches("let n = text.len(); n > 2", b"abc"));
+        // Non-UTF-8 values leave text unit
+        assert!(matches("text == ()", &[0xff, 0xfe]));
+
+        // Errors and non-boolean results match nothing
+        assert!(!matches("text.parse_int() > 40", b"forty"));
+        assert!(!matches("value.len()", b"abc"));
+        assert!(check("value.len() >").is_err());
+        assert!(check("text.len() > 3").is_ok());
+    }
+
+    #[test]
+    fn test_script_sandbox() {
+        // Runaway scripts are stopped
+        assert!(!matches("loop {}", b"x"));
+        assert!(!matches("let s = \"x\"; loop { s += s; }", b"x"));
+        // No imports or eval
+        assert!(!matches("import \"other\" as m; true", b"x"));
+        assert!(check("eval(\"true\")").is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use std::{
+    io::{Error, Result as IoResult},
+    path::PathBuf,
+};
+
+use clap::{CommandFactory, Parser, Subcommand};
+use rustyline::{
+    completion::Completer,
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::Validator,
+    Context, Editor, Helper,
+};
+
+use crate::cli::{execute, Backend, Command, OutputFormat};
+
+/// Commands whose first argument is a column family name
+const CF_COMMANDS: &[&str] = &["put", "get", "scan", "delete", "flush", "compact", "freeze", "stats", "verify", "import", "export"];
+
+/// A single line entered at the shell prompt.
+#[derive(Parser, Debug)]
+#[command(multicall = true)]
+struct ShellLine {
+    #[command(subcommand)]
+    command: ShellCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum ShellCommand {
+    #[command(flatten)]
+    Table(Command),
+    /// Leave the shell
+    #[command(alias = "quit")]
+    Exit,
+}
+
+/// Tab completion over command names and the table's column families.
+struct ShellHelper {
+    commands: Vec<String>,
+    column_families: Vec<String>,
+}
+
+impl ShellHelper {
+    fn new() -> Self {
+        let mut commands: Vec<String> = ShellLine::command()
+            .get_subcommands()
+            .map(|c| c.get_name().to_string())
+            .filter(|name| name != "shell")
+            .collect();
+        commands.push("quit".to_string());
+        commands.sort();
+        Self {
+            commands,
+            column_families: Vec::new(),
+        }
+    }
+
+    /// Return the start of the word under the cursor and the candidates completing it.
+    fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let before = &line[..pos];
+        let start = before.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let prefix = &before[start..];
+        let preceding: Vec<&str> = before[..start].split_whitespace().collect();
+
+        let pool = match preceding.as_slice() {
+            [] => &self.commands,
+            [command] if CF_COMMANDS.contains(command) => &self.column_families,
+            _ => return (start, Vec::new()),
+        };
+
+        let matches = pool.iter()
+            .filter(|candidate| candidate.starts_with(prefix))
+            .cloned()
+            .collect();
+        (start, matches)
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+fn readline_error(e: ReadlineError) -> Error {
+    match e {
+        ReadlineError::Io(e) => e,
+        other => Error::other(other.to_string()),
+    }
+}
+
+fn default_history_file() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".redbase_history"))
+}
+
+/// Run an interactive read-eval-print loop against `backend`.
+///
+/// Each line is parsed like a `redbase-cli` subcommand (`put`, `get`, `scan`, `delete`,
+/// `describe`, ...). `help` lists the commands, `exit`/`quit` or Ctrl-D leave the shell,
+/// and history is persisted to `history_file` (or `~/.redbase_history`).
+pub fn run_shell(backend: &mut dyn Backend, format: OutputFormat, history_file: Option<PathBuf>) -> IoResult<()> {
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::new().map_err(readline_error)?;
+    editor.set_helper(Some(ShellHelper::new()));
+
+    let history_file = history_file.or_else(default_history_file);
+    if let Some(path) = &history_file {
+        // A missing history file is expected on first use
+        let _ = editor.load_history(path);
+    }
+
+    let mut description = backend.describe()?;
+    let prompt = format!("redbase({})> ", description.table);
+
+    loop {
+        if let Some(helper) = editor.helper_mut() {
+            helper.column_families = std::mem::take(&mut description.column_families);
+        }
+
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(readline_error(e)),
+        };
+
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line).map_err(readline_error)?;
+
+        let Some(words) = shlex::split(line) else {
+            eprintln!("error: unbalanced quotes");
+            continue;
+        };
+
+        match ShellLine::try_parse_from(words) {
+            Ok(ShellLine { command: ShellCommand::Exit }) => break,
+            Ok(ShellLine { command: ShellCommand::Table(command) }) => match execute(backend, &command) {
+                Ok(output) => println!("{}", output.render(format)),
+                Err(e) => eprintln!("error: {}", e),
+            },
+            // Also covers `help` and `--help`, which clap reports as errors
+            Err(e) => {
+                let _ = e.print();
+            }
+        }
+
+        // Refresh completions, e.g. after `create-cf`
+        if let Ok(latest) = backend.describe() {
+            description = latest;
+        }
+    }
+
+    if let Some(path) = &history_file {
+        editor.save_history(path).map_err(readline_error)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completion_candidates() {
+        let mut helper = ShellHelper::new();
+        helper.column_families = vec!["default".to_string(), "metrics".to_string()];
+
+        let (start, commands) = helper.candidates("de", 2);
+        assert_eq!(start, 0);
+        assert_eq!(commands, vec!["delete", "describe"]);
+
+        let (start, cfs) = helper.candidates("get me", 6);
+        assert_eq!(start, 4);
+        assert_eq!(cfs, vec!["metrics"]);
+
+        let (_, none) = helper.candidates("get default ro", 14);
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn test_line_parsing() {
+        let words = shlex::split("put default row1 col1 'hello world'").unwrap();
+        match ShellLine::try_parse_from(words).unwrap().command {
+            ShellCommand::Table(Command::Put { value, .. }) => assert_eq!(value, "hello world"),
+            other => panic!("Expected put, got {:?}", other),
+        }
+
+        let words = shlex::split("quit").unwrap();
+        assert!(matches!(ShellLine::try_parse_from(words).unwrap().command, ShellCommand::Exit));
+    }
+}
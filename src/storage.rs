@@ -1,107 +1,837 @@
-use crate::api::{Entry, EntryKey, CellValue, Column, Timestamp};
+use crate::api::{Entry, EntryKey, CellValue, Column, RowKey, Seq, Timestamp};
+use crate::backend::{LocalFs, StorageBackend, WriteFile};
+use crate::bloom::{BloomFilter, BloomFilterBuilder, BloomFilterOptions, PrefixBloomFilter};
 use bincode;
 use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument};
 use std::{
+    borrow::Cow,
+    fmt,
     fs::File,
-    io::{BufReader, BufWriter, Read, Result as IoResult, Write},
-    path::Path,
+    io::{BufReader, BufWriter, Cursor, Error, ErrorKind, Read, Result as IoResult, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
 };
+use std::sync::Arc;
 
 /// An on-disk SSTable.
 /// Format (all big-endian u32 for lengths):
 ///
-/// 1) [u32: number_of_entries]
+/// 1) Header:
+///    a) [u32: `SSTABLE_MAGIC`]
+///    b) [u32: format version, see `FormatVersion`]
+///    c) [u32: number_of_entries]
 /// 2) For each entry:
 ///    a) [u32: length of serialized EntryKey]
-///    b) [bytes: bincode(serialized EntryKey)]
+///    b) [bytes: bincode(serialized EntryKey), then its sequence number (see `serialize_key`)]
 ///    c) [u32: length of serialized CellValue]
 ///    d) [bytes: bincode(serialized CellValue)]
+///    e) [u32: CRC32C of a) to d)]
+/// 3) Footer (see `SSTableFooter`):
+///    a) [bytes: bincode(serialized SSTableFooter)]
+///    b) [u32: CRC32C of a)]
+///    c) [u32: length of serialized SSTableFooter]
+///    d) [u32: `FOOTER_MAGIC`]
+///
+/// Checksums are verified when the file is opened, so that a bit flipped on disk fails
+/// the open with a `ChecksumMismatch` rather than being read back as another value.
+///
+/// Readers dispatch on the format version, so that files of every version can be read
+/// side by side; a version newer than the reader knows fails with `Unsupported`.
+///
+/// SSTables written before headers existed (`FormatVersion::Legacy`) start with the
+/// number of entries and end after them, without checksums; their footer is computed
+/// from the entries when they are opened.
 pub struct SSTable;
 
+/// Starts an SSTable with a header.
+pub const SSTABLE_MAGIC: u32 = 0x5242_5354; // "RBST"
+
+/// Version of the format of an SSTable file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum FormatVersion {
+    /// Written before headers existed: the entry count, then the entries, without
+    /// checksums or footer
+    Legacy = 1,
+    /// Header, then checksummed entries and footer
+    V2 = 2,
+}
+
+impl FormatVersion {
+    /// The version new SSTables are written in.
+    pub const CURRENT: FormatVersion = FormatVersion::V2;
+
+    /// The version numbered number in a header, if this build knows it.
+    pub fn from_number(number: u32) -> Option<Self> {
+        match number {
+            2 => Some(FormatVersion::V2),
+            _ => None,
+        }
+    }
+
+    pub fn number(self) -> u32 {
+        self as u32
+    }
+
+    /// Bytes before the first entry.
+    fn header_len(self) -> usize {
+        match self {
+            FormatVersion::Legacy => 4,
+            FormatVersion::V2 => 12,
+        }
+    }
+
+    /// Whether entries of this version are followed by their checksum.
+    fn checksummed(self) -> bool {
+        self != FormatVersion::Legacy
+    }
+}
+
+impl fmt::Display for FormatVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatVersion::Legacy => write!(f, "legacy"),
+            version => write!(f, "v{}", version.number()),
+        }
+    }
+}
+
+/// Write the header of an SSTable of the current version holding count entries.
+fn write_header(w: &mut impl Write, count: u32) -> IoResult<()> {
+    w.write_all(&SSTABLE_MAGIC.to_be_bytes())?;
+    w.write_all(&FormatVersion::CURRENT.number().to_be_bytes())?;
+    w.write_all(&count.to_be_bytes())
+}
+
+/// Read the header of the SSTable at path from r: its format version and entry count.
+fn read_header(path: &Path, r: &mut impl Read) -> IoResult<(FormatVersion, usize)> {
+    let truncated = |_| Error::new(ErrorKind::InvalidData, format!("Truncated SSTable header in {}", path.display()));
+    let mut buf4 = [0u8; 4];
+    r.read_exact(&mut buf4).map_err(truncated)?;
+    // Files without a header start with their entry count instead; no count of a file
+    // smaller than 10 GiB can be mistaken for the magic
+    if u32::from_be_bytes(buf4) != SSTABLE_MAGIC {
+        return Ok((FormatVersion::Legacy, u32::from_be_bytes(buf4) as usize));
+    }
+    r.read_exact(&mut buf4).map_err(truncated)?;
+    let number = u32::from_be_bytes(buf4);
+    let version = FormatVersion::from_number(number).ok_or_else(|| Error::new(
+        ErrorKind::Unsupported,
+        format!(
+            "SSTable {} has format version {}, but this build reads up to version {}",
+            path.display(), number, FormatVersion::CURRENT.number(),
+        ),
+    ))?;
+    r.read_exact(&mut buf4).map_err(truncated)?;
+    Ok((version, u32::from_be_bytes(buf4) as usize))
+}
+
+/// Marks the end of an SSTable with a footer, whose entries and footer are checksummed.
+pub const FOOTER_MAGIC: u32 = 0x5242_4636; // "RBF6"
+
+/// An SSTable entry or footer whose bytes do not match the checksum stored with them,
+/// e.g. after a bit flipped on disk.
+///
+/// It is wrapped in an `std::io::Error` of kind `InvalidData`, and can be recovered
+/// with `ChecksumMismatch::from_io`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumMismatch {
+    pub path: PathBuf,
+    /// Index of the damaged entry, `None` for the footer
+    pub entry: Option<usize>,
+    /// Checksum stored in the file
+    pub stored: u32,
+    /// Checksum of the bytes read
+    pub computed: u32,
+}
+
+impl ChecksumMismatch {
+    /// The checksum mismatch that caused err, if any.
+    pub fn from_io(err: &Error) -> Option<&ChecksumMismatch> {
+        err.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.entry {
+            Some(i) => write!(f, "Corrupt SSTable {} at entry {}", self.path.display(), i)?,
+            None => write!(f, "Corrupt SSTable footer in {}", self.path.display())?,
+        }
+        write!(f, ": checksum mismatch ({:08x} stored, {:08x} computed)", self.stored, self.computed)
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {}
+
+impl From<ChecksumMismatch> for Error {
+    fn from(err: ChecksumMismatch) -> Self {
+        Error::new(ErrorKind::InvalidData, err)
+    }
+}
+
+/// Check the checksum computed for the entry (or footer) of the SSTable at path
+/// against the one stored.
+fn verify_checksum(path: &Path, entry: Option<usize>, stored: u32, computed: u32) -> IoResult<()> {
+    if computed != stored {
+        return Err(ChecksumMismatch { path: path.to_path_buf(), entry, stored, computed }.into());
+    }
+    Ok(())
+}
+
+/// Checksum of an entry with the serialized key and value: that of its bytes, length
+/// prefixes included.
+fn entry_checksum(key: &[u8], value: &[u8]) -> u32 {
+    let crc = crc32c::crc32c(&(key.len() as u32).to_be_bytes());
+    let crc = crc32c::crc32c_append(crc, key);
+    let crc = crc32c::crc32c_append(crc, &(value.len() as u32).to_be_bytes());
+    crc32c::crc32c_append(crc, value)
+}
+
+
+/// Summary of an SSTable's entries, stored at the end of the file so that readers can
+/// skip SSTables that cannot hold what they look for.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct SSTableFooter {
+    /// Number of entries
+    pub entries: u64,
+    /// Number of entries that are delete markers
+    pub tombstones: u64,
+    /// Number of distinct rows
+    pub rows: u64,
+    /// Smallest and largest row key of the entries, `None` if there are none
+    pub row_range: Option<(RowKey, RowKey)>,
+    /// Oldest and newest timestamp of the entries, `None` if there are none
+    pub time_range: Option<(Timestamp, Timestamp)>,
+    /// Bloom filter of the rows or cells, `None` if bloom filters were disabled or did
+    /// not exist yet when the file was written
+    pub bloom: Option<BloomFilter>,
+    /// Bloom filter of the row prefixes, `None` unless the column family asked for one
+    pub prefix_bloom: Option<PrefixBloomFilter>,
+}
+
+impl SSTableFooter {
+    /// Account for the next entry in key order.
+    pub(crate) fn add(&mut self, key: &EntryKey, cell: &CellValue) {
+        self.entries += 1;
+        if matches!(cell, CellValue::Delete(_) | CellValue::DeleteFamily) {
+            self.tombstones += 1;
+        }
+        // In key order, a row is new exactly when it extends the row range
+        let new_row = match &mut self.row_range {
+            Some((min, _)) if key.row < *min => {
+                *min = key.row.clone();
+                true
+            }
+            Some((_, max)) if key.row > *max => {
+                *max = key.row.clone();
+                true
+            }
+            Some(_) => false,
+            None => {
+                self.row_range = Some((key.row.clone(), key.row.clone()));
+                true
+            }
+        };
+        self.rows += new_row as u64;
+        let ts = key.timestamp;
+        self.time_range = Some(match self.time_range {
+            Some((min, max)) => (min.min(ts), max.max(ts)),
+            None => (ts, ts),
+        });
+    }
+
+    /// Whether an entry may belong to row.
+    pub fn may_contain_row(&self, row: &[u8]) -> bool {
+        self.may_contain_prefix(row) && self.bloom.as_ref().is_none_or(|bloom| bloom.may_contain_row(row))
+    }
+
+    /// Whether an entry may belong to (row, column).
+    pub fn may_contain_cell(&self, row: &[u8], column: &[u8]) -> bool {
+        self.may_contain_prefix(row) && self.bloom.as_ref().is_none_or(|bloom| bloom.may_contain_cell(row, column))
+    }
+
+    /// Whether an entry may have a row starting with prefix. A row of the range is one
+    /// unless the smallest row is past every such row.
+    pub fn may_contain_prefix(&self, prefix: &[u8]) -> bool {
+        self.row_range.as_ref().is_some_and(|(min, max)| {
+            prefix <= max.as_slice() && (min.as_slice() <= prefix || min.starts_with(prefix))
+        }) && self.prefix_bloom.as_ref().is_none_or(|bloom| bloom.may_contain_prefix(prefix))
+    }
+
+    /// Whether an entry may have a row in [start_row, end_row].
+    pub fn overlaps_rows(&self, start_row: &[u8], end_row: &[u8]) -> bool {
+        self.row_range.as_ref().is_some_and(|(min, max)| min.as_slice() <= end_row && start_row <= max.as_slice())
+    }
+
+    /// Whether an entry may have a timestamp in [start_time, end_time].
+    pub fn overlaps_time_range(&self, start_time: Timestamp, end_time: Timestamp) -> bool {
+        self.time_range.is_some_and(|(min, max)| min <= end_time && start_time <= max)
+    }
+
+    fn write(&self, w: &mut impl Write) -> IoResult<usize> {
+        let ser = bincode::serialize(self).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        w.write_all(&ser)?;
+        w.write_all(&crc32c::crc32c(&ser).to_be_bytes())?;
+        w.write_all(&(ser.len() as u32).to_be_bytes())?;
+        w.write_all(&FOOTER_MAGIC.to_be_bytes())?;
+        Ok(ser.len() + 12)
+    }
+
+    /// Decode the footer of an SSTable of version at path from tail, the bytes following
+    /// the entries, and check it against count, the number of entries; `None` for legacy
+    /// SSTables, which have none.
+    fn decode(path: &Path, version: FormatVersion, tail: &[u8], count: usize) -> IoResult<Option<Self>> {
+        let bad = |reason: &str| Error::new(
+            ErrorKind::InvalidData,
+            format!("Corrupt SSTable footer in {}: {}", path.display(), reason),
+        );
+        if version == FormatVersion::Legacy {
+            return match tail.is_empty() {
+                true => Ok(None),
+                false => Err(bad("bytes past the entries of a legacy SSTable")),
+            };
+        }
+        let Some(split) = tail.len().checked_sub(12) else {
+            return Err(bad("truncated"));
+        };
+        let (ser, trailer) = tail.split_at(split);
+        let len = u32::from_be_bytes(trailer[4..8].try_into().unwrap()) as usize;
+        let magic = u32::from_be_bytes(trailer[8..].try_into().unwrap());
+        if magic != FOOTER_MAGIC || len != ser.len() {
+            return Err(bad("bad trailer"));
+        }
+        verify_checksum(path, None, u32::from_be_bytes(trailer[..4].try_into().unwrap()), crc32c::crc32c(ser))?;
+        let footer: Self = bincode::deserialize(ser).map_err(|e| bad(&e.to_string()))?;
+        // The entry count in the header is not checksummed, but must agree
+        if footer.entries != count as u64 {
+            return Err(bad(&format!("{} entries, but the header has a count of {}", footer.entries, count)));
+        }
+        Ok(Some(footer))
+    }
+}
+
 impl SSTable {
-    /// Create an SSTable at path from a sorted slice of Entry.
+    /// Create an SSTable at path from a sorted slice of Entry, synced to disk.
     pub fn create(path: impl AsRef<Path>, entries: &[Entry]) -> IoResult<()> {
-        let f = File::create(path)?;
-        let mut w = BufWriter::new(f);
+        Self::create_with(path, entries, Durability::Sync)
+    }
 
-        let count = (entries.len() as u32).to_be_bytes();
-        w.write_all(&count)?;
+    /// Create an SSTable at path from a sorted slice of Entry, with the given durability
+    /// and a bloom filter of the default options.
+    pub fn create_with(path: impl AsRef<Path>, entries: &[Entry], durability: Durability) -> IoResult<()> {
+        Self::create_with_bloom_filter(path, entries, durability, BloomFilterOptions::default())
+    }
+
+    /// Create an SSTable at path from a sorted slice of Entry, with the given durability
+    /// and bloom filter.
+    #[instrument(level = "debug", skip_all, fields(path = %path.as_ref().display(), entries = entries.len()))]
+    pub fn create_with_bloom_filter(
+        path: impl AsRef<Path>,
+        entries: &[Entry],
+        durability: Durability,
+        bloom: BloomFilterOptions,
+    ) -> IoResult<()> {
+        Self::create_paced(path, entries, durability, bloom, |_| ())
+    }
+
+    /// Create an SSTable at path of backend from a sorted slice of Entry, with the
+    /// given durability.
+    pub fn create_in(backend: &dyn StorageBackend, path: impl AsRef<Path>, entries: &[Entry], durability: Durability) -> IoResult<()> {
+        Self::write(backend, path.as_ref(), entries, durability, BloomFilterOptions::default(), |_| ())
+    }
 
+    /// Like `create_with_bloom_filter`, calling pace with the size of every entry once it
+    /// is written, so that background writers can throttle themselves.
+    pub(crate) fn create_paced(
+        path: impl AsRef<Path>,
+        entries: &[Entry],
+        durability: Durability,
+        bloom: BloomFilterOptions,
+        pace: impl FnMut(u64),
+    ) -> IoResult<()> {
+        Self::write(&LocalFs, path.as_ref(), entries, durability, bloom, pace)
+    }
+
+    fn write(
+        backend: &dyn StorageBackend,
+        path: &Path,
+        entries: &[Entry],
+        durability: Durability,
+        bloom: BloomFilterOptions,
+        mut pace: impl FnMut(u64),
+    ) -> IoResult<()> {
+        // Unlink rather than truncate an existing file, which may be hard-linked by a snapshot
+        let _ = backend.remove(path);
+        let mut w = BufWriter::new(backend.create(path)?);
+
+        write_header(&mut w, entries.len() as u32)?;
+
+        let mut footer = SSTableFooter::default();
+        let mut bloom = BloomFilterBuilder::new(bloom);
         for entry in entries {
-            let key_ser = bincode::serialize(&entry.key).unwrap();
-            let key_len = (key_ser.len() as u32).to_be_bytes();
-            w.write_all(&key_len)?;
-            w.write_all(&key_ser)?;
+            pace(write_entry(&mut w, entry)? as u64);
+            footer.add(&entry.key, &entry.value);
+            bloom.add(&entry.key);
+        }
+        (footer.bloom, footer.prefix_bloom) = bloom.finish();
+        pace(footer.write(&mut w)? as u64);
+        let mut file = w.into_inner().map_err(|e| e.into_error())?;
+        durability.commit(backend, file.as_mut(), path)
+    }
+}
+
+/// How far writes of SSTables (and changes to the set of files) are pushed to disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Durability {
+    /// Only hand writes to the OS. Faster, but files written or removed shortly before a
+    /// power loss may be missing, torn or back again after it.
+    Buffered,
+    /// fsync every new file, and its directory after files are created, renamed or removed.
+    #[default]
+    Sync,
+}
+
+impl Durability {
+    /// Sync the data and metadata of file.
+    pub fn sync_file(self, file: &File) -> IoResult<()> {
+        match self {
+            Durability::Buffered => Ok(()),
+            Durability::Sync => file.sync_all(),
+        }
+    }
+
+    /// Sync the directory dir, making the creation, renaming or removal of its entries durable.
+    pub fn sync_dir(self, dir: &Path) -> IoResult<()> {
+        match self {
+            Durability::Buffered => Ok(()),
+            // Directories cannot be opened as files on Windows, where NTFS journals entries
+            Durability::Sync if cfg!(windows) => Ok(()),
+            Durability::Sync => File::open(dir)?.sync_all(),
+        }
+    }
+
+    /// Sync the directory containing path.
+    pub fn sync_parent(self, path: &Path) -> IoResult<()> {
+        self.sync_dir(parent_dir(path))
+    }
+
+    /// Flush file, written at path of backend, and sync it and its directory.
+    fn commit(self, backend: &dyn StorageBackend, file: &mut dyn WriteFile, path: &Path) -> IoResult<()> {
+        match self {
+            Durability::Buffered => file.flush(),
+            Durability::Sync => {
+                file.sync()?;
+                backend.sync_dir(parent_dir(path))
+            }
+        }
+    }
+}
+
+/// The directory containing path, `.` for a bare file name.
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Serialize key as SSTables store it: its bincode encoding, which leaves out the
+/// sequence number, followed by the sequence number as 8 big-endian bytes.
+pub(crate) fn serialize_key(key: &EntryKey) -> IoResult<Vec<u8>> {
+    let mut ser = bincode::serialize(key).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    ser.extend_from_slice(&key.seq.to_be_bytes());
+    Ok(ser)
+}
+
+/// Decode a key serialized by `serialize_key`, or by versions before sequence numbers,
+/// which end after the bincode encoding and get sequence number 0.
+pub(crate) fn deserialize_key(buf: &[u8]) -> bincode::Result<EntryKey> {
+    let mut key: EntryKey = bincode::deserialize(buf)?;
+    key.seq = trailing_seq(buf, bincode::serialized_size(&key)?)?;
+    Ok(key)
+}
+
+/// The sequence number following the first used bytes of record, 0 if the record ends
+/// there.
+pub(crate) fn trailing_seq(record: &[u8], used: u64) -> bincode::Result<Seq> {
+    match record.get(used as usize..) {
+        Some([]) => Ok(0),
+        Some(rest) => rest.try_into().map(Seq::from_be_bytes).map_err(|_| {
+            bincode::ErrorKind::Custom(format!("{} unexpected bytes after the key", rest.len())).into()
+        }),
+        None => Err(bincode::ErrorKind::Custom("record shorter than its key".to_string()).into()),
+    }
+}
+
+/// Write entry in the SSTable format, returning the number of bytes written.
+fn write_entry(w: &mut impl Write, entry: &Entry) -> IoResult<usize> {
+    let key_ser = serialize_key(&entry.key)?;
+    let key_len = (key_ser.len() as u32).to_be_bytes();
+    w.write_all(&key_len)?;
+    w.write_all(&key_ser)?;
+
+    let val_ser = bincode::serialize(&entry.value).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+    let val_len = (val_ser.len() as u32).to_be_bytes();
+    w.write_all(&val_len)?;
+    w.write_all(&val_ser)?;
+    w.write_all(&entry_checksum(&key_ser, &val_ser).to_be_bytes())?;
+    Ok(12 + key_ser.len() + val_ser.len())
+}
 
-            let val_ser = bincode::serialize(&entry.value).unwrap();
-            let val_len = (val_ser.len() as u32).to_be_bytes();
-            w.write_all(&val_len)?;
-            w.write_all(&val_ser)?;
+/// Number of bytes entry takes in an SSTable.
+pub(crate) fn entry_size(entry: &Entry) -> u64 {
+    let key_len = bincode::serialized_size(&entry.key).unwrap_or(0) + 8;
+    let val_len = bincode::serialized_size(&entry.value).unwrap_or(0);
+    12 + key_len + val_len
+}
+
+/// Streaming SSTable builder for data that does not fit in memory at once.
+///
+/// Writes the same format as `SSTable::create`, back-filling the entry count of the
+/// header on `finish`. Entries must be appended in strictly increasing EntryKey order.
+pub struct SSTableWriter {
+    w: BufWriter<Box<dyn WriteFile>>,
+    backend: Arc<dyn StorageBackend>,
+    path: PathBuf,
+    durability: Durability,
+    count: u32,
+    last_key: Option<EntryKey>,
+    footer: SSTableFooter,
+    bloom: BloomFilterBuilder,
+}
+
+impl SSTableWriter {
+    /// Create (or replace) the SSTable file at path, synced to disk on `finish`.
+    pub fn create(path: impl AsRef<Path>) -> IoResult<Self> {
+        Self::create_with(path, Durability::Sync)
+    }
+
+    /// Create (or replace) the SSTable file at path, with the given durability.
+    pub fn create_with(path: impl AsRef<Path>, durability: Durability) -> IoResult<Self> {
+        Self::create_in(Arc::new(LocalFs), path, durability)
+    }
+
+    /// Create (or replace) the SSTable file at path of backend, with the given durability.
+    pub fn create_in(backend: Arc<dyn StorageBackend>, path: impl AsRef<Path>, durability: Durability) -> IoResult<Self> {
+        let path = path.as_ref();
+        // Unlink rather than truncate an existing file, which may be hard-linked by a snapshot
+        let _ = backend.remove(path);
+        let mut w = BufWriter::new(backend.create(path)?);
+        write_header(&mut w, 0)?;
+        Ok(Self {
+            w,
+            backend,
+            path: path.to_path_buf(),
+            durability,
+            count: 0,
+            last_key: None,
+            footer: SSTableFooter::default(),
+            bloom: BloomFilterBuilder::new(BloomFilterOptions::default()),
+        })
+    }
+
+    /// Write a bloom filter of options rather than of the default ones. Must be called
+    /// before the first entry is appended.
+    pub fn with_bloom_filter(mut self, options: BloomFilterOptions) -> Self {
+        debug_assert!(self.is_empty(), "bloom filter options set after entries were appended");
+        self.bloom = BloomFilterBuilder::new(options);
+        self
+    }
+
+    /// Append the next entry. Fails with `InvalidInput` if it is not sorted after the previous one.
+    pub fn append(&mut self, entry: &Entry) -> IoResult<()> {
+        if self.last_key.as_ref().is_some_and(|last| *last >= entry.key) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "SSTable entries must be appended in strictly increasing key order",
+            ));
         }
-        w.flush()?;
+        if self.count == u32::MAX {
+            return Err(Error::new(ErrorKind::InvalidInput, "SSTable entry count overflow"));
+        }
+
+        write_entry(&mut self.w, entry)?;
+        self.footer.add(&entry.key, &entry.value);
+        self.bloom.add(&entry.key);
+        self.count += 1;
+        self.last_key = Some(entry.key.clone());
         Ok(())
     }
+
+    /// Number of entries appended so far.
+    pub fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Write the footer and entry count, flush and sync the file and its directory.
+    pub fn finish(mut self) -> IoResult<()> {
+        (self.footer.bloom, self.footer.prefix_bloom) = self.bloom.finish();
+        self.footer.write(&mut self.w)?;
+        self.w.seek(SeekFrom::Start(FormatVersion::CURRENT.header_len() as u64 - 4))?;
+        self.w.write_all(&self.count.to_be_bytes())?;
+        let mut file = self.w.into_inner().map_err(|e| e.into_error())?;
+        self.durability.commit(self.backend.as_ref(), file.as_mut(), &self.path)
+    }
 }
 
-/// A reader for a single SSTable. For simplicity, we load all entries into memory on open().
+/// Files at least this large are memory-mapped by `SSTableReader::open` rather than
+/// loaded onto the heap, when the `mmap` feature is enabled (Unix only).
+pub const MMAP_MIN_FILE_BYTES: u64 = 4 * 1024 * 1024;
+
+/// A reader for a single SSTable.
+///
+/// Small files are loaded into memory on open. With the `mmap` feature (on by default),
+/// larger files are memory-mapped instead: only an index of entry offsets is kept on
+/// the heap, and entries are deserialized when they are looked up. SSTables are never
+/// modified in place (they are replaced by unlinking or renaming), so a mapping stays
+/// valid for as long as the reader lives.
 #[derive(Clone)]
 pub struct SSTableReader {
-    entries: Vec<(EntryKey, CellValue)>,
+    entries: Entries,
+    footer: SSTableFooter,
+    version: FormatVersion,
+}
+
+#[derive(Clone)]
+enum Entries {
+    /// Every entry, deserialized onto the heap
+    Loaded(Vec<(EntryKey, CellValue)>),
+    /// Entries deserialized on access from a mapped file
+    #[cfg(all(feature = "mmap", unix))]
+    Mapped(Arc<MappedEntries>),
+}
+
+impl Entries {
+    fn len(&self) -> usize {
+        match self {
+            Entries::Loaded(entries) => entries.len(),
+            #[cfg(all(feature = "mmap", unix))]
+            Entries::Mapped(mapped) => mapped.offsets.len(),
+        }
+    }
 }
 
 impl SSTableReader {
-    /// Open an SSTable file, read all entries (key + CellValue) into memory.
+    /// Open an SSTable file: memory-map it if it is large and the `mmap` feature is
+    /// enabled, otherwise read all entries (key + CellValue) into memory.
     pub fn open(path: impl AsRef<Path>) -> IoResult<Self> {
+        #[cfg(all(feature = "mmap", unix))]
+        if std::fs::metadata(&path)?.len() >= MMAP_MIN_FILE_BYTES {
+            return Self::open_mapped(path);
+        }
+        Self::load(path)
+    }
+
+    /// Open an SSTable file, reading all entries (key + CellValue) into memory.
+    #[instrument(level = "trace", skip_all, fields(path = %path.as_ref().display()))]
+    pub fn load(path: impl AsRef<Path>) -> IoResult<Self> {
+        let path = path.as_ref();
         let f = File::open(path)?;
-        let mut r = BufReader::new(f);
+        let file_len = f.metadata()?.len() as usize;
+        Self::parse(path, BufReader::new(f), file_len)
+    }
+
+    /// Open the SSTable file at path of backend, reading all entries (key + CellValue)
+    /// into memory. Only local files can be memory-mapped, by `open`.
+    #[instrument(level = "trace", skip_all, fields(path = %path.as_ref().display()))]
+    pub fn open_in(backend: &dyn StorageBackend, path: impl AsRef<Path>) -> IoResult<Self> {
+        let path = path.as_ref();
+        let data = backend.read(path)?;
+        let file_len = data.len();
+        Self::parse(path, Cursor::new(data), file_len)
+    }
 
+    /// Read the entries and footer of the file_len bytes SSTable at path from r.
+    fn parse(path: &Path, mut r: impl Read + Seek, file_len: usize) -> IoResult<Self> {
+        let (version, count) = read_header(path, &mut r)?;
+        let checksummed = version.checksummed();
         let mut buf4 = [0u8; 4];
-        r.read_exact(&mut buf4)?;
-        let count = u32::from_be_bytes(buf4) as usize;
+        // A corrupt length must not make us allocate more than the file holds
+        let mut read_field = |r: &mut dyn Read, i: usize| -> IoResult<Vec<u8>> {
+            r.read_exact(&mut buf4)?;
+            let len = u32::from_be_bytes(buf4) as usize;
+            if len > file_len {
+                return Err(corrupt(path, i, "length past the end of the file"));
+            }
+            let mut buf = vec![0u8; len];
+            r.read_exact(&mut buf)?;
+            Ok(buf)
+        };
 
         let entries = (0..count)
-            .map(|_| -> IoResult<(EntryKey, CellValue)> {
-                r.read_exact(&mut buf4)?;
-                let key_len = u32::from_be_bytes(buf4) as usize;
-                let mut key_buf = vec![0u8; key_len];
-                r.read_exact(&mut key_buf)?;
-                let key: EntryKey = bincode::deserialize(&key_buf).unwrap();
-
-                r.read_exact(&mut buf4)?;
-                let val_len = u32::from_be_bytes(buf4) as usize;
-                let mut val_buf = vec![0u8; val_len];
-                r.read_exact(&mut val_buf)?;
-                let cell: CellValue = bincode::deserialize(&val_buf).unwrap();
-
+            .map(|i| -> IoResult<(EntryKey, CellValue)> {
+                let key_buf = read_field(&mut r, i)?;
+                let val_buf = read_field(&mut r, i)?;
+                if checksummed {
+                    let mut crc = [0u8; 4];
+                    r.read_exact(&mut crc)?;
+                    verify_checksum(path, Some(i), u32::from_be_bytes(crc), entry_checksum(&key_buf, &val_buf))?;
+                }
+                let key = deserialize_key(&key_buf).map_err(|e| corrupt(path, i, e))?;
+                let cell: CellValue = bincode::deserialize(&val_buf).map_err(|e| corrupt(path, i, e))?;
                 Ok((key, cell))
             })
             .collect::<IoResult<Vec<_>>>()?;
-        Ok(SSTableReader { entries })
+        let mut tail = Vec::new();
+        r.read_to_end(&mut tail)?;
+        let footer = SSTableFooter::decode(path, version, &tail, count)?.unwrap_or_else(|| {
+            let mut footer = SSTableFooter::default();
+            entries.iter().for_each(|(key, cell)| footer.add(key, cell));
+            footer
+        });
+        debug!(entries = entries.len(), %version, "loaded SSTable");
+        Ok(SSTableReader { entries: Entries::Loaded(entries), footer, version })
     }
 
-    /// Look up the latest CellValue for (row, column) by scanning backwards.
-    pub fn get_full(&mut self, row: &[u8], column: &[u8]) -> IoResult<Option<CellValue>> {
-        for (key, cell) in self.entries.iter().rev() {
-            if key.row.as_slice() == row && key.column.as_slice() == column {
-                return Ok(Some(cell.clone()));
+    /// Open an SSTable file by memory-mapping it. Only the offsets of its entries are
+    /// kept; fails with `InvalidData` if the file is truncated or an entry does not decode.
+    #[cfg(all(feature = "mmap", unix))]
+    #[instrument(level = "trace", skip_all, fields(path = %path.as_ref().display()))]
+    pub fn open_mapped(path: impl AsRef<Path>) -> IoResult<Self> {
+        let path = path.as_ref();
+        let map = mmap::Mmap::map(&File::open(path)?)?;
+        let data = map.as_slice();
+
+        let (version, count) = read_header(path, &mut &data[..])?;
+        let checksummed = version.checksummed();
+        // Every entry takes at least 8 bytes, which bounds the count of a corrupt header
+        let mut offsets = Vec::with_capacity(count.min(data.len() / 8));
+        let mut pos = version.header_len();
+        let mut computed = SSTableFooter::default();
+        for i in 0..count {
+            offsets.push(pos);
+            let key_pos = pos + 4;
+            let val_pos = key_pos + read_len(data, pos)?;
+            let mut end = val_pos + 4 + read_len(data, val_pos)?;
+            if end + 4 * checksummed as usize > data.len() {
+                return Err(Error::new(ErrorKind::InvalidData, "Truncated SSTable entry"));
             }
+            if checksummed {
+                let stored = u32::from_be_bytes(data[end..end + 4].try_into().unwrap());
+                verify_checksum(path, Some(i), stored, crc32c::crc32c(&data[pos..end]))?;
+                end += 4;
+            }
+            // Decoded once here, so that reads can rely on every entry decoding
+            let key = deserialize_key(&data[key_pos..val_pos]).map_err(|e| corrupt(path, i, e))?;
+            let cell = bincode::deserialize::<CellValue>(&data[val_pos + 4..end - 4 * checksummed as usize]).map_err(|e| corrupt(path, i, e))?;
+            computed.add(&key, &cell);
+            pos = end;
         }
-        Ok(None)
+        let footer = SSTableFooter::decode(path, version, &data[pos..], count)?.unwrap_or(computed);
+        debug!(entries = offsets.len(), bytes = data.len(), %version, "mapped SSTable");
+        Ok(SSTableReader { entries: Entries::Mapped(Arc::new(MappedEntries { map, offsets })), footer, version })
     }
 
-    /// *MVCC helper*: return all versions (timestamp + CellValue) for (row, column), sorted descending by timestamp.
-    pub fn get_versions_full(&mut self, row: &[u8], column: &[u8]) -> IoResult<Vec<(Timestamp, CellValue)>> {
-        let mut versions = Vec::new();
+    /// Whether entries are served from a memory-mapped file.
+    pub fn is_mapped(&self) -> bool {
+        !matches!(self.entries, Entries::Loaded(_))
+    }
 
-        for (key, cell) in self.entries.iter() {
-            if key.row.as_slice() == row && key.column.as_slice() == column {
-                versions.push((key.timestamp, cell.clone()));
+    /// Version of the format the file was written in.
+    pub fn format_version(&self) -> FormatVersion {
+        self.version
+    }
+
+    /// The summary stored at the end of the file, or computed for SSTables without one.
+    pub fn footer(&self) -> &SSTableFooter {
+        &self.footer
+    }
+
+    /// Every entry, borrowed without copying them if the SSTable is loaded in memory.
+    pub fn all_entries(&self) -> Cow<'_, [(EntryKey, CellValue)]> {
+        self.slice(0, self.len())
+    }
+
+    /// Number of entries in the SSTable.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.len() == 0
+    }
+
+    /// Index of the first entry in [lo, hi) whose key does not satisfy pred, given
+    /// that pred holds for a prefix of the entries.
+    fn partition_point(&self, lo: usize, hi: usize, pred: impl Fn(&EntryKey) -> bool) -> usize {
+        match &self.entries {
+            Entries::Loaded(entries) => lo + entries[lo..hi].partition_point(|(k, _)| pred(k)),
+            #[cfg(all(feature = "mmap", unix))]
+            Entries::Mapped(mapped) => {
+                let (mut lo, mut hi) = (lo, hi);
+                while lo < hi {
+                    let mid = lo + (hi - lo) / 2;
+                    if pred(&mapped.key(mid)) {
+                        lo = mid + 1;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                lo
             }
         }
+    }
 
-        versions.sort_by(|a, b| b.0.cmp(&a.0));
+    /// Entries [lo, hi), borrowed if they are in memory.
+    fn slice(&self, lo: usize, hi: usize) -> Cow<'_, [(EntryKey, CellValue)]> {
+        match &self.entries {
+            Entries::Loaded(entries) => Cow::Borrowed(&entries[lo..hi]),
+            #[cfg(all(feature = "mmap", unix))]
+            Entries::Mapped(mapped) => Cow::Owned((lo..hi).map(|i| mapped.entry(i)).collect()),
+        }
+    }
+
+    /// Whether entries are in strictly increasing EntryKey order, as lookups require.
+    pub fn is_sorted(&self) -> bool {
+        match &self.entries {
+            Entries::Loaded(entries) => entries.windows(2).all(|pair| pair[0].0 < pair[1].0),
+            #[cfg(all(feature = "mmap", unix))]
+            Entries::Mapped(mapped) => {
+                let mut keys = (0..mapped.offsets.len()).map(|i| mapped.key(i));
+                let Some(mut previous) = keys.next() else {
+                    return true;
+                };
+                keys.all(|key| {
+                    let sorted = previous < key;
+                    previous = key;
+                    sorted
+                })
+            }
+        }
+    }
+
+    /// The entries for (row, column), oldest version first.
+    /// Entries are sorted by EntryKey, so this is two binary searches.
+    pub fn cell_entries(&self, row: &[u8], column: &[u8]) -> Cow<'_, [(EntryKey, CellValue)]> {
+        let target = (row, column);
+        let len = self.len();
+        let lo = self.partition_point(0, len, |k| (k.row.as_slice(), k.column.as_slice()) < target);
+        let hi = self.partition_point(lo, len, |k| (k.row.as_slice(), k.column.as_slice()) == target);
+        self.slice(lo, hi)
+    }
+
+    /// Look up the latest CellValue for (row, column).
+    pub fn get_full(&mut self, row: &[u8], column: &[u8]) -> IoResult<Option<CellValue>> {
+        Ok(self.cell_entries(row, column).last().map(|(_, cell)| cell.clone()))
+    }
+
+    /// Return the newest version (timestamp + CellValue) for (row, column).
+    pub fn get_latest(&self, row: &[u8], column: &[u8]) -> Option<(Timestamp, CellValue)> {
+        self.cell_entries(row, column).last().map(|(key, cell)| (key.timestamp, cell.clone()))
+    }
 
-        Ok(versions)
+    /// *MVCC helper*: return all versions (timestamp + CellValue) for (row, column), sorted descending by timestamp.
+    pub fn get_versions_full(&mut self, row: &[u8], column: &[u8]) -> IoResult<Vec<(Timestamp, CellValue)>> {
+        Ok(self.cell_entries(row, column)
+            .iter()
+            .rev()
+            .map(|(key, cell)| (key.timestamp, cell.clone()))
+            .collect())
     }
 
     /// Scan all entries for a given row, returning (column, timestamp, CellValue) tuples.
@@ -109,44 +839,154 @@ impl SSTableReader {
         &mut self,
         row: &[u8],
     ) -> IoResult<impl Iterator<Item = (Column, Timestamp, CellValue)>> {
-        let mut matches = Vec::new();
-        for (key, cell) in self.entries.iter() {
-            if key.row.as_slice() == row {
-                matches.push((key.column.clone(), key.timestamp, cell.clone()));
-            }
-        }
+        let matches: Vec<_> = self.range_entries(row, row)
+            .iter()
+            .map(|(key, cell)| (key.column.clone(), key.timestamp, cell.clone()))
+            .collect();
         Ok(matches.into_iter())
     }
 
     /// *Return ALL (EntryKey, CellValue) pairs* from this SSTable.
     /// Used by the compaction routine.
     pub fn scan_all(&self) -> IoResult<Vec<(EntryKey, CellValue)>> {
-        Ok(self.entries.clone())
+        Ok(self.slice(0, self.len()).into_owned())
     }
 
     /// Scan a range of rows and return all entries within that range.
     /// The range is inclusive of start_row and end_row.
     pub fn scan_range(&mut self, start_row: &[u8], end_row: &[u8]) -> IoResult<Vec<(EntryKey, CellValue)>> {
-        let mut result = Vec::new();
+        Ok(self.range_entries(start_row, end_row).into_owned())
+    }
+
+    /// The sorted entries whose row lies in [start_row, end_row], borrowed without
+    /// copying them if the SSTable is loaded in memory.
+    pub fn range_entries(&self, start_row: &[u8], end_row: &[u8]) -> Cow<'_, [(EntryKey, CellValue)]> {
+        let (lo, hi) = self.range_bounds(start_row, end_row);
+        self.slice(lo, hi)
+    }
+
+    /// Number of entries whose row lies in [start_row, end_row], counted without
+    /// decoding more than the keys a binary search visits.
+    pub fn range_len(&self, start_row: &[u8], end_row: &[u8]) -> usize {
+        let (lo, hi) = self.range_bounds(start_row, end_row);
+        hi - lo
+    }
+
+    fn range_bounds(&self, start_row: &[u8], end_row: &[u8]) -> (usize, usize) {
+        let len = self.len();
+        let lo = self.partition_point(0, len, |k| k.row.as_slice() < start_row);
+        let hi = self.partition_point(lo, len, |k| k.row.as_slice() <= end_row);
+        (lo, hi)
+    }
 
-        for (key, cell) in &self.entries {
-            if key.row.as_slice() >= start_row && key.row.as_slice() <= end_row {
-                result.push((key.clone(), cell.clone()));
+    /// Get all unique row keys in a range.
+    pub fn get_row_keys_in_range(&self, start_row: &[u8], end_row: &[u8]) -> IoResult<Vec<Vec<u8>>> {
+        let mut row_keys: Vec<Vec<u8>> = Vec::new();
+        for (key, _) in self.range_entries(start_row, end_row).iter() {
+            if row_keys.last() != Some(&key.row) {
+                row_keys.push(key.row.clone());
             }
         }
+        Ok(row_keys)
+    }
+}
+
+/// Error for entry i of the SSTable at path, which cannot be decoded.
+fn corrupt(path: &Path, i: usize, reason: impl std::fmt::Display) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Corrupt SSTable {} at entry {}: {}", path.display(), i, reason),
+    )
+}
 
-        Ok(result)
+/// Read the big-endian u32 length at pos of a mapped file.
+#[cfg(all(feature = "mmap", unix))]
+fn read_len(data: &[u8], pos: usize) -> IoResult<usize> {
+    data.get(pos..pos + 4)
+        .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()) as usize)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Truncated SSTable"))
+}
+
+/// The entries of a mapped SSTable, with the offset of each entry in the file.
+#[cfg(all(feature = "mmap", unix))]
+struct MappedEntries {
+    map: mmap::Mmap,
+    offsets: Vec<usize>,
+}
+
+#[cfg(all(feature = "mmap", unix))]
+impl MappedEntries {
+    /// The serialized key and value of entry i; offsets and entries were checked on open.
+    fn raw(&self, i: usize) -> (&[u8], &[u8]) {
+        let data = self.map.as_slice();
+        let pos = self.offsets[i];
+        let key_len = read_len(data, pos).unwrap();
+        let key = &data[pos + 4..pos + 4 + key_len];
+        let val_pos = pos + 4 + key_len;
+        let val_len = read_len(data, val_pos).unwrap();
+        (key, &data[val_pos + 4..val_pos + 4 + val_len])
     }
 
-    /// Get all unique row keys in a range.
-    pub fn get_row_keys_in_range(&mut self, start_row: &[u8], end_row: &[u8]) -> IoResult<Vec<Vec<u8>>> {
-        let mut row_keys = std::collections::BTreeSet::new();
+    fn key(&self, i: usize) -> EntryKey {
+        deserialize_key(self.raw(i).0).expect("SSTable entries are decoded on open")
+    }
+
+    fn entry(&self, i: usize) -> (EntryKey, CellValue) {
+        let (key, value) = self.raw(i);
+        (
+            deserialize_key(key).expect("SSTable entries are decoded on open"),
+            bincode::deserialize(value).expect("SSTable entries are decoded on open"),
+        )
+    }
+}
 
-        for (key, _) in self.scan_range(start_row, end_row)? {
-            row_keys.insert(key.row);
+#[cfg(all(feature = "mmap", unix))]
+mod mmap {
+    use std::{
+        fs::File,
+        io::{Error, Result as IoResult},
+        os::unix::io::AsRawFd,
+        ptr,
+        slice,
+    };
+
+    /// A read-only memory map of a whole file.
+    pub struct Mmap {
+        ptr: *mut libc::c_void,
+        len: usize,
+    }
+
+    // The mapping is read-only for its whole life, so it can be shared between threads
+    unsafe impl Send for Mmap {}
+    unsafe impl Sync for Mmap {}
+
+    impl Mmap {
+        /// Map file, which must not be empty.
+        pub fn map(file: &File) -> IoResult<Self> {
+            let len = file.metadata()?.len() as usize;
+            // SAFETY: a new private read-only mapping of an open file descriptor
+            let ptr = unsafe {
+                libc::mmap(ptr::null_mut(), len, libc::PROT_READ, libc::MAP_PRIVATE, file.as_raw_fd(), 0)
+            };
+            if ptr == libc::MAP_FAILED {
+                return Err(Error::last_os_error());
+            }
+            Ok(Self { ptr, len })
         }
 
-        Ok(row_keys.into_iter().collect())
+        pub fn as_slice(&self) -> &[u8] {
+            // SAFETY: the mapping covers len readable bytes until it is dropped
+            unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+        }
+    }
+
+    impl Drop for Mmap {
+        fn drop(&mut self) {
+            // SAFETY: ptr and len are those of a live mapping created by map
+            unsafe {
+                libc::munmap(self.ptr, self.len);
+            }
+        }
     }
 }
 
@@ -154,8 +994,8 @@ impl SSTableReader {
 mod tests {
     use super::*;
     use crate::api::{CellValue, Entry, EntryKey};
-    use std::fs;
-    use std::path::PathBuf;
+    
+    
     use tempfile::tempdir;
 
     // Helper function to create test entries
@@ -168,6 +1008,7 @@ mod tests {
                 row: b"row1".to_vec(),
                 column: format!("col{}", i).into_bytes(),
                 timestamp: 100 + i as u64,
+                seq: 0,
             },
             value: CellValue::Put(format!("value{}", i).into_bytes()),
         }));
@@ -178,6 +1019,7 @@ mod tests {
                 row: b"row2".to_vec(),
                 column: b"col1".to_vec(),
                 timestamp: 200,
+                seq: 0,
             },
             value: CellValue::Put(b"row2value".to_vec()),
         });
@@ -188,6 +1030,7 @@ mod tests {
                 row: b"row1".to_vec(),
                 column: b"col4".to_vec(),
                 timestamp: 300,
+                seq: 0,
             },
             value: CellValue::Delete(Some(3600 * 1000)), // 1 hour TTL
         });
@@ -278,6 +1121,7 @@ mod tests {
                     row: b"row1".to_vec(),
                     column: b"col1".to_vec(),
                     timestamp: i * 100, // Different timestamps
+                    seq: 0,
                 },
                 value: CellValue::Put(format!("value{}", i).into_bytes()),
             });
@@ -388,4 +1232,349 @@ mod tests {
         drop(reader);
         drop(dir);
     }
+
+    #[test]
+    fn test_sstable_reader_binary_search() {
+        let dir = tempdir().unwrap();
+        let sst_path = dir.path().join("test.sst");
+
+        // Columns that share prefixes ("col1" < "col10" < "col2") and several versions each
+        let mut entries: Vec<Entry> = ["row1", "row2", "row3"].iter()
+            .flat_map(|row| ["col1", "col10", "col2"].iter().map(move |col| (row, col)))
+            .flat_map(|(row, col)| (1..=3).map(move |ts| Entry {
+                key: EntryKey {
+                    row: row.as_bytes().to_vec(),
+                    column: col.as_bytes().to_vec(),
+                    timestamp: ts,
+                    seq: 0,
+                },
+                value: CellValue::Put(format!("{}:{}:{}", row, col, ts).into_bytes()),
+            }))
+            .collect();
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        SSTable::create(&sst_path, &entries).unwrap();
+
+        let mut reader = SSTableReader::open(&sst_path).unwrap();
+
+        assert_eq!(
+            reader.get_full(b"row2", b"col10").unwrap(),
+            Some(CellValue::Put(b"row2:col10:3".to_vec()))
+        );
+        assert!(reader.get_full(b"row2", b"col3").unwrap().is_none());
+        assert!(reader.get_full(b"row0", b"col1").unwrap().is_none());
+
+        let versions = reader.get_versions_full(b"row3", b"col1").unwrap();
+        let timestamps: Vec<_> = versions.iter().map(|(ts, _)| *ts).collect();
+        assert_eq!(timestamps, vec![3, 2, 1]);
+
+        assert_eq!(reader.scan_row_full(b"row1").unwrap().count(), 9);
+        assert_eq!(reader.scan_range(b"row2", b"row3").unwrap().len(), 18);
+        assert_eq!(
+            reader.get_row_keys_in_range(b"row0", b"row2").unwrap(),
+            vec![b"row1".to_vec(), b"row2".to_vec()]
+        );
+
+        drop(reader);
+        drop(dir);
+    }
+
+    #[test]
+    fn test_sstable_writer() {
+        let dir = tempdir().unwrap();
+        let sst_path = dir.path().join("test.sst");
+
+        let entries = create_test_entries();
+        let mut writer = SSTableWriter::create(&sst_path).unwrap();
+        for entry in &entries {
+            writer.append(entry).unwrap();
+        }
+        assert_eq!(writer.len(), entries.len());
+
+        // Out-of-order entries are rejected
+        assert_eq!(writer.append(&entries[0]).unwrap_err().kind(), ErrorKind::InvalidInput);
+        writer.finish().unwrap();
+
+        let mut reader = SSTableReader::open(&sst_path).unwrap();
+        assert_eq!(reader.len(), entries.len());
+        assert!(reader.is_sorted());
+        assert_eq!(
+            reader.get_full(b"row2", b"col1").unwrap(),
+            Some(CellValue::Put(b"row2value".to_vec()))
+        );
+
+        drop(reader);
+        drop(dir);
+    }
+
+    #[test]
+    fn test_sstable_in_memory_backend() {
+        let backend = Arc::new(crate::backend::MemoryBackend::new());
+        let entries = create_test_entries();
+        SSTable::create_in(backend.as_ref(), "/cf/1.sst", &entries, Durability::Sync).unwrap();
+
+        let mut writer = SSTableWriter::create_in(backend.clone(), "/cf/2.sst", Durability::Buffered).unwrap();
+        for entry in &entries[..2] {
+            writer.append(entry).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let reader = SSTableReader::open_in(backend.as_ref(), "/cf/1.sst").unwrap();
+        assert_eq!(reader.len(), entries.len());
+        assert_eq!(reader.footer().tombstones, 1);
+        assert_eq!(reader.get_latest(b"row2", b"col1").unwrap().1, CellValue::Put(b"row2value".to_vec()));
+        assert_eq!(SSTableReader::open_in(backend.as_ref(), "/cf/2.sst").unwrap().len(), 2);
+        assert_eq!(
+            SSTableReader::open_in(backend.as_ref(), "/cf/3.sst").err().unwrap().kind(),
+            ErrorKind::NotFound
+        );
+    }
+
+    /// The entries of an SSTable as written before checksums existed, without a footer.
+    fn unchecksummed(entries: &[Entry]) -> Vec<u8> {
+        let mut data = (entries.len() as u32).to_be_bytes().to_vec();
+        for entry in entries {
+            for field in [serialize_key(&entry.key).unwrap(), bincode::serialize(&entry.value).unwrap()] {
+                data.extend_from_slice(&(field.len() as u32).to_be_bytes());
+                data.extend_from_slice(&field);
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_sstable_footer() {
+        let dir = tempdir().unwrap();
+        let sst_path = dir.path().join("test.sst");
+        let entries = create_test_entries();
+        SSTable::create(&sst_path, &entries).unwrap();
+
+        let footer = SSTableReader::open(&sst_path).unwrap().footer().clone();
+        let unfiltered = SSTableFooter {
+            entries: 5,
+            tombstones: 1,
+            rows: 2,
+            row_range: Some((b"row1".to_vec(), b"row2".to_vec())),
+            time_range: Some((101, 300)),
+            bloom: None,
+            prefix_bloom: None,
+        };
+        assert_eq!(SSTableFooter { bloom: None, ..footer.clone() }, unfiltered);
+        assert!(footer.overlaps_time_range(250, 400));
+        assert!(!footer.overlaps_time_range(301, 400));
+        assert!(footer.may_contain_row(b"row2"));
+        assert!(!footer.may_contain_row(b"row3"));
+        assert!(footer.overlaps_rows(b"row0", b"row1"));
+        assert!(!footer.overlaps_rows(b"row20", b"row9"));
+        // Rows within the range but not in the file are filtered out
+        assert!(!footer.may_contain_row(b"row15"));
+        assert!(footer.may_contain_cell(b"row1", b"col9"));
+        assert!(unfiltered.may_contain_row(b"row15"));
+
+        // A filter of cells, or none
+        SSTable::create_with_bloom_filter(&sst_path, &entries, Durability::Sync, BloomFilterOptions {
+            key_mode: crate::bloom::BloomKeyMode::RowColumn,
+            ..BloomFilterOptions::default()
+        }).unwrap();
+        let by_cell = SSTableReader::open(&sst_path).unwrap().footer().clone();
+        assert!(by_cell.may_contain_cell(b"row1", b"col2"));
+        assert!(!by_cell.may_contain_cell(b"row1", b"col9"));
+        SSTable::create_with_bloom_filter(&sst_path, &entries, Durability::Sync, BloomFilterOptions::disabled()).unwrap();
+        assert_eq!(*SSTableReader::open(&sst_path).unwrap().footer(), unfiltered);
+
+        // A filter of row prefixes, checked by row reads too
+        assert!(footer.may_contain_prefix(b"row"));
+        assert!(footer.may_contain_prefix(b"r"));
+        assert!(!footer.may_contain_prefix(b"row3"));
+        assert!(!footer.may_contain_prefix(b"rox"));
+        SSTable::create_with_bloom_filter(&sst_path, &entries, Durability::Sync, BloomFilterOptions {
+            key_mode: crate::bloom::BloomKeyMode::Disabled,
+            prefix_len: Some(3),
+            ..BloomFilterOptions::default()
+        }).unwrap();
+        let by_prefix = SSTableReader::open(&sst_path).unwrap().footer().clone();
+        assert!(by_prefix.bloom.is_none());
+        assert_eq!(by_prefix.prefix_bloom.as_ref().unwrap().prefix_len(), 3);
+        assert!(by_prefix.may_contain_prefix(b"row"));
+        assert!(by_prefix.may_contain_prefix(b"row1"));
+        assert!(by_prefix.may_contain_row(b"row15"));
+        assert!(!by_prefix.may_contain_prefix(b"rov"));
+
+        let writer_path = dir.path().join("writer.sst");
+        let mut writer = SSTableWriter::create(&writer_path).unwrap();
+        let mut sorted = entries.clone();
+        sorted.sort_by(|a, b| a.key.cmp(&b.key));
+        sorted.iter().for_each(|entry| writer.append(entry).unwrap());
+        writer.finish().unwrap();
+        assert_eq!(*SSTableReader::open(&writer_path).unwrap().footer(), footer);
+
+        // SSTables written before footers existed get one computed from their entries
+        std::fs::write(&sst_path, unchecksummed(&sorted)).unwrap();
+        let reader = SSTableReader::load(&sst_path).unwrap();
+        assert_eq!((reader.len(), reader.footer()), (5, &unfiltered));
+        #[cfg(all(feature = "mmap", unix))]
+        assert_eq!(*SSTableReader::open_mapped(&sst_path).unwrap().footer(), unfiltered);
+    }
+
+
+    #[cfg(all(feature = "mmap", unix))]
+    #[test]
+    fn test_sstable_mapped_reader() {
+        let dir = tempdir().unwrap();
+        let sst_path = dir.path().join("test.sst");
+        let entries = create_test_entries();
+        SSTable::create(&sst_path, &entries).unwrap();
+
+        let loaded = SSTableReader::load(&sst_path).unwrap();
+        let mapped = SSTableReader::open_mapped(&sst_path).unwrap();
+        assert!(mapped.is_mapped() && !loaded.is_mapped());
+        assert_eq!(mapped.len(), entries.len());
+        assert!(mapped.is_sorted());
+
+        // Lookups match the loaded reader's
+        assert_eq!(mapped.scan_all().unwrap(), loaded.scan_all().unwrap());
+        assert_eq!(mapped.cell_entries(b"row1", b"col2"), loaded.cell_entries(b"row1", b"col2"));
+        assert_eq!(mapped.range_entries(b"row1", b"row1").len(), 4);
+        assert_eq!(mapped.range_entries(b"row2", b"row9"), loaded.range_entries(b"row2", b"row9"));
+        assert!(mapped.cell_entries(b"row3", b"col1").is_empty());
+        assert_eq!(mapped.get_row_keys_in_range(b"row0", b"row9").unwrap(), vec![b"row1".to_vec(), b"row2".to_vec()]);
+
+        // A truncated file is rejected
+        let data = std::fs::read(&sst_path).unwrap();
+        std::fs::write(&sst_path, &data[..data.len() - 3]).unwrap();
+        assert_eq!(SSTableReader::open_mapped(&sst_path).err().unwrap().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_sstable_corrupt_entries() {
+        let dir = tempdir().unwrap();
+        let sst_path = dir.path().join("test.sst");
+        SSTable::create(&sst_path, &create_test_entries()).unwrap();
+        let data = std::fs::read(&sst_path).unwrap();
+        let header_len = FormatVersion::CURRENT.header_len();
+        let key_len = u32::from_be_bytes(data[header_len..header_len + 4].try_into().unwrap()) as usize;
+        let val_pos = header_len + 4 + key_len + 4;
+
+        // An unknown CellValue variant
+        let mut corrupted = data.clone();
+        corrupted[val_pos..val_pos + 4].copy_from_slice(&[0xff; 4]);
+        std::fs::write(&sst_path, &corrupted).unwrap();
+        let err = SSTableReader::load(&sst_path).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert!(err.to_string().contains("at entry 0"));
+        #[cfg(all(feature = "mmap", unix))]
+        assert_eq!(SSTableReader::open_mapped(&sst_path).err().unwrap().kind(), ErrorKind::InvalidData);
+
+        // A key length far past the end of the file
+        let mut corrupted = data.clone();
+        corrupted[header_len..header_len + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        std::fs::write(&sst_path, &corrupted).unwrap();
+        assert_eq!(SSTableReader::load(&sst_path).err().unwrap().kind(), ErrorKind::InvalidData);
+        #[cfg(all(feature = "mmap", unix))]
+        assert_eq!(SSTableReader::open_mapped(&sst_path).err().unwrap().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_sstable_checksums() {
+        let dir = tempdir().unwrap();
+        let sst_path = dir.path().join("test.sst");
+        SSTable::create(&sst_path, &create_test_entries()).unwrap();
+        let data = std::fs::read(&sst_path).unwrap();
+
+        // A bit flipped in the last byte of the second entry's value, which still decodes
+        let len_at = |pos: usize| u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+        let entry_end = |pos: usize| {
+            let val_pos = pos + 4 + len_at(pos);
+            val_pos + 4 + len_at(val_pos)
+        };
+        let header_len = FormatVersion::CURRENT.header_len();
+        let second = entry_end(header_len) + 4;
+        let mut corrupted = data.clone();
+        corrupted[entry_end(second) - 1] ^= 0x01;
+        std::fs::write(&sst_path, &corrupted).unwrap();
+        let err = SSTableReader::load(&sst_path).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let mismatch = ChecksumMismatch::from_io(&err).unwrap();
+        assert_eq!((mismatch.path.as_path(), mismatch.entry), (sst_path.as_path(), Some(1)));
+        assert_ne!(mismatch.stored, mismatch.computed);
+        assert!(err.to_string().contains("at entry 1"));
+        #[cfg(all(feature = "mmap", unix))]
+        assert_eq!(ChecksumMismatch::from_io(&SSTableReader::open_mapped(&sst_path).err().unwrap()).unwrap().entry, Some(1));
+
+        // A bit flipped in the footer
+        let mut corrupted = data.clone();
+        let footer_len = u32::from_be_bytes(data[data.len() - 8..data.len() - 4].try_into().unwrap()) as usize;
+        corrupted[data.len() - 12 - footer_len] ^= 0x01;
+        std::fs::write(&sst_path, &corrupted).unwrap();
+        let err = SSTableReader::load(&sst_path).err().unwrap();
+        assert_eq!(ChecksumMismatch::from_io(&err).unwrap().entry, None);
+
+        // An entry count that disagrees with the footer
+        let mut corrupted = data.clone();
+        corrupted[header_len - 4..header_len].copy_from_slice(&4u32.to_be_bytes());
+        std::fs::write(&sst_path, &corrupted).unwrap();
+        assert_eq!(SSTableReader::load(&sst_path).err().unwrap().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_sstable_format_versions() {
+        let dir = tempdir().unwrap();
+        let sst_path = dir.path().join("test.sst");
+        let mut entries = create_test_entries();
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        SSTable::create(&sst_path, &entries).unwrap();
+        let current = SSTableReader::load(&sst_path).unwrap();
+        assert_eq!(current.format_version(), FormatVersion::CURRENT);
+        let data = std::fs::read(&sst_path).unwrap();
+        let open_all = |bytes: &[u8]| {
+            std::fs::write(&sst_path, bytes).unwrap();
+            let loaded = SSTableReader::load(&sst_path);
+            #[cfg(all(feature = "mmap", unix))]
+            {
+                let mapped = SSTableReader::open_mapped(&sst_path);
+                assert_eq!(
+                    mapped.as_ref().map(|r| (r.format_version(), r.scan_all().unwrap())).map_err(|e| e.kind()),
+                    loaded.as_ref().map(|r| (r.format_version(), r.scan_all().unwrap())).map_err(|e| e.kind()),
+                );
+            }
+            loaded
+        };
+
+        // Files without a header are read side by side, and end after their entries
+        let legacy = unchecksummed(&entries);
+        let reader = open_all(&legacy).unwrap();
+        assert_eq!(reader.format_version(), FormatVersion::Legacy);
+        assert_eq!(reader.scan_all().unwrap(), current.scan_all().unwrap());
+        assert_eq!(
+            SSTableFooter { bloom: None, ..reader.footer().clone() },
+            SSTableFooter { bloom: None, ..current.footer().clone() },
+        );
+        assert_eq!(open_all(&[&legacy[..], &[0]].concat()).err().unwrap().kind(), ErrorKind::InvalidData);
+
+        // A version this build does not know
+        let mut newer = data.clone();
+        newer[4..8].copy_from_slice(&3u32.to_be_bytes());
+        assert_eq!(open_all(&newer).err().unwrap().kind(), ErrorKind::Unsupported);
+
+        // A current file must end with a checksummed footer
+        assert_eq!(open_all(&data[..data.len() - 1]).err().unwrap().kind(), ErrorKind::InvalidData);
+        assert_eq!(open_all(&data[..6]).err().unwrap().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_sstable_sequence_numbers() {
+        let dir = tempdir().unwrap();
+        let sst_path = dir.path().join("test.sst");
+        let mut entries = create_test_entries();
+        entries[1].key.seq = 7;
+        SSTable::create(&sst_path, &entries).unwrap();
+        let reader = SSTableReader::load(&sst_path).unwrap();
+        assert_eq!(reader.all_entries()[1].0.seq, 7);
+        assert_eq!(reader.all_entries()[0].0.seq, 0);
+
+        // Keys written before sequence numbers end without one
+        let legacy = bincode::serialize(&entries[1].key).unwrap();
+        assert_eq!(deserialize_key(&legacy).unwrap().seq, 0);
+        assert_eq!(deserialize_key(&serialize_key(&entries[1].key).unwrap()).unwrap(), entries[1].key);
+        assert!(deserialize_key(&[legacy.as_slice(), &[1, 2, 3]].concat()).is_err());
+    }
 }
@@ -0,0 +1,362 @@
+use std::{
+    collections::BTreeSet,
+    io::{BufRead, Error, ErrorKind, Result as IoResult, Write},
+    path::Path,
+};
+
+use serde_json::{Map, Value};
+
+use crate::api::{ColumnFamily, Put, RangeVersions};
+use crate::filter::FilterSet;
+
+/// Text format for row import and export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextFormat {
+    /// Comma-separated values with a header line (RFC 4180 quoting)
+    Csv,
+    /// One JSON object per line
+    Ndjson,
+}
+
+impl TextFormat {
+    /// Guess the format from a file extension (`.csv`, `.ndjson`, `.jsonl`, `.json`).
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "csv" => Some(TextFormat::Csv),
+            "ndjson" | "jsonl" | "json" => Some(TextFormat::Ndjson),
+            _ => None,
+        }
+    }
+}
+
+/// Maps input fields to a row key and columns
+#[derive(Debug, Clone)]
+pub struct ImportMapping {
+    /// Fields whose values form the row key, joined with `row_key_separator`
+    pub row_key_fields: Vec<String>,
+    /// Separator between the parts of a composite row key
+    pub row_key_separator: String,
+    /// (field, column) pairs to import; empty imports every non-key field under its own name
+    pub columns: Vec<(String, String)>,
+    /// Skip fields with empty values instead of storing empty cells
+    pub skip_empty: bool,
+}
+
+impl ImportMapping {
+    /// Use field as the row key and import every other field as a column.
+    pub fn new(row_key_field: &str) -> Self {
+        Self {
+            row_key_fields: vec![row_key_field.to_string()],
+            row_key_separator: ":".to_string(),
+            columns: Vec::new(),
+            skip_empty: true,
+        }
+    }
+
+    /// Import field as column (may be called repeatedly to select and rename fields).
+    pub fn with_column(&mut self, field: &str, column: &str) -> &mut Self {
+        self.columns.push((field.to_string(), column.to_string()));
+        self
+    }
+
+    /// Build a record from (field, value) pairs, or fail if a row key field is missing.
+    fn map_fields(&self, fields: &[(String, String)], line: u64) -> IoResult<ImportRecord> {
+        let lookup = |name: &str| fields.iter().find(|(field, _)| field == name).map(|(_, v)| v.as_str());
+
+        let row = self.row_key_fields.iter()
+            .map(|field| {
+                lookup(field)
+                    .filter(|v| !v.is_empty())
+                    .ok_or_else(|| Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Record {} has no value for row key field {:?}", line, field),
+                    ))
+            })
+            .collect::<IoResult<Vec<_>>>()?
+            .join(&self.row_key_separator);
+
+        let selected: Vec<(&str, &str)> = if self.columns.is_empty() {
+            fields.iter()
+                .filter(|(field, _)| !self.row_key_fields.contains(field))
+                .map(|(field, _)| (field.as_str(), field.as_str()))
+                .collect()
+        } else {
+            self.columns.iter().map(|(field, column)| (field.as_str(), column.as_str())).collect()
+        };
+
+        let columns = selected.into_iter()
+            .filter_map(|(field, column)| lookup(field).map(|value| (column, value)))
+            .filter(|(_, value)| !(self.skip_empty && value.is_empty()))
+            .map(|(column, value)| (column.as_bytes().to_vec(), value.as_bytes().to_vec()))
+            .collect();
+
+        Ok(ImportRecord { row: row.into_bytes(), columns })
+    }
+}
+
+/// One input record mapped to a row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportRecord {
+    /// The row key
+    pub row: Vec<u8>,
+    /// (column, value) pairs
+    pub columns: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+/// Counts reported by an import
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportStats {
+    /// Rows written
+    pub rows: u64,
+    /// Cells written
+    pub cells: u64,
+}
+
+/// Read one CSV record, honouring quoted fields that contain commas, quotes or newlines.
+/// Returns None at end of input.
+fn read_csv_record(reader: &mut impl BufRead) -> IoResult<Option<Vec<String>>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    loop {
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, in_quotes) {
+                ('"', true) if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                ('"', true) => in_quotes = false,
+                ('"', false) if field.is_empty() => in_quotes = true,
+                (',', false) => fields.push(std::mem::take(&mut field)),
+                ('\r' | '\n', false) => {}
+                (c, _) => field.push(c),
+            }
+        }
+
+        if !in_quotes {
+            break;
+        }
+        // The quoted field continues on the next line
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "Unterminated quoted CSV field"));
+        }
+    }
+    fields.push(field);
+    Ok(Some(fields))
+}
+
+fn write_csv_record(writer: &mut impl Write, fields: &[&str]) -> IoResult<()> {
+    let line = fields.iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    writeln!(writer, "{}", line)
+}
+
+/// Render a JSON value as cell text: strings as-is, other scalars and nested values as JSON.
+fn json_to_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// Parse every record of reader and map it to a row.
+pub fn read_records(
+    reader: &mut impl BufRead,
+    format: TextFormat,
+    mapping: &ImportMapping,
+) -> IoResult<Vec<ImportRecord>> {
+    let mut records = Vec::new();
+    match format {
+        TextFormat::Csv => {
+            let header = read_csv_record(reader)?
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "CSV input has no header line"))?;
+            let mut line = 1;
+            while let Some(values) = read_csv_record(reader)? {
+                line += 1;
+                if values.len() == 1 && values[0].is_empty() {
+                    continue;
+                }
+                let fields: Vec<(String, String)> = header.iter().cloned().zip(values).collect();
+                records.push(mapping.map_fields(&fields, line)?);
+            }
+        }
+        TextFormat::Ndjson => {
+            for (index, line) in reader.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let object: Map<String, Value> = serde_json::from_str(&line).map_err(|e| {
+                    Error::new(ErrorKind::InvalidData, format!("Line {}: {}", index + 1, e))
+                })?;
+                let fields: Vec<(String, String)> = object.iter()
+                    .filter_map(|(field, value)| json_to_text(value).map(|text| (field.clone(), text)))
+                    .collect();
+                records.push(mapping.map_fields(&fields, index as u64 + 1)?);
+            }
+        }
+    }
+    Ok(records)
+}
+
+/// Import rows from CSV or NDJSON into cf. Each record becomes one multi-column Put.
+pub fn import_rows(
+    cf: &ColumnFamily,
+    reader: &mut impl BufRead,
+    format: TextFormat,
+    mapping: &ImportMapping,
+) -> IoResult<ImportStats> {
+    let mut stats = ImportStats::default();
+    for record in read_records(reader, format, mapping)? {
+        if record.columns.is_empty() {
+            continue;
+        }
+        let mut put = Put::new(record.row);
+        for (column, value) in record.columns {
+            put.add_column(column, value);
+            stats.cells += 1;
+        }
+        cf.execute_put(put)?;
+        stats.rows += 1;
+    }
+    Ok(stats)
+}
+
+/// Write the latest version of every cell in rows as CSV or NDJSON.
+///
+/// The row key is written to the field named row_key_field. CSV output has one column
+/// per distinct column name (sorted), with empty values for missing cells.
+/// Returns the number of rows written.
+pub fn write_rows(
+    writer: &mut impl Write,
+    format: TextFormat,
+    rows: &RangeVersions,
+    row_key_field: &str,
+) -> IoResult<u64> {
+    let latest = |versions: &Vec<(u64, Vec<u8>)>| -> Option<String> {
+        versions.first().map(|(_, value)| String::from_utf8_lossy(value).to_string())
+    };
+
+    match format {
+        TextFormat::Csv => {
+            let columns: BTreeSet<&Vec<u8>> = rows.values().flat_map(|cols| cols.keys()).collect();
+            let names: Vec<String> = columns.iter().map(|c| String::from_utf8_lossy(c).to_string()).collect();
+
+            let mut header = vec![row_key_field];
+            header.extend(names.iter().map(String::as_str));
+            write_csv_record(writer, &header)?;
+
+            for (row, cols) in rows {
+                let row = String::from_utf8_lossy(row).to_string();
+                let values: Vec<String> = columns.iter()
+                    .map(|column| cols.get(*column).and_then(latest).unwrap_or_default())
+                    .collect();
+                let mut record = vec![row.as_str()];
+                record.extend(values.iter().map(String::as_str));
+                write_csv_record(writer, &record)?;
+            }
+        }
+        TextFormat::Ndjson => {
+            for (row, cols) in rows {
+                let mut object = Map::new();
+                object.insert(row_key_field.to_string(), Value::String(String::from_utf8_lossy(row).to_string()));
+                for (column, versions) in cols {
+                    if let Some(value) = latest(versions) {
+                        object.insert(String::from_utf8_lossy(column).to_string(), Value::String(value));
+                    }
+                }
+                writeln!(writer, "{}", Value::Object(object))?;
+            }
+        }
+    }
+    Ok(rows.len() as u64)
+}
+
+/// Export the latest values of rows in [start_row, end_row] from cf as CSV or NDJSON.
+/// Returns the number of rows written.
+pub fn export_rows(
+    cf: &ColumnFamily,
+    writer: &mut impl Write,
+    format: TextFormat,
+    start_row: &[u8],
+    end_row: &[u8],
+    row_key_field: &str,
+) -> IoResult<u64> {
+    let mut filter_set = FilterSet::new();
+    filter_set.with_max_versions(1);
+    let rows = cf.scan_with_filter(start_row, end_row, &filter_set)?;
+    write_rows(writer, format, &rows, row_key_field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Table;
+    use std::io::Cursor;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_csv_parsing() {
+        let input = "id,name,bio\n1,alice,\"likes \"\"rust\"\", tea\"\n2,bob,\"line one\nline two\"\n";
+        let mut mapping = ImportMapping::new("id");
+        mapping.with_column("bio", "profile:bio");
+
+        let records = read_records(&mut Cursor::new(input), TextFormat::Csv, &mapping).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].row, b"1");
+        assert_eq!(records[0].columns, vec![(b"profile:bio".to_vec(), b"likes \"rust\", tea".to_vec())]);
+        assert_eq!(records[1].columns[0].1, b"line one\nline two");
+    }
+
+    #[test]
+    fn test_ndjson_composite_key() {
+        let input = "{\"user\": \"u1\", \"day\": \"2024-01-01\", \"clicks\": 3, \"note\": null}\n\n{\"user\": \"u2\"}\n";
+        let mut mapping = ImportMapping::new("user");
+        mapping.row_key_fields.push("day".to_string());
+
+        let err = read_records(&mut Cursor::new(input), TextFormat::Ndjson, &mapping).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        let records = read_records(&mut Cursor::new(&input[..input.find('\n').unwrap()]), TextFormat::Ndjson, &mapping).unwrap();
+        assert_eq!(records[0].row, b"u1:2024-01-01");
+        assert_eq!(records[0].columns, vec![(b"clicks".to_vec(), b"3".to_vec())]);
+    }
+
+    #[test]
+    fn test_import_export_roundtrip() {
+        let dir = tempdir().unwrap();
+        let table = Table::open(dir.path()).unwrap();
+        table.create_cf("users").unwrap();
+        let cf = table.cf("users").unwrap();
+
+        let input = "id,name,city\nu1,alice,\"Paris, FR\"\nu2,bob,\n";
+        let stats = import_rows(&cf, &mut Cursor::new(input), TextFormat::Csv, &ImportMapping::new("id")).unwrap();
+        assert_eq!(stats, ImportStats { rows: 2, cells: 3 });
+        assert_eq!(cf.get(b"u1", b"city").unwrap(), Some(b"Paris, FR".to_vec()));
+
+        let mut csv = Vec::new();
+        assert_eq!(export_rows(&cf, &mut csv, TextFormat::Csv, b"u1", b"u9", "id").unwrap(), 2);
+        assert_eq!(String::from_utf8(csv).unwrap(), "id,city,name\nu1,\"Paris, FR\",alice\nu2,,bob\n");
+
+        let mut ndjson = Vec::new();
+        export_rows(&cf, &mut ndjson, TextFormat::Ndjson, b"u2", b"u2", "id").unwrap();
+        assert_eq!(String::from_utf8(ndjson).unwrap(), "{\"id\":\"u2\",\"name\":\"bob\"}\n");
+    }
+}
@@ -0,0 +1,66 @@
+use std::io::{Error, ErrorKind, Result as IoResult};
+
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
+
+/// Output format for log lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Multi-line, human-friendly output
+    Pretty,
+    /// Single-line output (default)
+    Compact,
+    /// One JSON object per line, for log shippers
+    Json,
+}
+
+/// Configuration for the global tracing subscriber
+#[derive(Debug, Clone)]
+pub struct TelemetryConfig {
+    /// Filter directives, e.g. `"info"` or `"RedBase=debug,actix_web=info"`.
+    /// The `RUST_LOG` environment variable takes precedence when set.
+    pub filter: String,
+    /// Output format
+    pub format: LogFormat,
+    /// Emit an event when each span closes, including its duration
+    pub log_span_close: bool,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            filter: "info".to_string(),
+            format: LogFormat::Compact,
+            log_span_close: false,
+        }
+    }
+}
+
+/// Install the global tracing subscriber, writing to stderr.
+///
+/// Spans are emitted by the api, memstore, storage and REST layers; events from
+/// crates using the `log` facade (e.g. actix-web's request logger) are forwarded
+/// as well. Fails if a global subscriber has already been installed.
+pub fn init_tracing(config: &TelemetryConfig) -> IoResult<()> {
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(&config.filter))
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("Invalid log filter: {}", e)))?;
+
+    let span_events = if config.log_span_close {
+        FmtSpan::CLOSE
+    } else {
+        FmtSpan::NONE
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_env_filter(filter)
+        .with_span_events(span_events);
+
+    let result = match config.format {
+        LogFormat::Pretty => builder.pretty().try_init(),
+        LogFormat::Compact => builder.compact().try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    };
+
+    result.map_err(|e| Error::new(ErrorKind::AlreadyExists, format!("Failed to install subscriber: {}", e)))
+}
@@ -0,0 +1,125 @@
+//! Throttling of background compaction I/O.
+//!
+//! A major compaction reads and rewrites every SSTable of a column family as fast as
+//! the disk allows, which starves foreground reads. `CompactionThrottle` (set per column
+//! family) caps the bytes per second compactions and small-file merges read and write,
+//! and how many of them may run at once in the process. Waiting for a slot doubles as a
+//! priority: a column family allowing only one concurrent compaction waits until no
+//! other compaction is running.
+
+use std::{
+    sync::{Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use tracing::debug;
+
+/// Limits on the compactions and small-file merges of a column family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CompactionThrottle {
+    /// Read inputs and write outputs at most this many bytes per second
+    pub max_bytes_per_sec: Option<u64>,
+    /// Start only while fewer than this many compactions and merges run in the process
+    pub max_concurrent: Option<usize>,
+}
+
+/// Sleep only once I/O is this far ahead of the rate, so that small writes do not each
+/// pay for a sleep.
+const MIN_SLEEP: Duration = Duration::from_millis(5);
+
+/// Paces I/O to a number of bytes per second, measured from its creation.
+pub(crate) struct RateLimiter {
+    bytes_per_sec: Option<u64>,
+    started: Instant,
+    consumed: u64,
+}
+
+impl RateLimiter {
+    /// Pace I/O to bytes_per_sec, or not at all with `None`.
+    pub fn new(bytes_per_sec: Option<u64>) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.map(|rate| rate.max(1)),
+            started: Instant::now(),
+            consumed: 0,
+        }
+    }
+
+    /// Account for bytes of I/O, sleeping while it is ahead of the rate.
+    pub fn consume(&mut self, bytes: u64) {
+        let Some(rate) = self.bytes_per_sec else {
+            return;
+        };
+        self.consumed += bytes;
+        let due = Duration::from_secs_f64(self.consumed as f64 / rate as f64);
+        let elapsed = self.started.elapsed();
+        if due > elapsed + MIN_SLEEP {
+            thread::sleep(due - elapsed);
+        }
+    }
+}
+
+/// Number of compactions and merges running in the process.
+static RUNNING: Mutex<usize> = Mutex::new(0);
+static SLOT_FREED: Condvar = Condvar::new();
+
+/// A running compaction or merge, counted until dropped.
+pub(crate) struct CompactionSlot(());
+
+impl CompactionSlot {
+    /// Wait until fewer than max_concurrent compactions run (at once if `None`), then
+    /// count this one.
+    pub fn acquire(max_concurrent: Option<usize>) -> Self {
+        let mut running = RUNNING.lock().unwrap();
+        if let Some(max) = max_concurrent {
+            if *running >= max.max(1) {
+                debug!(running = *running, max, "waiting for a compaction slot");
+            }
+            running = SLOT_FREED.wait_while(running, |running| *running >= max.max(1)).unwrap();
+        }
+        *running += 1;
+        Self(())
+    }
+}
+
+impl Drop for CompactionSlot {
+    fn drop(&mut self) {
+        *RUNNING.lock().unwrap() -= 1;
+        SLOT_FREED.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter_paces_io() {
+        let mut limiter = RateLimiter::new(Some(100_000));
+        let started = Instant::now();
+        for _ in 0..10 {
+            limiter.consume(2_000);
+        }
+        // 20 KB at 100 KB/s takes about 200 ms
+        assert!(started.elapsed() >= Duration::from_millis(190));
+
+        let mut unlimited = RateLimiter::new(None);
+        let started = Instant::now();
+        unlimited.consume(u64::MAX);
+        assert!(started.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_compaction_slots() {
+        let running = CompactionSlot::acquire(None);
+        let started = Instant::now();
+        let waiter = thread::spawn(move || {
+            // Only one compaction at a time: waits for the one running
+            let _slot = CompactionSlot::acquire(Some(1));
+            started.elapsed()
+        });
+        thread::sleep(Duration::from_millis(100));
+        drop(running);
+        assert!(waiter.join().unwrap() >= Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,419 @@
+//! Order-preserving encodings of typed cell values and row keys (`TypedValue`,
+//! `RowKeyBuilder`), so that encoded values compare bytewise as they do as values.
+
+use std::io::{Error, ErrorKind, Result as IoResult};
+
+use chrono::{DateTime, Utc};
+
+use crate::api::RowKey;
+
+/// A type with an order-preserving byte encoding. RedBase compares bytes (row ranges,
+/// the comparison filters, MIN and MAX), by which numbers written as text or in their
+/// native byte order sort wrongly: `"9" > "10"`, and a negative i64 sorts after every
+/// positive one. Encoded values sort as the values do, so they can be used as row keys
+/// and with the filters directly:
+///
+/// - `i64` and `u64`: 8 bytes big-endian, with the sign bit of an i64 flipped
+/// - `f64`: 8 bytes big-endian, with the sign bit flipped for positive numbers and all
+///   bits flipped for negative ones, so `-inf < -1.0 < -0.0 < 0.0 < 1.0 < inf`
+/// - `bool`: one byte, 0 or 1
+/// - `String`: its UTF-8 bytes
+/// - `DateTime<Utc>`: the milliseconds since the epoch, encoded as an i64
+///
+/// The encodings are fixed-width binary, not text, so the numeric aggregations (which
+/// parse values as text) do not apply to them; decode the values instead.
+pub trait TypedValue: Sized {
+    /// Name of the type, used in decoding errors.
+    const TYPE_NAME: &'static str;
+
+    /// Encode the value.
+    fn encode(&self) -> Vec<u8>;
+
+    /// Decode a value written by `encode`, failing with `InvalidData` if bytes are not
+    /// a value of this type.
+    fn decode(bytes: &[u8]) -> IoResult<Self>;
+}
+
+/// Encode value.
+pub fn encode<T: TypedValue>(value: &T) -> Vec<u8> {
+    value.encode()
+}
+
+/// Decode bytes as a T.
+pub fn decode<T: TypedValue>(bytes: &[u8]) -> IoResult<T> {
+    T::decode(bytes)
+}
+
+fn invalid<T: TypedValue>(bytes: &[u8], reason: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("{} bytes are not a {}: {}", bytes.len(), T::TYPE_NAME, reason),
+    )
+}
+
+fn fixed<T: TypedValue, const N: usize>(bytes: &[u8]) -> IoResult<[u8; N]> {
+    bytes.try_into().map_err(|_| invalid::<T>(bytes, &format!("expected {} bytes", N)))
+}
+
+const SIGN_BIT: u64 = 1 << 63;
+
+impl TypedValue for i64 {
+    const TYPE_NAME: &'static str = "i64";
+
+    fn encode(&self) -> Vec<u8> {
+        ((*self as u64) ^ SIGN_BIT).to_be_bytes().to_vec()
+    }
+
+    fn decode(bytes: &[u8]) -> IoResult<Self> {
+        Ok((u64::from_be_bytes(fixed::<Self, 8>(bytes)?) ^ SIGN_BIT) as i64)
+    }
+}
+
+impl TypedValue for u64 {
+    const TYPE_NAME: &'static str = "u64";
+
+    fn encode(&self) -> Vec<u8> {
+        self.to_be_bytes().to_vec()
+    }
+
+    fn decode(bytes: &[u8]) -> IoResult<Self> {
+        Ok(u64::from_be_bytes(fixed::<Self, 8>(bytes)?))
+    }
+}
+
+impl TypedValue for f64 {
+    const TYPE_NAME: &'static str = "f64";
+
+    fn encode(&self) -> Vec<u8> {
+        let bits = self.to_bits();
+        let ordered = if bits & SIGN_BIT == 0 { bits ^ SIGN_BIT } else { !bits };
+        ordered.to_be_bytes().to_vec()
+    }
+
+    fn decode(bytes: &[u8]) -> IoResult<Self> {
+        let ordered = u64::from_be_bytes(fixed::<Self, 8>(bytes)?);
+        let bits = if ordered & SIGN_BIT != 0 { ordered ^ SIGN_BIT } else { !ordered };
+        Ok(f64::from_bits(bits))
+    }
+}
+
+impl TypedValue for bool {
+    const TYPE_NAME: &'static str = "bool";
+
+    fn encode(&self) -> Vec<u8> {
+        vec![*self as u8]
+    }
+
+    fn decode(bytes: &[u8]) -> IoResult<Self> {
+        match bytes {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(invalid::<Self>(bytes, "expected a single 0 or 1 byte")),
+        }
+    }
+}
+
+impl TypedValue for String {
+    const TYPE_NAME: &'static str = "String";
+
+    fn encode(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn decode(bytes: &[u8]) -> IoResult<Self> {
+        String::from_utf8(bytes.to_vec()).map_err(|e| invalid::<Self>(bytes, &e.to_string()))
+    }
+}
+
+impl TypedValue for DateTime<Utc> {
+    const TYPE_NAME: &'static str = "timestamp";
+
+    /// Sub-millisecond precision is dropped.
+    fn encode(&self) -> Vec<u8> {
+        self.timestamp_millis().encode()
+    }
+
+    fn decode(bytes: &[u8]) -> IoResult<Self> {
+        let millis = i64::decode(bytes).map_err(|_| invalid::<Self>(bytes, "expected 8 bytes"))?;
+        DateTime::from_timestamp_millis(millis).ok_or_else(|| invalid::<Self>(bytes, "out of range"))
+    }
+}
+
+impl TypedValue for Vec<u8> {
+    const TYPE_NAME: &'static str = "byte string";
+
+    fn encode(&self) -> Vec<u8> {
+        self.clone()
+    }
+
+    fn decode(bytes: &[u8]) -> IoResult<Self> {
+        Ok(bytes.to_vec())
+    }
+}
+
+/// Builds a row key from typed components, in order. Keys built with the same kinds of
+/// components sort component by component, e.g. by user and then by time:
+///
+/// ```
+/// use RedBase::types::{RowKeyBuilder, RowKeyDecoder};
+///
+/// let key = RowKeyBuilder::new().add_str("alice").add_u64(7).build();
+/// let mut decoder = RowKeyDecoder::new(&key);
+/// assert_eq!(decoder.str().unwrap(), "alice");
+/// assert_eq!(decoder.u64().unwrap(), 7);
+/// decoder.finish().unwrap();
+/// ```
+///
+/// Strings and byte strings are escaped and terminated, so `"a"` sorts before `"ab"`
+/// whatever follows them; the other components have a fixed width. A key built from the
+/// leading components of another sorts before it, and the key built from all components
+/// is the bound to scan from or to.
+#[derive(Debug, Clone, Default)]
+pub struct RowKeyBuilder {
+    key: RowKey,
+}
+
+/// Escape of a 0 byte inside a string component.
+const ESCAPED_ZERO: [u8; 2] = [0x00, 0xff];
+/// End of a string component, sorting before any byte that can continue it.
+const TERMINATOR: [u8; 2] = [0x00, 0x01];
+
+impl RowKeyBuilder {
+    /// Start an empty row key.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a string component.
+    pub fn add_str(&mut self, value: &str) -> &mut Self {
+        self.add_bytes(value.as_bytes())
+    }
+
+    /// Add a byte string component.
+    pub fn add_bytes(&mut self, value: &[u8]) -> &mut Self {
+        for &byte in value {
+            match byte {
+                0 => self.key.extend_from_slice(&ESCAPED_ZERO),
+                byte => self.key.push(byte),
+            }
+        }
+        self.key.extend_from_slice(&TERMINATOR);
+        self
+    }
+
+    /// Add a u64 component.
+    pub fn add_u64(&mut self, value: u64) -> &mut Self {
+        self.key.extend_from_slice(&value.encode());
+        self
+    }
+
+    /// Add an i64 component.
+    pub fn add_i64(&mut self, value: i64) -> &mut Self {
+        self.key.extend_from_slice(&value.encode());
+        self
+    }
+
+    /// Add a timestamp component (to the millisecond), sorting oldest first.
+    pub fn add_timestamp(&mut self, value: DateTime<Utc>) -> &mut Self {
+        self.key.extend_from_slice(&value.encode());
+        self
+    }
+
+    /// Add a timestamp component (to the millisecond), sorting newest first, so a scan
+    /// from the start of the other components finds the latest keys first.
+    pub fn add_reversed_timestamp(&mut self, value: DateTime<Utc>) -> &mut Self {
+        self.key.extend(value.encode().iter().map(|byte| !byte));
+        self
+    }
+
+    /// The row key built so far.
+    pub fn build(&self) -> RowKey {
+        self.key.clone()
+    }
+}
+
+/// Reads the components of a row key built by `RowKeyBuilder`, in the order they were
+/// added. Each read fails with `InvalidData` if the key does not continue with a
+/// component of that kind.
+#[derive(Debug, Clone)]
+pub struct RowKeyDecoder<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> RowKeyDecoder<'a> {
+    /// Start reading key.
+    pub fn new(key: &'a [u8]) -> Self {
+        Self { rest: key }
+    }
+
+    /// Whether every component has been read.
+    pub fn is_empty(&self) -> bool {
+        self.rest.is_empty()
+    }
+
+    /// Fail with `InvalidData` unless every component has been read.
+    pub fn finish(&self) -> IoResult<()> {
+        if self.rest.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("{} bytes left after the last row key component", self.rest.len()),
+            ))
+        }
+    }
+
+    fn fixed<T: TypedValue>(&mut self) -> IoResult<T> {
+        if self.rest.len() < 8 {
+            return Err(invalid::<T>(self.rest, "expected 8 bytes"));
+        }
+        let (component, rest) = self.rest.split_at(8);
+        let value = T::decode(component)?;
+        self.rest = rest;
+        Ok(value)
+    }
+
+    /// Read a string component.
+    pub fn str(&mut self) -> IoResult<String> {
+        String::decode(&self.bytes()?)
+    }
+
+    /// Read a byte string component.
+    pub fn bytes(&mut self) -> IoResult<Vec<u8>> {
+        let mut value = Vec::new();
+        let mut i = 0;
+        loop {
+            match self.rest.get(i..i + 2) {
+                Some(pair) if pair == TERMINATOR => break,
+                Some(pair) if pair == ESCAPED_ZERO => {
+                    value.push(0);
+                    i += 2;
+                }
+                _ => match self.rest.get(i) {
+                    Some(&byte) if byte != 0 => {
+                        value.push(byte);
+                        i += 1;
+                    }
+                    _ => return Err(invalid::<Vec<u8>>(self.rest, "no terminated string component")),
+                },
+            }
+        }
+        self.rest = &self.rest[i + TERMINATOR.len()..];
+        Ok(value)
+    }
+
+    /// Read a u64 component.
+    pub fn u64(&mut self) -> IoResult<u64> {
+        self.fixed()
+    }
+
+    /// Read an i64 component.
+    pub fn i64(&mut self) -> IoResult<i64> {
+        self.fixed()
+    }
+
+    /// Read a timestamp component.
+    pub fn timestamp(&mut self) -> IoResult<DateTime<Utc>> {
+        self.fixed()
+    }
+
+    /// Read a reversed timestamp component.
+    pub fn reversed_timestamp(&mut self) -> IoResult<DateTime<Utc>> {
+        let inverted: Vec<u8> = self.rest.iter().take(8).map(|byte| !byte).collect();
+        let value = RowKeyDecoder::new(&inverted).timestamp()?;
+        self.rest = &self.rest[8..];
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_ordered<T: TypedValue + PartialOrd + std::fmt::Debug>(values: &[T]) {
+        for pair in values.windows(2) {
+            assert!(pair[0] < pair[1]);
+            assert!(encode(&pair[0]) < encode(&pair[1]), "{:?} < {:?}", pair[0], pair[1]);
+        }
+        for value in values {
+            assert_eq!(decode::<T>(&encode(value)).unwrap(), *value);
+        }
+    }
+
+    #[test]
+    fn test_encodings_preserve_order() {
+        assert_ordered(&[i64::MIN, -10, -1, 0, 1, 9, 10, i64::MAX]);
+        assert_ordered(&[0u64, 9, 10, u64::MAX]);
+        assert_ordered(&[f64::NEG_INFINITY, -10.5, -1.0, -f64::MIN_POSITIVE, 0.0, f64::MIN_POSITIVE, 1.0, 9.0, 10.0, f64::INFINITY]);
+        assert_ordered(&[false, true]);
+        assert_ordered(&["".to_string(), "a".to_string(), "ab".to_string(), "b".to_string()]);
+        let epoch = DateTime::from_timestamp_millis(0).unwrap();
+        let before = DateTime::from_timestamp_millis(-1_000).unwrap();
+        let now = DateTime::from_timestamp_millis(Utc::now().timestamp_millis()).unwrap();
+        assert_ordered(&[before, epoch, now]);
+        assert!(encode(&-0.0f64) < encode(&0.0f64));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        for err in [
+            decode::<i64>(b"short").unwrap_err(),
+            decode::<f64>(&[0; 9]).unwrap_err(),
+            decode::<bool>(&[2]).unwrap_err(),
+            decode::<String>(&[0xff]).unwrap_err(),
+            decode::<DateTime<Utc>>(&[]).unwrap_err(),
+        ] {
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+        }
+        assert_eq!(decode::<DateTime<Utc>>(&encode(&i64::MAX)).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_row_keys_sort_by_component() {
+        let at = |millis| DateTime::from_timestamp_millis(millis).unwrap();
+        let key = |user: &str, seq: u64, time: i64| {
+            RowKeyBuilder::new().add_str(user).add_u64(seq).add_reversed_timestamp(at(time)).build()
+        };
+        let ordered = [
+            key("", 5, 0),
+            key("a", 0, 0),
+            key("a", 1, 20),
+            key("a", 1, 10),
+            key("a", 1, -10),
+            key("a", 2, 0),
+            key("a\0", 0, 0),
+            key("a\x01", 0, 0),
+            key("ab", 0, 0),
+            key("b", 0, 0),
+        ];
+        for pair in ordered.windows(2) {
+            assert!(pair[0] < pair[1]);
+        }
+        // A key of leading components sorts before the keys extending it
+        assert!(RowKeyBuilder::new().add_str("a").add_u64(1).build() < key("a", 1, 20));
+
+        let key = RowKeyBuilder::new()
+            .add_str("a\0b")
+            .add_bytes(&[0, 0xff, 1])
+            .add_i64(-7)
+            .add_timestamp(at(1_000))
+            .add_reversed_timestamp(at(2_000))
+            .build();
+        let mut decoder = RowKeyDecoder::new(&key);
+        assert_eq!(decoder.str().unwrap(), "a\0b");
+        assert_eq!(decoder.bytes().unwrap(), vec![0, 0xff, 1]);
+        assert_eq!(decoder.i64().unwrap(), -7);
+        assert_eq!(decoder.timestamp().unwrap(), at(1_000));
+        assert!(!decoder.is_empty());
+        assert_eq!(decoder.reversed_timestamp().unwrap(), at(2_000));
+        decoder.finish().unwrap();
+
+        // Reading components of the wrong kind fails
+        let key = RowKeyBuilder::new().add_u64(1).build();
+        assert_eq!(RowKeyDecoder::new(&key).str().unwrap_err().kind(), ErrorKind::InvalidData);
+        let mut decoder = RowKeyDecoder::new(b"abc");
+        assert_eq!(decoder.u64().unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(decoder.reversed_timestamp().unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(decoder.finish().unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+}
